@@ -1,12 +1,11 @@
-// A Vertex shaderA
+// Plain vertex colors, the "default" pipeline. The other mesh shaders include this for fs_main
+// and vs_main and add their own entry points on top
+#include "common.wgsl"
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
 }
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec3<f32>,
-};
 
 @vertex
 fn vs_main(
@@ -22,8 +21,3 @@ fn vs_main(
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(in.color, 1.0);
 }
-
-@fragment
-fn fs_main_pos(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(fract((in.clip_position.x + in.clip_position.y)), fract(1000 * (in.clip_position.x+in.clip_position.y)),fract(in.clip_position.z+in.clip_position.x), 1.);
-}
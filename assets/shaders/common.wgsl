@@ -0,0 +1,5 @@
+// What every vertex path of the mesh pipelines hands its fragment stage
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
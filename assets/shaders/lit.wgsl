@@ -0,0 +1,17 @@
+#include "color.wgsl"
+
+// Same output from vertices laid out like the deferred demo's, position, normal, color
+struct LitVertexInput {
+    @location(0) position: vec3<f32>,
+    @location(2) color: vec3<f32>,
+}
+
+@vertex
+fn vs_lit(
+    model: LitVertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = model.color;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    return out;
+}
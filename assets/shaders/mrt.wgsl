@@ -0,0 +1,15 @@
+#include "color.wgsl"
+
+// Multiple render targets, one output per @location
+struct MrtOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) pattern: vec4<f32>,
+};
+
+@fragment
+fn fs_mrt(in: VertexOutput) -> MrtOutput {
+    var out: MrtOutput;
+    out.color = vec4<f32>(in.color, 1.0);
+    out.pattern = vec4<f32>(fract(in.clip_position.xy / 32.0), 0.5, 1.0);
+    return out;
+}
@@ -0,0 +1,7 @@
+#include "color.wgsl"
+
+// Colored by where the fragment is on screen instead of by the vertices
+@fragment
+fn fs_main_pos(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(fract((in.clip_position.x + in.clip_position.y)), fract(1000 * (in.clip_position.x+in.clip_position.y)),fract(in.clip_position.z+in.clip_position.x), 1.);
+}
@@ -0,0 +1,178 @@
+// CPU side hot paths, nothing here touches a GPU. The library only makes State public, so
+// the modules these need are pulled in by path and sit at this crate's root the same way
+// they do in lib.rs (their `crate::` imports line up). Run with `cargo bench`. Their unit
+// tests are left out of a bench build, which leaves the tests modules' imports unused
+#![allow(dead_code, unused_imports)]
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::{Vec2, Vec3};
+
+#[path = "../src/colors.rs"]
+mod colors;
+#[path = "../src/error.rs"]
+mod error;
+#[path = "../src/geometry.rs"]
+mod geometry;
+#[path = "../src/memory.rs"]
+mod memory;
+#[path = "../src/mesh.rs"]
+mod mesh;
+#[path = "../src/mesh_arena.rs"]
+mod mesh_arena;
+#[path = "../src/morph.rs"]
+mod morph;
+#[path = "../src/pacing.rs"]
+mod pacing;
+#[path = "../src/spatial_hash.rs"]
+mod spatial_hash;
+#[path = "../src/transform.rs"]
+mod transform;
+
+use colors::RgbaColor;
+use mesh::MeshData;
+use spatial_hash::SpatialHash;
+
+// Small, typical and silly side counts
+const SIDES: [u32; 4] = [5, 64, 1024, 16384];
+
+fn polygons(c: &mut Criterion) {
+    let mut group = c.benchmark_group("regular_polygon");
+    for sides in SIDES {
+        group.bench_with_input(BenchmarkId::from_parameter(sides), &sides, |b, &sides| {
+            b.iter(|| geometry::regular_polygon_unchecked(black_box(sides), 60.0, 0.0));
+        });
+    }
+    group.finish();
+
+    // What a round shape costs as a morph target: an outline resampled to `count` points
+    // plus the fan over it
+    let outline = geometry::regular_polygon_unchecked(64, 60.0, 0.0);
+    let mut group = c.benchmark_group("circle_fan");
+    for count in SIDES {
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            b.iter(|| {
+                let points = morph::resample_closed(black_box(&outline), count as usize);
+                (points, morph::fan_indices(count as usize))
+            });
+        });
+    }
+    group.finish();
+}
+
+// A flat `cells` x `cells` grid of quads, 2 * cells^2 triangles. Its rim stays put when
+// decimated, everything inside can collapse
+fn grid(cells: u32) -> MeshData<Vec3> {
+    let side = cells + 1;
+    let vertices = (0..side * side)
+        .map(|i| Vec3::new((i % side) as f32, (i / side) as f32, 0.0))
+        .collect();
+    let indices = (0..cells * cells)
+        .flat_map(|quad| {
+            let corner = quad / cells * side + quad % cells;
+            let (a, b, c, d) = (corner, corner + 1, corner + side + 1, corner + side);
+            [a, b, c, a, c, d]
+        })
+        .collect();
+    MeshData::new("grid", vertices, indices)
+}
+
+fn meshes(c: &mut Criterion) {
+    // ~100k triangles
+    let mesh = grid(224);
+    let triangles = mesh.indices.len() / 3;
+
+    c.bench_function(&format!("merge/{triangles}_triangles_in_100_parts"), |b| {
+        let parts: Vec<MeshData<Vec3>> = (0..100).map(|_| grid(22)).collect();
+        b.iter_batched(
+            || parts.clone(),
+            MeshData::merge,
+            criterion::BatchSize::LargeInput,
+        );
+    });
+
+    let mut group = c.benchmark_group("decimate");
+    group.sample_size(10);
+    group.bench_function(format!("{triangles}_triangles_to_half"), |b| {
+        b.iter(|| mesh.decimate("grid", black_box(0.5)).unwrap());
+    });
+    group.finish();
+}
+
+// Boxes scattered over a 2000 x 2000 area, sizes 5 to 45, in a fixed pattern
+fn scattered_boxes(count: usize) -> Vec<(usize, Vec2, Vec2)> {
+    let mut seed = 0x2545_f491_u32;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+    (0..count)
+        .map(|id| {
+            let min = Vec2::new(next(), next()) * 2000.0;
+            (id, min, min + Vec2::splat(5.0 + next() * 40.0))
+        })
+        .collect()
+}
+
+fn spatial_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spatial_hash");
+    for count in [100, 1_000, 10_000] {
+        let boxes = scattered_boxes(count);
+        group.bench_with_input(BenchmarkId::new("rebuild", count), &boxes, |b, boxes| {
+            let mut grid = SpatialHash::new();
+            b.iter(|| grid.rebuild(boxes.iter().copied()));
+        });
+
+        let mut grid = SpatialHash::new();
+        grid.rebuild(boxes.iter().copied());
+        group.bench_with_input(BenchmarkId::new("query_point", count), &grid, |b, grid| {
+            b.iter(|| grid.query_point(black_box(Vec2::new(1000.0, 1000.0))));
+        });
+    }
+    group.finish();
+}
+
+fn colors(c: &mut Criterion) {
+    let hex: Vec<String> = (0..10_000u32)
+        .map(|i| format!("#{:06x}", i.wrapping_mul(2_654_435_761) & 0xff_ffff))
+        .collect();
+    let parsed: Vec<RgbaColor> = hex
+        .iter()
+        .map(|text| RgbaColor::parse_hex(text).unwrap())
+        .collect();
+
+    let mut group = c.benchmark_group("colors_10k");
+    group.bench_function("parse_hex", |b| {
+        b.iter(|| {
+            hex.iter()
+                .map(|text| RgbaColor::parse_hex(text).unwrap())
+                .fold(0.0, |sum, color| sum + color.to_f32_array()[0])
+        });
+    });
+    group.bench_function("to_hex", |b| {
+        b.iter(|| parsed.iter().copied().map(RgbaColor::to_hex).count());
+    });
+    group.bench_function("to_linear", |b| {
+        b.iter(|| {
+            parsed
+                .iter()
+                .map(|color| color.to_f32_array_linear()[0])
+                .sum::<f32>()
+        });
+    });
+    group.bench_function("unorm8_round_trip", |b| {
+        b.iter(|| {
+            parsed
+                .iter()
+                .map(|color| RgbaColor::from_unorm8(color.to_unorm8_array()).to_f32_array()[0])
+                .sum::<f32>()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, polygons, meshes, spatial_hash, colors);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+// Embeds every file in assets/shaders, so a release binary doesn't need the directory next
+// to it. Writes `FILES: &[(name, contents)]` to $OUT_DIR/shader_files.rs for ShaderBank
+use std::fmt::Write;
+use std::path::Path;
+
+fn main() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/shaders");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .expect("assets/shaders is missing")
+        .map(|entry| entry.expect("Can't list assets/shaders").path())
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+
+    let mut out = String::from("pub const FILES: &[(&str, &str)] = &[\n");
+    for path in &files {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let name = path.file_name().unwrap().to_string_lossy();
+        writeln!(
+            out,
+            "    ({name:?}, include_str!({:?})),",
+            path.display().to_string()
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+
+    let generated = Path::new(&std::env::var("OUT_DIR").unwrap()).join("shader_files.rs");
+    std::fs::write(generated, out).expect("Can't write shader_files.rs");
+}
@@ -0,0 +1,98 @@
+// The renderer in a window and loop of the example's own, without run(). State only hears
+// about the window through the builder and the calls below. `cargo run --example embedded`,
+// Space switches between the rings and the deferred view, Escape quits
+use std::time::{Duration, Instant};
+
+use glfw::{fail_on_errors, Action, Key, WindowEvent};
+use wgpu::rwh::{HasDisplayHandle, HasWindowHandle};
+use wgpu_forray::{Options, StateBuilder, View, WindowRequest};
+
+// Rings out from the cursor, with the same globals the playground's shaders get
+const RINGS: &str = r#"
+#include "globals.wgsl"
+#include "fullscreen.wgsl"
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let d = distance(in.clip_position.xy, globals.mouse) / globals.resolution.y;
+    let ring = 0.5 + 0.5 * sin(d * 60.0 - globals.time * 4.0);
+    return vec4<f32>(in.uv * ring, ring, 1.0);
+}
+"#;
+
+// Fixed update step, drawn in between by how far into the next one the frame is
+const STEP: Duration = Duration::from_millis(16);
+
+fn main() {
+    pollster::block_on(run());
+}
+
+async fn run() {
+    let mut glfw = glfw::init(fail_on_errors!()).expect("Failed to get glfw");
+    glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
+    let (mut window, events) = glfw
+        .create_window(800, 600, "Embedded", glfw::WindowMode::Windowed)
+        .expect("Failed to get window and events");
+    window.set_key_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_framebuffer_size_polling(true);
+
+    let display = window.display_handle().expect("No display handle").as_raw();
+    let handle = window.window_handle().expect("No window handle").as_raw();
+    let options = Options::default();
+    // The window outlives the State, it's dropped after it at the end of run
+    let built = unsafe { StateBuilder::new(&options).raw_handles(display, handle) }
+        .size(window.get_framebuffer_size())
+        .preferred_formats(&[
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ])
+        .msaa(4)
+        .fullscreen_pipeline("rings", RINGS)
+        .build()
+        .await;
+    let mut state = match built {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let views = [View::Fullscreen("rings".to_owned()), View::Deferred];
+    let mut current = 0;
+    let mut last_update = Instant::now();
+    while !window.should_close() {
+        glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&events) {
+            match event {
+                WindowEvent::FramebufferSize(width, height) => state.resize((width, height)),
+                WindowEvent::CursorPos(x, y) => state.set_cursor((x, y)),
+                WindowEvent::Key(Key::Space, _, Action::Press, _) => {
+                    current = (current + 1) % views.len();
+                }
+                WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    window.set_should_close(true);
+                }
+                _ => {}
+            }
+        }
+
+        while last_update.elapsed() >= STEP {
+            state.update(STEP);
+            last_update += STEP;
+        }
+        state.receive_assets();
+        let alpha = last_update.elapsed().as_secs_f32() / STEP.as_secs_f32();
+        state.render(&views[current], alpha);
+
+        for request in state.take_window_requests() {
+            match request {
+                WindowRequest::Opacity(opacity) => window.set_opacity(opacity),
+                WindowRequest::Close => window.set_should_close(true),
+                // Nothing here captures the cursor or lets clicks through
+                WindowRequest::CaptureCursor(_) | WindowRequest::ClickThrough(_) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::targets::{TargetDesc, TargetHandle, TargetRegistry};
+
+// Past this many samples the 1/N weight is below what the format can still resolve, so
+// more samples would stop changing anything
+fn max_samples(format: wgpu::TextureFormat) -> u32 {
+    match format {
+        wgpu::TextureFormat::Rgba32Float => 16384,
+        wgpu::TextureFormat::Rgba16Float => 1024,
+        _ => 64,
+    }
+}
+
+// What the current average was rendered with, a different one resets it
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AccumulationKey {
+    pipeline: String,
+    // Resolution in pixels
+    size: (u32, u32),
+    // Mouse position, whole pixels
+    offset: [i32; 2],
+    // TargetRegistry::generation, bumped when targets are rebuilt
+    generation: u64,
+}
+
+// Progressive rendering: every frame is blended into a float target with weight 1/N, which
+// leaves the running average of all samples there. Anything that changes the picture
+// starts it over
+pub struct Accumulator {
+    pub enabled: bool,
+    pub format: wgpu::TextureFormat,
+    target: TargetHandle,
+    samples: u32,
+    key: Option<AccumulationKey>,
+}
+
+impl Accumulator {
+    // `format` is Capabilities::accumulation_format, the most precise one that can be both
+    // blended into and filtered by the blit
+    pub fn new(
+        device: &wgpu::Device,
+        registry: &mut TargetRegistry,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let target = registry.create(
+            device,
+            TargetDesc {
+                label: "Accumulation",
+                format,
+                scale: 1.0,
+                storage: false,
+            },
+        );
+        Self {
+            enabled: false,
+            format,
+            target,
+            samples: 0,
+            key: None,
+        }
+    }
+
+    // Source times the constant plus what's there times one minus it, with the 1/N
+    // constant set by the pass
+    pub fn blend() -> wgpu::BlendState {
+        let component = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::OneMinusConstant,
+            operation: wgpu::BlendOperation::Add,
+        };
+        wgpu::BlendState {
+            color: component,
+            alpha: component,
+        }
+    }
+
+    pub fn target(&self) -> TargetHandle {
+        self.target
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn reset(&mut self) {
+        self.samples = 0;
+        self.key = None;
+    }
+
+    // Starts over when anything the picture depends on differs from the last sample's
+    pub fn track(
+        &mut self,
+        pipeline: &str,
+        resolution: (u32, u32),
+        mouse: (f64, f64),
+        registry: &TargetRegistry,
+    ) {
+        let key = AccumulationKey {
+            pipeline: pipeline.to_owned(),
+            size: resolution,
+            offset: [mouse.0 as i32, mouse.1 as i32],
+            generation: registry.generation(),
+        };
+        if self.key.as_ref() != Some(&key) {
+            self.samples = 0;
+            self.key = Some(key);
+        }
+    }
+
+    // Blend weight for the sample about to be drawn and whether it's the first, None once
+    // the average has converged as far as the format allows
+    pub fn next_sample(&mut self) -> Option<(f64, bool)> {
+        if self.samples >= max_samples(self.format) {
+            return None;
+        }
+        self.samples += 1;
+        Some((1.0 / self.samples as f64, self.samples == 1))
+    }
+}
@@ -0,0 +1,334 @@
+use crate::assets::AssetRequest;
+use crate::frame::Background;
+use crate::inspector::InspectorKey;
+use crate::scene::MeshRef;
+use crate::sprites::{self, TilePolicy};
+use crate::undo::SceneCommand;
+use crate::{memory, screenshot, shapes, tilemap, timeline, State};
+
+// What the bound keys do to State, by the action names in Bindings. The ones that need the
+// window or the loop's own views are handled by WindowLoop before it gets here
+impl State {
+    // False for an action that isn't State's
+    pub fn run_action(&mut self, action: &str) -> bool {
+        self.effect_action(action)
+            || self.edit_action(action)
+            || self.view_action(action)
+            || self.capture_action(action)
+            || self.timeline_action(action)
+    }
+
+    // The post chain: switching effects on and off and stepping their parameters
+    fn effect_action(&mut self, action: &str) -> bool {
+        match action {
+            "dither levels" => self.step_effect_param("dither", "levels"),
+            "dither matrix" => self.step_effect_param("dither", "matrix_size"),
+            "pixel size" => self.step_effect_param("pixelate", "pixel_size"),
+            "vignette" => self.toggle_effect("vignette"),
+            "color grade" => self.toggle_effect("grade"),
+            "bloom" => self.toggle_effect("bloom"),
+            "auto exposure" => self.toggle_effect("exposure"),
+            "dither" => self.toggle_effect("dither"),
+            "pixelate" => self.toggle_effect("pixelate"),
+            "color blind simulation" => self.set_color_blind(self.color_blind.next()),
+            "cycle effect order" => {
+                // First effect to the back
+                let first = self.post.order().first().map(|(name, _)| name.to_string());
+                if let Some(name) = first {
+                    if let Err(e) = self.post.move_effect(&name, usize::MAX) {
+                        log::warn!("{e}");
+                    }
+                }
+                println!("Effect order: {:?}", self.post.order());
+            }
+            // Exposure compensation in half stops, with Shift the adaptation rate
+            "exposure down" => self.step_exposure("compensation", false),
+            "exposure up" => self.step_exposure("compensation", true),
+            "slower adaptation" => self.step_exposure("adaptation_rate", false),
+            "faster adaptation" => self.step_exposure("adaptation_rate", true),
+            _ => return false,
+        }
+        self.request_redraw();
+        true
+    }
+
+    fn toggle_effect(&mut self, name: &str) {
+        match self.post.toggle(name) {
+            Ok(enabled) => println!("{name} {}", if enabled { "on" } else { "off" }),
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    // Doubles it, back to the smallest past the largest
+    fn step_effect_param(&mut self, name: &str, param: &str) {
+        match self.post.param(name, param) {
+            Ok(value) => {
+                *value = match param {
+                    "matrix_size" if *value >= 8.0 => 4.0,
+                    "matrix_size" => 8.0,
+                    "levels" | "pixel_size" if *value >= 16.0 => 2.0,
+                    _ => *value * 2.0,
+                };
+                println!("{name} {param} {}", *value);
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    fn step_exposure(&mut self, param: &str, up: bool) {
+        match self.post.param("exposure", param) {
+            Ok(value) => {
+                *value = match (param, up) {
+                    ("compensation", true) => *value + 0.5,
+                    ("compensation", false) => *value - 0.5,
+                    (_, true) => *value * 1.5,
+                    (_, false) => (*value / 1.5).max(0.05),
+                };
+                println!("Exposure {param} {:.2}", *value);
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    // Scene items, the snapping grid and the inspector that edits them
+    fn edit_action(&mut self, action: &str) -> bool {
+        match action {
+            "undo" => self.undo(),
+            "redo" => self.redo(),
+            "save scene" => {
+                self.save_scene();
+                return true;
+            }
+            "new item" => {
+                let builtins = ["pentagon", "square", "triangle"];
+                let builtin = builtins[self.scene.items.len() % builtins.len()];
+                self.add_scene_item(MeshRef::Builtin(builtin.to_owned()));
+                return true;
+            }
+            "remove item" => {
+                if let Some(index) = self.pick_at_cursor() {
+                    self.edit(SceneCommand::remove(&self.scene, index));
+                }
+                return true;
+            }
+            "restore items" => {
+                // Whatever is still fading out comes back
+                for index in 0..self.scene.items.len() {
+                    if self.scene.items[index].removing {
+                        self.scene.restore(index);
+                    }
+                }
+                return true;
+            }
+            "rulers" => self.snap.rulers = !self.snap.rulers,
+            "snapping" => {
+                self.snap.enabled = !self.snap.enabled;
+                println!(
+                    "Snapping {} (grid {})",
+                    if self.snap.enabled { "on" } else { "off" },
+                    self.snap.spacing
+                );
+            }
+            "finer grid" | "coarser grid" => {
+                if action == "finer grid" {
+                    self.snap.halve();
+                } else {
+                    self.snap.double();
+                }
+                println!("Grid {}", self.snap.spacing);
+            }
+            "inspector" => self.inspector.enabled = !self.inspector.enabled,
+            // Held keys repeat, so long lists can be scrolled through
+            "select up" | "select down" => {
+                let rows = self.inspector_rows();
+                let delta = if action == "select up" { -1 } else { 1 };
+                self.inspector.move_selection(&rows, delta);
+            }
+            "edit row" => self.inspector_enter(),
+            "paint tiles" => {
+                self.tile_brush = match self.tile_brush {
+                    Some(_) => None,
+                    None => Some(0),
+                };
+                match self.tile_brush {
+                    Some(brush) => println!("Painting tile {brush}, Shift+click clears"),
+                    None => println!("Clicks in the tile view don't paint"),
+                }
+                return true;
+            }
+            _ => return false,
+        }
+        self.request_redraw();
+        true
+    }
+
+    // Which view is up and what's drawn over it
+    fn view_action(&mut self, action: &str) -> bool {
+        match action {
+            "deferred view" => self.deferred.active = !self.deferred.active,
+            "MRT view" => self.mrt.cycle_view(),
+            "primitives view" => self.show_primitives = !self.show_primitives,
+            "inset view" => self.inset.enabled = !self.inset.enabled,
+            "console" => self.console.enabled = !self.console.enabled,
+            "stats overlay" => self.overlay.enabled = !self.overlay.enabled,
+            "gizmos" => {
+                self.gizmos.enabled = !self.gizmos.enabled;
+                return true;
+            }
+            #[cfg(feature = "text")]
+            "name tags" => self.name_tags = !self.name_tags,
+            "background override" => {
+                // Per view -> preserve -> don't care -> per view
+                self.background_override = match self.background_override {
+                    None => Some(Background::Preserve),
+                    Some(Background::Preserve) => Some(Background::DontCare),
+                    Some(_) => None,
+                };
+                println!("Background override: {:?}", self.background_override);
+            }
+            "morph" => {
+                // Towards the other shape, from wherever it is now
+                let target = if self.morph_tween.to == 0.0 { 1.0 } else { 0.0 };
+                self.morph_tween.retarget(target);
+                println!("Morphing to {}", self.morph.targets()[target as usize].name);
+            }
+            "blend mode" => {
+                let modes = shapes::BLEND_MODES;
+                let current = modes
+                    .iter()
+                    .position(|&mode| mode == self.shapes.blend)
+                    .unwrap_or(0);
+                self.shapes.blend = modes[(current + 1) % modes.len()];
+                println!("Shapes blend: {:?}", self.shapes.blend);
+            }
+            // Pans between the dark and the bright side
+            "pan left" | "pan right" => {
+                let step = 60.0 / self.camera2d.zoom;
+                self.camera2d.center.x += if action == "pan left" { -step } else { step };
+            }
+            "accumulate frames" => {
+                self.accumulator.enabled = !self.accumulator.enabled;
+                self.accumulator.reset();
+                println!(
+                    "Accumulation {} ({:?})",
+                    if self.accumulator.enabled {
+                        "on"
+                    } else {
+                        "off"
+                    },
+                    self.accumulator.format
+                );
+                return true;
+            }
+            _ => return false,
+        }
+        self.request_redraw();
+        true
+    }
+
+    // Screenshots, frame dumps and exports, nothing changes on screen
+    fn capture_action(&mut self, action: &str) -> bool {
+        match action {
+            "dump frame" => self.dump_frame(screenshot::timestamped("frame", "json")),
+            "supersampled screenshot" => self.take_supersampled(screenshot::default_path()),
+            "screenshot" => self.take_screenshot(screenshot::default_path()),
+            "list GPU allocations" => {
+                // Whatever is still listed after a scene switch is a leak candidate
+                for (label, category, bytes) in self.memory.live_allocations() {
+                    println!(
+                        "{:<8} {:>10}  {label}",
+                        category.name(),
+                        memory::format_bytes(bytes)
+                    );
+                }
+            }
+            #[cfg(feature = "obj")]
+            "export OBJ" => {
+                let path = std::path::Path::new("export.obj");
+                match self.deferred.export_obj(path) {
+                    Ok(triangles) => {
+                        println!("Exported {triangles} triangles to {}", path.display());
+                    }
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn timeline_action(&mut self, action: &str) -> bool {
+        match action {
+            "loop timeline" => {
+                if let Some(timeline) = &mut self.timeline {
+                    timeline.looping = !timeline.looping;
+                    self.request_redraw();
+                }
+            }
+            "play timeline" => {
+                let loaded = self.timeline.is_some() || self.load_timeline("timeline.ron".as_ref());
+                if loaded {
+                    if let Some(timeline) = &mut self.timeline {
+                        timeline.toggle_playing();
+                    }
+                    self.apply_timeline();
+                    self.request_redraw();
+                }
+            }
+            "seek back" | "seek forward" => {
+                let seconds = if action == "seek back" {
+                    -timeline::SEEK_STEP
+                } else {
+                    timeline::SEEK_STEP
+                };
+                if let Some(timeline) = &mut self.timeline {
+                    timeline.seek(seconds);
+                }
+                self.apply_timeline();
+                self.request_redraw();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // A file dropped on the window. Loaded in the background like everything else, a big
+    // file doesn't freeze the window. PNGs are baked into the mesh selected in the
+    // inspector, without one they're taken as LUTs. Anything else is an outline
+    pub fn drop_files(&mut self, paths: Vec<std::path::PathBuf>) {
+        let selected_mesh = match self.inspector.selected() {
+            Some(InspectorKey::Mesh(name)) if self.inspector.enabled => Some(name.clone()),
+            _ => None,
+        };
+        for path in paths {
+            if path.extension().is_some_and(|ext| ext == "png") {
+                match &selected_mesh {
+                    Some(mesh) => {
+                        let request = self.assets.request(AssetRequest::Image(path));
+                        self.bake_request = Some((request, mesh.clone()));
+                    }
+                    None => {
+                        self.lut_request = Some(self.assets.request(AssetRequest::Lut(path)));
+                    }
+                }
+            } else {
+                self.add_scene_item(MeshRef::Asset(path));
+            }
+        }
+        self.request_redraw();
+    }
+
+    // The sprite stress view wants its sprites, built with the defaults the first time
+    pub fn ensure_sprite_stress(&mut self) {
+        if self.sprite_stress.is_none() {
+            self.build_sprite_stress(sprites::STRESS_SPRITES, TilePolicy::Letterbox);
+        }
+    }
+
+    // Same for the tile map view
+    pub fn ensure_tile_map(&mut self) {
+        if self.tile_map.is_none() {
+            self.build_tile_stress(tilemap::STRESS_SIDE);
+        }
+    }
+}
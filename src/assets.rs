@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use glam::Vec2;
+
+use crate::error::ForayError;
+use crate::image_file;
+use crate::lut::LutData;
+use crate::scene::{self, MeshRef};
+
+// Threads reading and decoding files
+const WORKERS: usize = 4;
+// Decoded assets handed out by poll() per frame at most, so a burst of finished loads gets
+// uploaded over a few frames instead of hitching one
+pub const UPLOADS_PER_FRAME: usize = 2;
+
+// What to load, and how
+#[derive(Clone, Debug)]
+pub enum AssetRequest {
+    Outline(MeshRef),
+    Lut(PathBuf),
+    // Any picture, decoded to RGBA
+    Image(PathBuf),
+}
+
+impl AssetRequest {
+    fn label(&self) -> String {
+        match self {
+            AssetRequest::Outline(MeshRef::Builtin(name)) => format!("builtin {name}"),
+            AssetRequest::Outline(MeshRef::Asset(path))
+            | AssetRequest::Lut(path)
+            | AssetRequest::Image(path) => path.display().to_string(),
+        }
+    }
+
+    // On a worker thread, nothing in here may touch the GPU
+    fn decode(self) -> Result<Asset, ForayError> {
+        match self {
+            AssetRequest::Outline(mesh) => scene::load_outline(&mesh).map(Asset::Outline),
+            AssetRequest::Lut(path) => LutData::load(&path).map(Asset::Lut),
+            AssetRequest::Image(path) => image_file::open(&path).map(Asset::Image),
+        }
+    }
+}
+
+// Decoded on the CPU, whoever asked for it puts it on the GPU
+pub enum Asset {
+    Outline(Vec<Vec2>),
+    Lut(LutData),
+    Image(image::RgbaImage),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AssetHandle(usize);
+
+enum Slot {
+    // Queued or being decoded
+    Pending,
+    // Decoded, waiting for its turn in poll()
+    Decoded,
+    // Handed out by poll()
+    Done,
+    Failed(ForayError),
+    // Nobody wants it anymore, whatever the worker comes back with is dropped
+    Cancelled,
+}
+
+// Loads files on a pool of background threads. request() returns a handle straight away,
+// poll() on the main thread hands back what finished, a few per frame. Errors (including a
+// decoder panicking) end up on the handle, nothing stays pending forever
+pub struct Assets {
+    slots: Vec<(String, Slot)>,
+    jobs: Option<mpsc::Sender<(usize, AssetRequest)>>,
+    results: mpsc::Receiver<(usize, Result<Asset, ForayError>)>,
+    // Decoded but not handed out yet, oldest first
+    decoded: VecDeque<(usize, Asset)>,
+    workers: Vec<JoinHandle<()>>,
+    // Set by shutdown, workers drop what's still queued instead of decoding it
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        let (jobs, queue) = mpsc::channel::<(usize, AssetRequest)>();
+        let (done, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut workers = Vec::with_capacity(WORKERS);
+        for worker in 0..WORKERS {
+            let queue = Arc::clone(&queue);
+            let done = done.clone();
+            let cancelled = Arc::clone(&cancelled);
+            let spawned = std::thread::Builder::new()
+                .name(format!("asset loader {worker}"))
+                .spawn(move || loop {
+                    // Holding the lock only while waiting for the next job
+                    let job = queue.lock().map(|queue| queue.recv());
+                    let Ok(Ok((index, request))) = job else {
+                        return;
+                    };
+                    if cancelled.load(Ordering::Acquire) {
+                        return;
+                    }
+                    let label = request.label();
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| request.decode()))
+                        .unwrap_or_else(|_| {
+                            Err(ForayError::AssetLoad {
+                                asset: label,
+                                reason: "the decoder panicked".to_owned(),
+                            })
+                        });
+                    // Assets may be gone by now, nothing to do then
+                    if done.send((index, result)).is_err() {
+                        return;
+                    }
+                });
+            match spawned {
+                Ok(handle) => workers.push(handle),
+                Err(e) => log::error!("Couldn't start asset loader {worker}: {e}"),
+            }
+        }
+
+        Self {
+            slots: Vec::new(),
+            jobs: Some(jobs),
+            results,
+            decoded: VecDeque::new(),
+            workers,
+            cancelled,
+        }
+    }
+
+    // Stops taking requests, drops the queued ones and joins the workers, giving the ones
+    // still decoding until `timeout`. Returns how many didn't make it, they're left to finish
+    // on their own (their results go nowhere)
+    pub fn shutdown(&mut self, timeout: Duration) -> usize {
+        self.cancelled.store(true, Ordering::Release);
+        // Workers waiting on an empty queue see it close
+        self.jobs = None;
+        let deadline = Instant::now() + timeout;
+        while self.workers.iter().any(|worker| !worker.is_finished()) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let (finished, running): (Vec<_>, Vec<_>) =
+            self.workers.drain(..).partition(JoinHandle::is_finished);
+        for worker in finished {
+            // A panic was already turned into an asset error, this can't fail
+            let _ = worker.join();
+        }
+        running.len()
+    }
+
+    pub fn request(&mut self, request: AssetRequest) -> AssetHandle {
+        let index = self.slots.len();
+        let label = request.label();
+        let sent = self
+            .jobs
+            .as_ref()
+            .is_some_and(|jobs| jobs.send((index, request)).is_ok());
+        let slot = if sent {
+            Slot::Pending
+        } else {
+            Slot::Failed(ForayError::AssetLoad {
+                asset: label.clone(),
+                reason: "no asset loader threads running".to_owned(),
+            })
+        };
+        self.slots.push((label, slot));
+        AssetHandle(index)
+    }
+
+    // Once per frame. Collects what the workers finished and returns up to
+    // UPLOADS_PER_FRAME decoded assets, failures are logged and kept on their handle
+    pub fn poll(&mut self) -> Vec<(AssetHandle, Asset)> {
+        loop {
+            match self.results.try_recv() {
+                Ok((index, _)) if matches!(self.slots[index].1, Slot::Cancelled) => {}
+                Ok((index, Ok(asset))) => {
+                    self.slots[index].1 = Slot::Decoded;
+                    self.decoded.push_back((index, asset));
+                }
+                Ok((index, Err(e))) => {
+                    log::warn!("{e}");
+                    self.slots[index].1 = Slot::Failed(e);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    // Every worker is gone, whatever they had will never arrive
+                    self.jobs = None;
+                    for (label, slot) in &mut self.slots {
+                        if matches!(slot, Slot::Pending) {
+                            *slot = Slot::Failed(ForayError::AssetLoad {
+                                asset: label.clone(),
+                                reason: "the asset loader threads stopped".to_owned(),
+                            });
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+
+        let slots = &self.slots;
+        self.decoded
+            .retain(|&(index, _)| !matches!(slots[index].1, Slot::Cancelled));
+        let count = self.decoded.len().min(UPLOADS_PER_FRAME);
+        self.decoded
+            .drain(..count)
+            .map(|(index, asset)| {
+                self.slots[index].1 = Slot::Done;
+                (AssetHandle(index), asset)
+            })
+            .collect()
+    }
+
+    // poll() won't hand it out, for a scene switched away from mid-preload. A worker already
+    // decoding it finishes, the result goes nowhere
+    pub fn cancel(&mut self, handle: AssetHandle) {
+        let slot = &mut self.slots[handle.0].1;
+        if matches!(slot, Slot::Pending | Slot::Decoded) {
+            *slot = Slot::Cancelled;
+        }
+    }
+
+    // Neither handed out by poll() nor failed yet
+    pub fn pending(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|(_, slot)| matches!(slot, Slot::Pending | Slot::Decoded))
+            .count()
+    }
+
+    pub fn requested(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Every request so far and how it went, for the crash report
+    pub fn list(&self) -> Vec<String> {
+        self.slots
+            .iter()
+            .map(|(label, slot)| match slot {
+                Slot::Pending | Slot::Decoded => format!("{label}: loading"),
+                Slot::Done => format!("{label}: loaded"),
+                Slot::Failed(e) => format!("{label}: failed, {e}"),
+                Slot::Cancelled => format!("{label}: cancelled"),
+            })
+            .collect()
+    }
+
+    pub fn error(&self, handle: AssetHandle) -> Option<&ForayError> {
+        match &self.slots[handle.0].1 {
+            Slot::Failed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
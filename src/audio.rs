@@ -0,0 +1,292 @@
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+
+use crate::error::ForayError;
+
+// Frequency bands in Globals::audio, low to high. Must match globals.wgsl
+pub const BANDS: usize = 8;
+// Samples per analysis, a power of two for the FFT. About 21 ms at 48 kHz
+const WINDOW: usize = 1024;
+// What the callback keeps for the worker, a few windows so a late worker still finds one
+const RING: usize = WINDOW * 4;
+// How often the worker looks at the latest window
+const UPDATE: Duration = Duration::from_millis(16);
+// No samples for this long is silence, a device that went away without saying so
+const STALE: Duration = Duration::from_millis(500);
+// The lowest band starts here, the highest ends here or at the Nyquist frequency
+const LOWEST_HZ: f32 = 40.0;
+const HIGHEST_HZ: f32 = 16_000.0;
+// Band power in dB goes 0 to 1 between these
+const FLOOR_DB: f32 = -70.0;
+const CEILING_DB: f32 = -10.0;
+// How fast a band falls back per second. Rises are immediate, so beats land
+const RELEASE: f32 = 3.0;
+
+// Written by the data callback, read by the worker
+struct Ring {
+    samples: Box<[f32]>,
+    // Ever written, the next one goes at written % RING
+    written: u64,
+}
+
+struct Shared {
+    ring: Mutex<Ring>,
+    // Bits of each band's f32 level, written by the worker and read every frame
+    bands: [AtomicU32; BANDS],
+    // Cleared by the error callback when the device goes away
+    connected: AtomicBool,
+    stop: AtomicBool,
+}
+
+// The default input device, turned into band levels on a worker thread. The data
+// callback only copies samples into a ring allocated up front, and drops a buffer rather
+// than wait while the worker holds it. A device that disconnects reads as silence
+pub struct AudioInput {
+    // Capture stops when it's dropped
+    _stream: cpal::Stream,
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl AudioInput {
+    pub fn start() -> Result<Self, ForayError> {
+        let error = |reason: String| ForayError::Audio { reason };
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| error("no default input device".to_owned()))?;
+        let config = device
+            .default_input_config()
+            .map_err(|e| error(e.to_string()))?;
+        let shared = Arc::new(Shared {
+            ring: Mutex::new(Ring {
+                samples: vec![0.0; RING].into_boxed_slice(),
+                written: 0,
+            }),
+            bands: std::array::from_fn(|_| AtomicU32::new(0)),
+            connected: AtomicBool::new(true),
+            stop: AtomicBool::new(false),
+        });
+
+        let channels = usize::from(config.channels());
+        let stream_config = config.config();
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => input::<f32>(&device, &stream_config, channels, &shared),
+            cpal::SampleFormat::I16 => input::<i16>(&device, &stream_config, channels, &shared),
+            cpal::SampleFormat::U16 => input::<u16>(&device, &stream_config, channels, &shared),
+            other => return Err(error(format!("unsupported sample format {other}"))),
+        }
+        .map_err(|e| error(e.to_string()))?;
+        stream.play().map_err(|e| error(e.to_string()))?;
+
+        let sample_rate = config.sample_rate().0 as f32;
+        let worker = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name("Audio Analysis".to_owned())
+                .spawn(move || analyse(&shared, sample_rate))
+                .map_err(|e| error(e.to_string()))?
+        };
+        println!(
+            "Capturing audio from {} at {} Hz",
+            device
+                .name()
+                .unwrap_or_else(|_| "the default input".to_owned()),
+            config.sample_rate().0
+        );
+        Ok(Self {
+            _stream: stream,
+            shared,
+            worker: Some(worker),
+        })
+    }
+
+    // Levels 0 to 1, packed four to a vec4 like Globals::audio
+    pub fn bands(&self) -> [[f32; 4]; BANDS / 4] {
+        let mut packed = [[0.0; 4]; BANDS / 4];
+        for (index, band) in self.shared.bands.iter().enumerate() {
+            packed[index / 4][index % 4] = f32::from_bits(band.load(Ordering::Relaxed));
+        }
+        packed
+    }
+}
+
+impl Drop for AudioInput {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+// A stream of `T` samples, mixed down to mono into the ring
+fn input<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    shared: &Arc<Shared>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let data = Arc::clone(shared);
+    let errors = Arc::clone(shared);
+    device.build_input_stream(
+        config,
+        move |samples: &[T], _: &cpal::InputCallbackInfo| {
+            let Ok(mut ring) = data.ring.try_lock() else {
+                return;
+            };
+            for frame in samples.chunks_exact(channels) {
+                let mono = frame
+                    .iter()
+                    .map(|&sample| sample.to_sample::<f32>())
+                    .sum::<f32>()
+                    / channels as f32;
+                let at = (ring.written % RING as u64) as usize;
+                ring.samples[at] = mono;
+                ring.written += 1;
+            }
+        },
+        move |e| {
+            log::warn!("Audio capture: {e}");
+            if matches!(e, cpal::StreamError::DeviceNotAvailable) {
+                errors.connected.store(false, Ordering::Relaxed);
+            }
+        },
+        None,
+    )
+}
+
+// The worker. Every UPDATE the latest window goes through a Hann window and an FFT, and its
+// power is averaged into log-spaced bands
+fn analyse(shared: &Shared, sample_rate: f32) {
+    let edges = band_edges(sample_rate);
+    let hann: Vec<f32> = (0..WINDOW)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f32 / WINDOW as f32).cos())
+        .collect();
+    let mut re = vec![0.0; WINDOW];
+    let mut im = vec![0.0; WINDOW];
+    let mut levels = [0.0f32; BANDS];
+    let mut analysed = 0;
+    let mut last = Instant::now();
+    let mut last_samples = last;
+    let mut silent = false;
+    while !shared.stop.load(Ordering::Relaxed) {
+        std::thread::sleep(UPDATE);
+        let now = Instant::now();
+        let dt = (now - last).as_secs_f32();
+        last = now;
+
+        let fresh = {
+            let ring = shared.ring.lock().unwrap_or_else(PoisonError::into_inner);
+            let fresh = ring.written != analysed && ring.written >= WINDOW as u64;
+            if fresh {
+                let start = ring.written - WINDOW as u64;
+                for (i, value) in (0..).zip(re.iter_mut()) {
+                    *value = ring.samples[((start + i) % RING as u64) as usize];
+                }
+                analysed = ring.written;
+            }
+            fresh
+        };
+        if fresh {
+            last_samples = now;
+        }
+        let now_silent = !shared.connected.load(Ordering::Relaxed) || now - last_samples > STALE;
+        if now_silent && !silent {
+            log::warn!("No audio coming in, the bands fall silent");
+        }
+        silent = now_silent;
+
+        // Held where they are between windows, rising to whatever's louder
+        let mut targets = levels;
+        if silent {
+            targets = [0.0; BANDS];
+        } else if fresh {
+            for (value, weight) in re.iter_mut().zip(&hann) {
+                *value *= weight;
+            }
+            im.fill(0.0);
+            fft(&mut re, &mut im);
+            for (target, edge) in targets.iter_mut().zip(edges.windows(2)) {
+                let bins = edge[0]..edge[1];
+                let count = bins.len().max(1) as f32;
+                let power = bins
+                    .map(|bin| re[bin] * re[bin] + im[bin] * im[bin])
+                    .sum::<f32>()
+                    / count;
+                // A full scale sine comes out near 0 dB, Hann halves the amplitude
+                let amplitude = 4.0 / WINDOW as f32;
+                let db = 10.0 * (power * amplitude * amplitude).max(1e-12).log10();
+                *target = ((db - FLOOR_DB) / (CEILING_DB - FLOOR_DB)).clamp(0.0, 1.0);
+            }
+        }
+        for ((level, target), band) in levels.iter_mut().zip(targets).zip(&shared.bands) {
+            *level = if target > *level {
+                target
+            } else {
+                (*level - RELEASE * dt).max(target)
+            };
+            band.store(level.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+// FFT bins where each band starts, then where the last one ends. Log spaced, at least a
+// bin each, the lowest bands are narrower than a bin
+fn band_edges(sample_rate: f32) -> [usize; BANDS + 1] {
+    let highest = HIGHEST_HZ.min(sample_rate / 2.0);
+    let bin_hz = sample_rate / WINDOW as f32;
+    let mut edges = [0; BANDS + 1];
+    let mut previous = 0;
+    for (i, edge) in edges.iter_mut().enumerate() {
+        let hz = LOWEST_HZ * (highest / LOWEST_HZ).powf(i as f32 / BANDS as f32);
+        *edge = ((hz / bin_hz).round() as usize)
+            .max(previous + 1)
+            .min(WINDOW / 2);
+        previous = *edge;
+    }
+    edges
+}
+
+// In place radix-2, `re` and `im` a power of two long
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -TAU / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let (b_re, b_im) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                re[b] = re[a] - b_re;
+                im[b] = im[a] - b_im;
+                re[a] += b_re;
+                im[a] += b_im;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,247 @@
+use std::path::Path;
+
+use crate::cursor::CursorKind;
+use crate::error::ForayError;
+use crate::image_file;
+use crate::options::{MonitorChoice, Options};
+
+#[cfg(feature = "textures")]
+const DEFAULT_ICON: &[u8] = include_bytes!("icon.png");
+// What taskbars and title bars pick from
+const ICON_SIZES: [u32; 3] = [16, 32, 48];
+
+#[derive(Clone, Debug)]
+pub struct MonitorInfo {
+    pub name: String,
+    // Of the current video mode
+    pub resolution: (u32, u32),
+    pub refresh_rate: u32,
+    // Top-left corner on the virtual desktop
+    pub position: (i32, i32),
+    // (x, y, width, height) without taskbars and docks
+    pub work_area: (i32, i32, i32, i32),
+}
+
+impl std::fmt::Display for MonitorInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}x{} @ {} Hz, at {}, {})",
+            self.name,
+            self.resolution.0,
+            self.resolution.1,
+            self.refresh_rate,
+            self.position.0,
+            self.position.1
+        )
+    }
+}
+
+// What the rest of the app needs from the windowing library. Only glfw implements it for
+// now, a winit path would implement the same
+pub trait WindowBackend {
+    // None puts back the icon embedded in the binary
+    fn set_window_icon(&mut self, path: Option<&Path>) -> Result<(), ForayError>;
+    // Primary first
+    fn monitors(&mut self) -> Vec<MonitorInfo>;
+    fn set_position(&mut self, position: (i32, i32));
+    fn position(&self) -> (i32, i32);
+    fn size(&self) -> (i32, i32);
+    // None is the system's default arrow
+    fn set_cursor(&mut self, cursor: Option<&CursorKind>);
+
+    // Text on the system clipboard, None when it's empty or not text
+    fn clipboard_string(&mut self) -> Option<String> {
+        log::warn!("This window backend has no clipboard support");
+        None
+    }
+
+    fn set_clipboard_string(&mut self, _text: &str) {
+        log::warn!("This window backend has no clipboard support");
+    }
+
+    // The whole window, decorations included, 0 invisible to 1 opaque
+    fn set_opacity(&mut self, _opacity: f32) {
+        log::warn!("This window backend can't change the window's opacity");
+    }
+
+    // Mouse input goes through the window to whatever is behind it
+    fn set_click_through(&mut self, _enabled: bool) {
+        log::warn!("This window backend can't pass clicks through");
+    }
+
+    // Hidden and held in the window, with motion still reported, for mouse-look
+    fn set_cursor_captured(&mut self, _captured: bool) {
+        log::warn!("This window backend can't capture the cursor");
+    }
+
+    // The one the window's center is on
+    fn current_monitor(&mut self) -> Option<MonitorInfo> {
+        let (x, y) = self.position();
+        let (width, height) = self.size();
+        let center = (x + width / 2, y + height / 2);
+        self.monitors().into_iter().find(|monitor| {
+            let (left, top) = monitor.position;
+            let (w, h) = (monitor.resolution.0 as i32, monitor.resolution.1 as i32);
+            (left..left + w).contains(&center.0) && (top..top + h).contains(&center.1)
+        })
+    }
+}
+
+// Moves the window where the options ask for and returns the monitor it ended up on.
+// A monitor that isn't connected falls back to the primary one
+pub fn place_window(window: &mut impl WindowBackend, options: &Options) -> Option<MonitorInfo> {
+    let monitors = window.monitors();
+    let chosen = match &options.monitor {
+        None => None,
+        Some(MonitorChoice::Index(index)) => monitors.get(*index),
+        Some(MonitorChoice::Name(name)) => {
+            let name = name.to_lowercase();
+            monitors
+                .iter()
+                .find(|monitor| monitor.name.to_lowercase().contains(&name))
+        }
+    };
+    if options.monitor.is_some() && chosen.is_none() {
+        log::warn!(
+            "No monitor matches {:?}, opening on the primary one",
+            options.monitor
+        );
+    }
+    let monitor = chosen.or(monitors.first())?.clone();
+
+    let (x, y, width, height) = monitor.work_area;
+    if options.center || (options.monitor.is_some() && options.window_pos.is_none()) {
+        let size = window.size();
+        window.set_position((x + (width - size.0) / 2, y + (height - size.1) / 2));
+    } else if let Some((dx, dy)) = options.window_pos {
+        window.set_position((monitor.position.0 + dx, monitor.position.1 + dy));
+    }
+    Some(monitor)
+}
+
+// The icon at every size in ICON_SIZES
+fn icon_images(path: Option<&Path>) -> Result<Vec<image::RgbaImage>, ForayError> {
+    let source = match path {
+        Some(path) => image_file::open(path)?,
+        #[cfg(feature = "textures")]
+        None => image::load_from_memory(DEFAULT_ICON)
+            .expect("The embedded icon is a valid PNG")
+            .to_rgba8(),
+        // Nothing to unpack the embedded PNG with, the window keeps the system's icon
+        #[cfg(not(feature = "textures"))]
+        None => return Ok(Vec::new()),
+    };
+    Ok(ICON_SIZES
+        .iter()
+        .map(|&size| {
+            image::imageops::resize(&source, size, size, image::imageops::FilterType::Lanczos3)
+        })
+        .collect())
+}
+
+impl WindowBackend for glfw::Window {
+    fn set_window_icon(&mut self, path: Option<&Path>) -> Result<(), ForayError> {
+        // glfwSetWindowIcon does nothing there, the bundle's icon is what gets shown
+        if cfg!(target_os = "macos") {
+            println!("Window icons aren't supported on macOS, keeping the default one");
+            return Ok(());
+        }
+        let images = icon_images(path)?
+            .into_iter()
+            .map(|image| glfw::PixelImage {
+                width: image.width(),
+                height: image.height(),
+                // glfw reads the u32s back as bytes, R first
+                pixels: image
+                    .pixels()
+                    .map(|pixel| u32::from_ne_bytes(pixel.0))
+                    .collect(),
+            })
+            .collect();
+        self.set_icon_from_pixels(images);
+        Ok(())
+    }
+
+    fn monitors(&mut self) -> Vec<MonitorInfo> {
+        self.glfw.with_connected_monitors(|_, monitors| {
+            monitors
+                .iter()
+                .map(|monitor| {
+                    let mode = monitor.get_video_mode();
+                    MonitorInfo {
+                        name: monitor.get_name().unwrap_or_else(|| "Unnamed".to_owned()),
+                        resolution: mode.map_or((0, 0), |mode| (mode.width, mode.height)),
+                        refresh_rate: mode.map_or(0, |mode| mode.refresh_rate),
+                        position: monitor.get_pos(),
+                        work_area: monitor.get_workarea(),
+                    }
+                })
+                .collect()
+        })
+    }
+
+    fn set_position(&mut self, (x, y): (i32, i32)) {
+        self.set_pos(x, y);
+    }
+
+    fn position(&self) -> (i32, i32) {
+        self.get_pos()
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.get_size()
+    }
+
+    fn set_cursor(&mut self, cursor: Option<&CursorKind>) {
+        let standard = |shape| Some(glfw::Cursor::standard(shape));
+        let cursor = match cursor {
+            None => None,
+            Some(CursorKind::Arrow) => standard(glfw::StandardCursor::Arrow),
+            Some(CursorKind::IBeam) => standard(glfw::StandardCursor::IBeam),
+            Some(CursorKind::Crosshair) => standard(glfw::StandardCursor::Crosshair),
+            Some(CursorKind::Hand) => standard(glfw::StandardCursor::Hand),
+            Some(CursorKind::HResize) => standard(glfw::StandardCursor::HResize),
+            Some(CursorKind::VResize) => standard(glfw::StandardCursor::VResize),
+            Some(CursorKind::Custom { image, hotspot }) => {
+                let pixels = glfw::PixelImage {
+                    width: image.width(),
+                    height: image.height(),
+                    pixels: image
+                        .pixels()
+                        .map(|pixel| u32::from_ne_bytes(pixel.0))
+                        .collect(),
+                };
+                Some(glfw::Cursor::create_from_pixels(
+                    pixels, hotspot.0, hotspot.1,
+                ))
+            }
+        };
+        // The window owns the new one, the previous one is destroyed as it's dropped here
+        drop(glfw::Window::set_cursor(self, cursor));
+    }
+
+    fn clipboard_string(&mut self) -> Option<String> {
+        self.get_clipboard_string()
+    }
+
+    fn set_clipboard_string(&mut self, text: &str) {
+        glfw::Window::set_clipboard_string(self, text);
+    }
+
+    fn set_opacity(&mut self, opacity: f32) {
+        glfw::Window::set_opacity(self, opacity);
+    }
+
+    fn set_click_through(&mut self, enabled: bool) {
+        self.set_mouse_passthrough(enabled);
+    }
+
+    fn set_cursor_captured(&mut self, captured: bool) {
+        self.set_cursor_mode(if captured {
+            glfw::CursorMode::Disabled
+        } else {
+            glfw::CursorMode::Normal
+        });
+    }
+}
@@ -0,0 +1,432 @@
+use std::sync::{Arc, Weak};
+
+use crate::targets::{TargetHandle, TargetRegistry};
+
+// Stale groups rebuilt per validate at most, the rest wait for the next frame so a resize
+// with a lot of groups hanging off it doesn't land on one frame
+const MAX_REBUILDS: usize = 32;
+
+// What a binding points at. Targets are looked up again on every rebuild, the rest is kept
+// as it was given
+pub enum BindResource {
+    Target(TargetHandle),
+    View(wgpu::TextureView),
+    Sampler(wgpu::Sampler),
+    Buffer(wgpu::Buffer),
+}
+
+// A bind group that keeps its recipe, so TargetRegistry::validate_bind_groups can build it
+// again when a target it points at is recreated
+pub struct BindGroupBuilder {
+    label: String,
+    layout: wgpu::BindGroupLayout,
+    entries: Vec<(u32, BindResource)>,
+}
+
+impl BindGroupBuilder {
+    pub fn new(label: &str, layout: &wgpu::BindGroupLayout) -> Self {
+        Self {
+            label: label.to_owned(),
+            layout: layout.clone(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn target(mut self, binding: u32, target: TargetHandle) -> Self {
+        self.entries.push((binding, BindResource::Target(target)));
+        self
+    }
+
+    pub fn view(mut self, binding: u32, view: &wgpu::TextureView) -> Self {
+        self.entries
+            .push((binding, BindResource::View(view.clone())));
+        self
+    }
+
+    pub fn sampler(mut self, binding: u32, sampler: &wgpu::Sampler) -> Self {
+        self.entries
+            .push((binding, BindResource::Sampler(sampler.clone())));
+        self
+    }
+
+    pub fn buffer(mut self, binding: u32, buffer: &wgpu::Buffer) -> Self {
+        self.entries
+            .push((binding, BindResource::Buffer(buffer.clone())));
+        self
+    }
+
+    fn create(&self, device: &wgpu::Device, registry: &TargetRegistry) -> wgpu::BindGroup {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(binding, resource)| wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: match resource {
+                    BindResource::Target(target) => {
+                        wgpu::BindingResource::TextureView(registry.view(*target))
+                    }
+                    BindResource::View(view) => wgpu::BindingResource::TextureView(view),
+                    BindResource::Sampler(sampler) => wgpu::BindingResource::Sampler(sampler),
+                    BindResource::Buffer(buffer) => buffer.as_entire_binding(),
+                },
+            })
+            .collect();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&self.label),
+            layout: &self.layout,
+            entries: &entries,
+        })
+    }
+
+    // The generation of every target it points at, as of now
+    fn generations(&self, registry: &TargetRegistry) -> Vec<(TargetHandle, u64)> {
+        self.entries
+            .iter()
+            .filter_map(|(_, resource)| match resource {
+                BindResource::Target(target) => {
+                    Some((*target, registry.target_generation(*target)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// Whoever built the group holds this, dropping it lets the group go at the next validate
+pub struct BindGroupHandle {
+    index: usize,
+    _alive: Arc<()>,
+}
+
+struct Entry {
+    builder: BindGroupBuilder,
+    group: wgpu::BindGroup,
+    // What the targets were at when `group` was built
+    generations: Vec<(TargetHandle, u64)>,
+    alive: Weak<()>,
+}
+
+impl Entry {
+    fn is_stale(&self, registry: &TargetRegistry) -> bool {
+        self.generations
+            .iter()
+            .any(|&(target, generation)| registry.target_generation(target) != generation)
+    }
+}
+
+// Every group built through a BindGroupBuilder, owned by the TargetRegistry since that's
+// what recreates the views they hold
+#[derive(Default)]
+pub struct BindGroups {
+    // Freed slots stay None, handles are never reused
+    entries: Vec<Option<Entry>>,
+}
+
+impl BindGroups {
+    pub fn create(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &TargetRegistry,
+        builder: BindGroupBuilder,
+    ) -> BindGroupHandle {
+        let alive = Arc::new(());
+        self.entries.push(Some(Entry {
+            group: builder.create(device, registry),
+            generations: builder.generations(registry),
+            builder,
+            alive: Arc::downgrade(&alive),
+        }));
+        BindGroupHandle {
+            index: self.entries.len() - 1,
+            _alive: alive,
+        }
+    }
+
+    pub fn get(&self, handle: &BindGroupHandle) -> &wgpu::BindGroup {
+        &self.entries[handle.index]
+            .as_ref()
+            .expect("A live handle's group is never freed")
+            .group
+    }
+
+    // Frees the groups whose handle was dropped and rebuilds up to MAX_REBUILDS stale ones
+    pub fn validate(&mut self, device: &wgpu::Device, registry: &TargetRegistry) {
+        for slot in &mut self.entries {
+            if slot
+                .as_ref()
+                .is_some_and(|entry| entry.alive.strong_count() == 0)
+            {
+                *slot = None;
+            }
+        }
+        let mut rebuilt = Vec::new();
+        let mut waiting = 0;
+        for entry in self.entries.iter_mut().flatten() {
+            if !entry.is_stale(registry) {
+                continue;
+            }
+            if rebuilt.len() == MAX_REBUILDS {
+                waiting += 1;
+                continue;
+            }
+            entry.group = entry.builder.create(device, registry);
+            entry.generations = entry.builder.generations(registry);
+            rebuilt.push(entry.builder.label.as_str());
+        }
+        if !rebuilt.is_empty() {
+            log::debug!("Rebuilt bind groups: {}", rebuilt.join(", "));
+        }
+        if waiting > 0 {
+            log::debug!("{waiting} stale bind group(s) left for the next frame");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+    use crate::memory::GpuMemoryTracker;
+    use crate::stats;
+    use crate::targets::TargetDesc;
+
+    // Copies the bound texture to the output texel for texel
+    const SHADER: &str = "
+        @group(0) @binding(0) var source: texture_2d<f32>;
+
+        @vertex
+        fn vs(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+            return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            return textureLoad(source, vec2<i32>(position.xy), 0);
+        }
+    ";
+    // Comes through an sRGB reload unchanged
+    const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+
+    fn layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bind Groups Test Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        })
+    }
+
+    fn target(
+        registry: &mut TargetRegistry,
+        device: &wgpu::Device,
+        label: &'static str,
+    ) -> TargetHandle {
+        registry.create(
+            device,
+            TargetDesc {
+                label,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                scale: 1.0,
+                storage: false,
+            },
+        )
+    }
+
+    fn fill(gpu: &GpuContext, registry: &TargetRegistry, handle: TargetHandle, texel: [u8; 4]) {
+        let (width, height) = registry.size(handle);
+        let texels: Vec<u8> = texel.repeat((width * height) as usize);
+        gpu.queue.write_texture(
+            registry.texture(handle).as_image_copy(),
+            &texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: None,
+            },
+            registry.texture(handle).size(),
+        );
+    }
+
+    #[test]
+    fn dependent_groups_are_rebuilt_once_per_change() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let mut registry = TargetRegistry::new((8, 8), &memory);
+        let source = target(&mut registry, &gpu.device, "Source");
+        let layout = layout(&gpu.device);
+        let follows = registry.create_bind_group(
+            &gpu.device,
+            BindGroupBuilder::new("Follows Source", &layout).target(0, source),
+        );
+        // Holds a view of its own, nothing the registry recreates
+        let own = registry
+            .texture(source)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let fixed = registry.create_bind_group(
+            &gpu.device,
+            BindGroupBuilder::new("Fixed", &layout).view(0, &own),
+        );
+        let fixed_group = registry.bind_group(&fixed).clone();
+
+        // How many times `follows` was rebuilt by one validate, called twice to see the
+        // second one leave it alone
+        let rebuilds = |registry: &mut TargetRegistry| {
+            let mut seen = vec![registry.bind_group(&follows).clone()];
+            for _ in 0..2 {
+                registry.validate_bind_groups(&gpu.device);
+                let group = registry.bind_group(&follows).clone();
+                if !seen.contains(&group) {
+                    seen.push(group);
+                }
+            }
+            assert!(registry.bind_group(&fixed) == &fixed_group);
+            seen.len() - 1
+        };
+        assert_eq!(rebuilds(&mut registry), 0);
+        // A resize, and two of them before a frame starts still only need one
+        registry.resize(&gpu.device, (16, 16));
+        assert_eq!(rebuilds(&mut registry), 1);
+        registry.resize(&gpu.device, (4, 4));
+        registry.resize(&gpu.device, (32, 24));
+        assert_eq!(rebuilds(&mut registry), 1);
+        // Recreated in another format, the way a texture reload swaps it out
+        registry.set_format(&gpu.device, source, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(rebuilds(&mut registry), 1);
+        // A target nothing here points at
+        let other = target(&mut registry, &gpu.device, "Other");
+        registry.set_format(&gpu.device, other, wgpu::TextureFormat::Bgra8Unorm);
+        assert_eq!(rebuilds(&mut registry), 0);
+    }
+
+    #[test]
+    fn rebuilds_past_the_limit_wait_for_the_next_validate() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let mut registry = TargetRegistry::new((8, 8), &memory);
+        let source = target(&mut registry, &gpu.device, "Source");
+        let layout = layout(&gpu.device);
+        let handles: Vec<_> = (0..MAX_REBUILDS + 8)
+            .map(|_| {
+                let builder = BindGroupBuilder::new("Follows Source", &layout).target(0, source);
+                registry.create_bind_group(&gpu.device, builder)
+            })
+            .collect();
+        let groups = |registry: &TargetRegistry| -> Vec<wgpu::BindGroup> {
+            handles
+                .iter()
+                .map(|handle| registry.bind_group(handle).clone())
+                .collect()
+        };
+        let before = groups(&registry);
+        registry.resize(&gpu.device, (16, 16));
+        registry.validate_bind_groups(&gpu.device);
+        let first = groups(&registry);
+        let changed = |a: &[wgpu::BindGroup], b: &[wgpu::BindGroup]| {
+            a.iter().zip(b).filter(|(a, b)| a != b).count()
+        };
+        assert_eq!(changed(&before, &first), MAX_REBUILDS);
+        registry.validate_bind_groups(&gpu.device);
+        let second = groups(&registry);
+        assert_eq!(changed(&first, &second), 8);
+        assert_eq!(changed(&before, &second), MAX_REBUILDS + 8);
+    }
+
+    #[test]
+    fn draws_read_the_recreated_target() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let device = &gpu.device;
+        let memory = GpuMemoryTracker::new();
+        let mut registry = TargetRegistry::new((8, 8), &memory);
+        let source = target(&mut registry, device, "Source");
+        let output = target(&mut registry, device, "Output");
+        let layout = layout(device);
+        let group = registry.create_bind_group(
+            device,
+            BindGroupBuilder::new("Follows Source", &layout).target(0, source),
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bind Groups Test Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bind Groups Test Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Bind Groups Test Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        // Resized, then reloaded as sRGB
+        for change in 0..2 {
+            if change == 0 {
+                registry.resize(device, (12, 6));
+            } else {
+                registry.set_format(device, source, wgpu::TextureFormat::Rgba8UnormSrgb);
+            }
+            fill(gpu, &registry, source, MAGENTA);
+            registry.validate_bind_groups(device);
+
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bind Groups Test Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: registry.view(output),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, registry.bind_group(&group), &[]);
+            pass.draw(0..3, 0..1);
+            drop(pass);
+            stats::submit(&gpu.queue, std::iter::once(encoder.finish()));
+            assert!(pollster::block_on(device.pop_error_scope()).is_none());
+
+            let image = gpu.read_back(registry.texture(output));
+            assert_eq!(image.dimensions(), (12, 6));
+            assert!(image.pixels().all(|pixel| pixel.0 == MAGENTA), "{change}");
+        }
+    }
+}
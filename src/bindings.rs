@@ -0,0 +1,363 @@
+use glfw::{Key, Modifiers};
+
+// A key and the modifiers held with it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Chord {
+    pub key: Key,
+    pub mods: Modifiers,
+}
+
+impl Chord {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            mods: Modifiers::empty(),
+        }
+    }
+
+    // What a key event pressed, without the lock keys that don't count
+    pub fn pressed(key: Key, mods: Modifiers) -> Self {
+        Self {
+            key,
+            mods: mods & (Modifiers::Control | Modifiers::Alt | Modifiers::Shift),
+        }
+    }
+
+    pub fn with(self, mods: Modifiers) -> Self {
+        Self {
+            mods: self.mods | mods,
+            ..self
+        }
+    }
+
+    // "Ctrl+Shift+C", "F5", "`"
+    pub fn label(self) -> String {
+        let mut label = String::new();
+        for (modifier, name) in [
+            (Modifiers::Control, "Ctrl+"),
+            (Modifiers::Alt, "Alt+"),
+            (Modifiers::Shift, "Shift+"),
+        ] {
+            if self.mods.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        label.push_str(&key_name(self.key));
+        label
+    }
+}
+
+fn key_name(key: Key) -> String {
+    let name = match key {
+        Key::GraveAccent => "`",
+        Key::LeftBracket => "[",
+        Key::RightBracket => "]",
+        Key::Minus => "-",
+        Key::Equal => "=",
+        Key::Escape => "Esc",
+        Key::Delete => "Del",
+        _ => return format!("{key:?}"),
+    };
+    name.to_owned()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Binding {
+    pub chord: Chord,
+    // What it does, as the splash screen lists it and the main loop matches on
+    pub action: &'static str,
+    // The view or mode it only works in, None for everywhere
+    pub only_in: Option<&'static str>,
+    // Held down it goes again with every key repeat
+    pub repeats: bool,
+}
+
+// Every key the main loop reacts to, with a name for what it does. The loop looks keys up
+// here and matches on the action, so what the splash screen lists is what the keys do. An
+// action the loop has no arm for says so when pressed
+pub struct Bindings {
+    bindings: Vec<Binding>,
+}
+
+impl Bindings {
+    pub fn builtin() -> Self {
+        let ctrl = Modifiers::Control;
+        let shift = Modifiers::Shift;
+        let mut bindings = Self {
+            bindings: Vec::new(),
+        };
+        bindings
+            .bind(Chord::new(Key::Escape), "quit")
+            .bind(Chord::new(Key::GraveAccent), "console")
+            .bind(Chord::new(Key::F3), "stats overlay")
+            .bind(Chord::new(Key::F4), "gizmos")
+            .bind(Chord::new(Key::F5), "inspector")
+            .bind(Chord::new(Key::F5).with(shift), "list GPU allocations")
+            .bind(Chord::new(Key::F7), "play timeline")
+            .bind(Chord::new(Key::F7).with(shift), "loop timeline")
+            .bind(Chord::new(Key::F8), "click-through")
+            .bind(Chord::new(Key::F10), "dump frame")
+            .bind(Chord::new(Key::F12), "screenshot")
+            .bind(Chord::new(Key::F12).with(shift), "supersampled screenshot")
+            .bind(Chord::new(Key::Space), "pentagon pipeline")
+            .bind(Chord::new(Key::L), "primitives view")
+            .bind(Chord::new(Key::G), "deferred view")
+            .bind_in(Chord::new(Key::F), "capture mouse", "deferred")
+            .bind_in(Chord::new(Key::W), "forward", "captured mouse")
+            .bind_in(Chord::new(Key::S), "back", "captured mouse")
+            .bind_in(Chord::new(Key::A), "left", "captured mouse")
+            .bind_in(Chord::new(Key::D), "right", "captured mouse")
+            .bind_in(Chord::new(Key::Q), "down", "captured mouse")
+            .bind_in(Chord::new(Key::E), "up", "captured mouse")
+            .bind_in(Chord::new(Key::LeftShift), "faster", "captured mouse")
+            .bind_in(Chord::new(Key::RightShift), "faster", "captured mouse")
+            .bind_in(Chord::new(Key::Escape), "release mouse", "captured mouse")
+            .bind(Chord::new(Key::E), "exposure view")
+            .bind(Chord::new(Key::J), "sprite stress view")
+            .bind(Chord::new(Key::Y), "tile map view")
+            .bind(Chord::new(Key::Y).with(shift), "paint tiles")
+            .bind(Chord::new(Key::M), "MRT view")
+            .bind(Chord::new(Key::P), "SDF playground")
+            .bind(Chord::new(Key::Tab), "next playground shader")
+            .bind(Chord::new(Key::A), "accumulate frames")
+            .bind(Chord::new(Key::B), "background override")
+            .bind(Chord::new(Key::X), "morph")
+            .bind(Chord::new(Key::V), "vignette")
+            .bind(Chord::new(Key::C), "color grade")
+            .bind(Chord::new(Key::O), "bloom")
+            .bind(Chord::new(Key::H), "auto exposure")
+            .bind(Chord::new(Key::D), "dither")
+            .bind(Chord::new(Key::D).with(shift), "dither levels")
+            .bind(Chord::new(Key::D).with(ctrl), "dither matrix")
+            .bind(Chord::new(Key::Q), "pixelate")
+            .bind(Chord::new(Key::Q).with(shift), "pixel size")
+            .bind(Chord::new(Key::W), "color blind simulation")
+            .bind(Chord::new(Key::R), "cycle effect order")
+            .bind(Chord::new(Key::Minus), "exposure down")
+            .repeats()
+            .bind(Chord::new(Key::Equal), "exposure up")
+            .repeats()
+            .bind(Chord::new(Key::Minus).with(shift), "slower adaptation")
+            .repeats()
+            .bind(Chord::new(Key::Equal).with(shift), "faster adaptation")
+            .repeats()
+            .bind(Chord::new(Key::K), "snapping")
+            .bind(Chord::new(Key::K).with(shift), "rulers")
+            .bind(Chord::new(Key::LeftBracket), "finer grid")
+            .bind(Chord::new(Key::RightBracket), "coarser grid")
+            .bind(Chord::new(Key::C).with(ctrl), "copy color")
+            .bind(Chord::new(Key::V).with(ctrl), "paste color")
+            .bind(
+                Chord::new(Key::C).with(ctrl | shift),
+                "copy screenshot path",
+            )
+            .bind(Chord::new(Key::Z).with(ctrl), "undo")
+            .bind(Chord::new(Key::Z).with(ctrl | shift), "redo")
+            .bind(Chord::new(Key::S).with(ctrl), "save scene")
+            .bind_in(Chord::new(Key::N), "new item", "primitives")
+            .bind_in(Chord::new(Key::Delete), "remove item", "primitives")
+            .bind_in(Chord::new(Key::Backspace), "restore items", "primitives")
+            .bind_in(Chord::new(Key::I), "inset view", "primitives")
+            .bind_in(Chord::new(Key::U), "blend mode", "primitives")
+            .bind_in(Chord::new(Key::Up), "select up", "inspector")
+            .repeats()
+            .bind_in(Chord::new(Key::Down), "select down", "inspector")
+            .repeats()
+            .bind_in(Chord::new(Key::Enter), "edit row", "inspector")
+            .bind_in(Chord::new(Key::Left), "pan left", "exposure")
+            .repeats()
+            .bind_in(Chord::new(Key::Right), "pan right", "exposure")
+            .repeats()
+            .bind_in(Chord::new(Key::Left), "pan left", "tiles")
+            .repeats()
+            .bind_in(Chord::new(Key::Right), "pan right", "tiles")
+            .repeats()
+            .bind_in(Chord::new(Key::Left), "seek back", "timeline")
+            .repeats()
+            .bind_in(Chord::new(Key::Right), "seek forward", "timeline")
+            .repeats();
+        #[cfg(feature = "trace")]
+        bindings.bind(Chord::new(Key::F6), "chrome trace");
+        #[cfg(feature = "obj")]
+        bindings.bind(Chord::new(Key::E).with(ctrl), "export OBJ");
+        #[cfg(feature = "text")]
+        bindings.bind_in(Chord::new(Key::T), "name tags", "primitives");
+        bindings
+    }
+
+    pub fn bind(&mut self, chord: Chord, action: &'static str) -> &mut Self {
+        self.bindings.push(Binding {
+            chord,
+            action,
+            only_in: None,
+            repeats: false,
+        });
+        self
+    }
+
+    pub fn bind_in(
+        &mut self,
+        chord: Chord,
+        action: &'static str,
+        only_in: &'static str,
+    ) -> &mut Self {
+        self.bindings.push(Binding {
+            chord,
+            action,
+            only_in: Some(only_in),
+            repeats: false,
+        });
+        self
+    }
+
+    // The last binding goes again with key repeats, for steps worth holding a key for
+    pub fn repeats(&mut self) -> &mut Self {
+        if let Some(binding) = self.bindings.last_mut() {
+            binding.repeats = true;
+        }
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter()
+    }
+
+    // What `chord` does while the views and modes `active` says yes to are on. One bound
+    // for such a view wins over one for everywhere, otherwise the first bound goes. A
+    // `repeat` only finds the bindings marked with repeats()
+    pub fn lookup(
+        &self,
+        chord: Chord,
+        repeat: bool,
+        active: impl Fn(&str) -> bool,
+    ) -> Option<Binding> {
+        let mut candidates = self
+            .bindings
+            .iter()
+            .filter(|binding| binding.chord == chord && (binding.repeats || !repeat));
+        let global = candidates.clone().find(|binding| binding.only_in.is_none());
+        candidates
+            .find(|binding| binding.only_in.is_some_and(&active))
+            .or(global)
+            .copied()
+    }
+
+    // "`: console", then "N: new item (primitives)" for the ones that only work somewhere,
+    // packed into lines of up to `columns` characters
+    pub fn lines(&self, columns: usize) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for binding in self.iter() {
+            let entry = match binding.only_in {
+                Some(only_in) => {
+                    format!("{}: {} ({only_in})", binding.chord.label(), binding.action)
+                }
+                None => format!("{}: {}", binding.chord.label(), binding.action),
+            };
+            match lines.last_mut() {
+                Some(line) if line.chars().count() + 3 + entry.chars().count() <= columns => {
+                    line.push_str("   ");
+                    line.push_str(&entry);
+                }
+                _ => lines.push(entry),
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(chord: Chord, repeat: bool, active: &[&str]) -> Option<&'static str> {
+        Bindings::builtin()
+            .lookup(chord, repeat, |only_in| active.contains(&only_in))
+            .map(|binding| binding.action)
+    }
+
+    #[test]
+    fn views_that_are_on_win_over_everywhere() {
+        let w = Chord::new(Key::W);
+        assert_eq!(lookup(w, false, &[]), Some("color blind simulation"));
+        assert_eq!(lookup(w, false, &["captured mouse"]), Some("forward"));
+        let escape = Chord::new(Key::Escape);
+        assert_eq!(lookup(escape, false, &[]), Some("quit"));
+        assert_eq!(
+            lookup(escape, false, &["captured mouse"]),
+            Some("release mouse")
+        );
+        // Only in a view that isn't on, nothing
+        assert_eq!(lookup(Chord::new(Key::Enter), false, &[]), None);
+        assert_eq!(
+            lookup(Chord::new(Key::Enter), false, &["inspector"]),
+            Some("edit row")
+        );
+        // Two views on that both bind it, the first bound goes
+        let left = Chord::new(Key::Left);
+        assert_eq!(
+            lookup(left, false, &["timeline", "tiles"]),
+            Some("pan left")
+        );
+        assert_eq!(lookup(left, false, &["timeline"]), Some("seek back"));
+    }
+
+    #[test]
+    fn modifiers_must_match_but_lock_keys_dont_count() {
+        let caps = Modifiers::CapsLock | Modifiers::NumLock;
+        assert_eq!(
+            lookup(
+                Chord::pressed(Key::Z, Modifiers::Control | caps),
+                false,
+                &[]
+            ),
+            Some("undo")
+        );
+        assert_eq!(
+            lookup(
+                Chord::pressed(Key::Z, Modifiers::Control | Modifiers::Shift),
+                false,
+                &[]
+            ),
+            Some("redo")
+        );
+        assert_eq!(
+            lookup(Chord::pressed(Key::Z, Modifiers::empty()), false, &[]),
+            None
+        );
+        assert_eq!(
+            lookup(Chord::pressed(Key::D, Modifiers::Control), false, &[]),
+            Some("dither matrix")
+        );
+    }
+
+    #[test]
+    fn only_marked_bindings_repeat() {
+        assert_eq!(
+            lookup(Chord::new(Key::Minus), true, &[]),
+            Some("exposure down")
+        );
+        assert_eq!(
+            lookup(Chord::new(Key::Up), true, &["inspector"]),
+            Some("select up")
+        );
+        assert_eq!(lookup(Chord::new(Key::F12), true, &[]), None);
+        assert_eq!(lookup(Chord::new(Key::F12), false, &[]), Some("screenshot"));
+    }
+
+    #[test]
+    fn every_chord_means_one_thing_per_view() {
+        let bindings = Bindings::builtin();
+        let all: Vec<&Binding> = bindings.iter().collect();
+        for (i, a) in all.iter().enumerate() {
+            for b in &all[i + 1..] {
+                assert!(
+                    a.chord != b.chord || a.only_in != b.only_in,
+                    "{} is bound to both \"{}\" and \"{}\"",
+                    a.chord.label(),
+                    a.action,
+                    b.action
+                );
+            }
+        }
+    }
+}
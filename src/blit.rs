@@ -0,0 +1,130 @@
+use crate::frame::{ColorTarget, Frame};
+use crate::pipeline_bank::{Pipeline, PipelineBuilder, Recipe};
+use crate::shaders;
+use crate::targets::{TargetHandle, TargetRegistry};
+
+// Draws an offscreen target onto the swapchain, mostly for looking at intermediate results
+pub struct Blitter {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipeline: Pipeline,
+    // It isn't in the bank, so it keeps its own way to follow surface format changes
+    recipe: Recipe,
+}
+
+impl Blitter {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Blit Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader = shaders::create_module(device, "Blit Shader", include_str!("blit.wgsl"));
+        let recipe = PipelineBuilder::new("Blit Pipeline", &shader)
+            .vertex_entry("vs_fullscreen")
+            .fragment_entry("fs_blit")
+            .bind_group_layout(&layout)
+            .cull_mode(None)
+            .recipe();
+
+        Self {
+            layout,
+            sampler,
+            pipeline: recipe.build(device, format),
+            recipe,
+        }
+    }
+
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        if self.pipeline.targets != [format] {
+            self.pipeline = self.recipe.build(device, format);
+        }
+    }
+
+    pub fn blit_to_swapchain(
+        &self,
+        device: &wgpu::Device,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        source: TargetHandle,
+    ) {
+        let load = frame.background.color();
+        self.blit(device, frame, targets, source, load, None);
+    }
+
+    // Into `rect` (x, y, width, height in pixels) over what's on the swapchain already
+    pub fn blit_to_rect(
+        &self,
+        device: &wgpu::Device,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        source: TargetHandle,
+        rect: (f32, f32, f32, f32),
+    ) {
+        self.blit(
+            device,
+            frame,
+            targets,
+            source,
+            wgpu::LoadOp::Load,
+            Some(rect),
+        );
+    }
+
+    fn blit(
+        &self,
+        device: &wgpu::Device,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        source: TargetHandle,
+        load: wgpu::LoadOp<wgpu::Color>,
+        rect: Option<(f32, f32, f32, f32)>,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(targets.view(source)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        let mut pass = frame.pass("Blit Pass", &[(ColorTarget::Swapchain, load)], targets);
+        if let Some((x, y, width, height)) = rect {
+            pass.raw.set_viewport(x, y, width, height, 0.0, 1.0);
+        }
+        pass.raw.set_pipeline(&self.pipeline.raw);
+        pass.raw.set_bind_group(0, &bind_group, &[]);
+        pass.raw.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,12 @@
+// Copies a texture onto whatever target is bound, with filtering
+#include "fullscreen.wgsl"
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@fragment
+fn fs_blit(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
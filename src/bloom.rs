@@ -0,0 +1,118 @@
+use crate::bind_groups::{BindGroupBuilder, BindGroupHandle};
+use crate::gpu_image::GpuImage;
+use crate::post::{Effect, EffectContext};
+use crate::shaders;
+use crate::targets::{TargetHandle, TargetRegistry};
+
+// Brightness (max of r, g, b in linear) above which things start to glow
+const THRESHOLD: f32 = 0.9;
+// In texels of the half resolution glow
+const RADIUS: u32 = 8;
+
+// Bright pass and blur at half resolution, then added back over the input
+pub struct Bloom {
+    pub intensity: f32,
+    glow: TargetHandle,
+    scratch: TargetHandle,
+    images: GpuImage,
+    shader: wgpu::ShaderModule,
+    layout: wgpu::BindGroupLayout,
+    // Points at the glow view, the registry rebuilds it on resizes
+    bind_group: BindGroupHandle,
+}
+
+impl Bloom {
+    pub fn new(device: &wgpu::Device, registry: &mut TargetRegistry) -> Self {
+        let glow = registry.create(device, GpuImage::target("Bloom Glow", 0.5));
+        let scratch = registry.create(device, GpuImage::target("Bloom Scratch", 0.5));
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom Glow Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = registry.create_bind_group(
+            device,
+            BindGroupBuilder::new("Bloom Glow Bind Group", &layout)
+                .target(0, glow)
+                .sampler(1, &sampler),
+        );
+        Self {
+            intensity: 0.8,
+            glow,
+            scratch,
+            images: GpuImage::new(device),
+            shader: shaders::create_module(device, "Bloom Shader", include_str!("bloom.wgsl")),
+            layout,
+            bind_group,
+        }
+    }
+}
+
+impl Effect for Bloom {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_composite")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&self.intensity).to_vec()
+    }
+
+    fn extra_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+        Some(&self.layout)
+    }
+
+    fn bind_group(&self) -> Option<&BindGroupHandle> {
+        Some(&self.bind_group)
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        (name == "intensity").then_some(&mut self.intensity)
+    }
+
+    fn prepare(&mut self, ctx: &mut EffectContext, input: TargetHandle) {
+        self.images.downsample(
+            ctx.device,
+            ctx.queue,
+            ctx.encoder,
+            ctx.pool,
+            ctx.registry,
+            input,
+            self.glow,
+            THRESHOLD,
+        );
+        self.images.blur(
+            ctx.device,
+            ctx.queue,
+            ctx.encoder,
+            ctx.pool,
+            ctx.registry,
+            self.glow,
+            self.scratch,
+            RADIUS,
+        );
+    }
+}
@@ -0,0 +1,17 @@
+#include "post.wgsl"
+
+struct Bloom {
+    intensity: f32,
+};
+
+@group(1) @binding(0) var<uniform> bloom: Bloom;
+@group(2) @binding(0) var glow: texture_2d<f32>;
+@group(2) @binding(1) var glow_sampler: sampler;
+
+// The glow is at a lower resolution, the sampler smooths it back up
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv).rgb;
+    let halo = textureSample(glow, glow_sampler, in.uv).rgb;
+    return vec4<f32>(color + halo * bloom.intensity, 1.0);
+}
@@ -0,0 +1,124 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+
+// Smallest size class, anything below gets rounded up to this
+const MIN_CLASS: u64 = 256;
+// Idle buffers older than this many frames get dropped even when under the cap
+const MAX_IDLE_FRAMES: u64 = 300;
+
+struct Pooled {
+    buffer: Tracked<wgpu::Buffer>,
+    usage: wgpu::BufferUsages,
+    // Frame it was last handed out in
+    last_used: u64,
+}
+
+// Buffers that only live for one frame (batch vertices, staging, readbacks) come from here
+// instead of being created and dropped every time. A buffer handed out in frame N goes back
+// to the free list once the GPU has finished frame N's submission.
+pub struct BufferPool {
+    free: Vec<Pooled>,
+    active: Vec<Pooled>,
+    frame: u64,
+    // Latest frame whose submission is known to be done, written from wgpu's callback
+    completed: Arc<AtomicU64>,
+    // Upper bound on idle bytes kept around, least recently used goes first
+    cap_bytes: u64,
+    memory: GpuMemoryTracker,
+}
+
+impl BufferPool {
+    pub fn new(memory: &GpuMemoryTracker, cap_bytes: u64) -> Self {
+        Self {
+            free: Vec::new(),
+            active: Vec::new(),
+            frame: 1,
+            completed: Arc::new(AtomicU64::new(0)),
+            cap_bytes,
+            memory: memory.clone(),
+        }
+    }
+
+    // Sizes go up in powers of two so differently sized requests can share buffers
+    fn size_class(size: u64) -> u64 {
+        size.next_power_of_two().max(MIN_CLASS)
+    }
+
+    // A buffer of at least `size` bytes, only valid for the current frame
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        usage: wgpu::BufferUsages,
+        size: u64,
+    ) -> wgpu::Buffer {
+        let class = Self::size_class(size);
+        let reusable = self
+            .free
+            .iter()
+            .position(|pooled| pooled.usage == usage && pooled.buffer.size() == class);
+        let mut pooled = match reusable {
+            Some(index) => self.free.swap_remove(index),
+            None => Pooled {
+                buffer: self.memory.create_buffer(
+                    device,
+                    &wgpu::BufferDescriptor {
+                        label: Some(label),
+                        size: class,
+                        usage,
+                        mapped_at_creation: false,
+                    },
+                    MemoryCategory::Transient,
+                ),
+                usage,
+                last_used: self.frame,
+            },
+        };
+        pooled.last_used = self.frame;
+        let buffer = (*pooled.buffer).clone();
+        self.active.push(pooled);
+        self.memory.set_pool_idle(self.idle_bytes());
+        buffer
+    }
+
+    // After the frame has been submitted. Hands back whatever the GPU is done with and trims
+    pub fn end_frame(&mut self, queue: &wgpu::Queue) {
+        let frame = self.frame;
+        let completed = Arc::clone(&self.completed);
+        queue.on_submitted_work_done(move || {
+            completed.fetch_max(frame, Ordering::AcqRel);
+        });
+        self.frame += 1;
+
+        let completed = self.completed.load(Ordering::Acquire);
+        let (done, still_busy): (Vec<_>, Vec<_>) = std::mem::take(&mut self.active)
+            .into_iter()
+            .partition(|pooled| pooled.last_used <= completed);
+        self.active = still_busy;
+        self.free.extend(done);
+        self.trim();
+        self.memory.set_pool_idle(self.idle_bytes());
+    }
+
+    fn trim(&mut self) {
+        let now = self.frame;
+        self.free
+            .retain(|pooled| now - pooled.last_used <= MAX_IDLE_FRAMES);
+
+        // Most recently used first, drop from the back until under the cap
+        self.free
+            .sort_by_key(|pooled| std::cmp::Reverse(pooled.last_used));
+        let cap = self.cap_bytes;
+        let mut kept = 0;
+        self.free.retain(|pooled| {
+            kept += pooled.buffer.size();
+            kept <= cap
+        });
+    }
+
+    pub fn idle_bytes(&self) -> u64 {
+        self.free.iter().map(|pooled| pooled.buffer.size()).sum()
+    }
+}
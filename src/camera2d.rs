@@ -0,0 +1,263 @@
+use glam::Vec2;
+#[cfg(feature = "serde-scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::pacing::Interpolate;
+
+// What a window of another size does to the 2D view. Picking, the gizmos and the shaders
+// all go through Camera2d's conversions, so they agree under each of these
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ResizePolicy {
+    // One world unit per pixel at zoom 1 whatever the size, a bigger window shows more world
+    #[default]
+    Extend,
+    // Always shows what a window this many pixels across would at zoom 1, squashed or
+    // stretched to the window's aspect
+    Stretch(Vec2),
+    // The same scaled evenly to fit, centered, with bars over the rest of the window
+    Letterbox(Vec2),
+}
+
+impl ResizePolicy {
+    // "extend", "stretch" or "letterbox", the latter two keeping `design` (the window size
+    // when it's picked, usually)
+    pub fn parse(name: &str, design: (u32, u32)) -> Option<Self> {
+        let design = Vec2::new(design.0 as f32, design.1 as f32).max(Vec2::ONE);
+        match name {
+            "extend" => Some(ResizePolicy::Extend),
+            "stretch" => Some(ResizePolicy::Stretch(design)),
+            "letterbox" => Some(ResizePolicy::Letterbox(design)),
+            _ => None,
+        }
+    }
+}
+
+// World space is y-up with one unit per pixel at zoom 1 (under ResizePolicy::Extend),
+// `center` sits in the middle of the window
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-scene", derive(Serialize, Deserialize))]
+pub struct Camera2d {
+    pub center: Vec2,
+    pub zoom: f32,
+    // The window's business rather than the scene's, it isn't saved with one
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    pub resize: ResizePolicy,
+}
+
+impl Camera2d {
+    pub const MIN_ZOOM: f32 = 0.05;
+    pub const MAX_ZOOM: f32 = 50.0;
+
+    pub fn new() -> Self {
+        Self {
+            center: Vec2::ZERO,
+            zoom: 1.0,
+            resize: ResizePolicy::Extend,
+        }
+    }
+
+    // Window pixels per world unit along each axis at zoom 1
+    fn pixels_per_unit(&self, viewport: (u32, u32)) -> Vec2 {
+        let window = Vec2::new(viewport.0 as f32, viewport.1 as f32);
+        match self.resize {
+            ResizePolicy::Extend => Vec2::ONE,
+            ResizePolicy::Stretch(design) => window / design,
+            ResizePolicy::Letterbox(design) => Vec2::splat((window / design).min_element()),
+        }
+    }
+
+    // Where in the window the world shows (x, y, width, height in pixels, origin top-left).
+    // All of it except under Letterbox
+    pub fn content_rect(&self, viewport: (u32, u32)) -> (f32, f32, f32, f32) {
+        let window = Vec2::new(viewport.0 as f32, viewport.1 as f32);
+        let size = match self.resize {
+            ResizePolicy::Letterbox(design) => design * self.pixels_per_unit(viewport),
+            _ => window,
+        };
+        let corner = (window - size) * 0.5;
+        (corner.x, corner.y, size.x, size.y)
+    }
+
+    // The size the shaders take the window to be: camera pixels (world units times zoom)
+    // from the center to the window's edge, times two. The window's own size under Extend
+    pub fn clip_extent(&self, viewport: (u32, u32)) -> [f32; 2] {
+        let window = Vec2::new(viewport.0 as f32, viewport.1 as f32);
+        (window / self.pixels_per_unit(viewport)).into()
+    }
+
+    // Screen positions are glfw's: pixels, origin top-left, y down
+    pub fn screen_to_world(&self, screen: Vec2, viewport: (u32, u32)) -> Vec2 {
+        let half = Vec2::new(viewport.0 as f32, viewport.1 as f32) * 0.5;
+        let offset = Vec2::new(screen.x - half.x, half.y - screen.y);
+        self.center + offset / (self.zoom * self.pixels_per_unit(viewport))
+    }
+
+    pub fn world_to_screen(&self, world: Vec2, viewport: (u32, u32)) -> Vec2 {
+        let half = Vec2::new(viewport.0 as f32, viewport.1 as f32) * 0.5;
+        let offset = (world - self.center) * self.zoom * self.pixels_per_unit(viewport);
+        Vec2::new(half.x + offset.x, half.y - offset.y)
+    }
+
+    // World space (min, max) corners of what the window shows, inside the bars under
+    // Letterbox
+    pub fn visible(&self, viewport: (u32, u32)) -> (Vec2, Vec2) {
+        let (_, _, width, height) = self.content_rect(viewport);
+        let half = Vec2::new(width, height) * 0.5 / (self.zoom * self.pixels_per_unit(viewport));
+        (self.center - half, self.center + half)
+    }
+
+    // Zooms by `factor` keeping whatever is under `screen` where it is
+    pub fn zoom_at(&mut self, screen: Vec2, factor: f32, viewport: (u32, u32)) {
+        let anchor = self.screen_to_world(screen, viewport);
+        self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        let moved = self.screen_to_world(screen, viewport);
+        self.center += anchor - moved;
+    }
+}
+
+// Zoom goes geometrically, so zooming from 1 to 16 takes as long getting to 4 as from 4 on
+impl Interpolate for Camera2d {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        Self {
+            center: self.center.lerp(next.center, alpha),
+            zoom: self.zoom * (next.zoom / self.zoom).powf(alpha),
+            resize: self.resize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{self, Scene};
+    use crate::spatial_hash::SpatialHash;
+
+    const DESIGN: Vec2 = Vec2::new(800.0, 600.0);
+    // The size the policies were picked at and a wider, taller one
+    const SIZES: [(u32, u32); 2] = [(800, 600), (1600, 1000)];
+    const POLICIES: [ResizePolicy; 3] = [
+        ResizePolicy::Extend,
+        ResizePolicy::Stretch(DESIGN),
+        ResizePolicy::Letterbox(DESIGN),
+    ];
+
+    fn camera(resize: ResizePolicy) -> Camera2d {
+        Camera2d {
+            center: Vec2::new(30.0, -20.0),
+            zoom: 1.5,
+            resize,
+        }
+    }
+
+    fn near(a: Vec2, b: Vec2) -> bool {
+        (a - b).abs().max_element() < 1e-3
+    }
+
+    // Where shapes.wgsl puts `world` in the window: clip space from the camera uniform,
+    // then the rasterizer's mapping of -1..1 onto the window's pixels
+    fn drawn_at(camera: &Camera2d, world: Vec2, viewport: (u32, u32)) -> Vec2 {
+        let extent = Vec2::from(camera.clip_extent(viewport));
+        let clip = (world - camera.center) * camera.zoom / (extent * 0.5);
+        let window = Vec2::new(viewport.0 as f32, viewport.1 as f32);
+        Vec2::new(
+            (clip.x + 1.0) * 0.5 * window.x,
+            (1.0 - clip.y) * 0.5 * window.y,
+        )
+    }
+
+    #[test]
+    fn conversions_agree_with_what_the_shader_draws() {
+        for policy in POLICIES {
+            let camera = camera(policy);
+            for size in SIZES {
+                for world in [
+                    Vec2::ZERO,
+                    camera.center,
+                    Vec2::new(-250.0, 130.0),
+                    Vec2::new(400.0, -333.0),
+                ] {
+                    let screen = camera.world_to_screen(world, size);
+                    assert!(
+                        near(screen, drawn_at(&camera, world, size)),
+                        "{policy:?} {size:?} {world}"
+                    );
+                    assert!(near(camera.screen_to_world(screen, size), world));
+                }
+                // The corners of the content rect are the corners of what's visible
+                let (x, y, width, height) = camera.content_rect(size);
+                let (min, max) = camera.visible(size);
+                let bottom_left = camera.screen_to_world(Vec2::new(x, y + height), size);
+                let top_right = camera.screen_to_world(Vec2::new(x + width, y), size);
+                assert!(
+                    near(bottom_left, min) && near(top_right, max),
+                    "{policy:?} {size:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn each_policy_shows_what_it_says_at_another_size() {
+        let visible = |policy, size| camera(policy).visible(size);
+        let (small, big) = (SIZES[0], SIZES[1]);
+        // Extend shows more world in a bigger window, a world unit staying 1.5 pixels
+        let (min, max) = visible(ResizePolicy::Extend, big);
+        assert!(near(max - min, Vec2::new(1600.0, 1000.0) / 1.5));
+        let (min, max) = visible(ResizePolicy::Extend, small);
+        assert!(near(max - min, Vec2::new(800.0, 600.0) / 1.5));
+        // Stretch shows the same world at any size, squashed to fit
+        let stretch = ResizePolicy::Stretch(DESIGN);
+        let (min, max) = visible(stretch, big);
+        assert!(near(max - min, DESIGN / 1.5));
+        assert!(near(min, visible(stretch, small).0));
+        // Letterbox shows the same world too, evenly scaled, with bars left and right
+        let letterbox = ResizePolicy::Letterbox(DESIGN);
+        let (min, max) = visible(letterbox, big);
+        assert!(near(max - min, DESIGN / 1.5));
+        let (x, y, width, height) = camera(letterbox).content_rect(big);
+        assert!(near(Vec2::new(x, y), Vec2::new(400.0 / 3.0, 0.0)));
+        assert!(near(
+            Vec2::new(width, height),
+            Vec2::new(4000.0 / 3.0, 1000.0)
+        ));
+        // No bars at the size it was picked at
+        assert_eq!(
+            camera(letterbox).content_rect(small),
+            (0.0, 0.0, 800.0, 600.0)
+        );
+    }
+
+    #[test]
+    fn clicking_an_item_where_it_is_drawn_picks_it() {
+        let scene = Scene::starter();
+        let outlines: Vec<Vec<Vec2>> = scene
+            .items
+            .iter()
+            .map(|item| scene::load_outline(&item.mesh).unwrap())
+            .collect();
+        let mut grid = SpatialHash::new();
+        grid.rebuild(scene.item_bounds(&outlines));
+        for policy in POLICIES {
+            let mut camera = camera(policy);
+            camera.center = Vec2::new(0.0, -250.0);
+            camera.zoom = 1.0;
+            for size in SIZES {
+                for (index, item) in scene.items.iter().enumerate() {
+                    // A little off its center, toward a corner, where it's still drawn
+                    let world = item.transform.translation + Vec2::new(20.0, -15.0);
+                    let click = drawn_at(&camera, world, size);
+                    let (x, y, width, height) = camera.content_rect(size);
+                    assert!(
+                        click.x > x && click.x < x + width && click.y > y && click.y < y + height
+                    );
+                    let picked = scene.pick(&outlines, &grid, camera.screen_to_world(click, size));
+                    assert_eq!(picked, Some(index), "{policy:?} {size:?} {}", item.name);
+                }
+                // And between two items there's nothing
+                let gap = drawn_at(&camera, Vec2::new(-100.0, -300.0), size);
+                let picked = scene.pick(&outlines, &grid, camera.screen_to_world(gap, size));
+                assert_eq!(picked, None, "{policy:?} {size:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::pacing::{Interpolate, Stepped};
+
+// Radians a pixel of mouse movement turns the camera
+const LOOK_SENSITIVITY: f32 = 0.0025;
+// Pitch stops this short of straight up or down, where yaw stops meaning anything
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+// Shift multiplies the fly speed by this
+const BOOST: f32 = 4.0;
+// One scroll notch multiplies the fly speed (or divides the orbit distance) by this, so
+// scrolling covers slow and fast alike
+const SCROLL_STEP: f32 = 1.25;
+// Units per second the fly speed is kept between
+const SPEED_RANGE: (f32, f32) = (0.05, 200.0);
+const DISTANCE_RANGE: (f32, f32) = (0.5, 60.0);
+pub const DEFAULT_FLY_SPEED: f32 = 2.0;
+// Rotation and translation time constants of the fly camera, in seconds
+pub const DEFAULT_SMOOTHING: (f32, f32) = (0.05, 0.1);
+
+// What moves a 3D camera, gathered over a frame by the main loop
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CameraInput {
+    // -1 to 1 along the view's right, up and forward, from WASD and QE
+    pub movement: Vec3,
+    // Shift is held
+    pub boost: bool,
+    // Pixels the mouse moved while the cursor was captured
+    pub look: Vec2,
+    // Scroll wheel notches, up positive
+    pub scroll: f32,
+}
+
+// Where a camera is and which way it looks. Yaw 0 looks down -Z and turns towards +X,
+// pitch is up from the horizon
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraPose {
+    pub eye: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl CameraPose {
+    pub fn looking_at(eye: Vec3, target: Vec3) -> Self {
+        let direction = (target - eye).normalize_or(Vec3::NEG_Z);
+        Self {
+            eye,
+            yaw: direction.x.atan2(-direction.z),
+            pitch: direction.y.asin().clamp(-PITCH_LIMIT, PITCH_LIMIT),
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(cos_pitch * sin_yaw, sin_pitch, -cos_pitch * cos_yaw)
+    }
+
+    // Stays level, the camera never rolls
+    pub fn right(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        Vec3::new(cos_yaw, 0.0, sin_yaw)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.eye, self.forward(), Vec3::Y)
+    }
+
+    // Turned by `look` pixels, pitch clamped short of the poles
+    fn turned(self, look: Vec2) -> Self {
+        Self {
+            yaw: self.yaw + look.x * LOOK_SENSITIVITY,
+            pitch: (self.pitch - look.y * LOOK_SENSITIVITY).clamp(-PITCH_LIMIT, PITCH_LIMIT),
+            ..self
+        }
+    }
+}
+
+impl Interpolate for CameraPose {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        Self {
+            eye: self.eye.lerp(next.eye, alpha),
+            yaw: self.yaw.lerp_state(&next.yaw, alpha),
+            pitch: self.pitch.lerp_state(&next.pitch, alpha),
+        }
+    }
+}
+
+// Turns CameraInput into where the deferred view looks from. Updated in fixed steps and
+// drawn interpolated, like the rest of what animates. The console's `camera` command
+// swaps them
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+    // `dt` in seconds
+    fn update(&mut self, input: &CameraInput, dt: f32);
+    // `alpha` of the way from the second to last update to the last
+    fn pose(&self, alpha: f32) -> CameraPose;
+    // Still easing somewhere without input, so the view has to keep redrawing
+    fn is_settling(&self) -> bool {
+        false
+    }
+    // Units per second, for the ones that fly. What a controller swapped in after it starts at
+    fn speed(&self) -> Option<f32> {
+        None
+    }
+}
+
+// Turns around a point. Looking around swings it, scrolling gets closer or further
+pub struct OrbitCamera {
+    pub target: Vec3,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    pose: Stepped<CameraPose>,
+}
+
+impl OrbitCamera {
+    // Around `target`, from wherever `pose` is
+    pub fn new(target: Vec3, pose: CameraPose) -> Self {
+        let facing = CameraPose::looking_at(pose.eye, target);
+        let mut orbit = Self {
+            target,
+            yaw: facing.yaw,
+            pitch: facing.pitch,
+            distance: pose
+                .eye
+                .distance(target)
+                .clamp(DISTANCE_RANGE.0, DISTANCE_RANGE.1),
+            pose: Stepped::new(facing),
+        };
+        orbit.pose = Stepped::new(orbit.current());
+        orbit
+    }
+
+    fn current(&self) -> CameraPose {
+        let facing = CameraPose {
+            eye: Vec3::ZERO,
+            yaw: self.yaw,
+            pitch: self.pitch,
+        };
+        CameraPose {
+            eye: self.target - facing.forward() * self.distance,
+            ..facing
+        }
+    }
+}
+
+impl CameraController for OrbitCamera {
+    fn name(&self) -> &'static str {
+        "orbit"
+    }
+
+    fn update(&mut self, input: &CameraInput, _dt: f32) {
+        let turned = self.current().turned(input.look);
+        self.yaw = turned.yaw;
+        self.pitch = turned.pitch;
+        self.distance = (self.distance / SCROLL_STEP.powf(input.scroll))
+            .clamp(DISTANCE_RANGE.0, DISTANCE_RANGE.1);
+        let current = self.current();
+        self.pose.step(|_| current);
+    }
+
+    fn pose(&self, alpha: f32) -> CameraPose {
+        self.pose.at(alpha)
+    }
+}
+
+// First person fly-through. WASD moves along the view, QE down and up, shift goes faster
+// and scrolling changes the base speed. Where the input takes it is followed with
+// exponential smoothing, the same at any step length
+pub struct FlyCamera {
+    // Where input has taken it, `shown` follows
+    target: CameraPose,
+    shown: Stepped<CameraPose>,
+    // Units per second
+    pub speed: f32,
+    // Time constants in seconds, how long it takes to get about two thirds of the way to
+    // where input took it. 0 follows right away
+    pub rotation_smoothing: f32,
+    pub translation_smoothing: f32,
+}
+
+impl FlyCamera {
+    pub fn new(pose: CameraPose, speed: f32) -> Self {
+        Self {
+            target: pose,
+            shown: Stepped::new(pose),
+            speed: clamp_speed(speed),
+            rotation_smoothing: DEFAULT_SMOOTHING.0,
+            translation_smoothing: DEFAULT_SMOOTHING.1,
+        }
+    }
+}
+
+impl CameraController for FlyCamera {
+    fn name(&self) -> &'static str {
+        "fly"
+    }
+
+    fn update(&mut self, input: &CameraInput, dt: f32) {
+        self.speed = clamp_speed(self.speed * SCROLL_STEP.powf(input.scroll));
+        self.target = self.target.turned(input.look);
+        let speed = if input.boost {
+            self.speed * BOOST
+        } else {
+            self.speed
+        };
+        let forward = self.target.forward();
+        let right = self.target.right();
+        let up = right.cross(forward);
+        // Diagonals aren't faster
+        let movement = input.movement.clamp_length_max(1.0);
+        self.target.eye +=
+            (right * movement.x + up * movement.y + forward * movement.z) * speed * dt;
+
+        let target = self.target;
+        let rotation = follow(self.rotation_smoothing, dt);
+        let translation = follow(self.translation_smoothing, dt);
+        self.shown.step(|shown| CameraPose {
+            eye: shown.eye.lerp(target.eye, translation),
+            yaw: shown.yaw.lerp_state(&target.yaw, rotation),
+            pitch: shown.pitch.lerp_state(&target.pitch, rotation),
+        });
+    }
+
+    fn pose(&self, alpha: f32) -> CameraPose {
+        self.shown.at(alpha)
+    }
+
+    fn is_settling(&self) -> bool {
+        let shown = self.shown.current;
+        shown.eye.distance_squared(self.target.eye) > 1e-8
+            || (shown.yaw - self.target.yaw).abs() > 1e-5
+            || (shown.pitch - self.target.pitch).abs() > 1e-5
+    }
+
+    fn speed(&self) -> Option<f32> {
+        Some(self.speed)
+    }
+}
+
+// How far of the way to its target something smoothed with time constant `tau` gets in
+// `dt`. Two steps of dt get exactly as far as one of 2 * dt
+fn follow(tau: f32, dt: f32) -> f32 {
+    if tau <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-dt / tau).exp()
+    }
+}
+
+// Scrolling far enough overflows to infinity, that's as fast as it goes rather than a reset
+pub fn clamp_speed(speed: f32) -> f32 {
+    if speed.is_nan() {
+        DEFAULT_FLY_SPEED
+    } else {
+        speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 120.0;
+
+    fn start() -> CameraPose {
+        CameraPose::looking_at(Vec3::new(0.0, 2.0, 6.0), Vec3::ZERO)
+    }
+
+    // Part way through easing after a turn and a move, so there's smoothing left to do
+    fn settling() -> FlyCamera {
+        let mut fly = FlyCamera::new(start(), DEFAULT_FLY_SPEED);
+        let input = CameraInput {
+            movement: Vec3::new(0.5, 0.2, 1.0),
+            look: Vec2::new(120.0, -40.0),
+            ..CameraInput::default()
+        };
+        fly.update(&input, DT);
+        assert!(fly.is_settling());
+        fly
+    }
+
+    fn assert_close(a: CameraPose, b: CameraPose, what: &str) {
+        assert!(
+            a.eye.distance(b.eye) < 1e-5
+                && (a.yaw - b.yaw).abs() < 1e-5
+                && (a.pitch - b.pitch).abs() < 1e-5,
+            "{what}: {a:?} vs {b:?}"
+        );
+    }
+
+    fn bits(pose: CameraPose) -> [u32; 5] {
+        let [x, y, z] = pose.eye.to_array().map(f32::to_bits);
+        [x, y, z, pose.yaw.to_bits(), pose.pitch.to_bits()]
+    }
+
+    #[test]
+    fn smoothing_gets_as_far_in_n_steps_as_in_one_n_times_longer() {
+        let idle = CameraInput::default();
+        for steps in [2, 8, 30] {
+            let mut many = settling();
+            for _ in 0..steps {
+                many.update(&idle, DT);
+            }
+            let mut one = settling();
+            one.update(&idle, DT * steps as f32);
+            assert_close(many.pose(1.0), one.pose(1.0), &format!("{steps} steps"));
+            assert!(one.is_settling());
+        }
+
+        // Uneven steps adding up to the same time, like a variable frame rate would give
+        let mut uneven = settling();
+        for dt in [0.004, 0.031, 0.0125, 0.0025, 0.05] {
+            uneven.update(&idle, dt);
+        }
+        let mut one = settling();
+        one.update(&idle, 0.1);
+        assert_close(uneven.pose(1.0), one.pose(1.0), "uneven steps");
+
+        // And given long enough everything arrives
+        one.update(&idle, 10.0);
+        assert!(!one.is_settling());
+    }
+
+    #[test]
+    fn movement_covers_the_same_ground_at_any_step_length() {
+        let input = CameraInput {
+            movement: Vec3::new(-1.0, 1.0, 1.0),
+            boost: true,
+            ..CameraInput::default()
+        };
+        let moved = |steps: u32| {
+            let mut fly = FlyCamera::new(start(), DEFAULT_FLY_SPEED);
+            fly.rotation_smoothing = 0.0;
+            fly.translation_smoothing = 0.0;
+            for _ in 0..steps {
+                fly.update(&input, DT * 16.0 / steps as f32);
+            }
+            fly.pose(1.0)
+        };
+        let one = moved(1);
+        for steps in [2, 4, 16] {
+            assert_close(moved(steps), one, &format!("{steps} steps"));
+        }
+        // Diagonals clamped to unit length, boosted
+        let travelled = one.eye.distance(start().eye);
+        let expected = DEFAULT_FLY_SPEED * BOOST * DT * 16.0;
+        assert!(
+            (travelled - expected).abs() < 1e-4,
+            "{travelled} vs {expected}"
+        );
+    }
+
+    #[test]
+    fn replaying_the_same_inputs_gives_the_same_poses() {
+        // Inputs made up from the step number, the same every run
+        let input = |step: u32| {
+            let t = step as f32 * 0.37;
+            CameraInput {
+                movement: Vec3::new(t.sin(), (t * 0.5).cos(), 1.0),
+                boost: step % 7 < 2,
+                look: Vec2::new((t * 1.3).cos() * 30.0, t.sin() * 12.0),
+                scroll: if step.is_multiple_of(50) { 1.0 } else { 0.0 },
+            }
+        };
+        let replay = || {
+            let mut fly = FlyCamera::new(start(), DEFAULT_FLY_SPEED);
+            (0..600)
+                .map(|step| {
+                    fly.update(&input(step), DT);
+                    bits(fly.pose(0.5))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(replay(), replay());
+    }
+
+    #[test]
+    fn pitch_stops_short_of_the_poles() {
+        let mut fly = FlyCamera::new(start(), DEFAULT_FLY_SPEED);
+        fly.rotation_smoothing = 0.0;
+        let up = CameraInput {
+            look: Vec2::new(0.0, -1e6),
+            ..CameraInput::default()
+        };
+        fly.update(&up, DT);
+        assert!((fly.pose(1.0).pitch - PITCH_LIMIT).abs() < 1e-6);
+        let down = CameraInput {
+            look: Vec2::new(0.0, 1e6),
+            ..CameraInput::default()
+        };
+        fly.update(&down, DT);
+        assert!((fly.pose(1.0).pitch + PITCH_LIMIT).abs() < 1e-6);
+        // Still has a usable right vector looking straight down
+        assert!(fly.pose(1.0).right().is_normalized());
+
+        let mut orbit = OrbitCamera::new(Vec3::ZERO, start());
+        orbit.update(&up, DT);
+        assert!(orbit.pose(1.0).pitch.abs() <= PITCH_LIMIT);
+    }
+
+    #[test]
+    fn scrolling_keeps_the_speed_in_range() {
+        let mut fly = FlyCamera::new(start(), DEFAULT_FLY_SPEED);
+        let scroll = |notches| CameraInput {
+            scroll: notches,
+            ..CameraInput::default()
+        };
+        fly.update(&scroll(2.0), DT);
+        let faster = DEFAULT_FLY_SPEED * SCROLL_STEP * SCROLL_STEP;
+        assert!((fly.speed - faster).abs() < 1e-5);
+        fly.update(&scroll(1000.0), DT);
+        assert!((fly.speed - SPEED_RANGE.1).abs() < 1e-3);
+        fly.update(&scroll(-1000.0), DT);
+        assert!((fly.speed - SPEED_RANGE.0).abs() < 1e-6);
+        assert!((clamp_speed(f32::NAN) - DEFAULT_FLY_SPEED).abs() < 1e-6);
+    }
+}
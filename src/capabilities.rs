@@ -0,0 +1,386 @@
+use std::fmt::Write as _;
+
+use crate::error::ForayError;
+use crate::requirements::DeviceRequirements;
+
+// Picks the graphics API, e.g. WGPU_FORAY_BACKEND=gl for the OpenGL fallback
+pub const BACKEND_VAR: &str = "WGPU_FORAY_BACKEND";
+
+// The backends to try, Vulkan unless the environment says otherwise
+pub fn backends() -> wgpu::Backends {
+    match std::env::var(BACKEND_VAR) {
+        Ok(list) => {
+            let backends = wgpu::Backends::from_comma_list(&list);
+            if backends.is_empty() {
+                log::warn!("{BACKEND_VAR}={list} names no known backend, using Vulkan");
+                wgpu::Backends::VULKAN
+            } else {
+                backends
+            }
+        }
+        Err(_) => wgpu::Backends::VULKAN,
+    }
+}
+
+// Something the app can do better with, but can live without
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Optional {
+    // Line polygon mode, for wireframe views
+    Wireframe,
+    // More than one sample per pixel on the surface format
+    Msaa,
+    PushConstants,
+    // GPU timestamp queries, for per pass timings
+    Timestamps,
+    // Vertex and fragment invocation counts per pass, for overdraw
+    PipelineStatistics,
+    // A float format the accumulator can blend into and filter
+    FloatBlending,
+    // Compute passes, which the bloom effect is made of
+    Compute,
+    // wgpu's default limits rather than the downlevel ones
+    FullLimits,
+    // Fragment shaders writing a storage buffer, for debug_print in debug builds
+    ShaderDebug,
+}
+
+impl Optional {
+    pub const ALL: [Optional; 9] = [
+        Optional::Wireframe,
+        Optional::Msaa,
+        Optional::PushConstants,
+        Optional::Timestamps,
+        Optional::PipelineStatistics,
+        Optional::FloatBlending,
+        Optional::Compute,
+        Optional::FullLimits,
+        Optional::ShaderDebug,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Optional::Wireframe => "wireframe",
+            Optional::Msaa => "msaa",
+            Optional::PushConstants => "push constants",
+            Optional::Timestamps => "timestamps",
+            Optional::PipelineStatistics => "pipeline statistics",
+            Optional::FloatBlending => "float blending",
+            Optional::Compute => "compute",
+            Optional::FullLimits => "full limits",
+            Optional::ShaderDebug => "shader debug",
+        }
+    }
+
+    // What the adapter has to offer for it, as shown in the report
+    pub fn requirement(self) -> &'static str {
+        match self {
+            Optional::Wireframe => "Features::POLYGON_MODE_LINE",
+            Optional::Msaa => "sample count > 1 on the surface format",
+            Optional::PushConstants => "Features::PUSH_CONSTANTS",
+            Optional::Timestamps => "Features::TIMESTAMP_QUERY",
+            Optional::PipelineStatistics => "Features::PIPELINE_STATISTICS_QUERY",
+            Optional::FloatBlending => "blendable + filterable Rgba32Float or Rgba16Float",
+            Optional::Compute => "DownlevelFlags::COMPUTE_SHADERS",
+            Optional::FullLimits => "Limits::default() within the adapter's limits",
+            Optional::ShaderDebug => "DownlevelFlags::FRAGMENT_WRITABLE_STORAGE",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Support {
+    Available,
+    // Works, but with something lesser
+    Fallback(String),
+    Disabled(String),
+}
+
+// What the adapter and surface can do, worked out once before the device is created.
+// Subsystems ask has() instead of poking at features and limits themselves, and every
+// optional thing that isn't fully there comes with a reason in the report
+pub struct Capabilities {
+    pub info: wgpu::AdapterInfo,
+    pub features: wgpu::Features,
+    pub downlevel: wgpu::DownlevelCapabilities,
+    // What the device gets created with, the adapter may allow more
+    pub limits: wgpu::Limits,
+    pub surface_formats: Vec<wgpu::TextureFormat>,
+    // The first sRGB one, or whatever comes first when there's none
+    pub surface_format: wgpu::TextureFormat,
+    pub present_modes: Vec<wgpu::PresentMode>,
+    pub sample_counts: Vec<u32>,
+    // Rgba32Float or Rgba16Float when FloatBlending is there, Rgba8Unorm otherwise
+    pub accumulation_format: wgpu::TextureFormat,
+    // Subsystems that didn't get something optional they asked for, and what
+    pub degraded: Vec<(&'static str, String)>,
+    matrix: Vec<(Optional, Support)>,
+}
+
+// The first of `preference` the surface offers. Without a preference the first sRGB one,
+// shaders write linear colors and let it encode
+pub fn preferred_format(
+    formats: &[wgpu::TextureFormat],
+    preference: &[wgpu::TextureFormat],
+) -> Option<wgpu::TextureFormat> {
+    if preference.is_empty() {
+        return formats
+            .iter()
+            .find(|format| format.is_srgb())
+            .or(formats.first())
+            .copied();
+    }
+    preference
+        .iter()
+        .find(|format| formats.contains(format))
+        .copied()
+}
+
+impl Capabilities {
+    // Err when the adapter lacks something in `requirements` that's required, or the surface
+    // has none of the `formats` preferred (see preferred_format)
+    pub fn new(
+        adapter: &wgpu::Adapter,
+        surface: &wgpu::Surface,
+        requirements: &DeviceRequirements,
+        formats: &[wgpu::TextureFormat],
+    ) -> Result<Self, ForayError> {
+        let info = adapter.get_info();
+        let adapter_features = adapter.features();
+        let downlevel = adapter.get_downlevel_capabilities();
+        let adapter_limits = adapter.limits();
+        let surface_caps = surface.get_capabilities(adapter);
+
+        // Asking for more than the adapter has fails device creation outright
+        let full = wgpu::Limits::default().check_limits(&adapter_limits);
+        let base_limits = if full {
+            wgpu::Limits::default()
+        } else if wgpu::Limits::downlevel_defaults().check_limits(&adapter_limits) {
+            wgpu::Limits::downlevel_defaults()
+        } else {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        }
+        .using_resolution(adapter_limits.clone());
+        let granted = requirements.negotiate(adapter_features, &adapter_limits, base_limits)?;
+        let features = granted.features;
+        let format_features = |format: wgpu::TextureFormat| {
+            if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                adapter.get_texture_format_features(format)
+            } else {
+                format.guaranteed_format_features(features)
+            }
+        };
+
+        let mut matrix = Vec::new();
+        let mut require = |optional: Optional, present: bool, otherwise: Support| {
+            let support = if present {
+                Support::Available
+            } else {
+                otherwise
+            };
+            matrix.push((optional, support));
+        };
+
+        require(
+            Optional::Wireframe,
+            features.contains(wgpu::Features::POLYGON_MODE_LINE),
+            Support::Disabled("no line polygon mode".to_owned()),
+        );
+
+        let surface_format = preferred_format(&surface_caps.formats, formats).ok_or_else(|| {
+            ForayError::UnsupportedSurfaceFormat {
+                wanted: formats.to_vec(),
+                offered: surface_caps.formats.clone(),
+            }
+        })?;
+        let sample_counts = format_features(surface_format)
+            .flags
+            .supported_sample_counts();
+        require(
+            Optional::Msaa,
+            sample_counts.iter().any(|&count| count > 1),
+            Support::Fallback("1 sample per pixel".to_owned()),
+        );
+
+        require(
+            Optional::PushConstants,
+            features.contains(wgpu::Features::PUSH_CONSTANTS),
+            Support::Fallback("uniform buffers".to_owned()),
+        );
+        require(
+            Optional::Timestamps,
+            features.contains(wgpu::Features::TIMESTAMP_QUERY),
+            Support::Disabled("only CPU timings".to_owned()),
+        );
+        require(
+            Optional::PipelineStatistics,
+            features.contains(wgpu::Features::PIPELINE_STATISTICS_QUERY),
+            Support::Disabled("no overdraw counts".to_owned()),
+        );
+
+        let blendable = [
+            wgpu::TextureFormat::Rgba32Float,
+            wgpu::TextureFormat::Rgba16Float,
+        ]
+        .into_iter()
+        .find(|&format| {
+            let format_features = format_features(format);
+            format_features.flags.contains(
+                wgpu::TextureFormatFeatureFlags::BLENDABLE
+                    | wgpu::TextureFormatFeatureFlags::FILTERABLE,
+            ) && format_features.allowed_usages.contains(
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            )
+        });
+        // Blending into a float target that doesn't support it would just give black
+        require(
+            Optional::FloatBlending,
+            blendable.is_some(),
+            Support::Fallback("accumulating in 8 bit, expect banding".to_owned()),
+        );
+
+        require(
+            Optional::Compute,
+            downlevel
+                .flags
+                .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS),
+            Support::Disabled("the bloom effect is left out".to_owned()),
+        );
+
+        let limits = granted.limits;
+        require(
+            Optional::FullLimits,
+            full,
+            Support::Fallback(format!(
+                "downlevel limits, textures up to {}px",
+                limits.max_texture_dimension_2d
+            )),
+        );
+
+        require(
+            Optional::ShaderDebug,
+            downlevel
+                .flags
+                .contains(wgpu::DownlevelFlags::FRAGMENT_WRITABLE_STORAGE)
+                && limits.max_storage_buffers_per_shader_stage > 0,
+            Support::Disabled("debug_print does nothing".to_owned()),
+        );
+
+        Ok(Self {
+            info,
+            features,
+            downlevel,
+            limits,
+            surface_formats: surface_caps.formats,
+            surface_format,
+            present_modes: surface_caps.present_modes,
+            sample_counts,
+            accumulation_format: blendable.unwrap_or(wgpu::TextureFormat::Rgba8Unorm),
+            degraded: granted.degraded,
+            matrix,
+        })
+    }
+
+    pub fn support(&self, optional: Optional) -> &Support {
+        self.matrix
+            .iter()
+            .find(|(entry, _)| *entry == optional)
+            .map(|(_, support)| support)
+            .expect("Every Optional is checked in Capabilities::new")
+    }
+
+    pub fn has(&self, optional: Optional) -> bool {
+        *self.support(optional) == Support::Available
+    }
+
+    // Window sizes past the texture limit can't be configured, the surface stays at the limit
+    pub fn clamp_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let max = self.limits.max_texture_dimension_2d;
+        (width.clamp(1, max), height.clamp(1, max))
+    }
+
+    // One line for the adapter, a warning for everything that had to give
+    pub fn log(&self) {
+        log::info!(
+            "{} on {:?} ({:?})",
+            self.info.name,
+            self.info.backend,
+            self.info.device_type
+        );
+        for (optional, support) in &self.matrix {
+            match support {
+                Support::Available => {}
+                Support::Fallback(reason) => {
+                    log::warn!("{}: falling back to {reason}", optional.name())
+                }
+                Support::Disabled(reason) => log::warn!("{}: disabled, {reason}", optional.name()),
+            }
+        }
+        for (subsystem, reason) in &self.degraded {
+            log::warn!("{subsystem}: degraded, {reason}");
+        }
+    }
+
+    // Everything, for --capabilities
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "Adapter: {} ({:?}, {:?})",
+            self.info.name, self.info.backend, self.info.device_type
+        );
+        let _ = writeln!(
+            report,
+            "Driver: {} {}",
+            self.info.driver, self.info.driver_info
+        );
+        let _ = writeln!(report, "Shader model: {:?}", self.downlevel.shader_model);
+        let _ = writeln!(report, "Granted features: {:?}", self.features);
+        let _ = writeln!(
+            report,
+            "Max texture size: {}, max bind groups: {}, max uniform buffer: {} bytes, push constants: {} bytes",
+            self.limits.max_texture_dimension_2d,
+            self.limits.max_bind_groups,
+            self.limits.max_uniform_buffer_binding_size,
+            self.limits.max_push_constant_size
+        );
+        let _ = writeln!(
+            report,
+            "Surface formats: {:?}, using {:?}",
+            self.surface_formats, self.surface_format
+        );
+        let _ = writeln!(report, "Present modes: {:?}", self.present_modes);
+        let _ = writeln!(report, "Sample counts: {:?}", self.sample_counts);
+        let _ = writeln!(
+            report,
+            "Accumulation format: {:?}",
+            self.accumulation_format
+        );
+        let _ = writeln!(report);
+
+        let width = Optional::ALL
+            .iter()
+            .map(|optional| optional.name().len())
+            .max()
+            .unwrap_or(0);
+        for (optional, support) in &self.matrix {
+            let status = match support {
+                Support::Available => "available".to_owned(),
+                Support::Fallback(reason) => format!("fallback: {reason}"),
+                Support::Disabled(reason) => format!("disabled: {reason}"),
+            };
+            let _ = writeln!(
+                report,
+                "{:width$}  {status}  (needs {})",
+                optional.name(),
+                optional.requirement()
+            );
+        }
+        if !self.degraded.is_empty() {
+            let _ = writeln!(report);
+        }
+        for (subsystem, reason) in &self.degraded {
+            let _ = writeln!(report, "{subsystem} degraded: {reason}");
+        }
+        report
+    }
+}
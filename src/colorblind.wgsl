@@ -0,0 +1,15 @@
+#include "post.wgsl"
+
+struct ColorBlind {
+    // Linear RGB in, linear RGB out, see ColorBlindMode::simulation in effects.rs
+    simulation: mat3x3<f32>,
+};
+
+@group(1) @binding(0) var<uniform> colorblind: ColorBlind;
+
+@fragment
+fn fs_colorblind(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv).rgb;
+    // Saturated colors can land slightly outside the gamut
+    return vec4<f32>(max(colorblind.simulation * color, vec3<f32>(0.0)), 1.0);
+}
@@ -1,3 +1,9 @@
+use crate::error::ForayError;
+use crate::pacing::Interpolate;
+
+// Colors on the CPU side are always sRGB (what a color picker or a hex code gives you).
+// They only get converted to linear at the point they're handed to the GPU.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct RgbaColor(f64, f64, f64, f64);
 
 #[non_exhaustive]
@@ -7,55 +13,489 @@ impl Colors {
     pub const WHITE: RgbaColor = RgbaColor(1., 1., 1., 1.);
 }
 
-impl RgbaColor {
-    pub fn new<R, G, B, A>((r, g, b, a): (R, G, B, A)) -> Option<Self>
-    where
-        R: Into<f64>,
-        G: Into<f64>,
-        B: Into<f64>,
-        A: Into<f64>,
-    {
-        // Shadowing
-        let r = r.into();
-        let g = g.into();
-        let b = b.into();
-        let a = a.into();
-
-        // Confirm
-        let is_invalid = |x: &f64| {
-            if 0.0 > *x || *x > 1.0 {
-                false
-            } else {
-                true
+// What the built-in visuals are drawn with: overlay panels, the console, the inspector,
+// grids and gizmos. The dark one is for the default dark backgrounds, the light one for a
+// white clear color (--theme light, or `theme light` in the console). sRGB like any other
+// RgbaColor, the overlay and the gizmos convert on the way to the GPU
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub text: RgbaColor,
+    // Behind overlay text, translucent so the scene still shows through
+    pub panel: RgbaColor,
+    // Titles, headers and progress
+    pub accent: RgbaColor,
+    pub warning: RgbaColor,
+    pub error: RgbaColor,
+    pub grid_major: RgbaColor,
+    pub grid_minor: RgbaColor,
+    pub axis_x: RgbaColor,
+    pub axis_y: RgbaColor,
+    pub axis_z: RgbaColor,
+    // The picked inspector row, the gizmo handle being dragged
+    pub selection: RgbaColor,
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        name: "dark",
+        text: RgbaColor(0.95, 0.95, 0.95, 1.),
+        panel: RgbaColor(0., 0., 0., 0.65),
+        accent: RgbaColor(0.8, 0.9, 1., 1.),
+        warning: RgbaColor(1., 0.9, 0.54, 1.),
+        error: RgbaColor(1., 0.63, 0.58, 1.),
+        grid_major: RgbaColor(0.5, 0.5, 0.5, 0.8),
+        grid_minor: RgbaColor(0.3, 0.3, 0.3, 0.5),
+        axis_x: RgbaColor(0.9, 0.2, 0.2, 1.),
+        axis_y: RgbaColor(0.2, 0.9, 0.2, 1.),
+        axis_z: RgbaColor(0.2, 0.4, 0.9, 1.),
+        selection: RgbaColor(1., 0.85, 0.1, 1.),
+    };
+
+    pub const LIGHT: Theme = Theme {
+        name: "light",
+        text: RgbaColor(0.1, 0.1, 0.12, 1.),
+        panel: RgbaColor(1., 1., 1., 0.75),
+        accent: RgbaColor(0.1, 0.35, 0.75, 1.),
+        warning: RgbaColor(0.65, 0.42, 0., 1.),
+        error: RgbaColor(0.8, 0.1, 0.1, 1.),
+        grid_major: RgbaColor(0.35, 0.35, 0.4, 0.8),
+        grid_minor: RgbaColor(0.6, 0.6, 0.65, 0.5),
+        axis_x: RgbaColor(0.8, 0.1, 0.1, 1.),
+        axis_y: RgbaColor(0.1, 0.6, 0.1, 1.),
+        axis_z: RgbaColor(0.1, 0.25, 0.8, 1.),
+        selection: RgbaColor(0.95, 0.55, 0., 1.),
+    };
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::DARK, Self::LIGHT]
+            .into_iter()
+            .find(|theme| theme.name == name)
+    }
+
+    // The console's `theme <dark|light>`, the usage when that isn't what it got
+    pub fn command(&mut self, args: &[&str]) -> Result<(), &'static str> {
+        match args {
+            [name] => {
+                *self = Theme::from_name(name).ok_or(THEME_USAGE)?;
+                Ok(())
             }
-        };
+            _ => Err(THEME_USAGE),
+        }
+    }
+}
+
+const THEME_USAGE: &str = "theme <dark|light>";
 
-        // I <3 Functional Programming
-        let proceed = vec![r, g, b, a].iter().any(|x| is_invalid(x));
+impl RgbaColor {
+    pub const fn rgba(r: f64, g: f64, b: f64, a: f64) -> Self {
+        RgbaColor(r, g, b, a)
+    }
+
+    // Alpha scaled by `factor`, for fading something in or out
+    pub fn faded(self, factor: f64) -> Self {
+        RgbaColor(self.0, self.1, self.2, self.3 * factor)
+    }
+
+    // 0xRRGGBB, opaque
+    pub fn from_hex(rgb: u32) -> Self {
+        let channel = |shift: u32| f64::from((rgb >> shift) & 0xff) / 255.0;
+        RgbaColor(channel(16), channel(8), channel(0), 1.0)
+    }
 
-        // Return
-        if proceed {
-            Some(RgbaColor(r.into(), g.into(), b.into(), a.into()))
+    // "#rrggbb", or "#rrggbbaa" when not opaque
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_unorm8_array();
+        let rgb = format!("#{r:02x}{g:02x}{b:02x}");
+        if self.3 < 1.0 {
+            format!("{rgb}{a:02x}")
         } else {
-            None
+            rgb
         }
     }
+
+    // Six or eight hex digits, with or without a leading "#" or "0x"
+    pub fn parse_hex(text: &str) -> Result<Self, ForayError> {
+        let invalid = || ForayError::InvalidHexColor(text.to_owned());
+        let digits = text.trim();
+        let digits = digits
+            .strip_prefix('#')
+            .or_else(|| digits.strip_prefix("0x"))
+            .unwrap_or(digits);
+        if !matches!(digits.len(), 6 | 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let value = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        Ok(if digits.len() == 6 {
+            Self::from_hex(value)
+        } else {
+            let mut color = Self::from_hex(value >> 8);
+            color.3 = f64::from(value & 0xff) / 255.0;
+            color
+        })
+    }
+
+    // Every conversion in and out goes through the ones below, so channel order and the
+    // color space are settled in one place. Arrays are always [r, g, b, a]. The plain ones
+    // keep the sRGB values as they are, the _linear ones decode them for the GPU. Alpha is
+    // never encoded, it comes out the same either way
+
+    // sRGB floats, like the consts on Vertex or a scene item's color. Unchecked like rgba,
+    // out of range channels clamp on the way to unorm8 and go through as they are otherwise
+    pub fn from_f32_array([r, g, b, a]: [f32; 4]) -> Self {
+        RgbaColor(r.into(), g.into(), b.into(), a.into())
+    }
+
+    // sRGB bytes, like an image's pixels
+    pub fn from_unorm8([r, g, b, a]: [u8; 4]) -> Self {
+        let channel = |c: u8| f64::from(c) / 255.0;
+        RgbaColor(channel(r), channel(g), channel(b), channel(a))
+    }
+
+    // For uniforms of shaders that work in sRGB
+    pub fn to_f32_array(self) -> [f32; 4] {
+        [self.0 as f32, self.1 as f32, self.2 as f32, self.3 as f32]
+    }
+
+    // What goes into vertex, instance and uniform data
+    pub fn to_f32_array_linear(self) -> [f32; 4] {
+        let [r, g, b, a] = self.linear_channels();
+        [r as f32, g as f32, b as f32, a as f32]
+    }
+
+    pub fn to_unorm8_array(self) -> [u8; 4] {
+        [self.0, self.1, self.2, self.3].map(unorm8)
+    }
+
+    // For Unorm8x4 attributes. Quantized after decoding, the darkest few steps band
+    pub fn to_unorm8_array_linear(self) -> [u8; 4] {
+        self.linear_channels().map(unorm8)
+    }
+
+    // Clear colors are linear too (the surface is sRGB and encodes on write), so this
+    // converts the same way vertex colors do and a color looks the same either way
+    pub fn to_wgpu_linear(self) -> wgpu::Color {
+        let [r, g, b, a] = self.linear_channels();
+        wgpu::Color { r, g, b, a }
+    }
+
+    fn linear_channels(self) -> [f64; 4] {
+        [
+            srgb_to_linear(self.0),
+            srgb_to_linear(self.1),
+            srgb_to_linear(self.2),
+            self.3,
+        ]
+    }
 }
 
-impl RgbaColor {
-    pub fn red(&self) -> f64 {
-        self.0
+// `count` hues evenly around a cosine color wheel, in order. The channels always add up
+// to the same, so none of them comes out much darker than the others
+pub fn palette(count: usize) -> Vec<RgbaColor> {
+    (0..count)
+        .map(|i| {
+            let hue = i as f64 / count as f64;
+            let channel = |offset: f64| 0.5 + 0.5 * (std::f64::consts::TAU * (hue + offset)).cos();
+            RgbaColor(channel(0.0), channel(1.0 / 3.0), channel(2.0 / 3.0), 1.0)
+        })
+        .collect()
+}
+
+fn unorm8(c: f64) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// The sRGB transfer function's constants. dither.wgsl, grade.wgsl and overlay.wgsl have
+// their own to_srgb and to_linear with these written out, a test keeps them the same
+const SRGB_SLOPE: f64 = 12.92;
+const SRGB_SCALE: f64 = 1.055;
+const SRGB_OFFSET: f64 = 0.055;
+const SRGB_GAMMA: f64 = 2.4;
+// Where the linear segment ends, on the sRGB side and on the linear side
+const SRGB_KNEE: f64 = 0.04045;
+const LINEAR_KNEE: f64 = 0.003_130_8;
+
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= SRGB_KNEE {
+        c / SRGB_SLOPE
+    } else {
+        ((c + SRGB_OFFSET) / SRGB_SCALE).powf(SRGB_GAMMA)
+    }
+}
+
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= LINEAR_KNEE {
+        c * SRGB_SLOPE
+    } else {
+        SRGB_SCALE * c.powf(1.0 / SRGB_GAMMA) - SRGB_OFFSET
+    }
+}
+
+// Blends the sRGB values, what a gradient between two picked colors looks like
+impl Interpolate for RgbaColor {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        let alpha = f64::from(alpha);
+        let lerp = |from: f64, to: f64| from + (to - from) * alpha;
+        RgbaColor(
+            lerp(self.0, next.0),
+            lerp(self.1, next.1),
+            lerp(self.2, next.2),
+            lerp(self.3, next.3),
+        )
+    }
+}
+
+// Color channels scaled by alpha, what a premultiplied surface or blend expects. Works on
+// the linear values, after the sRGB conversion, since that's where blending happens
+pub fn premultiply(color: wgpu::Color) -> wgpu::Color {
+    wgpu::Color {
+        r: color.r * color.a,
+        g: color.g * color.a,
+        b: color.b * color.a,
+        a: color.a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn srgb_to_linear_reference_points() {
+        assert!(close(srgb_to_linear(0.0), 0.0));
+        assert!(close(srgb_to_linear(1.0), 1.0));
+        // The usual 0.5 mid-gray, about a fifth of the light
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-6);
+        // Linear segment up to the knee
+        assert!(close(srgb_to_linear(0.04045), 0.04045 / 12.92));
+        assert!(close(srgb_to_linear(0.02), 0.02 / 12.92));
+    }
+
+    #[test]
+    fn the_knee_is_continuous() {
+        let knee = 0.04045;
+        let below = srgb_to_linear(knee - 1e-9);
+        let above = srgb_to_linear(knee + 1e-9);
+        assert!((above - below).abs() < 1e-7, "{below} vs {above}");
+        let knee = srgb_to_linear(knee);
+        let below = linear_to_srgb(knee - 1e-9);
+        let above = linear_to_srgb(knee + 1e-9);
+        assert!((above - below).abs() < 1e-6, "{below} vs {above}");
+    }
+
+    #[test]
+    fn linear_to_srgb_inverts_it() {
+        for step in 0..=1000 {
+            let c = f64::from(step) / 1000.0;
+            let back = linear_to_srgb(srgb_to_linear(c));
+            assert!((back - c).abs() < 1e-6, "{c} came back as {back}");
+        }
+        assert!(close(linear_to_srgb(0.0), 0.0));
+        assert!(close(linear_to_srgb(1.0), 1.0));
+    }
+
+    #[test]
+    fn hex_parses_and_prints_back() {
+        for text in ["#000000", "#ff8000", "#1a2b3c", "#12345678"] {
+            assert_eq!(RgbaColor::parse_hex(text).unwrap().to_hex(), text);
+        }
+        // Prefixes, case and surrounding space are all fine
+        let orange = RgbaColor::from_hex(0xff_8000);
+        for text in ["ff8000", "0xff8000", "#FF8000", " #ff8000\n", "#ff8000ff"] {
+            assert_eq!(RgbaColor::parse_hex(text).unwrap(), orange, "{text}");
+        }
+        assert_eq!(orange.to_unorm8_array(), [255, 128, 0, 255]);
+        for text in [
+            "", "#", "#fff", "#ff800", "#ff80000", "#gg8000", "#+f8000", "0x",
+        ] {
+            assert!(
+                matches!(RgbaColor::parse_hex(text), Err(ForayError::InvalidHexColor(t)) if t == text),
+                "{text}"
+            );
+        }
+    }
+
+    // Vertex colors and clears go through the same conversion, the point of keeping both
+    // in sRGB on the CPU
+    #[test]
+    fn clears_and_vertices_linearize_alike() {
+        let purple = RgbaColor::rgba(0.5, 0.0, 0.5, 0.75);
+        let clear = purple.to_wgpu_linear();
+        let vertex = purple.to_f32_array_linear();
+        let wide = [clear.r, clear.g, clear.b, clear.a];
+        for (v, c) in vertex.into_iter().zip(wide) {
+            assert!((f64::from(v) - c).abs() < 1e-6);
+        }
+        // Alpha is never encoded
+        assert!(close(f64::from(vertex[3]), 0.75));
+        assert!((wide[0] - 0.214_041).abs() < 1e-6);
+    }
+
+    // A different value in every channel, so a swapped pair shows up
+    fn distinct(step: u8) -> [u8; 4] {
+        [step, 255 - step, step / 2, 255 - step / 3]
+    }
+
+    #[test]
+    fn unorm8_and_f32_round_trip_in_channel_order() {
+        for step in 0..=255 {
+            let bytes = distinct(step);
+            let color = RgbaColor::from_unorm8(bytes);
+            assert_eq!(color.to_unorm8_array(), bytes);
+            let floats = color.to_f32_array();
+            for (float, byte) in floats.into_iter().zip(bytes) {
+                assert!((float - f32::from(byte) / 255.0).abs() < 1e-6, "{bytes:?}");
+            }
+            let back = RgbaColor::from_f32_array(floats);
+            assert_eq!(back.to_unorm8_array(), bytes);
+        }
+        // Hex is written red first
+        assert_eq!(
+            RgbaColor::from_hex(0x11_2233).to_unorm8_array(),
+            [0x11, 0x22, 0x33, 255]
+        );
+    }
+
+    #[test]
+    fn linear_conversions_keep_channel_order() {
+        for step in 0..=255 {
+            let bytes = distinct(step);
+            let color = RgbaColor::from_unorm8(bytes);
+            let linear = color.to_f32_array_linear();
+            let wgpu::Color { r, g, b, a } = color.to_wgpu_linear();
+            let srgb = color.to_f32_array();
+            for channel in 0..3 {
+                let expected = srgb_to_linear(f64::from(bytes[channel]) / 255.0);
+                assert!(
+                    (f64::from(linear[channel]) - expected).abs() < 1e-6,
+                    "{bytes:?}"
+                );
+                // And back to the sRGB it came from
+                let back = linear_to_srgb(f64::from(linear[channel]));
+                assert!((back - f64::from(srgb[channel])).abs() < 1e-5, "{bytes:?}");
+            }
+            for (wide, narrow) in [r, g, b, a].into_iter().zip(linear) {
+                assert!((wide - f64::from(narrow)).abs() < 1e-6, "{bytes:?}");
+            }
+            // Alpha is never encoded
+            assert!((linear[3] - srgb[3]).abs() < 1e-7);
+            assert_eq!(color.to_unorm8_array_linear()[3], bytes[3]);
+            // The linear bytes are the linear floats quantized, channel for channel
+            let quantized = linear.map(|c| unorm8(f64::from(c)));
+            assert_eq!(color.to_unorm8_array_linear(), quantized);
+        }
     }
 
-    pub fn green(&self) -> f64 {
-        self.1
+    // Every color a theme has, by name
+    fn slots(theme: &Theme) -> [(&'static str, RgbaColor); 11] {
+        [
+            ("text", theme.text),
+            ("panel", theme.panel),
+            ("accent", theme.accent),
+            ("warning", theme.warning),
+            ("error", theme.error),
+            ("grid_major", theme.grid_major),
+            ("grid_minor", theme.grid_minor),
+            ("axis_x", theme.axis_x),
+            ("axis_y", theme.axis_y),
+            ("axis_z", theme.axis_z),
+            ("selection", theme.selection),
+        ]
     }
 
-    pub fn blue(&self) -> f64 {
-        self.2
+    fn luminance(color: RgbaColor) -> f64 {
+        0.2126 * srgb_to_linear(color.0)
+            + 0.7152 * srgb_to_linear(color.1)
+            + 0.0722 * srgb_to_linear(color.2)
     }
 
-    pub fn alpha(&self) -> f64 {
-        self.3
+    #[test]
+    fn both_themes_fill_every_slot() {
+        for theme in [Theme::DARK, Theme::LIGHT] {
+            for (slot, color) in slots(&theme) {
+                let RgbaColor(r, g, b, a) = color;
+                assert!(
+                    [r, g, b, a].iter().all(|c| (0.0..=1.0).contains(c)),
+                    "{} {slot} is out of range: {color:?}",
+                    theme.name
+                );
+                assert!(a > 0.0, "{} {slot} is invisible", theme.name);
+            }
+            // What's written on panels reads on them
+            for (slot, color) in [
+                ("text", theme.text),
+                ("accent", theme.accent),
+                ("error", theme.error),
+            ] {
+                let contrast = (luminance(color) - luminance(theme.panel)).abs();
+                assert!(contrast > 0.3, "{} {slot} is lost on its panel", theme.name);
+            }
+            assert_eq!(Theme::from_name(theme.name), Some(theme));
+        }
+        // Light isn't Dark under another name
+        let differ = slots(&Theme::DARK)
+            .iter()
+            .zip(slots(&Theme::LIGHT))
+            .filter(|((_, dark), (_, light))| dark != light)
+            .count();
+        assert_eq!(differ, 11);
+    }
+
+    #[test]
+    fn the_theme_command_switches_themes() {
+        let mut theme = Theme::DARK;
+        assert_eq!(theme.command(&["light"]), Ok(()));
+        assert_eq!(theme, Theme::LIGHT);
+        assert_eq!(theme.command(&["dark"]), Ok(()));
+        assert_eq!(theme, Theme::DARK);
+        // Anything else keeps the theme it had
+        for args in [&["sepia"][..], &[], &["light", "dark"]] {
+            assert_eq!(theme.command(args), Err("theme <dark|light>"));
+            assert_eq!(theme, Theme::DARK);
+        }
+    }
+
+    // Every number in `function`'s body in `source`, but the 0s and 1s of clamps and
+    // reciprocals
+    fn constants_in(source: &str, function: &str) -> Vec<f64> {
+        let start = source
+            .find(&format!("fn {function}("))
+            .unwrap_or_else(|| panic!("no {function}"));
+        let body = &source[start..];
+        let body = &body[..body.find("\n}").unwrap()];
+        let mut constants: Vec<f64> = body
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .filter(|token| token.contains('.') && !matches!(*token, "0.0" | "1.0"))
+            .map(|token| token.parse().unwrap())
+            .collect();
+        constants.sort_by(f64::total_cmp);
+        constants.dedup();
+        constants
+    }
+
+    #[test]
+    fn the_shaders_transfer_functions_match() {
+        let mut encode = vec![SRGB_SLOPE, SRGB_SCALE, SRGB_OFFSET, SRGB_GAMMA, LINEAR_KNEE];
+        let mut decode = vec![SRGB_SLOPE, SRGB_SCALE, SRGB_OFFSET, SRGB_GAMMA, SRGB_KNEE];
+        encode.sort_by(f64::total_cmp);
+        decode.sort_by(f64::total_cmp);
+        for (name, source, has_decode) in [
+            ("dither.wgsl", include_str!("dither.wgsl"), true),
+            ("grade.wgsl", include_str!("grade.wgsl"), true),
+            ("overlay.wgsl", include_str!("overlay.wgsl"), false),
+        ] {
+            assert_eq!(constants_in(source, "to_srgb"), encode, "{name} to_srgb");
+            if has_decode {
+                assert_eq!(
+                    constants_in(source, "to_linear"),
+                    decode,
+                    "{name} to_linear"
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,412 @@
+use glam::Vec2;
+
+use crate::camera2d::ResizePolicy;
+use crate::colors::RgbaColor;
+use crate::effects::ColorBlindMode;
+use crate::error::ForayError;
+use crate::frame::Background;
+use crate::geometry::Grid;
+use crate::mesh_stream::{self, CacheSource, ChunkSource, CircleSource};
+use crate::scene::Scene;
+use crate::sprites::TilePolicy;
+use crate::{camera3d, screenshot, tilemap, State, Vertex, WindowRequest};
+#[cfg(feature = "remote")]
+use crate::{log_sink, remote};
+
+// The streamed circle's vertices, inside the pentagon's clip space and shaded around the
+// rim so it's easy to see how far it has landed
+fn circle_vertex(point: Vec2, around: f32) -> Vertex {
+    let point = point * 0.9;
+    Vertex {
+        position: [point.x, point.y, 0.0],
+        color: [around, 0.3, 1.0 - around],
+    }
+}
+
+fn circle_source(segments: u32) -> Box<dyn ChunkSource> {
+    Box::new(CircleSource::new(segments, circle_vertex))
+}
+
+// The circle written out for `stream load`, a chunk at a time like streaming it
+fn save_circle_cache(path: &std::path::Path, segments: u32) {
+    match mesh_stream::write_cache(path, &mut CircleSource::new(segments, circle_vertex)) {
+        Ok(bytes) => println!(
+            "Wrote a {segments} segment circle to {} ({:.1} MB)",
+            path.display(),
+            bytes as f64 / (1024.0 * 1024.0)
+        ),
+        Err(e) => log::error!("{e}"),
+    }
+}
+
+// The console's commands, each handed the words after its name by run_command
+impl State {
+    // A line typed into the console. Results are printed, problems logged so they show up
+    // in the console itself
+    pub fn run_command(&mut self, command: &str) {
+        println!("{command}");
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let Some((&name, args)) = words.split_first() else {
+            return;
+        };
+        match name {
+            "override" => self.override_command(args),
+            "colorblind" => match args {
+                [mode] => match ColorBlindMode::parse(mode) {
+                    Some(mode) => self.set_color_blind(mode),
+                    None => log::warn!("Usage: colorblind <off|deut|prot|trit>"),
+                },
+                _ => log::warn!("Usage: colorblind <off|deut|prot|trit>"),
+            },
+            "theme" => match self.overlay.theme.command(args) {
+                Ok(()) => println!("Theme {}", self.overlay.theme.name),
+                Err(usage) => log::warn!("Usage: {usage}"),
+            },
+            "scene" => self.scene_command(args),
+            "save" | "undo" | "redo" => self.edit_command(name, args),
+            "sprites" => self.sprites_command(args),
+            "tiles" => self.tiles_command(args),
+            "stream" => self.stream_command(args),
+            "camera" | "grid" | "msaa" | "prepass" => self.deferred_command(name, args),
+            #[cfg(feature = "gltf")]
+            "gltf" => self.gltf_command(args),
+            "resize" | "clear" | "opacity" | "clickthrough" => self.window_command(name, args),
+            "screenshot" | "dump_frame" | "supersample" => self.capture_command(name, args),
+            "stats" => {
+                for line in self.stats.lines() {
+                    println!("{line}");
+                }
+            }
+            #[cfg(debug_assertions)]
+            "debug" => match &self.debug_channel {
+                Some(debug_channel) if debug_channel.latest.is_empty() => {
+                    println!("Nothing from debug_print yet");
+                }
+                Some(debug_channel) => {
+                    for line in debug_channel.lines() {
+                        println!("{line}");
+                    }
+                }
+                None => log::warn!("No debug channel, debug_print does nothing on this GPU"),
+            },
+            other => log::warn!("Unknown command \"{other}\""),
+        }
+    }
+
+    // A command from --remote, run like one typed into the console. What it logs comes back
+    // as the error, `stats` answers with the stats lines and `screenshot` with the file once
+    // the next frame is done
+    #[cfg(feature = "remote")]
+    pub fn run_remote(&mut self, command: remote::RemoteCommand) {
+        let text = command.command.clone();
+        let problems = log_sink::capture(|| self.run_command(&text));
+        if !problems.is_empty() {
+            command.answer(Err(problems.join("\n")));
+            return;
+        }
+        match text.split_whitespace().next() {
+            Some("stats") => command.answer(Ok(remote::Answer {
+                output: self.stats.lines(),
+                ..remote::Answer::default()
+            })),
+            Some("screenshot") => self.remote_screenshots.push(command),
+            _ => command.answer(Ok(remote::Answer::default())),
+        }
+    }
+
+    // `override <pipeline> <name> <value>`, a pipeline's override constant
+    fn override_command(&mut self, args: &[&str]) {
+        let [pipeline, constant, value] = args else {
+            log::warn!("Usage: override <pipeline> <name> <value>");
+            return;
+        };
+        let Ok(value) = value.parse::<f64>() else {
+            log::warn!("\"{value}\" isn't a number");
+            return;
+        };
+        match self
+            .render_pipelines
+            .set_override(&self.device, pipeline, constant, value)
+        {
+            Ok(()) => println!("{pipeline}: {constant} = {value}"),
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    fn scene_command(&mut self, args: &[&str]) {
+        let [name] = args else {
+            log::warn!("Usage: scene <starter|instancing_ring|bouncing_pentagons|stress>");
+            return;
+        };
+        match Scene::named(name, &self.stress) {
+            Some(scene) => {
+                self.set_scene(name, scene);
+                self.show_primitives = true;
+                println!("Scene {name}");
+            }
+            None => log::warn!("No built-in scene named \"{name}\""),
+        }
+    }
+
+    // `save [path]` (later Ctrl+S saves go to the same file), `undo` and `redo`
+    fn edit_command(&mut self, name: &str, args: &[&str]) {
+        match (name, args) {
+            ("save", []) => self.save_scene(),
+            ("save", [path]) => {
+                self.scene_path = path.into();
+                self.save_scene();
+            }
+            ("save", _) => log::warn!("Usage: save [path]"),
+            ("undo", []) => self.undo(),
+            ("redo", []) => self.redo(),
+            _ => log::warn!("Usage: {name}"),
+        }
+    }
+
+    // `sprites <count> [letterbox|reject]`
+    fn sprites_command(&mut self, args: &[&str]) {
+        const USAGE: &str = "Usage: sprites <count> [letterbox|reject]";
+        let Some((count, policy)) = args.split_first() else {
+            log::warn!("{USAGE}");
+            return;
+        };
+        let policy = match policy {
+            [] | ["letterbox"] => TilePolicy::Letterbox,
+            ["reject"] => TilePolicy::Reject,
+            _ => {
+                log::warn!("{USAGE}");
+                return;
+            }
+        };
+        match count.parse() {
+            Ok(count) => self.build_sprite_stress(count, policy),
+            Err(_) => log::warn!("{USAGE}"),
+        }
+    }
+
+    // `tiles stress [side]`, `tiles load <path>` and `tiles brush <index|off>`
+    fn tiles_command(&mut self, args: &[&str]) {
+        match args {
+            ["stress"] => self.build_tile_stress(tilemap::STRESS_SIDE),
+            ["stress", side] => match side.parse() {
+                Ok(side) if side > 0 => self.build_tile_stress(side),
+                _ => log::warn!("Usage: tiles stress [side]"),
+            },
+            ["load", path] => match self.load_tiles(std::path::Path::new(path)) {
+                Ok(tiles) => println!("Loaded {tiles} tiles from {path}, Y shows the map"),
+                Err(e) => log::error!("{e}"),
+            },
+            ["brush", "off"] => {
+                self.tile_brush = None;
+                println!("Clicks in the tile view don't paint");
+            }
+            ["brush", index] => match (index.parse::<u32>(), &self.tile_map) {
+                (Ok(index), Some(map)) if (index as usize) < map.tile_count() => {
+                    self.tile_brush = Some(index);
+                    println!("Painting tile {index}, Shift+click clears");
+                }
+                (Ok(index), Some(map)) => log::warn!(
+                    "{}",
+                    ForayError::TileIndex {
+                        map: map.name.clone(),
+                        index,
+                        tiles: map.tile_count(),
+                    }
+                ),
+                (Ok(_), None) => log::warn!("No tile map yet, Y or tiles stress builds one"),
+                (Err(_), _) => log::warn!("Usage: tiles brush <index|off>"),
+            },
+            _ => log::warn!("Usage: tiles <stress [side]|load <path>|brush <index|off>>"),
+        }
+    }
+
+    // Mesh streams: the generated circle, a cache file of one, the upload budget
+    fn stream_command(&mut self, args: &[&str]) {
+        const SEGMENTS: &str = "segments, 3 or more";
+        match args {
+            ["circle"] => self.start_stream("Circle", circle_source(mesh_stream::STRESS_SEGMENTS)),
+            ["circle", segments] => match segments.parse() {
+                Ok(segments) if segments >= 3 => {
+                    self.start_stream("Circle", circle_source(segments));
+                }
+                _ => log::warn!("Usage: stream circle [{SEGMENTS}]"),
+            },
+            ["save", path] => {
+                save_circle_cache(std::path::Path::new(path), mesh_stream::STRESS_SEGMENTS);
+            }
+            ["save", path, segments] => match segments.parse() {
+                Ok(segments) if segments >= 3 => {
+                    save_circle_cache(std::path::Path::new(path), segments);
+                }
+                _ => log::warn!("Usage: stream save <path> [{SEGMENTS}]"),
+            },
+            ["load", path] => match CacheSource::open(std::path::Path::new(path)) {
+                Ok(source) => self.start_stream(path, Box::new(source)),
+                Err(e) => log::error!("{e}"),
+            },
+            ["budget", megabytes] => match megabytes.parse::<f64>() {
+                Ok(megabytes) if megabytes > 0.0 => {
+                    self.stream_budget = (megabytes * 1024.0 * 1024.0) as u64;
+                    if let Some(stream) = &mut self.mesh_stream {
+                        stream.budget = self.stream_budget;
+                    }
+                    println!("Mesh streams upload {megabytes} MB a frame");
+                }
+                _ => log::warn!("Usage: stream budget <MB a frame>"),
+            },
+            ["off"] => {
+                if let Some(stream) = self.mesh_stream.take() {
+                    println!("Dropped {}", stream.readout());
+                }
+            }
+            _ => log::warn!(
+                "Usage: stream <circle [segments]|save <path> [segments]|load <path>|budget <MB>|off>"
+            ),
+        }
+    }
+
+    // The deferred view's camera, grid, MSAA and depth pre-pass
+    fn deferred_command(&mut self, name: &str, args: &[&str]) {
+        match (name, args) {
+            ("camera", []) => println!("Camera {}", self.deferred.camera.name()),
+            ("camera", ["orbit"]) => self.set_camera3d(None),
+            ("camera", ["fly"]) => self.set_camera3d(Some(camera3d::DEFAULT_SMOOTHING)),
+            ("camera", ["fly", rotation, translation]) => {
+                match (rotation.parse::<f32>(), translation.parse::<f32>()) {
+                    (Ok(rotation), Ok(translation)) if rotation >= 0.0 && translation >= 0.0 => {
+                        self.set_camera3d(Some((rotation, translation)));
+                    }
+                    _ => log::warn!("Smoothing is seconds, 0 or more"),
+                }
+            }
+            ("camera", _) => {
+                log::warn!(
+                    "Usage: camera <orbit|fly> [rotation smoothing] [translation smoothing]"
+                );
+            }
+            ("grid", _) => match Grid::command(args) {
+                Ok(grid) => {
+                    self.deferred.grid = grid;
+                    println!(
+                        "Grid {} lines per side, {} apart",
+                        grid.lines_per_side, grid.spacing
+                    );
+                }
+                Err(e) => log::warn!("{e}"),
+            },
+            ("msaa", []) => println!(
+                "Deferred view at {} sample(s) per pixel",
+                self.deferred.samples()
+            ),
+            ("msaa", [samples]) => match samples.parse() {
+                Ok(samples) => {
+                    match self
+                        .deferred
+                        .set_samples(&self.device, &mut self.targets, samples)
+                    {
+                        Ok(()) => println!("Deferred view at {samples} sample(s) per pixel"),
+                        Err(e) => log::warn!("{e}"),
+                    }
+                }
+                Err(_) => log::warn!("Usage: msaa [samples]"),
+            },
+            ("msaa", _) => log::warn!("Usage: msaa [samples]"),
+            ("prepass", _) => {
+                self.deferred.depth_prepass = !self.deferred.depth_prepass;
+                println!(
+                    "Depth pre-pass {}",
+                    if self.deferred.depth_prepass {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                );
+            }
+            _ => {}
+        }
+    }
+
+    // `gltf <path.gltf|path.glb|off>`, into the deferred view
+    #[cfg(feature = "gltf")]
+    fn gltf_command(&mut self, args: &[&str]) {
+        match args {
+            ["off"] => {
+                self.deferred.clear_import();
+                println!("Deferred view back to its own meshes");
+            }
+            [path] => {
+                let path = std::path::Path::new(path);
+                match self
+                    .deferred
+                    .import_gltf(&self.device, &self.queue, &self.memory, path)
+                {
+                    Ok(items) => {
+                        self.deferred.active = true;
+                        println!("Imported {items} draw items from {}", path.display());
+                    }
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+            _ => log::warn!("Usage: gltf <path.gltf|path.glb|off>"),
+        }
+    }
+
+    // How the window shows what's drawn: the resize policy, the clear color, opacity and
+    // click-through
+    fn window_command(&mut self, name: &str, args: &[&str]) {
+        match (name, args) {
+            ("resize", [policy]) => {
+                let window = (self.config.width, self.config.height);
+                match ResizePolicy::parse(policy, window) {
+                    Some(resize) => {
+                        self.camera2d.resize = resize;
+                        println!("Resize policy {policy}, at {}x{}", window.0, window.1);
+                    }
+                    None => log::warn!("Usage: resize <extend|stretch|letterbox>"),
+                }
+            }
+            ("resize", _) => log::warn!("Usage: resize <extend|stretch|letterbox>"),
+            ("clear", ["off"]) => {
+                self.background_override = None;
+                println!("Clear color per view");
+            }
+            ("clear", [hex]) => match RgbaColor::parse_hex(hex) {
+                Ok(color) => {
+                    self.background_override = Some(Background::Clear(color.to_wgpu_linear()));
+                    println!("Clear color {}", color.to_hex());
+                }
+                Err(e) => log::warn!("{e}"),
+            },
+            ("clear", _) => log::warn!("Usage: clear <#rrggbb|off>"),
+            ("opacity", [opacity]) => match opacity.parse() {
+                Ok(opacity) => self.window_requests.push(WindowRequest::Opacity(opacity)),
+                Err(_) => log::warn!("Usage: opacity <0..1>"),
+            },
+            ("opacity", _) => log::warn!("Usage: opacity <0..1>"),
+            ("clickthrough", []) => {
+                let enabled = !self.transparency.click_through;
+                self.window_requests
+                    .push(WindowRequest::ClickThrough(enabled));
+            }
+            _ => log::warn!("Usage: {name}"),
+        }
+    }
+
+    // `screenshot`, `dump_frame` and `supersample`, each with an optional path
+    fn capture_command(&mut self, name: &str, args: &[&str]) {
+        let path = match args {
+            [] if name == "dump_frame" => screenshot::timestamped("frame", "json"),
+            [] => screenshot::default_path(),
+            [path] => path.into(),
+            _ => {
+                log::warn!("Usage: {name} [path]");
+                return;
+            }
+        };
+        match name {
+            "dump_frame" => self.dump_frame(path),
+            "supersample" => self.take_supersampled(path),
+            _ => self.take_screenshot(path),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use glfw::{Action, Key, WindowEvent};
+
+use crate::log_sink;
+use crate::overlay::DebugOverlay;
+use crate::text_input::TextInput;
+
+const VISIBLE_LINES: usize = 12;
+const PROMPT: &str = "> ";
+// Lines per notch of the mouse wheel
+const SCROLL_SPEED: f32 = 3.0;
+
+// The whole warning and error history in a panel across the top, where the overlay's log
+// lines only show the last few for a moment. The wheel scrolls back through it a fraction
+// of a line at a time, lines half out of the panel are clipped at its edge. While it's open
+// typing goes into a command line at the bottom, Enter hands it to State::run_command
+pub struct Console {
+    pub enabled: bool,
+    pub input: TextInput,
+    // Lines up from the newest, 0 follows new lines as they come in
+    scroll: f32,
+    // Where it was last queued, in physical pixels
+    rect: Option<(f32, f32, f32, f32)>,
+}
+
+// What the console made of a window event
+pub enum ConsoleInput {
+    // Not for the console, the loop handles it as usual
+    Passed,
+    // A key for the command line that didn't change it
+    Swallowed,
+    // Typed into, edited or closed, the panel needs drawing again
+    Changed,
+    // Enter on a line, for State::run_command
+    Command(String),
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            input: TextInput::new(),
+            scroll: 0.0,
+            rect: None,
+        }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        self.enabled
+            && self.rect.is_some_and(|(left, top, width, height)| {
+                x >= left && x < left + width && y >= top && y < top + height
+            })
+    }
+
+    // Positive `notches` scroll back in time. Kept past the oldest line only until the
+    // next queue, which knows how many lines there are
+    pub fn scroll(&mut self, notches: f32) {
+        self.scroll = (self.scroll + notches * SCROLL_SPEED).max(0.0);
+    }
+
+    // The key that opens the console types its own character too, that one stays out
+    fn type_char(&mut self, c: char) {
+        if c != '`' && !c.is_control() {
+            self.input.insert(c);
+        }
+    }
+
+    // While it's open every key is for the command line, only ` goes on to close it
+    pub fn handle(&mut self, event: &WindowEvent) -> ConsoleInput {
+        if !self.enabled {
+            return ConsoleInput::Passed;
+        }
+        match *event {
+            WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                self.enabled = false;
+                ConsoleInput::Changed
+            }
+            WindowEvent::Char(c) => {
+                self.type_char(c);
+                ConsoleInput::Changed
+            }
+            WindowEvent::Key(Key::Enter, _, Action::Press, _) => match self.submit() {
+                Some(command) => ConsoleInput::Command(command),
+                None => ConsoleInput::Changed,
+            },
+            WindowEvent::Key(key, _, Action::Press | Action::Repeat, _) if self.edit(key) => {
+                ConsoleInput::Changed
+            }
+            WindowEvent::Key(Key::GraveAccent, ..) => ConsoleInput::Passed,
+            WindowEvent::Key(..) => ConsoleInput::Swallowed,
+            _ => ConsoleInput::Passed,
+        }
+    }
+
+    // Moves the command line's cursor or deletes around it, false for any other key
+    fn edit(&mut self, key: Key) -> bool {
+        match key {
+            Key::Backspace => self.input.backspace(),
+            Key::Delete => self.input.delete(),
+            Key::Left => self.input.left(),
+            Key::Right => self.input.right(),
+            Key::Home => self.input.home(),
+            Key::End => self.input.end(),
+            _ => return false,
+        }
+        true
+    }
+
+    // The command typed so far, clearing the line. None for an empty line
+    pub fn submit(&mut self) -> Option<String> {
+        let line = self.input.take();
+        let line = line.trim();
+        (!line.is_empty()).then(|| line.to_owned())
+    }
+
+    pub fn queue(&mut self, overlay: &mut DebugOverlay) {
+        let records = log_sink::history();
+        let (_, line_height) = overlay.measure("#");
+        let margin = overlay.logical(4.0);
+        let inset = overlay.logical(8.0);
+        let (screen_width, _) = overlay.screen();
+        let title = format!(
+            "Log, {} of at most {} lines",
+            records.len(),
+            log_sink::HISTORY
+        );
+        let panel = (
+            inset,
+            inset,
+            (screen_width - 2.0 * inset).max(0.0),
+            (VISIBLE_LINES + 2) as f32 * line_height + 4.0 * margin,
+        );
+        self.rect = Some(panel);
+        let max_scroll = records.len().saturating_sub(VISIBLE_LINES) as f32;
+        self.scroll = self.scroll.min(max_scroll);
+
+        overlay.push_clip(panel);
+        let (x, y, width, height) = panel;
+        let theme = overlay.theme;
+        overlay.rect(panel, theme.panel);
+        overlay.text((x + margin, y + margin), theme.accent, &title);
+
+        // The history itself only shows below the title, inside the panel's own clip
+        let body_top = y + 2.0 * margin + line_height;
+        let prompt_y = y + height - margin - line_height;
+        let body = (x, body_top, width, prompt_y - margin - body_top);
+        overlay.rect(body, theme.panel.faded(0.7));
+        overlay.push_clip(body);
+        let bottom = body.1 + body.3;
+        for (age, record) in records.iter().rev().enumerate() {
+            let line_y = bottom - (age as f32 + 1.0 - self.scroll) * line_height;
+            if line_y >= bottom {
+                continue;
+            }
+            if line_y + line_height <= body.1 {
+                break;
+            }
+            let color = match record.level {
+                log::Level::Error => theme.error,
+                log::Level::Warn => theme.warning,
+                _ => theme.text,
+            };
+            overlay.text((x + margin, line_y), color, &record.line());
+        }
+        overlay.pop_clip();
+        let (before, _) = self.input.split();
+        let prompt = format!("{PROMPT}{before}");
+        let (cursor_x, _) = overlay.measure(&prompt);
+        overlay.text(
+            (x + margin, prompt_y),
+            theme.text,
+            &format!("{PROMPT}{}", self.input.as_str()),
+        );
+        overlay.rect(
+            (
+                x + margin + cursor_x,
+                prompt_y,
+                overlay.logical(1.0),
+                line_height,
+            ),
+            theme.text,
+        );
+        overlay.pop_clip();
+    }
+}
@@ -0,0 +1,210 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, PoisonError, TryLockError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::buffer_pool::BufferPool;
+use crate::image_file;
+use crate::log_sink;
+use crate::memory::GpuMemoryTracker;
+use crate::screenshot::Readback;
+use crate::stats;
+
+// Frame --crash-test panics on, far enough in that there's a frame and some stats to save
+pub const TEST_FRAME: u64 = 10;
+// Log records in the bundle, the newest ones
+const LOG_RECORDS: usize = 100;
+// The screenshot gets this long in all, after that the bundle is written without it
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(3);
+
+// What the frame loop keeps up to date for the hook. Plain strings, so the hook has nothing
+// left to ask of a State that's being unwound
+#[derive(Default)]
+struct CrashContext {
+    capabilities: String,
+    stats: Vec<String>,
+    scene: String,
+    assets: Vec<String>,
+    gpu: Option<(wgpu::Device, wgpu::Queue)>,
+    // The surface texture of the frame being recorded, None between frames
+    frame: Option<wgpu::Texture>,
+}
+
+static CONTEXT: Mutex<Option<CrashContext>> = Mutex::new(None);
+// Set while the bundle is written, a panic in there doesn't start another one
+static WRITING: AtomicBool = AtomicBool::new(false);
+
+// Once, first thing. Panics on the main thread write a bundle to the temp directory after
+// the usual message: the panic and a backtrace, the capabilities report, the last log
+// records, the frame stats, the scene and its assets, and when the GPU still answers a
+// screenshot of the frame that was being drawn. Other threads (asset loaders catch their
+// own panics) only get the usual message
+pub fn install() {
+    *lock() = Some(CrashContext::default());
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if std::thread::current().name() != Some("main") || WRITING.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        match write_bundle(info) {
+            Ok(dir) => eprintln!("Crash report written to {}", dir.display()),
+            Err(e) => eprintln!("Couldn't write the crash report: {e}"),
+        }
+        WRITING.store(false, Ordering::Release);
+    }));
+}
+
+pub fn set_capabilities(report: String) {
+    with_context(|context| context.capabilities = report);
+}
+
+pub fn set_gpu(device: &wgpu::Device, queue: &wgpu::Queue) {
+    with_context(|context| context.gpu = Some((device.clone(), queue.clone())));
+}
+
+// Around each frame, Some after it begins and None once it's presented
+pub fn set_frame(texture: Option<&wgpu::Texture>) {
+    with_context(|context| context.frame = texture.cloned());
+}
+
+// End of every frame
+pub fn snapshot(stats: Vec<String>, scene: String, assets: Vec<String>) {
+    with_context(|context| {
+        context.stats = stats;
+        context.scene = scene;
+        context.assets = assets;
+    });
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<CrashContext>> {
+    CONTEXT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn with_context(update: impl FnOnce(&mut CrashContext)) {
+    if let Some(context) = lock().as_mut() {
+        update(context);
+    }
+}
+
+fn write_bundle(info: &PanicHookInfo) -> std::io::Result<PathBuf> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dir = std::env::temp_dir().join(format!("wgpu-foray-crash-{seconds}"));
+    std::fs::create_dir_all(&dir)?;
+
+    // The panic could have come from under the lock, the hook never waits on it
+    let guard = match CONTEXT.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    };
+    let context = guard.as_ref().and_then(|guard| guard.as_ref());
+
+    let mut report = String::new();
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_owned())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(not a string)".to_owned());
+    let _ = writeln!(report, "Panic: {message}");
+    if let Some(location) = info.location() {
+        let _ = writeln!(report, "At: {location}");
+    }
+    let _ = writeln!(report, "\nBacktrace:\n{}", Backtrace::force_capture());
+
+    let _ = writeln!(report, "\nLog, newest last:");
+    match log_sink::last(LOG_RECORDS) {
+        Some(records) => {
+            for record in records {
+                let _ = writeln!(report, "{}", record.line());
+            }
+        }
+        None => report.push_str("(the log was busy when it panicked)\n"),
+    }
+
+    match context {
+        Some(context) => {
+            let _ = writeln!(report, "\nScene: {}", context.scene);
+            let _ = writeln!(report, "\nAssets:");
+            for asset in &context.assets {
+                let _ = writeln!(report, "{asset}");
+            }
+            let _ = writeln!(report, "\nLast frame's stats:");
+            for line in &context.stats {
+                let _ = writeln!(report, "{line}");
+            }
+            let _ = writeln!(report, "\nCapabilities:\n{}", context.capabilities);
+            let screenshot = dir.join("screenshot.png");
+            let result = match (&context.gpu, &context.frame) {
+                (Some((device, queue)), Some(texture)) => {
+                    capture(device.clone(), queue.clone(), texture.clone(), screenshot)
+                }
+                (None, _) => Err("no GPU yet".to_owned()),
+                (_, None) => Err("it panicked between frames".to_owned()),
+            };
+            let _ = match result {
+                Ok(()) => writeln!(report, "Screenshot: screenshot.png"),
+                Err(reason) => writeln!(report, "No screenshot, {reason}"),
+            };
+        }
+        None => report.push_str("\n(the frame loop's state was locked when it panicked)\n"),
+    }
+
+    std::fs::write(dir.join("report.txt"), report)?;
+    Ok(dir)
+}
+
+// On its own thread, so a panic in there (wgpu on a lost device) or a GPU that doesn't
+// answer costs SCREENSHOT_TIMEOUT at most and can't take the hook down with it
+fn capture(
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    texture: wgpu::Texture,
+    path: PathBuf,
+) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("crash screenshot".to_owned())
+        .spawn(move || {
+            let _ = sender.send(save_texture(&device, &queue, &texture, &path));
+        })
+        .map_err(|e| format!("couldn't start a thread for it, {e}"))?;
+    match receiver.recv_timeout(SCREENSHOT_TIMEOUT) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            Err(format!("the GPU didn't answer in {SCREENSHOT_TIMEOUT:?}"))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err("taking it panicked".to_owned()),
+    }
+}
+
+fn save_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    path: &Path,
+) -> Result<(), String> {
+    // The frame may be half recorded or already presented, errors stay in the scope instead
+    // of going to the device's handler
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Crash Screenshot"),
+    });
+    // The app's pool is out of reach from the hook, and there's nothing to reuse the one
+    // buffer with anyway
+    let mut pool = BufferPool::new(&GpuMemoryTracker::new(), 0);
+    let readback = Readback::copy(device, &mut pool, &mut encoder, texture, "Crash Screenshot");
+    stats::submit(queue, std::iter::once(encoder.finish()));
+    if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+        return Err(format!("the copy failed, {e}"));
+    }
+    let image = readback?.read(device, SCREENSHOT_TIMEOUT)?;
+    image_file::save(&image, path)
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use crate::backend::WindowBackend;
+use crate::error::ForayError;
+use crate::image_file;
+
+#[derive(Clone, Debug)]
+pub enum CursorKind {
+    Arrow,
+    IBeam,
+    Crosshair,
+    Hand,
+    HResize,
+    VResize,
+    // Shown at the image's size, `hotspot` is the clicking point in pixels from the top left
+    Custom {
+        image: image::RgbaImage,
+        hotspot: (u32, u32),
+    },
+}
+
+impl CursorKind {
+    // One of the standard shape names, anything else is taken as a PNG path
+    pub fn from_arg(arg: &Path) -> Result<Self, ForayError> {
+        let name = arg.to_str().unwrap_or_default().to_ascii_lowercase();
+        Ok(match name.as_str() {
+            "arrow" => CursorKind::Arrow,
+            "ibeam" => CursorKind::IBeam,
+            "crosshair" => CursorKind::Crosshair,
+            "hand" => CursorKind::Hand,
+            "hresize" => CursorKind::HResize,
+            "vresize" => CursorKind::VResize,
+            _ => Self::load(arg)?,
+        })
+    }
+
+    // Hotspot in the middle of the image
+    pub fn load(path: &Path) -> Result<Self, ForayError> {
+        let image = image_file::open(path)?;
+        let hotspot = (image.width() / 2, image.height() / 2);
+        Ok(CursorKind::Custom { image, hotspot })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CursorId(u64);
+
+// Interactions push the cursor they want and pop it when they end, whatever is on top is
+// shown. Popping takes out that entry wherever it sits, so interactions that overlap can
+// end in any order and the right cursor comes back
+pub struct CursorStack {
+    entries: Vec<(CursorId, CursorKind)>,
+    next_id: u64,
+}
+
+impl CursorStack {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn push(&mut self, window: &mut impl WindowBackend, kind: CursorKind) -> CursorId {
+        let id = CursorId(self.next_id);
+        self.next_id += 1;
+        window.set_cursor(Some(&kind));
+        self.entries.push((id, kind));
+        id
+    }
+
+    pub fn pop(&mut self, window: &mut impl WindowBackend, id: CursorId) {
+        let Some(index) = self.entries.iter().position(|(entry, _)| *entry == id) else {
+            return;
+        };
+        self.entries.remove(index);
+        // Only the top one is on screen, anything below can go quietly
+        if index == self.entries.len() {
+            window.set_cursor(self.entries.last().map(|(_, kind)| kind));
+        }
+    }
+
+    // Back to the system cursor, which also frees any custom one before the window goes
+    pub fn clear(&mut self, window: &mut impl WindowBackend) {
+        self.entries.clear();
+        window.set_cursor(None);
+    }
+}
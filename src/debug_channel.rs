@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::maintain::Maintain;
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+
+// What the preprocessor pastes in for shaders calling debug_print, once enable() was called
+pub const SHADER: &str = include_str!("debug_channel.wgsl");
+// Calls a frame keeps before the oldest get overwritten, DEBUG_CAPACITY in the shader
+const CAPACITY: u32 = 256;
+// The count, then (tag, value) pairs
+const SIZE: u64 = 4 + CAPACITY as u64 * 8;
+// Frames whose entries can be on the way back at once. Frames finding none free aren't read
+const IN_FLIGHT: usize = 3;
+// Values shown per tag, the newest last
+pub const PER_TAG: usize = 8;
+
+// Whether shaders get the real debug_print, decided before the first one is preprocessed
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    // A frame was copied in and hasn't been read yet
+    busy: bool,
+    // map_async was called for it
+    waiting: bool,
+    // Set by the map_async callback, whether the buffer got mapped
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+// debug_print(tag, value) from fragment shaders, in debug builds on devices that can write
+// storage buffers from them (Optional::ShaderDebug). The buffer sits in the globals bind
+// group at the binding reflection keeps everything else off, each frame starts it over and
+// copies it out to be read back a few frames later without stalling, like
+// PipelineStatistics does
+pub struct DebugChannel {
+    buffer: Tracked<wgpu::Buffer>,
+    readbacks: Vec<Readback>,
+    // The last PER_TAG values of every tag seen so far
+    pub latest: BTreeMap<u32, VecDeque<f32>>,
+    // Calls in the last frame that came back, past CAPACITY some were lost
+    pub calls: u32,
+}
+
+impl DebugChannel {
+    pub fn new(device: &wgpu::Device, memory: &GpuMemoryTracker) -> Self {
+        let buffer = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Debug Channel"),
+                size: SIZE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+        let readbacks = (0..IN_FLIGHT)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Debug Channel Readback"),
+                    size: SIZE,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                busy: false,
+                waiting: false,
+                mapped: Arc::new(Mutex::new(None)),
+            })
+            .collect();
+        Self {
+            buffer,
+            readbacks,
+            latest: BTreeMap::new(),
+            calls: 0,
+        }
+    }
+
+    // For GlobalsUniform::new
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    // Before the frame's first pass, so it only has this frame's calls
+    pub fn begin_frame(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.buffer, 0, Some(4));
+    }
+
+    // After the frame's last pass, before its submit
+    pub fn end_frame(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(readback) = self.readbacks.iter_mut().find(|readback| !readback.busy) else {
+            return;
+        };
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &readback.buffer, 0, SIZE);
+        readback.busy = true;
+    }
+
+    // After the submit: starts mapping what end_frame copied, and picks up whatever has come
+    // back since the last frame into `latest`. The maps are polled by `maintain`
+    pub fn collect(&mut self, maintain: &mut Maintain) {
+        for readback in &mut self.readbacks {
+            if !readback.busy || readback.waiting {
+                continue;
+            }
+            let mapped = readback.mapped.clone();
+            let done = maintain.register("debug channel readback", IN_FLIGHT as u64);
+            readback
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if let Err(e) = &result {
+                        log::warn!("Debug channel readback failed: {e}");
+                    }
+                    *mapped.lock().unwrap() = Some(result.is_ok());
+                    done.finish();
+                });
+            readback.waiting = true;
+        }
+
+        for readback in &mut self.readbacks {
+            let Some(mapped) = readback.mapped.lock().unwrap().take() else {
+                continue;
+            };
+            readback.waiting = false;
+            readback.busy = false;
+            // That frame's entries are lost, the buffer's free for another
+            if !mapped {
+                continue;
+            }
+            let words: Vec<u32> =
+                bytemuck::pod_collect_to_vec(&readback.buffer.slice(..).get_mapped_range());
+            readback.buffer.unmap();
+            let calls = words[0];
+            // Once it wrapped the oldest entry is the one the next call would overwrite
+            let first = if calls > CAPACITY {
+                calls % CAPACITY
+            } else {
+                0
+            };
+            for i in 0..calls.min(CAPACITY) {
+                let at = 1 + 2 * ((first + i) % CAPACITY) as usize;
+                let values = self.latest.entry(words[at]).or_default();
+                values.push_back(f32::from_bits(words[at + 1]));
+                if values.len() > PER_TAG {
+                    values.pop_front();
+                }
+            }
+            self.calls = calls;
+        }
+    }
+
+    // For the overlay and `debug` in the console, a line per tag
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .latest
+            .iter()
+            .map(|(tag, values)| {
+                let values: Vec<String> =
+                    values.iter().map(|value| format!("{value:.4}")).collect();
+                format!("debug {tag}: {}", values.join(" "))
+            })
+            .collect();
+        if self.calls > CAPACITY {
+            lines.push(format!(
+                "debug_print: {} calls in a frame, only the last {CAPACITY} kept",
+                self.calls
+            ));
+        }
+        lines
+    }
+}
@@ -0,0 +1,25 @@
+// The shader side of debug_channel.rs, pasted in by the preprocessor for shaders that
+// call debug_print. Only fragment shaders drawn with the globals bind group at group 0
+// can, the binding isn't visible to anything else. CAPACITY matches debug_channel.rs
+
+const DEBUG_CAPACITY: u32 = 256u;
+
+struct DebugEntry {
+    tag: u32,
+    value: f32,
+}
+
+struct DebugChannel {
+    // Calls this frame, the entries wrap around once it passes DEBUG_CAPACITY
+    count: atomic<u32>,
+    entries: array<DebugEntry, DEBUG_CAPACITY>,
+}
+
+@group(0) @binding(15) var<storage, read_write> debug_channel: DebugChannel;
+
+// Read back a few frames later and shown per tag in the overlay and by `debug` in the
+// console. Every invocation that gets here writes, so guard it to the pixel you care about
+fn debug_print(tag: u32, value: f32) {
+    let index = atomicAdd(&debug_channel.count, 1u) % DEBUG_CAPACITY;
+    debug_channel.entries[index] = DebugEntry(tag, value);
+}
@@ -0,0 +1,1222 @@
+use std::cell::RefCell;
+#[cfg(any(feature = "obj", feature = "gltf"))]
+use std::path::Path;
+use std::time::Duration;
+
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+use crate::bind_groups::{BindGroupBuilder, BindGroupHandle};
+use crate::buffer_pool::BufferPool;
+use crate::camera3d::{CameraController, CameraPose, OrbitCamera};
+#[cfg(feature = "gltf")]
+use crate::colors;
+use crate::colors::{RgbaColor, Theme};
+use crate::depth::{self, DepthConvention};
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame, DEBUG_MAGENTA};
+use crate::geometry::Grid;
+use crate::gizmos::{self, GizmoCamera};
+#[cfg(feature = "gltf")]
+use crate::gltf;
+use crate::lod::{self, LodMesh};
+use crate::material::{self, DrawItem, MaterialHandle, MaterialLibrary, MaterialParams};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::mesh::{Mesh, MeshData, Position};
+use crate::mesh_arena::MeshArena;
+#[cfg(feature = "obj")]
+use crate::obj;
+use crate::pacing::Stepped;
+use crate::pipeline_bank::{BlendMode, DepthStage, PipelineBuilder, RenderPipelineBank};
+use crate::post;
+use crate::reflect::{self, Reflection};
+use crate::render_graph::RenderGraph;
+use crate::shaders;
+use crate::targets::{TargetDesc, TargetHandle, TargetRegistry};
+use crate::transform::{Affine, Transform};
+
+const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// MSAA sample counts the g-buffer pipelines are built for, set_samples picks one
+pub const SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+// Where the camera starts, looking at the cubes
+const EYE: Vec3 = Vec3::new(0.0, 1.5, 3.0);
+// The sphere swings between these distances behind the cubes, through its LODs
+const SPHERE_NEAR: f32 = 1.5;
+const SPHERE_FAR: f32 = 14.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LitVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    color: [f32; 3],
+}
+
+impl LitVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LitVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl Position for LitVertex {
+    fn position(&self) -> Vec3 {
+        self.position.into()
+    }
+}
+
+// What imported glTF meshes are made of, drawn by deferred_textured. Base color comes from
+// the material's texture, there's no vertex color
+#[cfg(feature = "gltf")]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TexturedVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+#[cfg(feature = "gltf")]
+impl TexturedVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TexturedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[cfg(feature = "gltf")]
+impl Position for TexturedVertex {
+    fn position(&self) -> Vec3 {
+        self.position.into()
+    }
+}
+
+// A glTF file's meshes and where its nodes put them, drawn instead of the demo's own
+#[cfg(feature = "gltf")]
+struct Imported {
+    meshes: Vec<Mesh>,
+    // (mesh, submesh, material, world transform) for each primitive of each node
+    items: Vec<(usize, usize, MaterialHandle, Mat4)>,
+}
+
+// Mirrors `struct Camera` in deferred.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view: Mat4,
+    proj: Mat4,
+    inv_proj: Mat4,
+    light_dir: Vec4,
+}
+
+// Mirrors `struct Wobble` in deferred.wgsl, the item block of the wobbly material
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Wobble {
+    amplitude: f32,
+    // Bands per unit up the y axis, in radians
+    frequency: f32,
+    phase: f32,
+    _padding: f32,
+}
+
+// Unit cube with flat normals, one color per face and one submesh per face
+fn cube() -> MeshData<LitVertex> {
+    // (normal, tangent, bitangent, sRGB color) with tangent x bitangent = normal, so the winding is CCW
+    let faces = [
+        (
+            Vec3::X,
+            Vec3::NEG_Z,
+            Vec3::Y,
+            RgbaColor::rgba(0.9, 0.3, 0.3, 1.0),
+        ),
+        (
+            Vec3::NEG_X,
+            Vec3::Z,
+            Vec3::Y,
+            RgbaColor::rgba(0.3, 0.9, 0.3, 1.0),
+        ),
+        (
+            Vec3::Y,
+            Vec3::X,
+            Vec3::NEG_Z,
+            RgbaColor::rgba(0.3, 0.3, 0.9, 1.0),
+        ),
+        (
+            Vec3::NEG_Y,
+            Vec3::X,
+            Vec3::Z,
+            RgbaColor::rgba(0.9, 0.9, 0.3, 1.0),
+        ),
+        (
+            Vec3::Z,
+            Vec3::X,
+            Vec3::Y,
+            RgbaColor::rgba(0.9, 0.3, 0.9, 1.0),
+        ),
+        (
+            Vec3::NEG_Z,
+            Vec3::NEG_X,
+            Vec3::Y,
+            RgbaColor::rgba(0.3, 0.9, 0.9, 1.0),
+        ),
+    ];
+
+    let names = ["+X", "-X", "+Y", "-Y", "+Z", "-Z"];
+    MeshData::merge(faces.into_iter().zip(names).map(
+        |((normal, tangent, bitangent, color), name)| {
+            let [red, green, blue, _] = color.to_f32_array_linear();
+            let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .into_iter()
+                .map(|(t, b)| LitVertex {
+                    position: ((normal + tangent * t + bitangent * b) * 0.5).into(),
+                    normal: normal.into(),
+                    color: [red, green, blue],
+                })
+                .collect();
+            MeshData::new(name, vertices, vec![0, 1, 2, 0, 2, 3])
+        },
+    ))
+}
+
+// Unit square in the xy plane facing +z, what the grass card is drawn on
+fn card() -> MeshData<LitVertex> {
+    let [red, green, blue, _] = RgbaColor::rgba(0.35, 0.75, 0.25, 1.0).to_f32_array_linear();
+    let vertices = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)]
+        .into_iter()
+        .map(|(x, y)| LitVertex {
+            position: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            color: [red, green, blue],
+        })
+        .collect();
+    MeshData::new("Card", vertices, vec![0, 1, 2, 0, 2, 3])
+}
+
+// Side of the grass test mask
+const GRASS_SIZE: u32 = 64;
+
+// Blades of grass in a GRASS_SIZE square, coverage falling off across each blade so the
+// cutoff decides how thin they get. Hashed heights and leans, the same every run
+fn grass_mask() -> Vec<u8> {
+    let size = GRASS_SIZE as f32;
+    let blades: Vec<(f32, f32, f32)> = (0..12u32)
+        .map(|blade| {
+            let hash = blade.wrapping_mul(2_654_435_761) >> 16;
+            let base = (blade as f32 + 0.5) / 12.0 * size;
+            let height = size * (0.55 + 0.45 * (hash % 97) as f32 / 96.0);
+            let lean = ((hash % 31) as f32 / 30.0 - 0.5) * size * 0.3;
+            (base, height, lean)
+        })
+        .collect();
+    let mut texels = vec![0u8; (GRASS_SIZE * GRASS_SIZE) as usize];
+    for (row, line) in texels.chunks_mut(GRASS_SIZE as usize).enumerate() {
+        // From the bottom, rows are stored from the top
+        let up = size - row as f32 - 0.5;
+        for (column, texel) in line.iter_mut().enumerate() {
+            let x = column as f32 + 0.5;
+            let coverage = blades
+                .iter()
+                .filter(|(_, height, _)| up < *height)
+                .map(|(base, height, lean)| {
+                    let along = up / height;
+                    let center = base + lean * along * along;
+                    let half_width = 2.5 * (1.0 - along);
+                    1.0 - (x - center).abs() / half_width.max(0.01)
+                })
+                .fold(0.0f32, f32::max);
+            *texel = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+    texels
+}
+
+// Unit diameter UV sphere with shared vertices, closed so it decimates cleanly. The seam
+// and the poles reuse vertices instead of duplicating them
+fn sphere(rings: u32, segments: u32) -> MeshData<LitVertex> {
+    let [red, green, blue, _] = RgbaColor::rgba(0.85, 0.85, 0.9, 1.0).to_f32_array_linear();
+    let vertex = |normal: Vec3| LitVertex {
+        position: (normal * 0.5).into(),
+        normal: normal.into(),
+        color: [red, green, blue],
+    };
+    let mut vertices = vec![vertex(Vec3::Y), vertex(Vec3::NEG_Y)];
+    for ring in 1..rings {
+        let (sin_polar, cos_polar) = (ring as f32 / rings as f32 * std::f32::consts::PI).sin_cos();
+        for segment in 0..segments {
+            let (sin, cos) = (segment as f32 / segments as f32 * std::f32::consts::TAU).sin_cos();
+            vertices.push(vertex(Vec3::new(
+                sin_polar * cos,
+                cos_polar,
+                -sin_polar * sin,
+            )));
+        }
+    }
+    // Vertex `segment` of ring `ring` (1 to rings - 1)
+    let at = |ring: u32, segment: u32| 2 + (ring - 1) * segments + segment % segments;
+    let mut indices = Vec::new();
+    for segment in 0..segments {
+        indices.extend([0, at(1, segment), at(1, segment + 1)]);
+        indices.extend([1, at(rings - 1, segment + 1), at(rings - 1, segment)]);
+        for ring in 1..rings - 1 {
+            let (a, b) = (at(ring, segment), at(ring, segment + 1));
+            let (c, d) = (at(ring + 1, segment), at(ring + 1, segment + 1));
+            indices.extend([a, c, d, a, d, b]);
+        }
+    }
+    MeshData::new("Sphere", vertices, indices)
+}
+
+// G-buffer (albedo + view-space normal + depth) then a fullscreen directional light
+pub struct DeferredDemo {
+    pub active: bool,
+    // Depth-only pass over the opaque materials first, then the g-buffer pass compares Equal
+    // so fs_geometry runs once per pixel
+    pub depth_prepass: bool,
+    // Under the cubes, the console's grid command changes it
+    pub grid: Grid,
+    depth_convention: DepthConvention,
+    albedo: TargetHandle,
+    normal: TargetHandle,
+    depth: TargetHandle,
+    // Per pixel in the g-buffer pass, see set_samples
+    samples: u32,
+    // What the g-buffer pass tests against when multisampled, made the first time it is
+    msaa_depth: Option<TargetHandle>,
+    camera_buffer: Tracked<wgpu::Buffer>,
+    camera_bind_group: wgpu::BindGroup,
+    // Points at the g-buffer views, the registry rebuilds it on resizes
+    gbuffer_bind_group: BindGroupHandle,
+    cube: Mesh,
+    materials: MaterialLibrary,
+    // One per cube, all three share the mesh
+    cube_materials: [MaterialHandle; 3],
+    // Satin, but every item drawn with it wobbles by its own Wobble block
+    wobbly: MaterialHandle,
+    // Cut out by the grass mask, in front of the cubes
+    grass: MaterialHandle,
+    card: Mesh,
+    // Dense enough to be worth its LODs, drawn with the wobbly material
+    sphere: LodMesh,
+    sphere_level: usize,
+    // Where it is in its swing toward and away from the camera
+    sphere_phase: Stepped<f32>,
+    // Where the sphere was last drawn and how far along its wobble, for exports
+    drawn_sphere: Transform,
+    drawn_wobble: f32,
+    // As of the last draw, turned by the spin they all share. Their bounds gizmos follow them
+    cubes: [Transform; 3],
+    // Advanced in fixed steps, drawn interpolated
+    spin: Stepped<Quat>,
+    // Orbits the cubes unless the console's camera command swapped in another
+    pub camera: Box<dyn CameraController>,
+    // Where the camera was last drawn from, for the gizmos
+    drawn_camera: CameraPose,
+    // Shifts the projection by this much of clip space, a fraction of a pixel for each
+    // sample of a supersampled screenshot. Zero the rest of the time
+    pub jitter: Vec2,
+    // The `gltf` command's model, shown in place of the cubes, sphere and grass
+    #[cfg(feature = "gltf")]
+    imported: Option<Imported>,
+}
+
+impl DeferredDemo {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        registry: &mut TargetRegistry,
+        bank: &mut RenderPipelineBank,
+        memory: &GpuMemoryTracker,
+        depth_convention: DepthConvention,
+    ) -> Self {
+        let albedo = registry.create(
+            device,
+            TargetDesc {
+                label: "G-Buffer Albedo",
+                format: ALBEDO_FORMAT,
+                scale: 1.0,
+                storage: false,
+            },
+        );
+        let normal = registry.create(
+            device,
+            TargetDesc {
+                label: "G-Buffer Normal",
+                format: NORMAL_FORMAT,
+                scale: 1.0,
+                storage: false,
+            },
+        );
+        let depth = registry.create(
+            device,
+            TargetDesc {
+                label: "G-Buffer Depth",
+                format: DEPTH_FORMAT,
+                scale: 1.0,
+                storage: false,
+            },
+        );
+
+        let camera_buffer = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Deferred Camera Buffer"),
+                size: std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Deferred Camera Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Deferred Camera Bind Group"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let source = include_str!("deferred.wgsl");
+        let shader = shaders::create_module(device, "Deferred Shader", source);
+        let reflection =
+            Reflection::new(source).unwrap_or_else(|e| panic!("deferred.wgsl didn't reflect, {e}"));
+        // Straight from what the lighting pass declares in group 1. There's no sampler, so
+        // the textures come out unfilterable, fine since everything is read with textureLoad
+        let lighting = reflection.bindings(&["vs_fullscreen", "fs_lighting"]);
+        let gbuffer_entries = reflect::layout_entries(&lighting, 1)
+            .unwrap_or_else(|e| panic!("No g-buffer layout from deferred.wgsl, {e}"));
+        let gbuffer_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("G-Buffer Layout"),
+            entries: &gbuffer_entries,
+        });
+
+        let mut materials = MaterialLibrary::new(device, queue, memory);
+        let cube_materials = [
+            ("Glossy", RgbaColor::rgba(1.0, 0.85, 0.85, 1.0), 0.1, 0),
+            ("Satin", RgbaColor::rgba(0.85, 0.9, 1.0, 1.0), 0.5, 0),
+            ("Matte", RgbaColor::rgba(0.9, 1.0, 0.85, 1.0), 0.95, 1),
+        ]
+        .map(|(name, tint, roughness, sort_key)| {
+            materials.create(
+                device,
+                memory,
+                name,
+                "deferred_geometry",
+                BlendMode::Replace,
+                MaterialParams::new(tint, roughness),
+                sort_key,
+            )
+        });
+
+        let wobbly = materials.create(
+            device,
+            memory,
+            "Wobbly",
+            "deferred_wobble",
+            BlendMode::Replace,
+            MaterialParams::new(RgbaColor::rgba(0.85, 0.9, 1.0, 1.0), 0.5),
+            0,
+        );
+        if let Err(e) = materials.declare_item_block::<Wobble>(wobbly) {
+            log::error!("{e}");
+        }
+        let grass = materials.create(
+            device,
+            memory,
+            "Grass",
+            "deferred_geometry",
+            BlendMode::Cutout,
+            MaterialParams::new(RgbaColor::rgba(1.0, 1.0, 1.0, 1.0), 0.8).with_cutoff(0.3),
+            0,
+        );
+        let mask = material::upload_mask(
+            device,
+            queue,
+            memory,
+            "Grass Mask",
+            (GRASS_SIZE, GRASS_SIZE),
+            &grass_mask(),
+        );
+        materials.set_mask(device, grass, mask);
+
+        // G-buffer contents don't blend, the materials all overwrite or cut out. Each also
+        // gets the variants for the depth pre-pass, and builds for multisampled g-buffers
+        let wobble_builder = PipelineBuilder::new("Deferred Wobble Pipeline", &shader)
+            .reflect(Some(&reflection))
+            .vertex_entry("vs_wobble")
+            .fragment_entry("fs_geometry")
+            .vertex_buffer(LitVertex::desc())
+            .vertex_buffer(material::transform_layout())
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&materials.layout)
+            .bind_group_layout(&materials.item_layout)
+            .color_target(ALBEDO_FORMAT)
+            .color_target(NORMAL_FORMAT)
+            .depth(DEPTH_FORMAT, wgpu::CompareFunction::Less)
+            .depth_convention(depth_convention);
+        let geometry_builder = PipelineBuilder::new("Deferred Geometry Pipeline", &shader)
+            .reflect(Some(&reflection))
+            .vertex_entry("vs_geometry")
+            .fragment_entry("fs_geometry")
+            .vertex_buffer(LitVertex::desc())
+            .vertex_buffer(material::transform_layout())
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&materials.layout)
+            .color_target(ALBEDO_FORMAT)
+            .color_target(NORMAL_FORMAT)
+            .depth(DEPTH_FORMAT, wgpu::CompareFunction::Less)
+            .depth_convention(depth_convention);
+        #[cfg(feature = "gltf")]
+        let textured_builder = PipelineBuilder::new("Deferred Textured Pipeline", &shader)
+            .reflect(Some(&reflection))
+            .vertex_entry("vs_textured")
+            .fragment_entry("fs_textured")
+            .vertex_buffer(TexturedVertex::desc())
+            .vertex_buffer(material::transform_layout())
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&materials.layout)
+            .color_target(ALBEDO_FORMAT)
+            .color_target(NORMAL_FORMAT)
+            .depth(DEPTH_FORMAT, wgpu::CompareFunction::Less)
+            .depth_convention(depth_convention);
+        for (name, builder, modes) in [
+            #[cfg(feature = "gltf")]
+            (
+                "deferred_textured",
+                &textured_builder,
+                &[BlendMode::Replace, BlendMode::Cutout][..],
+            ),
+            (
+                "deferred_wobble",
+                &wobble_builder,
+                &[BlendMode::Replace][..],
+            ),
+            (
+                "deferred_geometry",
+                &geometry_builder,
+                &[BlendMode::Replace, BlendMode::Cutout],
+            ),
+        ] {
+            bank.register_blends(device, format, name, builder, modes);
+            for &mode in modes {
+                let builder = builder.clone().blend_mode(mode);
+                bank.register_depth_stages(device, format, &mode.key(name), &builder);
+                bank.register_sample_counts(
+                    device,
+                    format,
+                    &mode.key(name),
+                    &builder,
+                    &SAMPLE_COUNTS,
+                );
+            }
+        }
+        bank.register_surface(
+            device,
+            "deferred_lighting",
+            &PipelineBuilder::new("Deferred Lighting Pipeline", &shader)
+                .reflect(Some(&reflection))
+                .vertex_entry("vs_fullscreen")
+                .fragment_entry("fs_lighting")
+                .bind_group_layout(&camera_layout)
+                .bind_group_layout(&gbuffer_layout)
+                .constant(depth::REVERSE_Z, depth_convention.override_value())
+                .cull_mode(None),
+            format,
+        );
+        // Same light, into the float target the effect chain reads from
+        bank.register(
+            "deferred_lighting_hdr",
+            PipelineBuilder::new("Deferred Lighting HDR Pipeline", &shader)
+                .reflect(Some(&reflection))
+                .vertex_entry("vs_fullscreen")
+                .fragment_entry("fs_lighting")
+                .bind_group_layout(&camera_layout)
+                .bind_group_layout(&gbuffer_layout)
+                .constant(depth::REVERSE_Z, depth_convention.override_value())
+                .cull_mode(None)
+                .build(device, post::SCENE_FORMAT),
+        );
+        if let Err(e) = materials.check(bank, 1) {
+            log::error!("{e}");
+        }
+
+        // The cube and every sphere level share one buffer pair, so draw_sorted going from
+        // one to the next doesn't rebind. Twice the full meshes is room for the LODs too
+        let (cube, sphere) = (cube(), sphere(48, 96));
+        let card = card();
+        let arena = MeshArena::new(
+            device,
+            memory,
+            "Deferred",
+            &LitVertex::desc(),
+            (2 * (cube.vertices.len() + sphere.vertices.len()) + card.vertices.len()) as u32,
+            (2 * (cube.indices.len() + sphere.indices.len()) + card.indices.len()) as u32,
+        );
+        let upload = |name: &str, data: &MeshData<LitVertex>| {
+            let topology = wgpu::PrimitiveTopology::TriangleList;
+            arena
+                .mesh_from_data(queue, name, topology, data)
+                .unwrap_or_else(|e| {
+                    log::warn!("{e}, giving it its own buffers");
+                    Mesh::from_data(device, memory, name, &LitVertex::desc(), topology, data)
+                })
+        };
+        let cube = upload("Cube", &cube);
+        let card = upload("Card", &card);
+        let sphere = LodMesh::new("Sphere", &sphere, &[(0.25, 120.0), (0.06, 60.0)], upload);
+        let start = CameraPose::looking_at(EYE, Vec3::ZERO);
+
+        let gbuffer_bind_group = registry.create_bind_group(
+            device,
+            BindGroupBuilder::new("G-Buffer Bind Group", &gbuffer_layout)
+                .target(0, albedo)
+                .target(1, normal)
+                .target(2, depth),
+        );
+
+        Self {
+            active: false,
+            depth_prepass: false,
+            grid: Grid::DEFAULT,
+            depth_convention,
+            albedo,
+            normal,
+            depth,
+            samples: 1,
+            msaa_depth: None,
+            camera_buffer,
+            camera_bind_group,
+            gbuffer_bind_group,
+            cube,
+            materials,
+            cube_materials,
+            wobbly,
+            grass,
+            card,
+            sphere,
+            sphere_level: 0,
+            sphere_phase: Stepped::new(0.0),
+            drawn_sphere: Transform::default(),
+            drawn_wobble: 0.0,
+            cubes: [0, 1, 2].map(Self::cube_placement),
+            spin: Stepped::new(Quat::IDENTITY),
+            camera: Box::new(OrbitCamera::new(Vec3::ZERO, start)),
+            drawn_camera: start,
+            jitter: Vec2::ZERO,
+            #[cfg(feature = "gltf")]
+            imported: None,
+        }
+    }
+
+    fn view_proj(&self, aspect: f32) -> (Mat4, Mat4) {
+        let view = self.drawn_camera.view();
+        let proj = self
+            .depth_convention
+            .perspective(45f32.to_radians(), aspect, 0.1, 100.0);
+        // Added to clip space xy scaled by w, so it's the same shift on screen at any depth
+        let jitter = Mat4::from_translation(self.jitter.extend(0.0));
+        (view, jitter * proj)
+    }
+
+    pub fn gizmo_camera(&self, aspect: f32) -> GizmoCamera {
+        let (view, proj) = self.view_proj(aspect);
+        GizmoCamera {
+            view_proj: proj * view,
+            eye: self.drawn_camera.eye,
+            fade: (4.0, 12.0),
+        }
+    }
+
+    // What the demo's meshes are built with, for pipelines specialized to draw them
+    pub fn vertex_layout() -> wgpu::VertexBufferLayout<'static> {
+        LitVertex::desc()
+    }
+
+    // The depth the gizmo pass tests against
+    pub fn depth_target(&self) -> TargetHandle {
+        self.depth
+    }
+
+    // Where cube `index` sits, side by side with the others
+    fn cube_placement(index: usize) -> Transform {
+        let x = (index as f32 - 1.0) * 1.2;
+        Transform::from_translation(Vec3::new(x, 0.0, 0.0)).with_scale(0.7)
+    }
+
+    // Cube `index` spinning in place. Asked for by the draw, the gizmos and exports, the
+    // matrix is only built once per frame
+    fn cube_transform(&self, index: usize) -> Mat4 {
+        self.cubes[index].to_matrix()
+    }
+
+    // MSAA for the g-buffer pass, one of SAMPLE_COUNTS the device can draw the g-buffer
+    // formats with. Cutouts cover samples by alpha above 1 and discard at 1. Lighting still
+    // runs once per pixel, on the resolved albedo and normals and the depth a single sampled
+    // pre-pass lays down
+    pub fn set_samples(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut TargetRegistry,
+        samples: u32,
+    ) -> Result<(), ForayError> {
+        let supported: Vec<u32> = SAMPLE_COUNTS
+            .into_iter()
+            .filter(|&count| {
+                [ALBEDO_FORMAT, NORMAL_FORMAT, DEPTH_FORMAT]
+                    .iter()
+                    .all(|format| {
+                        format
+                            .guaranteed_format_features(device.features())
+                            .flags
+                            .sample_count_supported(count)
+                    })
+            })
+            .collect();
+        if !supported.contains(&samples) {
+            return Err(ForayError::UnsupportedSampleCount {
+                what: "The deferred view".to_owned(),
+                samples,
+                supported,
+            });
+        }
+        let msaa_depth = *self.msaa_depth.get_or_insert_with(|| {
+            registry.create(
+                device,
+                TargetDesc {
+                    label: "G-Buffer MSAA Depth",
+                    format: DEPTH_FORMAT,
+                    scale: 1.0,
+                    storage: false,
+                },
+            )
+        });
+        for target in [self.albedo, self.normal, msaa_depth] {
+            registry.set_samples(device, target, samples);
+        }
+        self.samples = samples;
+        Ok(())
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // What draw() hands to draw_sorted, as of the last draw. Face by face so each one is
+    // labelled in a capture, sorting puts them back together per material
+    fn draw_items(&self) -> Vec<DrawItem<'_>> {
+        #[cfg(feature = "gltf")]
+        if let Some(imported) = &self.imported {
+            return imported
+                .items
+                .iter()
+                .map(|&(mesh, submesh, material, transform)| DrawItem {
+                    mesh: &imported.meshes[mesh],
+                    submesh: Some(submesh),
+                    material,
+                    transform,
+                    block: Vec::new(),
+                })
+                .collect();
+        }
+        let mut items: Vec<_> = (0..self.cube.submeshes.len())
+            .flat_map(|face| (0..self.cube_materials.len()).map(move |index| (face, index)))
+            .map(|(face, index)| DrawItem {
+                mesh: &self.cube,
+                submesh: Some(face),
+                material: self.cube_materials[index],
+                transform: self.cube_transform(index),
+                block: Vec::new(),
+            })
+            .collect();
+        let mut sphere = DrawItem {
+            mesh: &self.sphere.levels[self.sphere_level],
+            submesh: None,
+            material: self.wobbly,
+            transform: self.drawn_sphere.to_matrix(),
+            block: Vec::new(),
+        };
+        let wobble = Wobble {
+            amplitude: 0.03,
+            frequency: 18.0,
+            phase: self.drawn_wobble,
+            _padding: 0.0,
+        };
+        if let Err(e) = sphere.set_block(&self.materials, &wobble) {
+            log::error!("{e}");
+        }
+        items.push(sphere);
+        items.push(DrawItem {
+            mesh: &self.card,
+            submesh: None,
+            material: self.grass,
+            transform: Self::card_transform(),
+            block: Vec::new(),
+        });
+        items
+    }
+
+    // Standing in front of the middle cube, low enough that the cubes show over it
+    fn card_transform() -> Mat4 {
+        Mat4::from_translation(Vec3::new(0.0, -0.3, 0.9))
+    }
+
+    // The cubes and the sphere as they were last drawn, see obj::export
+    #[cfg(feature = "obj")]
+    pub fn export_obj(&self, path: &Path) -> Result<usize, ForayError> {
+        obj::export(path, &self.draw_items(), &self.materials)
+    }
+
+    // Behind the cubes, moving away and back
+    fn sphere_transform(&self, alpha: f32) -> Transform {
+        let swing = 0.5 - 0.5 * self.sphere_phase.at(alpha).cos();
+        let distance = SPHERE_NEAR + (SPHERE_FAR - SPHERE_NEAR) * swing;
+        Transform::from_translation(Vec3::new(0.0, 0.4, -distance))
+    }
+
+    // Grid, axes and the cubes' boxes. Call after draw() so the boxes follow the cubes
+    pub fn queue_gizmos(&self, frame: &mut Frame, theme: &Theme) {
+        gizmos::grid(frame, theme, &self.grid);
+        gizmos::axes(frame, theme, 1.0);
+        #[cfg(feature = "gltf")]
+        if self.imported.is_some() {
+            return;
+        }
+        for index in 0..self.cube_materials.len() {
+            gizmos::bounds(
+                frame,
+                self.cube_transform(index),
+                Vec3::splat(-0.5),
+                Vec3::splat(0.5),
+                theme.accent,
+            );
+        }
+    }
+
+    pub fn meshes(&self) -> Vec<&Mesh> {
+        let mut meshes = vec![&self.cube, &self.card];
+        meshes.extend(&self.sphere.levels);
+        #[cfg(feature = "gltf")]
+        if let Some(imported) = &self.imported {
+            meshes.extend(&imported.meshes);
+        }
+        meshes
+    }
+
+    // Replaces what the view shows with the file's meshes at its nodes' transforms, and
+    // points the orbit camera at them. What the file has that can't be drawn is logged.
+    // Its materials stay in the library after another import, there's no removing them
+    #[cfg(feature = "gltf")]
+    pub fn import_gltf(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        path: &Path,
+    ) -> Result<usize, ForayError> {
+        let model = gltf::load(path)?;
+        if !model.ignored.is_empty() {
+            let ignored: Vec<&str> = model.ignored.iter().map(String::as_str).collect();
+            log::warn!("{}: ignored {}", path.display(), ignored.join(", "));
+        }
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        let mut create = |name: &str,
+                          base_color: [f32; 4],
+                          texture: Option<&image::RgbaImage>,
+                          roughness: f32,
+                          cutoff: Option<f32>| {
+            // glTF factors are linear, MaterialParams takes sRGB like every other color
+            let [red, green, blue] =
+                [0, 1, 2].map(|channel| colors::linear_to_srgb(f64::from(base_color[channel])));
+            let tint = RgbaColor::rgba(red, green, blue, f64::from(base_color[3]));
+            let params = MaterialParams::new(tint, roughness).with_cutoff(cutoff.unwrap_or(0.5));
+            let blend = match cutoff {
+                Some(_) => BlendMode::Cutout,
+                None => BlendMode::Replace,
+            };
+            let handle =
+                self.materials
+                    .create(device, memory, name, "deferred_textured", blend, params, 0);
+            // Textureless ones still need a white one, the mask slot's default is red only
+            let texture = material::upload_texture(
+                device,
+                queue,
+                memory,
+                &format!("{name} Base Color"),
+                texture.unwrap_or(&white),
+            );
+            self.materials.set_mask(device, handle, texture);
+            handle
+        };
+        let fallback = create("glTF Default", [1.0; 4], None, 1.0, None);
+        let materials: Vec<MaterialHandle> = model
+            .materials
+            .iter()
+            .map(|material| {
+                create(
+                    &material.name,
+                    material.base_color,
+                    material.texture.as_ref(),
+                    material.roughness,
+                    material.cutoff,
+                )
+            })
+            .collect();
+
+        let mut meshes = Vec::new();
+        // glTF mesh index to where it is in `meshes`
+        let mut uploaded = vec![None; model.meshes.len()];
+        for (index, mesh) in model.meshes.iter().enumerate() {
+            if let Some(mesh) = mesh {
+                uploaded[index] = Some(meshes.len());
+                meshes.push(Mesh::from_data(
+                    device,
+                    memory,
+                    &mesh.name,
+                    &TexturedVertex::desc(),
+                    wgpu::PrimitiveTopology::TriangleList,
+                    &mesh.data,
+                ));
+            }
+        }
+        let mut items = Vec::new();
+        let (mut min, mut max) = (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY));
+        for item in &model.items {
+            let (Some(mesh), Some(at)) = (&model.meshes[item.mesh], uploaded[item.mesh]) else {
+                continue;
+            };
+            for (submesh, material) in mesh.materials.iter().enumerate() {
+                let material = material.map_or(fallback, |material| materials[material]);
+                items.push((at, submesh, material, item.transform));
+            }
+            let (low, high) = mesh.data.bounds();
+            for corner in 0..8 {
+                let pick = |bit: usize, axis: usize| {
+                    if corner & bit == 0 {
+                        low[axis]
+                    } else {
+                        high[axis]
+                    }
+                };
+                let point =
+                    item.transform
+                        .transform_point3(Vec3::new(pick(1, 0), pick(2, 1), pick(4, 2)));
+                (min, max) = (min.min(point), max.max(point));
+            }
+        }
+        if min.cmple(max).all() {
+            let (center, radius) = ((min + max) * 0.5, ((max - min).length() * 0.5).max(0.01));
+            let eye = center + Vec3::new(0.0, 0.5, 1.0).normalize() * radius * 2.5;
+            self.camera = Box::new(OrbitCamera::new(
+                center,
+                CameraPose::looking_at(eye, center),
+            ));
+        }
+        let count = items.len();
+        self.imported = Some(Imported { meshes, items });
+        Ok(count)
+    }
+
+    // Back to the cubes, sphere and grass
+    #[cfg(feature = "gltf")]
+    pub fn clear_import(&mut self) {
+        self.imported = None;
+        let start = CameraPose::looking_at(EYE, Vec3::ZERO);
+        self.camera = Box::new(OrbitCamera::new(Vec3::ZERO, start));
+    }
+
+    // One fixed-rate simulation step
+    pub fn fixed_update(&mut self, step: Duration) {
+        let dt = step.as_secs_f32();
+        self.spin.step(|spin| {
+            let turn = Quat::from_rotation_y(dt) * Quat::from_rotation_x(dt * 0.7);
+            (turn * *spin).normalize()
+        });
+        self.sphere_phase.step(|phase| phase + dt * 0.5);
+    }
+
+    // `alpha` is how far between the last two fixed updates this frame is. The lit result
+    // goes to `output`, the swapchain or a post::SCENE_FORMAT target
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        registry: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        output: ColorTarget,
+        viewport: (u32, u32),
+        alpha: f32,
+    ) -> Result<(), ForayError> {
+        let aspect = viewport.0 as f32 / viewport.1 as f32;
+        let spin = Transform::from_rotation(self.spin.at(alpha));
+        self.drawn_camera = self.camera.pose(alpha);
+        self.cubes = [0, 1, 2].map(|index| Self::cube_placement(index).then(&spin));
+        let (view, proj) = self.view_proj(aspect);
+        let light = view * Vec3::new(-0.5, -1.0, -0.3).normalize().extend(0.0);
+        let camera = CameraUniform {
+            view,
+            proj,
+            inv_proj: proj.inverse(),
+            light_dir: light,
+        };
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&camera));
+        // The sphere's level is picked from its size this frame, against the one it had
+        let sphere_transform = self.sphere_transform(alpha);
+        let pixels = lod::projected_size(
+            self.sphere.bounds,
+            sphere_transform.to_matrix(),
+            proj * view,
+            viewport,
+        );
+        self.sphere_level = self.sphere.select(self.sphere_level, pixels);
+
+        self.drawn_sphere = sphere_transform;
+        self.drawn_wobble = self.sphere_phase.at(alpha) * 8.0;
+
+        let background = frame.background;
+        let far = self.depth_convention.far_depth();
+        let (albedo, normal, depth) = (self.albedo, self.normal, self.depth);
+        // Multisampled, the g-buffer pass tests against its own depth and the pre-pass lays
+        // down the single sampled one lighting reads
+        let msaa_depth = self.msaa_depth.filter(|_| self.samples > 1);
+        let gbuffer_depth = msaa_depth.unwrap_or(depth);
+        let this = &*self;
+        // Both geometry passes draw through it, one after the other
+        let pool = RefCell::new(pool);
+        let mut graph = RenderGraph::new();
+        let (gbuffer_reads, stage) = if self.depth_prepass || msaa_depth.is_some() {
+            graph.add_pass("Depth Pre-Pass", &[], &[depth.into()], |frame, loads| {
+                let mut pass = frame.pass_with_depth(
+                    "Depth Pre-Pass",
+                    &[],
+                    Some((depth, loads.load(depth, background, far, far))),
+                    registry,
+                );
+                pass.measure();
+                pass.raw.set_bind_group(0, &this.camera_bind_group, &[]);
+                let mut items = this.draw_items();
+                material::draw_sorted(
+                    device,
+                    queue,
+                    &mut pool.borrow_mut(),
+                    &mut pass,
+                    bank,
+                    &this.materials,
+                    &mut items,
+                    1,
+                    DepthStage::Prepass,
+                )
+            });
+            let stage = if msaa_depth.is_some() {
+                DepthStage::Single
+            } else {
+                DepthStage::Shaded
+            };
+            (vec![depth.into()], stage)
+        } else {
+            (Vec::new(), DepthStage::Single)
+        };
+        graph.add_pass(
+            "G-Buffer Pass",
+            &gbuffer_reads,
+            &[albedo.into(), normal.into(), gbuffer_depth.into()],
+            |frame, loads| {
+                let mut pass = frame.pass_with_depth(
+                    "G-Buffer Pass",
+                    &[
+                        (
+                            ColorTarget::Offscreen(albedo),
+                            loads.load(albedo, background, wgpu::Color::BLACK, DEBUG_MAGENTA),
+                        ),
+                        (
+                            ColorTarget::Offscreen(normal),
+                            loads.load(
+                                normal,
+                                background,
+                                wgpu::Color::TRANSPARENT,
+                                wgpu::Color::TRANSPARENT,
+                            ),
+                        ),
+                    ],
+                    Some((
+                        gbuffer_depth,
+                        loads.load(gbuffer_depth, background, far, far),
+                    )),
+                    registry,
+                );
+                pass.measure();
+                pass.raw.set_bind_group(0, &this.camera_bind_group, &[]);
+                let mut items = this.draw_items();
+                material::draw_sorted(
+                    device,
+                    queue,
+                    &mut pool.borrow_mut(),
+                    &mut pass,
+                    bank,
+                    &this.materials,
+                    &mut items,
+                    1,
+                    stage,
+                )
+            },
+        );
+        graph.add_pass(
+            "Lighting Pass",
+            &[albedo.into(), normal.into(), depth.into()],
+            &[output.into()],
+            |frame, loads| {
+                frame.fullscreen_pass(
+                    "Lighting Pass",
+                    output,
+                    loads.color(output, background),
+                    registry,
+                    bank,
+                    match output {
+                        ColorTarget::Swapchain | ColorTarget::SwapchainUi => "deferred_lighting",
+                        ColorTarget::Offscreen(_) => "deferred_lighting_hdr",
+                    },
+                    &[
+                        &this.camera_bind_group,
+                        registry.bind_group(&this.gbuffer_bind_group),
+                    ],
+                )
+            },
+        );
+        graph.output(output);
+        graph.execute(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "textures")]
+    use crate::frame::Background;
+    use crate::gpu_context::GpuContext;
+
+    const SIZE: (u32, u32) = (160, 120);
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // The demo's first frame, the grass card in front of the cubes, after set_samples went
+    // through each of `samples`
+    #[cfg(feature = "textures")]
+    fn render(gpu: &GpuContext, samples: &[u32]) -> image::RgbaImage {
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let memory = GpuMemoryTracker::new();
+        let mut registry = TargetRegistry::new(SIZE, &memory);
+        let mut bank = RenderPipelineBank::new();
+        let mut deferred = DeferredDemo::new(
+            device,
+            queue,
+            FORMAT,
+            &mut registry,
+            &mut bank,
+            &memory,
+            DepthConvention::Standard,
+        );
+        for &count in samples {
+            deferred.set_samples(device, &mut registry, count).unwrap();
+        }
+        let mut pool = BufferPool::new(&memory, 0);
+        let texture = gpu.target(SIZE, FORMAT);
+        let mut frame = Frame::offscreen(
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            device,
+            FORMAT,
+            Background::Clear(wgpu::Color::BLACK),
+        );
+        deferred
+            .draw(
+                device,
+                queue,
+                &mut frame,
+                &registry,
+                &bank,
+                &mut pool,
+                ColorTarget::Swapchain,
+                SIZE,
+                0.0,
+            )
+            .unwrap();
+        frame.finish(queue);
+        gpu.read_back(&texture)
+    }
+
+    #[cfg(feature = "textures")]
+    #[test]
+    fn msaa_softens_the_grass_edges_and_nothing_else() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let single = render(gpu, &[]);
+        let multisampled = render(gpu, &[4]);
+        // Only edges move, the cut out ones and the cubes'
+        let differ = crate::golden::differing(&single, &multisampled);
+        assert!(differ > 0, "MSAA 4 drew the same frame as MSAA 1");
+        assert!(
+            differ < single.pixels().len() / 10,
+            "{differ} pixels changed, more than edges"
+        );
+        // Back at 1 the cutout discards again, as though it had never been multisampled
+        crate::golden::assert_similar(&single, &render(gpu, &[4, 1]), "MSAA 4 then 1");
+        crate::golden::check("deferred_msaa1", &single);
+        crate::golden::check("deferred_msaa4", &multisampled);
+    }
+
+    #[test]
+    fn sample_counts_without_pipelines_are_refused() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let mut registry = TargetRegistry::new(SIZE, &memory);
+        let mut bank = RenderPipelineBank::new();
+        let mut deferred = DeferredDemo::new(
+            &gpu.device,
+            &gpu.queue,
+            FORMAT,
+            &mut registry,
+            &mut bank,
+            &memory,
+            DepthConvention::Standard,
+        );
+        let refused = deferred.set_samples(&gpu.device, &mut registry, 2);
+        assert!(matches!(
+            refused,
+            Err(ForayError::UnsupportedSampleCount { samples: 2, .. })
+        ));
+        assert_eq!(deferred.samples(), 1);
+    }
+}
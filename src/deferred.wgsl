@@ -0,0 +1,194 @@
+// Deferred shading demo: a geometry pass filling the g-buffer, then a fullscreen lighting pass
+#include "fullscreen.wgsl"
+#include "depth.wgsl"
+
+struct Camera {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    light_dir: vec4<f32>, // view space, pointing away from the light
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Mirrors MaterialParams in material.rs
+struct Material {
+    tint: vec4<f32>,
+    roughness: f32,
+    cutoff: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> material: Material;
+// Red is coverage, white for materials that don't set one
+@group(1) @binding(1)
+var material_mask: texture_2d<f32>;
+
+// On for BlendMode::Cutout materials, which discard where the mask is under the cutoff
+override ALPHA_TEST: bool = false;
+// In their place for cutouts drawn multisampled, which write the mask to albedo alpha and
+// let alpha to coverage pick the samples. Lighting doesn't read that alpha
+override ALPHA_TO_COVERAGE: bool = false;
+
+// Alpha for alpha to coverage: a step at the cutoff, spread over about a pixel so the edge
+// covers some of the samples instead of all or none
+fn coverage_alpha(mask: f32, cutoff: f32) -> f32 {
+    return clamp((mask - cutoff) / max(fwidth(mask), 0.0001) + 0.5, 0.0, 1.0);
+}
+
+struct GeometryInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+    // Per instance, the draw item's transform
+    @location(3) model_0: vec4<f32>,
+    @location(4) model_1: vec4<f32>,
+    @location(5) model_2: vec4<f32>,
+    @location(6) model_3: vec4<f32>,
+}
+
+struct GeometryOutput {
+    // The depth pre-pass and the g-buffer pass compare Equal, they have to agree exactly
+    @invariant @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    // Where the mask is looked up, the mesh's own xy from -0.5 to 0.5. The UV for
+    // vs_textured
+    @location(2) local: vec2<f32>,
+}
+
+@vertex
+fn vs_geometry(in: GeometryInput) -> GeometryOutput {
+    return geometry(in);
+}
+
+// Per-item block of the wobbly material, mirrors Wobble in deferred.rs
+struct Wobble {
+    amplitude: f32,
+    frequency: f32,
+    phase: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> wobble: Wobble;
+
+// vs_geometry with the surface pushed in and out along its normal, in bands up the y axis
+@vertex
+fn vs_wobble(in: GeometryInput) -> GeometryOutput {
+    var moved = in;
+    let bands = sin(in.position.y * wobble.frequency + wobble.phase);
+    moved.position += in.normal * wobble.amplitude * bands;
+    return geometry(moved);
+}
+
+fn geometry(in: GeometryInput) -> GeometryOutput {
+    var out: GeometryOutput;
+    let model = mat4x4<f32>(in.model_0, in.model_1, in.model_2, in.model_3);
+    let model_view = camera.view * model;
+    out.clip_position = camera.proj * model_view * vec4<f32>(in.position, 1.0);
+    // Only rotations and uniform scale in the model matrix, so no inverse transpose needed
+    out.normal = (model_view * vec4<f32>(in.normal, 0.0)).xyz;
+    out.color = in.color;
+    out.local = in.position.xy;
+    return out;
+}
+
+struct GBuffer {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
+// Imported glTF meshes, a UV where the demo's meshes have a vertex color
+struct TexturedInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) model_0: vec4<f32>,
+    @location(4) model_1: vec4<f32>,
+    @location(5) model_2: vec4<f32>,
+    @location(6) model_3: vec4<f32>,
+}
+
+@vertex
+fn vs_textured(in: TexturedInput) -> GeometryOutput {
+    var untextured: GeometryInput;
+    untextured.position = in.position;
+    untextured.normal = in.normal;
+    untextured.color = vec3<f32>(1.0);
+    untextured.model_0 = in.model_0;
+    untextured.model_1 = in.model_1;
+    untextured.model_2 = in.model_2;
+    untextured.model_3 = in.model_3;
+    var out = geometry(untextured);
+    out.local = in.uv;
+    return out;
+}
+
+// The material's texture is its base color here. Nearest texel, the material layout has no
+// sampler, and wrapped by hand since glTF UVs repeat
+@fragment
+fn fs_textured(in: GeometryOutput) -> GBuffer {
+    let size = vec2<f32>(textureDimensions(material_mask));
+    let texel = min(vec2<u32>(fract(in.local) * size), vec2<u32>(size) - 1u);
+    let base = textureLoad(material_mask, texel, 0) * material.tint;
+    // Ahead of the discard, derivatives want every pixel of the quad still there
+    let alpha = coverage_alpha(base.a, material.cutoff);
+    if ALPHA_TEST && base.a < material.cutoff {
+        discard;
+    }
+    var out: GBuffer;
+    out.albedo = vec4<f32>(base.rgb, select(1.0, alpha, ALPHA_TO_COVERAGE));
+    out.normal = vec4<f32>(normalize(in.normal), material.roughness);
+    return out;
+}
+
+@fragment
+fn fs_geometry(in: GeometryOutput) -> GBuffer {
+    let size = vec2<f32>(textureDimensions(material_mask));
+    let uv = clamp(vec2<f32>(in.local.x + 0.5, 0.5 - in.local.y), vec2<f32>(0.0), vec2<f32>(1.0));
+    let texel = min(vec2<u32>(uv * size), vec2<u32>(size) - 1u);
+    let coverage = textureLoad(material_mask, texel, 0).r;
+    let alpha = coverage_alpha(coverage, material.cutoff);
+    if ALPHA_TEST && coverage < material.cutoff {
+        discard;
+    }
+    var out: GBuffer;
+    out.albedo = vec4<f32>(in.color * material.tint.rgb, select(1.0, alpha, ALPHA_TO_COVERAGE));
+    // Roughness rides along in the normal target's spare channel
+    out.normal = vec4<f32>(normalize(in.normal), material.roughness);
+    return out;
+}
+
+@group(1) @binding(0)
+var gbuffer_albedo: texture_2d<f32>;
+@group(1) @binding(1)
+var gbuffer_normal: texture_2d<f32>;
+@group(1) @binding(2)
+var gbuffer_depth: texture_depth_2d;
+
+@fragment
+fn fs_lighting(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    let depth = textureLoad(gbuffer_depth, coord, 0);
+    if is_far_plane(depth) {
+        return vec4<f32>(0.05, 0.05, 0.08, 1.0);
+    }
+
+    let position = view_position(camera.inv_proj, in.uv, depth);
+
+    let albedo = textureLoad(gbuffer_albedo, coord, 0).rgb;
+    let normal_roughness = textureLoad(gbuffer_normal, coord, 0);
+    let normal = normalize(normal_roughness.xyz);
+    let roughness = normal_roughness.w;
+    let to_light = -normalize(camera.light_dir.xyz);
+    let to_eye = normalize(-position);
+
+    let diffuse = max(dot(normal, to_light), 0.0);
+    let halfway = normalize(to_light + to_eye);
+    let shininess = mix(128.0, 4.0, roughness);
+    let specular = pow(max(dot(normal, halfway), 0.0), shininess) * (1.0 - roughness);
+    let ambient = 0.1;
+
+    return vec4<f32>(albedo * (ambient + diffuse) + vec3<f32>(specular * 0.5), 1.0);
+}
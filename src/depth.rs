@@ -0,0 +1,407 @@
+use glam::Mat4;
+
+// The WGSL override depth.wgsl declares, for pipelines that read depth back
+pub const REVERSE_Z: &str = "REVERSE_Z";
+
+// Which way depth runs in every 3D pass. Picked once at startup (--depth) and handed to
+// everything that projects, tests or reads depth, so no two passes can disagree. Reversed
+// puts the near plane at 1 and far at 0, where floats have their precision to spare
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DepthConvention {
+    Standard,
+    Reversed,
+    // Reversed with the far plane at infinity
+    ReversedInfinite,
+}
+
+impl DepthConvention {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(DepthConvention::Standard),
+            "reverse" => Some(DepthConvention::Reversed),
+            "reverse-infinite" => Some(DepthConvention::ReversedInfinite),
+            _ => None,
+        }
+    }
+
+    pub fn is_reversed(self) -> bool {
+        self != DepthConvention::Standard
+    }
+
+    // Right handed, depth 0..1 the way this convention runs. `far` is ignored for
+    // ReversedInfinite
+    pub fn perspective(self, fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        match self {
+            DepthConvention::Standard => Mat4::perspective_rh(fov_y, aspect, near, far),
+            // Swapping the planes is all it takes
+            DepthConvention::Reversed => Mat4::perspective_rh(fov_y, aspect, far, near),
+            DepthConvention::ReversedInfinite => {
+                Mat4::perspective_infinite_reverse_rh(fov_y, aspect, near)
+            }
+        }
+    }
+
+    // What a depth buffer is cleared to, the far plane
+    pub fn far_depth(self) -> f32 {
+        if self.is_reversed() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    // `compare` as written for standard depth, turned around when reversed
+    pub fn compare(self, compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        use wgpu::CompareFunction::{Greater, GreaterEqual, Less, LessEqual};
+        if !self.is_reversed() {
+            return compare;
+        }
+        match compare {
+            Less => Greater,
+            LessEqual => GreaterEqual,
+            Greater => Less,
+            GreaterEqual => LessEqual,
+            other => other,
+        }
+    }
+
+    // The value for the REVERSE_Z override
+    pub fn override_value(self) -> f64 {
+        if self.is_reversed() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Vec2, Vec3, Vec4Swizzles};
+
+    use crate::gpu_context::GpuContext;
+    use crate::pipeline_bank::PipelineBuilder;
+    use crate::shaders;
+    use crate::stats;
+
+    const ALL: [DepthConvention; 3] = [
+        DepthConvention::Standard,
+        DepthConvention::Reversed,
+        DepthConvention::ReversedInfinite,
+    ];
+    const NEAR: f32 = 0.1;
+    const FAR: f32 = 1000.0;
+    const FOV_Y: f32 = std::f32::consts::FRAC_PI_3;
+
+    // view_position in depth.wgsl
+    fn view_position(inv_proj: Mat4, uv: Vec2, depth: f32) -> Vec3 {
+        let ndc = glam::Vec4::new(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+        let view = inv_proj * ndc;
+        view.xyz() / view.w
+    }
+
+    // Where `position` lands on screen, uv and depth, the way the rasterizer writes it
+    fn project(proj: Mat4, position: Vec3) -> (Vec2, f32) {
+        let clip = proj * position.extend(1.0);
+        let ndc = clip.xyz() / clip.w;
+        (Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) / 2.0, ndc.z)
+    }
+
+    // How far off the round trip through depth puts points `distance` away, relative to
+    // the distance
+    fn round_trip_error(convention: DepthConvention, distance: f32) -> f32 {
+        let proj = convention.perspective(FOV_Y, 1.5, NEAR, FAR);
+        let position = Vec3::new(0.3 * distance, -0.2 * distance, -distance);
+        let (uv, depth) = project(proj, position);
+        (view_position(proj.inverse(), uv, depth) - position).length() / distance
+    }
+
+    #[test]
+    fn near_and_far_land_where_the_convention_puts_them() {
+        for convention in ALL {
+            let proj = convention.perspective(FOV_Y, 1.5, NEAR, FAR);
+            let (_, near) = project(proj, Vec3::new(0.0, 0.0, -NEAR));
+            let (_, far) = project(proj, Vec3::new(0.0, 0.0, -FAR));
+            let (near_end, far_end) = if convention.is_reversed() {
+                (1.0, 0.0)
+            } else {
+                (0.0, 1.0)
+            };
+            assert!((near - near_end).abs() < 1e-5, "{convention:?} near {near}");
+            // The infinite one only gets there at infinity
+            if convention == DepthConvention::ReversedInfinite {
+                assert!(far > 0.0 && far < 1e-3, "{convention:?} far {far}");
+            } else {
+                assert!((far - far_end).abs() < 1e-5, "{convention:?} far {far}");
+            }
+            // And the clear value is the far end, what nothing drawn can beat
+            assert!((convention.far_depth() - far_end).abs() < f32::EPSILON);
+            assert!((convention.override_value() - f64::from(near_end)).abs() < f64::EPSILON);
+        }
+    }
+
+    #[test]
+    fn compares_only_turn_around_when_reversed() {
+        use wgpu::CompareFunction::{Always, Equal, Greater, GreaterEqual, Less, LessEqual};
+        for (standard, reversed) in [
+            (Less, Greater),
+            (LessEqual, GreaterEqual),
+            (Greater, Less),
+            (GreaterEqual, LessEqual),
+            (Equal, Equal),
+            (Always, Always),
+        ] {
+            assert_eq!(DepthConvention::Standard.compare(standard), standard);
+            assert_eq!(DepthConvention::Reversed.compare(standard), reversed);
+            assert_eq!(
+                DepthConvention::ReversedInfinite.compare(standard),
+                reversed
+            );
+        }
+        for name in ["standard", "reverse", "reverse-infinite"] {
+            assert!(DepthConvention::parse(name).is_some());
+        }
+        assert_eq!(DepthConvention::parse("reversed"), None);
+    }
+
+    #[test]
+    fn view_positions_come_back_from_depth() {
+        for convention in ALL {
+            for distance in [0.5, 1.0, 7.5, 30.0, 100.0] {
+                // Standard depth is already a hundredth of a percent out at 100
+                let error = round_trip_error(convention, distance);
+                assert!(error < 1e-3, "{convention:?} at {distance}: {error}");
+            }
+        }
+    }
+
+    #[test]
+    fn reversed_depth_holds_its_precision_at_range() {
+        let total = |convention| {
+            [200.0, 400.0, 650.0, 900.0]
+                .into_iter()
+                .map(|distance| round_trip_error(convention, distance))
+                .sum::<f32>()
+        };
+        let standard = total(DepthConvention::Standard);
+        for convention in [DepthConvention::Reversed, DepthConvention::ReversedInfinite] {
+            let reversed = total(convention);
+            assert!(
+                reversed * 10.0 < standard,
+                "{convention:?} {reversed} against standard {standard}"
+            );
+        }
+    }
+
+    // A floor a unit under the eye out to 200 units ahead, then its view positions back out
+    // of the depth buffer through depth.wgsl, zero where nothing was drawn
+    const SHADER: &str = "
+        #include \"fullscreen.wgsl\"
+        #include \"depth.wgsl\"
+
+        struct Camera {
+            proj: mat4x4<f32>,
+            inv_proj: mat4x4<f32>,
+        };
+
+        @group(0) @binding(0) var<uniform> camera: Camera;
+        @group(1) @binding(0) var depth_buffer: texture_depth_2d;
+
+        @vertex
+        fn vs_floor(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            var corners = array<vec2<f32>, 6>(
+                vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(-1.0, 1.0),
+                vec2<f32>(-1.0, 1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+            );
+            let corner = corners[index];
+            let position = vec3<f32>(corner.x * 200.0, -1.0, -100.25 + corner.y * 99.75);
+            return camera.proj * vec4<f32>(position, 1.0);
+        }
+
+        @fragment
+        fn fs_positions(in: FullscreenOutput) -> @location(0) vec4<f32> {
+            let depth = textureLoad(depth_buffer, vec2<i32>(in.clip_position.xy), 0);
+            if is_far_plane(depth) {
+                return vec4<f32>(0.0);
+            }
+            return vec4<f32>(view_position(camera.inv_proj, in.uv, depth), 1.0);
+        }
+    ";
+
+    // What the GPU rebuilds for every pixel, xyz and 1 where the floor is
+    fn rebuilt(gpu: &GpuContext, convention: DepthConvention, size: (u32, u32)) -> Vec<[f32; 4]> {
+        let device = &gpu.device;
+        let proj = convention.perspective(FOV_Y, size.0 as f32 / size.1 as f32, NEAR, FAR);
+        let camera = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Depth Test Camera"),
+                contents: bytemuck::cast_slice(&[proj, proj.inverse()]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        let layout = |label, ty| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty,
+                    count: None,
+                }],
+            })
+        };
+        let camera_layout = layout(
+            "Depth Test Camera Layout",
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+        );
+        let depth_layout = layout(
+            "Depth Test Depth Layout",
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+        );
+        let shader = shaders::create_module(device, "Depth Test Shader", SHADER);
+        let floor = PipelineBuilder::new("Depth Test Floor", &shader)
+            .vertex_entry("vs_floor")
+            .bind_group_layout(&camera_layout)
+            .cull_mode(None)
+            .depth(
+                wgpu::TextureFormat::Depth32Float,
+                wgpu::CompareFunction::Less,
+            )
+            .depth_convention(convention)
+            .depth_only()
+            .build(device, wgpu::TextureFormat::Rgba32Float);
+        let positions = PipelineBuilder::new("Depth Test Positions", &shader)
+            .vertex_entry("vs_fullscreen")
+            .fragment_entry("fs_positions")
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&depth_layout)
+            .blend(None)
+            .constant(REVERSE_Z, convention.override_value())
+            .build(device, wgpu::TextureFormat::Rgba32Float);
+
+        let depth = gpu.target(size, wgpu::TextureFormat::Depth32Float);
+        let output = gpu.target(size, wgpu::TextureFormat::Rgba32Float);
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = |layout, resource| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Depth Test Bind Group"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource,
+                }],
+            })
+        };
+        let camera_group = bind_group(&camera_layout, camera.as_entire_binding());
+        let depth_group = bind_group(
+            &depth_layout,
+            wgpu::BindingResource::TextureView(&depth_view),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Depth Test Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Test Floor Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(convention.far_depth()),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&floor.raw);
+            pass.set_bind_group(0, &camera_group, &[]);
+            pass.draw(0..6, 0..1);
+        }
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Test Positions Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&positions.raw);
+            pass.set_bind_group(0, &camera_group, &[]);
+            pass.set_bind_group(1, &depth_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        stats::submit(&gpu.queue, std::iter::once(encoder.finish()));
+        bytemuck::cast_slice(&gpu.read_texels(&output)).to_vec()
+    }
+
+    #[test]
+    fn the_gpu_rebuilds_view_positions_in_every_convention() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let size = (64, 48);
+        let aspect = size.0 as f32 / size.1 as f32;
+        let half_height = (FOV_Y / 2.0).tan();
+        for convention in ALL {
+            let texels = rebuilt(gpu, convention, size);
+            let (mut floor, mut sky) = (0, 0);
+            for (index, texel) in texels.iter().enumerate() {
+                let (x, y) = (index as u32 % size.0, index as u32 / size.0);
+                // The ray through the pixel's center, one unit ahead
+                let ray = Vec3::new(
+                    ((x as f32 + 0.5) / size.0 as f32 * 2.0 - 1.0) * half_height * aspect,
+                    (1.0 - (y as f32 + 0.5) / size.1 as f32 * 2.0) * half_height,
+                    -1.0,
+                );
+                // Nothing under the horizon, that's the cleared far plane
+                if ray.y > 0.01 {
+                    assert!(
+                        texel[3] < 0.5,
+                        "{convention:?} ({x}, {y}) isn't far: {texel:?}"
+                    );
+                    sky += 1;
+                    continue;
+                }
+                // Where it meets the floor, well short of the floor's far edge
+                let expected = ray * (-1.0 / ray.y);
+                if ray.y > -0.02 || expected.z < -150.0 {
+                    continue;
+                }
+                let actual = Vec3::new(texel[0], texel[1], texel[2]);
+                let error = (actual - expected).length() / expected.length();
+                assert!(
+                    texel[3] > 0.5 && error < 2e-3,
+                    "{convention:?} ({x}, {y}): {actual} instead of {expected}"
+                );
+                floor += 1;
+            }
+            // Both halves of the screen were looked at
+            assert!(
+                floor > 100 && sky > 100,
+                "{floor} floor and {sky} sky pixels"
+            );
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// Depth the way DepthConvention (depth.rs) runs it, for shaders that read a depth buffer.
+// Set through the REVERSE_Z override, the projection matrices already account for it
+override REVERSE_Z: bool = false;
+
+// What a cleared depth buffer holds, nothing was drawn there
+fn is_far_plane(depth: f32) -> bool {
+    return select(depth >= 1.0, depth <= 0.0, REVERSE_Z);
+}
+
+// Back from a depth buffer texel to where it is in view space. `uv` runs 0..1 down the
+// screen, `inv_proj` undoes either convention
+fn view_position(inv_proj: mat4x4<f32>, uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let view_h = inv_proj * ndc;
+    return view_h.xyz / view_h.w;
+}
@@ -0,0 +1,215 @@
+use std::ops::Range;
+
+// Clean runs of up to this many elements between two dirty ones get written along with
+// them. One write with a little extra beats hundreds of tiny ones
+pub const MERGE_GAP: usize = 16;
+// With more than this fraction of the elements dirty, the whole array is written
+pub const FULL_UPLOAD: f32 = 0.5;
+// Marks kept before giving up and calling it all dirty, for owners that keep marking while
+// nothing takes them (a view that doesn't draw the scene, say)
+const MAX_MARKS: usize = 4096;
+
+// Which items of an array kept on the GPU changed since it was last written. Items are
+// whatever the owner marks by (scene items, say) and can each cover several elements, see
+// take_ranges. Starts out all dirty, nothing has been written yet
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirtySet {
+    all: bool,
+    items: Vec<usize>,
+}
+
+impl Default for DirtySet {
+    fn default() -> Self {
+        Self {
+            all: true,
+            items: Vec::new(),
+        }
+    }
+}
+
+impl DirtySet {
+    pub fn mark(&mut self, item: usize) {
+        if self.all || self.items.last() == Some(&item) {
+            return;
+        }
+        if self.items.len() >= MAX_MARKS {
+            self.mark_all();
+        } else {
+            self.items.push(item);
+        }
+    }
+
+    // Items came or went or changed how many elements they take, so everything after the
+    // change moved and nothing written before can be kept
+    pub fn mark_all(&mut self) {
+        self.all = true;
+        self.items.clear();
+    }
+
+    // The element ranges to write for what was marked, clearing the marks. `spans` is each
+    // item's elements and `len` the whole array's length. Marks past the end of `spans` are
+    // items that are gone, they're dropped
+    pub fn take_ranges(&mut self, spans: &[Range<usize>], len: usize) -> Vec<Range<usize>> {
+        let all = std::mem::replace(&mut self.all, false);
+        let items = std::mem::take(&mut self.items);
+        if all {
+            return if len > 0 { everything(len) } else { Vec::new() };
+        }
+        coalesce(
+            items
+                .into_iter()
+                .filter_map(|item| spans.get(item).cloned())
+                .collect(),
+            len,
+        )
+    }
+}
+
+// Sorted, overlapping and nearby ranges (within MERGE_GAP) merged, empty ones dropped. The
+// whole of 0..len once that covers more than FULL_UPLOAD of it. Whatever lies past `len`
+// (spans from before the array shrank) is cut off, it has nowhere to go
+pub fn coalesce(mut ranges: Vec<Range<usize>>, len: usize) -> Vec<Range<usize>> {
+    for range in &mut ranges {
+        range.end = range.end.min(len);
+    }
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end + MERGE_GAP => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    let dirty: usize = merged.iter().map(ExactSizeIterator::len).sum();
+    if len > 0 && dirty as f32 > len as f32 * FULL_UPLOAD {
+        return everything(len);
+    }
+    merged
+}
+
+// The one range 0..len, as the full upload
+#[allow(clippy::single_range_in_vec_init)]
+pub fn everything(len: usize) -> Vec<Range<usize>> {
+    vec![0..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_merge_across_gaps_up_to_merge_gap() {
+        let len = 1000;
+        // A gap of exactly MERGE_GAP is written through, one more isn't
+        assert_eq!(
+            coalesce(vec![0..4, 20..24], len),
+            std::slice::from_ref(&(0..24))
+        );
+        assert_eq!(coalesce(vec![0..4, 21..25], len), [0..4, 21..25]);
+        // Out of order, overlapping, touching and empty ones
+        assert_eq!(
+            coalesce(
+                vec![300..310, 5..5, 100..120, 110..130, 130..140, 400..400],
+                len
+            ),
+            [100..140, 300..310]
+        );
+        // Merging chains along, each range measured from the end of the last
+        let chain: Vec<_> = (0..10).map(|i| i * 20..i * 20 + 4).collect();
+        assert_eq!(coalesce(chain, len), std::slice::from_ref(&(0..184)));
+        assert!(coalesce(Vec::new(), len).is_empty());
+    }
+
+    #[test]
+    fn more_than_full_upload_dirty_writes_everything() {
+        let len = 100;
+        // Exactly half isn't more than FULL_UPLOAD
+        assert_eq!(coalesce(vec![0..25, 50..75], len), [0..25, 50..75]);
+        assert_eq!(coalesce(vec![0..26, 50..75], len), everything(len));
+        // What merging wrote through counts as dirty too: 0..60 after merging
+        assert_eq!(coalesce(vec![0..20, 30..60], len), everything(len));
+        // An empty array has nothing to write whatever comes in
+        assert!(coalesce(everything(10), 0).is_empty());
+    }
+
+    #[test]
+    fn marks_turn_into_the_items_element_ranges() {
+        // Five items of ten elements each
+        let spans: Vec<_> = (0..5).map(|i| i * 10..i * 10 + 10).collect();
+        let mut dirty = DirtySet::default();
+        // Nothing's been written yet
+        assert_eq!(
+            dirty.take_ranges(&spans, 50),
+            std::slice::from_ref(&(0..50))
+        );
+        assert!(dirty.take_ranges(&spans, 50).is_empty());
+
+        dirty.mark(4);
+        dirty.mark(0);
+        dirty.mark(0);
+        assert_eq!(dirty.take_ranges(&spans, 50), [0..10, 40..50]);
+        // Items 0 and 2 with item 1 between them, a gap of 10, written through
+        dirty.mark(2);
+        dirty.mark(0);
+        assert_eq!(
+            dirty.take_ranges(&spans, 200),
+            std::slice::from_ref(&(0..30))
+        );
+        // Taking clears the marks
+        assert!(dirty.take_ranges(&spans, 50).is_empty());
+    }
+
+    #[test]
+    fn marks_past_the_end_are_dropped() {
+        let spans: Vec<_> = (0..5).map(|i| i * 10..i * 10 + 10).collect();
+        let mut dirty = DirtySet::default();
+        dirty.take_ranges(&spans, 50);
+        // Items 5 and up are gone
+        dirty.mark(7);
+        dirty.mark(1);
+        dirty.mark(5);
+        assert_eq!(
+            dirty.take_ranges(&spans, 50),
+            std::slice::from_ref(&(10..20))
+        );
+        // Spans from before the array shrank are cut to what's left of it
+        dirty.mark(3);
+        dirty.mark(4);
+        assert_eq!(
+            dirty.take_ranges(&spans, 35),
+            std::slice::from_ref(&(30..35))
+        );
+        dirty.mark(4);
+        assert!(dirty.take_ranges(&spans, 35).is_empty());
+    }
+
+    #[test]
+    fn too_many_marks_or_mark_all_write_everything() {
+        let spans: Vec<_> = (0..=MAX_MARKS).map(|i| i..i + 1).collect();
+        let len = spans.len() * 100;
+        let mut dirty = DirtySet::default();
+        dirty.take_ranges(&spans, len);
+        for item in 0..MAX_MARKS {
+            dirty.mark(item);
+        }
+        // Sparse enough not to be a full upload on their own, just too many to keep
+        assert_eq!(
+            dirty.take_ranges(&spans, len),
+            std::slice::from_ref(&(0..MAX_MARKS))
+        );
+        for item in 0..=MAX_MARKS {
+            dirty.mark(item);
+        }
+        assert_eq!(dirty.take_ranges(&spans, len), everything(len));
+
+        dirty.mark(3);
+        dirty.mark_all();
+        // Marks after mark_all add nothing to it
+        dirty.mark(1);
+        assert_eq!(dirty.take_ranges(&spans, len), everything(len));
+        assert!(dirty.take_ranges(&spans, len).is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+#include "post.wgsl"
+
+// Must match MAX_PALETTE in effects.rs
+const MAX_PALETTE: u32 = 16u;
+
+struct Dither {
+    // Steps per channel when there's no palette
+    levels: f32,
+    // 4 or 8, the Bayer matrix side
+    matrix_size: u32,
+    // How many of `palette` are used, 0 quantizes each channel to `levels` instead
+    palette_len: u32,
+    // Dither offset in quantization steps, 0 is plain banding
+    spread: f32,
+    // sRGB
+    palette: array<vec4<f32>, MAX_PALETTE>,
+};
+
+@group(1) @binding(0) var<uniform> dither: Dither;
+
+// 8x8 Bayer matrix, the 4x4 one is its top left quarter divided down
+const BAYER: array<u32, 64> = array<u32, 64>(
+     0u, 32u,  8u, 40u,  2u, 34u, 10u, 42u,
+    48u, 16u, 56u, 24u, 50u, 18u, 58u, 26u,
+    12u, 44u,  4u, 36u, 14u, 46u,  6u, 38u,
+    60u, 28u, 52u, 20u, 62u, 30u, 54u, 22u,
+     3u, 35u, 11u, 43u,  1u, 33u,  9u, 41u,
+    51u, 19u, 59u, 27u, 49u, 17u, 57u, 25u,
+    15u, 47u,  7u, 39u, 13u, 45u,  5u, 37u,
+    63u, 31u, 55u, 23u, 61u, 29u, 53u, 21u,
+);
+
+// In [0, 1), centered on the cell
+fn threshold(pixel: vec2<u32>) -> f32 {
+    if dither.matrix_size == 4u {
+        let cell = pixel % 4u;
+        return (f32(BAYER[cell.y * 8u + cell.x] / 4u) + 0.5) / 16.0;
+    }
+    let cell = pixel % 8u;
+    return (f32(BAYER[cell.y * 8u + cell.x]) + 0.5) / 64.0;
+}
+
+fn to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let c1 = clamp(c, vec3<f32>(0.0), vec3<f32>(1.0));
+    return select(1.055 * pow(c1, vec3<f32>(1.0 / 2.4)) - 0.055, c1 * 12.92, c1 <= vec3<f32>(0.0031308));
+}
+
+fn to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn nearest(color: vec3<f32>) -> vec3<f32> {
+    var best = dither.palette[0].rgb;
+    var best_distance = distance(color, best);
+    for (var i = 1u; i < min(dither.palette_len, MAX_PALETTE); i++) {
+        let candidate = dither.palette[i].rgb;
+        let d = distance(color, candidate);
+        if d < best_distance {
+            best = candidate;
+            best_distance = d;
+        }
+    }
+    return best;
+}
+
+// Quantizes in sRGB, where the steps are evenly spaced to the eye, the way the old
+// hardware this imitates did it
+@fragment
+fn fs_dither(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = to_srgb(textureSample(input, input_sampler, in.uv).rgb);
+    let offset = (threshold(vec2<u32>(in.clip_position.xy)) - 0.5) * dither.spread;
+    var result: vec3<f32>;
+    if dither.palette_len > 0u {
+        // A palette has no even step, spread it as if its colors were evenly spaced
+        let step = 1.0 / max(f32(dither.palette_len) - 1.0, 1.0);
+        result = nearest(color + offset * step);
+    } else {
+        let steps = max(round(dither.levels), 2.0) - 1.0;
+        result = clamp(round(color * steps + offset) / steps, vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+    return vec4<f32>(to_linear(result), 1.0);
+}
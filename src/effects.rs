@@ -0,0 +1,757 @@
+use glam::Mat3;
+
+use crate::bind_groups::{BindGroupBuilder, BindGroupHandle};
+use crate::colors::RgbaColor;
+use crate::lut::LutData;
+use crate::memory::{GpuMemoryTracker, Tracked};
+use crate::post::Effect;
+use crate::shaders;
+use crate::targets::TargetRegistry;
+
+// Mirrors `struct Vignette` in vignette.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VignetteParams {
+    pub strength: f32,
+    pub radius: f32,
+    pub softness: f32,
+    _padding: f32,
+}
+
+// Darkens towards the corners
+pub struct Vignette {
+    pub params: VignetteParams,
+    shader: wgpu::ShaderModule,
+}
+
+impl Vignette {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            params: VignetteParams {
+                strength: 0.6,
+                radius: 0.5,
+                softness: 0.9,
+                _padding: 0.0,
+            },
+            shader: shaders::create_module(
+                device,
+                "Vignette Shader",
+                include_str!("vignette.wgsl"),
+            ),
+        }
+    }
+}
+
+impl Effect for Vignette {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_vignette")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&self.params).to_vec()
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "strength" => Some(&mut self.params.strength),
+            "radius" => Some(&mut self.params.radius),
+            "softness" => Some(&mut self.params.softness),
+            _ => None,
+        }
+    }
+}
+
+// Mirrors `struct Grade` in grade.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GradeParams {
+    pub exposure: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+    pub lut_intensity: f32,
+}
+
+// Exposure, contrast and saturation in linear, then a lookup table
+pub struct ColorGrade {
+    pub params: GradeParams,
+    shader: wgpu::ShaderModule,
+    layout: wgpu::BindGroupLayout,
+    // Kept alive for the bind group
+    _lut: Tracked<wgpu::Texture>,
+    bind_group: BindGroupHandle,
+}
+
+impl ColorGrade {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        registry: &mut TargetRegistry,
+        lut: &LutData,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grade LUT Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let (texture, view) = lut.upload(device, queue, memory);
+        // Linear in all three directions is the trilinear lookup, clamped so the edges
+        // don't wrap around
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Grade LUT Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = registry.create_bind_group(
+            device,
+            BindGroupBuilder::new("Grade LUT Bind Group", &layout)
+                .view(0, &view)
+                .sampler(1, &sampler),
+        );
+
+        Self {
+            params: GradeParams {
+                exposure: 0.3,
+                contrast: 1.15,
+                saturation: 1.2,
+                lut_intensity: 1.0,
+            },
+            shader: shaders::create_module(device, "Grade Shader", include_str!("grade.wgsl")),
+            layout,
+            _lut: texture,
+            bind_group,
+        }
+    }
+}
+
+impl Effect for ColorGrade {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_grade")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&self.params).to_vec()
+    }
+
+    fn extra_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+        Some(&self.layout)
+    }
+
+    fn bind_group(&self) -> Option<&BindGroupHandle> {
+        Some(&self.bind_group)
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "exposure" => Some(&mut self.params.exposure),
+            "contrast" => Some(&mut self.params.contrast),
+            "saturation" => Some(&mut self.params.saturation),
+            "lut_intensity" => Some(&mut self.params.lut_intensity),
+            _ => None,
+        }
+    }
+}
+
+// Mirrors `struct Pixelate` in pixelate.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PixelateParams {
+    pub pixel_size: f32,
+    _padding: [f32; 3],
+}
+
+// Blocky low resolution look, each block is one texel of the input
+pub struct Pixelate {
+    pub params: PixelateParams,
+    shader: wgpu::ShaderModule,
+}
+
+impl Pixelate {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            params: PixelateParams {
+                pixel_size: 6.0,
+                _padding: [0.0; 3],
+            },
+            shader: shaders::create_module(
+                device,
+                "Pixelate Shader",
+                include_str!("pixelate.wgsl"),
+            ),
+        }
+    }
+}
+
+impl Effect for Pixelate {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_pixelate")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&self.params).to_vec()
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "pixel_size" => Some(&mut self.params.pixel_size),
+            _ => None,
+        }
+    }
+}
+
+// Must match MAX_PALETTE in dither.wgsl
+pub const MAX_PALETTE: usize = 16;
+
+// Mirrors `struct Dither` in dither.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DitherUniform {
+    levels: f32,
+    matrix_size: u32,
+    palette_len: u32,
+    spread: f32,
+    palette: [[f32; 4]; MAX_PALETTE],
+}
+
+// Ordered dithering down to a few levels per channel, or to the nearest colors of a palette.
+// The uniform block is rebuilt from these every frame, so changing the levels or the
+// palette never touches the pipeline
+pub struct Dither {
+    pub levels: f32,
+    pub spread: f32,
+    // Side of the Bayer matrix, 8 or more is the 8x8 one, anything else 4x4
+    pub matrix_size: f32,
+    palette: Vec<RgbaColor>,
+    shader: wgpu::ShaderModule,
+}
+
+impl Dither {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            levels: 4.0,
+            spread: 1.0,
+            matrix_size: 4.0,
+            palette: Vec::new(),
+            shader: shaders::create_module(device, "Dither Shader", include_str!("dither.wgsl")),
+        }
+    }
+
+    // An empty palette goes back to per channel levels. Past MAX_PALETTE colors are dropped
+    pub fn set_palette(&mut self, palette: &[RgbaColor]) {
+        if palette.len() > MAX_PALETTE {
+            log::warn!(
+                "Dither palettes hold up to {MAX_PALETTE} colors, dropping the last {}",
+                palette.len() - MAX_PALETTE
+            );
+        }
+        self.palette = palette.iter().take(MAX_PALETTE).copied().collect();
+    }
+}
+
+impl Effect for Dither {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_dither")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        // The shader matches in sRGB, so the colors go in as they were picked
+        let mut palette = [[0.0; 4]; MAX_PALETTE];
+        for (slot, color) in palette.iter_mut().zip(&self.palette) {
+            *slot = color.to_f32_array();
+            slot[3] = 1.0;
+        }
+        let uniform = DitherUniform {
+            levels: self.levels,
+            matrix_size: if self.matrix_size >= 8.0 { 8 } else { 4 },
+            palette_len: self.palette.len() as u32,
+            spread: self.spread,
+            palette,
+        };
+        bytemuck::bytes_of(&uniform).to_vec()
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "levels" => Some(&mut self.levels),
+            "spread" => Some(&mut self.spread),
+            "matrix_size" => Some(&mut self.matrix_size),
+            _ => None,
+        }
+    }
+}
+
+// Which kind of dichromacy the color blind effect simulates
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorBlindMode {
+    Off,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl ColorBlindMode {
+    pub const ALL: [ColorBlindMode; 4] = [
+        ColorBlindMode::Off,
+        ColorBlindMode::Deuteranopia,
+        ColorBlindMode::Protanopia,
+        ColorBlindMode::Tritanopia,
+    ];
+
+    // Off, deut, prot, trit, and round again
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorBlindMode::Off => "off",
+            ColorBlindMode::Deuteranopia => "deuteranopia",
+            ColorBlindMode::Protanopia => "protanopia",
+            ColorBlindMode::Tritanopia => "tritanopia",
+        }
+    }
+
+    // The full name or its first four letters, "deut" and friends
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == text || mode.name().get(..4) == Some(text.as_str()))
+    }
+
+    // Back from the "mode" param, which timelines may have left between two modes
+    fn from_param(value: f32) -> Self {
+        Self::ALL[(value.round().max(0.0) as usize).min(Self::ALL.len() - 1)]
+    }
+
+    pub fn to_param(self) -> f32 {
+        self as usize as f32
+    }
+
+    // Linear RGB to what a dichromat sees, in linear RGB. Goes through LMS cone space with
+    // the Viénot, Brettel and Mollon (1999) matrices, replaces the missing cone's response
+    // with what the other two predict for it, and goes back
+    fn simulation(self) -> Mat3 {
+        // glam takes columns, so each line below is a column of the matrix as the paper
+        // writes it
+        let rgb_to_lms = Mat3::from_cols_array(&[
+            17.8824, 3.45565, 0.0299566, //
+            43.5161, 27.1554, 0.184309, //
+            4.11935, 3.86714, 1.46709,
+        ]);
+        let lms_to_rgb = rgb_to_lms.inverse();
+        let project = match self {
+            ColorBlindMode::Off => return Mat3::IDENTITY,
+            // M from L and S
+            ColorBlindMode::Deuteranopia => Mat3::from_cols_array(&[
+                1.0, 0.494207, 0.0, //
+                0.0, 0.0, 0.0, //
+                0.0, 1.24827, 1.0,
+            ]),
+            // L from M and S
+            ColorBlindMode::Protanopia => Mat3::from_cols_array(&[
+                0.0, 0.0, 0.0, //
+                2.02344, 1.0, 0.0, //
+                -2.52581, 0.0, 1.0,
+            ]),
+            // S from L and M
+            ColorBlindMode::Tritanopia => Mat3::from_cols_array(&[
+                1.0, 0.0, -0.395913, //
+                0.0, 1.0, 0.801109, //
+                0.0, 0.0, 0.0,
+            ]),
+        };
+        lms_to_rgb * project * rgb_to_lms
+    }
+}
+
+// Mirrors `struct ColorBlind` in colorblind.wgsl, a mat3x3 is three padded columns
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColorBlindUniform {
+    simulation: [[f32; 4]; 3],
+}
+
+// Shows the frame the way someone with a dichromacy would see it. The chain hands effects
+// linear colors, which is what the simulation matrices are for, so there's no decoding.
+// Goes last so it sees what the other effects did
+pub struct ColorBlind {
+    // A ColorBlindMode as a number, see ColorBlindMode::to_param
+    pub mode: f32,
+    shader: wgpu::ShaderModule,
+}
+
+impl ColorBlind {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            mode: ColorBlindMode::Off.to_param(),
+            shader: shaders::create_module(
+                device,
+                "Color Blind Shader",
+                include_str!("colorblind.wgsl"),
+            ),
+        }
+    }
+}
+
+impl Effect for ColorBlind {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_colorblind")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        let simulation = ColorBlindMode::from_param(self.mode).simulation();
+        let uniform = ColorBlindUniform {
+            simulation: [
+                simulation.x_axis.extend(0.0).to_array(),
+                simulation.y_axis.extend(0.0).to_array(),
+                simulation.z_axis.extend(0.0).to_array(),
+            ],
+        };
+        bytemuck::bytes_of(&uniform).to_vec()
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "mode" => Some(&mut self.mode),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::{self, Colors};
+    use crate::gpu_context::GpuContext;
+    use crate::post::tests::run_effect;
+
+    const BLACK: RgbaColor = RgbaColor::rgba(0.0, 0.0, 0.0, 1.0);
+
+    // The same matrix as BAYER in dither.wgsl
+    const BAYER: [u32; 64] = [
+        0, 32, 8, 40, 2, 34, 10, 42, //
+        48, 16, 56, 24, 50, 18, 58, 26, //
+        12, 44, 4, 36, 14, 46, 6, 38, //
+        60, 28, 52, 20, 62, 30, 54, 22, //
+        3, 35, 11, 43, 1, 33, 9, 41, //
+        51, 19, 59, 27, 49, 17, 57, 25, //
+        15, 47, 7, 39, 13, 45, 5, 37, //
+        63, 31, 55, 23, 61, 29, 53, 21,
+    ];
+
+    // threshold() in dither.wgsl
+    fn threshold((x, y): (u32, u32), matrix_size: u32) -> f64 {
+        if matrix_size == 4 {
+            let (cx, cy) = (x % 4, y % 4);
+            return (f64::from(BAYER[(cy * 8 + cx) as usize] / 4) + 0.5) / 16.0;
+        }
+        let (cx, cy) = (x % 8, y % 8);
+        (f64::from(BAYER[(cy * 8 + cx) as usize]) + 0.5) / 64.0
+    }
+
+    // fs_dither on the CPU, sRGB in and out
+    fn dither(color: [f64; 3], pixel: (u32, u32), uniform: &DitherUniform) -> [f64; 3] {
+        let offset = (threshold(pixel, uniform.matrix_size) - 0.5) * f64::from(uniform.spread);
+        if uniform.palette_len > 0 {
+            let step = 1.0 / (f64::from(uniform.palette_len) - 1.0).max(1.0);
+            let shifted = color.map(|c| c + offset * step);
+            let distance = |entry: &[f32; 4]| -> f64 {
+                (0..3)
+                    .map(|i| (shifted[i] - f64::from(entry[i])).powi(2))
+                    .sum()
+            };
+            let best = uniform.palette[..uniform.palette_len as usize]
+                .iter()
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .unwrap();
+            return [0, 1, 2].map(|i| f64::from(best[i]));
+        }
+        let steps = f64::from(uniform.levels).round().max(2.0) - 1.0;
+        color.map(|c| ((c * steps + offset).round() / steps).clamp(0.0, 1.0))
+    }
+
+    fn uniform(levels: f32, matrix_size: u32, spread: f32, palette: &[RgbaColor]) -> DitherUniform {
+        let mut entries = [[0.0; 4]; MAX_PALETTE];
+        for (slot, color) in entries.iter_mut().zip(palette) {
+            *slot = color.to_f32_array();
+        }
+        DitherUniform {
+            levels,
+            matrix_size,
+            palette_len: palette.len() as u32,
+            spread,
+            palette: entries,
+        }
+    }
+
+    // Share of the 8x8 tile a flat color comes out as the upper of its two levels
+    fn upper_share(color: f64, uniform: &DitherUniform) -> f64 {
+        let upper = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&pixel| dither([color; 3], pixel, uniform)[0] > color)
+            .count();
+        upper as f64 / 64.0
+    }
+
+    #[test]
+    fn bayer_thresholds_are_evenly_spread() {
+        for (size, cells) in [(8, 64), (4, 16)] {
+            let mut seen: Vec<f64> = (0..size)
+                .flat_map(|y| (0..size).map(move |x| threshold((x, y), size)))
+                .collect();
+            seen.sort_by(f64::total_cmp);
+            // Every cell a different step, centered in it
+            for (i, t) in seen.iter().enumerate() {
+                assert!((t - (i as f64 + 0.5) / f64::from(cells)).abs() < 1e-12);
+            }
+            // And the pattern tiles
+            assert!((threshold((size, 0), size) - threshold((0, 0), size)).abs() < 1e-12);
+            assert!((threshold((3, size + 1), size) - threshold((3, 1), size)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn flat_colors_dither_to_their_share() {
+        let two_levels = uniform(2.0, 8, 1.0, &[]);
+        let black_and_white = uniform(4.0, 8, 1.0, &[BLACK, Colors::WHITE]);
+        for color in [0.1, 0.25, 0.4, 0.5, 0.73, 0.9] {
+            // Half a cell either way, the tile has 64
+            assert!((upper_share(color, &two_levels) - color).abs() <= 1.0 / 128.0 + 1e-9);
+            assert!((upper_share(color, &black_and_white) - color).abs() <= 1.0 / 128.0 + 1e-9);
+        }
+        // More levels split the difference between the two around it
+        let five = uniform(5.0, 4, 1.0, &[]);
+        for pixel in [(0, 0), (1, 2), (3, 3)] {
+            let out = dither([0.6; 3], pixel, &five)[0];
+            assert!(
+                (out - 0.5).abs() < 1e-9 || (out - 0.75).abs() < 1e-9,
+                "{out}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_spread_is_plain_banding() {
+        let banding = uniform(4.0, 4, 0.0, &[]);
+        for (color, expected) in [
+            (0.0, 0.0),
+            (0.1, 0.0),
+            (0.2, 1.0 / 3.0),
+            (0.55, 2.0 / 3.0),
+            (0.9, 1.0),
+        ] {
+            for pixel in [(0, 0), (2, 1), (7, 7)] {
+                let out = dither([color; 3], pixel, &banding);
+                assert!(
+                    out.iter().all(|c| (c - expected).abs() < 1e-9),
+                    "{color}: {out:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn palettes_snap_to_the_nearest_color() {
+        let red = RgbaColor::rgba(1.0, 0.0, 0.0, 1.0);
+        let teal = RgbaColor::rgba(0.0, 0.5, 0.5, 1.0);
+        let palette = uniform(4.0, 4, 0.0, &[BLACK, red, teal]);
+        for (color, expected) in [
+            ([0.9, 0.1, 0.0], red),
+            ([0.1, 0.4, 0.6], teal),
+            ([0.1, 0.1, 0.1], BLACK),
+        ] {
+            let out = dither(color, (0, 0), &palette);
+            let expected = expected.to_f32_array();
+            assert!((0..3).all(|i| (out[i] - f64::from(expected[i])).abs() < 1e-6));
+        }
+        // Slots past palette_len are never picked, even when closer
+        let short = uniform(4.0, 4, 0.0, &[BLACK, red]);
+        let out = dither([0.0, 1.0, 1.0], (0, 0), &short);
+        assert!(out[1].abs() < 1e-9 && out[2].abs() < 1e-9, "{out:?}");
+    }
+
+    // A gradient in linear, exact in f16: red along x, green down y, blue along both
+    fn gradient((width, height): (u32, u32)) -> Vec<[f32; 4]> {
+        (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    [
+                        x as f32 / width as f32,
+                        y as f32 / height as f32,
+                        (x + y) as f32 / (width + height) as f32,
+                        1.0,
+                    ]
+                })
+            })
+            .collect()
+    }
+
+    fn to_byte(srgb: f64) -> u8 {
+        (srgb * 255.0).round() as u8
+    }
+
+    // fs_pixelate's block centers: every pixel in a block shows the same input texel, the
+    // last partial block clamped inside the texture
+    fn pixelated(x: u32, y: u32, size: u32, (width, height): (u32, u32)) -> (u32, u32) {
+        (
+            ((x / size) * size + size / 2).min(width - 1),
+            ((y / size) * size + size / 2).min(height - 1),
+        )
+    }
+
+    #[test]
+    fn pixelate_blocks_read_their_center() {
+        let size = (16, 8);
+        for y in 0..8 {
+            for x in 0..16 {
+                let (sx, sy) = pixelated(x, y, 3, size);
+                assert_eq!((sx / 3, sy / 3), (x / 3, y / 3));
+                assert!(sx < 16 && sy < 8);
+                assert_eq!(pixelated(x, y, 1, size), (x, y));
+            }
+        }
+        // The partial block along the bottom edge, rows 6 and 7 of three-row blocks
+        assert_eq!(pixelated(0, 7, 3, size).1, 7);
+    }
+
+    #[test]
+    fn pixelate_matches_the_cpu() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let size = (16, 8);
+        let input = gradient(size);
+        let mut effect = Pixelate::new(&gpu.device);
+        effect.params.pixel_size = 3.0;
+        let output = run_effect(gpu, Box::new(effect), size, &input);
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let (sx, sy) = pixelated(x, y, 3, size);
+            let texel = input[(sy * size.0 + sx) as usize];
+            for (channel, &linear) in texel[..3].iter().enumerate() {
+                let expected = to_byte(colors::linear_to_srgb(f64::from(linear)));
+                assert!(
+                    pixel.0[channel].abs_diff(expected) <= 1,
+                    "({x}, {y}): {pixel:?}, expected {expected} in channel {channel}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dither_matches_the_cpu() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let size = (16, 8);
+        let input = gradient(size);
+        let palette = [BLACK, Colors::WHITE, RgbaColor::from_hex(0x33_66_cc)];
+        for (levels, matrix, colors) in [
+            (4.0, 4.0, &[][..]),
+            (2.0, 8.0, &[][..]),
+            (4.0, 8.0, &palette[..]),
+        ] {
+            let mut effect = Dither::new(&gpu.device);
+            effect.levels = levels;
+            effect.matrix_size = matrix;
+            effect.set_palette(colors);
+            let mirror = uniform(levels, matrix as u32, effect.spread, colors);
+            let output = run_effect(gpu, Box::new(effect), size, &input);
+            for (x, y, pixel) in output.enumerate_pixels() {
+                let texel = input[(y * size.0 + x) as usize];
+                let srgb = [0, 1, 2].map(|i| colors::linear_to_srgb(f64::from(texel[i])));
+                let expected = dither(srgb, (x, y), &mirror).map(to_byte);
+                for channel in 0..3 {
+                    assert!(
+                        pixel.0[channel].abs_diff(expected[channel]) <= 1,
+                        "levels {levels}, matrix {matrix}, ({x}, {y}): {pixel:?}, expected {expected:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    // A color wheel on mid-gray, in linear: hue around the center, saturation out from it.
+    // Snapped to 1/256ths, which halves hold exactly
+    #[cfg(feature = "textures")]
+    fn color_wheel(side: u32) -> Vec<[f32; 4]> {
+        let center = side as f32 / 2.0;
+        let radius = center - 4.0;
+        let linear = |srgb: f32| {
+            let linear = colors::srgb_to_linear(f64::from(srgb)) as f32;
+            (linear * 256.0).round() / 256.0
+        };
+        (0..side)
+            .flat_map(|y| {
+                (0..side).map(move |x| (x as f32 + 0.5 - center, y as f32 + 0.5 - center))
+            })
+            .map(|(dx, dy)| {
+                let saturation = (dx.hypot(dy) / radius).min(1.0);
+                let hue = dy.atan2(dx) / std::f32::consts::TAU;
+                let channel = |offset: f32| {
+                    let full = 0.5 + 0.5 * (std::f32::consts::TAU * (hue + offset)).cos();
+                    if dx.hypot(dy) > radius {
+                        0.5
+                    } else {
+                        0.5 + (full - 0.5) * saturation
+                    }
+                };
+                [
+                    linear(channel(0.0)),
+                    linear(channel(1.0 / 3.0)),
+                    linear(channel(2.0 / 3.0)),
+                    1.0,
+                ]
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "textures")]
+    #[test]
+    fn color_blind_modes_render_like_their_golden_images() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let side = 96;
+        let input = color_wheel(side);
+        let mut outputs = Vec::new();
+        for mode in ColorBlindMode::ALL {
+            let mut effect = ColorBlind::new(&gpu.device);
+            effect.mode = mode.to_param();
+            let output = run_effect(gpu, Box::new(effect), (side, side), &input);
+            crate::golden::check(&format!("colorblind_{}", mode.name()), &output);
+            outputs.push(output);
+        }
+        // Each mode does something, and something different from the others
+        for (a, first) in outputs.iter().enumerate() {
+            for (b, second) in outputs.iter().enumerate().skip(a + 1) {
+                assert!(
+                    crate::golden::differing(first, second) > 0,
+                    "{} and {} look the same",
+                    ColorBlindMode::ALL[a].name(),
+                    ColorBlindMode::ALL[b].name()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::geometry::GeometryError;
+use crate::mesh::VertexLayoutId;
+use crate::transform::Transform2d;
+
+#[derive(Debug)]
+pub enum ForayError {
+    UnknownPipeline(String),
+    // No effect registered on the EffectChain under this name
+    UnknownEffect(String),
+    // The effect exists but has no parameter by that name, see Effect::param
+    UnknownEffectParam {
+        effect: String,
+        param: String,
+    },
+    // Requested in the background, not built yet and no placeholder for it
+    PipelineNotReady(String),
+    // Requested in the background and the build panicked
+    PipelineBuild {
+        pipeline: String,
+        reason: String,
+    },
+    // Built without a recipe, so there's nothing to build it again from
+    NotRebuildable(String),
+    // A bind group layout that doesn't give a pipeline's shader what it declares
+    BindingMismatch {
+        pipeline: String,
+        group: u32,
+        binding: u32,
+        reason: String,
+    },
+    // set_override with a name the pipeline's shader doesn't declare
+    UnknownOverride {
+        pipeline: String,
+        constant: String,
+        known: Vec<String>,
+    },
+    // Color attachments of a pass don't line up with what the pipeline writes
+    TargetMismatch {
+        pipeline: String,
+        pass: String,
+        pipeline_targets: Vec<wgpu::TextureFormat>,
+        pass_targets: Vec<wgpu::TextureFormat>,
+    },
+    DepthMismatch {
+        pipeline: String,
+        pass: String,
+        pipeline_depth: Option<wgpu::TextureFormat>,
+        pass_depth: Option<wgpu::TextureFormat>,
+    },
+    // Multisampled pipelines only draw into attachments with as many samples, and back
+    SampleMismatch {
+        pipeline: String,
+        pass: String,
+        pipeline_samples: u32,
+        pass_samples: u32,
+    },
+    // A mesh drawn with a pipeline built for other primitives, e.g. lines through a fill pipeline
+    TopologyMismatch {
+        mesh: String,
+        pipeline: String,
+        mesh_topology: wgpu::PrimitiveTopology,
+        pipeline_topology: wgpu::PrimitiveTopology,
+    },
+    // None on the pipeline side means it takes no vertex buffer at all
+    LayoutMismatch {
+        mesh: String,
+        pipeline: String,
+        mesh_layout: VertexLayoutId,
+        pipeline_layout: Option<VertexLayoutId>,
+    },
+    SubmeshOutOfRange {
+        mesh: String,
+        index: usize,
+        count: usize,
+    },
+    // MeshData::decimate only takes meshes where every edge has one or two triangles
+    NonManifold {
+        mesh: String,
+        reason: String,
+    },
+    // Couldn't read, write or parse a scene file
+    SceneFile {
+        path: PathBuf,
+        reason: String,
+    },
+    MissingAsset(PathBuf),
+    // A generator in geometry.rs turned down what it was given for this mesh
+    Geometry {
+        mesh: String,
+        error: GeometryError,
+    },
+    // A NaN or infinity on its way into a scene item, see Scene::set_transform
+    NonFiniteTransform {
+        item: String,
+        transform: Transform2d,
+    },
+    // Not something RgbaColor::parse_hex understands
+    InvalidHexColor(String),
+    // A LUT strip has to be N * N wide and N tall
+    LutSize {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+    },
+    // An image that isn't the ArrayTexture's tile size, under TilePolicy::Reject
+    TileSize {
+        image: String,
+        size: (u32, u32),
+        tile: (u32, u32),
+    },
+    // A tile index past the tile map's images
+    TileIndex {
+        map: String,
+        index: u32,
+        tiles: usize,
+    },
+    // Couldn't read or parse a tile layout, see TileLayout::read
+    TileLayout {
+        path: PathBuf,
+        reason: String,
+    },
+    // A glTF file that couldn't be read or uses something the importer refuses, see gltf::load
+    #[cfg(feature = "gltf")]
+    Gltf {
+        path: PathBuf,
+        reason: String,
+    },
+    // Couldn't open or decode an image
+    ImageFile {
+        path: PathBuf,
+        reason: String,
+    },
+    // Went wrong on an asset loader thread other than through the decoder's own error
+    AssetLoad {
+        asset: String,
+        reason: String,
+    },
+    // A DynamicMesh was given nothing to morph between
+    NoMorphTargets(String),
+    // Morph targets need the same vertex count, resample_closed gets outlines there
+    MorphMismatch {
+        mesh: String,
+        target: usize,
+        expected: usize,
+        found: usize,
+    },
+    // Couldn't read or parse a TTF/OTF
+    #[cfg(feature = "text")]
+    FontLoad {
+        font: String,
+        reason: String,
+    },
+    // Couldn't read or make sense of a timeline file
+    TimelineFile {
+        path: PathBuf,
+        reason: String,
+    },
+    // A headless frame that didn't render, read back or save
+    FrameDump {
+        frame: u32,
+        reason: String,
+    },
+    // Couldn't write an OBJ export or its MTL
+    #[cfg(feature = "obj")]
+    ObjExport {
+        path: PathBuf,
+        reason: String,
+    },
+    // No input device, or it wouldn't start capturing
+    #[cfg(feature = "audio")]
+    Audio {
+        reason: String,
+    },
+    // --remote couldn't listen, or wasn't allowed to without a token
+    #[cfg(feature = "remote")]
+    Remote {
+        reason: String,
+    },
+    // No adapter or device for the shared GpuContext
+    GpuUnavailable(String),
+    // A StateBuilder missing something State can't start without
+    StateBuilder(String),
+    // A mesh that doesn't go into a MeshArena, out of room or the wrong vertex size
+    ArenaFull {
+        arena: String,
+        mesh: String,
+        reason: String,
+    },
+    // A streamed mesh whose source gave out or handed over something that doesn't fit
+    MeshStream {
+        mesh: String,
+        reason: String,
+    },
+    // A subsystem's required feature or limit the adapter doesn't have
+    DeviceRequirement {
+        subsystem: String,
+        requirement: String,
+    },
+    // A material's per-item uniform block declared or filled in wrong
+    ItemBlock {
+        material: String,
+        reason: String,
+    },
+    // The shader manifest or a file it names, missing or not making sense
+    ShaderFile {
+        file: String,
+        reason: String,
+    },
+    // Passes a RenderGraph can't put in an order
+    RenderGraph(String),
+    // An MSAA sample count something has no pipelines for, or the adapter can't do
+    UnsupportedSampleCount {
+        what: String,
+        samples: u32,
+        supported: Vec<u32>,
+    },
+    // None of the surface formats asked for, or none at all, on the surface
+    UnsupportedSurfaceFormat {
+        wanted: Vec<wgpu::TextureFormat>,
+        offered: Vec<wgpu::TextureFormat>,
+    },
+}
+
+impl fmt::Display for ForayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForayError::UnknownPipeline(name) => write!(f, "No pipeline named \"{name}\""),
+            ForayError::UnknownEffect(name) => write!(f, "No post effect named \"{name}\""),
+            ForayError::UnknownEffectParam { effect, param } => {
+                write!(f, "Post effect \"{effect}\" has no parameter \"{param}\"")
+            }
+            ForayError::PipelineNotReady(name) => {
+                write!(f, "Pipeline \"{name}\" is still being built")
+            }
+            ForayError::PipelineBuild { pipeline, reason } => {
+                write!(f, "Building pipeline \"{pipeline}\" failed: {reason}")
+            }
+            ForayError::NotRebuildable(name) => {
+                write!(f, "Pipeline \"{name}\" wasn't registered with a recipe to rebuild from")
+            }
+            ForayError::BindingMismatch {
+                pipeline,
+                group,
+                binding,
+                reason,
+            } => write!(
+                f,
+                "Pipeline \"{pipeline}\" @group({group}) @binding({binding}): {reason}",
+            ),
+            ForayError::UnknownOverride {
+                pipeline,
+                constant,
+                known,
+            } if known.is_empty() => write!(
+                f,
+                "Pipeline \"{pipeline}\" has no override \"{constant}\", its shader declares none",
+            ),
+            ForayError::UnknownOverride {
+                pipeline,
+                constant,
+                known,
+            } => write!(
+                f,
+                "Pipeline \"{pipeline}\" has no override \"{constant}\", its shader declares {}",
+                known.join(", "),
+            ),
+            ForayError::TargetMismatch {
+                pipeline,
+                pass,
+                pipeline_targets,
+                pass_targets,
+            } => write!(
+                f,
+                "Pipeline \"{pipeline}\" writes {} target(s) {pipeline_targets:?} but pass \"{pass}\" has {} attachment(s) {pass_targets:?}",
+                pipeline_targets.len(),
+                pass_targets.len(),
+            ),
+            ForayError::DepthMismatch {
+                pipeline,
+                pass,
+                pipeline_depth,
+                pass_depth,
+            } => write!(
+                f,
+                "Pipeline \"{pipeline}\" expects depth {pipeline_depth:?} but pass \"{pass}\" has depth {pass_depth:?}",
+            ),
+            ForayError::SampleMismatch {
+                pipeline,
+                pass,
+                pipeline_samples,
+                pass_samples,
+            } => write!(
+                f,
+                "Pipeline \"{pipeline}\" was built for {pipeline_samples} sample(s) but pass \"{pass}\" has {pass_samples}",
+            ),
+            ForayError::TopologyMismatch {
+                mesh,
+                pipeline,
+                mesh_topology,
+                pipeline_topology,
+            } => write!(
+                f,
+                "Mesh \"{mesh}\" is {mesh_topology:?} but pipeline \"{pipeline}\" draws {pipeline_topology:?}",
+            ),
+            ForayError::LayoutMismatch {
+                mesh,
+                pipeline,
+                mesh_layout,
+                pipeline_layout: Some(pipeline_layout),
+            } => write!(
+                f,
+                "Mesh \"{mesh}\" has vertex layout {mesh_layout} but pipeline \"{pipeline}\" expects {pipeline_layout}",
+            ),
+            ForayError::LayoutMismatch {
+                mesh,
+                pipeline,
+                mesh_layout,
+                pipeline_layout: None,
+            } => write!(
+                f,
+                "Mesh \"{mesh}\" has vertex layout {mesh_layout} but pipeline \"{pipeline}\" takes no vertex buffer",
+            ),
+            ForayError::SubmeshOutOfRange { mesh, index, count } => write!(
+                f,
+                "Mesh \"{mesh}\" has {count} submesh(es), there's no submesh {index}",
+            ),
+            ForayError::NonManifold { mesh, reason } => {
+                write!(f, "Mesh \"{mesh}\" isn't manifold, {reason}")
+            }
+            ForayError::SceneFile { path, reason } => {
+                write!(f, "Scene file {}: {reason}", path.display())
+            }
+            ForayError::MissingAsset(path) => write!(f, "Missing asset {}", path.display()),
+            ForayError::Geometry { mesh, error } => write!(f, "Mesh \"{mesh}\": {error}"),
+            ForayError::NonFiniteTransform { item, transform } => write!(
+                f,
+                "Scene item \"{item}\" can't take a non-finite transform \
+                 (translation {}, rotation {}, scale {})",
+                transform.translation, transform.rotation, transform.scale
+            ),
+            ForayError::InvalidHexColor(text) => {
+                write!(f, "\"{text}\" isn't a hex color, expected #rrggbb or #rrggbbaa")
+            }
+            ForayError::LutSize {
+                path,
+                width,
+                height,
+            } => write!(
+                f,
+                "LUT {} is {width}x{height}, expected N*N wide and N tall",
+                path.display()
+            ),
+            ForayError::TileSize { image, size, tile } => write!(
+                f,
+                "Image {image} is {}x{}, the array's tiles are {}x{}",
+                size.0, size.1, tile.0, tile.1
+            ),
+            ForayError::TileIndex { map, index, tiles } => write!(
+                f,
+                "Tile {index} isn't in tile map {map}, it has {tiles} tile images"
+            ),
+            ForayError::TileLayout { path, reason } => {
+                write!(f, "Tile layout {}: {reason}", path.display())
+            }
+            #[cfg(feature = "gltf")]
+            ForayError::Gltf { path, reason } => write!(f, "glTF {}: {reason}", path.display()),
+            ForayError::ImageFile { path, reason } => {
+                write!(f, "Image {}: {reason}", path.display())
+            }
+            ForayError::AssetLoad { asset, reason } => {
+                write!(f, "Loading {asset} failed: {reason}")
+            }
+            #[cfg(feature = "text")]
+            ForayError::FontLoad { font, reason } => write!(f, "Font {font}: {reason}"),
+            ForayError::FrameDump { frame, reason } => write!(f, "Frame {frame}: {reason}"),
+            #[cfg(feature = "obj")]
+            ForayError::ObjExport { path, reason } => {
+                write!(f, "OBJ export {}: {reason}", path.display())
+            }
+            #[cfg(feature = "audio")]
+            ForayError::Audio { reason } => write!(f, "Audio capture: {reason}"),
+            #[cfg(feature = "remote")]
+            ForayError::Remote { reason } => write!(f, "Remote control: {reason}"),
+            ForayError::TimelineFile { path, reason } => {
+                write!(f, "Timeline {}: {reason}", path.display())
+            }
+            ForayError::GpuUnavailable(reason) => write!(f, "No GPU to render with: {reason}"),
+            ForayError::StateBuilder(reason) => write!(f, "Can't set up the renderer: {reason}"),
+            ForayError::ArenaFull {
+                arena,
+                mesh,
+                reason,
+            } => write!(f, "Mesh \"{mesh}\" doesn't fit arena \"{arena}\": {reason}"),
+            ForayError::MeshStream { mesh, reason } => {
+                write!(f, "Streaming mesh \"{mesh}\" failed: {reason}")
+            }
+            ForayError::DeviceRequirement {
+                subsystem,
+                requirement,
+            } => write!(f, "Can't create the device, {subsystem} needs {requirement}"),
+            ForayError::ItemBlock { material, reason } => {
+                write!(f, "Item block of material \"{material}\": {reason}")
+            }
+            ForayError::ShaderFile { file, reason } => write!(f, "Shader file {file}: {reason}"),
+            ForayError::RenderGraph(reason) => write!(f, "Render graph: {reason}"),
+            ForayError::UnsupportedSampleCount {
+                what,
+                samples,
+                supported,
+            } => write!(
+                f,
+                "{what} can't draw with {samples} samples per pixel, only {supported:?}"
+            ),
+            ForayError::UnsupportedSurfaceFormat { wanted, offered } => write!(
+                f,
+                "The surface has none of the formats {wanted:?}, it offers {offered:?}"
+            ),
+            ForayError::NoMorphTargets(mesh) => write!(f, "{mesh} has no morph targets"),
+            ForayError::MorphMismatch {
+                mesh,
+                target,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Morph target {target} of {mesh} has {found} vertices, the first one has {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ForayError {}
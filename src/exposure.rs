@@ -0,0 +1,381 @@
+use std::time::{Duration, Instant};
+
+use crate::bind_groups::{BindGroupBuilder, BindGroupHandle};
+use crate::camera2d::Camera2d;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::pipeline_bank::{PipelineBuilder, RenderPipelineBank};
+use crate::post::{self, Effect, EffectContext};
+use crate::shaders;
+use crate::targets::{TargetHandle, TargetRegistry};
+
+// Must match the 8 x 8 workgroups of cs_measure in luminance.wgsl, one partial sum each
+const MEASURE_GROUPS: u32 = 8;
+// A long stall (a hitch, the window being dragged) adapts at most this much in one go
+const MAX_STEP: Duration = Duration::from_millis(250);
+
+// Mirrors `struct Exposure` in exposure.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ExposureParams {
+    pub compensation: f32,
+    pub adaptation_rate: f32,
+    pub key: f32,
+    _padding: f32,
+}
+
+// Mirrors `struct Params` in luminance.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AdaptParams {
+    dt: f32,
+    rate: f32,
+    _padding: [f32; 2],
+}
+
+// Eye adaptation: the average log luminance of the input is measured with two compute
+// passes every frame, the adapted luminance follows it over time in a small buffer that
+// stays on the GPU, and the pass scales the input so that lands on `key` before a Reinhard
+// tonemap. Nothing is read back, so it never stalls on the GPU
+pub struct AutoExposure {
+    pub params: ExposureParams,
+    shader: wgpu::ShaderModule,
+    compute_layout: wgpu::BindGroupLayout,
+    measure: wgpu::ComputePipeline,
+    adapt: wgpu::ComputePipeline,
+    // One sum per measure workgroup
+    partials: Tracked<wgpu::Buffer>,
+    // Adapted and measured luminance, carried from frame to frame
+    state: Tracked<wgpu::Buffer>,
+    layout: wgpu::BindGroupLayout,
+    bind_group: BindGroupHandle,
+    // When the last measurement ran, the adaptation goes by real time
+    last: Option<Instant>,
+}
+
+impl AutoExposure {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        registry: &mut TargetRegistry,
+    ) -> Self {
+        let compute_layout = compute_layout(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Luminance Pipeline Layout"),
+            bind_group_layouts: &[&compute_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_shader =
+            shaders::create_module(device, "Luminance Shader", include_str!("luminance.wgsl"));
+        let pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &compute_shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        let partials = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Luminance Partials"),
+                size: u64::from(MEASURE_GROUPS * MEASURE_GROUPS) * 4,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+        // Zeroed at creation, which the adapt pass takes as "nothing measured yet"
+        let state = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Exposure State"),
+                size: 16,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::UNIFORM,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Exposure State Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = registry.create_bind_group(
+            device,
+            BindGroupBuilder::new("Exposure State Bind Group", &layout).buffer(0, &state),
+        );
+
+        Self {
+            params: ExposureParams {
+                compensation: 0.0,
+                adaptation_rate: 1.5,
+                key: 0.18,
+                _padding: 0.0,
+            },
+            shader: shaders::create_module(
+                device,
+                "Exposure Shader",
+                include_str!("exposure.wgsl"),
+            ),
+            measure: pipeline("Luminance Measure Pipeline", "cs_measure"),
+            adapt: pipeline("Luminance Adapt Pipeline", "cs_adapt"),
+            compute_layout,
+            partials,
+            state,
+            layout,
+            bind_group,
+            last: None,
+        }
+    }
+}
+
+impl Effect for AutoExposure {
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str) {
+        (&self.shader, "fs_exposure")
+    }
+
+    fn uniforms(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&self.params).to_vec()
+    }
+
+    fn extra_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+        Some(&self.layout)
+    }
+
+    fn bind_group(&self) -> Option<&BindGroupHandle> {
+        Some(&self.bind_group)
+    }
+
+    fn param(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "compensation" => Some(&mut self.params.compensation),
+            "adaptation_rate" => Some(&mut self.params.adaptation_rate),
+            "key" => Some(&mut self.params.key),
+            _ => None,
+        }
+    }
+
+    fn prepare(&mut self, ctx: &mut EffectContext, input: TargetHandle) {
+        let now = Instant::now();
+        // While the effect was off the scene may have changed completely, so after a break
+        // it adapts as if no time passed and catches up from there
+        let step = self
+            .last
+            .map_or(Duration::ZERO, |last| (now - last).min(MAX_STEP));
+        self.last = Some(now);
+
+        let params = AdaptParams {
+            dt: step.as_secs_f32(),
+            rate: self.params.adaptation_rate,
+            _padding: [0.0; 2],
+        };
+        let params_buffer = ctx.pool.acquire(
+            ctx.device,
+            "Luminance Params",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<AdaptParams>() as u64,
+        );
+        ctx.queue
+            .write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Luminance Bind Group"),
+            layout: &self.compute_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(ctx.registry.view(input)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.partials.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.state.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &params_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<AdaptParams>() as u64),
+                    }),
+                },
+            ],
+        });
+
+        for (label, pipeline, groups) in [
+            ("Measure Luminance", &self.measure, MEASURE_GROUPS),
+            ("Adapt Luminance", &self.adapt, 1),
+        ] {
+            let mut pass = ctx
+                .encoder
+                .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some(label),
+                    timestamp_writes: None,
+                });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups, groups, 1);
+        }
+    }
+}
+
+// Source texture, per-workgroup sums, the carried state and this frame's params
+fn compute_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let storage = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Luminance Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            storage(1),
+            storage(2),
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+// Mirrors `struct Scene` in hdr_scene.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SceneUniform {
+    center: [f32; 2],
+    zoom: f32,
+    _padding: f32,
+    resolution: [f32; 2],
+    _padding2: [f32; 2],
+}
+
+// Something for the auto exposure to adapt to: a fullscreen shader with a dark cave and
+// bright daylight side by side, a couple of hundred times apart, under the 2D camera
+pub struct HdrScene {
+    uniform: Tracked<wgpu::Buffer>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl HdrScene {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        bank: &mut RenderPipelineBank,
+    ) -> Self {
+        let uniform = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("HDR Scene Uniform"),
+                size: std::mem::size_of::<SceneUniform>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HDR Scene Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("HDR Scene Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+        });
+        let shader =
+            shaders::create_module(device, "HDR Scene Shader", include_str!("hdr_scene.wgsl"));
+        bank.register(
+            "hdr_scene",
+            PipelineBuilder::new("HDR Scene Pipeline", &shader)
+                .vertex_entry("vs_fullscreen")
+                .fragment_entry("fs_hdr_scene")
+                .bind_group_layout(&layout)
+                .cull_mode(None)
+                .build(device, post::SCENE_FORMAT),
+        );
+        Self {
+            uniform,
+            bind_group,
+        }
+    }
+
+    // Into `target`, which has to be post::SCENE_FORMAT
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        registry: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        target: TargetHandle,
+        camera: &Camera2d,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let uniform = SceneUniform {
+            center: camera.center.into(),
+            zoom: camera.zoom,
+            _padding: 0.0,
+            resolution: camera.clip_extent(viewport),
+            _padding2: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+        frame.fullscreen_pass(
+            "HDR Scene Pass",
+            ColorTarget::Offscreen(target),
+            frame.background.color(),
+            registry,
+            bank,
+            "hdr_scene",
+            &[&self.bind_group],
+        )
+    }
+}
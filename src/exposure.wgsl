@@ -0,0 +1,31 @@
+#include "post.wgsl"
+
+struct Exposure {
+    // In stops, on top of what the adaptation picks
+    compensation: f32,
+    // Read by the compute passes, see luminance.wgsl
+    adaptation_rate: f32,
+    // Where the adapted luminance ends up after exposure, middle grey by default
+    key: f32,
+    _padding: f32,
+};
+
+// Written by cs_adapt in luminance.wgsl
+struct State {
+    adapted: f32,
+    measured: f32,
+    _padding: vec2<f32>,
+};
+
+@group(1) @binding(0) var<uniform> exposure: Exposure;
+@group(2) @binding(0) var<uniform> state: State;
+
+@fragment
+fn fs_exposure(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv).rgb;
+    let scale = exposure.key / max(state.adapted, 0.0001) * exp2(exposure.compensation);
+    let exposed = color * scale;
+    // Reinhard on the luminance, so bright colors roll off without losing their hue
+    let luminance = dot(exposed, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(exposed / (1.0 + luminance), 1.0);
+}
@@ -0,0 +1,185 @@
+// Classic 5x7 LCD font for ASCII 32..=126, column-major with bit 0 at the top
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+pub const FIRST_CHAR: char = ' ';
+pub const LAST_CHAR: char = '~';
+
+#[rustfmt::skip]
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x00, 0x7F, 0x41, 0x41], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // backslash
+    [0x41, 0x41, 0x7F, 0x00, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3C], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x00, 0x7F, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x10, 0x08, 0x08, 0x10, 0x08], // ~
+];
+
+// Anything outside the table (including non-ASCII) shows up as '?'
+pub fn glyph_index(c: char) -> u32 {
+    if (FIRST_CHAR..=LAST_CHAR).contains(&c) {
+        c as u32 - FIRST_CHAR as u32
+    } else {
+        '?' as u32 - FIRST_CHAR as u32
+    }
+}
+
+// All glyphs side by side in one row, one byte per pixel (0 or 255), ready for an R8 texture.
+// Each cell is padded by a pixel so filtering never bleeds the neighbour in.
+pub const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+pub const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+pub const GLYPH_COUNT: u32 = GLYPHS.len() as u32;
+// One extra fully lit cell after the glyphs, for drawing solid rectangles with the same pipeline
+pub const SOLID_CELL: u32 = GLYPH_COUNT;
+pub const ATLAS_WIDTH: u32 = CELL_WIDTH * (GLYPH_COUNT + 1);
+pub const CELLS_PER_ROW: u32 = GLYPH_COUNT + 1;
+// Rows under the table for characters outside it, filled in as they're first drawn (see
+// rasterize_cell). Cell numbers go on from SOLID_CELL row by row
+pub const EXTRA_ROWS: u32 = 8;
+pub const ATLAS_HEIGHT: u32 = CELL_HEIGHT * (1 + EXTRA_ROWS);
+#[cfg(feature = "text")]
+pub const CELL_COUNT: u32 = CELLS_PER_ROW * (1 + EXTRA_ROWS);
+
+pub fn atlas_pixels() -> Vec<u8> {
+    let width = ATLAS_WIDTH;
+    let mut pixels = vec![0u8; (width * CELL_HEIGHT) as usize];
+    for y in 0..CELL_HEIGHT {
+        let row = (y * width + SOLID_CELL * CELL_WIDTH) as usize;
+        pixels[row..row + CELL_WIDTH as usize].fill(255);
+    }
+    for (i, columns) in GLYPHS.iter().enumerate() {
+        for (x, column) in columns.iter().enumerate() {
+            for y in 0..GLYPH_HEIGHT {
+                if column >> y & 1 == 1 {
+                    let px = i * CELL_WIDTH as usize + x;
+                    pixels[y as usize * width as usize + px] = 255;
+                }
+            }
+        }
+    }
+    pixels
+}
+
+// A character outside the table from a TTF face, one cell's worth of pixels (CELL_WIDTH by
+// CELL_HEIGHT). Sized so capitals are about as tall as the table's and sat on the same
+// baseline, whatever doesn't fit in the 5x7 box is cut off. A hollow box (tofu) when the
+// face doesn't have it either
+#[cfg(feature = "text")]
+pub fn rasterize_cell(face: &fontdue::Font, c: char) -> Vec<u8> {
+    let mut pixels = vec![0u8; (CELL_WIDTH * CELL_HEIGHT) as usize];
+    let (width, height) = (GLYPH_WIDTH as i32, GLYPH_HEIGHT as i32);
+    let mut set = |x: i32, y: i32, value: u8| {
+        if (0..width).contains(&x) && (0..height).contains(&y) {
+            pixels[(y * CELL_WIDTH as i32 + x) as usize] = value;
+        }
+    };
+    if face.lookup_glyph_index(c) == 0 {
+        for x in 0..width {
+            set(x, 0, 255);
+            set(x, height - 1, 255);
+        }
+        for y in 0..height {
+            set(0, y, 255);
+            set(width - 1, y, 255);
+        }
+        return pixels;
+    }
+    let (metrics, coverage) = face.rasterize(c, GLYPH_HEIGHT as f32 * 1.4);
+    // Centered, glyphs wider than the cell lose both edges rather than one
+    let left = (width - metrics.width as i32) / 2;
+    let top = height - (metrics.ymin + metrics.height as i32);
+    for (row, line) in coverage.chunks(metrics.width.max(1)).enumerate() {
+        for (column, &value) in line.iter().enumerate() {
+            set(left + column as i32, top + row as i32, value);
+        }
+    }
+    pixels
+}
@@ -0,0 +1,711 @@
+use std::ops::Range;
+
+use glam::{Vec2, Vec3};
+
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::frame_dump::{Command, FrameDump, PassDump, TargetDump};
+use crate::gizmos::GizmoLine;
+use crate::immediate::{Immediate, Space};
+use crate::mesh::{self, Mesh, VertexLayoutId};
+use crate::mesh_arena::BufferSetId;
+use crate::pipeline_bank::RenderPipelineBank;
+use crate::pipeline_stats::StatisticsQueries;
+#[cfg(feature = "text")]
+use crate::sdf_text::{SdfFont, SdfRun};
+use crate::shapes::{ShapeInstance, Stroke, Width};
+use crate::stats;
+use crate::surface_views::SurfaceViews;
+use crate::targets::{TargetHandle, TargetRegistry};
+#[cfg(feature = "text")]
+use crate::text::{Font, TextBounds, TextRun};
+
+// Where a color attachment of a pass ends up
+#[derive(Copy, Clone, Debug)]
+pub enum ColorTarget {
+    // Through the scene's view, see SurfaceViews
+    Swapchain,
+    // The same image through the UI's view, for pipelines from register_ui
+    SwapchainUi,
+    Offscreen(TargetHandle),
+}
+
+// Shows up wherever nothing was drawn when the background is DontCare (debug builds)
+pub const DEBUG_MAGENTA: wgpu::Color = wgpu::Color {
+    r: 1.0,
+    g: 0.0,
+    b: 1.0,
+    a: 1.0,
+};
+
+// How a frame's attachments start out. Passes that open a view's output (color and depth)
+// ask this for their LoadOp instead of hardcoding one, overlays on top just Load
+#[derive(Copy, Clone, Debug)]
+pub enum Background {
+    Clear(wgpu::Color),
+    // Keep what's there. On the swapchain that's what the image held when it was last
+    // presented, which with several images in flight isn't necessarily the previous frame
+    Preserve,
+    // The view overdraws everything anyway. Debug builds clear to magenta to expose gaps
+    DontCare,
+}
+
+impl Background {
+    // For the view's main color attachment
+    pub fn color(self) -> wgpu::LoadOp<wgpu::Color> {
+        match self {
+            Background::Clear(color) => wgpu::LoadOp::Clear(color),
+            _ => self.load(wgpu::Color::BLACK, DEBUG_MAGENTA),
+        }
+    }
+
+    // For attachments with a clear value of their own (depth, g-buffer), `cleared` is used
+    // in Clear mode and `debug` for DontCare in debug builds
+    pub fn load<V>(self, cleared: V, debug: V) -> wgpu::LoadOp<V> {
+        match self {
+            Background::Clear(_) => wgpu::LoadOp::Clear(cleared),
+            Background::Preserve => wgpu::LoadOp::Load,
+            Background::DontCare if cfg!(debug_assertions) => wgpu::LoadOp::Clear(debug),
+            Background::DontCare => wgpu::LoadOp::Load,
+        }
+    }
+}
+
+// One acquired swapchain image and the encoder everything for it gets recorded into
+pub struct Frame {
+    // None when rendering headless, "swapchain" is then a texture owned by the caller
+    output: Option<wgpu::SurfaceTexture>,
+    pub swapchain_view: wgpu::TextureView,
+    pub swapchain_format: wgpu::TextureFormat,
+    // The swapchain for ColorTarget::SwapchainUi, the same view as swapchain_view unless
+    // the views are paired
+    pub ui_view: wgpu::TextureView,
+    pub ui_format: wgpu::TextureFormat,
+    pub encoder: wgpu::CommandEncoder,
+    pub background: Background,
+    // Queued by draw_line/draw_circle, drawn by the ShapeRenderer before the frame ends
+    pub shapes: Vec<ShapeInstance>,
+    // Where among `shapes` the scene's ShapeBuffer goes, set by draw_scene_shapes
+    pub scene_shapes_at: Option<usize>,
+    // Same idea for world-space lines, drawn by Gizmos over a 3D view
+    pub lines3d: Vec<GizmoLine>,
+    // Laid out by draw_text, drawn by the TextRenderer
+    #[cfg(feature = "text")]
+    pub text: Vec<TextRun>,
+    // World space text from draw_text_world, drawn by the SdfTextRenderer
+    #[cfg(feature = "text")]
+    pub world_text: Vec<SdfRun>,
+    // Hairlines and flat rects from line/rect and their world_ versions, drawn by the
+    // ImmediateRenderer
+    pub immediate: Immediate,
+    // Added up by the passes' mesh draws, for FrameStats
+    pub counts: DrawCounts,
+    // Set when this frame gets pipeline statistics, for passes that call Pass::measure
+    pub statistics: Option<StatisticsQueries>,
+    // Every pipeline bound through Pass::set_pipeline, once each in the order they were
+    // first used. What the watchdog names when the frame's work doesn't finish
+    pub pipelines: Vec<String>,
+    // Set for a frame `dump_frame` asked for, every pass and what it recorded goes in
+    pub dump: Option<FrameDump>,
+}
+
+// What a frame's Pass mesh and sprite draws added up to. Instanced shapes, text and
+// anything drawn through `raw` aren't counted
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DrawCounts {
+    pub triangles: u64,
+    pub draws: u64,
+    // Times a mesh draw had to set the vertex and index buffers, draws from the same arena
+    // (or mesh) in a row share one
+    pub buffer_binds: u64,
+    pub sprites: u64,
+    // Instanced draws the sprites took, one per texture array
+    pub sprite_batches: u64,
+}
+
+impl Frame {
+    pub fn begin(
+        surface: &wgpu::Surface,
+        device: &wgpu::Device,
+        views: SurfaceViews,
+        background: Background,
+    ) -> Result<Self, wgpu::SurfaceError> {
+        let output = surface.get_current_texture()?;
+        let view = |format| {
+            output.texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(format),
+                ..Default::default()
+            })
+        };
+        let (swapchain_view, ui_view) = (view(views.scene), view(views.ui));
+        Ok(Self::with_view(
+            Some(output),
+            swapchain_view,
+            device,
+            views.scene,
+            background,
+        )
+        .with_ui_view(ui_view, views.ui))
+    }
+
+    // Composites UI through `view` instead, a view of the same image in another format
+    pub fn with_ui_view(mut self, view: wgpu::TextureView, format: wgpu::TextureFormat) -> Self {
+        self.ui_view = view;
+        self.ui_format = format;
+        self
+    }
+
+    // What's being drawn to, None when offscreen
+    pub fn surface_texture(&self) -> Option<&wgpu::Texture> {
+        self.output.as_ref().map(|output| &output.texture)
+    }
+
+    // Draws into `view` wherever a pass asks for the swapchain, nothing gets presented
+    pub fn offscreen(
+        view: wgpu::TextureView,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        background: Background,
+    ) -> Self {
+        Self::with_view(None, view, device, format, background)
+    }
+
+    fn with_view(
+        output: Option<wgpu::SurfaceTexture>,
+        swapchain_view: wgpu::TextureView,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        background: Background,
+    ) -> Self {
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Frame Encoder"),
+        });
+        Self {
+            output,
+            ui_view: swapchain_view.clone(),
+            ui_format: format,
+            swapchain_view,
+            swapchain_format: format,
+            encoder,
+            background,
+            shapes: Vec::new(),
+            scene_shapes_at: None,
+            lines3d: Vec::new(),
+            #[cfg(feature = "text")]
+            text: Vec::new(),
+            #[cfg(feature = "text")]
+            world_text: Vec::new(),
+            immediate: Immediate::default(),
+            counts: DrawCounts::default(),
+            statistics: None,
+            pipelines: Vec::new(),
+            dump: None,
+        }
+    }
+
+    // Every attachment gets its own load op, in @location order
+    pub fn pass<'f>(
+        &'f mut self,
+        label: &'f str,
+        attachments: &[(ColorTarget, wgpu::LoadOp<wgpu::Color>)],
+        targets: &TargetRegistry,
+    ) -> Pass<'f> {
+        self.pass_with_depth(label, attachments, None, targets)
+    }
+
+    pub fn pass_with_depth<'f>(
+        &'f mut self,
+        label: &'f str,
+        attachments: &[(ColorTarget, wgpu::LoadOp<wgpu::Color>)],
+        depth: Option<(TargetHandle, wgpu::LoadOp<f32>)>,
+        targets: &TargetRegistry,
+    ) -> Pass<'f> {
+        let resolve = |target: ColorTarget| match target {
+            ColorTarget::Swapchain => (&self.swapchain_view, self.swapchain_format),
+            ColorTarget::SwapchainUi => (&self.ui_view, self.ui_format),
+            ColorTarget::Offscreen(handle) => (targets.view(handle), targets.format(handle)),
+        };
+
+        let formats = attachments
+            .iter()
+            .map(|&(target, _)| resolve(target).1)
+            .collect();
+        // Multisampled targets are drawn into through their own texture and resolved
+        let attach = |target: ColorTarget| match target {
+            ColorTarget::Offscreen(handle) => targets.attachment(handle),
+            _ => (resolve(target).0, None),
+        };
+        let samples = attachments
+            .iter()
+            .filter_map(|&(target, _)| match target {
+                ColorTarget::Offscreen(handle) => Some(handle),
+                _ => None,
+            })
+            .chain(depth.map(|(handle, _)| handle))
+            .map(|handle| targets.samples(handle))
+            .max()
+            .unwrap_or(1);
+        let color_attachments: Vec<_> = attachments
+            .iter()
+            .map(|&(target, load)| {
+                let (view, resolve_target) = attach(target);
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            })
+            .collect();
+
+        let swapchain_size = self
+            .output
+            .as_ref()
+            .map(|output| (output.texture.width(), output.texture.height()));
+        let dump = self.dump.as_mut().map(|dump| {
+            let target = |target: ColorTarget, load: String| {
+                let (name, size) = match target {
+                    ColorTarget::Swapchain => ("swapchain".to_owned(), swapchain_size),
+                    ColorTarget::SwapchainUi => ("swapchain ui".to_owned(), swapchain_size),
+                    ColorTarget::Offscreen(handle) => {
+                        (targets.label(handle).to_owned(), Some(targets.size(handle)))
+                    }
+                };
+                TargetDump {
+                    target: name,
+                    format: format!("{:?}", resolve(target).1),
+                    size,
+                    load,
+                }
+            };
+            dump.passes.push(PassDump {
+                label: label.to_owned(),
+                targets: attachments
+                    .iter()
+                    .map(|&(color, load)| target(color, format!("{load:?}")))
+                    .collect(),
+                depth: depth.map(|(handle, load)| TargetDump {
+                    target: targets.label(handle).to_owned(),
+                    format: format!("{:?}", targets.format(handle)),
+                    size: Some(targets.size(handle)),
+                    load: format!("{load:?}"),
+                }),
+                commands: Vec::new(),
+            });
+            dump.passes.last_mut().expect("just pushed")
+        });
+
+        let depth_stencil_attachment =
+            depth.map(|(handle, load)| wgpu::RenderPassDepthStencilAttachment {
+                view: targets.attachment(handle).0,
+                depth_ops: Some(wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            });
+
+        let raw = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        Pass {
+            raw,
+            label,
+            formats,
+            depth: depth.map(|(handle, _)| targets.format(handle)),
+            samples,
+            bound: None,
+            buffers: None,
+            counts: &mut self.counts,
+            statistics: self.statistics.as_mut(),
+            pipelines: &mut self.pipelines,
+            measuring: false,
+            dump,
+        }
+    }
+
+    // A single-target pass that just runs a fullscreen-triangle pipeline over it
+    #[allow(clippy::too_many_arguments)]
+    pub fn fullscreen_pass(
+        &mut self,
+        label: &str,
+        target: ColorTarget,
+        load: wgpu::LoadOp<wgpu::Color>,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pipeline: &str,
+        bind_groups: &[&wgpu::BindGroup],
+    ) -> Result<(), ForayError> {
+        let mut pass = self.pass(label, &[(target, load)], targets);
+        pass.set_pipeline(bank, pipeline)?;
+        for (index, bind_group) in (0u32..).zip(bind_groups) {
+            pass.raw.set_bind_group(index, *bind_group, &[]);
+        }
+        pass.record(|| Command::Fullscreen);
+        pass.raw.draw(0..3, 0..1);
+        Ok(())
+    }
+
+    pub fn draw_line(&mut self, p0: Vec2, p1: Vec2, width: Width, color: RgbaColor) {
+        self.shapes.push(ShapeInstance::line(p0, p1, width, color));
+    }
+
+    // 1px line in screen pixels, top-left origin like the overlay
+    pub fn line(&mut self, p0: Vec2, p1: Vec2, color: RgbaColor) {
+        self.immediate.line(Space::Screen, p0, p1, color);
+    }
+
+    // Filled, (x, y) is the top-left corner in screen pixels
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: RgbaColor) {
+        let min = Vec2::new(x, y);
+        self.immediate
+            .rect(Space::Screen, min, min + Vec2::new(w, h), color);
+    }
+
+    // 1px line between world points, under whatever draw_line/draw_circle queued
+    pub fn world_line(&mut self, p0: Vec2, p1: Vec2, color: RgbaColor) {
+        self.immediate.line(Space::World, p0, p1, color);
+    }
+
+    pub fn world_rect(&mut self, min: Vec2, max: Vec2, color: RgbaColor) {
+        self.immediate.rect(Space::World, min, max, color);
+    }
+
+    // The scene's shapes are kept on the GPU between frames rather than queued, this puts
+    // them after whatever draw_line/draw_circle queued so far
+    pub fn draw_scene_shapes(&mut self) {
+        self.scene_shapes_at = Some(self.shapes.len());
+    }
+
+    pub fn draw_circle(&mut self, center: Vec2, radius: f32, stroke: Stroke, color: RgbaColor) {
+        self.shapes
+            .push(ShapeInstance::circle(center, radius, stroke, color));
+    }
+
+    // `pos` is the top-left corner in screen pixels. Laid out right away, so the bounds
+    // can place whatever comes next
+    #[cfg(feature = "text")]
+    pub fn draw_text(
+        &mut self,
+        font: &Font,
+        size_px: f32,
+        pos: Vec2,
+        text: &str,
+        color: RgbaColor,
+    ) -> TextBounds {
+        let (glyphs, bounds) = font.layout(size_px, pos, text);
+        self.text.push(TextRun::new(font, size_px, glyphs, color));
+        bounds
+    }
+
+    // Distance field text in world units, sharp under any camera zoom. `pos` is where the
+    // first baseline starts, `outline` is a color and a width in world units
+    #[cfg(feature = "text")]
+    pub fn draw_text_world(
+        &mut self,
+        font: &SdfFont,
+        size: f32,
+        pos: Vec2,
+        text: &str,
+        color: RgbaColor,
+        outline: Option<(RgbaColor, f32)>,
+    ) -> TextBounds {
+        let (vertices, bounds) = font.layout(size, pos, text, color, outline);
+        self.world_text.push(SdfRun {
+            font: font.clone(),
+            vertices,
+        });
+        bounds
+    }
+
+    // `width` is in screen pixels
+    pub fn draw_line_3d(&mut self, p0: Vec3, p1: Vec3, width: f32, color: RgbaColor) {
+        self.lines3d.push(GizmoLine::new(p0, p1, width, color));
+    }
+
+    // The frame's one submit, presenting only after it so the image has all the passes
+    pub fn finish(self, queue: &wgpu::Queue) {
+        stats::submit(queue, std::iter::once(self.encoder.finish()));
+        if let Some(output) = self.output {
+            output.present();
+        }
+    }
+}
+
+pub struct Pass<'f> {
+    pub raw: wgpu::RenderPass<'f>,
+    label: &'f str,
+    formats: Vec<wgpu::TextureFormat>,
+    depth: Option<wgpu::TextureFormat>,
+    // Of its attachments, the swapchain always having 1
+    samples: u32,
+    // Name, topology and vertex layout of the last pipeline set, for draw_mesh
+    bound: Option<(String, wgpu::PrimitiveTopology, Option<VertexLayoutId>)>,
+    // Buffers the last mesh draw left bound. Binding slot 0 or the index buffer through
+    // `raw` between mesh draws has to reset this
+    pub buffers: Option<BufferSetId>,
+    // The frame's counts
+    counts: &'f mut DrawCounts,
+    statistics: Option<&'f mut StatisticsQueries>,
+    // The frame's pipelines
+    pipelines: &'f mut Vec<String>,
+    // A statistics query is open and gets closed with the pass
+    measuring: bool,
+    // This pass's part of the frame's dump, when there is one
+    dump: Option<&'f mut PassDump>,
+}
+
+impl Drop for Pass<'_> {
+    fn drop(&mut self) {
+        if self.measuring {
+            self.raw.end_pipeline_statistics_query();
+        }
+    }
+}
+
+impl Pass<'_> {
+    // MSAA samples per pixel, which build of a pipeline it takes (see sample_key)
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // Adds to the frame's dump, `command` is only made when the frame has one
+    pub fn record(&mut self, command: impl FnOnce() -> Command) {
+        if let Some(dump) = &mut self.dump {
+            dump.commands.push(command());
+        }
+    }
+
+    // Counts vertex and fragment invocations for the rest of the pass, under its label in
+    // FrameStats. Costs a query, so only passes worth watching call it. Does nothing when the
+    // frame isn't measured
+    pub fn measure(&mut self) {
+        if self.measuring {
+            return;
+        }
+        if let Some((set, index)) = self
+            .statistics
+            .as_mut()
+            .and_then(|statistics| statistics.next(self.label))
+        {
+            self.raw.begin_pipeline_statistics_query(set, index);
+            self.measuring = true;
+        }
+    }
+
+    // Checks the pipeline writes exactly the attachments this pass has before binding it,
+    // wgpu would catch it too but only with a generic validation panic
+    pub fn set_pipeline(
+        &mut self,
+        bank: &RenderPipelineBank,
+        name: &str,
+    ) -> Result<(), ForayError> {
+        let pipeline = bank.resolve(name)?;
+
+        if pipeline.targets != self.formats {
+            return Err(ForayError::TargetMismatch {
+                pipeline: name.to_owned(),
+                pass: self.label.to_owned(),
+                pipeline_targets: pipeline.targets.clone(),
+                pass_targets: self.formats.clone(),
+            });
+        }
+
+        if pipeline.depth != self.depth {
+            return Err(ForayError::DepthMismatch {
+                pipeline: name.to_owned(),
+                pass: self.label.to_owned(),
+                pipeline_depth: pipeline.depth,
+                pass_depth: self.depth,
+            });
+        }
+
+        if pipeline.samples != self.samples {
+            return Err(ForayError::SampleMismatch {
+                pipeline: name.to_owned(),
+                pass: self.label.to_owned(),
+                pipeline_samples: pipeline.samples,
+                pass_samples: self.samples,
+            });
+        }
+
+        self.raw.set_pipeline(&pipeline.raw);
+        self.record(|| Command::set_pipeline(name, pipeline));
+        if !self.pipelines.iter().any(|used| used == name) {
+            self.pipelines.push(name.to_owned());
+        }
+        self.bound = Some((name.to_owned(), pipeline.topology, pipeline.vertex_layout));
+        Ok(())
+    }
+
+    // Picks the member of `family` that draws `mesh`'s topology (see family_member), then
+    // that member's build for the mesh's vertex layout (see specialization_for)
+    pub fn set_pipeline_for(
+        &mut self,
+        bank: &RenderPipelineBank,
+        family: &str,
+        mesh: &Mesh,
+    ) -> Result<(), ForayError> {
+        let member = bank.family_member(family, mesh.topology);
+        self.set_pipeline(bank, &bank.specialization_for(&member, mesh.layout))
+    }
+
+    // Refuses meshes the bound pipeline wasn't built for instead of drawing garbage
+    fn count(&mut self, mesh: &Mesh, indices: u32, instances: &Range<u32>) {
+        self.counts.triangles +=
+            u64::from(mesh::triangles(mesh.topology, indices)) * instances.len() as u64;
+        self.counts.draws += 1;
+    }
+
+    pub fn draw_mesh(&mut self, mesh: &Mesh) -> Result<(), ForayError> {
+        self.draw_mesh_instances(mesh, 0..1)
+    }
+
+    pub fn draw_mesh_instances(
+        &mut self,
+        mesh: &Mesh,
+        instances: Range<u32>,
+    ) -> Result<(), ForayError> {
+        if let Some((name, topology, layout)) = &self.bound {
+            mesh.check(name, *topology, *layout)?;
+        }
+        self.count(mesh, mesh.count(), &instances);
+        self.record(|| Command::DrawMesh {
+            mesh: mesh.name.clone(),
+            submesh: None,
+            elements: mesh.count(),
+            instances: (instances.start, instances.end),
+        });
+        if mesh.record(
+            &mut self.raw,
+            &mut self.buffers,
+            mesh.full_range(),
+            instances,
+        ) {
+            self.counts.buffer_binds += 1;
+        }
+        Ok(())
+    }
+
+    // One part of `mesh`, wrapped in a debug group named after it for RenderDoc
+    pub fn draw_submesh(
+        &mut self,
+        mesh: &Mesh,
+        index: usize,
+        instances: Range<u32>,
+    ) -> Result<(), ForayError> {
+        let submesh = mesh.submesh(index)?;
+        if let Some((name, topology, layout)) = &self.bound {
+            mesh.check(name, *topology, *layout)?;
+        }
+        self.count(mesh, submesh.index_range.len() as u32, &instances);
+        self.record(|| Command::DrawMesh {
+            mesh: mesh.name.clone(),
+            submesh: Some(submesh.name.clone()),
+            elements: submesh.index_range.len() as u32,
+            instances: (instances.start, instances.end),
+        });
+        self.raw
+            .push_debug_group(&format!("{} / {}", mesh.name, submesh.name));
+        let range = submesh.index_range.clone();
+        if mesh.record(&mut self.raw, &mut self.buffers, range, instances) {
+            self.counts.buffer_binds += 1;
+        }
+        self.raw.pop_debug_group();
+        Ok(())
+    }
+
+    // One quad per instance, corners from the vertex index, see SpriteRenderer
+    pub fn draw_sprites(&mut self, instances: Range<u32>) {
+        self.counts.sprites += instances.len() as u64;
+        self.counts.sprite_batches += 1;
+        self.record(|| Command::DrawSprites {
+            instances: (instances.start, instances.end),
+        });
+        self.raw.draw(0..6, instances);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+    use crate::shapes::tests::{draw, FORMAT, SIZE};
+    use crate::shapes::ShapeInstance;
+
+    const RED: RgbaColor = RgbaColor::rgba(1.0, 0.0, 0.0, 1.0);
+    const GREEN: RgbaColor = RgbaColor::rgba(0.0, 1.0, 0.0, 1.0);
+
+    #[test]
+    fn backgrounds_pick_load_ops() {
+        let clear = RED.to_wgpu_linear();
+        assert_eq!(Background::Clear(clear).color(), wgpu::LoadOp::Clear(clear));
+        assert_eq!(Background::Preserve.color(), wgpu::LoadOp::Load);
+        assert_eq!(
+            Background::Clear(clear).load(1.0, 0.0),
+            wgpu::LoadOp::Clear(1.0)
+        );
+        assert_eq!(Background::Preserve.load(1.0, 0.0), wgpu::LoadOp::Load);
+        let (color, depth) = if cfg!(debug_assertions) {
+            (wgpu::LoadOp::Clear(DEBUG_MAGENTA), wgpu::LoadOp::Clear(0.0))
+        } else {
+            (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
+        };
+        assert_eq!(Background::DontCare.color(), color);
+        assert_eq!(Background::DontCare.load(1.0, 0.0), depth);
+    }
+
+    // A dot in the middle of the target, the corners stay whatever the background left
+    fn dot() -> ShapeInstance {
+        ShapeInstance::circle(Vec2::ZERO, 4.0, Stroke::Fill, GREEN)
+    }
+
+    fn pixels(gpu: &GpuContext, texture: &wgpu::Texture) -> ([u8; 4], [u8; 4]) {
+        let image = gpu.read_back(texture);
+        (
+            image.get_pixel(0, 0).0,
+            image.get_pixel(SIZE.0 / 2, SIZE.1 / 2).0,
+        )
+    }
+
+    #[test]
+    fn preserve_keeps_the_last_frame() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let texture = gpu.target(SIZE, FORMAT);
+        draw(gpu, &texture, &[], Background::Clear(RED.to_wgpu_linear()));
+        draw(gpu, &texture, &[dot()], Background::Preserve);
+        let (corner, middle) = pixels(gpu, &texture);
+        assert_eq!(corner, [255, 0, 0, 255]);
+        assert_eq!(middle, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn dont_care_shows_gaps() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let texture = gpu.target(SIZE, FORMAT);
+        draw(gpu, &texture, &[], Background::Clear(RED.to_wgpu_linear()));
+        draw(gpu, &texture, &[dot()], Background::DontCare);
+        let (corner, middle) = pixels(gpu, &texture);
+        // Release builds load instead, the red from before shows
+        let gap = if cfg!(debug_assertions) {
+            [255, 0, 255, 255]
+        } else {
+            [255, 0, 0, 255]
+        };
+        assert_eq!(corner, gap);
+        assert_eq!(middle, [0, 255, 0, 255]);
+    }
+}
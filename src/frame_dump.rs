@@ -0,0 +1,162 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::globals::Globals;
+use crate::pipeline_bank::Pipeline;
+
+// `dump_frame` (F10): one frame as it was recorded, for bug reports. The Frame and its
+// passes fill it in from what they hand to wgpu while they record, so passes come in the
+// order they ran and draw_sorted's items in the order they were drawn, not re-derived
+// afterwards. Written as pretty JSON next to a screenshot of the same frame
+#[derive(Serialize)]
+pub struct FrameDump {
+    pub frame: u64,
+    pub view: String,
+    pub surface: String,
+    // The globals block as it was uploaded for the frame. time and delta_time are the wall
+    // clock's, the only part two runs of the same scene won't agree on. Left out for
+    // `render --dump`, which is on the forced clock and uploads no globals, so its dumps of
+    // the same job come out identical
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub globals: Option<Globals>,
+    pub passes: Vec<PassDump>,
+    pub screenshot: Option<PathBuf>,
+}
+
+impl FrameDump {
+    // The globals are filled in once the frame is recorded, they're uploaded along the way
+    pub fn new(frame: u64, view: String, surface: String) -> Self {
+        Self {
+            frame,
+            view,
+            surface,
+            globals: None,
+            passes: Vec::new(),
+            screenshot: None,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("can't write {}, {e}", path.display()))
+    }
+}
+
+#[derive(Serialize)]
+pub struct PassDump {
+    pub label: String,
+    pub targets: Vec<TargetDump>,
+    pub depth: Option<TargetDump>,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Serialize)]
+pub struct TargetDump {
+    // "swapchain", "swapchain ui" or the offscreen target's label
+    pub target: String,
+    pub format: String,
+    // None for the swapchain of an offscreen frame, which only has a view
+    pub size: Option<(u32, u32)>,
+    pub load: String,
+}
+
+// What a pass recorded, in order. Draws made straight through Pass::raw (the overlay's,
+// instanced shapes) only show up as the pipeline they bound
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    SetPipeline {
+        name: String,
+        targets: Vec<String>,
+        depth: Option<String>,
+        topology: String,
+        bind_groups: u32,
+        // Every binding the shader uses, when the pipeline was built with reflection
+        bindings: Option<Vec<String>>,
+    },
+    // draw_sorted binding a material, and the sort key that put it here
+    Material {
+        name: String,
+        group: u32,
+        sort_key: u32,
+        blend: String,
+    },
+    // An item's uniform block, hex bytes as the shader gets them
+    ItemBlock {
+        group: u32,
+        offset: u32,
+        bytes: String,
+    },
+    DrawMesh {
+        mesh: String,
+        submesh: Option<String>,
+        // Indices when indexed, vertices otherwise
+        elements: u32,
+        instances: (u32, u32),
+    },
+    DrawSprites {
+        instances: (u32, u32),
+    },
+    Fullscreen,
+}
+
+impl Command {
+    pub fn set_pipeline(name: &str, pipeline: &Pipeline) -> Self {
+        Command::SetPipeline {
+            name: name.to_owned(),
+            targets: pipeline
+                .targets
+                .iter()
+                .map(|format| format!("{format:?}"))
+                .collect(),
+            depth: pipeline.depth.map(|format| format!("{format:?}")),
+            topology: format!("{:?}", pipeline.topology),
+            bind_groups: pipeline.bind_groups,
+            bindings: pipeline.bindings.as_ref().map(|bindings| {
+                bindings
+                    .iter()
+                    .map(|binding| {
+                        format!(
+                            "@group({}) @binding({}) {}: {}",
+                            binding.group,
+                            binding.binding,
+                            binding.name,
+                            binding.kind.describe()
+                        )
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_clock_is_only_dumped_with_the_globals() {
+        let mut dump = FrameDump::new(3, "Shapes".to_owned(), "Bgra8UnormSrgb".to_owned());
+        let json = serde_json::to_string(&dump).unwrap();
+        assert!(!json.contains("\"globals\"") && !json.contains("time"));
+
+        dump.globals = Some(Globals {
+            time: 1.5,
+            delta_time: 0.016,
+            frame: 3,
+            ..Globals::default()
+        });
+        let json = serde_json::to_string(&dump).unwrap();
+        assert!(json.contains("\"time\":1.5") && json.contains("\"delta_time\":0.016"));
+    }
+}
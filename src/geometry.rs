@@ -0,0 +1,307 @@
+use std::fmt;
+
+use glam::Vec2;
+
+// Parameters a generator below can't make anything sensible out of. Caught where they come
+// in (a console command, a dropped outline file) rather than as an empty or NaN mesh at draw
+// time
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeometryError {
+    // Fewer than 3 corners for a polygon or closed outline
+    TooFewSides(u32),
+    // Zero, negative or not a number at all
+    NonPositiveRadius(f32),
+    // Nothing to build from, says what was empty
+    EmptyInput(&'static str),
+    // A spacing or width of zero or less, or NaN
+    WidthTooSmall(f32),
+    // An infinity or NaN somewhere else, or numbers that multiply out to one. Says where
+    NotFinite(&'static str),
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeometryError::TooFewSides(sides) => {
+                write!(f, "A polygon needs at least 3 sides, got {sides}")
+            }
+            GeometryError::NonPositiveRadius(radius) => {
+                write!(f, "Radius has to be above 0, got {radius}")
+            }
+            GeometryError::EmptyInput(what) => write!(f, "Nothing to build from, {what} is empty"),
+            GeometryError::WidthTooSmall(width) => {
+                write!(f, "Width has to be above 0, got {width}")
+            }
+            GeometryError::NotFinite(what) => {
+                write!(f, "Can't build from {what}, it comes to an infinity or NaN")
+            }
+        }
+    }
+}
+
+// Corners of a regular polygon around the origin, counter-clockwise from `phase` radians.
+// Enough sides and it passes for a circle
+pub fn regular_polygon(sides: u32, radius: f32, phase: f32) -> Result<Vec<Vec2>, GeometryError> {
+    if sides < 3 {
+        return Err(GeometryError::TooFewSides(sides));
+    }
+    // Written so NaN fails too
+    if !(radius > 0.0 && radius.is_finite()) {
+        return Err(GeometryError::NonPositiveRadius(radius));
+    }
+    if !phase.is_finite() {
+        return Err(GeometryError::NotFinite("the phase"));
+    }
+    Ok(regular_polygon_unchecked(sides, radius, phase))
+}
+
+// regular_polygon for callers whose numbers are known good, constants mostly
+pub fn regular_polygon_unchecked(sides: u32, radius: f32, phase: f32) -> Vec<Vec2> {
+    (0..sides)
+        .map(|i| Vec2::from_angle(phase + i as f32 / sides as f32 * std::f32::consts::TAU) * radius)
+        .collect()
+}
+
+// A closed outline read from somewhere else, a file usually. `what` names it in the error
+pub fn closed_outline(points: Vec<Vec2>, what: &'static str) -> Result<Vec<Vec2>, GeometryError> {
+    match points.len() {
+        0 => Err(GeometryError::EmptyInput(what)),
+        sides @ 1..=2 => Err(GeometryError::TooFewSides(sides as u32)),
+        _ if !points.iter().all(|point| point.is_finite()) => Err(GeometryError::NotFinite(what)),
+        _ => Ok(points),
+    }
+}
+
+const GRID_USAGE: &str = "grid <lines per side> <spacing> [major every]";
+
+// Lines of the ground grid, see gizmos::grid
+#[derive(Debug, Clone, Copy)]
+pub struct Grid {
+    // Each side of the center line
+    pub lines_per_side: i32,
+    pub spacing: f32,
+    // Every this many lines is a major one
+    pub major_every: i32,
+}
+
+impl Grid {
+    pub const DEFAULT: Self = Self {
+        lines_per_side: 20,
+        spacing: 0.5,
+        major_every: 4,
+    };
+
+    pub fn new(lines_per_side: i32, spacing: f32, major_every: i32) -> Result<Self, GeometryError> {
+        if lines_per_side < 1 {
+            return Err(GeometryError::EmptyInput("the grid"));
+        }
+        if !(spacing > 0.0 && spacing.is_finite()) {
+            return Err(GeometryError::WidthTooSmall(spacing));
+        }
+        // 0 would be a division by zero picking out the major lines
+        if major_every < 1 {
+            return Err(GeometryError::WidthTooSmall(major_every as f32));
+        }
+        let grid = Self {
+            lines_per_side,
+            spacing,
+            major_every,
+        };
+        // A huge spacing times the lines can still overflow
+        if !grid.extent().is_finite() {
+            return Err(GeometryError::NotFinite("the grid's extent"));
+        }
+        Ok(grid)
+    }
+
+    // The console's `grid <lines per side> <spacing> [major every]`, what's wrong when it
+    // isn't a grid
+    pub fn command(args: &[&str]) -> Result<Self, String> {
+        let usage = || format!("Usage: {GRID_USAGE}");
+        let (lines, spacing, major) = match args {
+            [lines, spacing] => (lines, spacing, None),
+            [lines, spacing, major] => (lines, spacing, Some(major)),
+            _ => return Err(usage()),
+        };
+        let major = major.map_or(Ok(Self::DEFAULT.major_every), |major| major.parse());
+        let (Ok(lines), Ok(spacing), Ok(major)) = (lines.parse(), spacing.parse(), major) else {
+            return Err(usage());
+        };
+        Self::new(lines, spacing, major).map_err(|e| format!("Grid unchanged: {e}"))
+    }
+
+    // How far the lines reach out from the center
+    pub fn extent(&self) -> f32 {
+        self.lines_per_side as f32 * self.spacing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Xorshift over raw bits, so the fuzzing sees every kind of f32 there is
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn any_f32(&mut self) -> f32 {
+            f32::from_bits(self.next())
+        }
+
+        // Spread over magnitudes rather than evenly, 10^lo to 10^hi
+        fn magnitude(&mut self, lo: f32, hi: f32) -> f32 {
+            let t = (self.next() >> 8) as f32 / 16_777_216.0;
+            10f32.powf(lo + (hi - lo) * t)
+        }
+    }
+
+    #[test]
+    fn regular_polygons_name_the_bad_parameter() {
+        for sides in 0..3 {
+            assert_eq!(
+                regular_polygon(sides, 1.0, 0.0),
+                Err(GeometryError::TooFewSides(sides))
+            );
+        }
+        // Sides are checked first
+        assert_eq!(
+            regular_polygon(2, f32::NAN, 0.0),
+            Err(GeometryError::TooFewSides(2))
+        );
+        for radius in [0.0, -0.0, -1.0, f32::NEG_INFINITY, f32::INFINITY, f32::NAN] {
+            let result = regular_polygon(5, radius, 0.0);
+            let named = matches!(
+                result,
+                Err(GeometryError::NonPositiveRadius(r)) if r.to_bits() == radius.to_bits()
+            );
+            assert!(named, "{radius}: {result:?}");
+        }
+        for phase in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(
+                regular_polygon(5, 1.0, phase),
+                Err(GeometryError::NotFinite("the phase"))
+            );
+        }
+    }
+
+    #[test]
+    fn closed_outlines_name_the_bad_parameter() {
+        let triangle = vec![Vec2::ZERO, Vec2::X, Vec2::Y];
+        assert_eq!(
+            closed_outline(Vec::new(), "star.ron"),
+            Err(GeometryError::EmptyInput("star.ron"))
+        );
+        for sides in 1..3 {
+            assert_eq!(
+                closed_outline(triangle[..sides].to_vec(), "star.ron"),
+                Err(GeometryError::TooFewSides(sides as u32))
+            );
+        }
+        for bad in [Vec2::NAN, Vec2::new(0.0, f32::INFINITY)] {
+            let mut points = triangle.clone();
+            points.push(bad);
+            assert_eq!(
+                closed_outline(points, "star.ron"),
+                Err(GeometryError::NotFinite("star.ron"))
+            );
+        }
+        assert_eq!(closed_outline(triangle.clone(), "star.ron"), Ok(triangle));
+    }
+
+    #[test]
+    fn grids_name_the_bad_parameter() {
+        for lines in [0, -1, i32::MIN] {
+            assert_eq!(
+                Grid::new(lines, 0.5, 4).unwrap_err(),
+                GeometryError::EmptyInput("the grid")
+            );
+        }
+        for spacing in [0.0, -0.0, -0.5, f32::INFINITY, f32::NAN] {
+            let result = Grid::new(20, spacing, 4);
+            let named = matches!(
+                result,
+                Err(GeometryError::WidthTooSmall(s)) if s.to_bits() == spacing.to_bits()
+            );
+            assert!(named, "{spacing}: {result:?}");
+        }
+        for major in [0, -2] {
+            assert_eq!(
+                Grid::new(20, 0.5, major).unwrap_err(),
+                GeometryError::WidthTooSmall(major as f32)
+            );
+        }
+        assert_eq!(
+            Grid::new(i32::MAX, f32::MAX / 2.0, 4).unwrap_err(),
+            GeometryError::NotFinite("the grid's extent")
+        );
+        assert!(Grid::new(Grid::DEFAULT.lines_per_side, 0.5, 4).is_ok());
+    }
+
+    #[test]
+    fn the_grid_command_takes_the_major_lines_as_optional() {
+        let grid = Grid::command(&["10", "0.25"]).unwrap();
+        assert_eq!(grid.lines_per_side, 10);
+        assert_eq!(grid.major_every, Grid::DEFAULT.major_every);
+        assert_eq!(Grid::command(&["10", "0.25", "5"]).unwrap().major_every, 5);
+        for args in [
+            &[][..],
+            &["10"],
+            &["ten", "0.25"],
+            &["10", "0.25", "5", "1"],
+        ] {
+            assert_eq!(
+                Grid::command(args).unwrap_err(),
+                format!("Usage: {GRID_USAGE}")
+            );
+        }
+        assert!(Grid::command(&["0", "0.25"])
+            .unwrap_err()
+            .starts_with("Grid unchanged"));
+    }
+
+    #[test]
+    fn whatever_gets_through_is_finite() {
+        let mut rng = Rng(0x9e37_79b9);
+        for _ in 0..2000 {
+            // Anything at all: an error, or points that are all finite
+            let sides = 3 + rng.next() % 64;
+            if let Ok(points) = regular_polygon(sides, rng.any_f32(), rng.any_f32()) {
+                assert!(points.iter().all(|p| p.is_finite()));
+            }
+            // Any valid radius and phase: on the circle, however big or small
+            let radius = rng.magnitude(-30.0, 38.0);
+            let phase = (rng.any_f32() % 1e4).abs();
+            let phase = if phase.is_finite() { phase } else { 0.0 };
+            let points = regular_polygon(sides, radius, phase).unwrap();
+            assert_eq!(points.len(), sides as usize);
+            for point in points {
+                assert!(point.is_finite(), "{sides} {radius} {phase}");
+                assert!(
+                    // Scaled down first, squaring the extremes over- or underflows
+                    ((point / radius).length() - 1.0).abs() < 1e-4,
+                    "{sides} {radius} {phase}"
+                );
+            }
+
+            let lines = 1 + (rng.next() % 100_000) as i32;
+            let major = 1 + (rng.next() % 16) as i32;
+            if let Ok(grid) = Grid::new(lines, rng.any_f32(), major) {
+                assert!(grid.extent().is_finite() && grid.extent() > 0.0, "{grid:?}");
+            }
+            let grid = Grid::new(lines, rng.magnitude(-20.0, 30.0), major).unwrap();
+            assert!(grid.extent().is_finite() && grid.extent() > 0.0, "{grid:?}");
+
+            let points = (0..3 + rng.next() % 8).map(|_| Vec2::new(rng.any_f32(), rng.any_f32()));
+            if let Ok(points) = closed_outline(points.collect(), "fuzz") {
+                assert!(points.iter().all(|p| p.is_finite()));
+            }
+        }
+    }
+}
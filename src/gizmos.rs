@@ -0,0 +1,271 @@
+use glam::{Mat4, Vec3};
+
+use crate::buffer_pool::BufferPool;
+use crate::colors::{RgbaColor, Theme};
+use crate::depth::DepthConvention;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::geometry::Grid;
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::shaders;
+use crate::targets::{TargetHandle, TargetRegistry};
+
+// Must match gizmos.wgsl
+const FLAG_FADE: u32 = 1;
+
+// One world-space segment, `width` is in screen pixels
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GizmoLine {
+    p0: [f32; 3],
+    p1: [f32; 3],
+    color: [f32; 4],
+    width: f32,
+    flags: u32,
+}
+
+impl GizmoLine {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x4,
+        3 => Float32,
+        4 => Uint32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoLine>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    pub fn new(p0: Vec3, p1: Vec3, width: f32, color: RgbaColor) -> Self {
+        Self {
+            p0: p0.into(),
+            p1: p1.into(),
+            color: color.to_f32_array_linear(),
+            width,
+            flags: 0,
+        }
+    }
+
+    // Goes transparent with distance from the eye, see GizmoCamera::fade
+    pub fn fading(mut self) -> Self {
+        self.flags |= FLAG_FADE;
+        self
+    }
+}
+
+// Square grid on the XZ plane centered on the origin. Grid::new has ruled out the sizes
+// that would come out empty or divide by zero
+pub fn grid(frame: &mut Frame, theme: &Theme, grid: &Grid) {
+    let &Grid {
+        lines_per_side,
+        spacing,
+        major_every,
+    } = grid;
+    let extent = grid.extent();
+    for i in -lines_per_side..=lines_per_side {
+        // The axes cover the center lines
+        if i == 0 {
+            continue;
+        }
+        let (color, width) = if i % major_every == 0 {
+            (theme.grid_major, 1.5)
+        } else {
+            (theme.grid_minor, 1.0)
+        };
+        let offset = i as f32 * spacing;
+        frame.lines3d.push(
+            GizmoLine::new(
+                Vec3::new(offset, 0.0, -extent),
+                Vec3::new(offset, 0.0, extent),
+                width,
+                color,
+            )
+            .fading(),
+        );
+        frame.lines3d.push(
+            GizmoLine::new(
+                Vec3::new(-extent, 0.0, offset),
+                Vec3::new(extent, 0.0, offset),
+                width,
+                color,
+            )
+            .fading(),
+        );
+    }
+}
+
+// X red, Y green, Z blue, in the theme's shades
+pub fn axes(frame: &mut Frame, theme: &Theme, length: f32) {
+    for (axis, color) in [
+        (Vec3::X, theme.axis_x),
+        (Vec3::Y, theme.axis_y),
+        (Vec3::Z, theme.axis_z),
+    ] {
+        frame.draw_line_3d(Vec3::ZERO, axis * length, 2.0, color);
+    }
+}
+
+// The 12 edges of a local-space box, moved by `transform`
+pub fn bounds(frame: &mut Frame, transform: Mat4, min: Vec3, max: Vec3, color: RgbaColor) {
+    let corner = |i: usize| {
+        let local = Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        transform.transform_point3(local)
+    };
+    for i in 0..8 {
+        // Each edge once: from a corner to the neighbours with one more bit set
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                frame.draw_line_3d(corner(i), corner(i | bit), 1.5, color);
+            }
+        }
+    }
+}
+
+// What the gizmo pass needs to know about the 3D view it draws over
+pub struct GizmoCamera {
+    pub view_proj: Mat4,
+    pub eye: Vec3,
+    pub fade: (f32, f32),
+}
+
+// Mirrors `struct GizmoCamera` in gizmos.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    viewport: [f32; 2],
+    fade: [f32; 2],
+}
+
+// Draws the frame's 3D lines after the scene, depth tested against it but never writing depth
+pub struct Gizmos {
+    pub enabled: bool,
+    camera_buffer: Tracked<wgpu::Buffer>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Gizmos {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        depth_convention: DepthConvention,
+        bank: &mut RenderPipelineBank,
+        memory: &GpuMemoryTracker,
+    ) -> Self {
+        let camera_buffer = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Gizmo Camera Buffer"),
+                size: std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Gizmo Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Gizmo Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = shaders::create_module(device, "Gizmo Shader", include_str!("gizmos.wgsl"));
+        bank.register_surface(
+            device,
+            "gizmos",
+            &PipelineBuilder::new("Gizmo Pipeline", &shader)
+                .vertex_entry("vs_gizmo")
+                .fragment_entry("fs_gizmo")
+                .vertex_buffer(GizmoLine::desc())
+                .bind_group_layout(&layout)
+                .cull_mode(None)
+                .blend_mode(BlendMode::Alpha)
+                .depth(depth_format, wgpu::CompareFunction::LessEqual)
+                .depth_convention(depth_convention)
+                .depth_write(false),
+            format,
+        );
+
+        Self {
+            enabled: false,
+            camera_buffer,
+            bind_group,
+        }
+    }
+
+    // Onto the swapchain, `depth` is the scene's depth target. Empties the frame's line queue
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        camera: &GizmoCamera,
+        depth: TargetHandle,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let lines = std::mem::take(&mut frame.lines3d);
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        let bytes: &[u8] = bytemuck::cast_slice(&lines);
+        let instance_buffer = pool.acquire(
+            device,
+            "Gizmo Instance Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            bytes.len() as u64,
+        );
+        queue.write_buffer(&instance_buffer, 0, bytes);
+        let uniform = CameraUniform {
+            view_proj: camera.view_proj.to_cols_array_2d(),
+            eye: camera.eye.extend(1.0).into(),
+            viewport: [viewport.0 as f32, viewport.1 as f32],
+            fade: [camera.fade.0, camera.fade.1],
+        };
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let mut pass = frame.pass_with_depth(
+            "Gizmo Pass",
+            &[(ColorTarget::Swapchain, wgpu::LoadOp::Load)],
+            Some((depth, wgpu::LoadOp::Load)),
+            targets,
+        );
+        pass.set_pipeline(bank, "gizmos")?;
+        pass.raw.set_bind_group(0, &self.bind_group, &[]);
+        pass.raw
+            .set_vertex_buffer(0, instance_buffer.slice(..bytes.len() as u64));
+        pass.raw.draw(0..6, 0..lines.len() as u32);
+        Ok(())
+    }
+}
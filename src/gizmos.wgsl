@@ -0,0 +1,115 @@
+// World-space lines with a constant screen-space width, drawn after the scene with the
+// scene's depth buffer bound read-only
+
+struct GizmoCamera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    viewport: vec2<f32>,
+    // Distances (start, end) over which fading lines go transparent
+    fade: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: GizmoCamera;
+
+const FLAG_FADE: u32 = 1u;
+
+// Width of the soft edge in pixels
+const FEATHER: f32 = 1.5;
+
+// How far in front of the eye a segment gets cut, in clip-space w
+const NEAR_W: f32 = 0.0001;
+
+struct GizmoLine {
+    @location(0) p0: vec3<f32>,
+    @location(1) p1: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) width: f32,
+    @location(4) flags: u32,
+}
+
+struct GizmoOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    // Pixels from the center line
+    @location(2) edge: f32,
+    @location(3) @interpolate(flat) half_width: f32,
+    @location(4) @interpolate(flat) flags: u32,
+}
+
+@vertex
+fn vs_gizmo(@builtin(vertex_index) vertex_index: u32, line: GizmoLine) -> GizmoOutput {
+    // x picks the end of the segment, y the side
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    var out: GizmoOutput;
+    var world0 = line.p0;
+    var world1 = line.p1;
+    var clip0 = camera.view_proj * vec4<f32>(world0, 1.0);
+    var clip1 = camera.view_proj * vec4<f32>(world1, 1.0);
+
+    // Cut the segment where it crosses behind the eye, otherwise the projection flips it
+    if clip0.w < NEAR_W && clip1.w < NEAR_W {
+        out.clip_position = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return out;
+    }
+    if clip0.w < NEAR_W {
+        let t = (NEAR_W - clip0.w) / (clip1.w - clip0.w);
+        clip0 = mix(clip0, clip1, t);
+        world0 = mix(world0, world1, t);
+    } else if clip1.w < NEAR_W {
+        let t = (NEAR_W - clip1.w) / (clip0.w - clip1.w);
+        clip1 = mix(clip1, clip0, t);
+        world1 = mix(world1, world0, t);
+    }
+
+    let half_viewport = camera.viewport * 0.5;
+    let screen0 = clip0.xy / clip0.w * half_viewport;
+    let screen1 = clip1.xy / clip1.w * half_viewport;
+    var dir = screen1 - screen0;
+    if dot(dir, dir) > 0.0 {
+        dir = normalize(dir);
+    } else {
+        dir = vec2<f32>(1.0, 0.0);
+    }
+    let normal = vec2<f32>(-dir.y, dir.x);
+
+    let half_width = line.width * 0.5;
+    let pad = half_width + FEATHER;
+    var clip = mix(clip0, clip1, corner.x);
+    clip = vec4<f32>(clip.xy + normal * corner.y * pad / half_viewport * clip.w, clip.zw);
+
+    out.clip_position = clip;
+    out.world = mix(world0, world1, corner.x);
+    out.color = line.color;
+    out.edge = corner.y * pad;
+    out.half_width = half_width;
+    out.flags = line.flags;
+    return out;
+}
+
+@fragment
+fn fs_gizmo(in: GizmoOutput) -> @location(0) vec4<f32> {
+    let d = abs(in.edge) - in.half_width;
+    var alpha = in.color.a * (1.0 - smoothstep(-FEATHER * 0.5, FEATHER * 0.5, d));
+
+    // Far grid lines crowd together into moire, so they fade out with distance
+    if (in.flags & FLAG_FADE) != 0u {
+        let distance = length(in.world - camera.eye.xyz);
+        alpha *= 1.0 - smoothstep(camera.fade.x, camera.fade.y, distance);
+    }
+
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, alpha);
+}
@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::shaders::DEBUG_BINDING;
+
+// Mirrors `struct Globals` in globals.wgsl, keep the two in sync (16 byte aligned)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable, Serialize)]
+pub struct Globals {
+    pub resolution: [f32; 2],
+    pub mouse: [f32; 2],
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame: u32,
+    // Index of the sample being accumulated, 0 when not accumulating
+    pub sample: u32,
+    // Eight band levels 0 to 1, low to high, four to a vec4. Zeros unless capturing with
+    // --audio, see audio.rs
+    pub audio: [[f32; 4]; 2],
+}
+
+// Everything needed to get the Globals into a shader at @group(0) @binding(0). The group
+// also carries debug_print's buffer when there's a DebugChannel
+pub struct GlobalsUniform {
+    pub data: Globals,
+    buffer: Tracked<wgpu::Buffer>,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    // Time stands still, e.g. while frames are being accumulated
+    pub paused: bool,
+    start: Instant,
+    last_tick: Instant,
+}
+
+impl GlobalsUniform {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        debug_channel: Option<&wgpu::Buffer>,
+    ) -> Self {
+        let data = Globals::default();
+        let buffer = memory.create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Globals Buffer"),
+                contents: bytemuck::bytes_of(&data),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+            MemoryCategory::Uniforms,
+        );
+
+        let mut layout_entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }];
+        if let Some(debug_channel) = debug_channel {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: DEBUG_BINDING,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: DEBUG_BINDING,
+                resource: debug_channel.as_entire_binding(),
+            });
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Globals Bind Group Layout"),
+            entries: &layout_entries,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Globals Bind Group"),
+            layout: &layout,
+            entries: &entries,
+        });
+
+        let now = Instant::now();
+        Self {
+            data,
+            buffer,
+            layout,
+            bind_group,
+            paused: false,
+            start: now,
+            last_tick: now,
+        }
+    }
+
+    // Called once per frame, before anything that reads the globals gets recorded
+    pub fn tick(&mut self, queue: &wgpu::Queue, resolution: (u32, u32), mouse: (f64, f64)) {
+        let now = Instant::now();
+        if self.paused {
+            self.start += now - self.last_tick;
+        }
+        self.data.resolution = [resolution.0 as f32, resolution.1 as f32];
+        self.data.mouse = [mouse.0 as f32, mouse.1 as f32];
+        self.data.time = (now - self.start).as_secs_f32();
+        self.data.delta_time = if self.paused {
+            0.0
+        } else {
+            (now - self.last_tick).as_secs_f32()
+        };
+        self.data.frame = self.data.frame.wrapping_add(1);
+        self.last_tick = now;
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.data));
+    }
+}
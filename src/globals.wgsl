@@ -0,0 +1,20 @@
+// Per-frame values shared by every shader that wants them, see globals.rs
+struct Globals {
+    resolution: vec2<f32>, // in pixels
+    mouse: vec2<f32>, // in pixels, origin top-left
+    time: f32, // seconds since startup
+    delta_time: f32,
+    frame: u32,
+    sample: u32, // of the accumulated average, 0 when not accumulating
+    audio: array<vec4<f32>, 2>, // 8 band levels 0 to 1, low to high, zeros without --audio
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+// Subpixel offset for the current sample, along the R2 sequence so accumulated samples
+// cover the pixel evenly. Zero for sample 0
+fn sample_offset() -> vec2<f32> {
+    let n = f32(globals.sample);
+    return fract(vec2<f32>(0.5) + n * vec2<f32>(0.7548776662, 0.5698402910)) - 0.5;
+}
@@ -0,0 +1,1155 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use glam::{Mat4, Quat, Vec2, Vec3};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::deferred::TexturedVertex;
+use crate::error::ForayError;
+use crate::mesh::MeshData;
+
+// What the glTF JSON says, as far as the importer reads it. Unknown fields are left to
+// serde to skip, the ones listed here only to warn about are kept as raw values
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    asset: Asset,
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<SceneDef>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    meshes: Vec<MeshDef>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    materials: Vec<MaterialDef>,
+    #[serde(default)]
+    textures: Vec<TextureDef>,
+    #[serde(default)]
+    images: Vec<ImageDef>,
+    #[serde(default)]
+    skins: Vec<serde_json::Value>,
+    #[serde(default)]
+    animations: Vec<serde_json::Value>,
+    #[serde(default)]
+    cameras: Vec<serde_json::Value>,
+    #[serde(default)]
+    extensions_used: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct SceneDef {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    name: Option<String>,
+    mesh: Option<usize>,
+    #[serde(default)]
+    children: Vec<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    skin: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct MeshDef {
+    name: Option<String>,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+struct Primitive {
+    attributes: BTreeMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: Option<u32>,
+    #[serde(default)]
+    targets: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialDef {
+    name: Option<String>,
+    pbr_metallic_roughness: Option<Pbr>,
+    alpha_mode: Option<String>,
+    alpha_cutoff: Option<f32>,
+    normal_texture: Option<serde_json::Value>,
+    occlusion_texture: Option<serde_json::Value>,
+    emissive_texture: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pbr {
+    base_color_factor: Option<[f32; 4]>,
+    base_color_texture: Option<TextureRef>,
+    roughness_factor: Option<f32>,
+    metallic_roughness_texture: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextureRef {
+    index: usize,
+    #[serde(default)]
+    tex_coord: u32,
+}
+
+#[derive(Deserialize)]
+struct TextureDef {
+    source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageDef {
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+// A .gltf or .glb file read into what DeferredDemo::import_gltf uploads. Only what the
+// textured deferred pipeline can draw comes through: triangle lists with positions,
+// normals and the first UV set, and each material's base color. Everything else in the
+// file is named in `ignored` instead of failing the import
+pub struct GltfModel {
+    // By glTF mesh index, None for meshes without a primitive it could draw
+    pub meshes: Vec<Option<GltfMesh>>,
+    pub materials: Vec<GltfMaterial>,
+    // The node hierarchy flattened, every node with a mesh at its world transform
+    pub items: Vec<GltfItem>,
+    pub ignored: BTreeSet<String>,
+}
+
+// A submesh per primitive drawn, and each one's material index
+pub struct GltfMesh {
+    pub name: String,
+    pub data: MeshData<TexturedVertex>,
+    pub materials: Vec<Option<usize>>,
+}
+
+pub struct GltfMaterial {
+    pub name: String,
+    // Linear, as glTF stores it
+    pub base_color: [f32; 4],
+    pub texture: Option<RgbaImage>,
+    pub roughness: f32,
+    // Some for alphaMode MASK
+    pub cutoff: Option<f32>,
+}
+
+pub struct GltfItem {
+    pub mesh: usize,
+    pub transform: Mat4,
+}
+
+pub fn load(path: &Path) -> Result<GltfModel, ForayError> {
+    let error = |reason: String| ForayError::Gltf {
+        path: path.to_owned(),
+        reason,
+    };
+    let bytes = std::fs::read(path).map_err(|e| error(e.to_string()))?;
+    parse(&bytes, path.parent().unwrap_or(Path::new(""))).map_err(error)
+}
+
+// A .gltf or .glb file's bytes, with relative URIs in it read from `directory`
+fn parse(bytes: &[u8], directory: &Path) -> Result<GltfModel, String> {
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+        split_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let document: Document = serde_json::from_slice(json).map_err(|e| format!("bad JSON, {e}"))?;
+    if !document.asset.version.starts_with("2.") {
+        return Err(format!(
+            "glTF {} isn't supported, only 2.x",
+            document.asset.version
+        ));
+    }
+    let buffers = document
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| load_buffer(directory, index, buffer, bin))
+        .collect::<Result<Vec<_>, _>>()?;
+    Reader {
+        document: &document,
+        buffers,
+        directory,
+        ignored: BTreeSet::new(),
+    }
+    .read()
+}
+
+// The JSON chunk and the binary one if there is one
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let word = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize)
+            .ok_or_else(|| format!("GLB ends early, at byte {at}"))
+    };
+    if word(4)? != 2 {
+        return Err(format!("GLB container version {} isn't 2", word(4)?));
+    }
+    let length = word(8)?.min(bytes.len());
+    let mut chunks = Vec::new();
+    let mut at = 12;
+    while at + 8 <= length {
+        let (size, kind) = (word(at)?, word(at + 4)?);
+        let data = bytes
+            .get(at + 8..at + 8 + size)
+            .ok_or_else(|| format!("GLB chunk at byte {at} runs past the end"))?;
+        chunks.push((kind, data));
+        at += 8 + size;
+    }
+    match chunks[..] {
+        [(0x4E4F_534A, json), (0x004E_4942, bin), ..] => Ok((json, Some(bin))),
+        [(0x4E4F_534A, json), ..] => Ok((json, None)),
+        _ => Err("GLB doesn't start with a JSON chunk".to_owned()),
+    }
+}
+
+fn load_buffer(
+    directory: &Path,
+    index: usize,
+    buffer: &Buffer,
+    bin: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let data = match &buffer.uri {
+        // Only the first buffer of a GLB can be its binary chunk
+        None => bin
+            .filter(|_| index == 0)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| format!("buffer {index} has no uri and no GLB chunk"))?,
+        Some(uri) => read_uri(directory, uri)?,
+    };
+    if data.len() < buffer.byte_length {
+        return Err(format!(
+            "buffer {index} is {} bytes, it says {}",
+            data.len(),
+            buffer.byte_length
+        ));
+    }
+    Ok(data)
+}
+
+// A base64 data URI or a path relative to the file
+fn read_uri(directory: &Path, uri: &str) -> Result<Vec<u8>, String> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data
+            .split_once(";base64,")
+            .ok_or_else(|| "data URIs have to be base64".to_owned())?;
+        return base64(encoded);
+    }
+    let file = directory.join(percent_decode(uri));
+    std::fs::read(&file).map_err(|e| format!("{}: {e}", file.display()))
+}
+
+// %20 and the like, URIs in glTF files are escaped
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        let escaped = (bytes[at] == b'%')
+            .then(|| uri.get(at + 1..at + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                at += 3;
+            }
+            None => {
+                out.push(bytes[at]);
+                at += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Padding is optional, but only at the end and only as much as the last group needs. A
+// lone character left over can't be a whole byte, that's a cut short URI
+fn base64(encoded: &str) -> Result<Vec<u8>, String> {
+    let data = encoded.trim_end_matches('=');
+    let padding = encoded.len() - data.len();
+    if data.len() % 4 == 1 {
+        return Err(format!("{} characters of base64 is cut short", data.len()));
+    }
+    if padding > 0 && (padding > 2 || !encoded.len().is_multiple_of(4)) {
+        return Err(format!("base64 can't end in {padding} '='"));
+    }
+    let mut out = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    let (mut bits, mut held) = (0u32, 0);
+    for byte in data.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(format!("{:?} isn't base64", byte as char)),
+        };
+        bits = bits << 6 | u32::from(value);
+        held += 6;
+        if held >= 8 {
+            held -= 8;
+            out.push((bits >> held) as u8);
+        }
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    document: &'a Document,
+    buffers: Vec<Vec<u8>>,
+    directory: &'a Path,
+    ignored: BTreeSet<String>,
+}
+
+impl Reader<'_> {
+    fn read(mut self) -> Result<GltfModel, String> {
+        let document = self.document;
+        for (what, count) in [
+            ("skins", document.skins.len()),
+            ("animations", document.animations.len()),
+            ("cameras", document.cameras.len()),
+        ] {
+            if count > 0 {
+                self.ignored.insert(format!("{count} {what}"));
+            }
+        }
+        for extension in &document.extensions_used {
+            self.ignored.insert(format!("extension {extension}"));
+        }
+        let meshes = (0..document.meshes.len())
+            .map(|index| self.mesh(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let materials = (0..document.materials.len())
+            .map(|index| self.material(index))
+            .collect();
+        let items = self.items(&meshes)?;
+        Ok(GltfModel {
+            meshes,
+            materials,
+            items,
+            ignored: self.ignored,
+        })
+    }
+
+    fn mesh(&mut self, index: usize) -> Result<Option<GltfMesh>, String> {
+        let mesh = &self.document.meshes[index];
+        let name = mesh.name.clone().unwrap_or_else(|| format!("mesh {index}"));
+        let mut parts = Vec::new();
+        let mut materials = Vec::new();
+        for (number, primitive) in mesh.primitives.iter().enumerate() {
+            let what = format!("{name} primitive {number}");
+            if let Some(mode) = primitive.mode.filter(|&mode| mode != 4) {
+                self.ignored.insert(format!(
+                    "{what}: mode {mode}, only triangle lists are drawn"
+                ));
+                continue;
+            }
+            if !primitive.targets.is_empty() {
+                self.ignored.insert(format!("{what}: morph targets"));
+            }
+            for attribute in primitive.attributes.keys() {
+                if !["POSITION", "NORMAL", "TEXCOORD_0"].contains(&attribute.as_str()) {
+                    self.ignored.insert(format!("attribute {attribute}"));
+                }
+            }
+            let Some(&positions) = primitive.attributes.get("POSITION") else {
+                self.ignored.insert(format!("{what}: no POSITION"));
+                continue;
+            };
+            let positions: Vec<Vec3> = self
+                .floats(positions, "VEC3")?
+                .chunks(3)
+                .map(Vec3::from_slice)
+                .collect();
+            let count = positions.len();
+            let indices = match primitive.indices {
+                Some(accessor) => self.indices(accessor, count)?,
+                None => (0..count as u32).collect(),
+            };
+            if indices.len() % 3 != 0 {
+                return Err(format!(
+                    "{what} has {} indices, not whole triangles",
+                    indices.len()
+                ));
+            }
+            let normals: Vec<Vec3> = match primitive.attributes.get("NORMAL") {
+                Some(&normals) => self
+                    .floats(normals, "VEC3")?
+                    .chunks(3)
+                    .map(Vec3::from_slice)
+                    .collect(),
+                None => smooth_normals(&positions, &indices),
+            };
+            let uvs: Vec<Vec2> = match primitive.attributes.get("TEXCOORD_0") {
+                Some(&uvs) => self
+                    .floats(uvs, "VEC2")?
+                    .chunks(2)
+                    .map(Vec2::from_slice)
+                    .collect(),
+                None => vec![Vec2::ZERO; count],
+            };
+            if normals.len() != count || uvs.len() != count {
+                return Err(format!(
+                    "{what}'s attributes don't have the same number of vertices"
+                ));
+            }
+            let vertices = (0..count)
+                .map(|vertex| TexturedVertex {
+                    position: positions[vertex].into(),
+                    normal: normals[vertex].into(),
+                    uv: uvs[vertex].into(),
+                })
+                .collect();
+            parts.push(MeshData::new(
+                &format!("primitive {number}"),
+                vertices,
+                indices,
+            ));
+            if let Some(material) = primitive.material {
+                if material >= self.document.materials.len() {
+                    return Err(format!("{what} uses material {material}, there's none"));
+                }
+            }
+            materials.push(primitive.material);
+        }
+        Ok((!parts.is_empty()).then(|| GltfMesh {
+            name,
+            data: MeshData::merge(parts),
+            materials,
+        }))
+    }
+
+    // Each element's bytes, checked to be inside its buffer view. Err for sparse accessors
+    fn elements(&self, index: usize, kind: &str) -> Result<(&Accessor, Vec<&[u8]>), String> {
+        let accessor = self
+            .document
+            .accessors
+            .get(index)
+            .ok_or_else(|| format!("accessor {index} doesn't exist"))?;
+        if accessor.sparse.is_some() {
+            return Err(format!(
+                "accessor {index} is sparse, sparse accessors aren't supported"
+            ));
+        }
+        if accessor.kind != kind {
+            return Err(format!(
+                "accessor {index} is {}, {kind} was expected",
+                accessor.kind
+            ));
+        }
+        let size = component_size(accessor.component_type).ok_or_else(|| {
+            format!(
+                "accessor {index} has component type {}",
+                accessor.component_type
+            )
+        })? * components(kind);
+        let Some(view_index) = accessor.buffer_view else {
+            // No view means all zeros, with nothing in the file to say how many is too many
+            if accessor.count > MAX_ZEROS {
+                return Err(format!(
+                    "accessor {index} has no data but {} elements",
+                    accessor.count
+                ));
+            }
+            return Ok((accessor, vec![&ZEROS[..size]; accessor.count]));
+        };
+        let data = self.view(view_index)?;
+        let stride = self.document.buffer_views[view_index]
+            .byte_stride
+            .unwrap_or(size);
+        let past = || format!("accessor {index} runs past buffer view {view_index}");
+        if stride < size {
+            return Err(format!(
+                "accessor {index}'s elements are {size} bytes, more than view {view_index}'s stride"
+            ));
+        }
+        // Checked before anything's collected, so a made up count can't allocate much
+        if accessor.count > data.len() / stride + 1 {
+            return Err(past());
+        }
+        (0..accessor.count)
+            .map(|element| {
+                let at = element
+                    .checked_mul(stride)
+                    .and_then(|at| at.checked_add(accessor.byte_offset))
+                    .ok_or_else(past)?;
+                at.checked_add(size)
+                    .and_then(|end| data.get(at..end))
+                    .ok_or_else(past)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|elements| (accessor, elements))
+    }
+
+    // A buffer view's bytes, checked to be inside its buffer
+    fn view(&self, view_index: usize) -> Result<&[u8], String> {
+        let view = self
+            .document
+            .buffer_views
+            .get(view_index)
+            .ok_or_else(|| format!("buffer view {view_index} doesn't exist"))?;
+        view.byte_offset
+            .checked_add(view.byte_length)
+            .and_then(|end| self.buffers.get(view.buffer)?.get(view.byte_offset..end))
+            .ok_or_else(|| format!("buffer view {view_index} is outside its buffer"))
+    }
+
+    // Float components, normalized integers scaled to -1..1 or 0..1 as the spec has it
+    fn floats(&self, index: usize, kind: &str) -> Result<Vec<f32>, String> {
+        let (accessor, elements) = self.elements(index, kind)?;
+        let size = component_size(accessor.component_type).unwrap_or(4);
+        Ok(elements
+            .into_iter()
+            .flat_map(|element| element.chunks(size))
+            .map(|bytes| component(bytes, accessor.component_type, accessor.normalized))
+            .collect())
+    }
+
+    // u8, u16 or u32 indices, each checked against the vertex count
+    fn indices(&self, index: usize, vertices: usize) -> Result<Vec<u32>, String> {
+        let (accessor, elements) = self.elements(index, "SCALAR")?;
+        if ![5121, 5123, 5125].contains(&accessor.component_type) {
+            return Err(format!(
+                "index accessor {index} has component type {}",
+                accessor.component_type
+            ));
+        }
+        elements
+            .into_iter()
+            .map(|bytes| {
+                let mut word = [0u8; 4];
+                word[..bytes.len()].copy_from_slice(bytes);
+                let vertex = u32::from_le_bytes(word);
+                if vertex as usize >= vertices {
+                    return Err(format!(
+                        "index accessor {index} points at vertex {vertex} of {vertices}"
+                    ));
+                }
+                Ok(vertex)
+            })
+            .collect()
+    }
+
+    fn material(&mut self, index: usize) -> GltfMaterial {
+        let material = &self.document.materials[index];
+        let name = material
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("material {index}"));
+        for (what, present) in [
+            ("normal textures", material.normal_texture.is_some()),
+            ("occlusion textures", material.occlusion_texture.is_some()),
+            ("emissive textures", material.emissive_texture.is_some()),
+            (
+                "metallic-roughness textures",
+                material
+                    .pbr_metallic_roughness
+                    .as_ref()
+                    .is_some_and(|pbr| pbr.metallic_roughness_texture.is_some()),
+            ),
+        ] {
+            if present {
+                self.ignored.insert(what.to_owned());
+            }
+        }
+        let cutoff = match material.alpha_mode.as_deref() {
+            Some("MASK") => Some(material.alpha_cutoff.unwrap_or(0.5)),
+            Some("BLEND") => {
+                self.ignored
+                    .insert(format!("{name}: alphaMode BLEND, drawn opaque"));
+                None
+            }
+            _ => None,
+        };
+        let pbr = material.pbr_metallic_roughness.as_ref();
+        let texture = pbr
+            .and_then(|pbr| pbr.base_color_texture.as_ref())
+            .and_then(|texture| match self.texture(texture) {
+                Ok(image) => Some(image),
+                Err(reason) => {
+                    self.ignored
+                        .insert(format!("{name}'s base color texture: {reason}"));
+                    None
+                }
+            });
+        GltfMaterial {
+            name,
+            base_color: pbr
+                .and_then(|pbr| pbr.base_color_factor)
+                .unwrap_or([1.0; 4]),
+            texture,
+            roughness: pbr.and_then(|pbr| pbr.roughness_factor).unwrap_or(1.0),
+            cutoff,
+        }
+    }
+
+    fn texture(&self, texture: &TextureRef) -> Result<RgbaImage, String> {
+        if texture.tex_coord != 0 {
+            return Err(format!(
+                "uses TEXCOORD_{}, only TEXCOORD_0 is read",
+                texture.tex_coord
+            ));
+        }
+        let source = self
+            .document
+            .textures
+            .get(texture.index)
+            .and_then(|texture| texture.source)
+            .ok_or_else(|| format!("texture {} has no image", texture.index))?;
+        let image = self
+            .document
+            .images
+            .get(source)
+            .ok_or_else(|| format!("image {source} doesn't exist"))?;
+        let bytes = match (&image.uri, image.buffer_view) {
+            (Some(uri), _) => read_uri(self.directory, uri)?,
+            (None, Some(view_index)) => self.view(view_index)?.to_vec(),
+            (None, None) => return Err(format!("image {source} has no data")),
+        };
+        image::load_from_memory(&bytes)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| format!("image {source}: {e}"))
+    }
+
+    // Depth first from the scene's roots, each node's transform applied under its parent's
+    fn items(&mut self, meshes: &[Option<GltfMesh>]) -> Result<Vec<GltfItem>, String> {
+        let document = self.document;
+        let roots: Vec<usize> = match document.scenes.get(document.scene.unwrap_or(0)) {
+            Some(scene) => scene.nodes.clone(),
+            // No scene, every node nothing lists as a child is a root
+            None => {
+                let children: BTreeSet<usize> = document
+                    .nodes
+                    .iter()
+                    .flat_map(|node| node.children.iter().copied())
+                    .collect();
+                (0..document.nodes.len())
+                    .filter(|node| !children.contains(node))
+                    .collect()
+            }
+        };
+        let mut items = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut stack: Vec<(usize, Mat4)> = roots
+            .into_iter()
+            .rev()
+            .map(|root| (root, Mat4::IDENTITY))
+            .collect();
+        while let Some((index, parent)) = stack.pop() {
+            let node = document
+                .nodes
+                .get(index)
+                .ok_or_else(|| format!("node {index} doesn't exist"))?;
+            // A node in two places would make the hierarchy a graph, the spec rules it out
+            if !visited.insert(index) {
+                return Err(format!("node {index} has more than one parent"));
+            }
+            let transform = parent * node_transform(node);
+            if node.skin.is_some() {
+                let name = node.name.clone().unwrap_or_else(|| format!("node {index}"));
+                self.ignored
+                    .insert(format!("{name}: skinned, drawn unskinned"));
+            }
+            if let Some(mesh) = node.mesh {
+                match meshes.get(mesh) {
+                    Some(Some(_)) => items.push(GltfItem { mesh, transform }),
+                    Some(None) => {}
+                    None => return Err(format!("node {index} uses mesh {mesh}, there's none")),
+                }
+            }
+            stack.extend(node.children.iter().rev().map(|&child| (child, transform)));
+        }
+        Ok(items)
+    }
+}
+
+// Enough zeros for the largest element, a MAT4 of floats, for accessors without a view
+const ZEROS: [u8; 64] = [0; 64];
+// How many elements an accessor without a view can have, far more than a real file leaves
+// out and far less than a made up count would have allocated
+const MAX_ZEROS: usize = 1 << 20;
+
+fn component_size(component_type: u32) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+fn components(kind: &str) -> usize {
+    match kind {
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" | "MAT2" => 4,
+        "MAT3" => 9,
+        "MAT4" => 16,
+        _ => 1,
+    }
+}
+
+fn component(bytes: &[u8], component_type: u32, normalized: bool) -> f32 {
+    let scale = |value: f32, max: f32| {
+        if normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    };
+    match component_type {
+        5120 => scale(f32::from(bytes[0] as i8), 127.0),
+        5121 => scale(f32::from(bytes[0]), 255.0),
+        5122 => scale(f32::from(i16::from_le_bytes([bytes[0], bytes[1]])), 32767.0),
+        5123 => scale(f32::from(u16::from_le_bytes([bytes[0], bytes[1]])), 65535.0),
+        5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+fn node_transform(node: &Node) -> Mat4 {
+    match node.matrix {
+        Some(matrix) => Mat4::from_cols_array(&matrix),
+        None => Mat4::from_scale_rotation_translation(
+            node.scale.map_or(Vec3::ONE, Vec3::from),
+            node.rotation.map_or(Quat::IDENTITY, Quat::from_array),
+            node.translation.map_or(Vec3::ZERO, Vec3::from),
+        ),
+    }
+}
+
+// For primitives without normals: each vertex gets the area weighted average of the
+// triangles around it. The spec asks for flat normals, which would take splitting vertices
+fn smooth_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+        let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for vertex in [a, b, c] {
+            normals[vertex] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or(Vec3::Y))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn checker_cube() -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/models/checker_cube.gltf");
+        serde_json::from_slice(&std::fs::read(path).expect("No checker cube")).unwrap()
+    }
+
+    // A GLB holding `json` and `bin`, each chunk padded to 4 bytes the way the spec has it
+    fn glb(json: &Value, bin: &[u8]) -> Vec<u8> {
+        let mut json = serde_json::to_vec(json).unwrap();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut out = Vec::with_capacity(length);
+        for word in [0x4654_6C67, 2, length] {
+            out.extend_from_slice(&(word as u32).to_le_bytes());
+        }
+        for (kind, data) in [(0x4E4F_534A_u32, &json), (0x004E_4942, &bin)] {
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&kind.to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    // One triangle, indexed with `component_type` indices (or drawn in order when None),
+    // everything in the GLB's binary chunk. Tests break it in the way they need
+    fn triangle(component_type: Option<u32>) -> (Value, Vec<u8>) {
+        let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mut bin: Vec<u8> = bytemuck::cast_slice(&positions).to_vec();
+        let mut document = json!({
+            "asset": {"version": "2.0"},
+            "nodes": [{"mesh": 0, "translation": [1.0, 2.0, 3.0]}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+            "bufferViews": [{"buffer": 0, "byteOffset": 0, "byteLength": 36}],
+            "buffers": [{"byteLength": 0}],
+        });
+        if let Some(component_type) = component_type {
+            let size = component_size(component_type).unwrap();
+            for index in [2u32, 1, 0] {
+                bin.extend_from_slice(&index.to_le_bytes()[..size]);
+            }
+            document["meshes"][0]["primitives"][0]["indices"] = json!(1);
+            let push = |list: &mut Value, entry| list.as_array_mut().unwrap().push(entry);
+            push(
+                &mut document["accessors"],
+                json!({
+                    "bufferView": 1,
+                    "componentType": component_type,
+                    "count": 3,
+                    "type": "SCALAR",
+                }),
+            );
+            push(
+                &mut document["bufferViews"],
+                json!({"buffer": 0, "byteOffset": 36, "byteLength": size * 3}),
+            );
+        }
+        document["buffers"][0]["byteLength"] = json!(bin.len());
+        (document, bin)
+    }
+
+    fn parsed(document: &Value, bin: &[u8]) -> Result<GltfModel, String> {
+        parse(&glb(document, bin), Path::new(""))
+    }
+
+    fn assert_checker_cube(model: &GltfModel) {
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = model.meshes[0].as_ref().expect("Cube wasn't read");
+        assert_eq!(mesh.name, "Checker Cube");
+        assert_eq!(mesh.data.vertices.len(), 24);
+        assert_eq!(mesh.data.indices.len(), 36);
+        assert!(mesh.data.indices.iter().all(|&index| index < 24));
+        assert_eq!(mesh.materials, [Some(0)]);
+        // Normalized u16 UVs come out 0 to 1
+        assert!(mesh
+            .data
+            .vertices
+            .iter()
+            .flat_map(|vertex| vertex.uv)
+            .all(|uv| (0.0..=1.0).contains(&uv)));
+        assert!(mesh.data.vertices.iter().any(|vertex| vertex.uv[0] > 0.99));
+
+        let material = &model.materials[0];
+        assert_eq!(material.name, "Checker");
+        assert_eq!(
+            material.base_color.map(f32::to_bits),
+            [1.0, 0.8, 0.6, 1.0].map(f32::to_bits)
+        );
+        assert!((material.roughness - 0.7).abs() < 1e-6);
+        assert_eq!(material.cutoff, None);
+        let texture = material.texture.as_ref().expect("No base color texture");
+        assert_eq!(texture.dimensions(), (8, 8));
+
+        // Root turns everything 45 degrees about Y, Cap sits under Top
+        let root = Mat4::from_rotation_y(std::f32::consts::FRAC_PI_4);
+        let local = |translation: [f32; 3], scale: f32| {
+            Mat4::from_translation(translation.into()) * Mat4::from_scale(Vec3::splat(scale))
+        };
+        let base = root
+            * Mat4::from_translation(Vec3::new(0.0, -0.25, 0.0))
+            * Mat4::from_scale(Vec3::new(1.5, 0.5, 1.5));
+        let top = root * local([0.0, 0.5, 0.0], 0.75);
+        let cap = top * local([0.0, 0.75, 0.0], 0.5);
+        assert_eq!(model.items.len(), 3);
+        for (item, expected) in model.items.iter().zip([base, top, cap]) {
+            assert_eq!(item.mesh, 0);
+            assert!(
+                item.transform.abs_diff_eq(expected, 1e-5),
+                "{}",
+                item.transform
+            );
+        }
+        assert!(model.ignored.is_empty(), "{:?}", model.ignored);
+    }
+
+    #[test]
+    fn reads_the_checker_cube() {
+        let bytes = serde_json::to_vec(&checker_cube()).unwrap();
+        assert_checker_cube(&parse(&bytes, Path::new("")).unwrap());
+    }
+
+    #[test]
+    fn reads_the_checker_cube_repacked_as_glb() {
+        let mut document = checker_cube();
+        let uri = document["buffers"][0]["uri"].as_str().unwrap().to_owned();
+        let bin = read_uri(Path::new(""), &uri).unwrap();
+        document["buffers"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("uri");
+        let bytes = glb(&document, &bin);
+        let (json, chunk) = split_glb(&bytes).unwrap();
+        assert!(json.starts_with(b"{"));
+        // Padded up to a whole word, the buffer says how much of it counts
+        assert_eq!(chunk.map(<[u8]>::len), Some(bin.len().next_multiple_of(4)));
+        assert_checker_cube(&parse(&bytes, Path::new("")).unwrap());
+    }
+
+    #[test]
+    fn indices_of_every_width_are_read() {
+        for component_type in [5121, 5123, 5125] {
+            let (document, bin) = triangle(Some(component_type));
+            let model = parsed(&document, &bin).unwrap();
+            let mesh = model.meshes[0].as_ref().unwrap();
+            assert_eq!(
+                mesh.data.indices,
+                [2, 1, 0],
+                "component type {component_type}"
+            );
+            let expected = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+            assert!(model.items[0].transform.abs_diff_eq(expected, 0.0));
+        }
+        // None drawn in order, and with normals made up facing +Z
+        let (document, bin) = triangle(None);
+        let model = parsed(&document, &bin).unwrap();
+        let mesh = model.meshes[0].as_ref().unwrap();
+        assert_eq!(mesh.data.indices, [0, 1, 2]);
+        assert!(mesh
+            .data
+            .vertices
+            .iter()
+            .all(|vertex| Vec3::from(vertex.normal).abs_diff_eq(Vec3::Z, 1e-6)));
+    }
+
+    #[test]
+    fn broken_glb_containers_are_refused() {
+        let (document, bin) = triangle(None);
+        let bytes = glb(&document, &bin);
+        let mut version = bytes.clone();
+        version[4] = 1;
+        assert!(split_glb(&version).unwrap_err().contains("version 1"));
+        // A chunk that says it's longer than the file
+        let mut long = bytes.clone();
+        long[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(split_glb(&long).unwrap_err().contains("runs past the end"));
+        assert!(split_glb(&bytes[..10]).is_err());
+        // The binary chunk first
+        let mut swapped = bytes.clone();
+        swapped[16..20].copy_from_slice(&0x004E_4942_u32.to_le_bytes());
+        assert!(split_glb(&swapped).is_err());
+    }
+
+    #[test]
+    fn sparse_accessors_are_refused() {
+        let (mut document, bin) = triangle(None);
+        document["accessors"][0]["sparse"] = json!({"count": 1});
+        let error = parsed(&document, &bin).err().unwrap();
+        assert_eq!(
+            error,
+            "accessor 0 is sparse, sparse accessors aren't supported"
+        );
+    }
+
+    #[test]
+    fn what_isnt_drawn_is_listed_as_ignored() {
+        let (mut document, bin) = triangle(None);
+        document["skins"] = json!([{"joints": [0]}]);
+        document["animations"] =
+            json!([{"channels": [], "samplers": []}, {"channels": [], "samplers": []}]);
+        document["cameras"] = json!([{"type": "perspective"}]);
+        document["extensionsUsed"] = json!(["KHR_materials_clearcoat", "KHR_texture_transform"]);
+        document["nodes"][0]["name"] = json!("Body");
+        document["nodes"][0]["skin"] = json!(0);
+        document["meshes"][0]["primitives"][0]["attributes"]["JOINTS_0"] = json!(0);
+        let model = parsed(&document, &bin).unwrap();
+        let ignored: Vec<_> = model.ignored.iter().map(String::as_str).collect();
+        for expected in [
+            "1 skins",
+            "2 animations",
+            "1 cameras",
+            "extension KHR_materials_clearcoat",
+            "extension KHR_texture_transform",
+            "Body: skinned, drawn unskinned",
+            "attribute JOINTS_0",
+        ] {
+            assert!(ignored.contains(&expected), "{expected} not in {ignored:?}");
+        }
+        // Still drawn
+        assert_eq!(model.items.len(), 1);
+    }
+
+    // What a broken file's error says, and how to break it
+    type Case<'a> = (&'a str, &'a dyn Fn(&mut Value));
+
+    #[test]
+    fn out_of_range_indices_and_offsets_are_errors() {
+        let broken = |change: &dyn Fn(&mut Value)| {
+            let (mut document, bin) = triangle(Some(5123));
+            change(&mut document);
+            parsed(&document, &bin)
+        };
+        assert!(broken(&|_| {}).is_ok());
+        let cases: [Case; 14] = [
+            ("runs past buffer view 1", &|d| {
+                d["accessors"][1]["count"] = json!(4);
+            }),
+            ("not whole triangles", &|d| {
+                d["accessors"][1]["count"] = json!(2);
+            }),
+            ("runs past buffer view 0", &|d| {
+                d["accessors"][0]["byteOffset"] = json!(u64::MAX);
+            }),
+            ("runs past buffer view 0", &|d| {
+                d["accessors"][0]["count"] = json!(u64::MAX);
+            }),
+            ("buffer view 7 doesn't exist", &|d| {
+                d["accessors"][0]["bufferView"] = json!(7);
+            }),
+            ("component type 1234", &|d| {
+                d["accessors"][0]["componentType"] = json!(1234);
+            }),
+            ("outside its buffer", &|d| {
+                d["bufferViews"][0]["byteOffset"] = json!(u64::MAX);
+            }),
+            ("outside its buffer", &|d| {
+                d["bufferViews"][0]["byteLength"] = json!(u64::MAX);
+            }),
+            ("more than view 0's stride", &|d| {
+                d["bufferViews"][0]["byteStride"] = json!(4);
+            }),
+            ("outside its buffer", &|d| {
+                d["bufferViews"][1]["byteLength"] = json!(1000);
+            }),
+            ("accessor 9 doesn't exist", &|d| {
+                d["meshes"][0]["primitives"][0]["indices"] = json!(9);
+            }),
+            ("material 0, there's none", &|d| {
+                d["meshes"][0]["primitives"][0]["material"] = json!(0);
+            }),
+            ("mesh 3, there's none", &|d| {
+                d["nodes"][0]["mesh"] = json!(3);
+            }),
+            // Its own child, from a scene that lists it as a root
+            ("more than one parent", &|d| {
+                d["scenes"] = json!([{"nodes": [0]}]);
+                d["nodes"][0]["children"] = json!([0]);
+            }),
+        ];
+        for (expected, change) in cases {
+            let error = broken(change)
+                .err()
+                .unwrap_or_else(|| panic!("Read, not {expected}"));
+            assert!(error.contains(expected), "{error} isn't {expected}");
+        }
+        // The index pointing past the last vertex
+        let mut bin = triangle(Some(5123)).1;
+        bin[36..38].copy_from_slice(&3u16.to_le_bytes());
+        let error = parsed(&triangle(Some(5123)).0, &bin).err().unwrap();
+        assert_eq!(error, "index accessor 1 points at vertex 3 of 3");
+        // A huge zero filled accessor is refused rather than allocated
+        let error = broken(&|d| {
+            d["accessors"][0]
+                .as_object_mut()
+                .unwrap()
+                .remove("bufferView");
+            d["accessors"][0]["count"] = json!(u64::MAX);
+        })
+        .err()
+        .unwrap();
+        assert!(error.contains("has no data"), "{error}");
+    }
+
+    #[test]
+    fn normalized_components_scale_to_unit_range() {
+        let close = |bytes: &[u8], component_type, normalized, expected: f32| {
+            let value = component(bytes, component_type, normalized);
+            assert!(
+                (value - expected).abs() < 1e-6,
+                "{component_type} {bytes:?}: {value} isn't {expected}"
+            );
+        };
+        // Signed bytes, -128 clamps to -1 like the spec says
+        close(&[127], 5120, true, 1.0);
+        close(&[0x81], 5120, true, -1.0);
+        close(&[0x80], 5120, true, -1.0);
+        close(&[0x80], 5120, false, -128.0);
+        close(&[255], 5121, true, 1.0);
+        close(&[51], 5121, true, 0.2);
+        close(&[51], 5121, false, 51.0);
+        close(&32767i16.to_le_bytes(), 5122, true, 1.0);
+        close(&i16::MIN.to_le_bytes(), 5122, true, -1.0);
+        close(&(-16384i16).to_le_bytes(), 5122, true, -16384.0 / 32767.0);
+        close(&(-16384i16).to_le_bytes(), 5122, false, -16384.0);
+        close(&u16::MAX.to_le_bytes(), 5123, true, 1.0);
+        close(&13107u16.to_le_bytes(), 5123, true, 0.2);
+        close(&7u32.to_le_bytes(), 5125, false, 7.0);
+        close(&0.25f32.to_le_bytes(), 5126, false, 0.25);
+        // Floats aren't scaled, normalized or not
+        close(&2.5f32.to_le_bytes(), 5126, true, 2.5);
+    }
+
+    #[test]
+    fn base64_decodes_and_rejects_bad_input() {
+        assert_eq!(base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(base64("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(base64("aGk=").unwrap(), b"hi");
+        assert_eq!(base64("").unwrap(), b"");
+        // URL safe alphabet too
+        assert_eq!(base64("-_8=").unwrap(), [0xFB, 0xFF]);
+        for bad in [
+            "aGVs bG8=", // space
+            "aGVs\nbG8=",
+            "aGV=sbG8", // padding in the middle
+            "aGVsbG8==",
+            "aGk===",
+            "a===",
+            "aGVsb", // a lone character left over
+            "aGVsbG8*",
+            "aGVsbG8é",
+        ] {
+            assert!(base64(bad).is_err(), "{bad:?} decoded");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use image::RgbaImage;
+
+// Reference images for tests that draw, tests/golden/<name>.png. Drivers round a little
+// differently, so a pixel only counts as different past CHANNEL_SLACK and a few of those
+// are let through. A missing reference (or every one, with FORAY_BLESS=1) is written from
+// what was drawn, so a new test's first run on a GPU makes its own
+
+// Channels further apart than this make a pixel different
+const CHANNEL_SLACK: u8 = 8;
+// Share of the pixels that may differ, edges land a pixel over from GPU to GPU
+const PIXEL_SLACK: f64 = 0.005;
+
+// Pixels of `a` and `b` further apart than the slack allows. They have to be the same size
+pub fn differing(a: &RgbaImage, b: &RgbaImage) -> usize {
+    assert_eq!(a.dimensions(), b.dimensions(), "Images of different sizes");
+    a.pixels()
+        .zip(b.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0)
+                .any(|(a, b)| a.abs_diff(b) > CHANNEL_SLACK)
+        })
+        .count()
+}
+
+// `a` and `b` the same but for rounding
+pub fn assert_similar(a: &RgbaImage, b: &RgbaImage, what: &str) {
+    let differ = differing(a, b);
+    let total = a.pixels().len();
+    assert!(
+        differ as f64 <= total as f64 * PIXEL_SLACK,
+        "{what}: {differ} of {total} pixels differ"
+    );
+}
+
+pub fn check(name: &str, image: &RgbaImage) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.png"));
+    if std::env::var_os("FORAY_BLESS").is_some() || !path.exists() {
+        std::fs::create_dir_all(path.parent().expect("No golden directory")).unwrap();
+        image.save(&path).expect("Can't write the golden image");
+        eprintln!("Wrote golden image {}", path.display());
+        return;
+    }
+    let golden = image::open(&path)
+        .expect("Can't read the golden image")
+        .to_rgba8();
+    if golden.dimensions() != image.dimensions() || differing(&golden, image) > 0 {
+        // Next to the reference's name in the temp dir, to look at the two side by side
+        let actual = std::env::temp_dir().join(format!("wgpu-foray-{name}.png"));
+        let _ = image.save(&actual);
+        eprintln!("{name} drew {}", actual.display());
+    }
+    assert_eq!(
+        golden.dimensions(),
+        image.dimensions(),
+        "{name} isn't the golden image's size"
+    );
+    assert_similar(
+        &golden,
+        image,
+        &format!("{name} against {}", path.display()),
+    );
+}
@@ -0,0 +1,214 @@
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use crate::capabilities;
+use crate::error::ForayError;
+
+// One windowless device per process for anything that doesn't present, the headless
+// renderer and tools. Adapter selection follows the app's (WGPU_FORAY_BACKEND, default power
+// preference), minus the surface. Made on the first get_or_init, and a failure sticks too, so
+// every later caller gets the same error instead of trying again
+pub struct GpuContext {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    // Device and queue are Sync on their own, this is for map_async + poll(Wait) sequences.
+    // Taken by whoever reads back, so threads sharing the context wait their turn instead
+    // of one thread's poll running another's map callbacks halfway through its readback
+    readbacks: Mutex<()>,
+}
+
+static CONTEXT: OnceLock<Result<GpuContext, String>> = OnceLock::new();
+
+impl GpuContext {
+    pub fn get_or_init() -> Result<&'static GpuContext, ForayError> {
+        CONTEXT
+            .get_or_init(|| pollster::block_on(Self::open()))
+            .as_ref()
+            .map_err(|reason| ForayError::GpuUnavailable(reason.clone()))
+    }
+
+    async fn open() -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: capabilities::backends(),
+            ..Default::default()
+        });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+            .ok_or_else(|| {
+                format!(
+                    "no adapter on {:?}, {} picks another backend",
+                    capabilities::backends(),
+                    capabilities::BACKEND_VAR
+                )
+            })?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_defaults()
+                        .using_resolution(adapter.limits()),
+                    label: Some("Shared Device"),
+                    memory_hints: wgpu::MemoryHints::default(),
+                },
+                None,
+            )
+            .await
+            .map_err(|e| format!("{} refused a device, {e}", adapter.get_info().name))?;
+        log::info!("GPU context on {}", adapter.get_info().name);
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+            readbacks: Mutex::new(()),
+        })
+    }
+
+    // Whoever had it before panicking can't have left the queue in a bad state, so a
+    // poisoned lock is just taken over
+    pub fn lock_readbacks(&self) -> MutexGuard<'_, ()> {
+        self.readbacks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+// For tests that draw into a texture and look at what came out
+#[cfg(test)]
+impl GpuContext {
+    // Renderable, sampleable and copyable, so read_back can get at it
+    pub fn target(
+        &self,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> wgpu::Texture {
+        self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Test Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    // Texels of any uncompressed format as they are in memory, rows packed tightly
+    pub fn read_texels(&self, texture: &wgpu::Texture) -> Vec<u8> {
+        let texel = texture
+            .format()
+            .block_copy_size(None)
+            .expect("Not a single-aspect format");
+        let row_bytes = texture.width() * texel;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Test Texel Readback"),
+            size: u64::from(padded_row_bytes) * u64::from(texture.height()),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Test Texel Readback"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        crate::stats::submit(&self.queue, std::iter::once(encoder.finish()));
+        let _readback = self.lock_readbacks();
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("Map callback dropped")
+            .expect("Readback failed");
+        let texels = slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect();
+        texels
+    }
+
+    // `range` of a COPY_SRC buffer, after whatever has been submitted or written so far
+    pub fn read_buffer(&self, buffer: &wgpu::Buffer, range: std::ops::Range<u64>) -> Vec<u8> {
+        let size = range.end - range.start;
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Test Buffer Readback"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Test Buffer Readback"),
+            });
+        encoder.copy_buffer_to_buffer(buffer, range.start, &readback, 0, size);
+        crate::stats::submit(&self.queue, std::iter::once(encoder.finish()));
+        let _readback = self.lock_readbacks();
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("Map callback dropped")
+            .expect("Readback failed");
+        let bytes = slice.get_mapped_range().to_vec();
+        bytes
+    }
+
+    // Whatever has been submitted so far has to have drawn into `texture`, 8 bit RGBA or BGRA
+    pub fn read_back(&self, texture: &wgpu::Texture) -> image::RgbaImage {
+        let mut pool =
+            crate::buffer_pool::BufferPool::new(&crate::memory::GpuMemoryTracker::new(), 0);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Test Readback"),
+            });
+        let readback = crate::screenshot::Readback::copy(
+            &self.device,
+            &mut pool,
+            &mut encoder,
+            texture,
+            "Test Readback",
+        )
+        .expect("Texture can't be read back");
+        crate::stats::submit(&self.queue, std::iter::once(encoder.finish()));
+        let _readback = self.lock_readbacks();
+        readback
+            .read(&self.device, crate::screenshot::TIMEOUT)
+            .expect("Readback failed")
+    }
+}
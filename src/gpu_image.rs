@@ -0,0 +1,462 @@
+use crate::buffer_pool::BufferPool;
+use crate::shaders;
+use crate::targets::{TargetDesc, TargetHandle, TargetRegistry};
+
+// Storage textures can't be sRGB, so everything these passes write is linear half float.
+// Views in other formats can still be read as the source
+pub const WORK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Must match @workgroup_size in gpu_image.wgsl
+const WORKGROUP_SIZE: u32 = 8;
+
+// Mirrors `struct Params` in gpu_image.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    direction: [i32; 2],
+    radius: i32,
+    threshold: f32,
+}
+
+// Image processing on the GPU as compute passes: downsampling (optionally keeping only
+// the bright parts) and separable Gaussian blur. Works on registry targets, so
+// intermediates follow resizes like any other target
+pub struct GpuImage {
+    layout: wgpu::BindGroupLayout,
+    downsample: wgpu::ComputePipeline,
+    blur: wgpu::ComputePipeline,
+}
+
+impl GpuImage {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GPU Image Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: WORK_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GPU Image Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader =
+            shaders::create_module(device, "GPU Image Shader", include_str!("gpu_image.wgsl"));
+        let pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            downsample: pipeline("Downsample Pipeline", "cs_downsample"),
+            blur: pipeline("Blur Pipeline", "cs_blur"),
+            layout,
+        }
+    }
+
+    // What targets written by these passes have to be created with
+    pub fn target(label: &'static str, scale: f32) -> TargetDesc {
+        TargetDesc {
+            label,
+            format: WORK_FORMAT,
+            scale,
+            storage: true,
+        }
+    }
+
+    // Box filters `source` down to the size of `dest`. With a threshold only what's
+    // brighter than it survives, which is the bright pass of a bloom
+    #[allow(clippy::too_many_arguments)]
+    pub fn downsample(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut BufferPool,
+        registry: &TargetRegistry,
+        source: TargetHandle,
+        dest: TargetHandle,
+        threshold: f32,
+    ) {
+        let params = Params {
+            direction: [0, 0],
+            radius: 0,
+            threshold,
+        };
+        self.dispatch(
+            device,
+            queue,
+            encoder,
+            pool,
+            registry,
+            "Downsample",
+            &self.downsample,
+            source,
+            dest,
+            params,
+        );
+    }
+
+    // Gaussian blur of `image` in place, going through `scratch` (same size) in between.
+    // `radius` is in texels of `image`
+    #[allow(clippy::too_many_arguments)]
+    pub fn blur(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut BufferPool,
+        registry: &TargetRegistry,
+        image: TargetHandle,
+        scratch: TargetHandle,
+        radius: u32,
+    ) {
+        let pass = |direction| Params {
+            direction,
+            radius: radius as i32,
+            threshold: 0.0,
+        };
+        self.dispatch(
+            device,
+            queue,
+            encoder,
+            pool,
+            registry,
+            "Blur Horizontal",
+            &self.blur,
+            image,
+            scratch,
+            pass([1, 0]),
+        );
+        self.dispatch(
+            device,
+            queue,
+            encoder,
+            pool,
+            registry,
+            "Blur Vertical",
+            &self.blur,
+            scratch,
+            image,
+            pass([0, 1]),
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut BufferPool,
+        registry: &TargetRegistry,
+        label: &str,
+        pipeline: &wgpu::ComputePipeline,
+        source: TargetHandle,
+        dest: TargetHandle,
+        params: Params,
+    ) {
+        let params_buffer = pool.acquire(
+            device,
+            "GPU Image Params",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<Params>() as u64,
+        );
+        queue.write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(registry.view(source)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(registry.view(dest)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &params_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<Params>() as u64),
+                    }),
+                },
+            ],
+        });
+
+        let (width, height) = registry.size(dest);
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(label),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+    use crate::memory::GpuMemoryTracker;
+    use crate::stats;
+
+    const SIZE: (u32, u32) = (8, 8);
+
+    // What the passes read, an impulse in red and ramps in green and blue
+    fn source_texel(x: u32, y: u32) -> [u8; 4] {
+        let red = if (x, y) == (3, 4) { 255 } else { 0 };
+        [red, (x * 36) as u8, (y * 30) as u8, 255]
+    }
+
+    fn half_to_f32(bits: u16) -> f32 {
+        let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+        let exponent = i32::from((bits >> 10) & 0x1f);
+        let mantissa = f32::from(bits & 0x3ff);
+        sign * match exponent {
+            0 => mantissa * 2f32.powi(-24),
+            31 => f32::INFINITY,
+            _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+        }
+    }
+
+    // WORK_FORMAT texels as f32, row by row
+    fn read_work(
+        gpu: &GpuContext,
+        registry: &TargetRegistry,
+        target: TargetHandle,
+    ) -> Vec<[f32; 4]> {
+        let bytes = gpu.read_texels(registry.texture(target));
+        bytes
+            .chunks_exact(8)
+            .map(|texel| {
+                let channel = |i: usize| half_to_f32(u16::from_le_bytes([texel[i], texel[i + 1]]));
+                [channel(0), channel(2), channel(4), channel(6)]
+            })
+            .collect()
+    }
+
+    // cs_downsample on the CPU
+    fn downsample(
+        source: &[[f32; 4]],
+        (sw, sh): (u32, u32),
+        (dw, dh): (u32, u32),
+        threshold: f32,
+    ) -> Vec<[f32; 4]> {
+        let mut out = Vec::new();
+        for y in 0..dh {
+            for x in 0..dw {
+                let (x0, y0) = (x * sw / dw, y * sh / dh);
+                let (x1, y1) = (
+                    ((x + 1) * sw / dw).max(x0 + 1),
+                    ((y + 1) * sh / dh).max(y0 + 1),
+                );
+                let mut sum = [0.0; 3];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        for (c, total) in sum.iter_mut().enumerate() {
+                            *total += source[(sy * sw + sx) as usize][c];
+                        }
+                    }
+                }
+                let color = sum.map(|c| c / ((x1 - x0) * (y1 - y0)) as f32);
+                let brightness = color[0].max(color[1]).max(color[2]);
+                let keep = (brightness - threshold).max(0.0) / brightness.max(0.0001);
+                out.push([color[0] * keep, color[1] * keep, color[2] * keep, 1.0]);
+            }
+        }
+        out
+    }
+
+    // One cs_blur pass on the CPU
+    fn blur_pass(
+        image: &[[f32; 4]],
+        (w, h): (u32, u32),
+        radius: i32,
+        (dx, dy): (i32, i32),
+    ) -> Vec<[f32; 4]> {
+        let sigma = (radius as f32 / 2.0).max(0.5);
+        let mut out = Vec::new();
+        for y in 0..h as i32 {
+            for x in 0..w as i32 {
+                let (mut sum, mut total) = ([0.0; 4], 0.0);
+                for i in -radius..=radius {
+                    let sx = (x + dx * i).clamp(0, w as i32 - 1);
+                    let sy = (y + dy * i).clamp(0, h as i32 - 1);
+                    let weight = (-((i * i) as f32) / (2.0 * sigma * sigma)).exp();
+                    for (c, s) in sum.iter_mut().enumerate() {
+                        *s += image[(sy * w as i32 + sx) as usize][c] * weight;
+                    }
+                    total += weight;
+                }
+                out.push(sum.map(|s| s / total));
+            }
+        }
+        out
+    }
+
+    fn assert_close(gpu: &[[f32; 4]], cpu: &[[f32; 4]]) {
+        assert_eq!(gpu.len(), cpu.len());
+        for (i, (g, c)) in gpu.iter().zip(cpu).enumerate() {
+            for channel in 0..4 {
+                // Half floats keep about three decimal digits
+                let tolerance = 2e-3 * c[channel].abs().max(1.0);
+                assert!(
+                    (g[channel] - c[channel]).abs() <= tolerance,
+                    "texel {i}: GPU {g:?}, CPU {c:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn blur_and_downsample_match_the_cpu() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let memory = GpuMemoryTracker::new();
+        let mut registry = TargetRegistry::new(SIZE, &memory);
+        let source = registry.create(
+            device,
+            TargetDesc {
+                label: "Source",
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                scale: 1.0,
+                storage: false,
+            },
+        );
+        let image = registry.create(device, GpuImage::target("Image", 1.0));
+        let scratch = registry.create(device, GpuImage::target("Scratch", 1.0));
+        let half = registry.create(device, GpuImage::target("Half", 0.5));
+        let texels: Vec<u8> = (0..SIZE.1)
+            .flat_map(|y| (0..SIZE.0).flat_map(move |x| source_texel(x, y)))
+            .collect();
+        queue.write_texture(
+            registry.texture(source).as_image_copy(),
+            &texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(SIZE.0 * 4),
+                rows_per_image: None,
+            },
+            registry.texture(source).size(),
+        );
+        let seeded: Vec<[f32; 4]> = texels
+            .chunks_exact(4)
+            .map(|texel| [0, 1, 2, 3].map(|c| f32::from(texel[c]) / 255.0))
+            .collect();
+
+        let images = GpuImage::new(device);
+        let mut pool = BufferPool::new(&memory, 0);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        // Same size, a straight copy into the work format
+        images.downsample(
+            device,
+            queue,
+            &mut encoder,
+            &mut pool,
+            &registry,
+            source,
+            image,
+            0.0,
+        );
+        images.downsample(
+            device,
+            queue,
+            &mut encoder,
+            &mut pool,
+            &registry,
+            source,
+            half,
+            0.3,
+        );
+        stats::submit(queue, std::iter::once(encoder.finish()));
+        let copied = read_work(gpu, &registry, image);
+        assert_close(&copied, &downsample(&seeded, SIZE, SIZE, 0.0));
+        assert_close(
+            &read_work(gpu, &registry, half),
+            &downsample(&seeded, SIZE, (4, 4), 0.3),
+        );
+
+        for radius in [1, 3] {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            images.downsample(
+                device,
+                queue,
+                &mut encoder,
+                &mut pool,
+                &registry,
+                source,
+                image,
+                0.0,
+            );
+            images.blur(
+                device,
+                queue,
+                &mut encoder,
+                &mut pool,
+                &registry,
+                image,
+                scratch,
+                radius,
+            );
+            stats::submit(queue, std::iter::once(encoder.finish()));
+            let horizontal = blur_pass(&copied, SIZE, radius as i32, (1, 0));
+            let expected = blur_pass(&horizontal, SIZE, radius as i32, (0, 1));
+            let blurred = read_work(gpu, &registry, image);
+            assert_close(&blurred, &expected);
+            // The impulse spreads out but none of it goes missing away from the edges
+            let red: f32 = blurred.iter().map(|texel| texel[0]).sum();
+            assert!(
+                (red - 1.0).abs() < 1e-2,
+                "radius {radius}: red adds up to {red}"
+            );
+        }
+    }
+}
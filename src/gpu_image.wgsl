@@ -0,0 +1,61 @@
+// Compute passes over linear Rgba16Float images, see gpu_image.rs
+struct Params {
+    // (1, 0) for the horizontal blur pass, (0, 1) for the vertical one
+    direction: vec2<i32>,
+    radius: i32,
+    // Downsample only, brightness below this is dropped. 0 keeps everything
+    threshold: f32,
+};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var dest: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+// Box filter over the source texels under each destination texel
+@compute @workgroup_size(8, 8)
+fn cs_downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(dest));
+    let pixel = vec2<i32>(id.xy);
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    let source_size = vec2<i32>(textureDimensions(source));
+    let start = pixel * source_size / size;
+    let end = max((pixel + 1) * source_size / size, start + 1);
+    var sum = vec4<f32>(0.0);
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            sum += textureLoad(source, vec2<i32>(x, y), 0);
+        }
+    }
+    let area = end - start;
+    let color = sum.rgb / f32(area.x * area.y);
+
+    // Scaled rather than cut off, so highlights don't get a hard edge
+    let brightness = max(color.r, max(color.g, color.b));
+    let keep = max(brightness - params.threshold, 0.0) / max(brightness, 0.0001);
+    textureStore(dest, pixel, vec4<f32>(color * keep, 1.0));
+}
+
+// One direction of a separable Gaussian, source and dest have the same size
+@compute @workgroup_size(8, 8)
+fn cs_blur(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(dest));
+    let pixel = vec2<i32>(id.xy);
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    // Weights are all but gone at the radius
+    let sigma = max(f32(params.radius) / 2.0, 0.5);
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -params.radius; i <= params.radius; i++) {
+        let texel = clamp(pixel + params.direction * i, vec2<i32>(0), size - 1);
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        sum += textureLoad(source, texel, 0) * weight;
+        total += weight;
+    }
+    textureStore(dest, pixel, sum / total);
+}
@@ -0,0 +1,48 @@
+#include "post.wgsl"
+
+struct Grade {
+    // In stops
+    exposure: f32,
+    contrast: f32,
+    saturation: f32,
+    // 0 skips the LUT, 1 is fully graded by it
+    lut_intensity: f32,
+};
+
+@group(1) @binding(0) var<uniform> grade: Grade;
+@group(2) @binding(0) var lut: texture_3d<f32>;
+@group(2) @binding(1) var lut_sampler: sampler;
+
+// Contrast pivots around middle grey
+const MIDDLE_GREY: f32 = 0.18;
+
+fn to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+// LUTs map sRGB encoded colors in [0, 1]. The coordinates land on texel centers so the
+// end entries are hit exactly
+fn apply_lut(color: vec3<f32>) -> vec3<f32> {
+    let size = f32(textureDimensions(lut).x);
+    let encoded = to_srgb(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let coords = encoded * ((size - 1.0) / size) + 0.5 / size;
+    return to_linear(textureSampleLevel(lut, lut_sampler, coords, 0.0).rgb);
+}
+
+@fragment
+fn fs_grade(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(input, input_sampler, in.uv).rgb * exp2(grade.exposure);
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    color = mix(vec3<f32>(luminance), color, grade.saturation);
+    color = max((color - MIDDLE_GREY) * grade.contrast + MIDDLE_GREY, vec3<f32>(0.0));
+    color = mix(color, apply_lut(color), grade.lut_intensity);
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,37 @@
+#include "fullscreen.wgsl"
+
+// Mirrors `struct SceneUniform` in exposure.rs
+struct Scene {
+    // Camera2d: world space is y-up, one unit a pixel at zoom 1
+    center: vec2<f32>,
+    zoom: f32,
+    _padding: f32,
+    resolution: vec2<f32>,
+    _padding2: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> scene: Scene;
+
+// Linear radiance way past 1: a dim cave to the left of x = 0, daylight to the right with
+// the sun in it. Bricks everywhere so there's detail to see at any exposure
+@fragment
+fn fs_hdr_scene(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<f32>(in.uv.x - 0.5, 0.5 - in.uv.y) * scene.resolution;
+    let world = scene.center + pixel / scene.zoom;
+
+    let row = floor(world.y / 40.0);
+    let brick = fract(vec2<f32>(world.x / 80.0 + row * 0.5, world.y / 40.0));
+    let mortar = step(0.06, brick.x) * step(0.1, brick.y);
+    let pattern = 0.35 + 0.65 * mortar;
+
+    let torch = vec3<f32>(1.0, 0.45, 0.15) * 0.4 * exp(-length(world - vec2<f32>(-700.0, 0.0)) / 150.0);
+    let cave = vec3<f32>(0.02, 0.018, 0.025) * pattern + torch;
+
+    let height = clamp(world.y / 600.0 + 0.5, 0.0, 1.0);
+    let sky = mix(vec3<f32>(3.0, 3.5, 4.0), vec3<f32>(1.2, 2.0, 4.5), height) * pattern;
+    let sun_distance = length(world - vec2<f32>(900.0, 150.0)) / 70.0;
+    let sun = vec3<f32>(60.0, 55.0, 45.0) * exp(-sun_distance * sun_distance);
+
+    let daylight = smoothstep(-150.0, 150.0, world.x);
+    return vec4<f32>(mix(cave, sky + sun, daylight), 1.0);
+}
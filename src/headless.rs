@@ -0,0 +1,917 @@
+#[cfg(feature = "remote")]
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use glam::Vec2;
+
+use crate::buffer_pool::BufferPool;
+use crate::colors::{Colors, RgbaColor, Theme};
+use crate::deferred::DeferredDemo;
+#[cfg(feature = "gltf")]
+use crate::depth::DepthConvention;
+use crate::error::ForayError;
+use crate::frame::{Background, ColorTarget, Frame};
+use crate::frame_dump::FrameDump;
+use crate::gpu_context::GpuContext;
+use crate::image_file;
+use crate::inspector::{self, Inspector};
+use crate::maintain;
+use crate::memory::{GpuMemoryTracker, MemoryCategory};
+use crate::options;
+use crate::overlay::{Anchor, DebugOverlay};
+use crate::pacing::FramePacer;
+use crate::pipeline_bank::RenderPipelineBank;
+#[cfg(feature = "remote")]
+use crate::remote::{self, Answer, Remote, RemoteCommand};
+use crate::scene::{self, Scene, StressParams};
+use crate::shapes::ShapeRenderer;
+use crate::surface_views::SurfaceViews;
+use crate::targets::TargetRegistry;
+use crate::timeline::Timeline;
+
+// sRGB like the swapchain usually is, so the PNGs look like the window would. --surface
+// linear stands in for a surface that came with the plain format first
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+// A frame that takes longer than this to come back means the device is gone
+const READBACK_TIMEOUT: Duration = Duration::from_secs(10);
+// How long a --remote job with nothing to render sleeps on the listener at a time
+#[cfg(feature = "remote")]
+const IDLE_WAIT: Duration = Duration::from_millis(100);
+
+// `foray render [--scene <name|path>] [--frames <n>] [--fps <n>] [--out <dir>] [--size <w>x<h>]
+// [--timeline <path>] [--items <n>] [--seed <n>] [--spin <radians/s>] [--surface <srgb|linear>]
+// [--single-view] [--overlay] [--inspector] [--theme <dark|light>] [--dump] [--gltf <path>]
+// [--remote [port]] [--remote-bind <address>]`
+pub struct RenderJob {
+    // A built-in scene (starter, instancing_ring, bouncing_pentagons, stress) or a scene file
+    pub scene: String,
+    pub stress: StressParams,
+    pub frames: u32,
+    // The clock moves exactly 1/fps per frame, however long a frame takes to render
+    pub fps: u32,
+    // Gets frame_00000.png, frame_00001.png, ... and is created when missing
+    pub out: PathBuf,
+    pub size: (u32, u32),
+    // Played from the start on the same clock. There's no post chain headless, so only
+    // the camera, clear color and visibility keys do anything
+    pub timeline: Option<PathBuf>,
+    // The format of the texture standing in for the swapchain, viewed as SurfaceViews would
+    // view a window's. The PNGs come out the same either way, unless --single-view
+    pub surface: wgpu::TextureFormat,
+    // Pairs the views like a surface that allows it, --single-view draws through just one
+    pub paired: bool,
+    // A translucent overlay panel over each frame, what the window composites as UI
+    pub overlay: bool,
+    // The inspector's list over each frame, its first entry selected, in `theme`
+    pub inspector: bool,
+    pub theme: Theme,
+    // frame_00000.json and so on next to the PNGs, what dump_frame writes in the window
+    pub dump: bool,
+    // Draws the model through the deferred view in place of the scene, for checking an
+    // import against a known good frame
+    #[cfg(feature = "gltf")]
+    pub gltf: Option<PathBuf>,
+    // Listens like the window's --remote. After its frames the job stays up taking commands
+    // until `quit`, rendering another frame for each `screenshot`, see Session::dispatch
+    #[cfg(feature = "remote")]
+    pub remote: Option<u16>,
+    #[cfg(feature = "remote")]
+    pub remote_bind: IpAddr,
+}
+
+impl RenderJob {
+    // Whatever follows `render` on the command line
+    pub fn from_args(args: impl Iterator<Item = String>) -> Self {
+        let mut args = args.peekable();
+        let mut job = Self {
+            scene: "starter".to_owned(),
+            stress: StressParams::default(),
+            frames: 60,
+            fps: 60,
+            out: PathBuf::from("frames"),
+            size: (800, 600),
+            timeline: None,
+            surface: FORMAT,
+            paired: true,
+            overlay: false,
+            inspector: false,
+            theme: Theme::DARK,
+            dump: false,
+            #[cfg(feature = "gltf")]
+            gltf: None,
+            #[cfg(feature = "remote")]
+            remote: None,
+            #[cfg(feature = "remote")]
+            remote_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scene" => match args.next() {
+                    Some(scene) => job.scene = scene,
+                    None => log::warn!("--scene wants a scene name or file, keeping starter"),
+                },
+                "--frames" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(frames) => job.frames = frames,
+                    None => log::warn!("--frames wants a number, keeping 60"),
+                },
+                "--fps" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(fps) if fps > 0 => job.fps = fps,
+                    _ => log::warn!("--fps wants a number above 0, keeping 60"),
+                },
+                "--out" => match args.next() {
+                    Some(out) => job.out = PathBuf::from(out),
+                    None => log::warn!("--out wants a directory, keeping frames/"),
+                },
+                "--size" => {
+                    let size = args.next().and_then(|size| {
+                        let (width, height) = size.split_once('x')?;
+                        Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+                    });
+                    match size {
+                        Some((width, height)) if width > 0 && height > 0 => {
+                            job.size = (width, height)
+                        }
+                        _ => log::warn!("--size wants <width>x<height>, keeping 800x600"),
+                    }
+                }
+                "--timeline" => match args.next() {
+                    Some(path) => job.timeline = Some(PathBuf::from(path)),
+                    None => log::warn!("--timeline wants a file, rendering without one"),
+                },
+                "--surface" => match args.next().as_deref() {
+                    Some("srgb") => job.surface = FORMAT,
+                    Some("linear") => job.surface = FORMAT.remove_srgb_suffix(),
+                    _ => log::warn!("--surface wants srgb or linear, keeping srgb"),
+                },
+                "--single-view" => job.paired = false,
+                "--overlay" => job.overlay = true,
+                "--inspector" => job.inspector = true,
+                "--theme" => match args.next().as_deref().and_then(Theme::from_name) {
+                    Some(theme) => job.theme = theme,
+                    None => log::warn!("--theme wants dark or light, keeping dark"),
+                },
+                "--dump" => job.dump = true,
+                #[cfg(feature = "gltf")]
+                "--gltf" => match args.next() {
+                    Some(path) => job.gltf = Some(PathBuf::from(path)),
+                    None => log::warn!("--gltf wants a .gltf or .glb file, rendering the scene"),
+                },
+                other if options::stress_arg(&mut job.stress, other, &mut args) => {}
+                #[cfg(feature = "remote")]
+                other
+                    if options::remote_arg(
+                        &mut job.remote,
+                        &mut job.remote_bind,
+                        other,
+                        &mut args,
+                    ) => {}
+                #[cfg(not(feature = "remote"))]
+                other if options::remote_arg(other, &mut args) => {}
+                other => log::warn!("Ignoring unknown render argument {other}"),
+            }
+        }
+        job
+    }
+}
+
+// A named scene, or a scene file when no built-in has that name, with its items' outlines.
+// Those load on the asset threads in the window, here there's no reason not to wait
+fn load_scene(name: &str, stress: &StressParams) -> Result<(Scene, Vec<Vec<Vec2>>), ForayError> {
+    let scene = match Scene::named(name, stress) {
+        Some(scene) => scene,
+        None => Scene::load(Path::new(name))?,
+    };
+    let outlines = scene
+        .items
+        .iter()
+        .map(|item| {
+            scene::load_outline(&item.mesh).unwrap_or_else(|e| {
+                log::warn!("{e}, {} is drawn as a cross", item.name);
+                Vec::new()
+            })
+        })
+        .collect();
+    Ok((scene, outlines))
+}
+
+// What a job draws and, with --remote, what commands change between its frames
+struct Session {
+    scene_name: String,
+    scene: Scene,
+    // Per item
+    outlines: Vec<Vec<Vec2>>,
+    // `clear <#rrggbb|off>`, over the timeline's clear color and the white without one
+    clear: Option<RgbaColor>,
+    frames: u32,
+    failed: u32,
+    // For `scene`
+    #[cfg(feature = "remote")]
+    stress: StressParams,
+    #[cfg(feature = "remote")]
+    quit: bool,
+    #[cfg(feature = "remote")]
+    remote: Option<Remote>,
+    // Answered once the frame they wait on is saved
+    #[cfg(feature = "remote")]
+    screenshots: Vec<RemoteCommand>,
+}
+
+impl Session {
+    fn new(job: &RenderJob) -> Result<Self, ForayError> {
+        #[cfg(feature = "remote")]
+        let remote = job
+            .remote
+            .map(|port| {
+                let token = std::env::var(remote::TOKEN_VARIABLE).ok();
+                Remote::start(job.remote_bind, port, token, || {})
+            })
+            .transpose()?;
+        #[cfg(feature = "remote")]
+        if let Some(remote) = &remote {
+            println!("Remote control listening on {}", remote.address());
+        }
+        let (scene, outlines) = load_scene(&job.scene, &job.stress)?;
+        Ok(Self {
+            scene_name: job.scene.clone(),
+            scene,
+            outlines,
+            clear: None,
+            frames: 0,
+            failed: 0,
+            #[cfg(feature = "remote")]
+            stress: job.stress,
+            #[cfg(feature = "remote")]
+            quit: false,
+            #[cfg(feature = "remote")]
+            remote,
+            #[cfg(feature = "remote")]
+            screenshots: Vec::new(),
+        })
+    }
+
+    // Whether to render frame `index`. The job's own frames come first. With --remote it
+    // then waits on commands until `quit`, rendering only for screenshots
+    fn another_frame(&mut self, index: u32, frames: u32) -> bool {
+        #[cfg(feature = "remote")]
+        if let Some(remote) = self.remote.take() {
+            let mut commands = remote.take();
+            loop {
+                for command in commands {
+                    self.run_remote(command);
+                }
+                if self.quit || index < frames || !self.screenshots.is_empty() {
+                    break;
+                }
+                commands = remote.wait(IDLE_WAIT);
+            }
+            self.remote = Some(remote);
+            return !self.quit;
+        }
+        index < frames
+    }
+
+    // Counts the frame that just went to `written`, and answers the screenshots waiting on it
+    fn frame_done(&mut self, written: &Result<PathBuf, ForayError>) {
+        self.frames += 1;
+        if let Err(e) = written {
+            log::error!("{e}");
+            self.failed += 1;
+        }
+        #[cfg(feature = "remote")]
+        for command in self.screenshots.drain(..) {
+            let inline = command.inline;
+            command.answer(
+                written
+                    .as_ref()
+                    .map_err(ToString::to_string)
+                    .and_then(|path| Answer::screenshot(path.clone(), inline)),
+            );
+        }
+    }
+
+    #[cfg(feature = "remote")]
+    fn run_remote(&mut self, command: RemoteCommand) {
+        if command.command.split_whitespace().next() == Some("screenshot") {
+            self.screenshots.push(command);
+            return;
+        }
+        let answer = self.dispatch(&command.command);
+        command.answer(answer);
+    }
+
+    // The console commands that mean something without a window: scene, clear, stats and
+    // quit. `screenshot` is the next frame, see run_remote
+    #[cfg(feature = "remote")]
+    fn dispatch(&mut self, command: &str) -> Result<Answer, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            ["scene", name] => {
+                (self.scene, self.outlines) =
+                    load_scene(name, &self.stress).map_err(|e| e.to_string())?;
+                (*name).clone_into(&mut self.scene_name);
+            }
+            ["clear", "off"] => self.clear = None,
+            ["clear", hex] => {
+                self.clear = Some(RgbaColor::parse_hex(hex).map_err(|e| e.to_string())?);
+            }
+            ["stats"] => {
+                return Ok(Answer {
+                    output: vec![
+                        format!("scene {}", self.scene_name),
+                        format!("items {}", self.scene.items.len()),
+                        format!("frames {}", self.frames),
+                        format!("failed {}", self.failed),
+                    ],
+                    ..Answer::default()
+                })
+            }
+            ["quit"] => self.quit = true,
+            _ => return Err(format!("\"{command}\" isn't a command headless")),
+        }
+        Ok(Answer::default())
+    }
+}
+
+// Renders the job with no window or surface at all: every frame goes into an offscreen
+// texture, is read back with a blocking poll and written out as a PNG. Returns how many
+// frames failed, errors are only for what stops the whole job before the first frame
+pub async fn render(job: &RenderJob) -> Result<u32, ForayError> {
+    let mut session = Session::new(job)?;
+    let mut timeline = job.timeline.as_deref().map(Timeline::load).transpose()?;
+    if let Some(timeline) = &mut timeline {
+        timeline.playing = true;
+    }
+    std::fs::create_dir_all(&job.out).map_err(|e| ForayError::FrameDump {
+        frame: 0,
+        reason: format!("can't create {}: {e}", job.out.display()),
+    })?;
+
+    let gpu = GpuContext::get_or_init()?;
+    let (device, queue) = (&gpu.device, &gpu.queue);
+    let max = device.limits().max_texture_dimension_2d;
+    let (width, height) = (job.size.0.min(max), job.size.1.min(max));
+    if (width, height) != job.size {
+        log::warn!("Frames are limited to {max}px a side, rendering {width}x{height}");
+    }
+    log::info!("Rendering headless on {}", gpu.adapter.get_info().name);
+
+    let memory = GpuMemoryTracker::new();
+    let targets = TargetRegistry::new((width, height), &memory);
+    let mut pool = BufferPool::new(&memory, 16 * 1024 * 1024);
+    let mut bank = RenderPipelineBank::new();
+    let views = SurfaceViews::new(job.surface, job.paired);
+    log::info!("Surface views: {}", views.describe());
+    let shapes = ShapeRenderer::new(device, views.scene, &mut bank);
+    #[cfg(feature = "gltf")]
+    let (targets, mut deferred) =
+        import_gltf(job, device, queue, views.scene, targets, &mut bank, &memory)?;
+    #[cfg(not(feature = "gltf"))]
+    let mut deferred: Option<DeferredDemo> = None;
+    let mut overlay = (job.overlay || job.inspector).then(|| {
+        let mut overlay = DebugOverlay::new(device, queue, views, &mut bank, &memory);
+        overlay.theme = job.theme;
+        overlay
+    });
+    let mut inspector = Inspector::new();
+
+    let texture = memory.create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some("Headless Frame"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: views.surface,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &views.view_formats(),
+        },
+        MemoryCategory::Targets,
+    );
+    // Rows of a texture to buffer copy have to start 256 byte aligned
+    let row_bytes = width * 4;
+    let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback_size = u64::from(padded_row_bytes) * u64::from(height);
+
+    let mut pacer = FramePacer::forced(job.fps);
+    let mut index = 0;
+    while session.another_frame(index, job.frames) {
+        let scene = &mut session.scene;
+        let mut clear = Colors::WHITE;
+        if let Some(timeline) = &timeline {
+            if let Some(camera) = timeline.camera() {
+                scene.camera = camera;
+            }
+            clear = timeline.clear_color().unwrap_or(clear);
+            timeline.apply_visibility(scene);
+        }
+        let clear = session.clear.unwrap_or(clear);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let view = |format| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(format),
+                ..Default::default()
+            })
+        };
+        let mut frame = Frame::offscreen(
+            view(views.scene),
+            device,
+            views.scene,
+            Background::Clear(clear.to_wgpu_linear()),
+        )
+        .with_ui_view(view(views.ui), views.ui);
+        if job.dump {
+            frame.dump = Some(FrameDump::new(
+                u64::from(index),
+                format!("render {}", session.scene_name),
+                views.describe(),
+            ));
+        }
+        let load = frame.background.color();
+        let drawn = match &mut deferred {
+            Some(deferred) => deferred.draw(
+                device,
+                queue,
+                &mut frame,
+                &targets,
+                &bank,
+                &mut pool,
+                ColorTarget::Swapchain,
+                (width, height),
+                1.0,
+            ),
+            None => shapes.draw_into(
+                device,
+                queue,
+                &mut frame,
+                &targets,
+                &bank,
+                &mut pool,
+                &session.scene.shapes(&session.outlines, None),
+                &session.scene.camera,
+                (width, height),
+                (ColorTarget::Swapchain, load),
+            ),
+        }
+        .and_then(|()| {
+            let Some(overlay) = &mut overlay else {
+                return Ok(());
+            };
+            overlay.set_screen((width, height), 1.0);
+            if job.overlay {
+                overlay.panel(
+                    Anchor::TopLeft,
+                    (8.0, 8.0),
+                    &format!("frame {index}\n{}", session.scene_name),
+                );
+            }
+            if job.inspector {
+                let rows = inspector::rows(&bank, &[], &memory, &targets, &session.scene);
+                if inspector.selected().is_none() {
+                    inspector.move_selection(&rows, 0);
+                }
+                inspector.queue(overlay, &rows);
+            }
+            overlay.draw(
+                device,
+                queue,
+                &mut frame,
+                &targets,
+                &bank,
+                &mut pool,
+                (width, height),
+            )
+        });
+        let readback = pool.acquire(
+            device,
+            "Headless Readback Buffer",
+            wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            readback_size,
+        );
+        frame.encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        let dump = frame.dump.take();
+        frame.finish(queue);
+        pool.end_frame(queue);
+        let validation = device.pop_error_scope().await;
+
+        let path = job.out.join(format!("frame_{index:05}.png"));
+        let written = drawn
+            .and_then(|()| match validation {
+                Some(e) => Err(ForayError::FrameDump {
+                    frame: index,
+                    reason: e.to_string(),
+                }),
+                None => Ok(()),
+            })
+            .and_then(|()| {
+                let pixels = read_back(gpu, (&readback, readback_size), index, (width, height))?;
+                save(
+                    &path,
+                    index,
+                    pixels,
+                    (row_bytes, padded_row_bytes),
+                    (width, height),
+                )
+            })
+            .and_then(|()| {
+                let Some(mut dump) = dump else {
+                    return Ok(());
+                };
+                dump.screenshot = Some(path.clone());
+                dump.save(&path.with_extension("json"))
+                    .map_err(|reason| ForayError::FrameDump {
+                        frame: index,
+                        reason,
+                    })
+            });
+        session.frame_done(&written.map(|()| path));
+
+        let scene = &mut session.scene;
+        for _ in 0..pacer.advance() {
+            if let Some(timeline) = &mut timeline {
+                timeline.step(pacer.fixed_step);
+            }
+            scene.physics.bounds = Some(scene.camera.visible((width, height)));
+            scene.update(pacer.fixed_step);
+        }
+        index += 1;
+    }
+    println!(
+        "Rendered {} of {} frames into {}",
+        session.frames - session.failed,
+        session.frames,
+        job.out.display()
+    );
+    Ok(session.failed)
+}
+
+// The deferred view with --gltf's model in it, None without one. The registry comes back
+// with the G-buffer targets the view added to it
+#[cfg(feature = "gltf")]
+fn import_gltf(
+    job: &RenderJob,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    mut targets: TargetRegistry,
+    bank: &mut RenderPipelineBank,
+    memory: &GpuMemoryTracker,
+) -> Result<(TargetRegistry, Option<DeferredDemo>), ForayError> {
+    let Some(path) = &job.gltf else {
+        return Ok((targets, None));
+    };
+    let mut deferred = DeferredDemo::new(
+        device,
+        queue,
+        format,
+        &mut targets,
+        bank,
+        memory,
+        DepthConvention::Standard,
+    );
+    let items = deferred.import_gltf(device, queue, memory, path)?;
+    log::info!("Rendering {} with {items} items", path.display());
+    Ok((targets, Some(deferred)))
+}
+
+// Maps the readback buffer and waits for it, the copy was the last thing submitted
+// Only the first `size` bytes, the pool rounds buffers up. Unmapped again whether or not it
+// worked, so the pool can hand it out for a later frame
+fn read_back(
+    gpu: &GpuContext,
+    (readback, size): (&wgpu::Buffer, u64),
+    frame: u32,
+    (width, height): (u32, u32),
+) -> Result<Vec<u8>, ForayError> {
+    let _readback = gpu.lock_readbacks();
+    let slice = readback.slice(..size);
+    let (sender, receiver) = mpsc::channel();
+    let done = maintain::untracked();
+    let finished = done.clone();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+        finished.finish();
+    });
+    let mapped = if maintain::wait_bounded(&gpu.device, &done, READBACK_TIMEOUT) {
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()))
+    } else {
+        Err(format!("no answer from the GPU in {READBACK_TIMEOUT:?}"))
+    };
+    if let Err(reason) = mapped {
+        readback.unmap();
+        return Err(ForayError::FrameDump {
+            frame,
+            reason: format!("reading back {width}x{height} failed, {reason}"),
+        });
+    }
+    let pixels = slice.get_mapped_range().to_vec();
+    readback.unmap();
+    Ok(pixels)
+}
+
+// Drops the row padding and writes the PNG
+fn save(
+    path: &Path,
+    frame: u32,
+    pixels: Vec<u8>,
+    (row_bytes, padded_row_bytes): (u32, u32),
+    (width, height): (u32, u32),
+) -> Result<(), ForayError> {
+    let rows: Vec<u8> = pixels
+        .chunks_exact(padded_row_bytes as usize)
+        .flat_map(|row| &row[..row_bytes as usize])
+        .copied()
+        .collect();
+    let image =
+        image::RgbaImage::from_raw(width, height, rows).ok_or_else(|| ForayError::FrameDump {
+            frame,
+            reason: "readback is smaller than the frame".to_owned(),
+        })?;
+    image_file::save(&image, path).map_err(|reason| ForayError::FrameDump { frame, reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(args: &[&str]) -> RenderJob {
+        RenderJob::from_args(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn dumps_are_asked_for_with_a_flag() {
+        assert!(!job(&["--scene", "stress"]).dump);
+        assert!(job(&["--scene", "stress", "--dump"]).dump);
+    }
+
+    #[test]
+    fn dumps_of_the_same_stress_job_are_identical() {
+        let Ok(_gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let out = std::env::temp_dir().join(format!("wgpu-foray-{}-dumps", std::process::id()));
+        let out_arg = out.to_str().expect("Temp dir isn't UTF-8");
+        let job = job(&[
+            "--scene", "stress", "--items", "300", "--seed", "7", "--frames", "4", "--size",
+            "96x64", "--dump", "--out", out_arg,
+        ]);
+        // Each run overwrites the last one's files, so they're read in between
+        let run = || {
+            let failed = pollster::block_on(render(&job)).expect("Render failed");
+            assert_eq!(failed, 0);
+            (0..job.frames)
+                .map(|index| {
+                    let path = out.join(format!("frame_{index:05}.json"));
+                    std::fs::read_to_string(&path).expect("No dump")
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = run();
+        let second = run();
+        let _ = std::fs::remove_dir_all(&out);
+
+        for (index, (first, second)) in first.iter().zip(&second).enumerate() {
+            assert!(first == second, "Frame {index}'s dumps differ");
+        }
+        // There's something in them to compare, and no wall clock
+        assert!(first[0].contains("\"set_pipeline\""));
+        assert!(!first[0].contains("\"globals\""));
+        // The scene moves between frames, the dumps follow it
+        assert_ne!(first[1], first[3]);
+    }
+
+    #[cfg(feature = "gltf")]
+    #[test]
+    fn the_shipped_model_renders_like_its_golden_image() {
+        let Ok(_gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let model = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/models/checker_cube.gltf");
+        let out = std::env::temp_dir().join(format!("wgpu-foray-{}-gltf", std::process::id()));
+        let job = job(&[
+            "--gltf",
+            model.to_str().expect("Path isn't UTF-8"),
+            "--frames",
+            "1",
+            "--size",
+            "160x120",
+            "--out",
+            out.to_str().expect("Temp dir isn't UTF-8"),
+        ]);
+        assert_eq!(pollster::block_on(render(&job)).expect("Render failed"), 0);
+        let frame = image::open(out.join("frame_00000.png")).unwrap().to_rgba8();
+        let _ = std::fs::remove_dir_all(&out);
+        // Something was drawn over the background before it's worth comparing
+        let corner = *frame.get_pixel(0, 0);
+        assert!(frame.get_pixel(80, 60) != &corner, "Nothing in the middle");
+        crate::golden::check("checker_cube", &frame);
+    }
+
+    #[test]
+    fn the_inspector_and_its_theme_are_flags() {
+        let plain = job(&[]);
+        assert!(!plain.inspector);
+        assert_eq!(plain.theme, Theme::DARK);
+        let job = job(&["--inspector", "--theme", "light"]);
+        assert!(job.inspector && !job.overlay);
+        assert_eq!(job.theme, Theme::LIGHT);
+    }
+
+    #[cfg(feature = "textures")]
+    #[test]
+    fn the_inspector_renders_like_its_golden_image_in_each_theme() {
+        let Ok(_gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        for theme in ["dark", "light"] {
+            let out = std::env::temp_dir().join(format!(
+                "wgpu-foray-{}-inspector-{theme}",
+                std::process::id()
+            ));
+            let job = job(&[
+                "--inspector",
+                "--theme",
+                theme,
+                "--frames",
+                "1",
+                "--size",
+                "480x320",
+                "--out",
+                out.to_str().expect("Temp dir isn't UTF-8"),
+            ]);
+            assert_eq!(pollster::block_on(render(&job)).expect("Render failed"), 0);
+            let frame = image::open(out.join("frame_00000.png")).unwrap().to_rgba8();
+            let _ = std::fs::remove_dir_all(&out);
+            crate::golden::check(&format!("inspector_{theme}"), &frame);
+        }
+    }
+
+    #[cfg(feature = "textures")]
+    #[test]
+    fn overlay_panels_come_out_the_same_whichever_format_the_surface_has() {
+        let Ok(_gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("wgpu-foray-{}-pairing", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let timeline = dir.join("gray.ron");
+        std::fs::write(
+            &timeline,
+            r##"(clear_color: [(time: 0.0, color: "#808080")])"##,
+        )
+        .unwrap();
+        let frames: Vec<_> = ["srgb", "linear"]
+            .into_iter()
+            .map(|surface| {
+                let out = dir.join(surface);
+                let job = job(&[
+                    "--surface",
+                    surface,
+                    "--overlay",
+                    "--timeline",
+                    timeline.to_str().expect("Temp dir isn't UTF-8"),
+                    "--frames",
+                    "1",
+                    "--size",
+                    "240x160",
+                    "--out",
+                    out.to_str().expect("Temp dir isn't UTF-8"),
+                ]);
+                assert_eq!(pollster::block_on(render(&job)).expect("Render failed"), 0);
+                image::open(out.join("frame_00000.png")).unwrap().to_rgba8()
+            })
+            .collect();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Not just similar, the views are the same whichever the surface came with
+        assert!(
+            frames[0] == frames[1],
+            "The sRGB-first and linear-first frames differ"
+        );
+        crate::golden::check("overlay_over_gray", &frames[0]);
+    }
+
+    // The loop render runs, with a file of known bytes saved where the GPU's frame would be
+    #[cfg(feature = "remote")]
+    fn serve(mut session: Session, out: PathBuf) -> std::thread::JoinHandle<Session> {
+        std::thread::spawn(move || {
+            let mut index = 0;
+            while session.another_frame(index, 0) {
+                let path = out.join(format!("frame_{index:05}.png"));
+                std::fs::write(&path, format!("frame {index}")).unwrap();
+                session.frame_done(&Ok(path));
+                index += 1;
+            }
+            session
+        })
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn a_scripted_remote_session_drives_the_headless_loop() {
+        use std::io::{BufRead, BufReader, Write};
+
+        use base64::Engine;
+        use serde_json::{json, Value};
+
+        let out = std::env::temp_dir().join(format!("wgpu-foray-{}-remote", std::process::id()));
+        std::fs::create_dir_all(&out).unwrap();
+        let session = Session::new(&job(&["--remote", "0", "--frames", "0"])).unwrap();
+        let address = session.remote.as_ref().expect("Not listening").address();
+        let looping = serve(session, out.clone());
+
+        let stream = std::net::TcpStream::connect(address).expect("Can't connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut send = |line: &str| -> Value {
+            writer.write_all(line.as_bytes()).unwrap();
+            writer.write_all(b"\n").unwrap();
+            let mut reply = String::new();
+            reader.read_line(&mut reply).expect("No answer");
+            serde_json::from_str(&reply).expect("Answer isn't JSON")
+        };
+
+        assert_eq!(
+            send(r#"{"command": "stats", "id": 1}"#),
+            json!({"id": 1, "ok": true, "output": ["scene starter", "items 3", "frames 0", "failed 0"]})
+        );
+        assert_eq!(send(r#"{"command": "scene stress"}"#), json!({"ok": true}));
+        let reply = send(r#"{"command": "scene nope"}"#);
+        assert_eq!(reply["ok"], json!(false), "{reply}");
+        assert_eq!(send(r#"{"command": "clear #ff0000"}"#), json!({"ok": true}));
+        assert_eq!(send(r#"{"command": "clear red"}"#)["ok"], json!(false));
+        assert_eq!(send(r#"{"command": "fly"}"#)["ok"], json!(false));
+
+        // Asked for inline, the frame comes back as the file's bytes in base64
+        let reply = send(r#"{"command": "screenshot", "inline": true, "id": 2}"#);
+        assert_eq!(reply["ok"], json!(true), "{reply}");
+        let path = PathBuf::from(reply["path"].as_str().expect("No path"));
+        assert_eq!(path, out.join("frame_00000.png"));
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(reply["png"].as_str().expect("No inline PNG"))
+            .expect("Not base64");
+        assert_eq!(png, std::fs::read(&path).unwrap());
+        // Otherwise just where it went
+        let reply = send(r#"{"command": "screenshot"}"#);
+        assert_eq!(
+            reply,
+            json!({"ok": true, "path": out.join("frame_00001.png")})
+        );
+
+        let reply = send(r#"{"command": "stats"}"#);
+        assert_eq!(reply["output"][0], json!("scene stress"));
+        assert_eq!(reply["output"][2], json!("frames 2"));
+        assert_eq!(send(r#"{"command": "quit"}"#), json!({"ok": true}));
+        let session = looping.join().expect("The loop panicked");
+        let _ = std::fs::remove_dir_all(&out);
+        assert_eq!(session.clear, Some(RgbaColor::from_hex(0xff_0000)));
+        assert_eq!(session.frames, 2);
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn a_remote_job_wont_listen_beyond_localhost_without_a_token() {
+        if std::env::var_os(remote::TOKEN_VARIABLE).is_some() {
+            eprintln!("{} is set, skipping", remote::TOKEN_VARIABLE);
+            return;
+        }
+        let job = job(&["--remote", "0", "--remote-bind", "0.0.0.0"]);
+        assert!(matches!(Session::new(&job), Err(ForayError::Remote { .. })));
+        // Refused before anything is rendered, GPU or not
+        assert!(matches!(
+            pollster::block_on(render(&job)),
+            Err(ForayError::Remote { .. })
+        ));
+    }
+}
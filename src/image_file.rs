@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use image::RgbaImage;
+
+use crate::error::ForayError;
+
+// What a build without the textures feature says when asked for an image file
+#[cfg(not(feature = "textures"))]
+const WITHOUT_TEXTURES: &str = "built without the textures feature, no image formats";
+
+// Image files in and out. The image crate only brings its formats along with the textures
+// feature, without it these fail saying so instead of with "unsupported format"
+
+// Whatever format the image crate reads, as RGBA8
+#[cfg(feature = "textures")]
+pub fn open(path: &Path) -> Result<RgbaImage, ForayError> {
+    image::open(path)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| ForayError::ImageFile {
+            path: path.to_owned(),
+            reason: e.to_string(),
+        })
+}
+
+#[cfg(not(feature = "textures"))]
+pub fn open(path: &Path) -> Result<RgbaImage, ForayError> {
+    Err(ForayError::ImageFile {
+        path: path.to_owned(),
+        reason: WITHOUT_TEXTURES.to_owned(),
+    })
+}
+
+// The format comes from the extension
+#[cfg(feature = "textures")]
+pub fn save(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    image
+        .save(path)
+        .map_err(|e| format!("can't write {}: {e}", path.display()))
+}
+
+#[cfg(not(feature = "textures"))]
+pub fn save(_image: &RgbaImage, path: &Path) -> Result<(), String> {
+    Err(format!(
+        "can't write {}, {WITHOUT_TEXTURES}",
+        path.display()
+    ))
+}
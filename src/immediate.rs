@@ -0,0 +1,233 @@
+use glam::Vec2;
+
+use crate::buffer_pool::BufferPool;
+use crate::camera2d::Camera2d;
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::shaders;
+use crate::targets::TargetRegistry;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ImmediateVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl ImmediateVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ImmediateVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    fn new(position: Vec2, color: [f32; 4]) -> Self {
+        Self {
+            position: position.into(),
+            color,
+        }
+    }
+}
+
+// Where immediate geometry's coordinates are. World goes through the 2D camera, Screen is
+// physical pixels from the top-left corner like the overlay
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Space {
+    World,
+    Screen,
+}
+
+// Plain vertices, one list per space and topology
+#[derive(Default)]
+struct Batch {
+    lines: Vec<ImmediateVertex>,
+    triangles: Vec<ImmediateVertex>,
+}
+
+// Hairlines and filled triangles added by the Frame's line/rect calls. Nothing is kept
+// between frames, ImmediateRenderer::draw empties it
+#[derive(Default)]
+pub struct Immediate {
+    world: Batch,
+    screen: Batch,
+}
+
+impl Immediate {
+    fn batch(&mut self, space: Space) -> &mut Batch {
+        match space {
+            Space::World => &mut self.world,
+            Space::Screen => &mut self.screen,
+        }
+    }
+
+    // One pixel wide whatever the zoom
+    pub fn line(&mut self, space: Space, p0: Vec2, p1: Vec2, color: RgbaColor) {
+        let color = color.to_f32_array_linear();
+        let lines = &mut self.batch(space).lines;
+        lines.push(ImmediateVertex::new(p0, color));
+        lines.push(ImmediateVertex::new(p1, color));
+    }
+
+    pub fn triangle(&mut self, space: Space, corners: [Vec2; 3], color: RgbaColor) {
+        let color = color.to_f32_array_linear();
+        let triangles = &mut self.batch(space).triangles;
+        triangles.extend(corners.map(|corner| ImmediateVertex::new(corner, color)));
+    }
+
+    // Filled, `min` and `max` are opposite corners
+    pub fn rect(&mut self, space: Space, min: Vec2, max: Vec2, color: RgbaColor) {
+        let (a, b) = (Vec2::new(max.x, min.y), Vec2::new(min.x, max.y));
+        self.triangle(space, [min, a, max], color);
+        self.triangle(space, [min, max, b], color);
+    }
+
+    pub fn is_empty(&self, space: Space) -> bool {
+        let batch = match space {
+            Space::World => &self.world,
+            Space::Screen => &self.screen,
+        };
+        batch.lines.is_empty() && batch.triangles.is_empty()
+    }
+}
+
+// Mirrors `struct Camera` in immediate.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    center: [f32; 2],
+    viewport: [f32; 2],
+    zoom: f32,
+    screen: u32,
+}
+
+// Draws a Frame's immediate geometry, each space with one draw per topology
+pub struct ImmediateRenderer {
+    layout: wgpu::BindGroupLayout,
+}
+
+impl ImmediateRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bank: &mut RenderPipelineBank,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Immediate Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let shader =
+            shaders::create_module(device, "Immediate Shader", include_str!("immediate.wgsl"));
+        let builder = PipelineBuilder::new("Immediate Pipeline", &shader)
+            .vertex_buffer(ImmediateVertex::desc())
+            .bind_group_layout(&layout)
+            .cull_mode(None)
+            .blend_mode(BlendMode::Alpha);
+        bank.register_surface(device, "immediate/fill", &builder, format);
+        bank.register_surface(
+            device,
+            "immediate/line",
+            &builder.topology(wgpu::PrimitiveTopology::LineList),
+            format,
+        );
+        Self { layout }
+    }
+
+    // What was queued in `space` onto the swapchain and gone from the frame. `camera` is
+    // only used for Space::World, `viewport` is the swapchain's size in pixels
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        space: Space,
+        camera: &Camera2d,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        if frame.immediate.is_empty(space) {
+            return Ok(());
+        }
+        let batch = std::mem::take(frame.immediate.batch(space));
+
+        // Lines then triangles in one buffer, a draw for each
+        let line_count = batch.lines.len() as u32;
+        let mut vertices = batch.lines;
+        vertices.extend(batch.triangles);
+        let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let vertex_buffer = pool.acquire(
+            device,
+            "Immediate Vertex Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            bytes.len() as u64,
+        );
+        queue.write_buffer(&vertex_buffer, 0, bytes);
+
+        let uniform = CameraUniform {
+            center: camera.center.into(),
+            viewport: match space {
+                Space::World => camera.clip_extent(viewport),
+                Space::Screen => [viewport.0 as f32, viewport.1 as f32],
+            },
+            zoom: camera.zoom,
+            screen: u32::from(space == Space::Screen),
+        };
+        let camera_buffer = pool.acquire(
+            device,
+            "Immediate Camera Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<CameraUniform>() as u64,
+        );
+        queue.write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&uniform));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Immediate Bind Group"),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &camera_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as u64),
+                }),
+            }],
+        });
+
+        let mut pass = frame.pass(
+            "Immediate Pass",
+            &[(ColorTarget::Swapchain, wgpu::LoadOp::Load)],
+            targets,
+        );
+        pass.raw.set_bind_group(0, &bind_group, &[]);
+        pass.raw
+            .set_vertex_buffer(0, vertex_buffer.slice(..bytes.len() as u64));
+        let total = vertices.len() as u32;
+        for (pipeline, range) in [
+            ("immediate/line", 0..line_count),
+            ("immediate/fill", line_count..total),
+        ] {
+            if !range.is_empty() {
+                pass.set_pipeline(bank, pipeline)?;
+                pass.raw.draw(range, 0..1);
+            }
+        }
+        Ok(())
+    }
+}
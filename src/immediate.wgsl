@@ -0,0 +1,44 @@
+// Immediate mode lines and triangles, see immediate.rs
+
+// Mirrors CameraUniform in immediate.rs
+struct Camera {
+    center: vec2<f32>,
+    viewport: vec2<f32>,
+    zoom: f32,
+    // 1 for screen pixels from the top-left, 0 for world units through the 2D camera
+    screen: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var ndc: vec2<f32>;
+    if camera.screen == 1u {
+        ndc = in.position / camera.viewport * 2.0 - 1.0;
+        ndc.y = -ndc.y;
+    } else {
+        // Same mapping as Camera2d::world_to_screen, y up
+        ndc = (in.position - camera.center) * camera.zoom * 2.0 / camera.viewport;
+    }
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,205 @@
+use crate::memory::{self, GpuMemoryTracker, MemoryCategory};
+use crate::mesh::Mesh;
+use crate::overlay::{Anchor, DebugOverlay};
+use crate::pipeline_bank::RenderPipelineBank;
+use crate::scene::Scene;
+use crate::targets::TargetRegistry;
+
+// Rows shown at once, the list scrolls to keep the selection in view
+const VISIBLE_ROWS: usize = 28;
+
+// What a row is about. The selection is held as one of these rather than a row index, so
+// it stays on the same thing when entries come and go above it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InspectorKey {
+    Pipeline(String),
+    Mesh(String),
+    Texture(String),
+    SceneItem(String),
+}
+
+pub struct InspectorRow {
+    // None for section headers, which can't be selected
+    pub key: Option<InspectorKey>,
+    pub text: String,
+}
+
+impl InspectorRow {
+    fn header(text: &str) -> Self {
+        Self {
+            key: None,
+            text: text.to_owned(),
+        }
+    }
+
+    fn entry(key: InspectorKey, text: String) -> Self {
+        Self {
+            key: Some(key),
+            text: format!("  {text}"),
+        }
+    }
+}
+
+// Everything the inspector lists, rebuilt every frame it's open
+pub fn rows(
+    bank: &RenderPipelineBank,
+    meshes: &[&Mesh],
+    memory: &GpuMemoryTracker,
+    targets: &TargetRegistry,
+    scene: &Scene,
+) -> Vec<InspectorRow> {
+    let mut rows = vec![InspectorRow::header("Pipelines")];
+    for (name, pipeline) in bank.entries() {
+        let status = match pipeline {
+            Some(pipeline) => format!("{:?}", pipeline.topology),
+            None => "building".to_owned(),
+        };
+        rows.push(InspectorRow::entry(
+            InspectorKey::Pipeline(name.to_owned()),
+            format!("{name}  {status}"),
+        ));
+    }
+
+    rows.push(InspectorRow::header("Meshes"));
+    for mesh in meshes {
+        let counted = if mesh.is_indexed() {
+            "indices"
+        } else {
+            "vertices"
+        };
+        rows.push(InspectorRow::entry(
+            InspectorKey::Mesh(mesh.name.clone()),
+            format!(
+                "{}  {} {counted}, {} submesh(es)",
+                mesh.name,
+                mesh.count(),
+                mesh.submeshes.len()
+            ),
+        ));
+    }
+
+    // Live allocations have the memory, render targets add their size and format
+    rows.push(InspectorRow::header("Textures"));
+    let mut textures: Vec<_> = memory
+        .live_allocations()
+        .into_iter()
+        .filter(|(_, category, _)| {
+            matches!(category, MemoryCategory::Textures | MemoryCategory::Targets)
+        })
+        .collect();
+    textures.sort_by(|a, b| a.0.cmp(&b.0));
+    for (label, _, bytes) in textures {
+        let details = match targets.entries().find(|(target, _, _)| *target == label) {
+            Some((_, format, (width, height))) => format!("{width}x{height} {format:?}, "),
+            None => String::new(),
+        };
+        rows.push(InspectorRow::entry(
+            InspectorKey::Texture(label.clone()),
+            format!("{label}  {details}{}", memory::format_bytes(bytes)),
+        ));
+    }
+
+    rows.push(InspectorRow::header("Scene"));
+    for item in &scene.items {
+        let transform = &item.transform;
+        rows.push(InspectorRow::entry(
+            InspectorKey::SceneItem(item.name.clone()),
+            format!(
+                "{}  ({:.0}, {:.0}) rot {:.0} scale {:.2}  {}  {:?}",
+                item.name,
+                transform.translation.x,
+                transform.translation.y,
+                transform.rotation.to_degrees(),
+                transform.scale,
+                item.pipeline,
+                item.visibility
+            ),
+        ));
+    }
+    rows
+}
+
+// F5 list of what's loaded, walked with the arrow keys
+pub struct Inspector {
+    pub enabled: bool,
+    selected: Option<InspectorKey>,
+    // Scene item Enter last toggled, Enter on a pipeline switches it to that pipeline
+    pub active_item: Option<String>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            selected: None,
+            active_item: None,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&InspectorKey> {
+        self.selected.as_ref()
+    }
+
+    // One selectable row up (negative) or down, from wherever the selection is now. When
+    // what was selected has gone, starts over from the top
+    pub fn move_selection(&mut self, rows: &[InspectorRow], delta: isize) {
+        let selectable: Vec<&InspectorKey> =
+            rows.iter().filter_map(|row| row.key.as_ref()).collect();
+        if selectable.is_empty() {
+            self.selected = None;
+            return;
+        }
+        let next = match selectable
+            .iter()
+            .position(|&key| Some(key) == self.selected.as_ref())
+        {
+            Some(current) => current
+                .saturating_add_signed(delta)
+                .min(selectable.len() - 1),
+            None => 0,
+        };
+        self.selected = Some(selectable[next].clone());
+    }
+
+    // Top right, scrolled so the selection is in view
+    pub fn queue(&self, overlay: &mut DebugOverlay, rows: &[InspectorRow]) {
+        let selected = rows
+            .iter()
+            .position(|row| row.key.is_some() && row.key == self.selected);
+        let first = selected
+            .map(|index| index.saturating_sub(VISIBLE_ROWS / 2))
+            .unwrap_or(0)
+            .min(rows.len().saturating_sub(VISIBLE_ROWS));
+        let shown = &rows[first..rows.len().min(first + VISIBLE_ROWS)];
+
+        let (_, line_height) = overlay.measure("#");
+        let width = shown
+            .iter()
+            .map(|row| overlay.measure(&row.text).0)
+            .fold(0.0, f32::max);
+        let margin = overlay.logical(4.0);
+        let size = (
+            width + 2.0 * margin,
+            shown.len() as f32 * line_height + 2.0 * margin,
+        );
+        let (x, y) = overlay.anchored(Anchor::TopRight, (8.0, 8.0), size);
+        let theme = overlay.theme;
+        overlay.rect((x, y, size.0, size.1), theme.panel);
+        // Long rows on a narrow window stop at the panel's edge
+        overlay.push_clip((x, y, size.0, size.1));
+        for (offset, row) in shown.iter().enumerate() {
+            let line_y = y + margin + offset as f32 * line_height;
+            let color = if row.key.is_none() {
+                theme.accent
+            } else {
+                if Some(first + offset) == selected {
+                    let highlight = theme.selection.faded(0.4);
+                    overlay.rect((x, line_y, size.0, line_height), highlight);
+                }
+                theme.text
+            };
+            overlay.text((x + margin, line_y), color, &row.text);
+        }
+        overlay.pop_clip();
+    }
+}
@@ -0,0 +1,3517 @@
+#![warn(clippy::all, clippy::pedantic)]
+
+mod accumulate;
+mod actions;
+mod assets;
+#[cfg(feature = "audio")]
+mod audio;
+mod backend;
+mod bind_groups;
+mod bindings;
+mod blit;
+mod bloom;
+mod buffer_pool;
+mod camera2d;
+mod camera3d;
+mod capabilities;
+mod colors;
+mod commands;
+mod console;
+mod crash;
+mod cursor;
+#[cfg(debug_assertions)]
+mod debug_channel;
+mod deferred;
+mod depth;
+mod dirty;
+mod effects;
+mod error;
+mod exposure;
+mod font;
+mod frame;
+mod frame_dump;
+mod geometry;
+mod gizmos;
+mod globals;
+#[cfg(feature = "gltf")]
+mod gltf;
+#[cfg(all(test, feature = "textures"))]
+mod golden;
+mod gpu_context;
+mod gpu_image;
+mod headless;
+mod image_file;
+mod immediate;
+mod inspector;
+mod lod;
+mod log_sink;
+mod lut;
+mod maintain;
+mod material;
+mod memory;
+mod mesh;
+mod mesh_arena;
+mod mesh_stream;
+mod morph;
+mod mrt;
+#[cfg(feature = "obj")]
+mod obj;
+mod options;
+mod overlay;
+mod pacing;
+mod physics;
+mod pipeline_bank;
+mod pipeline_stats;
+mod playground;
+mod post;
+mod preload;
+mod prelude; // Currently nothing in it, might become relevant as this grows -\(-.-)-\
+mod reflect;
+#[cfg(feature = "remote")]
+mod remote;
+mod render_graph;
+mod requirements;
+mod rng;
+mod scene;
+mod screenshot;
+#[cfg(feature = "text")]
+mod sdf_text;
+mod shader_bank;
+mod shaders;
+mod shapes;
+mod shutdown;
+mod snap;
+mod spatial_hash;
+mod sprites;
+mod stats;
+mod supersample;
+mod surface_views;
+mod targets;
+#[cfg(feature = "text")]
+mod text;
+mod text_input;
+mod tilemap;
+mod timeline;
+#[cfg(feature = "trace")]
+mod trace;
+mod transform;
+mod transform_gizmo;
+mod transparency;
+mod undo;
+mod viewport;
+mod warmup;
+mod watchdog;
+mod window_loop;
+
+// What an application drawing into its own window from its own loop needs, see
+// examples/embedded.rs. run() is the whole glfw app, main.rs just starts it
+pub use colors::RgbaColor;
+pub use error::ForayError;
+pub use options::Options;
+pub use scene::Scene;
+pub use window_loop::run;
+
+use std::cell::RefCell;
+
+use glam::{IVec2, Vec2, Vec3};
+use wgpu::{self, util::RenderEncoder, Color};
+
+use accumulate::Accumulator;
+use assets::{Asset, AssetHandle, AssetRequest, Assets};
+use bindings::Bindings;
+use blit::Blitter;
+use bloom::Bloom;
+use buffer_pool::BufferPool;
+use camera2d::{Camera2d, ResizePolicy};
+use camera3d::{CameraInput, FlyCamera, OrbitCamera};
+use capabilities::{Capabilities, Optional};
+use colors::Colors;
+use console::Console;
+#[cfg(debug_assertions)]
+use debug_channel::DebugChannel;
+use deferred::DeferredDemo;
+use effects::{ColorBlind, ColorBlindMode, ColorGrade, Dither, Pixelate, Vignette};
+use exposure::{AutoExposure, HdrScene};
+use frame::{Background, ColorTarget, Frame, DEBUG_MAGENTA};
+use frame_dump::FrameDump;
+use gizmos::Gizmos;
+use globals::GlobalsUniform;
+use immediate::{ImmediateRenderer, Space};
+use inspector::{Inspector, InspectorKey, InspectorRow};
+use lut::LutData;
+use maintain::Maintain;
+use memory::GpuMemoryTracker;
+use mesh::{
+    Indices, Mesh, MeshData, PackedVertex, Position, UvMapping, VertexColor, VertexLayoutId,
+};
+use mesh_arena::MeshArena;
+use mesh_stream::{ChunkSource, MeshStream};
+use morph::{DynamicMesh, Morph, MorphTarget};
+use mrt::MrtDemo;
+use overlay::{Anchor, DebugOverlay};
+use pacing::{Easing, RedrawRequests, Tween};
+use pipeline_bank::RenderPipelineBank;
+use pipeline_stats::PipelineStatistics;
+use post::EffectChain;
+use preload::{OutlineCache, ScenePreload};
+use render_graph::{RenderGraph, Resource};
+use requirements::DeviceRequirements;
+use scene::{ItemId, MeshRef, SceneItem, StressParams};
+#[cfg(feature = "text")]
+use sdf_text::{SdfFont, SdfTextRenderer};
+use shader_bank::ShaderBank;
+use shapes::{ShapeBuffer, ShapeInstance, ShapeRenderer, Stroke, Width};
+use shutdown::{App, Teardown};
+use snap::SnapGrid;
+use spatial_hash::SpatialHash;
+use sprites::{SpriteRenderer, SpriteStress, TilePolicy};
+use stats::FrameStats;
+use surface_views::SurfaceViews;
+use targets::TargetRegistry;
+#[cfg(feature = "text")]
+use text::{Font, TextRenderer};
+use tilemap::{TileLayout, TileMap, TileRenderer};
+use timeline::Timeline;
+use transform::{Affine, Transform2d};
+use transform_gizmo::{Handle, TransformGizmo};
+use transparency::Transparency;
+use undo::{SceneCommand, UndoStack};
+use viewport::Viewport;
+use watchdog::{GpuHealth, Watchdog};
+
+// Pentagon, colors are sRGB like everywhere else on the CPU side
+const VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        color: [0.5, 0.0, 0.5],
+    }, // A
+    Vertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        color: [0.5, 0.0, 0.5],
+    }, // B
+    Vertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        color: [0.5, 0.0, 0.5],
+    }, // C
+    Vertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        color: [0.5, 0.0, 0.5],
+    }, // D
+    Vertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        color: [0.5, 0.0, 0.5],
+    }, // E
+];
+
+// Star, the pentagon's corners with a point pushed in between each pair
+
+const STAR_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        color: [1.0, 0.6, 0.1],
+    }, // A
+    Vertex {
+        position: [-0.1373939, 0.13267975, 0.0],
+        color: [1.0, 0.6, 0.1],
+    },
+    Vertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        color: [1.0, 0.6, 0.1],
+    }, // B
+    Vertex {
+        position: [-0.16864299, -0.08966907, 0.0],
+        color: [1.0, 0.6, 0.1],
+    },
+    Vertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        color: [1.0, 0.6, 0.1],
+    }, // C
+    Vertex {
+        position: [0.0331668, -0.18809828, 0.0],
+        color: [1.0, 0.6, 0.1],
+    },
+    Vertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        color: [1.0, 0.6, 0.1],
+    }, // D
+    Vertex {
+        position: [0.1891412, -0.02658206, 0.0],
+        color: [1.0, 0.6, 0.1],
+    },
+    Vertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        color: [1.0, 0.6, 0.1],
+    }, // E
+    Vertex {
+        position: [0.08372889, 0.17166966, 0.0],
+        color: [1.0, 0.6, 0.1],
+    },
+];
+
+const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+// Pairs of corners for the line-list outline
+const OUTLINE_EDGES: &[u16] = &[0, 1, 1, 2, 2, 3, 3, 4, 4, 0];
+
+// Buffer Stuff
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl Morph for Vertex {
+    fn morph(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Vertex {
+            position: [0, 1, 2].map(|i| lerp(self.position[i], other.position[i])),
+            color: [0, 1, 2].map(|i| lerp(self.color[i], other.color[i])),
+        }
+    }
+}
+
+impl Position for Vertex {
+    fn position(&self) -> Vec3 {
+        Vec3::from_array(self.position)
+    }
+}
+
+impl VertexColor for Vertex {
+    fn set_color(&mut self, color: [f32; 3]) {
+        self.color = color;
+    }
+}
+
+impl Vertex {
+    // The shaders expect linear colors, the consts above are written in sRGB
+    fn linearized(&self) -> Vertex {
+        let [r, g, b] = self.color;
+        let [r, g, b, _] = RgbaColor::from_f32_array([r, g, b, 1.0]).to_f32_array_linear();
+        Vertex {
+            position: self.position,
+            color: [r, g, b],
+        }
+    }
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+// Vertex at half the size, 12 bytes instead of 24: positions as Snorm16 (the shapes are
+// in clip space, well inside -1..1) and linear color as Unorm8. The w and alpha are padding
+// that keeps both attributes 4-byte aligned, the shader only reads xyz and rgb
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompactVertex {
+    position: [i16; 4],
+    color: [u8; 4],
+}
+
+impl Position for CompactVertex {
+    fn position(&self) -> Vec3 {
+        let [x, y, z, _] = self.position.map(mesh::unpack_snorm16);
+        Vec3::new(x, y, z)
+    }
+}
+
+impl PackedVertex for CompactVertex {
+    type Full = Vertex;
+
+    fn pack(full: &Vertex) -> Self {
+        let mut packed = Self {
+            position: [0; 4],
+            color: [u8::MAX; 4],
+        };
+        packed.position[..3].copy_from_slice(&full.position.map(mesh::snorm16));
+        packed.color[..3].copy_from_slice(&full.color.map(mesh::unorm8));
+        packed
+    }
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Snorm16x4, 1 => Unorm8x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CompactVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+// A morph target from a closed outline: a center vertex (the average) followed by `count`
+// points resampled along it, ready for morph::fan_indices
+fn morph_target(name: &str, outline: &[Vertex], count: usize) -> MorphTarget<Vertex> {
+    let points: Vec<Vec2> = outline
+        .iter()
+        .map(|vertex| Vec2::new(vertex.position[0], vertex.position[1]))
+        .collect();
+    let center = points.iter().sum::<Vec2>() / points.len() as f32;
+    let color = outline[0].linearized().color;
+    let vertex = |point: Vec2| Vertex {
+        position: [point.x, point.y, 0.0],
+        color,
+    };
+    let mut vertices = vec![vertex(center)];
+    vertices.extend(
+        morph::resample_closed(&points, count)
+            .into_iter()
+            .map(vertex),
+    );
+    MorphTarget {
+        name: name.to_owned(),
+        vertices,
+    }
+}
+
+// Start implementation of Builder stuff
+struct ForayRender;
+
+enum Stage {
+    Uninitialized,
+    WithView,
+}
+
+// Radians per second the splash pentagon turns
+const SPLASH_SPIN: f32 = 0.4;
+
+// How often the loop looks in on background work (assets, pipelines) while it's running
+const PENDING_REDRAW: std::time::Duration = std::time::Duration::from_millis(50);
+
+// How long each shutdown stage waits on background work before leaving it behind
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+// What a draw that can't stop the frame does with its error
+fn report(result: Result<(), ForayError>) {
+    match result {
+        // Skipped for this frame, it'll be drawn once the pipeline is in
+        Ok(()) | Err(ForayError::PipelineNotReady(_)) => {}
+        Err(e) => log::error!("{e}"),
+    }
+}
+
+fn shape_pipeline(toggle: bool) -> &'static str {
+    if toggle {
+        "position"
+    } else {
+        "default"
+    }
+}
+
+// What gets drawn this frame
+#[derive(Debug)]
+pub enum View {
+    Shapes {
+        clear_color: RgbaColor,
+        toggle: bool,
+    },
+    Mrt(usize),
+    Primitives,
+    Deferred,
+    // Bright and dark regions for the auto exposure to adapt between
+    Exposure,
+    // Thousands of sprites out of a texture array, see SpriteStress
+    Sprites,
+    // A chunked tile map, see TileMap
+    Tiles,
+    Fullscreen(String),
+    // Progress bar while the startup assets come in
+    Loading {
+        done: usize,
+        total: usize,
+    },
+    // What's up when no scene was asked for, until another view is picked
+    Splash,
+}
+
+impl View {
+    // What the view starts from unless the B key overrides it
+    fn background(&self) -> Background {
+        match self {
+            View::Shapes { clear_color, .. } => Background::Clear(clear_color.to_wgpu_linear()),
+            View::Primitives | View::Splash => {
+                Background::Clear(RgbaColor::rgba(0.15, 0.15, 0.19, 1.0).to_wgpu_linear())
+            }
+            _ => Background::Clear(Color::BLACK),
+        }
+    }
+
+    // Seen through State::camera2d, so a letterbox puts bars around it
+    fn uses_camera2d(&self) -> bool {
+        matches!(
+            self,
+            View::Primitives | View::Exposure | View::Sprites | View::Tiles | View::Splash
+        )
+    }
+}
+
+// Main Structure. Never touches the window, whoever owns it (run(), or an application
+// embedding it) passes the input in and carries out window_requests, see StateBuilder
+pub struct State {
+    surface: wgpu::Surface<'static>,
+    // Kept to ask the surface what it supports again when the window changes monitor
+    adapter: wgpu::Adapter,
+    // StateBuilder's, a monitor change keeps to them
+    preferred_formats: Vec<wgpu::TextureFormat>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    // What the scene and the UI draw through, see SurfaceViews
+    surface_views: SurfaceViews,
+    size: (i32, i32),
+    // Window coordinates, set by the loop after each poll
+    cursor: (f64, f64),
+    content_scale: f32,
+    // What State wants done to the window, the loop applies them once per iteration
+    window_requests: Vec<WindowRequest>,
+    render_pipelines: RenderPipelineBank,
+    globals: GlobalsUniform,
+    targets: TargetRegistry,
+    blitter: Blitter,
+    accumulator: Accumulator,
+    mrt: MrtDemo,
+    deferred: DeferredDemo,
+    hdr_scene: HdrScene,
+    post: EffectChain,
+    // What the "colorblind" effect simulates, it's disabled while Off
+    color_blind: ColorBlindMode,
+    memory: GpuMemoryTracker,
+    pool: BufferPool,
+    stats: FrameStats,
+    overlay: DebugOverlay,
+    camera2d: Camera2d,
+    snap: SnapGrid,
+    // Where the F3 stats panel goes, the log lines stay bottom-left
+    stats_anchor: Anchor,
+    inspector: Inspector,
+    console: Console,
+    // Fixed overview of the 2D scene in the corner of the primitives view
+    inset: Viewport,
+    capabilities: Capabilities,
+    // None without Optional::PipelineStatistics
+    pipeline_stats: Option<PipelineStatistics>,
+    // debug_print from the shaders, None without Optional::ShaderDebug
+    #[cfg(debug_assertions)]
+    debug_channel: Option<DebugChannel>,
+    // Polls the device once a frame for everything waiting on a map_async
+    maintain: Maintain,
+    // --font or the embedded one, for Frame::draw_text
+    #[cfg(feature = "text")]
+    font: Font,
+    #[cfg(feature = "text")]
+    text: TextRenderer,
+    // Distance fields of `font`, for the scene items' name tags (T)
+    #[cfg(feature = "text")]
+    sdf_font: SdfFont,
+    #[cfg(feature = "text")]
+    sdf_text: SdfTextRenderer,
+    #[cfg(feature = "text")]
+    name_tags: bool,
+    // Handles around the item last picked with the right mouse button
+    transform_gizmo: TransformGizmo,
+    // Interactive scene edits, Ctrl+Z / Ctrl+Shift+Z
+    history: UndoStack,
+    // What every key does, looked up by the event loop and listed on the splash screen
+    bindings: Bindings,
+    // The splash screen's pentagon, spun by update. None once the splash is gone
+    splash: Option<Transform2d>,
+    // L or the scene command, the scene is only drawn there
+    show_primitives: bool,
+    // --timeline or timeline.ron, loaded on the first F7
+    timeline: Option<Timeline>,
+    shapes: ShapeRenderer,
+    sprites: SpriteRenderer,
+    // Built on the first J, or again by the sprites command
+    sprite_stress: Option<SpriteStress>,
+    tiles: TileRenderer,
+    // Built on the first Y, or by the tiles command
+    tile_map: Option<TileMap>,
+    // The tile a click paints with, None when clicks don't paint
+    tile_brush: Option<u32>,
+    // The tile the stroke in progress last painted, None when the button isn't held
+    tile_stroke: Option<IVec2>,
+    immediate: ImmediateRenderer,
+    gizmos: Gizmos,
+    scene: Scene,
+    // Resolved mesh outlines, one per scene item (empty while loading or when the mesh is missing)
+    scene_outlines: Vec<Vec<Vec2>>,
+    // The scene's shapes on the GPU, and the item they were last tinted for hovering
+    scene_shapes: ShapeBuffer,
+    scene_hovered: Option<usize>,
+    // Scene item bounds for picking, refreshed once per loop by index_items
+    item_grid: SpatialHash,
+    assets: Assets,
+    // Outlines still loading, with the scene item they're for
+    outline_requests: Vec<(usize, AssetHandle)>,
+    // Every loaded outline the scene uses, by mesh. What the next scene shares with this one
+    // is kept across the switch instead of loaded again
+    outline_cache: OutlineCache,
+    // The scene set_scene is loading, swapped in once it's done
+    preload: Option<ScenePreload>,
+    // --memory-budget, checked whenever a scene comes in
+    memory_budget: u64,
+    // --crash-test
+    crash_test: bool,
+    // --items, --seed and --spin, for `scene stress` in the console too
+    stress: StressParams,
+    watchdog: Watchdog,
+    // --lut, swapped into the grade effect once it's loaded
+    lut_request: Option<AssetHandle>,
+    // A dropped image on its way, and the mesh it's to be baked into
+    bake_request: Option<(AssetHandle, String)>,
+    // When the loop should draw next without an input event asking for it
+    redraw: RedrawRequests,
+    scene_path: std::path::PathBuf,
+    sync_after_present: bool,
+    // --warm-up
+    warm_up: bool,
+    // --audio, None when it's off or capture didn't start
+    #[cfg(feature = "audio")]
+    audio: Option<audio::AudioInput>,
+    // F12 or `screenshot` asked for one, saved here at the end of the next frame
+    screenshot: Option<std::path::PathBuf>,
+    // Where the last one went, for Ctrl+Shift+C
+    last_screenshot: Option<std::path::PathBuf>,
+    // F10 or `dump_frame` asked for the next frame as JSON here, with a screenshot of it
+    // next to it
+    frame_dump: Option<std::path::PathBuf>,
+    // Shift+F12 or `supersample`, taken after the next frame
+    supersample_request: Option<std::path::PathBuf>,
+    // --supersample and --supersample-scale: samples, and times the window's size
+    supersample: (u32, u32),
+    // While the samples are drawn
+    supersampling: bool,
+    // Remote `screenshot` commands, answered once the frame it's taken in is done
+    #[cfg(feature = "remote")]
+    remote_screenshots: Vec<remote::RemoteCommand>,
+    // Gathered by the main loop for the deferred view's camera, see CameraController.
+    // Mouse-look and the fly keys only work while the cursor is captured (F)
+    camera_input: CameraInput,
+    mouse_captured: bool,
+    // What the next fly camera starts at, --fly-speed until one has flown
+    fly_speed: f32,
+    // --transparent, --opacity and --click-through, the latter two changeable at runtime
+    transparency: Transparency,
+    // Set with the B key, otherwise every view brings its own
+    background_override: Option<Background>,
+    // Handed over to the Playground once it exists
+    playground_requests: Vec<pipeline_bank::PipelineHandle>,
+    pentagon: Mesh,
+    pentagon_outline: Mesh,
+    // Pentagon and star resampled to the same outline, X morphs between them
+    morph: DynamicMesh<Vertex>,
+    morph_tween: Tween,
+    // `stream circle` or `stream load`, drawn behind the pentagon as it lands
+    mesh_stream: Option<MeshStream>,
+    // Bytes a frame for mesh streams, `stream budget` changes it
+    stream_budget: u64,
+}
+
+// Things State asks of the window it draws into, see State::take_window_requests
+pub enum WindowRequest {
+    Opacity(f32),
+    ClickThrough(bool),
+    CaptureCursor(bool),
+    Close,
+}
+
+// Where State draws: a window's handles, or a surface whoever owns the window made
+enum SurfaceSource {
+    Target(wgpu::SurfaceTargetUnsafe),
+    Surface(wgpu::Instance, wgpu::Surface<'static>),
+}
+
+// Sets up a State for a window someone else owns, along with its event loop. Everything is
+// checked in build, which says what's wrong instead of panicking
+pub struct StateBuilder<'o> {
+    options: &'o Options,
+    source: Option<SurfaceSource>,
+    size: (i32, i32),
+    // All of them unless told, never with a surface passed in
+    backends: Option<wgpu::Backends>,
+    preferred_formats: Vec<wgpu::TextureFormat>,
+    // --msaa unless told
+    msaa: Option<u32>,
+    // Names and WGSL of the fullscreen pipelines to build along with the rest
+    pipelines: Vec<(String, String)>,
+    framebuffer_transparent: bool,
+}
+
+impl<'o> StateBuilder<'o> {
+    pub fn new(options: &'o Options) -> Self {
+        Self {
+            options,
+            source: None,
+            size: (0, 0),
+            backends: None,
+            preferred_formats: Vec::new(),
+            msaa: None,
+            pipelines: Vec::new(),
+            framebuffer_transparent: false,
+        }
+    }
+
+    // The window to draw into, by whatever wgpu can make a surface from
+    // Safety: the window and display have to stay valid for as long as the State lives
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn surface_target(mut self, target: wgpu::SurfaceTargetUnsafe) -> Self {
+        self.source = Some(SurfaceSource::Target(target));
+        self
+    }
+
+    // The same from a raw-window-handle pair, for windows that aren't glfw's
+    // Safety: as surface_target
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn raw_handles(
+        self,
+        display: wgpu::rwh::RawDisplayHandle,
+        window: wgpu::rwh::RawWindowHandle,
+    ) -> Self {
+        self.surface_target(wgpu::SurfaceTargetUnsafe::RawHandle {
+            raw_display_handle: display,
+            raw_window_handle: window,
+        })
+    }
+
+    // A surface someone already made, the adapter is picked from the instance it was made on
+    pub fn surface(mut self, instance: wgpu::Instance, surface: wgpu::Surface<'static>) -> Self {
+        self.source = Some(SurfaceSource::Surface(instance, surface));
+        self
+    }
+
+    // Framebuffer size in pixels, resize() from then on
+    pub fn size(mut self, size: (i32, i32)) -> Self {
+        self.size = size;
+        self
+    }
+
+    // Backends to look for an adapter on, the app passes what WGPU_FORAY_BACKEND asks for
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = Some(backends);
+        self
+    }
+
+    // Surface formats in order, the first one the surface offers is drawn into. Without
+    // them the first sRGB one it offers
+    pub fn preferred_formats(mut self, formats: &[wgpu::TextureFormat]) -> Self {
+        self.preferred_formats = formats.to_vec();
+        self
+    }
+
+    // Samples per pixel in the deferred view's g-buffer
+    pub fn msaa(mut self, samples: u32) -> Self {
+        self.msaa = Some(samples);
+        self
+    }
+
+    // A pipeline View::Fullscreen(name) draws, from WGSL with an fs_main fragment entry. It
+    // #includes "fullscreen.wgsl" and "globals.wgsl" the way playground.wgsl does
+    pub fn fullscreen_pipeline(mut self, name: &str, wgsl: &str) -> Self {
+        self.pipelines.push((name.to_owned(), wgsl.to_owned()));
+        self
+    }
+
+    // Whether the window got a transparent framebuffer, --transparent stays opaque without one
+    pub fn framebuffer_transparent(mut self, transparent: bool) -> Self {
+        self.framebuffer_transparent = transparent;
+        self
+    }
+
+    // What can be told without a GPU is checked before one is looked for
+    pub async fn build(mut self) -> Result<State, ForayError> {
+        if self.size.0 <= 0 || self.size.1 <= 0 {
+            return Err(ForayError::StateBuilder(format!(
+                "a {}x{} framebuffer has nothing to draw into",
+                self.size.0, self.size.1
+            )));
+        }
+        let samples = self.samples();
+        if !deferred::SAMPLE_COUNTS.contains(&samples) {
+            return Err(ForayError::UnsupportedSampleCount {
+                what: "The deferred view".to_owned(),
+                samples,
+                supported: deferred::SAMPLE_COUNTS.to_vec(),
+            });
+        }
+        for (index, (name, wgsl)) in self.pipelines.iter().enumerate() {
+            if self.pipelines[..index]
+                .iter()
+                .any(|(other, _)| other == name)
+            {
+                return Err(ForayError::StateBuilder(format!(
+                    "two fullscreen pipelines named \"{name}\""
+                )));
+            }
+            if let Some(include) = shaders::unknown_include(wgsl) {
+                return Err(ForayError::StateBuilder(format!(
+                    "fullscreen pipeline \"{name}\" includes \"{include}\", not a built-in file"
+                )));
+            }
+        }
+        let Some(source) = self.source.take() else {
+            return Err(ForayError::StateBuilder(
+                "no surface target to draw into".to_owned(),
+            ));
+        };
+        if matches!(source, SurfaceSource::Surface(..)) && self.backends.is_some() {
+            return Err(ForayError::StateBuilder(
+                "backends come from the instance the surface was made on".to_owned(),
+            ));
+        }
+        State::new(self, source).await
+    }
+
+    fn samples(&self) -> u32 {
+        self.msaa.unwrap_or(self.options.msaa)
+    }
+}
+
+impl State {
+    async fn new(builder: StateBuilder<'_>, source: SurfaceSource) -> Result<State, ForayError> {
+        let options = builder.options;
+        let size = builder.size;
+
+        let (surface, adapter) = match source {
+            SurfaceSource::Target(target) => {
+                open_adapter(target, builder.backends.unwrap_or(wgpu::Backends::all())).await?
+            }
+            SurfaceSource::Surface(instance, surface) => {
+                let adapter = request_adapter(&instance, &surface).await.ok_or_else(|| {
+                    ForayError::GpuUnavailable(
+                        "no adapter on the instance can present to the surface".to_owned(),
+                    )
+                })?;
+                (surface, adapter)
+            }
+        };
+        let capabilities = Capabilities::new(
+            &adapter,
+            &surface,
+            &device_requirements(options),
+            &builder.preferred_formats,
+        )?;
+        capabilities.log();
+        crash::set_capabilities(capabilities.report());
+        let (device, queue) = open_device(&adapter, &capabilities).await?;
+        crash::set_gpu(&device, &queue);
+        let watchdog = Watchdog::new(&device, options.gpu_timeout);
+
+        let surface_caps = surface.get_capabilities(&adapter);
+        let transparency = Transparency::negotiate(
+            options.transparent,
+            builder.framebuffer_transparent,
+            &surface_caps.alpha_modes,
+        );
+        let (config, views) =
+            surface_config(options, &capabilities, &surface_caps, &transparency, size);
+        surface.configure(&device, &config);
+
+        // Before the first shader is preprocessed, that's when debug_print gets picked
+        #[cfg(debug_assertions)]
+        if capabilities.has(Optional::ShaderDebug) {
+            debug_channel::enable();
+        }
+        let shaders = ShaderBank::load(&device)?;
+
+        let memory = GpuMemoryTracker::new();
+        #[cfg(debug_assertions)]
+        let debug_channel = debug_channel::enabled().then(|| DebugChannel::new(&device, &memory));
+        #[cfg(debug_assertions)]
+        let globals = GlobalsUniform::new(
+            &device,
+            &memory,
+            debug_channel.as_ref().map(DebugChannel::buffer),
+        );
+        #[cfg(not(debug_assertions))]
+        let globals = GlobalsUniform::new(&device, &memory, None);
+        let mut render_pipelines = RenderPipelineBank::new();
+        register_pentagon_pipelines(&device, &shaders, views, &mut render_pipelines);
+        MrtDemo::register_pipeline(&device, &shaders, &mut render_pipelines);
+
+        let mut targets = TargetRegistry::new((config.width, config.height), &memory);
+        let mrt = MrtDemo::new(&device, &mut targets);
+        let blitter = Blitter::new(&device, views.scene);
+        let mut deferred = DeferredDemo::new(
+            &device,
+            &queue,
+            views.scene,
+            &mut targets,
+            &mut render_pipelines,
+            &memory,
+            options.depth,
+        );
+        deferred.depth_prepass = options.depth_prepass;
+        deferred.set_samples(&device, &mut targets, builder.samples())?;
+        let mut assets = Assets::new();
+        let lut_request = options
+            .lut
+            .clone()
+            .map(|path| assets.request(AssetRequest::Lut(path)));
+        let post = effect_chain(
+            &device,
+            &queue,
+            &memory,
+            &mut targets,
+            &mut render_pipelines,
+            views.scene,
+            &capabilities,
+            options,
+        );
+        let hdr_scene = HdrScene::new(&device, &memory, &mut render_pipelines);
+        let shapes = ShapeRenderer::new(&device, views.scene, &mut render_pipelines);
+        let sprites = SpriteRenderer::new(&device, views.scene, &mut render_pipelines);
+        let tiles = TileRenderer::new(&device, views.scene, &mut render_pipelines, &sprites);
+        let immediate = ImmediateRenderer::new(&device, views.scene, &mut render_pipelines);
+        let inset = Viewport::new(
+            &device,
+            &mut targets,
+            "Inset Viewport",
+            views.scene,
+            Camera2d {
+                zoom: 0.125,
+                ..Camera2d::new()
+            },
+        );
+        let gizmos = Gizmos::new(
+            &device,
+            views.scene,
+            deferred::DEPTH_FORMAT,
+            options.depth,
+            &mut render_pipelines,
+            &memory,
+        );
+        let mut overlay = DebugOverlay::new(&device, &queue, views, &mut render_pipelines, &memory);
+        overlay.theme = options.theme;
+        #[cfg(feature = "text")]
+        let text = TextRenderer::new(&device, &queue, views.scene, &mut render_pipelines, &memory);
+        #[cfg(feature = "text")]
+        let font = Font::load_or_embedded(options.font.as_deref());
+        #[cfg(feature = "text")]
+        overlay.set_fallback(&font);
+        #[cfg(feature = "text")]
+        let sdf_font = SdfFont::new(&font);
+        #[cfg(feature = "text")]
+        let sdf_text = SdfTextRenderer::new(&device, views.scene, &mut render_pipelines);
+
+        // Shadertoy-style fullscreen pipelines
+        let accumulator = Accumulator::new(&device, &mut targets, capabilities.accumulation_format);
+        let playground_requests = playground::register_pipelines(
+            &device,
+            views.scene,
+            accumulator.format,
+            &globals.layout,
+            &mut render_pipelines,
+        );
+        for (name, wgsl) in &builder.pipelines {
+            if render_pipelines.contains(name) {
+                return Err(ForayError::StateBuilder(format!(
+                    "\"{name}\" is taken by a built-in pipeline"
+                )));
+            }
+            // A shader that doesn't compile fails the build instead of the first frame
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            playground::register_fullscreen(
+                &device,
+                name,
+                wgsl,
+                views.scene,
+                accumulator.format,
+                &globals.layout,
+                &mut render_pipelines,
+            );
+            if let Some(e) = device.pop_error_scope().await {
+                return Err(ForayError::StateBuilder(format!(
+                    "fullscreen pipeline \"{name}\": {e}"
+                )));
+            }
+        }
+
+        let (pentagon, pentagon_outline, morph) = pentagon_meshes(&device, &memory);
+        let pipeline_stats = capabilities
+            .has(Optional::PipelineStatistics)
+            .then(|| PipelineStatistics::new(&device));
+
+        Ok(Self {
+            surface,
+            adapter,
+            preferred_formats: builder.preferred_formats,
+            device,
+            queue,
+            config,
+            surface_views: views,
+            size,
+            cursor: (0.0, 0.0),
+            content_scale: 1.0,
+            window_requests: startup_window_requests(options),
+            render_pipelines,
+            globals,
+            targets,
+            blitter,
+            accumulator,
+            mrt,
+            deferred,
+            hdr_scene,
+            post,
+            color_blind: ColorBlindMode::Off,
+            pool: BufferPool::new(&memory, 16 * 1024 * 1024),
+            memory,
+            stats: FrameStats::new(),
+            overlay,
+            camera2d: Camera2d {
+                resize: ResizePolicy::parse(&options.resize, (size.0 as u32, size.1 as u32))
+                    .unwrap_or_default(),
+                ..Camera2d::new()
+            },
+            snap: SnapGrid::new(options.snap_spacing),
+            stats_anchor: options.stats_anchor,
+            inspector: Inspector::new(),
+            console: Console::new(),
+            inset,
+            pipeline_stats,
+            #[cfg(debug_assertions)]
+            debug_channel,
+            maintain: Maintain::new(),
+            capabilities,
+            #[cfg(feature = "text")]
+            font,
+            #[cfg(feature = "text")]
+            text,
+            #[cfg(feature = "text")]
+            sdf_font,
+            #[cfg(feature = "text")]
+            sdf_text,
+            #[cfg(feature = "text")]
+            name_tags: true,
+            transform_gizmo: TransformGizmo::new(),
+            history: UndoStack::new(),
+            bindings: Bindings::builtin(),
+            splash: options
+                .scene_file
+                .is_none()
+                .then(|| Transform2d::at(Vec2::ZERO)),
+            show_primitives: false,
+            timeline: None,
+            shapes,
+            sprites,
+            sprite_stress: None,
+            tiles,
+            tile_map: None,
+            tile_brush: None,
+            tile_stroke: None,
+            immediate,
+            gizmos,
+            scene: Scene::starter(),
+            scene_outlines: Vec::new(),
+            scene_shapes: ShapeBuffer::new(),
+            scene_hovered: None,
+            item_grid: SpatialHash::new(),
+            assets,
+            outline_requests: Vec::new(),
+            outline_cache: OutlineCache::new(),
+            preload: None,
+            memory_budget: options.memory_budget,
+            crash_test: options.crash_test,
+            stress: options.stress,
+            watchdog,
+            lut_request,
+            bake_request: None,
+            redraw: RedrawRequests::default(),
+            scene_path: options
+                .scene_file
+                .clone()
+                .unwrap_or_else(|| "scene.ron".into()),
+            sync_after_present: options.sync_after_present,
+            warm_up: options.warm_up,
+            #[cfg(feature = "audio")]
+            audio: options
+                .audio
+                .then(audio::AudioInput::start)
+                .and_then(|started| started.map_err(|e| log::warn!("{e}")).ok()),
+            screenshot: None,
+            last_screenshot: None,
+            frame_dump: None,
+            supersample_request: None,
+            supersample: (options.supersample_samples, options.supersample_scale),
+            supersampling: false,
+            #[cfg(feature = "remote")]
+            remote_screenshots: Vec::new(),
+            camera_input: CameraInput::default(),
+            mouse_captured: false,
+            fly_speed: options.fly_speed,
+            transparency,
+            background_override: None,
+            playground_requests,
+            pentagon,
+            pentagon_outline,
+            morph,
+            morph_tween: Tween::new(
+                0.0,
+                0.0,
+                std::time::Duration::from_millis(800),
+                Easing::SmoothStep,
+            ),
+            mesh_stream: None,
+            stream_budget: mesh_stream::DEFAULT_BUDGET,
+        })
+    }
+
+    pub fn resize(&mut self, new_size: (i32, i32)) {
+        if new_size.0 > 0 && new_size.1 > 0 {
+            self.size = new_size;
+            (self.config.width, self.config.height) = self
+                .capabilities
+                .clamp_size((new_size.0 as u32, new_size.1 as u32));
+            self.surface.configure(&self.device, &self.config);
+            self.targets
+                .resize(&self.device, (self.config.width, self.config.height));
+        }
+    }
+
+    // Cursor position in framebuffer pixels, what picking and the fullscreen views' mouse go by
+    pub fn set_cursor(&mut self, cursor: (f64, f64)) {
+        self.cursor = cursor;
+    }
+
+    // What the window's owner should do to it since last asked
+    pub fn take_window_requests(&mut self) -> Vec<WindowRequest> {
+        std::mem::take(&mut self.window_requests)
+    }
+
+    // The window moved to another monitor, which may want a different surface format or
+    // present mode (an HDR display next to an SDR one). When either changed the surface is
+    // configured again and everything drawing into it rebuilt for the new format
+    fn check_surface(&mut self) {
+        let caps = self.surface.get_capabilities(&self.adapter);
+        if caps.formats.is_empty() || caps.present_modes.is_empty() {
+            log::warn!("The surface reports no formats or present modes, keeping the old ones");
+            return;
+        }
+        let Some(format) = capabilities::preferred_format(&caps.formats, &self.preferred_formats)
+        else {
+            log::warn!(
+                "The surface offers none of {:?} anymore, keeping {:?}",
+                self.preferred_formats,
+                self.config.format
+            );
+            return;
+        };
+        let present_mode = caps.present_modes[0];
+        let format_changed = format != self.config.format;
+        if !format_changed && present_mode == self.config.present_mode {
+            return;
+        }
+        if present_mode != self.config.present_mode {
+            println!(
+                "Present mode {:?} -> {present_mode:?}",
+                self.config.present_mode
+            );
+        }
+        if format_changed {
+            println!("Surface format {:?} -> {format:?}", self.config.format);
+        }
+        let views = SurfaceViews::new(
+            format,
+            self.capabilities
+                .downlevel
+                .flags
+                .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS),
+        );
+        self.config.format = format;
+        self.config.view_formats = views.view_formats();
+        self.config.present_mode = present_mode;
+        self.surface.configure(&self.device, &self.config);
+        if !format_changed {
+            return;
+        }
+        self.surface_views = views;
+        self.capabilities.surface_format = format;
+        self.capabilities.surface_formats = caps.formats;
+        let rebuilt = self
+            .render_pipelines
+            .rebuild_for_format(&self.device, views);
+        self.blitter.set_format(&self.device, views.scene);
+        self.post.set_output_format(views.scene);
+        self.inset
+            .set_format(&self.device, &mut self.targets, views.scene);
+        println!("Rebuilt {rebuilt} pipelines for {}", views.describe());
+    }
+
+    fn cursor_world(&self) -> Vec2 {
+        let (x, y) = self.cursor;
+        self.camera2d.screen_to_world(
+            Vec2::new(x as f32, y as f32),
+            (self.config.width, self.config.height),
+        )
+    }
+
+    // Loads what the scene's manifest lists and isn't loaded yet, the loading view shows until
+    // it's all in and show_scene swaps it in. A scene still preloading is dropped
+    pub fn set_scene(&mut self, name: &str, scene: Scene) {
+        self.cancel_stream("Scene switch");
+        // What gets allocated from here on is put down to the new scene in the memory breakdown
+        self.memory.set_scene(Some(name));
+        if let Some(preload) = self.preload.take() {
+            let dropped = preload.cancel(&mut self.assets);
+            println!("Scene switch replaced, dropped {dropped} load(s)");
+        }
+        let preload = ScenePreload::start(scene, &self.outline_cache, &mut self.assets);
+        if preload.is_done() {
+            self.show_scene(preload.scene);
+        } else {
+            self.preload = Some(preload);
+        }
+    }
+
+    // (done, total) of the scene set_scene is loading
+    fn preload_progress(&self) -> Option<(usize, usize)> {
+        self.preload.as_ref().map(ScenePreload::progress)
+    }
+
+    // Everything in the scene's manifest is loaded (or failed)
+    fn show_scene(&mut self, mut scene: Scene) {
+        // Fade settings come from the command line, not the file
+        scene.fade = self.scene.fade;
+        scene.check_pipelines(&self.render_pipelines);
+        preload::evict_unused(&mut self.outline_cache, &scene);
+        let cache = &self.outline_cache;
+        self.scene_outlines = scene
+            .items
+            .iter()
+            .map(|item| cache.get(&item.mesh).cloned().unwrap_or_default())
+            .collect();
+        for (_, handle) in self.outline_requests.drain(..) {
+            self.assets.cancel(handle);
+        }
+        self.camera2d = Camera2d {
+            resize: self.camera2d.resize,
+            ..scene.camera
+        };
+        self.scene = scene;
+        self.transform_gizmo = TransformGizmo::new();
+        // Ids start over with every scene, old commands would hit the wrong items
+        self.history = UndoStack::new();
+        self.memory.check_budget(self.memory_budget);
+    }
+
+    // Once per frame, puts what the asset loaders finished where it belongs
+    pub fn receive_assets(&mut self) {
+        for (handle, asset) in self.assets.poll() {
+            self.request_redraw();
+            match asset {
+                Asset::Outline(outline) => {
+                    if let Some(mesh) = self
+                        .preload
+                        .as_mut()
+                        .and_then(|preload| preload.finish(handle))
+                    {
+                        self.outline_cache.insert(mesh, outline);
+                    } else if let Some(position) = self
+                        .outline_requests
+                        .iter()
+                        .position(|&(_, request)| request == handle)
+                    {
+                        let (index, _) = self.outline_requests.remove(position);
+                        let mesh = self.scene.items[index].mesh.clone();
+                        self.outline_cache.insert(mesh, outline.clone());
+                        self.scene_outlines[index] = outline;
+                        // Likely a different number of shapes, moving every item after it
+                        self.scene.dirty.mark_all();
+                    }
+                }
+                Asset::Lut(lut) if self.lut_request == Some(handle) => {
+                    let grade = ColorGrade::new(
+                        &self.device,
+                        &self.queue,
+                        &self.memory,
+                        &mut self.targets,
+                        &lut,
+                    );
+                    if let Err(e) = self.post.replace(
+                        &self.device,
+                        &mut self.render_pipelines,
+                        "grade",
+                        Box::new(grade),
+                    ) {
+                        log::error!("{e}");
+                    }
+                }
+                Asset::Lut(_) => {}
+                Asset::Image(image) => {
+                    if let Some((_, mesh)) =
+                        self.bake_request.take_if(|(request, _)| *request == handle)
+                    {
+                        self.bake_mesh_colors(&mesh, &image);
+                    }
+                }
+            }
+        }
+        // Failed ones keep the empty outline, drawn as a cross
+        let assets = &self.assets;
+        self.outline_requests
+            .retain(|&(_, handle)| assets.error(handle).is_none());
+        if let Some(preload) = &mut self.preload {
+            preload.drop_failed(assets);
+        }
+        if let Some(preload) = self.preload.take_if(|preload| preload.is_done()) {
+            self.show_scene(preload.scene);
+        }
+        // The loaders can't wake a waiting loop, so it checks back while they're busy
+        if self.assets.pending() > 0 {
+            self.request_redraw_after(PENDING_REDRAW);
+        }
+    }
+
+    // The image's colors into the vertices of `mesh`, by its name. Only the pentagons keep
+    // their vertices around for this
+    fn bake_mesh_colors(&mut self, mesh: &str, image: &image::RgbaImage) {
+        let mapping = UvMapping::default();
+        if mesh == self.morph.mesh.name {
+            self.morph.bake_colors(&self.queue, image, mapping);
+        } else if mesh == self.pentagon.name {
+            let vertices = VERTICES.iter().map(Vertex::linearized).collect();
+            let indices = INDICES.iter().map(|&i| u32::from(i)).collect();
+            let mut data = MeshData::new(mesh, vertices, indices);
+            data.bake_colors_from_image(image, mapping);
+            self.pentagon.write_vertices(&self.queue, &data.vertices);
+        } else {
+            log::warn!("Can't bake into {mesh}, only the pentagon meshes keep their vertices");
+            return;
+        }
+        println!(
+            "Baked {}x{} image into {mesh}",
+            image.width(),
+            image.height()
+        );
+    }
+
+    fn save_scene(&mut self) {
+        self.scene.camera = self.camera2d;
+        match self.scene.save(&self.scene_path) {
+            Ok(()) => println!("Saved scene to {}", self.scene_path.display()),
+            Err(e) => log::error!("{e}"),
+        }
+    }
+
+    // Once the loop is done drawing. The app's on_exit runs first, then background work is
+    // stopped or waited on (see Teardown::run), and the surface goes before the device and
+    // instance it was made from
+    fn shutdown(mut self, app: &mut impl App) {
+        Teardown {
+            device: &self.device,
+            assets: &mut self.assets,
+            pipelines: &mut self.render_pipelines,
+            maintain: &mut self.maintain,
+        }
+        .run(app, SHUTDOWN_TIMEOUT);
+        println!("Shutdown: releasing the surface");
+        let State { surface, .. } = self;
+        drop(surface);
+        println!("Shutdown: releasing the device");
+    }
+
+    // Might be repurposed, (?) Could be cool in the builder abstraction thingey
+    fn _draw_triangle(&mut self, toggle: bool) {
+        // My conditional here
+        let render_pipeline = self
+            .render_pipelines
+            .get(shape_pipeline(toggle))
+            .expect("Shape pipelines are registered in State::new");
+        // We will create a new pipeline
+        let output = self
+            .surface
+            .get_current_texture()
+            .expect("Failed to get texture");
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&render_pipeline.raw);
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+
+        stats::submit(&self.queue, std::iter::once(encoder.finish()));
+        output.present();
+    }
+
+    fn clear_screen_to(&mut self, color: Color) {
+        let output = self
+            .surface
+            .get_current_texture()
+            .expect("Failed to get texture");
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.transparency.clear_color(color)),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        drop(render_pass);
+
+        stats::submit(&self.queue, std::iter::once(encoder.finish()));
+        output.present();
+    }
+
+    // What's been queued in `space` with Frame::line/rect and friends
+    fn draw_immediate(&mut self, frame: &mut Frame, space: Space) {
+        if let Err(e) = self.immediate.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            space,
+            &self.camera2d,
+            (self.config.width, self.config.height),
+        ) {
+            log::error!("{e}");
+        }
+    }
+
+    // The view's own or the override, cleared to what --transparent wants
+    fn background(&self, view: &View) -> Background {
+        match self.background_override.unwrap_or(view.background()) {
+            Background::Clear(color) => Background::Clear(self.transparency.clear_color(color)),
+            background => background,
+        }
+    }
+
+    // None when there's no image to draw into this time, the frame is skipped
+    fn begin_frame(&self, background: Background) -> Option<Frame> {
+        match Frame::begin(&self.surface, &self.device, self.surface_views, background) {
+            Ok(frame) => Some(frame),
+            Err(e @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                log::warn!("Surface {e}, reconfiguring and skipping the frame");
+                self.surface.configure(&self.device, &self.config);
+                None
+            }
+            Err(e) => {
+                log::warn!("Skipping the frame: {e}");
+                None
+            }
+        }
+    }
+
+    // Everything for one frame: the view, the overlay on top, then the stats
+    // `alpha` is how far between the last two fixed updates this frame is drawn
+    pub fn render(&mut self, view: &View, alpha: f32) {
+        let _render = tracing::info_span!("render").entered();
+        log_sink::set_frame(self.stats.frame_index);
+        match self.watchdog.check(&self.device) {
+            GpuHealth::Running => {}
+            GpuHealth::Hung => return,
+            GpuHealth::Lost(report) => {
+                log::error!("{report}");
+                self.window_requests.push(WindowRequest::Close);
+                return;
+            }
+        }
+        self.render_pipelines.poll();
+        // Everything on the first frame, then whatever a format change, an override or a
+        // background build swapped in since
+        let cold = self.render_pipelines.take_cold();
+        if self.warm_up && !cold.is_empty() {
+            warmup::warm_up(&self.device, &self.queue, &self.render_pipelines, &cold);
+        }
+        self.targets.validate_bind_groups(&self.device);
+        let Some(mut frame) = self.begin_frame(self.background(view)) else {
+            return;
+        };
+        crash::set_frame(frame.surface_texture());
+        if self.frame_dump.is_some() {
+            frame.dump = Some(FrameDump::new(
+                self.stats.frame_index,
+                format!("{view:?}"),
+                self.surface_views.describe(),
+            ));
+        }
+        frame.statistics = self
+            .pipeline_stats
+            .as_ref()
+            .and_then(PipelineStatistics::begin_frame);
+        #[cfg(debug_assertions)]
+        if let Some(debug_channel) = &self.debug_channel {
+            debug_channel.begin_frame(&mut frame.encoder);
+        }
+        let record = tracing::info_span!("record").entered();
+        self.overlay
+            .set_screen((self.config.width, self.config.height), self.content_scale);
+        self.pump_stream();
+        self.stats.scene_upload = None;
+        self.stats.tiles = None;
+        if matches!(view, View::Primitives) {
+            self.upload_scene_shapes();
+        }
+        // Everything the overlay shows is queued first, it's drawn by the last pass
+        if matches!(view, View::Primitives) && self.inset.enabled {
+            self.queue_inset_border();
+        }
+        if matches!(view, View::Primitives) && self.snap.rulers {
+            self.queue_rulers();
+        }
+        if self.inspector.enabled {
+            let rows = self.inspector_rows();
+            self.inspector.queue(&mut self.overlay, &rows);
+        }
+        if let Some((progress, readout)) = self
+            .timeline
+            .as_ref()
+            .map(|timeline| (timeline.progress(), timeline.readout()))
+        {
+            self.queue_progress_bar(progress, &readout, 24.0);
+        }
+        if let Some(stream) = self.mesh_stream.as_ref().filter(|stream| !stream.is_done()) {
+            let (progress, readout) = (stream.progress(), stream.readout());
+            self.queue_progress_bar(progress, &readout, 64.0);
+        }
+        if self.overlay.enabled {
+            let lines = self.stats.lines();
+            #[cfg(debug_assertions)]
+            let lines = [
+                lines,
+                self.debug_channel
+                    .iter()
+                    .flat_map(DebugChannel::lines)
+                    .collect(),
+            ]
+            .concat();
+            let text = lines.join("\n");
+            self.overlay.panel(self.stats_anchor, (8.0, 8.0), &text);
+            self.queue_log_lines();
+        }
+        if self.console.enabled {
+            self.console.queue(&mut self.overlay);
+        }
+        self.draw_passes(&mut frame, view, alpha, true);
+
+        drop(record);
+        self.stats.triangles = frame.counts.triangles;
+        self.stats.mesh_draws = frame.counts.draws;
+        self.stats.buffer_binds = frame.counts.buffer_binds;
+        self.stats.sprites = frame.counts.sprites;
+        self.stats.sprite_batches = frame.counts.sprite_batches;
+        if let (Some(pipeline_stats), Some(queries)) =
+            (&mut self.pipeline_stats, frame.statistics.take())
+        {
+            pipeline_stats.resolve(&mut frame.encoder, queries);
+        }
+        #[cfg(debug_assertions)]
+        if let Some(debug_channel) = &mut self.debug_channel {
+            debug_channel.end_frame(&mut frame.encoder);
+        }
+        // Last, so it has the overlay and everything else on it
+        let readback = self.screenshot.take().map(|path| {
+            let readback = match frame.surface_texture().cloned() {
+                Some(texture) => screenshot::Readback::copy(
+                    &self.device,
+                    &mut self.pool,
+                    &mut frame.encoder,
+                    &texture,
+                    "Screenshot",
+                ),
+                None => Err("nothing on screen to take it of".to_owned()),
+            };
+            (path, readback)
+        });
+        let submit = tracing::info_span!("submit").entered();
+        let pipelines = std::mem::take(&mut frame.pipelines);
+        let dump = frame.dump.take();
+        frame.finish(&self.queue);
+        if let Some((path, readback)) = readback {
+            self.save_screenshot(path, readback);
+        }
+        if let (Some(path), Some(dump)) = (self.frame_dump.take(), dump) {
+            self.save_frame_dump(&path, dump);
+        }
+        self.watchdog
+            .submitted(&self.queue, self.stats.frame_index, pipelines);
+        crash::set_frame(None);
+        if let Some(pipeline_stats) = &mut self.pipeline_stats {
+            pipeline_stats.collect(&mut self.maintain);
+            self.stats.passes.clone_from(&pipeline_stats.latest);
+            self.stats.surface_pixels =
+                u64::from(self.config.width) * u64::from(self.config.height);
+        }
+        #[cfg(debug_assertions)]
+        if let Some(debug_channel) = &mut self.debug_channel {
+            debug_channel.collect(&mut self.maintain);
+        }
+        self.maintain.step(&self.device);
+        if self.sync_after_present {
+            self.device.poll(wgpu::Maintain::Wait);
+        }
+        drop(submit);
+        self.pool.end_frame(&self.queue);
+        self.stats.placeholder_draws = self.render_pipelines.take_placeholder_uses();
+        self.stats.pipelines_building = self.render_pipelines.pending_count();
+        // Placeholders are drawn until the real pipelines are in, look again soon
+        if self.stats.pipelines_building > 0 {
+            self.request_redraw_after(PENDING_REDRAW);
+        }
+        self.stats.scene_memory = self.memory.scene_bytes();
+        self.stats.end_frame(self.memory.report());
+        crash::snapshot(
+            self.stats.lines(),
+            self.scene_path.display().to_string(),
+            self.assets.list(),
+        );
+    }
+
+    // The frame's passes, put in order by a RenderGraph from what each reads and writes: the
+    // view (through the post chain when it's on), what goes over it in world space, the
+    // inset and, with `ui`, everything in screen space on top. Passes whose output nothing
+    // ends up on the swapchain from are left out, like the inset's view when it's hidden.
+    // Without `ui` it's everything of a frame that a supersampled screenshot has
+    fn draw_passes(&mut self, frame: &mut Frame, view: &View, alpha: f32, ui: bool) {
+        let (scene_target, inset_target) = (self.post.scene_target(), self.inset.target);
+        let depth = self.deferred.depth_target();
+        // Into the HDR scene target rather than straight onto the swapchain
+        let offscreen = match view {
+            View::Exposure => true,
+            View::Deferred => self.post.is_active(),
+            _ => false,
+        };
+        let post = offscreen && self.post.is_active();
+        let gizmos = matches!(view, View::Deferred) && self.gizmos.enabled;
+        let inset = ui && matches!(view, View::Primitives) && self.inset.enabled;
+        let crash = self.crash_test && self.stats.frame_index == crash::TEST_FRAME;
+        let swapchain = Resource::Swapchain;
+
+        let state = RefCell::new(self);
+        let mut graph = RenderGraph::new();
+        let view_output = if offscreen {
+            Resource::Target(scene_target)
+        } else {
+            swapchain
+        };
+        // The deferred view leaves its depth behind for the gizmos
+        let view_writes = if matches!(view, View::Deferred) {
+            vec![view_output, depth.into()]
+        } else {
+            vec![view_output]
+        };
+        graph.add_pass("View", &[], &view_writes, |frame, _| {
+            state.borrow_mut().draw_view(frame, view, alpha);
+            if crash {
+                panic!("--crash-test, panicking mid-frame on purpose");
+            }
+            Ok(())
+        });
+        if post {
+            graph.add_pass(
+                "Post Chain",
+                &[scene_target.into()],
+                &[swapchain],
+                |frame, _| {
+                    let state = &mut **state.borrow_mut();
+                    report(state.post.apply(
+                        &state.device,
+                        &state.queue,
+                        frame,
+                        &state.targets,
+                        &state.render_pipelines,
+                        &mut state.pool,
+                    ));
+                    Ok(())
+                },
+            );
+        } else if offscreen {
+            // Without the chain the bright side just clips
+            graph.add_pass(
+                "Scene Blit",
+                &[scene_target.into()],
+                &[swapchain],
+                |frame, _| {
+                    let state = state.borrow();
+                    state.blitter.blit_to_swapchain(
+                        &state.device,
+                        frame,
+                        &state.targets,
+                        scene_target,
+                    );
+                    Ok(())
+                },
+            );
+        }
+        if gizmos {
+            graph.add_pass(
+                "Gizmos",
+                &[swapchain, depth.into()],
+                &[swapchain],
+                |frame, _| {
+                    report(state.borrow_mut().draw_gizmos(frame));
+                    Ok(())
+                },
+            );
+        }
+        graph.add_pass("World Overlays", &[swapchain], &[swapchain], |frame, _| {
+            state.borrow_mut().draw_world_overlays(frame, view);
+            Ok(())
+        });
+        graph.add_pass("Inset View", &[], &[inset_target.into()], |frame, _| {
+            report(state.borrow_mut().draw_inset_view(frame));
+            Ok(())
+        });
+        if inset {
+            graph.add_pass(
+                "Inset",
+                &[swapchain, inset_target.into()],
+                &[swapchain],
+                |frame, _| {
+                    state.borrow().composite_inset(frame);
+                    Ok(())
+                },
+            );
+        }
+        if ui {
+            graph.add_pass("UI", &[swapchain], &[swapchain], |frame, _| {
+                state.borrow_mut().draw_ui(frame);
+                Ok(())
+            });
+        }
+        graph.output(swapchain);
+        if let Err(e) = graph.execute(frame) {
+            log::error!("{e}");
+        }
+    }
+
+    fn draw_view(&mut self, frame: &mut Frame, view: &View, alpha: f32) {
+        let result = match view {
+            View::Shapes { toggle, .. } => self.draw_shapes(frame, *toggle),
+            View::Mrt(target) => self.draw_mrt(frame, *target),
+            View::Primitives => self.draw_primitives(frame),
+            View::Deferred => self.draw_deferred(frame, alpha),
+            View::Exposure => self.draw_exposure(frame),
+            View::Sprites => self.draw_sprites(frame),
+            View::Tiles => self.draw_tiles(frame),
+            View::Fullscreen(pipeline) => self.draw_fullscreen(frame, pipeline),
+            View::Loading { done, total } => self.draw_loading(frame, *done, *total),
+            View::Splash => self.draw_splash(frame),
+        };
+        report(result);
+    }
+
+    // What the view queued and the app draws over it with the same camera
+    fn draw_world_overlays(&mut self, frame: &mut Frame, view: &View) {
+        // World space lines and rects first, the grid stays under the scene
+        self.draw_immediate(frame, Space::World);
+        // Whatever the view queued with draw_line/draw_circle
+        if let Err(e) = self.shapes.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            &self.scene_shapes,
+            &self.camera2d,
+            (self.config.width, self.config.height),
+        ) {
+            log::error!("{e}");
+        }
+        // Same camera, what the view queued with draw_text_world
+        #[cfg(feature = "text")]
+        if let Err(e) = self.sdf_text.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            &self.memory,
+            &self.camera2d,
+            (self.config.width, self.config.height),
+        ) {
+            log::error!("{e}");
+        }
+
+        // Right away rather than with the other screen space shapes, the inset goes on top
+        if view.uses_camera2d() && self.queue_letterbox(frame) {
+            self.draw_immediate(frame, Space::Screen);
+        }
+    }
+
+    fn draw_ui(&mut self, frame: &mut Frame) {
+        self.draw_immediate(frame, Space::Screen);
+        // Under the debug overlay, so panels stay readable
+        #[cfg(feature = "text")]
+        if let Err(e) = self.text.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            &self.memory,
+            (self.config.width, self.config.height),
+        ) {
+            log::error!("{e}");
+        }
+        // Nothing queued (rulers off, overlay off) draws nothing
+        if let Err(e) = self.overlay.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            (self.config.width, self.config.height),
+        ) {
+            log::error!("{e}");
+        }
+    }
+
+    // Shift+F12: the view without the UI, drawn `samples` times with the cameras shifted by
+    // a different fraction of a pixel each time and at `scale` times the window's size, then
+    // averaged and shrunk back down with a tent filter on the CPU. All in one go, each
+    // sample is read back before the next is drawn. The size, the cameras and the clock are
+    // put back afterwards
+    fn capture_supersampled(&mut self, view: &View, alpha: f32, path: std::path::PathBuf) {
+        let window = (self.config.width, self.config.height);
+        let (samples, mut scale) = self.supersample;
+        let mut size = (window.0 * scale, window.1 * scale);
+        if self.capabilities.clamp_size(size) != size {
+            log::warn!("{scale}x the window is more than the GPU takes, supersampling at 1x");
+            (scale, size) = (1, window);
+        }
+        (self.config.width, self.config.height) = size;
+        self.targets.resize(&self.device, size);
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Supersample"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_views.scene,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        // Nothing to preserve in a fresh texture
+        let background = match self.background(view) {
+            Background::Clear(color) => Background::Clear(color),
+            _ => Background::Clear(Color::BLACK),
+        };
+        // Under Extend a bigger window shows more world, this shows the same at any size
+        let camera2d = self.camera2d;
+        if camera2d.resize == ResizePolicy::Extend {
+            self.camera2d.resize =
+                ResizePolicy::Stretch(Vec2::new(window.0 as f32, window.1 as f32));
+        }
+        let srgb = self.surface_views.scene.is_srgb();
+
+        self.supersampling = true;
+        let mut run = SupersampleRun {
+            stretched: self.camera2d,
+            state: self,
+            view,
+            alpha,
+            texture: &texture,
+            background,
+        };
+        let result = supersample::capture(&mut run, size, samples, scale, srgb);
+        self.supersampling = false;
+        self.camera2d = camera2d;
+        (self.config.width, self.config.height) = window;
+        self.targets.resize(&self.device, window);
+        self.request_redraw();
+
+        match result.and_then(|image| image_file::save(&image, &path)) {
+            Ok(()) => {
+                println!(
+                    "Supersampled screenshot ({samples} samples at {scale}x) saved to {}",
+                    path.display()
+                );
+                self.last_screenshot = Some(path);
+            }
+            Err(e) => log::warn!("No supersampled screenshot, {e}"),
+        }
+    }
+
+    // The end of a frame that took a screenshot. Waits on the GPU, screenshots are rare
+    fn save_screenshot(
+        &mut self,
+        path: std::path::PathBuf,
+        readback: Result<screenshot::Readback, String>,
+    ) {
+        let saved = readback
+            .and_then(|readback| readback.read(&self.device, screenshot::TIMEOUT))
+            .and_then(|image| image_file::save(&image, &path))
+            .map(|()| path);
+        match &saved {
+            Ok(path) => {
+                println!("Screenshot saved to {}", path.display());
+                self.last_screenshot = Some(path.clone());
+            }
+            Err(e) => log::warn!("No screenshot, {e}"),
+        }
+        #[cfg(feature = "remote")]
+        for command in self.remote_screenshots.drain(..) {
+            let inline = command.inline;
+            command.answer(
+                saved
+                    .clone()
+                    .and_then(|path| remote::Answer::screenshot(path, inline)),
+            );
+        }
+    }
+
+    // Writes what dump_frame asked for, pointing at its screenshot when that got saved
+    fn save_frame_dump(&self, path: &std::path::Path, mut dump: FrameDump) {
+        dump.globals = Some(self.globals.data);
+        dump.screenshot = self
+            .last_screenshot
+            .clone()
+            .filter(|screenshot| *screenshot == path.with_extension("png"));
+        match dump.save(path) {
+            Ok(()) => println!("Frame dump saved to {}", path.display()),
+            Err(e) => log::warn!("No frame dump, {e}"),
+        }
+    }
+
+    // Draws on the next loop iteration even if nothing else changed. Anything animating
+    // calls this every update it moves in
+    fn request_redraw(&mut self) {
+        self.redraw.now();
+    }
+
+    // For something that changes on its own but not every frame, like work in the background
+    fn request_redraw_after(&mut self, delay: std::time::Duration) {
+        self.redraw.after(delay);
+    }
+
+    // The pentagon on top of a cleared background
+    fn draw_shapes(&self, frame: &mut Frame, toggle: bool) -> Result<(), ForayError> {
+        let mut pass = frame.pass(
+            "Render Pass",
+            &[(ColorTarget::Swapchain, frame.background.color())],
+            &self.targets,
+        );
+
+        pass.set_pipeline(&self.render_pipelines, shape_pipeline(toggle))?;
+        // Behind the pentagon, as much of it as has landed
+        if let Some(stream) = &self.mesh_stream {
+            pass.draw_mesh(&stream.mesh)?;
+        }
+        pass.draw_mesh(&self.morph.mesh)?;
+        // The outline is the pentagon's, only right while nothing is morphed
+        if self.morph_tween.value() == 0.0 {
+            pass.set_pipeline_for(&self.render_pipelines, "default", &self.pentagon_outline)?;
+            pass.draw_mesh(&self.pentagon_outline)?;
+        }
+        Ok(())
+    }
+
+    // The pentagon into every MRT target at once, then one of them shown on screen
+    fn draw_mrt(&self, frame: &mut Frame, view: usize) -> Result<(), ForayError> {
+        let attachments: Vec<_> = self
+            .mrt
+            .targets
+            .iter()
+            .map(|&target| {
+                (
+                    ColorTarget::Offscreen(target),
+                    frame.background.load(Color::BLACK, DEBUG_MAGENTA),
+                )
+            })
+            .collect();
+
+        let mut pass = frame.pass("MRT Pass", &attachments, &self.targets);
+        pass.set_pipeline(&self.render_pipelines, "mrt")?;
+        pass.draw_mesh(&self.pentagon)?;
+        drop(pass);
+
+        self.blitter
+            .blit_to_swapchain(&self.device, frame, &self.targets, self.mrt.targets[view]);
+        Ok(())
+    }
+
+    // Grid, a fan of hairlines and some circles to eyeball the anti-aliasing, scroll zooms
+    fn draw_primitives(&self, frame: &mut Frame) -> Result<(), ForayError> {
+        drop(frame.pass(
+            "Clear Pass",
+            &[(ColorTarget::Swapchain, frame.background.color())],
+            &self.targets,
+        ));
+
+        let viewport = (self.config.width, self.config.height);
+        let min = self
+            .camera2d
+            .screen_to_world(Vec2::new(0.0, viewport.1 as f32), viewport);
+        let max = self
+            .camera2d
+            .screen_to_world(Vec2::new(viewport.0 as f32, 0.0), viewport);
+        // Same lines dragged items snap to
+        let grid = self.overlay.theme.grid_major;
+        for x in self.snap.lines(min.x, max.x, &self.camera2d) {
+            frame.world_line(Vec2::new(x, min.y), Vec2::new(x, max.y), grid);
+        }
+        for y in self.snap.lines(min.y, max.y, &self.camera2d) {
+            frame.world_line(Vec2::new(min.x, y), Vec2::new(max.x, y), grid);
+        }
+        if self.snap.enabled {
+            self.queue_snap_cursor(frame);
+        }
+
+        for i in 0..24 {
+            let angle = i as f32 / 24.0 * std::f32::consts::TAU;
+            let dir = Vec2::from_angle(angle);
+            frame.draw_line(
+                dir * 40.0,
+                dir * 220.0,
+                Width::Pixels(0.5 + i as f32 * 0.25),
+                Colors::WHITE,
+            );
+        }
+        frame.draw_circle(
+            Vec2::ZERO,
+            30.0,
+            Stroke::Fill,
+            RgbaColor::rgba(0.9, 0.4, 0.2, 1.0),
+        );
+        frame.draw_circle(
+            Vec2::ZERO,
+            250.0,
+            Stroke::Outline(Width::Pixels(1.0)),
+            RgbaColor::rgba(0.0, 1.0, 0.0, 1.0),
+        );
+        frame.draw_circle(
+            Vec2::ZERO,
+            280.0,
+            Stroke::Outline(Width::World(8.0)),
+            RgbaColor::rgba(0.0, 0.0, 1.0, 1.0),
+        );
+
+        // Uploaded already by upload_scene_shapes
+        frame.draw_scene_shapes();
+        if let Some(index) = self.transform_gizmo.target {
+            let item = &self.scene.items[index];
+            if !item.removing {
+                self.transform_gizmo.queue(
+                    frame,
+                    &self.overlay.theme,
+                    &item.transform,
+                    &self.camera2d,
+                );
+            }
+        }
+        #[cfg(feature = "text")]
+        if self.name_tags {
+            self.queue_name_tags(frame);
+        }
+        #[cfg(feature = "text")]
+        self.queue_title(frame, "Primitives");
+        Ok(())
+    }
+
+    // What changed in the scene's shapes since they were last drawn into scene_shapes. The
+    // hovered item gets tinted, so hovering marks the item it left and the one it's on
+    fn upload_scene_shapes(&mut self) {
+        let hovered = self.pick_at_cursor();
+        if hovered != self.scene_hovered {
+            for index in [self.scene_hovered, hovered].into_iter().flatten() {
+                self.scene.dirty.mark(index);
+            }
+            self.scene_hovered = hovered;
+        }
+        let (shapes, spans) = self.scene.shapes_with_spans(&self.scene_outlines, hovered);
+        self.scene_shapes.update(
+            &self.device,
+            &self.queue,
+            &self.memory,
+            &shapes,
+            &spans,
+            &mut self.scene.dirty,
+        );
+        self.stats.scene_upload = Some(self.scene_shapes.uploaded);
+    }
+
+    // Centered at the top, measured first to find where it starts
+    #[cfg(feature = "text")]
+    fn queue_title(&self, frame: &mut Frame, title: &str) {
+        let size_px = self.overlay.logical(28.0);
+        let width = self.font.measure(size_px, title).width;
+        let position = Vec2::new(
+            (self.config.width as f32 - width) * 0.5,
+            self.overlay.logical(20.0),
+        );
+        frame.draw_text(&self.font, size_px, position, title, Colors::WHITE);
+    }
+
+    // Item names centered above their outlines, in world units so they zoom with the scene
+    #[cfg(feature = "text")]
+    fn queue_name_tags(&self, frame: &mut Frame) {
+        let size = 18.0;
+        for (index, (item, outline)) in self
+            .scene
+            .items
+            .iter()
+            .zip(&self.scene_outlines)
+            .enumerate()
+        {
+            let at = item.transform.translation;
+            let top = outline
+                .iter()
+                .map(|&p| item.transform.transform_point(p).y)
+                .fold(at.y + 10.0, f32::max);
+            let width = self.sdf_font.measure(size, &item.name).width;
+            let opacity = f64::from(self.scene.opacity(index));
+            frame.draw_text_world(
+                &self.sdf_font,
+                size,
+                Vec2::new(at.x - width * 0.5, top + size * 0.5),
+                &item.name,
+                self.overlay.theme.text.faded(opacity),
+                Some((self.overlay.theme.panel.faded(opacity), 2.0)),
+            );
+        }
+    }
+
+    // Bars over the window outside the world's rect under ResizePolicy::Letterbox, false
+    // when there are none
+    fn queue_letterbox(&self, frame: &mut Frame) -> bool {
+        let screen = (self.config.width, self.config.height);
+        let (x, y, width, height) = self.camera2d.content_rect(screen);
+        let (screen_width, screen_height) = (screen.0 as f32, screen.1 as f32);
+        let bar = RgbaColor::rgba(0.0, 0.0, 0.0, 1.0);
+        if x >= 1.0 {
+            frame.rect(0.0, 0.0, x, screen_height, bar);
+            frame.rect(x + width, 0.0, screen_width - x - width, screen_height, bar);
+        }
+        if y >= 1.0 {
+            frame.rect(0.0, 0.0, screen_width, y, bar);
+            frame.rect(
+                0.0,
+                y + height,
+                screen_width,
+                screen_height - y - height,
+                bar,
+            );
+        }
+        x >= 1.0 || y >= 1.0
+    }
+
+    // The scene through the inset's camera into its own target. The part the main camera
+    // sees shows up in it as a white rectangle
+    fn draw_inset_view(&mut self, frame: &mut Frame) -> Result<(), ForayError> {
+        let screen = (self.config.width, self.config.height);
+        let mut shapes = self.scene.shapes(&self.scene_outlines, None);
+        let (min, max) = self.camera2d.visible(screen);
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+        for (i, &p0) in corners.iter().enumerate() {
+            let p1 = corners[(i + 1) % corners.len()];
+            shapes.push(ShapeInstance::line(
+                p0,
+                p1,
+                Width::Pixels(1.0),
+                self.overlay.theme.text,
+            ));
+        }
+
+        self.shapes.draw_into(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            &shapes,
+            &self.inset.camera,
+            self.targets.size(self.inset.target),
+            (
+                ColorTarget::Offscreen(self.inset.target),
+                wgpu::LoadOp::Clear(Color {
+                    r: 0.05,
+                    g: 0.05,
+                    b: 0.07,
+                    a: 1.0,
+                }),
+            ),
+        )
+    }
+
+    // The inset's target onto the swapchain, inside the border
+    fn composite_inset(&self, frame: &mut Frame) {
+        let screen = (self.config.width, self.config.height);
+        let rect = self.inset.rect(&self.targets, screen);
+        self.blitter
+            .blit_to_rect(&self.device, frame, &self.targets, self.inset.target, rect);
+    }
+
+    fn queue_inset_border(&mut self) {
+        let screen = (self.config.width, self.config.height);
+        let (x, y, width, height) = self.inset.rect(&self.targets, screen);
+        let border = 2.0;
+        let color = self.overlay.theme.text;
+        let across = width + 2.0 * border;
+        self.overlay
+            .rect((x - border, y - border, across, border), color);
+        self.overlay
+            .rect((x - border, y + height, across, border), color);
+        self.overlay.rect((x - border, y, border, height), color);
+        self.overlay.rect((x + width, y, border, height), color);
+    }
+
+    // Scene item under the cursor, unless the inset covers that spot
+    fn pick_at_cursor(&self) -> Option<usize> {
+        let (x, y) = self.cursor;
+        let screen = (self.config.width, self.config.height);
+        let cursor = Vec2::new(x as f32, y as f32);
+        if self.inset.covers(&self.targets, screen, cursor) {
+            return None;
+        }
+        self.scene
+            .pick(&self.scene_outlines, &self.item_grid, self.cursor_world())
+    }
+
+    // Saved to `path` at the end of the next frame
+    fn take_screenshot(&mut self, path: std::path::PathBuf) {
+        self.screenshot = Some(path);
+        self.request_redraw();
+    }
+
+    // The next frame as JSON to `path`, its screenshot next to it with a .png extension
+    fn dump_frame(&mut self, path: std::path::PathBuf) {
+        self.screenshot = Some(path.with_extension("png"));
+        self.frame_dump = Some(path);
+        self.request_redraw();
+    }
+
+    // Drawn and saved by capture_supersampled once the next frame is done
+    fn take_supersampled(&mut self, path: std::path::PathBuf) {
+        self.supersample_request = Some(path);
+        self.request_redraw();
+    }
+
+    // Puts the scene items where they are now into the picking grid
+    fn index_items(&mut self) {
+        self.item_grid
+            .rebuild(self.scene.item_bounds(&self.scene_outlines));
+    }
+
+    // Gizmo handle of the selected item under the cursor. Checked before pick_at_cursor,
+    // handles reach over other items
+    fn handle_at_cursor(&self) -> Option<Handle> {
+        let (x, y) = self.cursor;
+        let screen = (self.config.width, self.config.height);
+        let cursor = Vec2::new(x as f32, y as f32);
+        if self.inset.covers(&self.targets, screen, cursor) {
+            return None;
+        }
+        let item = &self.scene.items[self.transform_gizmo.target?];
+        self.transform_gizmo
+            .hit(&item.transform, &self.camera2d, screen, cursor)
+    }
+
+    // Right click in the primitives view. A handle of the selected item, otherwise whatever
+    // item is under the cursor gets selected and moved. Empty space clears the selection.
+    // True when a drag started
+    fn begin_drag(&mut self) -> bool {
+        let handle = self.handle_at_cursor().or_else(|| {
+            self.transform_gizmo.target = self.pick_at_cursor();
+            self.transform_gizmo.target.map(|_| Handle::Move)
+        });
+        let (Some(handle), Some(index)) = (handle, self.transform_gizmo.target) else {
+            return false;
+        };
+        let start = self.scene.items[index].transform;
+        let grab = self.cursor_world();
+        self.transform_gizmo.begin(handle, grab, start);
+        true
+    }
+
+    // The cursor moved while dragging. `continuous` snaps from the unsnapped position,
+    // `constrain` keeps to one axis or to steps
+    fn drag_to(&mut self, continuous: bool, constrain: bool) {
+        let world = self.cursor_world();
+        let target = self.transform_gizmo.target;
+        let dragged = self.transform_gizmo.drag(world, constrain);
+        if let (Some(index), Some(mut transform)) = (target, dragged) {
+            if continuous && self.transform_gizmo.dragging() == Some(Handle::Move) {
+                transform.translation = self.snap.snap(transform.translation, &self.camera2d);
+            }
+            if let Err(e) = self.scene.set_transform(index, transform) {
+                log::warn!("{e}");
+            }
+        }
+    }
+
+    // The right button came up. The whole drag is one step to undo
+    fn end_drag(&mut self) {
+        let ended = self.transform_gizmo.end();
+        let (Some((handle, before)), Some(index)) = (ended, self.transform_gizmo.target) else {
+            return;
+        };
+        let mut after = self.scene.items[index].transform;
+        if handle == Handle::Move && self.snap.enabled {
+            after.translation = self.snap.snap(after.translation, &self.camera2d);
+            if let Err(e) = self.scene.set_transform(index, after) {
+                log::warn!("{e}");
+            }
+            // What the scene actually took, the scale may have been clamped
+            after = self.scene.items[index].transform;
+        }
+        if after != before {
+            self.history.record(SceneCommand::SetTransform {
+                item: self.scene.items[index].id,
+                before,
+                after,
+            });
+        }
+        self.request_redraw();
+    }
+
+    // Fullscreen triangle driven entirely by the fragment shader, no vertex buffer bound
+    fn draw_fullscreen(&mut self, frame: &mut Frame, pipeline: &str) -> Result<(), ForayError> {
+        let resolution = (self.config.width, self.config.height);
+        let mouse = self.cursor;
+        let accumulating = self.accumulator.enabled;
+        if accumulating {
+            self.accumulator
+                .track(pipeline, resolution, mouse, &self.targets);
+        }
+        // Frozen so the picture holds still while it converges, or while the samples of a
+        // supersampled screenshot are drawn
+        self.globals.paused = accumulating || self.supersampling;
+        self.globals.data.sample = if accumulating {
+            self.accumulator.samples()
+        } else {
+            0
+        };
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &self.audio {
+            self.globals.data.audio = audio.bands();
+        }
+        self.globals.tick(&self.queue, resolution, mouse);
+
+        if !accumulating {
+            return frame.fullscreen_pass(
+                "Fullscreen Pass",
+                ColorTarget::Swapchain,
+                frame.background.color(),
+                &self.targets,
+                &self.render_pipelines,
+                pipeline,
+                &[&self.globals.bind_group],
+            );
+        }
+
+        // Nothing new to add once converged, the average is just shown again
+        if let Some((weight, first)) = self.accumulator.next_sample() {
+            let load = if first {
+                wgpu::LoadOp::Clear(Color::TRANSPARENT)
+            } else {
+                wgpu::LoadOp::Load
+            };
+            let mut pass = frame.pass(
+                "Accumulate Pass",
+                &[(ColorTarget::Offscreen(self.accumulator.target()), load)],
+                &self.targets,
+            );
+            pass.set_pipeline(&self.render_pipelines, &format!("{pipeline}/accumulate"))?;
+            pass.raw.set_blend_constant(Color {
+                r: weight,
+                g: weight,
+                b: weight,
+                a: weight,
+            });
+            pass.raw.set_bind_group(0, &self.globals.bind_group, &[]);
+            pass.raw.draw(0..3, 0..1);
+        }
+        self.blitter.blit_to_swapchain(
+            &self.device,
+            frame,
+            &self.targets,
+            self.accumulator.target(),
+        );
+        Ok(())
+    }
+
+    // Geometry pass into the g-buffer, then lighting composited onto the swapchain
+    fn draw_deferred(&mut self, frame: &mut Frame, alpha: f32) -> Result<(), ForayError> {
+        let output = if self.post.is_active() {
+            ColorTarget::Offscreen(self.post.scene_target())
+        } else {
+            ColorTarget::Swapchain
+        };
+        self.deferred.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            output,
+            (self.config.width, self.config.height),
+            alpha,
+        )
+    }
+
+    // Over the deferred view, after the post chain, tested against its depth
+    fn draw_gizmos(&mut self, frame: &mut Frame) -> Result<(), ForayError> {
+        let aspect = self.config.width as f32 / self.config.height as f32;
+        self.deferred.queue_gizmos(frame, &self.overlay.theme);
+        self.gizmos.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            &self.deferred.gizmo_camera(aspect),
+            self.deferred.depth_target(),
+            (self.config.width, self.config.height),
+        )
+    }
+
+    // Always into the HDR scene target, the exposure effect tonemaps it when it's on
+    fn draw_exposure(&mut self, frame: &mut Frame) -> Result<(), ForayError> {
+        let target = self.post.scene_target();
+        self.hdr_scene.draw(
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            target,
+            &self.camera2d,
+            (self.config.width, self.config.height),
+        )
+    }
+
+    fn draw_sprites(&mut self, frame: &mut Frame) -> Result<(), ForayError> {
+        let Some(stress) = &self.sprite_stress else {
+            return Ok(());
+        };
+        self.sprites.draw(
+            &self.device,
+            &self.queue,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            &stress.images,
+            &stress.sprites,
+            &self.camera2d,
+            (self.config.width, self.config.height),
+        )
+    }
+
+    // Replaces the sprite stress test, keeping the old one if the new one fails
+    fn build_sprite_stress(&mut self, count: usize, policy: TilePolicy) {
+        match SpriteStress::new(
+            &self.device,
+            &self.queue,
+            &self.memory,
+            &self.sprites,
+            count,
+            policy,
+        ) {
+            Ok(stress) => self.sprite_stress = Some(stress),
+            Err(e) => log::error!("{e}"),
+        }
+    }
+
+    fn draw_tiles(&mut self, frame: &mut Frame) -> Result<(), ForayError> {
+        let Some(map) = &mut self.tile_map else {
+            return Ok(());
+        };
+        let result = self.tiles.draw(
+            &self.device,
+            &self.queue,
+            &self.memory,
+            frame,
+            &self.targets,
+            &self.render_pipelines,
+            &mut self.pool,
+            map,
+            &self.camera2d,
+            (self.config.width, self.config.height),
+        );
+        self.stats.tiles = Some(map.counts);
+        result
+    }
+
+    // An empty map over the generated tile set
+    fn new_tile_map(&self, name: &str) -> Result<TileMap, ForayError> {
+        let images = tilemap::tile_images(&self.device, &self.queue, &self.memory, &self.sprites)?;
+        Ok(TileMap::new(name, images, tilemap::TILE_SIZE))
+    }
+
+    // Replaces the tile map with a `side` x `side` one, keeping the old one if it fails
+    fn build_tile_stress(&mut self, side: i32) {
+        let built = self.new_tile_map("stress").and_then(|mut map| {
+            let tiles = map.apply(&TileLayout::stress(side))?;
+            Ok((map, tiles))
+        });
+        match built {
+            Ok((map, tiles)) => {
+                println!(
+                    "Tile map stress: {tiles} tiles in {} chunks",
+                    map.chunk_count()
+                );
+                self.tile_map = Some(map);
+            }
+            Err(e) => log::error!("{e}"),
+        }
+    }
+
+    // Replaces the mesh stream there is, in an arena just big enough for the new one. The
+    // arena goes away with the stream, finished or not
+    fn start_stream(&mut self, name: &str, source: Box<dyn ChunkSource>) {
+        self.mesh_stream = None;
+        let (vertices, indices) = source.totals();
+        let largest = (u64::from(vertices) * source.stride()).max(u64::from(indices) * 4);
+        let limit = self.device.limits().max_buffer_size;
+        if largest > limit {
+            log::warn!(
+                "Not streaming {name}, it needs a {largest} byte buffer and the limit is {limit}"
+            );
+            return;
+        }
+        let arena = MeshArena::new(
+            &self.device,
+            &self.memory,
+            name,
+            &Vertex::desc(),
+            vertices,
+            indices,
+        );
+        let topology = wgpu::PrimitiveTopology::TriangleList;
+        match MeshStream::new(&arena, name, topology, source, self.stream_budget) {
+            Ok(stream) => {
+                println!("Streaming {}", stream.readout());
+                self.mesh_stream = Some(stream);
+            }
+            Err(e) => log::error!("{e}"),
+        }
+    }
+
+    // Drops a stream that hasn't finished, with what it had uploaded and what it still had
+    // to read. A finished one stays
+    fn cancel_stream(&mut self, why: &str) {
+        if let Some(stream) = self.mesh_stream.take_if(|stream| !stream.is_done()) {
+            println!("{why}, cancelled streaming {}", stream.readout());
+        }
+    }
+
+    // This frame's share of the mesh stream, uploaded before anything is recorded so the
+    // frame draws what landed
+    fn pump_stream(&mut self) {
+        let Some(stream) = &mut self.mesh_stream else {
+            return;
+        };
+        if stream.is_done() {
+            return;
+        }
+        match stream.pump(&self.queue) {
+            Ok(true) => println!(
+                "Streamed {} in {} frames, the longest upload took {:.2?}",
+                stream.readout(),
+                stream.frames,
+                stream.longest
+            ),
+            Ok(false) => {}
+            Err(e) => {
+                log::error!("{e}");
+                self.mesh_stream = None;
+            }
+        }
+        self.request_redraw();
+    }
+
+    // Onto the tile map there is, or a new one
+    fn load_tiles(&mut self, path: &std::path::Path) -> Result<usize, ForayError> {
+        let layout = TileLayout::read(path)?;
+        let mut map = match self.tile_map.take() {
+            Some(map) => map,
+            None => self.new_tile_map(&path.display().to_string())?,
+        };
+        let result = map.apply(&layout);
+        self.tile_map = Some(map);
+        result
+    }
+
+    // From where the stroke last painted to the tile under the cursor, with the brush or
+    // clearing. Starts a stroke when there's none
+    fn paint_tiles(&mut self, erase: bool) {
+        let world = self.cursor_world();
+        let (Some(map), Some(brush)) = (&mut self.tile_map, self.tile_brush) else {
+            return;
+        };
+        let tile = map.tile_at(world);
+        let from = self.tile_stroke.unwrap_or(tile);
+        if let Err(e) = map.paint_line(from, tile, (!erase).then_some(brush)) {
+            log::warn!("{e}");
+        }
+        self.tile_stroke = Some(tile);
+    }
+
+    fn inspector_rows(&self) -> Vec<InspectorRow> {
+        let mut meshes = vec![&self.pentagon, &self.pentagon_outline, &self.morph.mesh];
+        meshes.extend(self.deferred.meshes());
+        meshes.extend(self.mesh_stream.as_ref().map(|stream| &stream.mesh));
+        inspector::rows(
+            &self.render_pipelines,
+            &meshes,
+            &self.memory,
+            &self.targets,
+            &self.scene,
+        )
+    }
+
+    // Enter in the inspector: a scene item fades out or back in and becomes the active
+    // one, a pipeline gets assigned to the active item
+    fn inspector_enter(&mut self) {
+        match self.inspector.selected().cloned() {
+            Some(InspectorKey::SceneItem(name)) => {
+                if let Some(index) = self.scene.find(&name) {
+                    let item = self.scene.items[index].id;
+                    self.edit(SceneCommand::ToggleHidden(item));
+                    self.inspector.active_item = Some(name);
+                }
+            }
+            Some(InspectorKey::Pipeline(pipeline)) => {
+                let active = self.inspector.active_item.clone();
+                match active.as_deref().and_then(|name| self.scene.find(name)) {
+                    Some(index) => {
+                        let item = &self.scene.items[index];
+                        println!("{} now uses {pipeline}", item.name);
+                        self.edit(SceneCommand::SetPipeline {
+                            item: item.id,
+                            before: item.pipeline.clone(),
+                            after: pipeline,
+                        });
+                    }
+                    None => log::warn!("Pick a scene item with Enter first"),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Recent warnings and errors in the bottom left corner, newest at the bottom
+    fn queue_log_lines(&mut self) {
+        let now = std::time::Instant::now();
+        let records = log_sink::recent(now);
+        let (_, line_height) = self.overlay.measure("#");
+        let block = (0.0, records.len() as f32 * line_height);
+        let (x, mut y) = self.overlay.anchored(Anchor::BottomLeft, (8.0, 8.0), block);
+        for record in records {
+            let alpha = record.opacity(now);
+            let line = record.line();
+            let theme = self.overlay.theme;
+            let color = match record.level {
+                log::Level::Error => theme.error,
+                _ => theme.warning,
+            };
+            let (width, _) = self.overlay.measure(&line);
+            self.overlay.rect(
+                (x, y, width, line_height),
+                theme.panel.faded(f64::from(alpha)),
+            );
+            let color = color.faded(f64::from(alpha));
+            self.overlay.text((x, y), color, &line);
+            y += line_height;
+        }
+    }
+
+    // A bar along the bottom, `above` up from it, with the readout over the bar. The
+    // timeline's scrub bar and the mesh stream's progress
+    fn queue_progress_bar(&mut self, progress: f32, readout: &str, above: f32) {
+        let (text_width, line_height) = self.overlay.measure(readout);
+        let bar = (self.config.width as f32 * 0.5, self.overlay.logical(4.0));
+        let (x, y) = self
+            .overlay
+            .anchored(Anchor::BottomCenter, (0.0, above), bar);
+        let theme = self.overlay.theme;
+        self.overlay.rect((x, y, bar.0, bar.1), theme.panel);
+        self.overlay
+            .rect((x, y, bar.0 * progress, bar.1), theme.accent);
+        let text_x = x + (bar.0 - text_width) * 0.5;
+        let text_y = y - line_height - self.overlay.logical(4.0);
+        self.overlay
+            .rect((text_x, text_y, text_width, line_height), theme.panel);
+        self.overlay.text((text_x, text_y), theme.text, readout);
+    }
+
+    // The grid cell under the cursor shaded, and a crosshair through the point a drag
+    // would snap to
+    fn queue_snap_cursor(&self, frame: &mut Frame) {
+        let viewport = (self.config.width, self.config.height);
+        let cursor = self.cursor_world();
+        let step = self.snap.step(&self.camera2d);
+        let cell = (cursor / step).floor() * step;
+        frame.world_rect(
+            cell,
+            cell + Vec2::splat(step),
+            self.overlay.theme.grid_major.faded(0.2),
+        );
+
+        let snapped = self
+            .camera2d
+            .world_to_screen(self.snap.snap(cursor, &self.camera2d), viewport);
+        let (width, height) = (viewport.0 as f32, viewport.1 as f32);
+        let accent = self.overlay.theme.accent;
+        let faint = accent.faded(0.35);
+        frame.line(
+            Vec2::new(0.0, snapped.y),
+            Vec2::new(width, snapped.y),
+            faint,
+        );
+        frame.line(
+            Vec2::new(snapped.x, 0.0),
+            Vec2::new(snapped.x, height),
+            faint,
+        );
+        frame.rect(snapped.x - 3.0, snapped.y - 3.0, 6.0, 6.0, accent);
+    }
+
+    // Strips along the bottom and left edges with a tick on every grid line and world
+    // coordinates at every label_step
+    fn queue_rulers(&mut self) {
+        let viewport = (self.config.width, self.config.height);
+        let (width, height) = (viewport.0 as f32, viewport.1 as f32);
+        let camera = self.camera2d;
+        let min = camera.screen_to_world(Vec2::new(0.0, height), viewport);
+        let max = camera.screen_to_world(Vec2::new(width, 0.0), viewport);
+        let background = self.overlay.theme.panel;
+        let tick = self.overlay.theme.text;
+        let margin = 2.0;
+
+        let y_labels: Vec<_> = self
+            .snap
+            .labels(min.y, max.y, &camera)
+            .into_iter()
+            .map(|y| (y, format!("{y:.0}")))
+            .collect();
+        let (_, line_height) = self.overlay.measure("0");
+        let bottom = line_height + 2.0 * margin;
+        let left = y_labels
+            .iter()
+            .map(|(_, label)| self.overlay.measure(label).0)
+            .fold(0.0, f32::max)
+            + 2.0 * margin;
+        self.overlay
+            .rect((0.0, height - bottom, width, bottom), background);
+        self.overlay
+            .rect((0.0, 0.0, left, height - bottom), background);
+
+        for x in self.snap.lines(min.x, max.x, &camera) {
+            let screen = camera.world_to_screen(Vec2::new(x, 0.0), viewport);
+            self.overlay
+                .rect((screen.x, height - margin * 2.0, 1.0, margin * 2.0), tick);
+        }
+        for y in self.snap.lines(min.y, max.y, &camera) {
+            let screen = camera.world_to_screen(Vec2::new(0.0, y), viewport);
+            self.overlay
+                .rect((left - margin * 2.0, screen.y, margin * 2.0, 1.0), tick);
+        }
+        for x in self.snap.labels(min.x, max.x, &camera) {
+            let screen = camera.world_to_screen(Vec2::new(x, 0.0), viewport);
+            self.overlay
+                .rect((screen.x, height - bottom, 1.0, bottom), tick);
+            self.overlay.text(
+                (screen.x + margin, height - bottom + margin),
+                tick,
+                &format!("{x:.0}"),
+            );
+        }
+        for (y, label) in &y_labels {
+            let screen = camera.world_to_screen(Vec2::new(0.0, *y), viewport);
+            self.overlay.rect((0.0, screen.y, left, 1.0), tick);
+            self.overlay.text((margin, screen.y + margin), tick, label);
+        }
+    }
+
+    // One fixed-rate step of everything that animates
+    // Tweens, fades, the timeline, physics and the fly camera, whatever changes between
+    // fixed updates without any input
+    fn is_moving(&self) -> bool {
+        self.timeline
+            .as_ref()
+            .is_some_and(|timeline| timeline.playing)
+            || !self.morph_tween.is_done()
+            || self.splash.is_some()
+            || self.scene.is_fading()
+            || self.scene.items.iter().any(|item| item.body.is_some())
+            || (self.deferred.active
+                && (self.camera_input.movement != Vec3::ZERO || self.deferred.camera.is_settling()))
+    }
+
+    pub fn update(&mut self, step: std::time::Duration) {
+        // Checked before stepping, so the step that finishes a tween or a fade gets drawn too
+        if self.is_moving() {
+            self.request_redraw();
+        }
+        let input = self.camera_input;
+        // Looking and scrolling happened once, movement is what's held and goes on
+        self.camera_input.look = Vec2::ZERO;
+        self.camera_input.scroll = 0.0;
+        self.deferred.camera.update(&input, step.as_secs_f32());
+        self.deferred.fixed_update(step);
+        if self
+            .timeline
+            .as_ref()
+            .is_some_and(|timeline| timeline.playing)
+        {
+            if let Some(timeline) = &mut self.timeline {
+                timeline.step(step);
+            }
+            self.apply_timeline();
+        }
+        self.morph_tween.step(step);
+        if let Some(pentagon) = &mut self.splash {
+            pentagon.rotation += SPLASH_SPIN * step.as_secs_f32();
+        }
+        self.morph
+            .set_position(&self.queue, self.morph_tween.value());
+        // Bodies bounce off the edges of the window
+        self.scene.physics.bounds = Some(
+            self.camera2d
+                .visible((self.config.width, self.config.height)),
+        );
+        for index in self.scene.update(step) {
+            self.transform_gizmo.removed(index);
+            self.scene_outlines.remove(index);
+            self.outline_requests.retain(|&(item, _)| item != index);
+            for (item, _) in &mut self.outline_requests {
+                if *item > index {
+                    *item -= 1;
+                }
+            }
+        }
+    }
+
+    // Replaces whatever timeline was loaded, on failure the old one stays
+    fn load_timeline(&mut self, path: &std::path::Path) -> bool {
+        match Timeline::load(path) {
+            Ok(mut timeline) => {
+                timeline.check_params(&mut self.post);
+                println!("Timeline {} ({:.1}s)", path.display(), timeline.duration);
+                self.timeline = Some(timeline);
+                true
+            }
+            Err(e) => {
+                log::error!("{e}");
+                false
+            }
+        }
+    }
+
+    // Swaps the deferred view's camera controller, starting from where the old one is
+    // looking. Some((rotation, translation)) smoothing makes it a fly camera
+    fn set_camera3d(&mut self, fly: Option<(f32, f32)>) {
+        if let Some(speed) = self.deferred.camera.speed() {
+            self.fly_speed = speed;
+        }
+        let pose = self.deferred.camera.pose(1.0);
+        self.deferred.camera = match fly {
+            Some((rotation, translation)) => {
+                let mut camera = FlyCamera::new(pose, self.fly_speed);
+                camera.rotation_smoothing = rotation;
+                camera.translation_smoothing = translation;
+                Box::new(camera)
+            }
+            None => Box::new(OrbitCamera::new(Vec3::ZERO, pose)),
+        };
+        println!("Camera {}", self.deferred.camera.name());
+    }
+
+    // F in the deferred view. Released again by Esc or leaving the view
+    fn capture_mouse(&mut self, captured: bool) {
+        if captured == self.mouse_captured {
+            return;
+        }
+        self.mouse_captured = captured;
+        self.camera_input = CameraInput::default();
+        self.window_requests
+            .push(WindowRequest::CaptureCursor(captured));
+    }
+
+    fn set_color_blind(&mut self, mode: ColorBlindMode) {
+        let result = self
+            .post
+            .set_param("colorblind", "mode", mode.to_param())
+            .and_then(|()| {
+                self.post
+                    .set_enabled("colorblind", mode != ColorBlindMode::Off)
+            });
+        match result {
+            Ok(()) => {
+                self.color_blind = mode;
+                self.stats.color_blind = mode;
+                println!("Color blind simulation {}", mode.name());
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    // Puts the camera, effect parameters and item visibility where the timeline has them
+    // now. The clear color is picked up when choosing the view
+    fn apply_timeline(&mut self) {
+        let Some(timeline) = &self.timeline else {
+            return;
+        };
+        if let Some(camera) = timeline.camera() {
+            self.camera2d = Camera2d {
+                resize: self.camera2d.resize,
+                ..camera
+            };
+        }
+        timeline.apply_params(&mut self.post);
+        timeline.apply_visibility(&mut self.scene);
+    }
+
+    // A new item under the cursor, its outline shows up once it has loaded
+    fn add_scene_item(&mut self, mesh: MeshRef) {
+        let name = match &mesh {
+            MeshRef::Asset(path) => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Item".to_owned()),
+            MeshRef::Builtin(_) => format!("Item {}", self.scene.items.len()),
+        };
+        let index = self.scene.add(SceneItem {
+            id: ItemId::default(),
+            name,
+            transform: Transform2d::at(self.cursor_world()),
+            color: [0.9, 0.9, 0.9, 1.0],
+            mesh,
+            pipeline: "shapes".to_owned(),
+            body: None,
+            visibility: Default::default(),
+            removing: false,
+        });
+        self.item_inserted(index);
+        self.history.record(SceneCommand::Add {
+            item: self.scene.items[index].clone(),
+            index,
+        });
+    }
+
+    // Something per item has to follow an item coming into the scene at `index`, the
+    // items after it moved up one. Its outline gets requested unless it's already loaded
+    fn item_inserted(&mut self, index: usize) {
+        for (item, _) in &mut self.outline_requests {
+            if *item >= index {
+                *item += 1;
+            }
+        }
+        self.transform_gizmo.inserted(index);
+        let mesh = &self.scene.items[index].mesh;
+        if let Some(outline) = self.outline_cache.get(mesh) {
+            self.scene_outlines.insert(index, outline.clone());
+            return;
+        }
+        self.scene_outlines.insert(index, Vec::new());
+        let request = AssetRequest::Outline(self.scene.items[index].mesh.clone());
+        self.outline_requests
+            .push((index, self.assets.request(request)));
+    }
+
+    fn edit(&mut self, command: SceneCommand) {
+        if let Some(index) = self.history.apply(&mut self.scene, command) {
+            self.item_inserted(index);
+        }
+    }
+
+    fn undo(&mut self) {
+        match self.history.undo(&mut self.scene) {
+            Some((command, inserted)) => {
+                println!("Undid {}", command.label());
+                if let Some(index) = inserted {
+                    self.item_inserted(index);
+                }
+            }
+            None => println!("Nothing to undo"),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.history.redo(&mut self.scene) {
+            Some((command, inserted)) => {
+                println!("Redid {}", command.label());
+                if let Some(index) = inserted {
+                    self.item_inserted(index);
+                }
+            }
+            None => println!("Nothing to redo"),
+        }
+    }
+
+    // A bar filling up as assets come in, drawn with the overlay
+    fn draw_loading(
+        &mut self,
+        frame: &mut Frame,
+        done: usize,
+        total: usize,
+    ) -> Result<(), ForayError> {
+        drop(frame.pass(
+            "Clear Pass",
+            &[(ColorTarget::Swapchain, frame.background.color())],
+            &self.targets,
+        ));
+        let bar = (self.config.width as f32 * 0.5, self.overlay.logical(12.0));
+        let (x, y) = self.overlay.anchored(Anchor::Center, (0.0, 0.0), bar);
+        let border = self.overlay.logical(2.0);
+        let progress = if total == 0 {
+            1.0
+        } else {
+            done as f32 / total as f32
+        };
+        let theme = self.overlay.theme;
+        self.overlay.rect(
+            (
+                x - border,
+                y - border,
+                bar.0 + 2.0 * border,
+                bar.1 + 2.0 * border,
+            ),
+            theme.panel,
+        );
+        self.overlay
+            .rect((x, y, bar.0 * progress, bar.1), theme.accent);
+        let text = format!("Loading {done}/{total}");
+        let size = self.overlay.measure(&text);
+        // Below the bar
+        let (text_x, _) = self.overlay.anchored(Anchor::Center, (0.0, 0.0), size);
+        let text_y = y + bar.1 + self.overlay.logical(8.0);
+        self.overlay.text((text_x, text_y), theme.text, &text);
+        Ok(())
+    }
+
+    // A slowly turning pentagon, every key binding and which GPU this is running on
+    fn draw_splash(&mut self, frame: &mut Frame) -> Result<(), ForayError> {
+        drop(frame.pass(
+            "Clear Pass",
+            &[(ColorTarget::Swapchain, frame.background.color())],
+            &self.targets,
+        ));
+        let Some(pentagon) = self.splash else {
+            return Ok(());
+        };
+        let corners: Vec<Vec2> =
+            geometry::regular_polygon_unchecked(5, 120.0, std::f32::consts::FRAC_PI_2)
+                .into_iter()
+                .map(|corner| pentagon.transform_point(corner))
+                .collect();
+        let fill = RgbaColor::rgba(0.5, 0.0, 0.5, 1.0);
+        for (i, &corner) in corners.iter().enumerate() {
+            let next = corners[(i + 1) % corners.len()];
+            frame
+                .immediate
+                .triangle(Space::World, [pentagon.translation, corner, next], fill);
+            frame.world_line(corner, next, Colors::WHITE);
+        }
+
+        #[cfg(feature = "text")]
+        self.queue_title(frame, "wgpu-foray");
+
+        // As many columns as fit the window, the overlay font is fixed width
+        let columns = (self.config.width as f32 / self.overlay.measure("M").0) as usize;
+        let keys = self.bindings.lines(columns.saturating_sub(4).max(40));
+        self.overlay
+            .panel(Anchor::BottomCenter, (0.0, 8.0), &keys.join("\n"));
+        let info = self.adapter.get_info();
+        self.overlay.panel(
+            Anchor::TopRight,
+            (8.0, 8.0),
+            &format!("{} ({:?})", info.name, info.backend),
+        );
+        Ok(())
+    }
+
+    fn _render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        Ok(())
+    }
+}
+
+// The view drawn for a supersampled screenshot, one offscreen frame per sample
+struct SupersampleRun<'s> {
+    state: &'s mut State,
+    view: &'s View,
+    alpha: f32,
+    texture: &'s wgpu::Texture,
+    background: Background,
+    // The 2D camera the samples are jittered around
+    stretched: Camera2d,
+}
+
+impl supersample::Jittered for SupersampleRun<'_> {
+    fn jitter(&mut self, offset: Vec2, size: (u32, u32)) {
+        let state = &mut *self.state;
+        state.deferred.jitter = Vec2::new(
+            2.0 * offset.x / size.0 as f32,
+            -2.0 * offset.y / size.1 as f32,
+        );
+        let half = Vec2::new(size.0 as f32, size.1 as f32) * 0.5;
+        state.camera2d.center =
+            2.0 * self.stretched.center - self.stretched.screen_to_world(half + offset, size);
+    }
+
+    fn sample(&mut self, _size: (u32, u32)) -> Result<image::RgbaImage, String> {
+        let state = &mut *self.state;
+        let mut frame = Frame::offscreen(
+            self.texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            &state.device,
+            state.surface_views.scene,
+            self.background,
+        );
+        if matches!(self.view, View::Primitives) {
+            state.upload_scene_shapes();
+        }
+        state.draw_passes(&mut frame, self.view, self.alpha, false);
+        let readback = screenshot::Readback::copy(
+            &state.device,
+            &mut state.pool,
+            &mut frame.encoder,
+            self.texture,
+            "Supersample",
+        );
+        frame.finish(&state.queue);
+        // Each sample is read before the next is drawn, so its buffer comes back around
+        state.pool.end_frame(&state.queue);
+        readback.and_then(|readback| readback.read(&state.device, screenshot::TIMEOUT))
+    }
+
+    fn finish(&mut self) {
+        // A progressive average at the capture's size is no good at the window's
+        self.state.accumulator.reset();
+    }
+}
+
+// The device with everything Capabilities settled on
+async fn open_device(
+    adapter: &wgpu::Adapter,
+    capabilities: &Capabilities,
+) -> Result<(wgpu::Device, wgpu::Queue), ForayError> {
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: capabilities.features,
+                required_limits: capabilities.limits.clone(),
+                label: None,
+                memory_hints: Default::default(),
+            },
+            None,
+        )
+        .await
+        .map_err(|e| ForayError::GpuUnavailable(format!("no device, {e}")))
+}
+
+// How the surface is set up for a framebuffer of `size`, and the views drawn through it
+fn surface_config(
+    options: &Options,
+    capabilities: &Capabilities,
+    surface_caps: &wgpu::SurfaceCapabilities,
+    transparency: &Transparency,
+    size: (i32, i32),
+) -> (wgpu::SurfaceConfiguration, SurfaceViews) {
+    let (width, height) = capabilities.clamp_size((size.0 as u32, size.1 as u32));
+    let views = SurfaceViews::new(
+        capabilities.surface_format,
+        capabilities
+            .downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS),
+    );
+    println!("Surface views: {}", views.describe());
+    let config = wgpu::SurfaceConfiguration {
+        // Copyable where it can be, for the crash report's screenshot
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
+        format: capabilities.surface_format,
+        width,
+        height,
+        present_mode: surface_caps.present_modes[0],
+        alpha_mode: transparency.alpha_mode,
+        view_formats: views.view_formats(),
+        desired_maximum_frame_latency: options.frame_latency,
+    };
+    (config, views)
+}
+
+// --opacity and --click-through, carried out by the window's owner like any other request
+fn startup_window_requests(options: &Options) -> Vec<WindowRequest> {
+    let mut window_requests = Vec::new();
+    if options.opacity < 1.0 {
+        window_requests.push(WindowRequest::Opacity(options.opacity));
+    }
+    if options.click_through {
+        window_requests.push(WindowRequest::ClickThrough(true));
+    }
+    window_requests
+}
+
+// The pentagon's "default" family (triangles, lines, lit and packed vertices) and
+// "position"
+fn register_pentagon_pipelines(
+    device: &wgpu::Device,
+    shaders: &ShaderBank,
+    views: SurfaceViews,
+    render_pipelines: &mut RenderPipelineBank,
+) {
+    // Default Pipeline
+    render_pipelines.register_surface(
+        device,
+        "default",
+        &shaders
+            .builder("Default Render Pipeline", "default")
+            .vertex_buffer(Vertex::desc()),
+        views.scene,
+    );
+
+    // Line member of the "default" family, picked for line-list meshes by set_pipeline_for
+    render_pipelines.register_surface(
+        device,
+        "default/line",
+        &shaders
+            .builder("Default Line Pipeline", "default")
+            .vertex_buffer(Vertex::desc())
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .cull_mode(None),
+        views.scene,
+    );
+
+    // "default" for the deferred demo's vertices, which carry a normal between position
+    // and color. set_pipeline_for picks it for those meshes, so they keep their colors
+    // instead of reading normals through a layout that happens to fit
+    let lit_layout = DeferredDemo::vertex_layout();
+    render_pipelines.register_surface(
+        device,
+        "default#lit",
+        &shaders
+            .builder("Default Lit Vertex Pipeline", "default#lit")
+            .vertex_buffer(lit_layout.clone()),
+        views.scene,
+    );
+    render_pipelines.specialize("default", VertexLayoutId::of(&lit_layout), "default#lit");
+
+    // Both "default" members again for CompactVertex meshes, same shader
+    let packed_layout = CompactVertex::desc();
+    for (member, topology, cull_mode) in [
+        (
+            "default",
+            wgpu::PrimitiveTopology::TriangleList,
+            Some(wgpu::Face::Back),
+        ),
+        ("default/line", wgpu::PrimitiveTopology::LineList, None),
+    ] {
+        let name = format!("{member}#packed");
+        render_pipelines.register_surface(
+            device,
+            &name,
+            &shaders
+                .builder("Default Packed Vertex Pipeline", "default")
+                .vertex_buffer(packed_layout.clone())
+                .topology(topology)
+                .cull_mode(cull_mode),
+            views.scene,
+        );
+        render_pipelines.specialize(member, VertexLayoutId::of(&packed_layout), name);
+    }
+
+    // The one that uses Position
+    render_pipelines.register_surface(
+        device,
+        "position",
+        &shaders
+            .builder("Position Render Pipeline", "position")
+            .vertex_buffer(Vertex::desc()),
+        views.scene,
+    );
+}
+
+// Every post effect, in the order they're applied
+#[allow(clippy::too_many_arguments)]
+fn effect_chain(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    memory: &GpuMemoryTracker,
+    targets: &mut TargetRegistry,
+    render_pipelines: &mut RenderPipelineBank,
+    format: wgpu::TextureFormat,
+    capabilities: &Capabilities,
+    options: &Options,
+) -> EffectChain {
+    // Graded with the identity until the --lut file has loaded
+    let lut = LutData::identity(lut::IDENTITY_SIZE);
+    let mut post = EffectChain::new(device, format, targets);
+    post.add(
+        device,
+        render_pipelines,
+        "vignette",
+        Box::new(Vignette::new(device)),
+    );
+    post.add(
+        device,
+        render_pipelines,
+        "grade",
+        Box::new(ColorGrade::new(device, queue, memory, targets, &lut)),
+    );
+    post.add(
+        device,
+        render_pipelines,
+        "pixelate",
+        Box::new(Pixelate::new(device)),
+    );
+    let mut dither = Dither::new(device);
+    dither.set_palette(&options.dither_palette);
+    post.add(device, render_pipelines, "dither", Box::new(dither));
+    // Made of compute passes, which the GL fallback may not have
+    if capabilities.has(Optional::Compute) {
+        let bloom = Bloom::new(device, targets);
+        post.add(device, render_pipelines, "bloom", Box::new(bloom));
+        let exposure = AutoExposure::new(device, memory, targets);
+        post.add(device, render_pipelines, "exposure", Box::new(exposure));
+    }
+    post.add(
+        device,
+        render_pipelines,
+        "colorblind",
+        Box::new(ColorBlind::new(device)),
+    );
+    post
+}
+
+// The pentagon, its outline and the pentagon to star morph
+fn pentagon_meshes(
+    device: &wgpu::Device,
+    memory: &GpuMemoryTracker,
+) -> (Mesh, Mesh, DynamicMesh<Vertex>) {
+    let vertices: Vec<_> = VERTICES.iter().map(Vertex::linearized).collect();
+    // Dynamic so a dropped image can be baked into its colors
+    let pentagon = Mesh::new_dynamic(
+        device,
+        memory,
+        "Pentagon",
+        &Vertex::desc(),
+        wgpu::PrimitiveTopology::TriangleList,
+        &vertices,
+        Indices::U16(INDICES),
+    );
+    // White, which packs exactly
+    let outline = MeshData::new(
+        "Pentagon Outline",
+        VERTICES
+            .iter()
+            .map(|vertex| Vertex {
+                position: vertex.position,
+                color: [1.0; 3],
+            })
+            .collect(),
+        OUTLINE_EDGES.iter().map(|&i| u32::from(i)).collect(),
+    );
+    let pentagon_outline = Mesh::from_data(
+        device,
+        memory,
+        "Pentagon Outline",
+        &CompactVertex::desc(),
+        wgpu::PrimitiveTopology::LineList,
+        &outline.pack_into::<CompactVertex>(),
+    );
+
+    let morph = DynamicMesh::new(
+        device,
+        memory,
+        "Pentagon To Star",
+        &Vertex::desc(),
+        wgpu::PrimitiveTopology::TriangleList,
+        vec![
+            morph_target("pentagon", VERTICES, STAR_VERTICES.len()),
+            morph_target("star", STAR_VERTICES, STAR_VERTICES.len()),
+        ],
+        &morph::fan_indices(STAR_VERTICES.len()),
+    )
+    .expect("Both morph targets are resampled to the same count");
+    (pentagon, pentagon_outline, morph)
+}
+
+// Surface for the window and an adapter that can present to it, on the backends picked
+// with WGPU_FORAY_BACKEND
+async fn open_adapter(
+    target: wgpu::SurfaceTargetUnsafe,
+    backends: wgpu::Backends,
+) -> Result<(wgpu::Surface<'static>, wgpu::Adapter), ForayError> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let surface = unsafe { instance.create_surface_unsafe(target) }
+        .map_err(|e| ForayError::GpuUnavailable(format!("no surface, {e}")))?;
+
+    let adapter = request_adapter(&instance, &surface).await.ok_or_else(|| {
+        ForayError::GpuUnavailable(format!(
+            "no adapter for {backends:?}, {} picks another backend",
+            capabilities::BACKEND_VAR
+        ))
+    })?;
+    Ok((surface, adapter))
+}
+
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+) -> Option<wgpu::Adapter> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptionsBase {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: Some(surface),
+        })
+        .await
+}
+
+// What the subsystems want from the device, Capabilities::new settles it with the adapter
+fn device_requirements(options: &Options) -> DeviceRequirements {
+    let mut requirements = DeviceRequirements::new();
+    if !options.required_features.is_empty() {
+        requirements.require_feature("command line (--require)", options.required_features);
+    }
+    requirements
+        // Input, uniforms and the effect's own group
+        .require_limit(
+            "post chain",
+            "max_bind_groups",
+            |limits| &mut limits.max_bind_groups,
+            3,
+        )
+        // Rgba32Float where the adapter can blend and filter it
+        .optional_feature(
+            "accumulator",
+            wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+        )
+        .optional_feature("wireframe", wgpu::Features::POLYGON_MODE_LINE)
+        .optional_feature("push constants", wgpu::Features::PUSH_CONSTANTS)
+        .optional_limit(
+            "push constants",
+            "max_push_constant_size",
+            |limits| &mut limits.max_push_constant_size,
+            128,
+        )
+        .optional_feature("timestamps", wgpu::Features::TIMESTAMP_QUERY)
+        .optional_feature(
+            "pipeline statistics",
+            wgpu::Features::PIPELINE_STATISTICS_QUERY,
+        );
+    requirements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // None of these get as far as looking for a GPU
+    fn build(builder: StateBuilder) -> Result<State, ForayError> {
+        pollster::block_on(builder.build())
+    }
+
+    #[test]
+    fn an_msaa_count_the_g_buffer_cant_take_fails_the_build() {
+        let options = Options::default();
+        let built = build(StateBuilder::new(&options).size((800, 600)).msaa(3));
+        assert!(matches!(
+            built,
+            Err(ForayError::UnsupportedSampleCount { samples: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn the_msaa_count_defaults_to_the_options() {
+        let options = Options {
+            msaa: 8,
+            ..Options::default()
+        };
+        let built = build(StateBuilder::new(&options).size((800, 600)));
+        assert!(matches!(
+            built,
+            Err(ForayError::UnsupportedSampleCount { samples: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn two_fullscreen_pipelines_cant_share_a_name() {
+        let options = Options::default();
+        let builder = StateBuilder::new(&options)
+            .size((800, 600))
+            .fullscreen_pipeline("plasma", "")
+            .fullscreen_pipeline("plasma", "");
+        assert!(matches!(build(builder), Err(ForayError::StateBuilder(_))));
+    }
+
+    #[test]
+    fn a_fullscreen_pipeline_only_includes_the_built_in_files() {
+        let options = Options::default();
+        let builder = StateBuilder::new(&options)
+            .size((800, 600))
+            .fullscreen_pipeline(
+                "plasma",
+                "#include \"globals.wgsl\"\n#include \"mine.wgsl\"",
+            );
+        let Err(ForayError::StateBuilder(reason)) = build(builder) else {
+            panic!("Built with an include that isn't there");
+        };
+        assert!(reason.contains("mine.wgsl"));
+    }
+
+    #[test]
+    fn nothing_to_draw_into_fails_the_build() {
+        let options = Options::default();
+        let no_target = build(StateBuilder::new(&options).size((800, 600)).msaa(4));
+        assert!(matches!(no_target, Err(ForayError::StateBuilder(_))));
+        let no_size = build(StateBuilder::new(&options));
+        assert!(matches!(no_size, Err(ForayError::StateBuilder(_))));
+    }
+}
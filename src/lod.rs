@@ -0,0 +1,96 @@
+use glam::{BVec3, Mat4, Vec2, Vec3, Vec4Swizzles};
+
+use crate::mesh::{Mesh, MeshData, Position};
+
+// A level only switches once the size is this far past its threshold, so something sitting
+// right at one doesn't swap meshes every frame
+const HYSTERESIS: f32 = 0.15;
+
+// A mesh and coarser versions of it made with MeshData::decimate, picked by how big the
+// mesh is on screen
+pub struct LodMesh {
+    // Finest first
+    pub levels: Vec<Mesh>,
+    // Object space, what the on-screen size is measured from
+    pub bounds: (Vec3, Vec3),
+    // Height in pixels below which level i + 1 takes over from level i
+    thresholds: Vec<f32>,
+}
+
+impl LodMesh {
+    // `levels` is (triangle ratio, pixel threshold) for every level after the full one, each
+    // level is made into a Mesh by `upload` (Mesh::from_data, or into a MeshArena). Input
+    // decimate refuses ends up as the one full level, with a warning
+    pub fn new<V: Position + Clone>(
+        name: &str,
+        data: &MeshData<V>,
+        levels: &[(f32, f32)],
+        mut upload: impl FnMut(&str, &MeshData<V>) -> Mesh,
+    ) -> Self {
+        let mut meshes = vec![upload(name, data)];
+        let mut thresholds = Vec::new();
+        for (level, &(ratio, pixels)) in (1..).zip(levels) {
+            match data.decimate(name, ratio) {
+                Ok(coarser) => {
+                    meshes.push(upload(&format!("{name} LOD{level}"), &coarser));
+                    thresholds.push(pixels);
+                }
+                Err(e) => {
+                    log::warn!("{e}, drawing it without LODs");
+                    meshes.truncate(1);
+                    thresholds.clear();
+                    break;
+                }
+            }
+        }
+        Self {
+            levels: meshes,
+            bounds: data.bounds(),
+            thresholds,
+        }
+    }
+
+    // The level for `pixels` on screen, `current` is the one drawn last frame
+    pub fn select(&self, current: usize, pixels: f32) -> usize {
+        let mut level = current.min(self.levels.len() - 1);
+        while level < self.thresholds.len() && pixels < self.thresholds[level] * (1.0 - HYSTERESIS)
+        {
+            level += 1;
+        }
+        while level > 0 && pixels > self.thresholds[level - 1] * (1.0 + HYSTERESIS) {
+            level -= 1;
+        }
+        level
+    }
+}
+
+// Larger side in pixels of the screen rectangle around `bounds` placed by `transform`.
+// Infinite when part of it is behind the camera, that's as close as it gets
+pub fn projected_size(
+    bounds: (Vec3, Vec3),
+    transform: Mat4,
+    view_proj: Mat4,
+    viewport: (u32, u32),
+) -> f32 {
+    let (min, max) = bounds;
+    let clip = view_proj * transform;
+    let mut low = Vec2::splat(f32::INFINITY);
+    let mut high = Vec2::splat(f32::NEG_INFINITY);
+    for corner in 0..8 {
+        let point = Vec3::select(
+            BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+            max,
+            min,
+        );
+        let projected = clip * point.extend(1.0);
+        if projected.w <= 0.0 {
+            return f32::INFINITY;
+        }
+        let ndc = projected.xy() / projected.w;
+        low = low.min(ndc);
+        high = high.max(ndc);
+    }
+    // NDC spans 2 across the viewport
+    let size = (high - low) * 0.5 * Vec2::new(viewport.0 as f32, viewport.1 as f32);
+    size.max_element()
+}
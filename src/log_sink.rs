@@ -0,0 +1,187 @@
+#[cfg(any(feature = "remote", test))]
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, TryLockError};
+use std::time::{Duration, Instant};
+
+// Warnings and errors kept for the overlay, older ones fall off the front
+const CAPACITY: usize = 8;
+// Lines kept for the console, which doesn't fade anything out
+pub const HISTORY: usize = 500;
+// Shown at full strength for this long after the last repeat, then faded out over FADE
+const HOLD: Duration = Duration::from_secs(4);
+const FADE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub level: log::Level,
+    pub message: String,
+    // Frame it was last logged in
+    pub frame: u64,
+    // Same message logged this many times in a row
+    pub count: u32,
+    pub last_seen: Instant,
+}
+
+impl LogRecord {
+    // 1 while fresh, down to 0 once it should disappear
+    pub fn opacity(&self, now: Instant) -> f32 {
+        let age = now.saturating_duration_since(self.last_seen);
+        if age <= HOLD {
+            1.0
+        } else {
+            1.0 - ((age - HOLD).as_secs_f32() / FADE.as_secs_f32()).min(1.0)
+        }
+    }
+
+    pub fn line(&self) -> String {
+        let repeats = if self.count > 1 {
+            format!(" x{}", self.count)
+        } else {
+            String::new()
+        };
+        format!("#{} {} {}{repeats}", self.frame, self.level, self.message)
+    }
+}
+
+// Warnings and errors, ours and wgpu's. They still go to the terminal and are also kept
+// for the debug overlay, so they can be seen without watching the terminal
+pub struct LogSink {
+    records: Mutex<VecDeque<LogRecord>>,
+    history: Mutex<VecDeque<LogRecord>>,
+    frame: AtomicU64,
+}
+
+static SINK: LogSink = LogSink {
+    records: Mutex::new(VecDeque::new()),
+    history: Mutex::new(VecDeque::new()),
+    frame: AtomicU64::new(0),
+};
+
+// Messages logged on this thread while capture() runs
+#[cfg(any(feature = "remote", test))]
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+// Once, before anything logs
+pub fn init() {
+    if log::set_logger(&SINK).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+}
+
+// So records can say which frame they came from
+pub fn set_frame(frame: u64) {
+    SINK.frame.store(frame, Ordering::Relaxed);
+}
+
+// Oldest first, only those that haven't faded out yet
+pub fn recent(now: Instant) -> Vec<LogRecord> {
+    let records = SINK.records.lock().expect("Log sink poisoned");
+    records
+        .iter()
+        .filter(|record| record.opacity(now) > 0.0)
+        .cloned()
+        .collect()
+}
+
+// Everything kept for the console, oldest first. Repeats only fold into the line before
+pub fn history() -> Vec<LogRecord> {
+    let history = SINK.history.lock().expect("Log sink poisoned");
+    history.iter().cloned().collect()
+}
+
+// Runs `f`, returning what it logged on this thread. That's still logged as usual, this is
+// for answering a remote command with the warnings it ran into, and for tests checking one
+// fired
+#[cfg(any(feature = "remote", test))]
+pub fn capture(f: impl FnOnce()) -> Vec<String> {
+    CAPTURED.with(|captured| captured.replace(Some(Vec::new())));
+    f();
+    CAPTURED
+        .with(|captured| captured.replace(None))
+        .unwrap_or_default()
+}
+
+// The newest `count` of history(), for the panic hook. None when the history is locked, the
+// panic may have come from under that lock and waiting on it would hang
+pub fn last(count: usize) -> Option<Vec<LogRecord>> {
+    let history = match SINK.history.try_lock() {
+        Ok(history) => history,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return None,
+    };
+    let skip = history.len().saturating_sub(count);
+    Some(history.iter().skip(skip).cloned().collect())
+}
+
+impl log::Log for LogSink {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        println!("[{}] {message}", record.level());
+        #[cfg(any(feature = "remote", test))]
+        CAPTURED.with(|captured| {
+            if let Ok(Some(captured)) = captured.try_borrow_mut().as_deref_mut() {
+                captured.push(message.clone());
+            }
+        });
+
+        let frame = self.frame.load(Ordering::Relaxed);
+        let mut history = self.history.lock().expect("Log sink poisoned");
+        match history.back_mut() {
+            Some(last) if last.level == record.level() && last.message == message => {
+                last.count += 1;
+                last.frame = frame;
+                last.last_seen = Instant::now();
+            }
+            _ => {
+                if history.len() == HISTORY {
+                    history.pop_front();
+                }
+                history.push_back(LogRecord {
+                    level: record.level(),
+                    message: message.clone(),
+                    frame,
+                    count: 1,
+                    last_seen: Instant::now(),
+                });
+            }
+        }
+        drop(history);
+
+        let mut records = self.records.lock().expect("Log sink poisoned");
+        // A warning repeated every frame becomes one line with a count
+        if let Some(index) = records
+            .iter()
+            .position(|r| r.level == record.level() && r.message == message)
+        {
+            let mut existing = records.remove(index).expect("Index from position");
+            existing.count += 1;
+            existing.frame = frame;
+            existing.last_seen = Instant::now();
+            records.push_back(existing);
+            return;
+        }
+        if records.len() == CAPACITY {
+            records.pop_front();
+        }
+        records.push_back(LogRecord {
+            level: record.level(),
+            message,
+            frame,
+            count: 1,
+            last_seen: Instant::now(),
+        });
+    }
+
+    fn flush(&self) {}
+}
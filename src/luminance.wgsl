@@ -0,0 +1,82 @@
+// Average log luminance of the chain's input and the adapted luminance kept across
+// frames, see exposure.rs
+struct Params {
+    // Seconds since the last measurement
+    dt: f32,
+    // How quickly the adapted luminance follows the measured one, per second
+    rate: f32,
+    _padding: vec2<f32>,
+};
+
+// Also read by exposure.wgsl
+struct State {
+    adapted: f32,
+    measured: f32,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> partials: array<f32, 64>;
+@group(0) @binding(2) var<storage, read_write> state: State;
+@group(0) @binding(3) var<uniform> params: Params;
+
+// The source is measured as GRID x GRID cells, one per invocation of cs_measure, which
+// runs 8 x 8 workgroups of 8 x 8
+const GRID: u32 = 64u;
+// Black would be log(0)
+const MIN_LUMINANCE: f32 = 0.0001;
+
+var<workgroup> sums: array<f32, 64>;
+
+// Adds up sums[0..64] into sums[0], every invocation has to get here
+fn reduce(local: u32) {
+    for (var stride = 32u; stride > 0u; stride >>= 1u) {
+        if (local < stride) {
+            sums[local] += sums[local + stride];
+        }
+        workgroupBarrier();
+    }
+}
+
+// Mean log luminance of each cell, each workgroup leaves the sum of its 64 in partials
+@compute @workgroup_size(8, 8)
+fn cs_measure(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+) {
+    let size = textureDimensions(source);
+    let start = min(id.xy * size / GRID, size - 1u);
+    let end = min(max((id.xy + 1u) * size / GRID, start + 1u), size);
+    var sum = 0.0;
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            let color = textureLoad(source, vec2<u32>(x, y), 0).rgb;
+            let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+            sum += log(max(luminance, MIN_LUMINANCE));
+        }
+    }
+    let area = end - start;
+    sums[local] = sum / f32(area.x * area.y);
+    workgroupBarrier();
+    reduce(local);
+    if (local == 0u) {
+        partials[group.y * 8u + group.x] = sums[0];
+    }
+}
+
+// One workgroup: the partials down to the scene's average, then the adapted value moves
+// towards it. Exponential, so it takes as long at any frame rate
+@compute @workgroup_size(64)
+fn cs_adapt(@builtin(local_invocation_index) local: u32) {
+    sums[local] = partials[local];
+    workgroupBarrier();
+    reduce(local);
+    if (local == 0u) {
+        let measured = exp(sums[0] / f32(GRID * GRID));
+        let blend = 1.0 - exp(-params.dt * params.rate);
+        // The state starts out zeroed, the first frame goes straight to the measurement
+        state.adapted = select(mix(state.adapted, measured, blend), measured, state.adapted <= 0.0);
+        state.measured = measured;
+    }
+}
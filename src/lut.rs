@@ -0,0 +1,271 @@
+use std::path::Path;
+
+use crate::error::ForayError;
+use crate::image_file;
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+
+// Entries per axis of the LUT used when none is given
+pub const IDENTITY_SIZE: u32 = 16;
+
+// A color lookup table on the CPU, red along x, green along y and blue along z. Values are
+// sRGB encoded, which is what LUT files hold and what grade.wgsl looks them up with
+pub struct LutData {
+    pub size: u32,
+    pub texels: Vec<[u8; 4]>,
+}
+
+impl LutData {
+    // Every color maps to itself, grading with it changes nothing
+    pub fn identity(size: u32) -> Self {
+        let level = |i: u32| ((i * 255 + (size - 1) / 2) / (size - 1)) as u8;
+        let mut texels = Vec::with_capacity((size * size * size) as usize);
+        for blue in 0..size {
+            for green in 0..size {
+                for red in 0..size {
+                    texels.push([level(red), level(green), level(blue), 255]);
+                }
+            }
+        }
+        Self { size, texels }
+    }
+
+    // The usual strip layout: N slices of N x N side by side, blue picking the slice, so
+    // the image is N * N wide and N tall
+    pub fn from_strip(path: &Path, strip: &image::RgbaImage) -> Result<Self, ForayError> {
+        let (width, height) = strip.dimensions();
+        if height < 2 || width != height * height {
+            return Err(ForayError::LutSize {
+                path: path.to_owned(),
+                width,
+                height,
+            });
+        }
+
+        let size = height;
+        let mut texels = Vec::with_capacity((size * size * size) as usize);
+        for blue in 0..size {
+            for green in 0..size {
+                for red in 0..size {
+                    texels.push(strip.get_pixel(blue * size + red, green).0);
+                }
+            }
+        }
+        Ok(Self { size, texels })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ForayError> {
+        let strip = image_file::open(path)?;
+        Self::from_strip(path, &strip)
+    }
+
+    // As a 3D texture, sampled with a linear filter that's trilinear interpolation
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let size = wgpu::Extent3d {
+            width: self.size,
+            height: self.size,
+            depth_or_array_layers: self.size,
+        };
+        let texture = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("Color Grading LUT"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                // Not an sRGB format, the shader wants the encoded values as they are
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            MemoryCategory::Textures,
+        );
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&self.texels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.size * 4),
+                rows_per_image: Some(self.size),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::RgbaColor;
+    use crate::effects::ColorGrade;
+    use crate::frame::{Background, Frame};
+    use crate::gpu_context::GpuContext;
+    use crate::pipeline_bank::RenderPipelineBank;
+    use crate::post::EffectChain;
+    use crate::targets::TargetRegistry;
+
+    const SIZE: (u32, u32) = (4, 4);
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // Back into the strip layout from_strip reads
+    fn strip(lut: &LutData) -> image::RgbaImage {
+        image::RgbaImage::from_fn(lut.size * lut.size, lut.size, |x, y| {
+            let (blue, red) = (x / lut.size, x % lut.size);
+            let index = (blue * lut.size + y) * lut.size + red;
+            image::Rgba(lut.texels[index as usize])
+        })
+    }
+
+    fn inverted(size: u32) -> LutData {
+        let mut lut = LutData::identity(size);
+        for texel in &mut lut.texels {
+            for channel in &mut texel[..3] {
+                *channel = 255 - *channel;
+            }
+        }
+        lut
+    }
+
+    #[test]
+    fn strips_must_be_n_squared_by_n() {
+        for (width, height) in [(15, 4), (16, 5), (1, 1), (0, 0), (64, 16)] {
+            let result =
+                LutData::from_strip(Path::new("bad.png"), &image::RgbaImage::new(width, height));
+            assert!(
+                matches!(
+                    result,
+                    Err(ForayError::LutSize { width: w, height: h, .. }) if (w, h) == (width, height)
+                ),
+                "{width}x{height} was accepted"
+            );
+        }
+        let lut =
+            LutData::from_strip(Path::new("good.png"), &image::RgbaImage::new(16, 4)).unwrap();
+        assert_eq!((lut.size, lut.texels.len()), (4, 64));
+    }
+
+    #[test]
+    fn strips_are_read_red_green_blue() {
+        let identity = LutData::identity(IDENTITY_SIZE);
+        assert_eq!(identity.texels.first(), Some(&[0, 0, 0, 255]));
+        assert_eq!(identity.texels.last(), Some(&[255, 255, 255, 255]));
+        // One step along blue is a whole slice further in
+        let slice = (IDENTITY_SIZE * IDENTITY_SIZE) as usize;
+        assert_eq!(identity.texels[slice], [0, 0, 17, 255]);
+        assert_eq!(identity.texels[IDENTITY_SIZE as usize], [0, 17, 0, 255]);
+
+        let read = LutData::from_strip(Path::new("identity.png"), &strip(&identity)).unwrap();
+        assert_eq!(read.size, identity.size);
+        assert_eq!(read.texels, identity.texels);
+    }
+
+    // `color` cleared into the chain's scene target and graded through `lut` with nothing
+    // else changing it, read back from the sRGB output
+    fn grade(gpu: &GpuContext, lut: &LutData, color: [u8; 4]) -> image::RgbaImage {
+        let memory = GpuMemoryTracker::new();
+        let mut bank = RenderPipelineBank::new();
+        let mut registry = TargetRegistry::new(SIZE, &memory);
+        let mut chain = EffectChain::new(&gpu.device, FORMAT, &mut registry);
+        let mut effect = ColorGrade::new(&gpu.device, &gpu.queue, &memory, &mut registry, lut);
+        effect.params.exposure = 0.0;
+        effect.params.contrast = 1.0;
+        effect.params.saturation = 1.0;
+        effect.params.lut_intensity = 1.0;
+        chain.add(&gpu.device, &mut bank, "grade", Box::new(effect));
+        chain.set_enabled("grade", true).unwrap();
+
+        let output = gpu.target(SIZE, FORMAT);
+        let mut frame = Frame::offscreen(
+            output.create_view(&wgpu::TextureViewDescriptor::default()),
+            &gpu.device,
+            FORMAT,
+            Background::Clear(wgpu::Color::BLACK),
+        );
+        frame
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Test Scene Clear"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: registry.view(chain.scene_target()),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(RgbaColor::from_unorm8(color).to_wgpu_linear()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+        let mut pool = crate::buffer_pool::BufferPool::new(&memory, 0);
+        chain
+            .apply(
+                &gpu.device,
+                &gpu.queue,
+                &mut frame,
+                &registry,
+                &bank,
+                &mut pool,
+            )
+            .unwrap();
+        frame.finish(&gpu.queue);
+        gpu.read_back(&output)
+    }
+
+    const COLORS: [[u8; 4]; 4] = [
+        [40, 128, 200, 255],
+        [255, 0, 90, 255],
+        [7, 250, 33, 255],
+        [128, 128, 128, 255],
+    ];
+
+    #[test]
+    fn identity_lut_leaves_colors_alone() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let lut = LutData::identity(IDENTITY_SIZE);
+        for color in COLORS {
+            for pixel in grade(gpu, &lut, color).pixels() {
+                for channel in 0..3 {
+                    assert!(
+                        pixel.0[channel].abs_diff(color[channel]) <= 2,
+                        "{color:?} came back as {pixel:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inverted_lut_inverts_colors() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        // Through a strip, the way one would come off disk
+        let lut = LutData::from_strip(Path::new("inverted.png"), &strip(&inverted(IDENTITY_SIZE)))
+            .unwrap();
+        for color in COLORS {
+            for pixel in grade(gpu, &lut, color).pixels() {
+                for channel in 0..3 {
+                    assert!(
+                        pixel.0[channel].abs_diff(255 - color[channel]) <= 2,
+                        "{color:?} came back as {pixel:?}"
+                    );
+                }
+            }
+        }
+    }
+}
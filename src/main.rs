@@ -1,547 +1,5 @@
 #![warn(clippy::all, clippy::pedantic)]
 
-mod prelude; // Currently nothing in it, might become relevant as this grows -\(-.-)-\
-
-use glfw::{fail_on_errors, Action, Context, Key, MouseButton, Window};
-use wgpu::{
-    self,
-    util::{DeviceExt, RenderEncoder},
-    Color,
-};
-
-// Render Pipeline Bank
-struct RenderPipelineBank {
-    store: Vec<wgpu::RenderPipeline>,
-}
-
-// Pentagon
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-0.0868241, 0.49240386, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // A
-    Vertex {
-        position: [-0.49513406, 0.06958647, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // B
-    Vertex {
-        position: [-0.21918549, -0.44939706, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // C
-    Vertex {
-        position: [0.35966998, -0.3473291, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // D
-    Vertex {
-        position: [0.44147372, 0.2347359, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // E
-];
-
-// Star
-
-const STAR_VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-0.0868241, 0.49240386, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // A
-    Vertex {
-        position: [-0.49513406, 0.06958647, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // B
-    Vertex {
-        position: [-0.21918549, -0.44939706, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // C
-    Vertex {
-        position: [0.35966998, -0.3473291, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // D
-    Vertex {
-        position: [0.44147372, 0.2347359, 0.0],
-        color: [0.5, 0.0, 0.5],
-    }, // E
-];
-
-const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
-
-// Buffer Stuff
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
-}
-
-impl Vertex {
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
-        }
-    }
-}
-
-// Start implementation of Builder stuff
-struct ForayRender;
-
-enum Stage {
-    Uninitialized,
-    WithView,
-}
-
-// Main Structure
-struct State<'a> {
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: (i32, i32),
-    window: &'a mut Window,
-    render_pipelines: Vec<wgpu::RenderPipeline>,
-    vertex_buffer: wgpu::Buffer,
-    num_vertices: u32,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-}
-
-impl<'a> State<'a> {
-    async fn new(window: &'a mut Window) -> State<'a> {
-        let size = window.get_size();
-
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
-            ..Default::default()
-        });
-        let target = unsafe { wgpu::SurfaceTargetUnsafe::from_window(window) }
-            .expect("Failed to get target");
-        let surface =
-            unsafe { instance.create_surface_unsafe(target) }.expect("Failed to create surface");
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptionsBase {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to create adapter");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                    label: None,
-                    memory_hints: Default::default(),
-                },
-                None,
-            )
-            .await
-            .expect("Failed to get device & queue.");
-
-        let surface_caps = surface.get_capabilities(&adapter);
-
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|format| format.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.0.max(1) as u32,
-            height: size.1.max(1) as u32,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-
-        surface.configure(&device, &config);
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
-
-        // Use the same layout (is probably fine?)
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            });
-
-        // Default Pipeline
-        let render_pipeline_0 = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Default Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[Vertex::desc()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-            cache: None,
-        });
-
-        // The one that uses Position
-        let render_pipeline_1 = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Position Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[Vertex::desc()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main_pos"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-            cache: None,
-        });
-
-        let render_pipelines = vec![render_pipeline_0, render_pipeline_1];
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        Self {
-            surface,
-            device,
-            queue,
-            config,
-            size,
-            window,
-            render_pipelines,
-            vertex_buffer,
-            num_vertices: VERTICES.len() as u32,
-            index_buffer,
-            num_indices: INDICES.len() as u32,
-        }
-    }
-
-    fn resize(&mut self, new_size: (i32, i32)) {
-        if new_size.0 > 0 && new_size.1 > 0 {
-            self.size = new_size;
-            self.config.width = new_size.0 as u32;
-            self.config.height = new_size.1 as u32;
-            self.surface.configure(&self.device, &self.config);
-        }
-    }
-
-    // Might be repurposed, (?) Could be cool in the builder abstraction thingey
-    fn _draw_triangle(&mut self, toggle: bool) {
-        // My conditional here
-        let render_pipeline: &wgpu::RenderPipeline = if toggle {
-            &self.render_pipelines[1]
-        } else {
-            &self.render_pipelines[0]
-        };
-        // We will create a new pipeline
-        let output = self
-            .surface
-            .get_current_texture()
-            .expect("Failed to get texture");
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-        render_pass.set_pipeline(render_pipeline);
-        render_pass.draw(0..3, 0..1);
-        drop(render_pass);
-
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-    }
-
-    fn clear_screen_to(&mut self, color: Color) {
-        let output = self
-            .surface
-            .get_current_texture()
-            .expect("Failed to get texture");
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-        let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: color.r,
-                        g: color.g,
-                        b: color.b,
-                        a: color.a,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-        drop(render_pass);
-
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-    }
-
-    fn update(&mut self) {}
-
-    fn _render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        Ok(())
-    }
-}
-
-async fn run() {
-    // glfw code
-    let mut glfw = glfw::init(fail_on_errors!()).expect("Failed to get glfw");
-
-    glfw.window_hint(glfw::WindowHint::Resizable(true));
-
-    let (mut window, events) = glfw
-        .create_window(800, 600, "wGPU training arc", glfw::WindowMode::Windowed)
-        .expect("Failed to get window and events");
-
-    window.make_current();
-    window.set_key_polling(true);
-    window.set_cursor_pos_polling(true);
-    window.set_cursor_enter_polling(true);
-    let mut state = State::new(&mut window).await;
-
-    state.clear_screen_to(Color::WHITE);
-    let mut triangle_toggle = false;
-    let mut last_color = Color::WHITE;
-
-    while !state.window.should_close() {
-        glfw.poll_events();
-
-        state.update(); // does nothing rn
-
-        // Capture all the events here
-        for (_, event) in glfw::flush_messages(&events) {
-            match event {
-                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                    state.window.set_should_close(true)
-                }
-                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => {
-                    triangle_toggle = !triangle_toggle;
-
-                    let output = state
-                        .surface
-                        .get_current_texture()
-                        .expect("Failed to get texture");
-                    let view = output
-                        .texture
-                        .create_view(&wgpu::TextureViewDescriptor::default());
-                    let mut encoder =
-                        state
-                            .device
-                            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                                label: Some("Render Encoder"),
-                            });
-
-                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: Some("Render Pass"),
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(last_color),
-                                store: wgpu::StoreOp::Store,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                        timestamp_writes: None,
-                        occlusion_query_set: None,
-                    });
-
-                    let render_pipeline: &wgpu::RenderPipeline = if triangle_toggle {
-                        &state.render_pipelines[1]
-                    } else {
-                        &state.render_pipelines[0]
-                    };
-                    render_pass.set_pipeline(render_pipeline);
-
-                    render_pass.set_vertex_buffer(0, state.vertex_buffer.slice(..));
-                    render_pass
-                        .set_index_buffer(state.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                    render_pass.draw_indexed(0..state.num_indices, 0, 0..1);
-                    drop(render_pass);
-                    state.queue.submit(std::iter::once(encoder.finish()));
-                    output.present();
-                }
-                glfw::WindowEvent::Size(width, height) => state.resize((width, height)),
-                glfw::WindowEvent::MouseButton(MouseButton::Left, Action::Press, _) => {
-                    state.window.set_should_close(true);
-                }
-                glfw::WindowEvent::CursorPos(x, y) => {
-                    println!("{}, {}", x, y);
-                    let x_normalized = x / (state.size.0 as f64);
-                    let y_normalized = y / (state.size.1 as f64);
-
-                    //TODO: Find a way to abstract the boiler plate in some sort of builder pattern
-                    let output = state
-                        .surface
-                        .get_current_texture()
-                        .expect("Failed to get texture");
-                    let view = output
-                        .texture
-                        .create_view(&wgpu::TextureViewDescriptor::default());
-                    let mut encoder =
-                        state
-                            .device
-                            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                                label: Some("Render Encoder"),
-                            });
-
-                    last_color = wgpu::Color {
-                        r: x_normalized,
-                        g: y_normalized,
-                        b: (x_normalized + y_normalized) / 2.,
-                        a: 1.,
-                    };
-                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: Some("Render Pass"),
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(last_color),
-                                store: wgpu::StoreOp::Store,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                        timestamp_writes: None,
-                        occlusion_query_set: None,
-                    });
-
-                    let render_pipeline: &wgpu::RenderPipeline = if triangle_toggle {
-                        &state.render_pipelines[1]
-                    } else {
-                        &state.render_pipelines[0]
-                    };
-
-                    render_pass.set_pipeline(&render_pipeline);
-                    render_pass.set_vertex_buffer(0, state.vertex_buffer.slice(..));
-                    render_pass
-                        .set_index_buffer(state.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                    render_pass.draw_indexed(0..state.num_indices, 0, 0..1);
-                    drop(render_pass);
-                    state.queue.submit(std::iter::once(encoder.finish()));
-                    output.present();
-                }
-                glfw::WindowEvent::Key(Key::Up, _, Action::Press, _) => {}
-                event => {
-                    println!("{:?}", event);
-                }
-            }
-        }
-    }
-}
-
 fn main() {
-    pollster::block_on(run());
+    pollster::block_on(wgpu_forray::run());
 }
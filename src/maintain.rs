@@ -0,0 +1,224 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// How long an overdue operation gets blocked on before it's reported
+const ESCALATION_TIMEOUT: Duration = Duration::from_millis(100);
+// Between polls while blocking, wgpu 24's poll(Wait) can't be given a timeout
+const WAIT_STEP: Duration = Duration::from_millis(1);
+
+// Handed to whoever started something on the GPU, the map_async callback (or whatever
+// finishes it) calls finish. Cloned into the callback, the registry keeps the other end
+#[derive(Clone)]
+pub struct OpDone(Arc<AtomicBool>);
+
+impl OpDone {
+    pub fn finish(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+struct PendingOp {
+    name: String,
+    done: OpDone,
+    // Frame index it should have finished by
+    deadline: u64,
+    // Blocked on once already and still not done, reported and not blocked on again
+    stuck: bool,
+}
+
+// The one place the frame loop polls the device. Subsystems with async GPU work (buffer
+// maps mostly) register it here with a deadline in frames, instead of each polling on its
+// own. Past the deadline an operation gets a short blocking wait, and if that doesn't do it
+// a warning, so nothing hangs or goes missing quietly. Shutdown drains whatever's left
+pub struct Maintain {
+    frame: u64,
+    pending: Vec<PendingOp>,
+}
+
+impl Maintain {
+    pub fn new() -> Self {
+        Self {
+            frame: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    // Something that should be done within `frames` steps from now
+    pub fn register(&mut self, name: &str, frames: u64) -> OpDone {
+        let done = OpDone(Arc::new(AtomicBool::new(false)));
+        self.pending.push(PendingOp {
+            name: name.to_owned(),
+            done: done.clone(),
+            deadline: self.frame + frames,
+            stuck: false,
+        });
+        done
+    }
+
+    // Once a frame, after its submit. Polls without blocking, then waits on whatever's
+    // overdue for at most ESCALATION_TIMEOUT
+    pub fn step(&mut self, device: &wgpu::Device) {
+        self.frame += 1;
+        device.poll(wgpu::Maintain::Poll);
+        self.pending.retain(|op| !op.done.is_done());
+
+        let frame = self.frame;
+        for op in &mut self.pending {
+            if op.stuck || op.deadline > frame {
+                continue;
+            }
+            if !wait_bounded(device, &op.done, ESCALATION_TIMEOUT) {
+                log::warn!(
+                    "GPU operation \"{}\" is {} frame(s) overdue and still not done",
+                    op.name,
+                    frame - op.deadline
+                );
+                op.stuck = true;
+            }
+        }
+        self.pending.retain(|op| !op.done.is_done());
+    }
+
+    // For shutdown, waits up to `timeout` for everything registered. What's still not done
+    // is named in a warning, the count comes back
+    pub fn drain(&mut self, device: &wgpu::Device, timeout: Duration) -> usize {
+        let start = Instant::now();
+        while !self.pending.is_empty() && start.elapsed() < timeout {
+            device.poll(wgpu::Maintain::Poll);
+            self.pending.retain(|op| !op.done.is_done());
+            std::thread::sleep(WAIT_STEP);
+        }
+        for op in &self.pending {
+            log::warn!("GPU operation \"{}\" never finished", op.name);
+        }
+        std::mem::take(&mut self.pending).len()
+    }
+}
+
+// Polls until `done` or `timeout`, true when it finished. For callers that need a result
+// now but shouldn't hang on a device that stopped answering
+pub fn wait_bounded(device: &wgpu::Device, done: &OpDone, timeout: Duration) -> bool {
+    let start = Instant::now();
+    loop {
+        device.poll(wgpu::Maintain::Poll);
+        if done.is_done() {
+            return true;
+        }
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(WAIT_STEP);
+    }
+}
+
+// An OpDone nobody else tracks, for wait_bounded on its own
+pub fn untracked() -> OpDone {
+    OpDone(Arc::new(AtomicBool::new(false)))
+}
+
+#[cfg(test)]
+impl Maintain {
+    // What's still registered, and whether it's been given up on
+    fn pending(&self) -> Vec<(&str, bool)> {
+        self.pending
+            .iter()
+            .map(|op| (op.name.as_str(), op.stuck))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+
+    // A buffer map whose callback finishes `done`, the kind of thing subsystems register
+    fn readback(device: &wgpu::Device, done: OpDone) -> wgpu::Buffer {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Maintain Test Readback"),
+            size: 16,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                result.expect("Mapping a fresh buffer works");
+                done.finish();
+            });
+        buffer
+    }
+
+    #[test]
+    fn overdue_operations_get_one_bounded_wait_then_a_warning() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let mut maintain = Maintain::new();
+        let _never = maintain.register("never", 2);
+        // Frame 1, not due yet, so no blocking
+        let started = Instant::now();
+        maintain.step(&gpu.device);
+        assert!(started.elapsed() < ESCALATION_TIMEOUT);
+        assert_eq!(maintain.pending(), [("never", false)]);
+        // Frame 2 is the deadline, it's blocked on for the whole timeout and given up on
+        let started = Instant::now();
+        maintain.step(&gpu.device);
+        assert!(started.elapsed() >= ESCALATION_TIMEOUT);
+        assert_eq!(maintain.pending(), [("never", true)]);
+        // And not blocked on again
+        let started = Instant::now();
+        maintain.step(&gpu.device);
+        assert!(started.elapsed() < ESCALATION_TIMEOUT);
+        assert_eq!(maintain.pending(), [("never", true)]);
+    }
+
+    #[test]
+    fn an_operation_due_now_is_waited_for_in_the_same_step() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let mut maintain = Maintain::new();
+        // Like a screenshot asked for synchronously, due the frame it's made
+        let done = maintain.register("screenshot", 0);
+        let _buffer = readback(&gpu.device, done.clone());
+        let later = maintain.register("later", 10);
+        maintain.step(&gpu.device);
+        assert!(done.is_done());
+        assert_eq!(maintain.pending(), [("later", false)]);
+        later.finish();
+        maintain.step(&gpu.device);
+        assert!(maintain.pending().is_empty());
+    }
+
+    #[test]
+    fn drain_waits_out_what_finishes_and_counts_what_never_does() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let mut maintain = Maintain::new();
+        let done = maintain.register("readback", 3);
+        let _buffer = readback(&gpu.device, done.clone());
+        assert_eq!(maintain.drain(&gpu.device, Duration::from_secs(5)), 0);
+        assert!(done.is_done());
+
+        let done = maintain.register("readback", 3);
+        let _buffer = readback(&gpu.device, done.clone());
+        let _never = maintain.register("never", 3);
+        let started = Instant::now();
+        assert_eq!(maintain.drain(&gpu.device, Duration::from_millis(50)), 1);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(done.is_done());
+        // Drained means forgotten, even what didn't finish
+        assert!(maintain.pending().is_empty());
+        assert_eq!(maintain.drain(&gpu.device, Duration::from_secs(5)), 0);
+    }
+}
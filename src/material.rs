@@ -0,0 +1,551 @@
+use glam::Mat4;
+
+use crate::buffer_pool::BufferPool;
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::frame::Pass;
+use crate::frame_dump::{self, Command};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::mesh::Mesh;
+use crate::pipeline_bank::{self, BlendMode, DepthStage, RenderPipelineBank};
+use crate::reflect;
+
+// The one uniform buffer of MaterialParams every material's bind group has, and its alpha
+// mask. The mask is read with textureLoad, so it doesn't need a sampler
+const LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 2] = [
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 1,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+];
+
+// Per-item blocks, one buffer for the whole draw list and each item's block at its own
+// dynamic offset
+const ITEM_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 1] = [wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+    ty: wgpu::BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: None,
+    },
+    count: None,
+}];
+
+// Mirrors `struct Material` in the shaders that take one
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialParams {
+    tint: [f32; 4],
+    roughness: f32,
+    // Mask values below this are cut out, by BlendMode::Cutout materials only
+    cutoff: f32,
+    _padding: [f32; 2],
+}
+
+impl MaterialParams {
+    pub fn new(tint: RgbaColor, roughness: f32) -> Self {
+        Self {
+            tint: tint.to_f32_array_linear(),
+            roughness,
+            cutoff: 0.5,
+            _padding: [0.0; 2],
+        }
+    }
+
+    pub fn with_cutoff(mut self, cutoff: f32) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+
+    // Linear RGB of the tint
+    #[cfg(feature = "obj")]
+    pub fn diffuse(&self) -> [f32; 3] {
+        [self.tint[0], self.tint[1], self.tint[2]]
+    }
+}
+
+// A pipeline plus the per-material state it's drawn with
+pub struct Material {
+    pub name: String,
+    // The bank entry for the pipeline in this material's blend mode, see BlendMode::key
+    pub pipeline: String,
+    pub blend: BlendMode,
+    // Lower draws first, ahead of the pipeline name, see draw_sorted
+    pub sort_key: u32,
+    // What the OBJ export writes into the MTL, the GPU has its own copy
+    #[cfg(feature = "obj")]
+    pub params: MaterialParams,
+    // Size of the uniform block each item drawn with it brings, see declare_item_block
+    pub item_block: Option<u64>,
+    // Kept alive for the bind group
+    buffer: Tracked<wgpu::Buffer>,
+    // Kept alive for the bind group too. None for the library's all-white one, see set_mask
+    mask: Option<Tracked<wgpu::Texture>>,
+    bind_group: wgpu::BindGroup,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MaterialHandle(usize);
+
+// Owns every material and the bind group layout they share
+pub struct MaterialLibrary {
+    pub layout: wgpu::BindGroupLayout,
+    // For materials with an item block, bound at the material's group + 1
+    pub item_layout: wgpu::BindGroupLayout,
+    materials: Vec<Material>,
+    // 1x1 white, what materials without a mask of their own get
+    _white: Tracked<wgpu::Texture>,
+    white_view: wgpu::TextureView,
+}
+
+impl MaterialLibrary {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, memory: &GpuMemoryTracker) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Material Layout"),
+            entries: &LAYOUT_ENTRIES,
+        });
+        let item_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Item Block Layout"),
+            entries: &ITEM_LAYOUT_ENTRIES,
+        });
+        let (white, white_view) = upload_mask(device, queue, memory, "White Mask", (1, 1), &[255]);
+        Self {
+            layout,
+            item_layout,
+            materials: Vec::new(),
+            _white: white,
+            white_view,
+        }
+    }
+
+    // `pipeline` has to be registered in `blend` with RenderPipelineBank::register_blends
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        &mut self,
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        pipeline: &str,
+        blend: BlendMode,
+        params: MaterialParams,
+        sort_key: u32,
+    ) -> MaterialHandle {
+        let buffer = memory.create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{name} Material")),
+                contents: bytemuck::bytes_of(&params),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+            MemoryCategory::Uniforms,
+        );
+        let bind_group = self.bind_group(device, name, &buffer, &self.white_view);
+        self.materials.push(Material {
+            name: name.to_owned(),
+            pipeline: blend.key(pipeline),
+            blend,
+            sort_key,
+            #[cfg(feature = "obj")]
+            params,
+            item_block: None,
+            buffer,
+            mask: None,
+            bind_group,
+        });
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    // The mask's red channel is what a Cutout material compares against its cutoff, see
+    // upload_mask. The shader maps it over the mesh's local xy from -0.5 to 0.5. Textured
+    // materials put their base color here instead, see upload_texture
+    pub fn set_mask(
+        &mut self,
+        device: &wgpu::Device,
+        handle: MaterialHandle,
+        (texture, view): (Tracked<wgpu::Texture>, wgpu::TextureView),
+    ) {
+        let material = &self.materials[handle.0];
+        let bind_group = self.bind_group(device, &material.name, &material.buffer, &view);
+        let material = &mut self.materials[handle.0];
+        material.bind_group = bind_group;
+        material.mask.replace(texture);
+    }
+
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        name: &str,
+        buffer: &wgpu::Buffer,
+        mask: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(name),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(mask),
+                },
+            ],
+        })
+    }
+
+    pub fn get(&self, handle: MaterialHandle) -> &Material {
+        &self.materials[handle.0]
+    }
+
+    // Every item drawn with the material brings a `T`, its bytes set with
+    // DrawItem::set_block. `T` mirrors a WGSL struct in the uniform address space, so its
+    // size has to be a multiple of 16 (pad it like MaterialParams)
+    pub fn declare_item_block<T: bytemuck::Pod>(
+        &mut self,
+        handle: MaterialHandle,
+    ) -> Result<(), ForayError> {
+        let material = &mut self.materials[handle.0];
+        let size = std::mem::size_of::<T>() as u64;
+        if size == 0 || !size.is_multiple_of(16) {
+            return Err(ForayError::ItemBlock {
+                material: material.name.clone(),
+                reason: format!(
+                    "{} is {size} bytes, uniform blocks come in multiples of 16",
+                    std::any::type_name::<T>()
+                ),
+            });
+        }
+        material.item_block = Some(size);
+        Ok(())
+    }
+
+    // Checks every material's pipeline takes the material layout at `group`, as draw_sorted
+    // will bind it, by the bindings reflected from its shader. Pipelines built without a
+    // Reflection are taken on trust
+    pub fn check(&self, bank: &RenderPipelineBank, group: u32) -> Result<(), ForayError> {
+        for material in &self.materials {
+            let pipeline = bank.resolve(&material.pipeline)?;
+            if let Some(bindings) = &pipeline.bindings {
+                reflect::check(&material.pipeline, group, bindings, &LAYOUT_ENTRIES)?;
+                if material.item_block.is_some() {
+                    reflect::check(
+                        &material.pipeline,
+                        group + 1,
+                        bindings,
+                        &ITEM_LAYOUT_ENTRIES,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A single channel alpha mask for MaterialLibrary::set_mask, one byte per texel, rows
+// from the top
+pub fn upload_mask(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    memory: &GpuMemoryTracker,
+    label: &str,
+    (width, height): (u32, u32),
+    texels: &[u8],
+) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = memory.create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        MemoryCategory::Textures,
+    );
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        texels,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+// An sRGB color texture for the same slot, for pipelines that read their base color from
+// it (deferred_textured) instead of a coverage mask
+#[cfg(feature = "gltf")]
+pub fn upload_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    memory: &GpuMemoryTracker,
+    label: &str,
+    image: &image::RgbaImage,
+) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+    let size = wgpu::Extent3d {
+        width: image.width(),
+        height: image.height(),
+        depth_or_array_layers: 1,
+    };
+    let texture = memory.create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        MemoryCategory::Textures,
+    );
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        image.as_raw(),
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(image.width() * 4),
+            rows_per_image: Some(image.height()),
+        },
+        size,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+// One mesh, or one submesh of it, drawn with one material somewhere in the world
+pub struct DrawItem<'m> {
+    pub mesh: &'m Mesh,
+    pub submesh: Option<usize>,
+    pub material: MaterialHandle,
+    pub transform: Mat4,
+    // The bytes of the material's item block, empty when it declares none
+    pub block: Vec<u8>,
+}
+
+impl DrawItem<'_> {
+    // Err unless the item's material declared a block of exactly `T`'s size
+    pub fn set_block<T: bytemuck::Pod>(
+        &mut self,
+        library: &MaterialLibrary,
+        block: &T,
+    ) -> Result<(), ForayError> {
+        let material = library.get(self.material);
+        let size = std::mem::size_of::<T>() as u64;
+        let reason = match material.item_block {
+            Some(declared) if declared == size => {
+                self.block = bytemuck::bytes_of(block).to_vec();
+                return Ok(());
+            }
+            Some(declared) => format!(
+                "it declares {declared} bytes, {} is {size}",
+                std::any::type_name::<T>()
+            ),
+            None => "it declares no item block".to_owned(),
+        };
+        Err(ForayError::ItemBlock {
+            material: material.name.clone(),
+            reason,
+        })
+    }
+}
+
+// Pipelines used through draw_sorted take the item's transform as a per-instance mat4 at
+// locations 3 to 6, in vertex buffer slot 1
+pub fn transform_layout() -> wgpu::VertexBufferLayout<'static> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+    ];
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Mat4>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &ATTRIBUTES,
+    }
+}
+
+// Draws sorted by material so the pipeline and the material's bind group (at
+// `material_group`) are only set when they change from one item to the next. Materials
+// that blend go after the ones that overwrite (Cutout ones included), whatever their sort
+// keys. Item blocks all go
+// into one uniform buffer, bound at `material_group + 1` with the item's offset.
+// `stage` picks the opaque materials' pipeline variant: a Prepass draw stops at the first
+// material that blends, and blending ones always draw with their own pipeline
+#[allow(clippy::too_many_arguments)]
+pub fn draw_sorted(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pool: &mut BufferPool,
+    pass: &mut Pass,
+    bank: &RenderPipelineBank,
+    library: &MaterialLibrary,
+    items: &mut [DrawItem],
+    material_group: u32,
+    stage: DepthStage,
+) -> Result<(), ForayError> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    items.sort_by(|a, b| {
+        let (a_material, b_material) = (library.get(a.material), library.get(b.material));
+        (
+            a_material.blend.blends(),
+            a_material.sort_key,
+            &a_material.pipeline,
+            a.material,
+        )
+            .cmp(&(
+                b_material.blend.blends(),
+                b_material.sort_key,
+                &b_material.pipeline,
+                b.material,
+            ))
+    });
+
+    // Every transform in draw order, item i is instance i
+    let transforms: Vec<Mat4> = items.iter().map(|item| item.transform).collect();
+    let bytes: &[u8] = bytemuck::cast_slice(&transforms);
+    let transform_buffer = pool.acquire(
+        device,
+        "Draw List Transforms",
+        wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        bytes.len() as u64,
+    );
+    queue.write_buffer(&transform_buffer, 0, bytes);
+    pass.raw
+        .set_vertex_buffer(1, transform_buffer.slice(..bytes.len() as u64));
+    let blocks = upload_blocks(device, queue, pool, library, items);
+
+    let mut bound_pipeline: Option<String> = None;
+    let mut bound_material = None;
+    for (instance, item) in (0u32..).zip(items.iter()) {
+        let material = library.get(item.material);
+        let pipeline = match stage {
+            _ if !material.blend.blends() => stage.key(&material.pipeline),
+            // Sorted last, nothing opaque comes after
+            DepthStage::Prepass => break,
+            _ => material.pipeline.clone(),
+        };
+        let pipeline = pipeline_bank::sample_key(&pipeline, pass.samples());
+        if bound_pipeline.as_ref() != Some(&pipeline) {
+            pass.set_pipeline(bank, &pipeline)?;
+            bound_pipeline = Some(pipeline);
+        }
+        if bound_material != Some(item.material) {
+            pass.raw
+                .set_bind_group(material_group, &material.bind_group, &[]);
+            pass.record(|| Command::Material {
+                name: material.name.clone(),
+                group: material_group,
+                sort_key: material.sort_key,
+                blend: format!("{:?}", material.blend),
+            });
+            bound_material = Some(item.material);
+        }
+        if let (Some(_), Some((bind_group, stride))) = (material.item_block, &blocks) {
+            // A few hundred bytes times the item count, nowhere near u32::MAX
+            let offset = instance * *stride as u32;
+            pass.raw
+                .set_bind_group(material_group + 1, bind_group, &[offset]);
+            pass.record(|| Command::ItemBlock {
+                group: material_group + 1,
+                offset,
+                bytes: frame_dump::hex(&item.block),
+            });
+        }
+        pass.raw.push_debug_group(&material.name);
+        let instances = instance..instance + 1;
+        let drawn = match item.submesh {
+            Some(index) => pass.draw_submesh(item.mesh, index, instances),
+            None => pass.draw_mesh_instances(item.mesh, instances),
+        };
+        pass.raw.pop_debug_group();
+        drawn?;
+    }
+    Ok(())
+}
+
+// Every item's block at `stride` times its place in the draw list, zeros for the items that
+// didn't set one. None when no material in the list declares a block
+fn upload_blocks(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pool: &mut BufferPool,
+    library: &MaterialLibrary,
+    items: &[DrawItem],
+) -> Option<(wgpu::BindGroup, u64)> {
+    let size = items
+        .iter()
+        .filter_map(|item| library.get(item.material).item_block)
+        .max()?;
+    let alignment = u64::from(device.limits().min_uniform_buffer_offset_alignment);
+    let stride = size.div_ceil(alignment) * alignment;
+    let mut bytes = vec![0u8; stride as usize * items.len()];
+    for (slot, item) in bytes.chunks_mut(stride as usize).zip(items) {
+        slot[..item.block.len()].copy_from_slice(&item.block);
+    }
+    let buffer = pool.acquire(
+        device,
+        "Draw List Item Blocks",
+        wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        bytes.len() as u64,
+    );
+    queue.write_buffer(&buffer, 0, &bytes);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Draw List Item Blocks"),
+        layout: &library.item_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size),
+            }),
+        }],
+    });
+    Some((bind_group, stride))
+}
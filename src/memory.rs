@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use wgpu::util::DeviceExt;
+
+// Used without --memory-budget. wgpu can't say how much memory the GPU has, so this is a
+// guess that even integrated GPUs should manage
+pub const DEFAULT_BUDGET: u64 = 1024 * 1024 * 1024;
+// Allocations named in the over budget warning
+const BUDGET_OFFENDERS: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryCategory {
+    Meshes,
+    Textures,
+    Targets,
+    Uniforms,
+    // Per-frame buffers handed out by the BufferPool
+    Transient,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 5] = [
+        MemoryCategory::Meshes,
+        MemoryCategory::Textures,
+        MemoryCategory::Targets,
+        MemoryCategory::Uniforms,
+        MemoryCategory::Transient,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MemoryCategory::Meshes => "meshes",
+            MemoryCategory::Textures => "textures",
+            MemoryCategory::Targets => "targets",
+            MemoryCategory::Uniforms => "uniforms",
+            MemoryCategory::Transient => "transient",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// Bytes currently alive per category, plus how many allocations make them up
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub bytes: [u64; 5],
+    pub allocations: [u32; 5],
+    // Part of the transient bytes sitting idle in the pool rather than in use this frame
+    pub pool_idle: u64,
+}
+
+impl MemoryReport {
+    pub fn bytes_in(&self, category: MemoryCategory) -> u64 {
+        self.bytes[category.index()]
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.iter().sum()
+    }
+}
+
+#[derive(Default)]
+struct Ledger {
+    report: MemoryReport,
+    // Every live allocation by id, so leaks can be traced back to a label
+    live: BTreeMap<u64, (String, MemoryCategory, u64)>,
+    // The scene that was up when each allocation was made, for the ones made under one
+    scenes: BTreeMap<u64, String>,
+    scene: Option<String>,
+    next_id: u64,
+}
+
+// Counts what we allocated on the GPU. It's an estimate from sizes and formats,
+// wgpu doesn't tell us what the driver really reserved.
+#[derive(Clone, Default)]
+pub struct GpuMemoryTracker {
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+impl GpuMemoryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        self.ledger.lock().expect("Memory tracker poisoned").report
+    }
+
+    // Allocations from here on are put down to `scene` until the next call, None for the
+    // app's own. Whatever a scene still holds after it's been switched away from is a leak
+    pub fn set_scene(&self, scene: Option<&str>) {
+        self.ledger.lock().expect("Memory tracker poisoned").scene = scene.map(str::to_owned);
+    }
+
+    // Live bytes per scene they were allocated under, by scene name
+    pub fn scene_bytes(&self) -> Vec<(String, u64)> {
+        let ledger = self.ledger.lock().expect("Memory tracker poisoned");
+        let mut totals: BTreeMap<&str, u64> = BTreeMap::new();
+        for (id, scene) in &ledger.scenes {
+            *totals.entry(scene).or_default() += ledger.live.get(id).map_or(0, |live| live.2);
+        }
+        totals
+            .into_iter()
+            .map(|(scene, bytes)| (scene.to_owned(), bytes))
+            .collect()
+    }
+
+    pub fn set_pool_idle(&self, bytes: u64) {
+        self.ledger
+            .lock()
+            .expect("Memory tracker poisoned")
+            .report
+            .pool_idle = bytes;
+    }
+
+    // Warns when more than `budget` bytes are alive, naming the largest allocations so it's
+    // clear what to cut. Returns whether it was over
+    pub fn check_budget(&self, budget: u64) -> bool {
+        let total = self.report().total_bytes();
+        if total <= budget {
+            return false;
+        }
+        let mut largest = self.live_allocations();
+        largest.sort_by_key(|&(_, _, bytes)| std::cmp::Reverse(bytes));
+        let largest: Vec<String> = largest
+            .iter()
+            .take(BUDGET_OFFENDERS)
+            .map(|(label, category, bytes)| {
+                format!("{label} ({}, {})", category.name(), format_bytes(*bytes))
+            })
+            .collect();
+        log::warn!(
+            "GPU memory at {} is over the {} budget, largest: {}",
+            format_bytes(total),
+            format_bytes(budget),
+            largest.join(", ")
+        );
+        true
+    }
+
+    // (label, category, bytes) of everything still alive, oldest first
+    pub fn live_allocations(&self) -> Vec<(String, MemoryCategory, u64)> {
+        let ledger = self.ledger.lock().expect("Memory tracker poisoned");
+        ledger.live.values().cloned().collect()
+    }
+
+    fn track<T>(
+        &self,
+        resource: T,
+        label: Option<&str>,
+        category: MemoryCategory,
+        bytes: u64,
+    ) -> Tracked<T> {
+        let mut ledger = self.ledger.lock().expect("Memory tracker poisoned");
+        ledger.report.bytes[category.index()] += bytes;
+        ledger.report.allocations[category.index()] += 1;
+        let id = ledger.next_id;
+        ledger.next_id += 1;
+        let label = label.unwrap_or("unlabeled").to_owned();
+        ledger.live.insert(id, (label, category, bytes));
+        if let Some(scene) = ledger.scene.clone() {
+            ledger.scenes.insert(id, scene);
+        }
+        Tracked {
+            resource,
+            _guard: AllocationGuard {
+                ledger: Arc::clone(&self.ledger),
+                id,
+            },
+        }
+    }
+
+    pub fn create_buffer(
+        &self,
+        device: &wgpu::Device,
+        desc: &wgpu::BufferDescriptor,
+        category: MemoryCategory,
+    ) -> Tracked<wgpu::Buffer> {
+        self.track(device.create_buffer(desc), desc.label, category, desc.size)
+    }
+
+    pub fn create_buffer_init(
+        &self,
+        device: &wgpu::Device,
+        desc: &wgpu::util::BufferInitDescriptor,
+        category: MemoryCategory,
+    ) -> Tracked<wgpu::Buffer> {
+        let buffer = device.create_buffer_init(desc);
+        let bytes = buffer.size();
+        self.track(buffer, desc.label, category, bytes)
+    }
+
+    pub fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        desc: &wgpu::TextureDescriptor,
+        category: MemoryCategory,
+    ) -> Tracked<wgpu::Texture> {
+        self.track(
+            device.create_texture(desc),
+            desc.label,
+            category,
+            texture_bytes(desc),
+        )
+    }
+}
+
+// Sum over the mip chain, times samples. Combined depth-stencil formats have no single
+// block size so those are counted as 4 bytes per texel.
+fn texture_bytes(desc: &wgpu::TextureDescriptor) -> u64 {
+    let texel = u64::from(desc.format.block_copy_size(None).unwrap_or(4));
+    let (block_w, block_h) = desc.format.block_dimensions();
+    let layers = u64::from(desc.size.depth_or_array_layers);
+    (0..desc.mip_level_count)
+        .map(|level| {
+            let w = (desc.size.width >> level).max(1).div_ceil(block_w);
+            let h = (desc.size.height >> level).max(1).div_ceil(block_h);
+            u64::from(w) * u64::from(h) * texel * layers
+        })
+        .sum::<u64>()
+        * u64::from(desc.sample_count)
+}
+
+// Takes the bytes back out of the tracker when the resource goes away
+struct AllocationGuard {
+    ledger: Arc<Mutex<Ledger>>,
+    id: u64,
+}
+
+impl Drop for AllocationGuard {
+    fn drop(&mut self) {
+        if let Ok(mut ledger) = self.ledger.lock() {
+            ledger.scenes.remove(&self.id);
+            if let Some((_, category, bytes)) = ledger.live.remove(&self.id) {
+                ledger.report.bytes[category.index()] -= bytes;
+                ledger.report.allocations[category.index()] -= 1;
+            }
+        }
+    }
+}
+
+// A buffer or texture that shows up in the tracker for as long as it lives
+pub struct Tracked<T> {
+    resource: T,
+    _guard: AllocationGuard,
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.resource
+    }
+}
+
+// Human readable sizes for the overlay
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for a buffer or texture, the ledger only sees labels and sizes
+    fn allocate(
+        memory: &GpuMemoryTracker,
+        label: &str,
+        category: MemoryCategory,
+        bytes: u64,
+    ) -> Tracked<()> {
+        memory.track((), Some(label), category, bytes)
+    }
+
+    #[test]
+    fn scene_push_and_pop_returns_to_baseline() {
+        let memory = GpuMemoryTracker::new();
+        let _app = allocate(&memory, "Surface Depth", MemoryCategory::Targets, 4096);
+        let baseline = memory.report();
+        for round in 0..2 {
+            memory.set_scene(Some("stress"));
+            let scene = [
+                allocate(&memory, "Shapes", MemoryCategory::Meshes, 1000 + round),
+                allocate(&memory, "Outline", MemoryCategory::Meshes, 300),
+                allocate(&memory, "Atlas", MemoryCategory::Textures, 65536),
+            ];
+            assert_eq!(
+                memory.scene_bytes(),
+                vec![("stress".to_owned(), 66836 + round)]
+            );
+            assert_eq!(
+                memory.report().total_bytes(),
+                baseline.total_bytes() + 66836 + round
+            );
+            memory.set_scene(None);
+            drop(scene);
+            assert_eq!(memory.report(), baseline);
+            assert!(memory.scene_bytes().is_empty());
+        }
+        assert_eq!(memory.live_allocations().len(), 1);
+    }
+
+    #[test]
+    fn leftovers_stay_with_the_scene_they_were_made_under() {
+        let memory = GpuMemoryTracker::new();
+        memory.set_scene(Some("starter"));
+        let leaked = allocate(&memory, "Starter Target", MemoryCategory::Targets, 512);
+        memory.set_scene(Some("stress"));
+        let _current = allocate(&memory, "Stress Shapes", MemoryCategory::Meshes, 2048);
+        assert_eq!(
+            memory.scene_bytes(),
+            vec![("starter".to_owned(), 512), ("stress".to_owned(), 2048)]
+        );
+        drop(leaked);
+        assert_eq!(memory.scene_bytes(), vec![("stress".to_owned(), 2048)]);
+        assert_eq!(memory.report().bytes_in(MemoryCategory::Targets), 0);
+    }
+
+    #[test]
+    fn texture_bytes_count_mips_layers_and_samples() {
+        let desc = |mips, layers, samples| wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 16,
+                height: 8,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: mips,
+            sample_count: samples,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+        assert_eq!(texture_bytes(&desc(1, 1, 1)), 16 * 8 * 4);
+        // 16x8, 8x4, 4x2, 2x1, 1x1
+        assert_eq!(texture_bytes(&desc(5, 1, 1)), (128 + 32 + 8 + 2 + 1) * 4);
+        assert_eq!(texture_bytes(&desc(1, 6, 4)), 16 * 8 * 4 * 6 * 4);
+    }
+}
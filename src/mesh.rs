@@ -0,0 +1,1230 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use glam::{Vec2, Vec3};
+
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::mesh_arena::{ArenaSlot, BufferSetId};
+
+// Stands for a vertex buffer layout (stride, step mode and every attribute), so a mesh
+// and a pipeline can be checked against each other without keeping the layouts around
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayoutId(u64);
+
+impl VertexLayoutId {
+    pub fn of(layout: &wgpu::VertexBufferLayout) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        layout.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+impl fmt::Display for VertexLayoutId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+// Which member of a pipeline family draws this topology, see Pass::set_pipeline_for
+pub fn family_variant(topology: wgpu::PrimitiveTopology) -> &'static str {
+    match topology {
+        wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip => "fill",
+        wgpu::PrimitiveTopology::LineList | wgpu::PrimitiveTopology::LineStrip => "line",
+        wgpu::PrimitiveTopology::PointList => "point",
+    }
+}
+
+pub enum Indices<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+
+// A named part of a mesh, drawn on its own with Pass::draw_submesh
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    pub name: String,
+    // Into the indices, or the vertices for meshes without indices
+    pub index_range: Range<u32>,
+}
+
+// CPU side of an indexed mesh, before it's uploaded with Mesh::from_data
+#[derive(Clone, Debug)]
+pub struct MeshData<V> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<SubMesh>,
+}
+
+impl<V> MeshData<V> {
+    // One submesh covering everything
+    pub fn new(name: &str, vertices: Vec<V>, indices: Vec<u32>) -> Self {
+        let submeshes = vec![SubMesh {
+            name: name.to_owned(),
+            index_range: 0..indices.len() as u32,
+        }];
+        Self {
+            vertices,
+            indices,
+            submeshes,
+        }
+    }
+
+    // Everything in one buffer pair, each input's submeshes carry over with shifted ranges
+    pub fn merge(parts: impl IntoIterator<Item = MeshData<V>>) -> Self {
+        let mut merged = Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            submeshes: Vec::new(),
+        };
+        for part in parts {
+            let base_vertex = merged.vertices.len() as u32;
+            let base_index = merged.indices.len() as u32;
+            merged
+                .indices
+                .extend(part.indices.iter().map(|i| i + base_vertex));
+            merged.vertices.extend(part.vertices);
+            merged
+                .submeshes
+                .extend(part.submeshes.into_iter().map(|submesh| SubMesh {
+                    name: submesh.name,
+                    index_range: submesh.index_range.start + base_index
+                        ..submesh.index_range.end + base_index,
+                }));
+        }
+        merged
+    }
+}
+
+// Vertex types MeshData::decimate and bounds can work with
+pub trait Position {
+    fn position(&self) -> Vec3;
+}
+
+impl Position for Vec3 {
+    fn position(&self) -> Vec3 {
+        *self
+    }
+}
+
+// Both ways round, so (a, b) and (b, a) are the same edge
+fn edge(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+fn normal(positions: [Vec3; 3]) -> Vec3 {
+    (positions[1] - positions[0]).cross(positions[2] - positions[0])
+}
+
+// Which vertices are on an open edge, Err when an edge isn't shared by one or two triangles
+// running opposite ways
+fn boundary(triangles: &[Option<([u32; 3], usize)>], vertices: usize) -> Result<Vec<bool>, String> {
+    // Each edge in at most two triangles, and in opposite directions when in two
+    let mut directed = HashSet::new();
+    let mut uses: HashMap<(u32, u32), u32> = HashMap::new();
+    for ([a, b, c], _) in triangles.iter().flatten() {
+        if a == b || b == c || c == a {
+            return Err(format!("triangle ({a}, {b}, {c}) is degenerate"));
+        }
+        for (from, to) in [(*a, *b), (*b, *c), (*c, *a)] {
+            if !directed.insert((from, to)) {
+                return Err(format!("edge {from}-{to} is used the same way round twice"));
+            }
+            let count = uses.entry(edge(from, to)).or_default();
+            *count += 1;
+            if *count > 2 {
+                return Err(format!(
+                    "edge {from}-{to} is shared by more than two triangles"
+                ));
+            }
+        }
+    }
+    // Vertices on an open edge stay put, collapsing them would eat into the outline
+    let mut boundary = vec![false; vertices];
+    for (&(a, b), _) in uses.iter().filter(|(_, &count)| count == 1) {
+        boundary[a as usize] = true;
+        boundary[b as usize] = true;
+    }
+    Ok(boundary)
+}
+
+impl<V: Position + Clone> MeshData<V> {
+    // Object space box around every vertex
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        self.vertices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), vertex| (min.min(vertex.position()), max.max(vertex.position())),
+        )
+    }
+
+    // About `target_ratio` of the triangles, by collapsing the shortest edges first. An edge
+    // collapses by moving one end onto the other, so surviving vertices keep their
+    // attributes as they are. Collapses that would pinch the surface, flip a triangle or
+    // move a boundary are skipped, which keeps a manifold mesh closed and its open edges
+    // where they were. Stops early once nothing more can go. Err for non-manifold input,
+    // `name` is for that error. Triangle lists only
+    pub fn decimate(&self, name: &str, target_ratio: f32) -> Result<Self, ForayError> {
+        let non_manifold = |reason: String| ForayError::NonManifold {
+            mesh: name.to_owned(),
+            reason,
+        };
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(non_manifold(format!(
+                "{} indices aren't whole triangles",
+                self.indices.len()
+            )));
+        }
+        // Triangles with the submesh they're in, None once collapsed away
+        let mut triangles: Vec<Option<([u32; 3], usize)>> = self
+            .submeshes
+            .iter()
+            .enumerate()
+            .flat_map(|(submesh, part)| {
+                let range = part.index_range.start as usize..part.index_range.end as usize;
+                self.indices[range]
+                    .chunks_exact(3)
+                    .map(move |t| Some(([t[0], t[1], t[2]], submesh)))
+            })
+            .collect();
+
+        let boundary = boundary(&triangles, self.vertices.len()).map_err(non_manifold)?;
+        let position = |index: u32| self.vertices[index as usize].position();
+        let mut alive = triangles.len();
+        // Below four triangles a closed mesh can only fold flat
+        let target = ((alive as f32 * target_ratio.clamp(0.0, 1.0)).ceil() as usize).max(4);
+        while alive > target {
+            // Rebuilt every round, a round only touches vertices nothing else changed near
+            let mut around: Vec<Vec<usize>> = vec![Vec::new(); self.vertices.len()];
+            for (index, (corners, _)) in triangles
+                .iter()
+                .enumerate()
+                .filter_map(|(index, t)| t.map(|t| (index, t)))
+            {
+                for corner in corners {
+                    around[corner as usize].push(index);
+                }
+            }
+            let mut edges: Vec<(f32, u32, u32)> = triangles
+                .iter()
+                .flatten()
+                .flat_map(|([a, b, c], _)| [edge(*a, *b), edge(*b, *c), edge(*c, *a)])
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .map(|(a, b)| (position(a).distance_squared(position(b)), a, b))
+                .collect();
+            edges.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+            let mut touched = vec![false; self.vertices.len()];
+            let mut collapsed = false;
+            for (_, a, b) in edges {
+                if alive <= target {
+                    break;
+                }
+                if touched[a as usize] || touched[b as usize] {
+                    continue;
+                }
+                // `gone` moves onto `kept`, only ever an interior vertex
+                let (kept, gone) = match (boundary[a as usize], boundary[b as usize]) {
+                    (false, _) => (b, a),
+                    (true, false) => (a, b),
+                    (true, true) => continue,
+                };
+                let neighbours = |vertex: u32| -> HashSet<u32> {
+                    around[vertex as usize]
+                        .iter()
+                        .filter_map(|&t| triangles[t])
+                        .flat_map(|(corners, _)| corners)
+                        .filter(|&corner| corner != vertex)
+                        .collect()
+                };
+                let shared: Vec<usize> = around[gone as usize]
+                    .iter()
+                    .copied()
+                    .filter(|&t| triangles[t].is_some_and(|(c, _)| c.contains(&kept)))
+                    .collect();
+                // Only the two triangles on the edge may have both ends, anything more and
+                // the collapse would pinch the surface into a non-manifold spot
+                let (kept_ring, gone_ring) = (neighbours(kept), neighbours(gone));
+                let common = kept_ring.intersection(&gone_ring).count();
+                if shared.len() != 2 || common != 2 {
+                    continue;
+                }
+                let flips = around[gone as usize].iter().any(|&t| {
+                    let Some((corners, _)) = triangles[t] else {
+                        return false;
+                    };
+                    if corners.contains(&kept) {
+                        return false;
+                    }
+                    let before = corners.map(position);
+                    let after = corners.map(|c| position(if c == gone { kept } else { c }));
+                    normal(before).dot(normal(after)) <= 0.0
+                });
+                if flips {
+                    continue;
+                }
+
+                for &t in &around[gone as usize] {
+                    if shared.contains(&t) {
+                        triangles[t] = None;
+                        alive -= 1;
+                    } else if let Some((corners, _)) = &mut triangles[t] {
+                        for corner in corners.iter_mut().filter(|c| **c == gone) {
+                            *corner = kept;
+                        }
+                    }
+                }
+                for vertex in kept_ring.into_iter().chain(gone_ring) {
+                    touched[vertex as usize] = true;
+                }
+                collapsed = true;
+            }
+            if !collapsed {
+                break;
+            }
+        }
+
+        // Packed back together per submesh, dropping the vertices nothing uses anymore
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut submeshes = Vec::new();
+        for (submesh, part) in self.submeshes.iter().enumerate() {
+            let start = indices.len() as u32;
+            for (corners, _) in triangles.iter().flatten().filter(|(_, s)| *s == submesh) {
+                for &corner in corners {
+                    let index = *remap.entry(corner).or_insert_with(|| {
+                        vertices.push(self.vertices[corner as usize].clone());
+                        vertices.len() as u32 - 1
+                    });
+                    indices.push(index);
+                }
+            }
+            submeshes.push(SubMesh {
+                name: part.name.clone(),
+                index_range: start..indices.len() as u32,
+            });
+        }
+        Ok(Self {
+            vertices,
+            indices,
+            submeshes,
+        })
+    }
+}
+
+// A smaller stand-in for the vertex type `Full`, with normalized integer formats where the
+// values' range allows. The shaders don't change: wgpu hands normalized formats to them as
+// floats, and extra components past what the shader declares are dropped. Pipelines get a
+// specialization for desc()'s layout, see RenderPipelineBank::specialize
+pub trait PackedVertex: bytemuck::Pod + Position {
+    type Full;
+
+    fn pack(full: &Self::Full) -> Self;
+    fn desc() -> wgpu::VertexBufferLayout<'static>;
+}
+
+impl<V> MeshData<V> {
+    // The same mesh in a packed vertex format, see PackedVertex
+    pub fn pack_into<P: PackedVertex<Full = V>>(&self) -> MeshData<P> {
+        let count = self.vertices.len();
+        log::debug!(
+            "Packed {count} vertices into {} bytes instead of {}",
+            count * std::mem::size_of::<P>(),
+            count * std::mem::size_of::<V>()
+        );
+        MeshData {
+            vertices: self.vertices.iter().map(P::pack).collect(),
+            indices: self.indices.clone(),
+            submeshes: self.submeshes.clone(),
+        }
+    }
+}
+
+// -1..1 to Snorm16, off by at most 1/65534 once unpacked. Outside the range clamps
+pub fn snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+pub fn unpack_snorm16(value: i16) -> f32 {
+    (f32::from(value) / f32::from(i16::MAX)).max(-1.0)
+}
+
+// 0..1 to Unorm8, off by at most 1/510 once unpacked. Fine for colors in the shaders'
+// linear space down to the darkest few steps, which band
+pub fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Vertex types bake_colors_from_image can write to, colors are linear like the shaders want
+pub trait VertexColor: Position {
+    fn set_color(&mut self, color: [f32; 3]);
+}
+
+// How bake_colors_from_image finds where a vertex samples. Positions are taken in x and y
+// relative to the box around the vertices, 0 to 1 across it, then scaled and offset
+#[derive(Copy, Clone, Debug)]
+pub struct UvMapping {
+    // Image rows go down, so by default v is flipped to keep the picture upright for y up
+    pub flip_v: bool,
+    pub scale: Vec2,
+    pub offset: Vec2,
+}
+
+impl Default for UvMapping {
+    fn default() -> Self {
+        Self {
+            flip_v: true,
+            scale: Vec2::ONE,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+// Colors every vertex with the image, bilinearly filtered, at its mapped position. Texels
+// are centered like on the GPU and samples past the edge clamp to it. The image is taken as
+// sRGB and the colors come out linear
+pub fn bake_vertex_colors<V: VertexColor>(
+    vertices: &mut [V],
+    image: &image::RgbaImage,
+    mapping: UvMapping,
+) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let (min, max) = vertices.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), vertex| {
+            let p = vertex.position().truncate();
+            (min.min(p), max.max(p))
+        },
+    );
+    // A flat box (a line of vertices, or just one) maps to the middle on that axis
+    let size = (max - min).max(Vec2::splat(f32::EPSILON));
+    // Decoded once per channel value instead of four times per vertex
+    let linear: Vec<f32> = (0..=255u8)
+        .map(|value| {
+            RgbaColor::from_unorm8([value, value, value, u8::MAX]).to_f32_array_linear()[0]
+        })
+        .collect();
+    let texel = |x: i64, y: i64| {
+        let x = x.clamp(0, i64::from(width) - 1) as u32;
+        let y = y.clamp(0, i64::from(height) - 1) as u32;
+        let [red, green, blue, _] = image.get_pixel(x, y).0;
+        Vec3::new(
+            linear[usize::from(red)],
+            linear[usize::from(green)],
+            linear[usize::from(blue)],
+        )
+    };
+    for vertex in vertices {
+        let mut uv = (vertex.position().truncate() - min) / size;
+        if mapping.flip_v {
+            uv.y = 1.0 - uv.y;
+        }
+        let uv = uv * mapping.scale + mapping.offset;
+        // In texels, 0 is the middle of the first one
+        let x = uv.x * width as f32 - 0.5;
+        let y = uv.y * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = texel(x0, y0).lerp(texel(x0 + 1, y0), tx);
+        let bottom = texel(x0, y0 + 1).lerp(texel(x0 + 1, y0 + 1), tx);
+        vertex.set_color(top.lerp(bottom, ty).to_array());
+    }
+}
+
+impl<V: VertexColor> MeshData<V> {
+    // See bake_vertex_colors. Vertices shared between triangles get one color, so a mesh
+    // needs enough of them, well indexed, to show more than a blur of the image
+    pub fn bake_colors_from_image(&mut self, image: &image::RgbaImage, mapping: UvMapping) {
+        bake_vertex_colors(&mut self.vertices, image, mapping);
+    }
+}
+
+// Triangles `count` indices (or vertices) of `topology` make, 0 for points and lines
+pub fn triangles(topology: wgpu::PrimitiveTopology, count: u32) -> u32 {
+    match topology {
+        wgpu::PrimitiveTopology::TriangleList => count / 3,
+        wgpu::PrimitiveTopology::TriangleStrip => count.saturating_sub(2),
+        _ => 0,
+    }
+}
+
+// Where a mesh's vertices and indices are
+enum Buffers {
+    Own {
+        id: BufferSetId,
+        vertex_buffer: Tracked<wgpu::Buffer>,
+        index_buffer: Option<(Tracked<wgpu::Buffer>, wgpu::IndexFormat)>,
+    },
+    // Ranges of a MeshArena's buffers, always u32 indexed
+    Arena(ArenaSlot),
+}
+
+impl Buffers {
+    fn id(&self) -> BufferSetId {
+        match self {
+            Buffers::Own { id, .. } => *id,
+            Buffers::Arena(slot) => slot.id(),
+        }
+    }
+}
+
+// Vertex (and maybe index) buffer plus what it takes to draw it with the right pipeline
+pub struct Mesh {
+    pub name: String,
+    pub topology: wgpu::PrimitiveTopology,
+    pub layout: VertexLayoutId,
+    buffers: Buffers,
+    // Indices when indexed, vertices otherwise
+    count: u32,
+    pub submeshes: Vec<SubMesh>,
+    // Positions and indices of meshes made with from_data, what obj::export writes out
+    #[cfg(feature = "obj")]
+    pub geometry: Option<MeshData<Vec3>>,
+}
+
+impl Mesh {
+    pub fn new<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        layout: &wgpu::VertexBufferLayout,
+        topology: wgpu::PrimitiveTopology,
+        vertices: &[V],
+        indices: Indices,
+    ) -> Self {
+        Self::create(
+            device,
+            memory,
+            name,
+            layout,
+            topology,
+            vertices,
+            indices,
+            wgpu::BufferUsages::VERTEX,
+        )
+    }
+
+    // Same, but the vertices can be rewritten with write_vertices afterwards
+    pub fn new_dynamic<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        layout: &wgpu::VertexBufferLayout,
+        topology: wgpu::PrimitiveTopology,
+        vertices: &[V],
+        indices: Indices,
+    ) -> Self {
+        Self::create(
+            device,
+            memory,
+            name,
+            layout,
+            topology,
+            vertices,
+            indices,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        layout: &wgpu::VertexBufferLayout,
+        topology: wgpu::PrimitiveTopology,
+        vertices: &[V],
+        indices: Indices,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let vertex_buffer = memory.create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{name} Vertices")),
+                contents: bytemuck::cast_slice(vertices),
+                usage,
+            },
+            MemoryCategory::Meshes,
+        );
+        let index_label = format!("{name} Indices");
+        let index_buffer = |contents: &[u8]| {
+            memory.create_buffer_init(
+                device,
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some(&index_label),
+                    contents,
+                    usage: wgpu::BufferUsages::INDEX,
+                },
+                MemoryCategory::Meshes,
+            )
+        };
+        let (index_buffer, count) = match indices {
+            Indices::U16(indices) => (
+                Some((
+                    index_buffer(bytemuck::cast_slice(indices)),
+                    wgpu::IndexFormat::Uint16,
+                )),
+                indices.len(),
+            ),
+            Indices::U32(indices) => (
+                Some((
+                    index_buffer(bytemuck::cast_slice(indices)),
+                    wgpu::IndexFormat::Uint32,
+                )),
+                indices.len(),
+            ),
+        };
+
+        Self {
+            name: name.to_owned(),
+            topology,
+            layout: VertexLayoutId::of(layout),
+            buffers: Buffers::Own {
+                id: BufferSetId::next(),
+                vertex_buffer,
+                index_buffer,
+            },
+            count: count as u32,
+            submeshes: vec![SubMesh {
+                name: name.to_owned(),
+                index_range: 0..count as u32,
+            }],
+            #[cfg(feature = "obj")]
+            geometry: None,
+        }
+    }
+
+    // Indices go up as 16 bit when they fit
+    pub fn from_data<V: bytemuck::Pod + Position>(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        layout: &wgpu::VertexBufferLayout,
+        topology: wgpu::PrimitiveTopology,
+        data: &MeshData<V>,
+    ) -> Self {
+        let short: Option<Vec<u16>> = data
+            .indices
+            .iter()
+            .map(|&i| u16::try_from(i).ok())
+            .collect();
+        let indices = match &short {
+            Some(short) => Indices::U16(short),
+            None => Indices::U32(&data.indices),
+        };
+        let mut mesh = Self::new(
+            device,
+            memory,
+            name,
+            layout,
+            topology,
+            &data.vertices,
+            indices,
+        );
+        mesh.keep(data);
+        mesh
+    }
+
+    // What MeshArena::mesh_from_data makes once it has room for `data`
+    pub fn in_arena<V: Position>(
+        name: &str,
+        topology: wgpu::PrimitiveTopology,
+        layout: VertexLayoutId,
+        slot: ArenaSlot,
+        data: &MeshData<V>,
+    ) -> Self {
+        let mut mesh = Self {
+            name: name.to_owned(),
+            topology,
+            layout,
+            buffers: Buffers::Arena(slot),
+            count: data.indices.len() as u32,
+            submeshes: Vec::new(),
+            #[cfg(feature = "obj")]
+            geometry: None,
+        };
+        mesh.keep(data);
+        mesh
+    }
+
+    // What MeshArena::reserve makes, with nothing to draw until grow
+    pub fn reserved(
+        name: &str,
+        topology: wgpu::PrimitiveTopology,
+        layout: VertexLayoutId,
+        slot: ArenaSlot,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            topology,
+            layout,
+            buffers: Buffers::Arena(slot),
+            count: 0,
+            submeshes: vec![SubMesh {
+                name: name.to_owned(),
+                index_range: 0..0,
+            }],
+            #[cfg(feature = "obj")]
+            geometry: None,
+        }
+    }
+
+    // A reserved mesh drawing its first `count` indices, the ones that have landed
+    pub fn grow(&mut self, count: u32) {
+        self.count = count;
+        for submesh in &mut self.submeshes {
+            submesh.index_range.end = count;
+        }
+    }
+
+    // None for meshes with their own buffers
+    pub fn slot(&self) -> Option<&ArenaSlot> {
+        match &self.buffers {
+            Buffers::Own { .. } => None,
+            Buffers::Arena(slot) => Some(slot),
+        }
+    }
+
+    // Submeshes and the CPU copy of the positions from `data`
+    fn keep<V: Position>(&mut self, data: &MeshData<V>) {
+        self.submeshes.clone_from(&data.submeshes);
+        #[cfg(feature = "obj")]
+        {
+            self.geometry = Some(MeshData {
+                vertices: data.vertices.iter().map(Position::position).collect(),
+                indices: data.indices.clone(),
+                submeshes: data.submeshes.clone(),
+            });
+        }
+    }
+
+    // Binds the buffers unless `bound` says they already are, then draws `range` of them.
+    // The pipeline has been checked by the Pass. True when it had to bind
+    pub fn record(
+        &self,
+        pass: &mut wgpu::RenderPass,
+        bound: &mut Option<BufferSetId>,
+        range: Range<u32>,
+        instances: Range<u32>,
+    ) -> bool {
+        let id = self.buffers.id();
+        let rebind = *bound != Some(id);
+        match &self.buffers {
+            Buffers::Own {
+                vertex_buffer,
+                index_buffer,
+                ..
+            } => {
+                if rebind {
+                    pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                }
+                match index_buffer {
+                    Some((buffer, format)) => {
+                        if rebind {
+                            pass.set_index_buffer(buffer.slice(..), *format);
+                        }
+                        pass.draw_indexed(range, 0, instances);
+                    }
+                    None => pass.draw(range, instances),
+                }
+            }
+            Buffers::Arena(slot) => {
+                if rebind {
+                    slot.bind(pass);
+                }
+                slot.draw(pass, range, instances);
+            }
+        }
+        *bound = Some(id);
+        rebind
+    }
+
+    // Only for meshes made with new_dynamic or in an arena, and as many vertices as it was
+    // created with
+    pub fn write_vertices<V: bytemuck::Pod>(&self, queue: &wgpu::Queue, vertices: &[V]) {
+        match &self.buffers {
+            Buffers::Own { vertex_buffer, .. } => {
+                queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(vertices));
+            }
+            Buffers::Arena(slot) => slot.write_vertices(queue, vertices),
+        }
+    }
+
+    // Indices when indexed, vertices otherwise
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        match &self.buffers {
+            Buffers::Own { index_buffer, .. } => index_buffer.is_some(),
+            Buffers::Arena(_) => true,
+        }
+    }
+
+    pub fn full_range(&self) -> Range<u32> {
+        0..self.count
+    }
+
+    pub fn submesh(&self, index: usize) -> Result<&SubMesh, ForayError> {
+        self.submeshes
+            .get(index)
+            .ok_or_else(|| ForayError::SubmeshOutOfRange {
+                mesh: self.name.clone(),
+                index,
+                count: self.submeshes.len(),
+            })
+    }
+
+    // Err when `pipeline` was built for another topology or vertex layout
+    pub fn check(
+        &self,
+        pipeline: &str,
+        topology: wgpu::PrimitiveTopology,
+        layout: Option<VertexLayoutId>,
+    ) -> Result<(), ForayError> {
+        if topology != self.topology {
+            return Err(ForayError::TopologyMismatch {
+                mesh: self.name.clone(),
+                pipeline: pipeline.to_owned(),
+                mesh_topology: self.topology,
+                pipeline_topology: topology,
+            });
+        }
+        if layout != Some(self.layout) {
+            return Err(ForayError::LayoutMismatch {
+                mesh: self.name.clone(),
+                pipeline: pipeline.to_owned(),
+                mesh_layout: self.layout,
+                pipeline_layout: layout,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITION: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+    const POSITION_COLOR: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn layout(
+        stride: u64,
+        step_mode: wgpu::VertexStepMode,
+        attributes: &'static [wgpu::VertexAttribute],
+    ) -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: stride,
+            step_mode,
+            attributes,
+        }
+    }
+
+    // The bench's grid: `cells` x `cells` quads over a flat square, 2 * cells^2 triangles
+    fn grid(name: &str, cells: u32) -> MeshData<Vec3> {
+        let side = cells + 1;
+        let vertices = (0..side * side)
+            .map(|i| Vec3::new((i % side) as f32, (i / side) as f32, 0.0))
+            .collect();
+        let indices = (0..cells * cells)
+            .flat_map(|quad| {
+                let corner = quad / cells * side + quad % cells;
+                let (a, b, c, d) = (corner, corner + 1, corner + side + 1, corner + side);
+                [a, b, c, a, c, d]
+            })
+            .collect();
+        MeshData::new(name, vertices, indices)
+    }
+
+    // Twice the area each triangle covers, signed so a flipped one takes away
+    fn doubled_area(mesh: &MeshData<Vec3>) -> f32 {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|t| normal([0, 1, 2].map(|i| mesh.vertices[t[i] as usize])).z)
+            .sum()
+    }
+
+    // Just a position and the color baked into it
+    #[derive(Clone, Debug)]
+    struct Baked {
+        position: Vec3,
+        color: [f32; 3],
+    }
+
+    impl Position for Baked {
+        fn position(&self) -> Vec3 {
+            self.position
+        }
+    }
+
+    impl VertexColor for Baked {
+        fn set_color(&mut self, color: [f32; 3]) {
+            self.color = color;
+        }
+    }
+
+    // Red, green on top, blue, white below. All 0 or 255 so linear is the same numbers
+    fn checker() -> image::RgbaImage {
+        image::RgbaImage::from_fn(2, 2, |x, y| match (x, y) {
+            (0, 0) => image::Rgba([255, 0, 0, 255]),
+            (1, 0) => image::Rgba([0, 255, 0, 255]),
+            (0, 1) => image::Rgba([0, 0, 255, 255]),
+            _ => image::Rgba([255, 255, 255, 255]),
+        })
+    }
+
+    // A 5 x 5 grid of vertices over 0..4, so u and v step by a quarter
+    fn baked(mapping: UvMapping) -> Vec<Baked> {
+        let mut vertices: Vec<Baked> = (0..25)
+            .map(|i| Baked {
+                position: Vec3::new((i % 5) as f32, (i / 5) as f32, 0.0),
+                color: [-1.0; 3],
+            })
+            .collect();
+        bake_vertex_colors(&mut vertices, &checker(), mapping);
+        vertices
+    }
+
+    fn assert_color(vertex: &Baked, expected: Vec3) {
+        let color = Vec3::from_array(vertex.color);
+        assert!(
+            color.distance(expected) < 1e-5,
+            "{} got {color}, wanted {expected}",
+            vertex.position
+        );
+    }
+
+    #[test]
+    fn bakes_bilinear_between_texel_centers_and_clamps_at_edges() {
+        let vertices = baked(UvMapping {
+            flip_v: false,
+            ..UvMapping::default()
+        });
+        let [red, green, blue, white] = [Vec3::X, Vec3::Y, Vec3::Z, Vec3::ONE];
+        // How much of the second column (or row) each grid step sees: clamped to the first
+        // texel up to its center at a quarter, halfway at a half, the second from three
+        // quarters on
+        let weight = [0.0, 0.0, 0.5, 1.0, 1.0];
+        for (i, vertex) in vertices.iter().enumerate() {
+            let (wx, wy) = (weight[i % 5], weight[i / 5]);
+            let top = red.lerp(green, wx);
+            let bottom = blue.lerp(white, wx);
+            assert_color(vertex, top.lerp(bottom, wy));
+        }
+        // Corners clamp to the corner texels, the middle is the average of all four
+        assert_color(&vertices[0], red);
+        assert_color(&vertices[4], green);
+        assert_color(&vertices[20], blue);
+        assert_color(&vertices[24], white);
+        assert_color(&vertices[12], Vec3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn bake_mapping_flips_scales_and_offsets() {
+        // y up puts the image's bottom row at the bottom of the mesh
+        let upright = baked(UvMapping::default());
+        assert_color(&upright[0], Vec3::Z);
+        assert_color(&upright[24], Vec3::Y);
+        // Half the image across the mesh, starting a quarter of the way in: only the first
+        // column's center to the second's
+        let zoomed = baked(UvMapping {
+            flip_v: false,
+            scale: Vec2::splat(0.5),
+            offset: Vec2::splat(0.25),
+        });
+        assert_color(&zoomed[0], Vec3::X);
+        assert_color(&zoomed[24], Vec3::ONE);
+        assert_color(&zoomed[2], Vec3::new(0.5, 0.5, 0.0));
+        // Far outside the image everything clamps
+        let outside = baked(UvMapping {
+            flip_v: false,
+            scale: Vec2::ONE,
+            offset: Vec2::new(5.0, -5.0),
+        });
+        for vertex in &outside {
+            assert_color(vertex, Vec3::Y);
+        }
+    }
+
+    #[test]
+    fn baked_colors_come_out_linear() {
+        let gray = image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 128, 255]));
+        let mut data = MeshData::new(
+            "dot",
+            vec![Baked {
+                position: Vec3::ZERO,
+                color: [0.0; 3],
+            }],
+            Vec::new(),
+        );
+        data.bake_colors_from_image(&gray, UvMapping::default());
+        let expected = crate::colors::srgb_to_linear(128.0 / 255.0) as f32;
+        assert!(data.vertices[0]
+            .color
+            .iter()
+            .all(|c| (c - expected).abs() < 1e-6));
+        // An empty image leaves the colors alone
+        bake_vertex_colors(
+            &mut data.vertices,
+            &image::RgbaImage::new(0, 0),
+            UvMapping::default(),
+        );
+        assert!((data.vertices[0].color[0] - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn merge_shifts_indices_and_submeshes() {
+        let merged = MeshData::merge([grid("a", 1), grid("b", 2), grid("c", 1)]);
+        assert_eq!(merged.vertices.len(), 4 + 9 + 4);
+        assert_eq!(merged.indices.len(), 6 + 24 + 6);
+        let ranges: Vec<_> = merged
+            .submeshes
+            .iter()
+            .map(|s| (s.name.as_str(), s.index_range.clone()))
+            .collect();
+        assert_eq!(ranges, [("a", 0..6), ("b", 6..30), ("c", 30..36)]);
+        // Each part still points at its own vertices
+        assert_eq!(merged.indices[..6], [0, 1, 3, 0, 3, 2]);
+        assert!(merged.indices[6..30].iter().all(|i| (4..13).contains(i)));
+        assert_eq!(merged.indices[30..], [13, 14, 16, 13, 16, 15]);
+        assert!(MeshData::<Vec3>::merge([]).indices.is_empty());
+    }
+
+    #[test]
+    fn decimating_a_grid_keeps_its_rim_and_area() {
+        let mesh = grid("grid", 16);
+        let half = mesh.decimate("grid", 0.5).unwrap();
+        let triangles = half.indices.len() / 3;
+        assert!(triangles < mesh.indices.len() / 3, "nothing collapsed");
+        assert!(triangles >= mesh.indices.len() / 6, "went below half");
+        // Only interior vertices move, so the square is covered the same and no
+        // triangle flipped over
+        assert!((doubled_area(&half) - 2.0 * 256.0).abs() < 1e-3);
+        for x in 0..=16 {
+            for rim in [
+                Vec3::new(x as f32, 0.0, 0.0),
+                Vec3::new(x as f32, 16.0, 0.0),
+                Vec3::new(0.0, x as f32, 0.0),
+                Vec3::new(16.0, x as f32, 0.0),
+            ] {
+                assert!(half.vertices.contains(&rim), "{rim} is gone");
+            }
+        }
+        assert_eq!(half.submeshes[0].index_range, 0..half.indices.len() as u32);
+        // Asked for nothing it stops at what it can
+        let none = mesh.decimate("grid", 0.0).unwrap();
+        assert!(none.indices.len() <= half.indices.len());
+        assert!(matches!(
+            MeshData::new("odd", mesh.vertices.clone(), vec![0, 1]).decimate("odd", 0.5),
+            Err(ForayError::NonManifold { mesh, .. }) if mesh == "odd"
+        ));
+    }
+
+    #[test]
+    fn layout_ids_tell_layouts_apart() {
+        let vertex = wgpu::VertexStepMode::Vertex;
+        let base = VertexLayoutId::of(&layout(12, vertex, &POSITION));
+        assert_eq!(base, VertexLayoutId::of(&layout(12, vertex, &POSITION)));
+        for other in [
+            layout(16, vertex, &POSITION),
+            layout(12, wgpu::VertexStepMode::Instance, &POSITION),
+            layout(12, vertex, &POSITION_COLOR),
+        ] {
+            assert_ne!(base, VertexLayoutId::of(&other), "{other:?}");
+        }
+    }
+
+    #[test]
+    fn family_variants_follow_topology() {
+        use wgpu::PrimitiveTopology as T;
+        let variants = [
+            T::TriangleList,
+            T::TriangleStrip,
+            T::LineList,
+            T::LineStrip,
+            T::PointList,
+        ]
+        .map(family_variant);
+        assert_eq!(variants, ["fill", "fill", "line", "line", "point"]);
+    }
+
+    #[test]
+    fn check_names_the_mismatch() {
+        let Ok(gpu) = crate::gpu_context::GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let positions = layout(12, wgpu::VertexStepMode::Vertex, &POSITION);
+        let colored = layout(28, wgpu::VertexStepMode::Vertex, &POSITION_COLOR);
+        let mesh = Mesh::new(
+            &gpu.device,
+            &GpuMemoryTracker::new(),
+            "Wire",
+            &positions,
+            wgpu::PrimitiveTopology::LineList,
+            &[[0.0f32; 3]; 2],
+            Indices::U16(&[0, 1]),
+        );
+        let id = VertexLayoutId::of(&positions);
+        assert!(mesh
+            .check("lines", wgpu::PrimitiveTopology::LineList, Some(id))
+            .is_ok());
+        let error = mesh
+            .check("fill", wgpu::PrimitiveTopology::TriangleList, Some(id))
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            ForayError::TopologyMismatch { mesh, pipeline, .. } if mesh == "Wire" && pipeline == "fill"
+        ));
+        let error = mesh
+            .check(
+                "colored_lines",
+                wgpu::PrimitiveTopology::LineList,
+                Some(VertexLayoutId::of(&colored)),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            ForayError::LayoutMismatch { pipeline, mesh_layout, .. }
+                if pipeline == "colored_lines" && *mesh_layout == id
+        ));
+        // Pipelines without vertex buffers can't draw a mesh either
+        assert!(matches!(
+            mesh.check("fullscreen", wgpu::PrimitiveTopology::LineList, None),
+            Err(ForayError::LayoutMismatch {
+                pipeline_layout: None,
+                ..
+            })
+        ));
+    }
+
+    // Snorm16 steps are 1/32767 apart, so rounding is off by at most half that
+    const SNORM16_ERROR: f32 = 1.0 / 65534.0;
+    // And Unorm8 steps 1/255
+    const UNORM8_ERROR: f32 = 1.0 / 510.0;
+
+    #[test]
+    fn snorm16_is_off_by_at_most_half_a_step() {
+        // Finer than the steps, so plenty of values land near the worst case in between
+        for i in -100_000..=100_000 {
+            let value = i as f32 / 100_000.0;
+            let error = (unpack_snorm16(snorm16(value)) - value).abs();
+            assert!(
+                error <= SNORM16_ERROR + f32::EPSILON,
+                "{value} is off by {error}"
+            );
+        }
+        // The ends are exact, past them clamps, and the one value below -1 reads as -1
+        assert_eq!(snorm16(1.0), i16::MAX);
+        assert_eq!(snorm16(-1.0), -i16::MAX);
+        assert_eq!(snorm16(3.0), i16::MAX);
+        assert_eq!(snorm16(-3.0), -i16::MAX);
+        assert_eq!(snorm16(unpack_snorm16(i16::MIN)), -i16::MAX);
+    }
+
+    #[test]
+    fn unorm8_is_off_by_at_most_half_a_step() {
+        for i in 0..=100_000 {
+            let value = i as f32 / 100_000.0;
+            let error = (f32::from(unorm8(value)) / 255.0 - value).abs();
+            assert!(
+                error <= UNORM8_ERROR + f32::EPSILON,
+                "{value} is off by {error}"
+            );
+        }
+        assert_eq!(unorm8(0.0), 0);
+        assert_eq!(unorm8(1.0), u8::MAX);
+        assert_eq!(unorm8(-0.5), 0);
+        assert_eq!(unorm8(2.0), u8::MAX);
+    }
+
+    // Baked packed the way CompactVertex packs Vertex
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+    struct PackedBaked {
+        position: [i16; 4],
+        color: [u8; 4],
+    }
+
+    impl Position for PackedBaked {
+        fn position(&self) -> Vec3 {
+            Vec3::new(
+                unpack_snorm16(self.position[0]),
+                unpack_snorm16(self.position[1]),
+                unpack_snorm16(self.position[2]),
+            )
+        }
+    }
+
+    impl PackedVertex for PackedBaked {
+        type Full = Baked;
+
+        fn pack(full: &Baked) -> Self {
+            let mut packed = Self {
+                position: [0; 4],
+                color: [u8::MAX; 4],
+            };
+            packed.position[..3].copy_from_slice(&full.position.to_array().map(snorm16));
+            packed.color[..3].copy_from_slice(&full.color.map(unorm8));
+            packed
+        }
+
+        fn desc() -> wgpu::VertexBufferLayout<'static> {
+            const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+                wgpu::vertex_attr_array![0 => Snorm16x4, 1 => Unorm8x4];
+            wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<PackedBaked>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &ATTRIBUTES,
+            }
+        }
+    }
+
+    #[test]
+    fn packed_meshes_keep_their_shape_within_the_bounds() {
+        // The grid squeezed into -1..1, colored by where each vertex is
+        let grid = grid("Grid", 16);
+        let full = MeshData {
+            vertices: grid
+                .vertices
+                .iter()
+                .map(|&p| Baked {
+                    position: p / 8.0 - Vec3::new(1.0, 1.0, 0.0),
+                    color: [p.x / 16.0, p.y / 16.0, 1.0 / 3.0],
+                })
+                .collect(),
+            indices: grid.indices.clone(),
+            submeshes: grid.submeshes.clone(),
+        };
+        let packed = full.pack_into::<PackedBaked>();
+        assert_eq!(packed.indices, full.indices);
+        assert_eq!(packed.submeshes, full.submeshes);
+        assert_eq!(std::mem::size_of::<PackedBaked>(), 12);
+        for (full, packed) in full.vertices.iter().zip(&packed.vertices) {
+            let moved = (packed.position() - full.position).abs().max_element();
+            assert!(
+                moved <= SNORM16_ERROR + f32::EPSILON,
+                "{full:?} moved {moved}"
+            );
+            for (channel, &byte) in full.color.iter().zip(&packed.color) {
+                let error = (f32::from(byte) / 255.0 - channel).abs();
+                assert!(
+                    error <= UNORM8_ERROR + f32::EPSILON,
+                    "{full:?} off by {error}"
+                );
+            }
+            assert_eq!(packed.color[3], u8::MAX);
+        }
+    }
+}
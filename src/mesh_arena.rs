@@ -0,0 +1,437 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::ForayError;
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::mesh::{Mesh, MeshData, Position, VertexLayoutId};
+
+// Tells apart the buffer pairs meshes draw from. Every standalone mesh gets its own, all the
+// meshes in an arena share the arena's, so a Pass can skip binding what's already bound
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferSetId(u64);
+
+impl BufferSetId {
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// Hands out ranges of 0..capacity, first fit. The free list stays sorted by start, and a
+// freed range is merged with the free ranges either side of it, so freeing everything
+// always gets back the one 0..capacity range
+#[derive(Clone, Debug)]
+pub struct RangeAllocator {
+    capacity: u32,
+    free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            free: std::iter::once(0..capacity)
+                .filter(|all| !all.is_empty())
+                .collect(),
+        }
+    }
+
+    // None when no free range is that long, even if the free space adds up to it
+    pub fn allocate(&mut self, size: u32) -> Option<Range<u32>> {
+        if size == 0 {
+            return Some(0..0);
+        }
+        let slot = self
+            .free
+            .iter()
+            .position(|free| free.len() >= size as usize)?;
+        let start = self.free[slot].start;
+        self.free[slot].start += size;
+        if self.free[slot].is_empty() {
+            self.free.remove(slot);
+        }
+        Some(start..start + size)
+    }
+
+    // `range` has to be one allocate gave out and not freed since
+    pub fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        debug_assert!(
+            range.end <= self.capacity,
+            "{range:?} is outside the allocator"
+        );
+        let at = self.free.partition_point(|free| free.start < range.start);
+        debug_assert!(
+            (at == 0 || self.free[at - 1].end <= range.start)
+                && (at == self.free.len() || range.end <= self.free[at].start),
+            "{range:?} overlaps free space, freed twice?"
+        );
+        let joins_previous = at > 0 && self.free[at - 1].end == range.start;
+        let joins_next = at < self.free.len() && self.free[at].start == range.end;
+        match (joins_previous, joins_next) {
+            (true, true) => {
+                self.free[at - 1].end = self.free[at].end;
+                self.free.remove(at);
+            }
+            (true, false) => self.free[at - 1].end = range.end,
+            (false, true) => self.free[at].start = range.start,
+            (false, false) => self.free.insert(at, range),
+        }
+    }
+
+    pub fn free_space(&self) -> u32 {
+        self.free.iter().map(|free| free.end - free.start).sum()
+    }
+}
+
+struct Shared {
+    name: String,
+    id: BufferSetId,
+    layout: VertexLayoutId,
+    stride: u64,
+    vertex_buffer: Tracked<wgpu::Buffer>,
+    index_buffer: Tracked<wgpu::Buffer>,
+    // In vertices and in indices
+    ranges: Mutex<(RangeAllocator, RangeAllocator)>,
+}
+
+// One big vertex buffer and one big (u32) index buffer that many meshes of the same vertex
+// layout live in. Drawing them one after the other only moves base_vertex and the index
+// range, the buffers stay bound. A mesh's ranges go back to the arena when it's dropped
+pub struct MeshArena {
+    shared: Arc<Shared>,
+}
+
+impl MeshArena {
+    // Room for `vertices` vertices of `layout` and `indices` indices, it doesn't grow
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        layout: &wgpu::VertexBufferLayout,
+        vertices: u32,
+        indices: u32,
+    ) -> Self {
+        let buffer = |label: String, size: u64, usage: wgpu::BufferUsages| {
+            memory.create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some(&label),
+                    size,
+                    // COPY_SRC so what's landed in them can be read back and checked
+                    usage: usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                },
+                MemoryCategory::Meshes,
+            )
+        };
+        let vertex_buffer = buffer(
+            format!("{name} Arena Vertices"),
+            u64::from(vertices) * layout.array_stride,
+            wgpu::BufferUsages::VERTEX,
+        );
+        let index_buffer = buffer(
+            format!("{name} Arena Indices"),
+            u64::from(indices) * 4,
+            wgpu::BufferUsages::INDEX,
+        );
+        Self {
+            shared: Arc::new(Shared {
+                name: name.to_owned(),
+                id: BufferSetId::next(),
+                layout: VertexLayoutId::of(layout),
+                stride: layout.array_stride,
+                vertex_buffer,
+                index_buffer,
+                ranges: Mutex::new((RangeAllocator::new(vertices), RangeAllocator::new(indices))),
+            }),
+        }
+    }
+
+    // Like Mesh::from_data, but into the arena. Err when `V` isn't the arena's vertex size or
+    // there's no free range long enough for the vertices or the indices
+    pub fn mesh_from_data<V: bytemuck::Pod + Position>(
+        &self,
+        queue: &wgpu::Queue,
+        name: &str,
+        topology: wgpu::PrimitiveTopology,
+        data: &MeshData<V>,
+    ) -> Result<Mesh, ForayError> {
+        let slot = self.allocate(
+            name,
+            std::mem::size_of::<V>() as u64,
+            data.vertices.len() as u32,
+            data.indices.len() as u32,
+        )?;
+        queue.write_buffer(
+            &self.shared.vertex_buffer,
+            u64::from(slot.vertices.start) * self.shared.stride,
+            bytemuck::cast_slice(&data.vertices),
+        );
+        queue.write_buffer(
+            &self.shared.index_buffer,
+            u64::from(slot.indices.start) * 4,
+            bytemuck::cast_slice(&data.indices),
+        );
+        Ok(Mesh::in_arena(
+            name,
+            topology,
+            self.shared.layout,
+            slot,
+            data,
+        ))
+    }
+
+    // Room for a mesh that isn't there yet, `stride` bytes a vertex. It draws nothing until
+    // Mesh::grow says some of it has landed, see mesh_stream
+    pub fn reserve(
+        &self,
+        name: &str,
+        topology: wgpu::PrimitiveTopology,
+        stride: u64,
+        vertices: u32,
+        indices: u32,
+    ) -> Result<Mesh, ForayError> {
+        let slot = self.allocate(name, stride, vertices, indices)?;
+        Ok(Mesh::reserved(name, topology, self.shared.layout, slot))
+    }
+
+    fn allocate(
+        &self,
+        name: &str,
+        stride: u64,
+        vertex_count: u32,
+        index_count: u32,
+    ) -> Result<ArenaSlot, ForayError> {
+        let full = |reason: String| ForayError::ArenaFull {
+            arena: self.shared.name.clone(),
+            mesh: name.to_owned(),
+            reason,
+        };
+        if stride != self.shared.stride {
+            return Err(full(format!(
+                "its vertices are {stride} bytes, the arena's are {}",
+                self.shared.stride
+            )));
+        }
+        let mut ranges = self.lock();
+        let vertices = ranges.0.allocate(vertex_count).ok_or_else(|| {
+            full(format!(
+                "no room for {vertex_count} vertices ({} free)",
+                ranges.0.free_space()
+            ))
+        })?;
+        let Some(indices) = ranges.1.allocate(index_count) else {
+            let free = ranges.1.free_space();
+            ranges.0.free(vertices);
+            return Err(full(format!(
+                "no room for {index_count} indices ({free} free)"
+            )));
+        };
+        Ok(ArenaSlot {
+            shared: Arc::clone(&self.shared),
+            vertices,
+            indices,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (RangeAllocator, RangeAllocator)> {
+        self.shared.ranges.lock().unwrap()
+    }
+}
+
+// A mesh's share of an arena, given back when the mesh goes away
+pub struct ArenaSlot {
+    shared: Arc<Shared>,
+    vertices: Range<u32>,
+    indices: Range<u32>,
+}
+
+impl ArenaSlot {
+    pub fn id(&self) -> BufferSetId {
+        self.shared.id
+    }
+
+    pub fn bind(&self, pass: &mut wgpu::RenderPass) {
+        pass.set_vertex_buffer(0, self.shared.vertex_buffer.slice(..));
+        pass.set_index_buffer(
+            self.shared.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+    }
+
+    // `range` counts from the mesh's first index, like for a standalone mesh
+    pub fn draw(&self, pass: &mut wgpu::RenderPass, range: Range<u32>, instances: Range<u32>) {
+        let first = self.indices.start;
+        pass.draw_indexed(
+            first + range.start..first + range.end,
+            self.vertices.start as i32,
+            instances,
+        );
+    }
+
+    pub fn write_vertices<V: bytemuck::Pod>(&self, queue: &wgpu::Queue, vertices: &[V]) {
+        queue.write_buffer(
+            &self.shared.vertex_buffer,
+            u64::from(self.vertices.start) * self.shared.stride,
+            bytemuck::cast_slice(vertices),
+        );
+    }
+
+    // Vertices from `first_vertex` on and indices from `first_index` on, both counted from
+    // the slot's start. Copied straight into the queue's staging memory, so the bytes can
+    // come from a memory-mapped file without another copy on the way
+    pub fn write_part(
+        &self,
+        queue: &wgpu::Queue,
+        (first_vertex, vertices): (u32, &[u8]),
+        (first_index, indices): (u32, &[u8]),
+    ) {
+        let write = |buffer: &wgpu::Buffer, offset: u64, bytes: &[u8]| {
+            let Some(size) = wgpu::BufferSize::new(bytes.len() as u64) else {
+                return;
+            };
+            // None means it failed validation, which is reported to the device like any other
+            if let Some(mut staging) = queue.write_buffer_with(buffer, offset, size) {
+                staging.copy_from_slice(bytes);
+            }
+        };
+        write(
+            &self.shared.vertex_buffer,
+            u64::from(self.vertices.start + first_vertex) * self.shared.stride,
+            vertices,
+        );
+        write(
+            &self.shared.index_buffer,
+            u64::from(self.indices.start + first_index) * 4,
+            indices,
+        );
+    }
+}
+
+// Where a slot sits in the arena's buffers, in bytes, for reading back what landed there
+#[cfg(test)]
+impl ArenaSlot {
+    pub fn vertex_bytes(&self) -> (&wgpu::Buffer, Range<u64>) {
+        let stride = self.shared.stride;
+        let range = u64::from(self.vertices.start) * stride..u64::from(self.vertices.end) * stride;
+        (&self.shared.vertex_buffer, range)
+    }
+
+    pub fn index_bytes(&self) -> (&wgpu::Buffer, Range<u64>) {
+        let range = u64::from(self.indices.start) * 4..u64::from(self.indices.end) * 4;
+        (&self.shared.index_buffer, range)
+    }
+}
+
+impl Drop for ArenaSlot {
+    fn drop(&mut self) {
+        // A panic elsewhere while holding the lock leaves the lists as they were
+        let mut ranges = self
+            .shared
+            .ranges
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        ranges.0.free(self.vertices.clone());
+        ranges.1.free(self.indices.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift, the same sequence every run so a failure can be replayed
+    struct Rng(u32);
+
+    impl Rng {
+        fn below(&mut self, n: u32) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 % n
+        }
+    }
+
+    // What has to hold after every allocate and free: live ranges inside the capacity and
+    // apart, free ranges sorted with a gap between each so nothing is left unmerged, and
+    // the two together covering all of it
+    fn check(allocator: &RangeAllocator, live: &[Range<u32>]) {
+        let mut taken: Vec<Range<u32>> = live.iter().filter(|r| !r.is_empty()).cloned().collect();
+        taken.sort_by_key(|r| r.start);
+        for pair in taken.windows(2) {
+            assert!(pair[0].end <= pair[1].start, "{pair:?} overlap");
+        }
+        assert!(taken.last().is_none_or(|r| r.end <= allocator.capacity));
+        for pair in allocator.free.windows(2) {
+            assert!(pair[0].end < pair[1].start, "{pair:?} should have merged");
+        }
+        assert!(allocator.free.iter().all(|r| !r.is_empty()));
+        for free in &allocator.free {
+            assert!(
+                taken
+                    .iter()
+                    .all(|r| r.end <= free.start || free.end <= r.start),
+                "{free:?} is free and handed out"
+            );
+        }
+        let used: u32 = taken.iter().map(|r| r.end - r.start).sum();
+        assert_eq!(used + allocator.free_space(), allocator.capacity);
+    }
+
+    #[test]
+    fn random_allocations_stay_apart_and_frees_merge() {
+        for seed in 1..=20u32 {
+            let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9));
+            let mut allocator = RangeAllocator::new(1000);
+            let mut live: Vec<Range<u32>> = Vec::new();
+            for _ in 0..2000 {
+                if live.is_empty() || rng.below(3) != 0 {
+                    let size = rng.below(60);
+                    let longest = allocator.free.iter().map(|r| r.end - r.start).max();
+                    match allocator.allocate(size) {
+                        Some(range) => {
+                            assert_eq!(range.end - range.start, size);
+                            live.push(range);
+                        }
+                        // Only when no single free range is long enough
+                        None => assert!(longest.unwrap_or(0) < size),
+                    }
+                } else {
+                    let index = rng.below(live.len() as u32) as usize;
+                    allocator.free(live.swap_remove(index));
+                }
+                check(&allocator, &live);
+            }
+            // Freed in any order it all comes back as one range
+            while !live.is_empty() {
+                let index = rng.below(live.len() as u32) as usize;
+                allocator.free(live.swap_remove(index));
+                check(&allocator, &live);
+            }
+            assert_eq!(allocator.free.as_slice(), std::slice::from_ref(&(0..1000)));
+        }
+    }
+
+    #[test]
+    fn freed_neighbours_merge_both_ways() {
+        let mut allocator = RangeAllocator::new(30);
+        let [a, b, c] = [10, 10, 10].map(|size| allocator.allocate(size).unwrap());
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..10, 10..20, 20..30));
+        assert_eq!(allocator.allocate(1), None);
+        allocator.free(a);
+        allocator.free(c);
+        assert_eq!(allocator.free, [0..10, 20..30]);
+        // 20 free but not in one piece
+        assert_eq!(allocator.allocate(11), None);
+        allocator.free(b);
+        assert_eq!(allocator.free.as_slice(), std::slice::from_ref(&(0..30)));
+        // First fit, and empty ranges cost nothing
+        assert_eq!(allocator.allocate(0), Some(0..0));
+        assert_eq!(allocator.allocate(5), Some(0..5));
+        assert!(RangeAllocator::new(0).allocate(1).is_none());
+    }
+}
@@ -0,0 +1,651 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use glam::Vec2;
+use memmap2::Mmap;
+
+use crate::error::ForayError;
+use crate::mesh::Mesh;
+use crate::mesh_arena::MeshArena;
+
+// Bytes uploaded a frame unless `stream budget` says otherwise
+pub const DEFAULT_BUDGET: u64 = 2 * 1024 * 1024;
+// `stream circle` without a count, about 36MB of Vertex and u32 indices
+pub const STRESS_SEGMENTS: u32 = 1_000_000;
+// How much a cache file chunk holds. Cache chunks aren't split, so a frame can go over its
+// budget by up to one of them
+const CACHE_CHUNK: u64 = 256 * 1024;
+const CACHE_MAGIC: &[u8; 8] = b"FORAYMS1";
+// The magic, then the stride, vertex count and index count as little endian u32s
+const CACHE_HEADER: usize = 20;
+
+// Part of a streamed mesh. Indices count from the mesh's first vertex and only use
+// vertices from this chunk or an earlier one, so whatever has landed can be drawn
+pub struct Chunk<'a> {
+    pub vertices: Cow<'a, [u8]>,
+    // u32s, little endian
+    pub indices: Cow<'a, [u8]>,
+}
+
+// Where a MeshStream's chunks come from, first to last
+pub trait ChunkSource {
+    // Bytes a vertex
+    fn stride(&self) -> u64;
+    // Vertices and indices in the whole mesh
+    fn totals(&self) -> (u32, u32);
+    // About `budget` bytes of what's left, never less than a vertex's worth. None once
+    // everything's been handed out
+    fn next_chunk(&mut self, budget: u64) -> Option<Result<Chunk<'_>, String>>;
+}
+
+// The circle stress mesh, a fan of `segments` triangles around the origin. It's made a
+// chunk at a time, so the whole thing never sits in memory. `vertex` gets a point on the
+// unit circle (the origin for the center) and how far around it is
+pub struct CircleSource<V> {
+    segments: u32,
+    // Points on the circle made so far
+    made: u32,
+    vertex: fn(Vec2, f32) -> V,
+}
+
+impl<V> CircleSource<V> {
+    pub fn new(segments: u32, vertex: fn(Vec2, f32) -> V) -> Self {
+        Self {
+            segments: segments.clamp(3, u32::MAX / 3),
+            made: 0,
+            vertex,
+        }
+    }
+}
+
+impl<V: bytemuck::Pod> ChunkSource for CircleSource<V> {
+    fn stride(&self) -> u64 {
+        std::mem::size_of::<V>() as u64
+    }
+
+    fn totals(&self) -> (u32, u32) {
+        (self.segments + 1, self.segments * 3)
+    }
+
+    fn next_chunk(&mut self, budget: u64) -> Option<Result<Chunk<'_>, String>> {
+        if self.made == self.segments {
+            return None;
+        }
+        // A point and the triangle it finishes
+        let each = self.stride() + 12;
+        let count = u32::try_from(budget / each)
+            .unwrap_or(u32::MAX)
+            .clamp(1, self.segments - self.made);
+        let mut vertices = Vec::with_capacity(count as usize + 1);
+        let mut indices: Vec<u32> = Vec::with_capacity(count as usize * 3 + 3);
+        if self.made == 0 {
+            vertices.push((self.vertex)(Vec2::ZERO, 0.0));
+        }
+        // Point n is vertex n, the center is vertex 0
+        for point in self.made + 1..=self.made + count {
+            let around = (point - 1) as f32 / self.segments as f32;
+            vertices.push((self.vertex)(
+                Vec2::from_angle(around * std::f32::consts::TAU),
+                around,
+            ));
+            if point > 1 {
+                indices.extend([0, point - 1, point]);
+            }
+            if point == self.segments {
+                indices.extend([0, point, 1]);
+            }
+        }
+        self.made += count;
+        Some(Ok(Chunk {
+            vertices: Cow::Owned(bytemuck::cast_slice(&vertices).to_vec()),
+            indices: Cow::Owned(bytemuck::cast_slice(&indices).to_vec()),
+        }))
+    }
+}
+
+// A mesh write_cache put in a file, memory-mapped so only the chunks being uploaded get
+// read in, and they go from the map straight into the queue's staging memory
+pub struct CacheSource {
+    map: Mmap,
+    stride: u64,
+    totals: (u32, u32),
+    // Where the next chunk starts
+    at: usize,
+}
+
+impl CacheSource {
+    pub fn open(path: &Path) -> Result<Self, ForayError> {
+        let error = |reason: String| ForayError::MeshStream {
+            mesh: path.display().to_string(),
+            reason,
+        };
+        let file = File::open(path).map_err(|e| error(e.to_string()))?;
+        // The map is only sound while nothing truncates the file under it. Caches are ours
+        // and written once, anything else writing one mid-stream gets the process a SIGBUS
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| error(e.to_string()))?;
+        if map.len() < CACHE_HEADER || &map[..8] != CACHE_MAGIC {
+            return Err(error("not a mesh stream cache".to_owned()));
+        }
+        let (stride, vertices, indices) = (word(&map, 8), word(&map, 12), word(&map, 16));
+        if stride == 0 {
+            return Err(error("its vertices have no size".to_owned()));
+        }
+        Ok(Self {
+            map,
+            stride: u64::from(stride),
+            totals: (vertices, indices),
+            at: CACHE_HEADER,
+        })
+    }
+}
+
+impl ChunkSource for CacheSource {
+    fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    fn totals(&self) -> (u32, u32) {
+        self.totals
+    }
+
+    // Whole cache chunks, whatever the budget
+    fn next_chunk(&mut self, _budget: u64) -> Option<Result<Chunk<'_>, String>> {
+        if self.at >= self.map.len() {
+            return None;
+        }
+        let at = self.at;
+        let Some(counts) = self.map.get(at..at + 8) else {
+            return Some(Err(format!("cut short in the chunk at byte {at}")));
+        };
+        let (vertices, indices) = (word(counts, 0), word(counts, 4));
+        let vertex_bytes = vertices as usize * self.stride as usize;
+        let end = at + 8 + vertex_bytes + indices as usize * 4;
+        if end > self.map.len() {
+            return Some(Err(format!("the chunk at byte {at} runs past the end")));
+        }
+        self.at = end;
+        let (vertices, indices) = self.map[at + 8..end].split_at(vertex_bytes);
+        Some(Ok(Chunk {
+            vertices: Cow::Borrowed(vertices),
+            indices: Cow::Borrowed(indices),
+        }))
+    }
+}
+
+fn word(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// Everything `source` has left, in a file CacheSource streams back. Returns the bytes written
+pub fn write_cache(path: &Path, source: &mut dyn ChunkSource) -> Result<u64, ForayError> {
+    let error = |reason: String| ForayError::MeshStream {
+        mesh: path.display().to_string(),
+        reason,
+    };
+    let stride = source.stride();
+    let (vertices, indices) = source.totals();
+    let mut out = BufWriter::new(File::create(path).map_err(|e| error(e.to_string()))?);
+    let put = |out: &mut BufWriter<File>, bytes: &[u8]| {
+        out.write_all(bytes)
+            .map(|()| bytes.len() as u64)
+            .map_err(|e| error(e.to_string()))
+    };
+    let mut written = put(&mut out, CACHE_MAGIC)?;
+    for value in [stride as u32, vertices, indices] {
+        written += put(&mut out, &value.to_le_bytes())?;
+    }
+    while let Some(chunk) = source.next_chunk(CACHE_CHUNK) {
+        let chunk = chunk.map_err(error)?;
+        let counts = [
+            (chunk.vertices.len() as u64 / stride) as u32,
+            (chunk.indices.len() / 4) as u32,
+        ];
+        written += put(&mut out, bytemuck::cast_slice(&counts))?;
+        written += put(&mut out, &chunk.vertices)?;
+        written += put(&mut out, &chunk.indices)?;
+    }
+    out.flush().map_err(|e| error(e.to_string()))?;
+    Ok(written)
+}
+
+// A mesh uploaded a few chunks a frame instead of in one go. Its whole size is reserved up
+// front and it draws whatever has landed, more of it every frame. Dropping it part way
+// through releases the source (its CPU side, or the file map) and the arena ranges
+pub struct MeshStream {
+    pub mesh: Mesh,
+    // None once everything's in
+    source: Option<Box<dyn ChunkSource>>,
+    stride: u64,
+    totals: (u32, u32),
+    // Vertices and indices uploaded so far
+    landed: (u32, u32),
+    pub budget: u64,
+    // Frames that uploaded something, and the longest one of them spent at it
+    pub frames: u32,
+    pub longest: Duration,
+}
+
+impl MeshStream {
+    pub fn new(
+        arena: &MeshArena,
+        name: &str,
+        topology: wgpu::PrimitiveTopology,
+        source: Box<dyn ChunkSource>,
+        budget: u64,
+    ) -> Result<Self, ForayError> {
+        let (stride, totals) = (source.stride(), source.totals());
+        // Copies into buffers go in whole words
+        if stride == 0 || !stride.is_multiple_of(4) {
+            return Err(ForayError::MeshStream {
+                mesh: name.to_owned(),
+                reason: format!("a {stride} byte vertex can't be streamed"),
+            });
+        }
+        let mesh = arena.reserve(name, topology, stride, totals.0, totals.1)?;
+        Ok(Self {
+            mesh,
+            source: Some(source),
+            stride,
+            totals,
+            landed: (0, 0),
+            budget,
+            frames: 0,
+            longest: Duration::ZERO,
+        })
+    }
+
+    // One frame's upload, `budget` bytes or so, and the mesh grows to draw what landed.
+    // True once it's all in
+    pub fn pump(&mut self, queue: &wgpu::Queue) -> Result<bool, ForayError> {
+        let Some(source) = &mut self.source else {
+            return Ok(true);
+        };
+        let error = |reason: String| ForayError::MeshStream {
+            mesh: self.mesh.name.clone(),
+            reason,
+        };
+        let slot = self
+            .mesh
+            .slot()
+            .ok_or_else(|| error("it isn't in an arena".to_owned()))?;
+        let started = Instant::now();
+        let mut sent = 0;
+        let mut exhausted = false;
+        while sent < self.budget {
+            let Some(chunk) = source.next_chunk(self.budget - sent) else {
+                exhausted = true;
+                break;
+            };
+            let chunk = chunk.map_err(error)?;
+            if !(chunk.vertices.len() as u64).is_multiple_of(self.stride)
+                || !chunk.indices.len().is_multiple_of(4)
+            {
+                return Err(error(
+                    "a chunk ends part way into a vertex or index".to_owned(),
+                ));
+            }
+            let vertices = (chunk.vertices.len() as u64 / self.stride) as u32;
+            let indices = (chunk.indices.len() / 4) as u32;
+            let landed = (self.landed.0 + vertices, self.landed.1 + indices);
+            if landed.0 > self.totals.0 || landed.1 > self.totals.1 {
+                return Err(error(format!(
+                    "more than the {} vertices and {} indices it said it had",
+                    self.totals.0, self.totals.1
+                )));
+            }
+            let early = chunk
+                .indices
+                .chunks_exact(4)
+                .map(|index| word(index, 0))
+                .find(|&index| index >= landed.0);
+            if let Some(index) = early {
+                return Err(error(format!(
+                    "index {index} comes before its vertex, only {} have landed",
+                    landed.0
+                )));
+            }
+            slot.write_part(
+                queue,
+                (self.landed.0, &chunk.vertices),
+                (self.landed.1, &chunk.indices),
+            );
+            sent += (chunk.vertices.len() + chunk.indices.len()) as u64;
+            self.landed = landed;
+        }
+        let done = self.landed == self.totals;
+        if exhausted && !done {
+            return Err(error(format!(
+                "the source ended after {} of {} vertices and {} of {} indices",
+                self.landed.0, self.totals.0, self.landed.1, self.totals.1
+            )));
+        }
+        self.mesh.grow(self.landed.1);
+        self.frames += 1;
+        self.longest = self.longest.max(started.elapsed());
+        if done {
+            self.source = None;
+        }
+        Ok(done)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.source.is_none()
+    }
+
+    // 0 to 1, by bytes
+    pub fn progress(&self) -> f32 {
+        let (landed, total) = (self.bytes(self.landed), self.bytes(self.totals));
+        if total == 0 {
+            1.0
+        } else {
+            (landed as f64 / total as f64) as f32
+        }
+    }
+
+    // For the progress bar, "Circle 40% of 36.0 MB"
+    pub fn readout(&self) -> String {
+        format!(
+            "{} {:.0}% of {:.1} MB",
+            self.mesh.name,
+            self.progress() * 100.0,
+            self.bytes(self.totals) as f64 / (1024.0 * 1024.0)
+        )
+    }
+
+    fn bytes(&self, (vertices, indices): (u32, u32)) -> u64 {
+        u64::from(vertices) * self.stride + u64::from(indices) * 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use wgpu::util::DeviceExt;
+
+    use super::*;
+    use crate::gpu_context::GpuContext;
+    use crate::memory::{GpuMemoryTracker, MemoryCategory};
+    use crate::stats;
+    use crate::Vertex;
+
+    // A vertex and the three indices of its triangle
+    const SEGMENT: u64 = 24 + 12;
+    // About 50MB of circle
+    const SEGMENTS: u32 = (50 * 1024 * 1024 / SEGMENT) as u32;
+    const TRIANGLES: wgpu::PrimitiveTopology = wgpu::PrimitiveTopology::TriangleList;
+
+    fn vertex(point: Vec2, around: f32) -> Vertex {
+        Vertex {
+            position: [point.x, point.y, 0.0],
+            color: [around, 0.0, 1.0 - around],
+        }
+    }
+
+    // The whole circle in a single chunk, what a direct upload writes
+    fn in_one_go(segments: u32) -> (Vec<u8>, Vec<u8>) {
+        let mut source = CircleSource::new(segments, vertex);
+        let chunk = source.next_chunk(u64::MAX).unwrap().unwrap();
+        let whole = (chunk.vertices.into_owned(), chunk.indices.into_owned());
+        assert!(source.next_chunk(u64::MAX).is_none());
+        whole
+    }
+
+    // Everything `source` has, put back together, and how big each chunk was. Checks on the
+    // way that no index comes before its vertex
+    fn drain(source: &mut dyn ChunkSource, budget: u64) -> (Vec<u8>, Vec<u8>, Vec<u64>) {
+        let stride = source.stride() as usize;
+        let (mut vertices, mut indices, mut sizes) = (Vec::new(), Vec::new(), Vec::new());
+        while let Some(chunk) = source.next_chunk(budget) {
+            let chunk = chunk.expect("Chunk failed");
+            vertices.extend_from_slice(&chunk.vertices);
+            let landed = (vertices.len() / stride) as u32;
+            let early = chunk
+                .indices
+                .chunks_exact(4)
+                .map(|index| word(index, 0))
+                .find(|&index| index >= landed);
+            assert_eq!(early, None, "Index before its vertex, {landed} landed");
+            indices.extend_from_slice(&chunk.indices);
+            sizes.push((chunk.vertices.len() + chunk.indices.len()) as u64);
+        }
+        (vertices, indices, sizes)
+    }
+
+    #[test]
+    fn a_50mb_circle_comes_in_2mb_chunks_identical_to_one_go() {
+        let mut source = CircleSource::new(SEGMENTS, vertex);
+        assert_eq!(source.totals(), (SEGMENTS + 1, SEGMENTS * 3));
+        let (vertices, indices, sizes) = drain(&mut source, DEFAULT_BUDGET);
+        let total: u64 = sizes.iter().sum();
+        assert!(total >= 50 * 1024 * 1024, "Only {total} bytes");
+        // The center and the closing triangle can take a chunk a segment over
+        assert!(sizes.iter().all(|&size| size <= DEFAULT_BUDGET + SEGMENT));
+        // And all but the last are full
+        let (last, full) = sizes.split_last().unwrap();
+        assert!(full.iter().all(|&size| size + SEGMENT > DEFAULT_BUDGET));
+        assert!(*last > 0);
+        assert!(sizes.len() as u64 <= total.div_ceil(DEFAULT_BUDGET) + 1);
+
+        let (direct_vertices, direct_indices) = in_one_go(SEGMENTS);
+        assert!(vertices == direct_vertices, "Vertices differ");
+        assert!(indices == direct_indices, "Indices differ");
+    }
+
+    #[test]
+    fn a_cache_streams_back_what_went_into_it() {
+        let segments = 100_000;
+        let path =
+            std::env::temp_dir().join(format!("wgpu-foray-{}-stream.cache", std::process::id()));
+        let written = write_cache(&path, &mut CircleSource::new(segments, vertex)).unwrap();
+        assert_eq!(written, std::fs::metadata(&path).unwrap().len());
+
+        let mut cache = CacheSource::open(&path).unwrap();
+        assert_eq!(cache.stride(), 24);
+        assert_eq!(cache.totals(), (segments + 1, segments * 3));
+        // Whole cache chunks, whatever the budget
+        let (vertices, indices, sizes) = drain(&mut cache, 1);
+        assert!(sizes.iter().all(|&size| size <= CACHE_CHUNK + SEGMENT));
+        assert_eq!((vertices, indices), in_one_go(segments));
+        drop(cache);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 5]).unwrap();
+        let mut cut = CacheSource::open(&path).unwrap();
+        let failed = std::iter::from_fn(|| cut.next_chunk(DEFAULT_BUDGET).map(|c| c.is_err()))
+            .any(|failed| failed);
+        assert!(failed, "A cut short cache streamed without complaint");
+        std::fs::write(&path, b"FORAYMS0 and then some").unwrap();
+        assert!(CacheSource::open(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn arena(
+        gpu: &GpuContext,
+        memory: &GpuMemoryTracker,
+        vertices: u32,
+        indices: u32,
+    ) -> MeshArena {
+        MeshArena::new(
+            &gpu.device,
+            memory,
+            "Stream Test",
+            &Vertex::desc(),
+            vertices,
+            indices,
+        )
+    }
+
+    #[test]
+    fn streaming_at_2mb_a_frame_lands_what_a_direct_upload_does() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let arena = arena(gpu, &memory, SEGMENTS + 1, SEGMENTS * 3);
+        let source = Box::new(CircleSource::new(SEGMENTS, vertex));
+        let mut stream = MeshStream::new(&arena, "Circle", TRIANGLES, source, DEFAULT_BUDGET)
+            .expect("No room for the circle");
+        let total = stream.bytes(stream.totals);
+        let (mut drawn, mut landed) = (0, 0);
+        loop {
+            let done = stream.pump(&gpu.queue).expect("Pump failed");
+            let now = stream.bytes(stream.landed);
+            assert!(
+                now - landed <= DEFAULT_BUDGET + SEGMENT,
+                "{} in a frame",
+                now - landed
+            );
+            // Drawable from the first frame, and more of it every frame after
+            assert!(
+                stream.mesh.count() > drawn,
+                "Frame {} drew no more",
+                stream.frames
+            );
+            (drawn, landed) = (stream.mesh.count(), now);
+            stats::submit(&gpu.queue, std::iter::empty());
+            if done {
+                break;
+            }
+        }
+        assert!(stream.is_done());
+        assert!((stream.progress() - 1.0).abs() < 1e-6);
+        assert_eq!(drawn, SEGMENTS * 3);
+        assert!(u64::from(stream.frames) <= total.div_ceil(DEFAULT_BUDGET) + 1);
+        // A frame's share is a couple of MB of memcpy, nowhere near the whole upload's stall
+        assert!(
+            stream.longest < Duration::from_millis(250),
+            "{:?}",
+            stream.longest
+        );
+
+        // Everything in one buffer_init, how the mesh was uploaded before streaming
+        let (vertices, indices) = in_one_go(SEGMENTS);
+        let direct = |label, contents: &[u8]| {
+            let buffer = gpu
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: wgpu::BufferUsages::COPY_SRC,
+                });
+            gpu.read_buffer(&buffer, 0..contents.len() as u64)
+        };
+        let slot = stream.mesh.slot().expect("Not in the arena");
+        let (buffer, range) = slot.vertex_bytes();
+        let streamed = gpu.read_buffer(buffer, range);
+        assert!(
+            streamed == direct("Direct Vertices", &vertices),
+            "Vertices differ"
+        );
+        let (buffer, range) = slot.index_bytes();
+        let streamed = gpu.read_buffer(buffer, range);
+        assert!(
+            streamed == direct("Direct Indices", &indices),
+            "Indices differ"
+        );
+    }
+
+    #[test]
+    fn cancelling_mid_stream_gives_everything_back() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let segments = 200_000;
+        let arena = arena(gpu, &memory, segments + 1, segments * 3);
+        let source = Box::new(CircleSource::new(segments, vertex));
+        let mut stream = MeshStream::new(&arena, "Circle", TRIANGLES, source, 256 * 1024).unwrap();
+        assert!(!stream.pump(&gpu.queue).unwrap());
+        assert!(!stream.pump(&gpu.queue).unwrap());
+        assert!(stream.progress() > 0.0 && stream.progress() < 1.0);
+        let again = |arena: &MeshArena| {
+            arena
+                .reserve("Again", TRIANGLES, 24, segments + 1, segments * 3)
+                .is_ok()
+        };
+        assert!(!again(&arena));
+
+        // The scene switched away
+        drop(stream);
+        assert!(again(&arena));
+        drop(arena);
+        assert_eq!(memory.report().bytes_in(MemoryCategory::Meshes), 0);
+    }
+
+    // Hands out exactly the chunks it was given, whatever the budget
+    struct Scripted {
+        totals: (u32, u32),
+        chunks: VecDeque<(Vec<Vertex>, Vec<u32>)>,
+    }
+
+    impl ChunkSource for Scripted {
+        fn stride(&self) -> u64 {
+            24
+        }
+
+        fn totals(&self) -> (u32, u32) {
+            self.totals
+        }
+
+        fn next_chunk(&mut self, _budget: u64) -> Option<Result<Chunk<'_>, String>> {
+            let (vertices, indices) = self.chunks.pop_front()?;
+            Some(Ok(Chunk {
+                vertices: Cow::Owned(bytemuck::cast_slice(&vertices).to_vec()),
+                indices: Cow::Owned(bytemuck::cast_slice(&indices).to_vec()),
+            }))
+        }
+    }
+
+    #[test]
+    fn sources_that_break_their_word_are_refused() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let arena = arena(gpu, &memory, 16, 16);
+        let v = |n: usize| vec![vertex(Vec2::ZERO, 0.0); n];
+        let pumped = |totals, chunks: Vec<(Vec<Vertex>, Vec<u32>)>| {
+            let source = Box::new(Scripted {
+                totals,
+                chunks: chunks.into(),
+            });
+            MeshStream::new(&arena, "Scripted", TRIANGLES, source, DEFAULT_BUDGET)
+                .and_then(|mut stream| stream.pump(&gpu.queue))
+                .map_err(|e| e.to_string())
+        };
+        let refused = |result: Result<bool, String>, why: &str| {
+            let error = result.expect_err(why);
+            assert!(error.contains(why), "{error}");
+        };
+
+        assert_eq!(
+            pumped((4, 6), vec![(v(3), vec![0, 1, 2]), (v(1), vec![0, 2, 3])]),
+            Ok(true)
+        );
+        refused(
+            pumped((3, 3), vec![(v(4), vec![0, 1, 2])]),
+            "more than the 3 vertices",
+        );
+        refused(
+            pumped((3, 3), vec![(v(3), vec![0, 1, 2, 0, 1, 2])]),
+            "more than the 3 vertices and 3 indices",
+        );
+        refused(
+            pumped((4, 6), vec![(v(2), vec![0, 1, 3])]),
+            "index 3 comes before its vertex",
+        );
+        refused(
+            pumped((6, 6), vec![(v(3), vec![0, 1, 2])]),
+            "the source ended after 3 of 6",
+        );
+        // All of it refused, none of it left in the arena
+        assert!(arena.reserve("After", TRIANGLES, 24, 16, 16).is_ok());
+    }
+}
@@ -0,0 +1,299 @@
+use glam::Vec2;
+
+use crate::error::ForayError;
+use crate::memory::GpuMemoryTracker;
+use crate::mesh::{self, Indices, Mesh, UvMapping, VertexColor};
+
+// Vertices that can be blended into each other, the blend is what gets drawn
+pub trait Morph: bytemuck::Pod {
+    // `t` 0 is self, 1 is `other`
+    fn morph(&self, other: &Self, t: f32) -> Self;
+}
+
+// One shape a DynamicMesh can take
+pub struct MorphTarget<V> {
+    pub name: String,
+    pub vertices: Vec<V>,
+}
+
+// A mesh rewritten from the CPU whenever its morph position changes. All targets share the
+// mesh's indices and topology, so they have to agree on the vertex count
+pub struct DynamicMesh<V> {
+    pub mesh: Mesh,
+    targets: Vec<MorphTarget<V>>,
+    // 0 is the first target, 1 the second and so on, in between blends the two around it
+    position: f32,
+}
+
+impl<V: Morph> DynamicMesh<V> {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        name: &str,
+        layout: &wgpu::VertexBufferLayout,
+        topology: wgpu::PrimitiveTopology,
+        targets: Vec<MorphTarget<V>>,
+        indices: &[u32],
+    ) -> Result<Self, ForayError> {
+        check_targets(name, &targets)?;
+        let mesh = Mesh::new_dynamic(
+            device,
+            memory,
+            name,
+            layout,
+            topology,
+            &targets[0].vertices,
+            Indices::U32(indices),
+        );
+        Ok(Self {
+            mesh,
+            targets,
+            position: 0.0,
+        })
+    }
+
+    pub fn targets(&self) -> &[MorphTarget<V>] {
+        &self.targets
+    }
+
+    // Blends the targets either side of `position` and uploads the result. Clamped to the
+    // targets there are, nothing is written when it hasn't moved
+    pub fn set_position(&mut self, queue: &wgpu::Queue, position: f32) {
+        let position = position.clamp(0.0, self.targets.len().saturating_sub(1) as f32);
+        if position == self.position {
+            return;
+        }
+        self.position = position;
+        self.upload(queue);
+    }
+
+    fn upload(&self, queue: &wgpu::Queue) {
+        let position = self.position;
+        let last = self.targets.len().saturating_sub(1);
+        let first = (position.floor() as usize).min(last);
+        let second = (first + 1).min(last);
+        let t = position - first as f32;
+        let blended: Vec<V> = self.targets[first]
+            .vertices
+            .iter()
+            .zip(&self.targets[second].vertices)
+            .map(|(a, b)| a.morph(b, t))
+            .collect();
+        self.mesh.write_vertices(queue, &blended);
+    }
+}
+
+impl<V: Morph + VertexColor> DynamicMesh<V> {
+    // Every target colored from the image, each over its own box, and what's drawn redone
+    pub fn bake_colors(
+        &mut self,
+        queue: &wgpu::Queue,
+        image: &image::RgbaImage,
+        mapping: UvMapping,
+    ) {
+        for target in &mut self.targets {
+            mesh::bake_vertex_colors(&mut target.vertices, image, mapping);
+        }
+        self.upload(queue);
+    }
+}
+
+// At least one target, none of them empty and all with the first one's vertex count
+fn check_targets<V>(name: &str, targets: &[MorphTarget<V>]) -> Result<(), ForayError> {
+    let Some(first) = targets.first() else {
+        return Err(ForayError::NoMorphTargets(name.to_owned()));
+    };
+    let expected = first.vertices.len();
+    for (index, target) in targets.iter().enumerate() {
+        if target.vertices.len() != expected || expected == 0 {
+            return Err(ForayError::MorphMismatch {
+                mesh: name.to_owned(),
+                target: index,
+                expected,
+                found: target.vertices.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// `count` points evenly spaced along the closed outline through `points`, starting at the
+// first one. Outlines with different corner counts become morphable this way, as long as
+// both start at corresponding corners and go around the same way
+pub fn resample_closed(points: &[Vec2], count: usize) -> Vec<Vec2> {
+    if points.len() < 2 || count == 0 {
+        return points.iter().copied().cycle().take(count).collect();
+    }
+    let edges: Vec<(Vec2, Vec2)> = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(&a, &b)| (a, b))
+        .collect();
+    let perimeter: f32 = edges.iter().map(|(a, b)| a.distance(*b)).sum();
+    let spacing = perimeter / count as f32;
+
+    let mut resampled = Vec::with_capacity(count);
+    let mut edge = 0;
+    // How far along the outline the current edge starts
+    let mut edge_start = 0.0;
+    for i in 0..count {
+        let along = i as f32 * spacing;
+        while edge < edges.len() - 1 && along > edge_start + edges[edge].0.distance(edges[edge].1) {
+            edge_start += edges[edge].0.distance(edges[edge].1);
+            edge += 1;
+        }
+        let (a, b) = edges[edge];
+        let length = a.distance(b);
+        let t = if length > 0.0 {
+            (along - edge_start) / length
+        } else {
+            0.0
+        };
+        resampled.push(a.lerp(b, t.clamp(0.0, 1.0)));
+    }
+    resampled
+}
+
+// Triangle fan indices for a center vertex at 0 followed by `count` outline vertices.
+// Fine for anything star shaped around its center
+pub fn fan_indices(count: usize) -> Vec<u32> {
+    (0..count as u32)
+        .flat_map(|i| [0, 1 + i, 1 + (i + 1) % count as u32])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Point([f32; 2]);
+
+    impl Morph for Point {
+        fn morph(&self, other: &Self, t: f32) -> Self {
+            Point([0, 1].map(|i| self.0[i] + (other.0[i] - self.0[i]) * t))
+        }
+    }
+
+    const LAYOUT: wgpu::VertexBufferLayout = wgpu::VertexBufferLayout {
+        array_stride: 8,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+    };
+
+    fn target(name: &str, count: usize) -> MorphTarget<Point> {
+        MorphTarget {
+            name: name.to_owned(),
+            vertices: (0..count).map(|i| Point([i as f32, 0.0])).collect(),
+        }
+    }
+
+    #[test]
+    fn resampled_points_are_evenly_spaced_on_the_outline() {
+        let square = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(4.0, 0.0),
+            Vec2::new(4.0, 4.0),
+            Vec2::new(0.0, 4.0),
+        ];
+        // Perimeter 16, so a point every 2 starting at the first corner
+        let points = resample_closed(&square, 8);
+        let expected = [
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 2.0),
+            (4.0, 4.0),
+            (2.0, 4.0),
+            (0.0, 4.0),
+            (0.0, 2.0),
+        ];
+        assert_eq!(points.len(), expected.len());
+        for (point, (x, y)) in points.iter().zip(expected) {
+            assert!(
+                point.distance(Vec2::new(x, y)) < 1e-5,
+                "{point} vs ({x}, {y})"
+            );
+        }
+        // Fewer points than corners still land on the outline
+        for point in resample_closed(&square, 3) {
+            let on_edge = [point.x, point.y]
+                .iter()
+                .any(|c| c.abs() < 1e-5 || (c - 4.0).abs() < 1e-5);
+            assert!(on_edge, "{point} is off the square");
+        }
+        // Too little outline to walk, it's repeated instead
+        assert_eq!(resample_closed(&square[..1], 3), vec![square[0]; 3]);
+        assert!(resample_closed(&square, 0).is_empty());
+    }
+
+    #[test]
+    fn fans_close_back_on_the_first_outline_vertex() {
+        assert_eq!(fan_indices(3), [0, 1, 2, 0, 2, 3, 0, 3, 1]);
+        let fan = fan_indices(64);
+        assert_eq!(fan.len(), 64 * 3);
+        assert!(fan.iter().all(|&i| i <= 64));
+        assert_eq!(fan[fan.len() - 3..], [0, 64, 1]);
+    }
+
+    #[test]
+    fn targets_must_agree_on_vertex_count() {
+        assert!(check_targets("Shape", &[target("a", 3), target("b", 3)]).is_ok());
+        assert!(matches!(
+            check_targets("Shape", &[target("a", 3), target("b", 3), target("c", 4)]),
+            Err(ForayError::MorphMismatch {
+                target: 2,
+                expected: 3,
+                found: 4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            check_targets("Shape", &[target("a", 0), target("b", 0)]),
+            Err(ForayError::MorphMismatch {
+                target: 0,
+                expected: 0,
+                found: 0,
+                ..
+            })
+        ));
+        assert!(matches!(
+            check_targets::<Point>("Shape", &[]),
+            Err(ForayError::NoMorphTargets(mesh)) if mesh == "Shape"
+        ));
+    }
+
+    #[test]
+    fn no_targets_is_an_error_not_a_panic() {
+        let Ok(gpu) = crate::gpu_context::GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let new = |targets| {
+            DynamicMesh::new(
+                &gpu.device,
+                &memory,
+                "Morph",
+                &LAYOUT,
+                wgpu::PrimitiveTopology::TriangleList,
+                targets,
+                &[0, 1, 2],
+            )
+        };
+        assert!(matches!(
+            new(Vec::new()),
+            Err(ForayError::NoMorphTargets(_))
+        ));
+
+        // A single target has nowhere to go, positions clamp to it
+        let mut single = new(vec![target("only", 3)]).unwrap();
+        single.set_position(&gpu.queue, 2.5);
+        assert!(single.position.abs() < f32::EPSILON);
+
+        let mut pair = new(vec![target("a", 3), target("b", 3)]).unwrap();
+        pair.set_position(&gpu.queue, 7.0);
+        assert!((pair.position - 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::pipeline_bank::RenderPipelineBank;
+use crate::shader_bank::ShaderBank;
+use crate::targets::{TargetDesc, TargetHandle, TargetRegistry};
+
+// One entry per @location written by fs_mrt
+const MRT_TARGETS: &[(&str, wgpu::TextureFormat)] = &[
+    ("MRT Color", wgpu::TextureFormat::Rgba8UnormSrgb),
+    ("MRT Pattern", wgpu::TextureFormat::Rgba16Float),
+];
+
+// Draws the pentagon into several targets at once, `view` picks which one gets shown
+pub struct MrtDemo {
+    pub targets: Vec<TargetHandle>,
+    pub view: Option<usize>,
+}
+
+impl MrtDemo {
+    pub fn new(device: &wgpu::Device, registry: &mut TargetRegistry) -> Self {
+        let targets = MRT_TARGETS
+            .iter()
+            .map(|&(label, format)| {
+                registry.create(
+                    device,
+                    TargetDesc {
+                        label,
+                        format,
+                        scale: 1.0,
+                        storage: false,
+                    },
+                )
+            })
+            .collect();
+        Self {
+            targets,
+            view: None,
+        }
+    }
+
+    pub fn register_pipeline(
+        device: &wgpu::Device,
+        shaders: &ShaderBank,
+        bank: &mut RenderPipelineBank,
+    ) {
+        let builder = shaders
+            .builder("MRT Pipeline", "mrt")
+            .vertex_buffer(crate::Vertex::desc());
+        let builder = MRT_TARGETS.iter().fold(builder, |builder, &(_, format)| {
+            builder.color_target(format)
+        });
+        // The surface format is unused, every target was given explicitly
+        bank.register("mrt", builder.build(device, MRT_TARGETS[0].1));
+    }
+
+    // Off -> target 0 -> target 1 -> ... -> Off
+    pub fn cycle_view(&mut self) {
+        self.view = match self.view {
+            None => Some(0),
+            Some(i) if i + 1 < self.targets.len() => Some(i + 1),
+            Some(_) => None,
+        };
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use glam::{Mat4, Vec3};
+
+use crate::error::ForayError;
+use crate::material::{DrawItem, MaterialLibrary};
+use crate::mesh::MeshData;
+
+// Writes the items as one OBJ with their transforms baked in, one `o` group per submesh
+// drawn, and an MTL next to it with each material's tint as its diffuse color. Only
+// positions go out. Items whose mesh kept no geometry (anything not made with
+// Mesh::from_data) or that aren't triangle lists are skipped with a warning. Returns how
+// many triangles were written
+pub fn export(
+    path: &Path,
+    items: &[DrawItem],
+    materials: &MaterialLibrary,
+) -> Result<usize, ForayError> {
+    let mtl_path = path.with_extension("mtl");
+    let mut obj = String::new();
+    let mut mtl = String::new();
+    let mut written_materials: Vec<String> = Vec::new();
+    if let Some(name) = mtl_path.file_name() {
+        writeln!(obj, "mtllib {}", name.to_string_lossy()).unwrap();
+    }
+
+    // OBJ indices are 1-based and count every vertex written so far, across objects
+    let mut base = 1;
+    let mut triangles = 0;
+    for item in items {
+        let Some(geometry) = &item.mesh.geometry else {
+            log::warn!("Mesh \"{}\" has no CPU copy, not exported", item.mesh.name);
+            continue;
+        };
+        if item.mesh.topology != wgpu::PrimitiveTopology::TriangleList {
+            log::warn!(
+                "Mesh \"{}\" isn't a triangle list, not exported",
+                item.mesh.name
+            );
+            continue;
+        }
+        let submeshes = match item.submesh {
+            Some(index) => std::slice::from_ref(item.mesh.submesh(index)?),
+            None => &item.mesh.submeshes[..],
+        };
+
+        let material = materials.get(item.material);
+        let material_name = obj_name(&material.name);
+        if !written_materials.contains(&material_name) {
+            let [r, g, b] = material.params.diffuse();
+            writeln!(mtl, "newmtl {material_name}\nKd {r} {g} {b}\n").unwrap();
+            written_materials.push(material_name.clone());
+        }
+
+        for submesh in submeshes {
+            writeln!(obj, "o {}\nusemtl {material_name}", obj_name(&submesh.name)).unwrap();
+            let range = submesh.index_range.start as usize..submesh.index_range.end as usize;
+            triangles += write_triangles(
+                &mut obj,
+                &geometry.indices[range],
+                geometry,
+                item.transform,
+                &mut base,
+            );
+        }
+    }
+
+    let write = |path: &Path, text: &str| {
+        std::fs::write(path, text).map_err(|e| ForayError::ObjExport {
+            path: path.to_owned(),
+            reason: e.to_string(),
+        })
+    };
+    write(path, &obj)?;
+    write(&mtl_path, &mtl)?;
+    Ok(triangles)
+}
+
+// Vertices the triangles use, in world space, then the faces pointing at them. `{}` on
+// an f32 prints the shortest text that parses back to the same value, so positions come
+// back exactly
+fn write_triangles(
+    obj: &mut String,
+    indices: &[u32],
+    geometry: &MeshData<Vec3>,
+    transform: Mat4,
+    base: &mut u32,
+) -> usize {
+    // Only the vertices these triangles touch, numbered in first use order
+    let mut remap: Vec<Option<u32>> = vec![None; geometry.vertices.len()];
+    let mut local = Vec::with_capacity(indices.len());
+    let mut used = 0;
+    for &index in indices {
+        let slot = remap[index as usize].get_or_insert_with(|| {
+            let p = transform.transform_point3(geometry.vertices[index as usize]);
+            writeln!(obj, "v {} {} {}", p.x, p.y, p.z).unwrap();
+            used += 1;
+            used - 1
+        });
+        local.push(*base + *slot);
+    }
+    // A mirroring transform turns counter-clockwise triangles clockwise, swapping two
+    // corners turns them back
+    let mirrored = transform.determinant() < 0.0;
+    for triangle in local.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+        if mirrored {
+            writeln!(obj, "f {a} {c} {b}").unwrap();
+        } else {
+            writeln!(obj, "f {a} {b} {c}").unwrap();
+        }
+    }
+    *base += used;
+    local.len() / 3
+}
+
+// OBJ and MTL names end at whitespace
+fn obj_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The v and f lines back out of the text, faces as 1-based indices
+    fn parse(obj: &str) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+        let mut positions = Vec::new();
+        let mut faces = Vec::new();
+        for line in obj.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let p: Vec<f32> = words.map(|w| w.parse().unwrap()).collect();
+                    positions.push(Vec3::new(p[0], p[1], p[2]));
+                }
+                Some("f") => {
+                    let f: Vec<u32> = words.map(|w| w.parse().unwrap()).collect();
+                    faces.push([f[0], f[1], f[2]]);
+                }
+                _ => {}
+            }
+        }
+        (positions, faces)
+    }
+
+    // Two counter-clockwise triangles in the xy plane, seen from +z, with coordinates that
+    // don't print short. The last vertex isn't used
+    fn quad() -> MeshData<Vec3> {
+        MeshData::new(
+            "Quad",
+            vec![
+                Vec3::new(0.1, -0.2, 0.0),
+                Vec3::new(1.0e-7, 0.3, 0.0),
+                Vec3::new(123_456.79, 0.7, 0.0),
+                Vec3::new(0.333_333_34, 99.99, 0.0),
+                Vec3::new(5.0, 5.0, 5.0),
+            ],
+            vec![0, 2, 1, 1, 2, 3],
+        )
+    }
+
+    fn faces_toward_z(corners: [Vec3; 3]) -> bool {
+        (corners[1] - corners[0]).cross(corners[2] - corners[0]).z > 0.0
+    }
+
+    // The triangles as world space corners, straight from the mesh
+    fn expected(mesh: &MeshData<Vec3>, transform: Mat4) -> Vec<[Vec3; 3]> {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|t| [0, 1, 2].map(|i| transform.transform_point3(mesh.vertices[t[i] as usize])))
+            .collect()
+    }
+
+    #[test]
+    fn objects_are_rebased_and_positions_exact() {
+        let mesh = quad();
+        let first = Mat4::from_translation(Vec3::new(10.0, 0.5, -3.0));
+        let second = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.5),
+            glam::Quat::from_rotation_z(0.3),
+            Vec3::new(-1.0, 4.0, 0.0),
+        );
+        let mut obj = String::new();
+        let mut base = 1;
+        assert_eq!(
+            write_triangles(&mut obj, &mesh.indices, &mesh, first, &mut base),
+            2
+        );
+        // Only the four used vertices were numbered
+        assert_eq!(base, 5);
+        assert_eq!(
+            write_triangles(&mut obj, &mesh.indices, &mesh, second, &mut base),
+            2
+        );
+        assert_eq!(base, 9);
+
+        let (positions, faces) = parse(&obj);
+        assert_eq!(positions.len(), 8);
+        // The second object points only at its own vertices
+        assert!(faces[..2].iter().flatten().all(|&i| (1..=4).contains(&i)));
+        assert!(faces[2..].iter().flatten().all(|&i| (5..=8).contains(&i)));
+
+        let read: Vec<[Vec3; 3]> = faces
+            .iter()
+            .map(|face| face.map(|i| positions[i as usize - 1]))
+            .collect();
+        let mut want = expected(&mesh, first);
+        want.extend(expected(&mesh, second));
+        // Bit for bit, `{}` prints what parses back to the same f32
+        assert_eq!(read, want);
+        assert!(read.iter().all(|&t| faces_toward_z(t)));
+    }
+
+    #[test]
+    fn mirrored_objects_stay_counter_clockwise() {
+        let mesh = quad();
+        let mirror = Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+        let mut obj = String::new();
+        let mut base = 1;
+        write_triangles(&mut obj, &mesh.indices, &mesh, mirror, &mut base);
+        let (positions, faces) = parse(&obj);
+        for (face, original) in faces.iter().zip(expected(&mesh, mirror)) {
+            let corners = face.map(|i| positions[i as usize - 1]);
+            // Mirroring alone would have flipped it
+            assert!(!faces_toward_z(original));
+            assert!(faces_toward_z(corners), "{corners:?}");
+            // Same corners, two of them swapped
+            assert_eq!(corners, [original[0], original[2], original[1]]);
+        }
+    }
+
+    #[test]
+    fn names_lose_their_whitespace() {
+        assert_eq!(obj_name("Left  Wing\tTip"), "Left_Wing_Tip");
+        assert_eq!(obj_name("plain"), "plain");
+    }
+}
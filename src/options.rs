@@ -0,0 +1,463 @@
+use std::iter::Peekable;
+#[cfg(feature = "remote")]
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::camera2d::ResizePolicy;
+use crate::camera3d;
+use crate::colors::{RgbaColor, Theme};
+use crate::depth::DepthConvention;
+use crate::headless::RenderJob;
+use crate::memory;
+use crate::overlay::Anchor;
+use crate::pacing::Easing;
+#[cfg(feature = "remote")]
+use crate::remote;
+use crate::scene::StressParams;
+use crate::supersample;
+use crate::watchdog;
+
+// Which display to open on
+#[derive(Clone, Debug)]
+pub enum MonitorChoice {
+    Index(usize),
+    // Case-insensitive substring of the monitor's name
+    Name(String),
+}
+
+// Command line switches
+pub struct Options {
+    // --scene-file <path>: load the scene from there, Ctrl+S saves back to it. A built-in
+    // name (starter, instancing_ring, bouncing_pentagons, stress) starts from that scene
+    // instead. --scene <name> is the same, like `render --scene`
+    pub scene_file: Option<PathBuf>,
+    // --items <n>, --seed <n>, --spin <radians/s>: what the stress scene generates
+    pub stress: StressParams,
+    // --frame-latency <n>: frames the swapchain may queue, 1 is the most responsive
+    pub frame_latency: u32,
+    // --sync: wait for the GPU after every present, so timings include the GPU work
+    pub sync_after_present: bool,
+    // --latency-test: left click flashes the screen and logs click-to-present time
+    pub latency_test: bool,
+    // --icon <path>: window icon instead of the embedded one
+    pub icon: Option<PathBuf>,
+    // --cursor <png|name>: custom mouse cursor (hotspot in the middle) or a standard one,
+    // arrow, ibeam, crosshair, hand, hresize or vresize
+    pub cursor: Option<PathBuf>,
+    // --monitor <index or name>: open there instead of on the primary display
+    pub monitor: Option<MonitorChoice>,
+    // --window-pos <x>,<y>: top-left corner, relative to the chosen monitor
+    pub window_pos: Option<(i32, i32)>,
+    // --center: centered in the chosen monitor's work area
+    pub center: bool,
+    // --transparent <alpha>: see-through window background, the views clear to this alpha.
+    // Needs a compositor, the window stays opaque without one
+    pub transparent: Option<f64>,
+    // --opacity <0..1>: the whole window, content and decorations, `opacity` in the console
+    pub opacity: f32,
+    // --click-through: mouse input goes to the window behind, F8 toggles it
+    pub click_through: bool,
+    // --list-monitors: print the connected monitors and quit
+    pub list_monitors: bool,
+    // --capabilities: print what the adapter supports and what gets disabled, then quit
+    pub capabilities: bool,
+    // --memory-budget <MiB>: warn when the GPU memory in use is over this after a scene loads
+    pub memory_budget: u64,
+    // --gpu-timeout <seconds>: how long a frame's GPU work may take before the watchdog
+    // calls it a hang and stops submitting. Raise it for scenes that are legitimately slow
+    pub gpu_timeout: Duration,
+    // --crash-test: panics on crash::TEST_FRAME to try out the crash report, not for users
+    pub crash_test: bool,
+    // --target-fps <n>: render at most this often instead of at the monitor's refresh rate
+    pub target_fps: Option<u32>,
+    // --event-driven: sleep until input or a redraw request instead of polling every frame,
+    // so an idle window costs next to no CPU
+    pub event_driven: bool,
+    // --depth-prepass: the deferred view lays down depth first and shades each pixel once,
+    // `prepass` in the console toggles it
+    pub depth_prepass: bool,
+    // --msaa <samples>: MSAA in the deferred view's g-buffer, 1 or 4. Cutouts cover
+    // samples by alpha instead of discarding. `msaa` in the console changes it
+    pub msaa: u32,
+    // --warm-up: draw once with every pipeline before the first frame, and with any rebuilt
+    // later, so drivers that compile on first use don't hitch mid-run. Costs startup time
+    pub warm_up: bool,
+    // --fly-speed <units per second>: what the fly camera starts at, scrolling while flying
+    // changes it from there
+    pub fly_speed: f32,
+    // --depth <standard|reverse|reverse-infinite>: which way depth runs in the 3D views,
+    // for the whole run
+    pub depth: DepthConvention,
+    // --effects <name>,<name>: post effects enabled at startup (vignette, grade, bloom,
+    // exposure, pixelate, dither)
+    pub effects: Vec<String>,
+    // --dither-palette <hex>,<hex>: colors the dither effect picks from instead of levels
+    pub dither_palette: Vec<RgbaColor>,
+    // --lut <png>: color grading LUT as an N*N x N strip, identity when left out
+    pub lut: Option<PathBuf>,
+    // --easing <linear|smoothstep|ease-out>: curve of scene item fades
+    pub easing: Easing,
+    // --theme <dark|light>: colors of the overlay, console, inspector and gizmos. Light is
+    // for light clear colors
+    pub theme: Theme,
+    // --resize <extend|stretch|letterbox>: what resizing the window does to the 2D views.
+    // Stretch and letterbox keep what the first window size showed, see ResizePolicy
+    pub resize: String,
+    // --trace-chrome <path>: F6 starts and stops recording frame phase spans, written
+    // there as a Chrome trace
+    #[cfg(feature = "trace")]
+    pub trace_chrome: Option<PathBuf>,
+    // --audio: capture the default input device into Globals::audio, the SDF playground's
+    // audio entry shows it
+    #[cfg(feature = "audio")]
+    pub audio: bool,
+    // --remote [port]: take console commands as JSON lines over TCP, see remote.rs. The
+    // port defaults to remote::DEFAULT_PORT
+    #[cfg(feature = "remote")]
+    pub remote: Option<u16>,
+    // --remote-bind <address>: where --remote listens, localhost by default. Anything else
+    // needs a token in FORAY_REMOTE_TOKEN
+    #[cfg(feature = "remote")]
+    pub remote_bind: IpAddr,
+    // --supersample <samples>: how many jittered samples Shift+F12 averages
+    pub supersample_samples: u32,
+    // --supersample-scale <1-4>: times the window's size Shift+F12 renders at before
+    // shrinking it back down
+    pub supersample_scale: u32,
+    // --snap <size>: world units between grid lines of the 2D view, [ and ] halve and double it
+    pub snap_spacing: f32,
+    // --stats-anchor <top-left|top-right|bottom-center|...>: where the F3 stats panel sits
+    pub stats_anchor: Anchor,
+    // --font <ttf|otf>: font for text drawn with Frame::draw_text instead of the embedded one
+    pub font: Option<PathBuf>,
+    // --timeline <path>: keyframed camera, colors, effect parameters and visibility, played
+    // from the start. F7 plays and pauses, Left/Right seek
+    pub timeline: Option<PathBuf>,
+    // --require <FEATURE>,<FEATURE>: wgpu features the device must have, by their
+    // Features constant name (TIMESTAMP_QUERY). Startup fails when the adapter lacks one
+    pub required_features: wgpu::Features,
+    // `render ...` as the first argument: frames to PNGs without a window, see RenderJob
+    pub render: Option<RenderJob>,
+}
+
+// Nothing passed, what an application embedding State starts from too
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scene_file: None,
+            stress: StressParams::default(),
+            frame_latency: 2,
+            sync_after_present: false,
+            latency_test: false,
+            icon: None,
+            cursor: None,
+            monitor: None,
+            window_pos: None,
+            center: false,
+            transparent: None,
+            opacity: 1.0,
+            click_through: false,
+            list_monitors: false,
+            capabilities: false,
+            crash_test: false,
+            gpu_timeout: watchdog::DEFAULT_TIMEOUT,
+            target_fps: None,
+            memory_budget: memory::DEFAULT_BUDGET,
+            event_driven: false,
+            depth_prepass: false,
+            msaa: 1,
+            warm_up: false,
+            fly_speed: camera3d::DEFAULT_FLY_SPEED,
+            depth: DepthConvention::Standard,
+            effects: Vec::new(),
+            dither_palette: Vec::new(),
+            lut: None,
+            easing: Easing::SmoothStep,
+            theme: Theme::DARK,
+            resize: "extend".to_owned(),
+            #[cfg(feature = "trace")]
+            trace_chrome: None,
+            #[cfg(feature = "audio")]
+            audio: false,
+            #[cfg(feature = "remote")]
+            remote: None,
+            #[cfg(feature = "remote")]
+            remote_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            supersample_samples: supersample::DEFAULT_SAMPLES,
+            supersample_scale: supersample::DEFAULT_SCALE,
+            snap_spacing: 50.0,
+            stats_anchor: Anchor::TopLeft,
+            font: None,
+            timeline: None,
+            required_features: wgpu::Features::empty(),
+            render: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Self {
+        let mut options = Self::default();
+
+        let mut args = std::env::args().skip(1).peekable();
+        if args.peek().map(String::as_str) == Some("render") {
+            args.next();
+            options.render = Some(RenderJob::from_args(args));
+            return options;
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scene-file" | "--scene" => {
+                    options.scene_file = args.next().map(PathBuf::from);
+                }
+                "--frame-latency" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(latency) => options.frame_latency = latency,
+                    None => log::warn!("--frame-latency wants a number, keeping 2"),
+                },
+                "--sync" => options.sync_after_present = true,
+                "--event-driven" => options.event_driven = true,
+                "--depth-prepass" => options.depth_prepass = true,
+                "--msaa" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(samples) => options.msaa = samples,
+                    None => log::warn!("--msaa wants a sample count, keeping 1"),
+                },
+                "--warm-up" => options.warm_up = true,
+                "--fly-speed" => match args.next().and_then(|n| n.parse::<f32>().ok()) {
+                    Some(speed) if speed > 0.0 => options.fly_speed = camera3d::clamp_speed(speed),
+                    _ => log::warn!("--fly-speed wants units per second above 0, keeping 2"),
+                },
+                "--depth" => match args.next().as_deref().and_then(DepthConvention::parse) {
+                    Some(depth) => options.depth = depth,
+                    None => log::warn!(
+                        "--depth wants standard, reverse or reverse-infinite, keeping standard"
+                    ),
+                },
+                "--latency-test" => options.latency_test = true,
+                "--icon" => options.icon = args.next().map(PathBuf::from),
+                "--cursor" => options.cursor = args.next().map(PathBuf::from),
+                "--monitor" => {
+                    options.monitor = args.next().map(|choice| match choice.parse() {
+                        Ok(index) => MonitorChoice::Index(index),
+                        Err(_) => MonitorChoice::Name(choice),
+                    })
+                }
+                "--window-pos" => {
+                    let pos = args.next().and_then(|pos| {
+                        let (x, y) = pos.split_once(',')?;
+                        Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+                    });
+                    if pos.is_none() {
+                        log::warn!("--window-pos wants <x>,<y>, ignoring it");
+                    }
+                    options.window_pos = pos;
+                }
+                "--center" => options.center = true,
+                "--transparent" => match args.next().and_then(|alpha| alpha.parse().ok()) {
+                    Some(alpha) => options.transparent = Some(alpha),
+                    None => log::warn!("--transparent wants the background alpha, staying opaque"),
+                },
+                "--opacity" => match args.next().and_then(|opacity| opacity.parse().ok()) {
+                    Some(opacity) => options.opacity = opacity,
+                    None => log::warn!("--opacity wants a number from 0 to 1, keeping 1"),
+                },
+                "--click-through" => options.click_through = true,
+                "--list-monitors" => options.list_monitors = true,
+                "--capabilities" => options.capabilities = true,
+                "--crash-test" => options.crash_test = true,
+                "--gpu-timeout" => match args
+                    .next()
+                    .and_then(|n| n.parse::<f32>().ok())
+                    .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok())
+                {
+                    Some(timeout) if !timeout.is_zero() => options.gpu_timeout = timeout,
+                    _ => log::warn!(
+                        "--gpu-timeout wants seconds above 0, keeping {:?}",
+                        watchdog::DEFAULT_TIMEOUT
+                    ),
+                },
+                "--target-fps" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(fps) => options.target_fps = Some(fps),
+                    None => log::warn!("--target-fps wants a number, following the monitor"),
+                },
+                "--memory-budget" => match args.next().and_then(|n| n.parse::<u64>().ok()) {
+                    Some(mib) => options.memory_budget = mib * 1024 * 1024,
+                    None => log::warn!("--memory-budget wants a size in MiB, keeping 1024"),
+                },
+                "--effects" => match args.next() {
+                    Some(list) => {
+                        options.effects =
+                            list.split(',').map(|name| name.trim().to_owned()).collect()
+                    }
+                    None => log::warn!("--effects wants a comma separated list of effect names"),
+                },
+                "--dither-palette" => match args.next() {
+                    Some(list) => options.dither_palette = palette(&list),
+                    None => log::warn!("--dither-palette wants a comma separated list of colors"),
+                },
+                "--require" => match args.next() {
+                    Some(list) => {
+                        for name in list.split(',').map(str::trim) {
+                            match wgpu::Features::from_name(&name.to_ascii_uppercase()) {
+                                Some(feature) => options.required_features |= feature,
+                                None => log::warn!("--require: no wgpu feature named {name}"),
+                            }
+                        }
+                    }
+                    None => log::warn!("--require wants a comma separated list of feature names"),
+                },
+                "--lut" => options.lut = args.next().map(PathBuf::from),
+                "--font" => options.font = args.next().map(PathBuf::from),
+                "--timeline" => options.timeline = args.next().map(PathBuf::from),
+                #[cfg(feature = "trace")]
+                "--trace-chrome" => options.trace_chrome = args.next().map(PathBuf::from),
+                #[cfg(not(feature = "trace"))]
+                "--trace-chrome" => {
+                    args.next();
+                    log::warn!("Built without the trace feature, ignoring --trace-chrome");
+                }
+                #[cfg(feature = "audio")]
+                "--audio" => options.audio = true,
+                #[cfg(not(feature = "audio"))]
+                "--audio" => log::warn!("Built without the audio feature, ignoring --audio"),
+                #[cfg(feature = "remote")]
+                other
+                    if remote_arg(
+                        &mut options.remote,
+                        &mut options.remote_bind,
+                        other,
+                        &mut args,
+                    ) => {}
+                #[cfg(not(feature = "remote"))]
+                other if remote_arg(other, &mut args) => {}
+                "--supersample" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(samples @ 1..=supersample::MAX_SAMPLES) => {
+                        options.supersample_samples = samples;
+                    }
+                    _ => log::warn!(
+                        "--supersample wants 1 to {} samples, keeping {}",
+                        supersample::MAX_SAMPLES,
+                        supersample::DEFAULT_SAMPLES
+                    ),
+                },
+                "--supersample-scale" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(scale @ 1..=supersample::MAX_SCALE) => options.supersample_scale = scale,
+                    _ => log::warn!(
+                        "--supersample-scale wants 1 to {}, keeping {}",
+                        supersample::MAX_SCALE,
+                        supersample::DEFAULT_SCALE
+                    ),
+                },
+                "--snap" => match args.next().and_then(|n| n.parse::<f32>().ok()) {
+                    Some(size) if size >= 1.0 => options.snap_spacing = size,
+                    _ => log::warn!("--snap wants a grid size of at least 1, keeping 50"),
+                },
+                "--stats-anchor" => match args.next().as_deref().and_then(Anchor::from_name) {
+                    Some(anchor) => options.stats_anchor = anchor,
+                    None => {
+                        log::warn!("--stats-anchor wants top-left, top-right, bottom-center, ...")
+                    }
+                },
+                "--easing" => match args.next().as_deref().and_then(Easing::from_name) {
+                    Some(easing) => options.easing = easing,
+                    None => log::warn!("--easing wants linear, smoothstep or ease-out"),
+                },
+                "--theme" => match args.next().as_deref().and_then(Theme::from_name) {
+                    Some(theme) => options.theme = theme,
+                    None => log::warn!("--theme wants dark or light, keeping dark"),
+                },
+                "--resize" => match args.next() {
+                    Some(name) if ResizePolicy::parse(&name, (1, 1)).is_some() => {
+                        options.resize = name;
+                    }
+                    _ => log::warn!("--resize wants extend, stretch or letterbox, keeping extend"),
+                },
+                other if stress_arg(&mut options.stress, other, &mut args) => {}
+                other => log::warn!("Ignoring unknown argument {other}"),
+            }
+        }
+        options
+    }
+}
+
+// --items, --seed and --spin, here and after `render`. False when `arg` is none of them
+pub fn stress_arg(
+    stress: &mut StressParams,
+    arg: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> bool {
+    match arg {
+        "--items" => match args.next().and_then(|n| n.parse().ok()) {
+            Some(items) => stress.items = items,
+            None => log::warn!("--items wants a number, keeping {}", stress.items),
+        },
+        "--seed" => match args.next().and_then(|n| n.parse().ok()) {
+            Some(seed) => stress.seed = seed,
+            None => log::warn!("--seed wants a whole number, keeping {}", stress.seed),
+        },
+        // "nan" and "inf" parse, and would spin every item to a NaN rotation
+        "--spin" => match args
+            .next()
+            .and_then(|n| n.parse().ok())
+            .filter(|spin: &f32| spin.is_finite())
+        {
+            Some(spin) => stress.spin = spin,
+            None => log::warn!("--spin wants radians per second, keeping {}", stress.spin),
+        },
+        _ => return false,
+    }
+    true
+}
+
+// --remote [port] and --remote-bind <address>, here and after `render`. False when `arg`
+// is neither
+#[cfg(feature = "remote")]
+pub fn remote_arg(
+    remote: &mut Option<u16>,
+    bind: &mut IpAddr,
+    arg: &str,
+    args: &mut Peekable<impl Iterator<Item = String>>,
+) -> bool {
+    match arg {
+        "--remote" => {
+            let port = args.next_if(|port| port.parse::<u16>().is_ok());
+            *remote = Some(
+                port.and_then(|port| port.parse().ok())
+                    .unwrap_or(remote::DEFAULT_PORT),
+            );
+        }
+        "--remote-bind" => match args.next().and_then(|address| address.parse().ok()) {
+            Some(address) => *bind = address,
+            None => log::warn!("--remote-bind wants an IP address, keeping {bind}"),
+        },
+        _ => return false,
+    }
+    true
+}
+
+// Without the feature they're still taken off the command line, with a warning
+#[cfg(not(feature = "remote"))]
+pub fn remote_arg(arg: &str, args: &mut Peekable<impl Iterator<Item = String>>) -> bool {
+    match arg {
+        "--remote" => {
+            args.next_if(|port| port.parse::<u16>().is_ok());
+        }
+        "--remote-bind" => {
+            args.next();
+        }
+        _ => return false,
+    }
+    log::warn!("Built without the remote feature, ignoring {arg}");
+    true
+}
+
+// Colors that don't parse are left out with a warning
+fn palette(list: &str) -> Vec<RgbaColor> {
+    list.split(',')
+        .filter_map(|hex| {
+            RgbaColor::parse_hex(hex)
+                .inspect_err(|e| log::warn!("{e}, leaving it out"))
+                .ok()
+        })
+        .collect()
+}
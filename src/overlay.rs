@@ -0,0 +1,644 @@
+#[cfg(feature = "text")]
+use std::collections::HashMap;
+
+use crate::buffer_pool::BufferPool;
+use crate::colors::{RgbaColor, Theme};
+use crate::error::ForayError;
+use crate::font;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::shaders;
+use crate::surface_views::SurfaceViews;
+use crate::targets::TargetRegistry;
+#[cfg(feature = "text")]
+use crate::text::Font;
+use crate::text_input;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    pub position: [f32; 2], // pixels, origin top-left
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl OverlayVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Which point of the window an overlay position is measured from. Offsets point inwards,
+// so (8, 8) from BottomRight is 8 pixels left of and above the corner
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let anchor = match name {
+            "top-left" => Anchor::TopLeft,
+            "top-center" => Anchor::TopCenter,
+            "top-right" => Anchor::TopRight,
+            "center-left" => Anchor::CenterLeft,
+            "center" => Anchor::Center,
+            "center-right" => Anchor::CenterRight,
+            "bottom-left" => Anchor::BottomLeft,
+            "bottom-center" => Anchor::BottomCenter,
+            "bottom-right" => Anchor::BottomRight,
+            _ => return None,
+        };
+        Some(anchor)
+    }
+
+    // 0 for the left/top edge, 0.5 for the middle, 1 for the right/bottom edge
+    fn factors(self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::TopCenter => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::CenterLeft => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::CenterRight => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::BottomCenter => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+
+    // Top-left corner of a block of `size` placed `offset` in from the anchor on a `screen`,
+    // all in the same pixels
+    fn place(self, screen: (f32, f32), offset: (f32, f32), size: (f32, f32)) -> (f32, f32) {
+        let (fx, fy) = self.factors();
+        // Offsets push away from the anchored edge, and do nothing along a centered axis
+        let push = |factor: f32| {
+            if factor == 0.5 {
+                0.0
+            } else {
+                1.0 - 2.0 * factor
+            }
+        };
+        (
+            (screen.0 - size.0) * fx + push(fx) * offset.0,
+            (screen.1 - size.1) * fy + push(fy) * offset.1,
+        )
+    }
+}
+
+// Bitmap-font text drawn on top of everything else. Text is queued during the frame
+// and thrown away once drawn, nothing is retained between frames.
+pub struct DebugOverlay {
+    pub enabled: bool,
+    // Size of a font pixel in logical pixels
+    pub scale: f32,
+    // Colors for panel(), and for whatever else queues into the overlay (console,
+    // inspector). Switched with --theme or the `theme` console command
+    pub theme: Theme,
+    // Physical pixels per logical pixel, from the window
+    content_scale: f32,
+    // In physical pixels, what anchored positions are relative to
+    screen: (f32, f32),
+    atlas: Tracked<wgpu::Texture>,
+    // Rasterizes what the table doesn't have into the atlas' extra rows, see set_fallback
+    #[cfg(feature = "text")]
+    fallback: Option<Font>,
+    // Cells handed out to characters outside the table so far
+    #[cfg(feature = "text")]
+    extra_cells: HashMap<char, u32>,
+    // Rasterized but not in the atlas yet, that happens in draw where there's a queue
+    uploads: Vec<(u32, Vec<u8>)>,
+    screen_buffer: Tracked<wgpu::Buffer>,
+    bind_group: wgpu::BindGroup,
+    vertices: Vec<OverlayVertex>,
+    // Intersection of everything pushed so far, x0 y0 x1 y1 in physical pixels
+    clips: Vec<[f32; 4]>,
+    // A new batch starts wherever the clip changes, drawn with its own scissor rect
+    batches: Vec<Batch>,
+}
+
+struct Batch {
+    first: u32,
+    clip: Option<[f32; 4]>,
+}
+
+impl DebugOverlay {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        views: SurfaceViews,
+        bank: &mut RenderPipelineBank,
+        memory: &GpuMemoryTracker,
+    ) -> Self {
+        // The table's row, the extra rows start out empty
+        let size = wgpu::Extent3d {
+            width: font::ATLAS_WIDTH,
+            height: font::CELL_HEIGHT,
+            depth_or_array_layers: 1,
+        };
+        let atlas = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("Font Atlas"),
+                size: wgpu::Extent3d {
+                    height: font::ATLAS_HEIGHT,
+                    ..size
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            MemoryCategory::Textures,
+        );
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &font::atlas_pixels(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(font::ATLAS_WIDTH),
+                rows_per_image: None,
+            },
+            size,
+        );
+        let atlas_view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Nearest, so the pixel font stays crisp at integer scales
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Font Sampler"),
+            ..Default::default()
+        });
+
+        let screen_buffer = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Overlay Screen Buffer"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Overlay Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Overlay Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = shaders::create_module(device, "Overlay Shader", include_str!("overlay.wgsl"));
+        bank.register_ui(
+            device,
+            "overlay",
+            &PipelineBuilder::new("Overlay Pipeline", &shader)
+                .vertex_entry("vs_overlay")
+                .fragment_entry("fs_overlay")
+                .vertex_buffer(OverlayVertex::desc())
+                .bind_group_layout(&layout)
+                .cull_mode(None)
+                .blend_mode(BlendMode::Alpha),
+            views,
+        );
+
+        Self {
+            enabled: false,
+            scale: 2.0,
+            theme: Theme::DARK,
+            content_scale: 1.0,
+            screen: (0.0, 0.0),
+            atlas,
+            #[cfg(feature = "text")]
+            fallback: None,
+            #[cfg(feature = "text")]
+            extra_cells: HashMap::new(),
+            uploads: Vec::new(),
+            screen_buffer,
+            bind_group,
+            vertices: Vec::new(),
+            clips: Vec::new(),
+            batches: Vec::new(),
+        }
+    }
+
+    // Everything queued until the matching pop_clip only shows inside `rect` (physical
+    // pixels, like everything else here). Nested clips intersect, a panel in a panel can't
+    // draw outside its parent
+    pub fn push_clip(&mut self, (x, y, w, h): (f32, f32, f32, f32)) {
+        let mut clip = [x, y, x + w, y + h];
+        if let Some(outer) = self.clips.last() {
+            clip = [
+                clip[0].max(outer[0]),
+                clip[1].max(outer[1]),
+                clip[2].min(outer[2]),
+                clip[3].min(outer[3]),
+            ];
+        }
+        self.clips.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) {
+        if self.clips.pop().is_none() {
+            log::warn!("Overlay pop_clip without a push_clip");
+        }
+    }
+
+    fn quad(&mut self, (x, y, w, h): (f32, f32, f32, f32), cell: u32, color: [f32; 4]) {
+        let clip = self.clips.last().copied();
+        // Nothing of it could show
+        if clip.is_some_and(|[x0, y0, x1, y1]| {
+            x0 >= x1 || y0 >= y1 || x + w <= x0 || x >= x1 || y + h <= y0 || y >= y1
+        }) {
+            return;
+        }
+        if self.batches.last().is_none_or(|batch| batch.clip != clip) {
+            self.batches.push(Batch {
+                first: self.vertices.len() as u32,
+                clip,
+            });
+        }
+        let atlas_width = font::ATLAS_WIDTH as f32;
+        let atlas_height = font::ATLAS_HEIGHT as f32;
+        let (column, row) = (cell % font::CELLS_PER_ROW, cell / font::CELLS_PER_ROW);
+        let u0 = (column * font::CELL_WIDTH) as f32 / atlas_width;
+        let u1 = u0 + font::GLYPH_WIDTH as f32 / atlas_width;
+        let v0 = (row * font::CELL_HEIGHT) as f32 / atlas_height;
+        let v1 = v0 + font::GLYPH_HEIGHT as f32 / atlas_height;
+        let corner = |px, py, u, v| OverlayVertex {
+            position: [px, py],
+            uv: [u, v],
+            color,
+        };
+        let top_left = corner(x, y, u0, v0);
+        let top_right = corner(x + w, y, u1, v0);
+        let bottom_left = corner(x, y + h, u0, v1);
+        let bottom_right = corner(x + w, y + h, u1, v1);
+        self.vertices.extend([
+            top_left,
+            bottom_left,
+            top_right,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ]);
+    }
+
+    // Once a frame before queuing anything, anchored positions follow resizes and DPI
+    // changes from then on
+    pub fn set_screen(&mut self, screen: (u32, u32), content_scale: f32) {
+        self.screen = (screen.0 as f32, screen.1 as f32);
+        self.content_scale = content_scale.max(f32::EPSILON);
+    }
+
+    // In physical pixels, as of the last set_screen
+    pub fn screen(&self) -> (f32, f32) {
+        self.screen
+    }
+
+    // Logical pixels to physical ones
+    pub fn logical(&self, pixels: f32) -> f32 {
+        pixels * self.content_scale
+    }
+
+    // Size of a font pixel in physical pixels
+    fn pixel(&self) -> f32 {
+        self.logical(self.scale)
+    }
+
+    // Top-left corner, in physical pixels, of a block of `size` physical pixels placed
+    // `offset` logical pixels in from `anchor`
+    pub fn anchored(&self, anchor: Anchor, offset: (f32, f32), size: (f32, f32)) -> (f32, f32) {
+        let offset = (self.logical(offset.0), self.logical(offset.1));
+        anchor.place(self.screen, offset, size)
+    }
+
+    // Draws what the pixel font doesn't have with `font`, the one draw_text uses
+    #[cfg(feature = "text")]
+    pub fn set_fallback(&mut self, font: &Font) {
+        self.fallback = Some(font.clone());
+    }
+
+    // The atlas cell for `c`, rasterizing it into a free one the first time it comes up
+    // outside the table. '?' once the extra rows are full
+    #[cfg(feature = "text")]
+    fn cell(&mut self, c: char) -> u32 {
+        if (font::FIRST_CHAR..=font::LAST_CHAR).contains(&c) {
+            return font::glyph_index(c);
+        }
+        if let Some(&cell) = self.extra_cells.get(&c) {
+            return cell;
+        }
+        let Some(fallback) = &self.fallback else {
+            return font::glyph_index('?');
+        };
+        let cell = font::SOLID_CELL + 1 + self.extra_cells.len() as u32;
+        if cell >= font::CELL_COUNT {
+            return font::glyph_index('?');
+        }
+        self.uploads
+            .push((cell, font::rasterize_cell(fallback.face(), c)));
+        self.extra_cells.insert(c, cell);
+        cell
+    }
+
+    // No TTF face to draw the rest with, anything outside the table is a '?'
+    #[cfg(not(feature = "text"))]
+    fn cell(&mut self, c: char) -> u32 {
+        if (font::FIRST_CHAR..=font::LAST_CHAR).contains(&c) {
+            font::glyph_index(c)
+        } else {
+            font::glyph_index('?')
+        }
+    }
+
+    // Size in physical pixels of a block of text, '\n' starts a new line. Combining marks
+    // and the like share their base character's column
+    pub fn measure(&self, text: &str) -> (f32, f32) {
+        let columns = text
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .filter(|&c| !text_input::is_extending(c))
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+        let rows = text.lines().count();
+        (
+            columns as f32 * font::CELL_WIDTH as f32 * self.pixel(),
+            rows as f32 * (font::CELL_HEIGHT + 1) as f32 * self.pixel(),
+        )
+    }
+
+    pub fn text(&mut self, (x, y): (f32, f32), color: RgbaColor, text: &str) {
+        let color = color.to_f32_array_linear();
+        let advance = font::CELL_WIDTH as f32 * self.pixel();
+        let line_height = (font::CELL_HEIGHT + 1) as f32 * self.pixel();
+        let glyph_size = (
+            font::GLYPH_WIDTH as f32 * self.pixel(),
+            font::GLYPH_HEIGHT as f32 * self.pixel(),
+        );
+        for (row, line) in text.lines().enumerate() {
+            let line_y = y + row as f32 * line_height;
+            // Combining marks and the like go on top of their base character
+            let mut column = 0;
+            for (index, c) in line.chars().enumerate() {
+                if index > 0 && !text_input::is_extending(c) {
+                    column += 1;
+                }
+                if c == ' ' || c == '\u{200D}' {
+                    continue;
+                }
+                let glyph_x = x + column as f32 * advance;
+                let cell = self.cell(c);
+                self.quad((glyph_x, line_y, glyph_size.0, glyph_size.1), cell, color);
+            }
+        }
+    }
+
+    pub fn rect(&mut self, rect: (f32, f32, f32, f32), color: RgbaColor) {
+        self.quad(rect, font::SOLID_CELL, color.to_f32_array_linear());
+    }
+
+    // Text on a translucent panel so it reads over any background, `offset` logical pixels
+    // in from `anchor`
+    pub fn panel(&mut self, anchor: Anchor, offset: (f32, f32), text: &str) {
+        let (w, h) = self.measure(text);
+        let margin = 2.0 * self.pixel();
+        let (x, y) = self.anchored(anchor, offset, (w + 2.0 * margin, h + 2.0 * margin));
+        self.rect((x, y, w + 2.0 * margin, h + 2.0 * margin), self.theme.panel);
+        self.text((x + margin, y + margin), self.theme.text, text);
+    }
+
+    // Draws whatever was queued on top of the swapchain and clears the queue
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        screen_size: (u32, u32),
+    ) -> Result<(), ForayError> {
+        for (cell, pixels) in self.uploads.drain(..) {
+            let (column, row) = (cell % font::CELLS_PER_ROW, cell / font::CELLS_PER_ROW);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.atlas,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: column * font::CELL_WIDTH,
+                        y: row * font::CELL_HEIGHT,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(font::CELL_WIDTH),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: font::CELL_WIDTH,
+                    height: font::CELL_HEIGHT,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+
+        let bytes: &[u8] = bytemuck::cast_slice(&self.vertices);
+        let vertex_buffer = pool.acquire(
+            device,
+            "Overlay Vertex Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            bytes.len() as u64,
+        );
+        queue.write_buffer(&vertex_buffer, 0, bytes);
+        let screen = [screen_size.0 as f32, screen_size.1 as f32, 0.0, 0.0];
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&screen));
+
+        let mut pass = frame.pass(
+            "Overlay Pass",
+            &[(ColorTarget::SwapchainUi, wgpu::LoadOp::Load)],
+            targets,
+        );
+        let result = pass.set_pipeline(bank, "overlay").map(|()| {
+            pass.raw.set_bind_group(0, &self.bind_group, &[]);
+            pass.raw
+                .set_vertex_buffer(0, vertex_buffer.slice(..bytes.len() as u64));
+            let ends = self
+                .batches
+                .iter()
+                .skip(1)
+                .map(|batch| batch.first)
+                .chain([self.vertices.len() as u32]);
+            for (batch, end) in self.batches.iter().zip(ends) {
+                let Some((x, y, width, height)) = scissor(batch.clip, screen_size) else {
+                    continue;
+                };
+                pass.raw.set_scissor_rect(x, y, width, height);
+                pass.raw.draw(batch.first..end, 0..1);
+            }
+        });
+        drop(pass);
+
+        if !self.clips.is_empty() {
+            log::warn!("{} overlay clips were never popped", self.clips.len());
+            self.clips.clear();
+        }
+        self.vertices.clear();
+        self.batches.clear();
+        result
+    }
+}
+
+// Whole pixels inside the surface, wgpu rejects scissor rects that reach outside it. None
+// when nothing is left
+fn scissor(clip: Option<[f32; 4]>, (width, height): (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+    let [x0, y0, x1, y1] = clip.unwrap_or([0.0, 0.0, width as f32, height as f32]);
+    let clamp = |value: f32, max: u32| (value.max(0.0) as u32).min(max);
+    let (x0, y0) = (clamp(x0.floor(), width), clamp(y0.floor(), height));
+    let (x1, y1) = (clamp(x1.ceil(), width), clamp(y1.ceil(), height));
+    (x1 > x0 && y1 > y0).then(|| (x0, y0, x1 - x0, y1 - y0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Windows of different sizes, at the content scales a monitor move can switch between
+    const SCREENS: [((f32, f32), f32); 5] = [
+        ((800.0, 600.0), 1.0),
+        ((1600.0, 1200.0), 2.0),
+        ((1280.0, 720.0), 1.0),
+        ((1920.0, 1080.0), 1.5),
+        ((3840.0, 2160.0), 2.0),
+    ];
+    const OFFSET: (f32, f32) = (12.0, 20.0);
+    // A panel's size in logical pixels, which measure keeps whatever the content scale
+    const SIZE: (f32, f32) = (150.0, 40.0);
+
+    // Where `anchor` puts the panel's top-left corner on `screen`, and the screen's size,
+    // both in logical pixels
+    fn placed(anchor: Anchor, (screen, scale): ((f32, f32), f32)) -> ((f32, f32), (f32, f32)) {
+        let physical = |(x, y): (f32, f32)| (x * scale, y * scale);
+        let (x, y) = anchor.place(screen, physical(OFFSET), physical(SIZE));
+        ((x / scale, y / scale), (screen.0 / scale, screen.1 / scale))
+    }
+
+    fn assert_near(actual: f32, expected: f32, what: &str) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{what}: {actual} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn top_left_stays_put() {
+        for screen in SCREENS {
+            let ((x, y), _) = placed(Anchor::TopLeft, screen);
+            assert_near(x, OFFSET.0, "left gap");
+            assert_near(y, OFFSET.1, "top gap");
+        }
+    }
+
+    #[test]
+    fn bottom_left_follows_the_bottom_edge() {
+        for screen in SCREENS {
+            let ((x, y), (_, height)) = placed(Anchor::BottomLeft, screen);
+            assert_near(x, OFFSET.0, "left gap");
+            assert_near(height - (y + SIZE.1), OFFSET.1, "bottom gap");
+        }
+    }
+
+    #[test]
+    fn bottom_center_stays_centered() {
+        for screen in SCREENS {
+            let ((x, y), (width, height)) = placed(Anchor::BottomCenter, screen);
+            // The offset doesn't move it off center
+            assert_near(x + SIZE.0 / 2.0, width / 2.0, "center");
+            assert_near(height - (y + SIZE.1), OFFSET.1, "bottom gap");
+        }
+    }
+
+    #[test]
+    fn anchors_are_named_like_the_console_spells_them() {
+        assert!(matches!(
+            Anchor::from_name("bottom-center"),
+            Some(Anchor::BottomCenter)
+        ));
+        assert!(Anchor::from_name("middle").is_none());
+    }
+}
@@ -0,0 +1,54 @@
+// Debug overlay text and panels, positions come in as pixels. Colors come in linear and
+// go out sRGB encoded when the pipeline is built for a plain view, see SurfaceViews
+override encode_srgb: bool = false;
+
+struct Screen {
+    size: vec2<f32>,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+@group(0) @binding(1)
+var atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var atlas_sampler: sampler;
+
+struct OverlayInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct OverlayOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_overlay(in: OverlayInput) -> OverlayOutput {
+    var out: OverlayOutput;
+    let ndc = in.position / screen.size * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.uv = in.uv;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_overlay(in: OverlayOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    var rgb = in.color.rgb;
+    if encode_srgb {
+        rgb = to_srgb(rgb);
+    }
+    return vec4<f32>(rgb, in.color.a * coverage);
+}
+
+fn to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let c = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
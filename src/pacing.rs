@@ -0,0 +1,352 @@
+use std::time::{Duration, Instant};
+
+use glam::Quat;
+
+// Simulation state is stepped at a fixed rate and drawn somewhere between its last two
+// steps, so a 60 Hz update still moves smoothly on a 144 Hz display
+pub trait Interpolate {
+    // `alpha` 0 is self, 1 is `next`
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self;
+}
+
+impl Interpolate for Quat {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        self.slerp(*next, alpha)
+    }
+}
+
+impl Interpolate for f32 {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        self + (next - self) * alpha
+    }
+}
+
+// A state and the one before it, what gets interpolated between
+pub struct Stepped<T> {
+    pub previous: T,
+    pub current: T,
+}
+
+impl<T: Interpolate + Clone> Stepped<T> {
+    pub fn new(state: T) -> Self {
+        Self {
+            previous: state.clone(),
+            current: state,
+        }
+    }
+
+    pub fn step(&mut self, update: impl FnOnce(&T) -> T) {
+        let next = update(&self.current);
+        self.previous = std::mem::replace(&mut self.current, next);
+    }
+
+    pub fn at(&self, alpha: f32) -> T {
+        self.previous.lerp_state(&self.current, alpha)
+    }
+}
+
+// Shapes the 0 to 1 progress of a transition
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    SmoothStep,
+    // Fast start, gentle landing
+    EaseOut,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "smoothstep" => Some(Easing::SmoothStep),
+            "ease-out" => Some(Easing::EaseOut),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+// A value moving from one number to another over a fixed time, stepped with the fixed
+// updates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tween {
+    pub from: f32,
+    pub to: f32,
+    pub duration: Duration,
+    pub easing: Easing,
+    elapsed: Duration,
+}
+
+impl Tween {
+    // Non-finite ends would make every value NaN, the tween holds at whichever end is
+    // finite instead (0 when neither is)
+    pub fn new(from: f32, to: f32, duration: Duration, easing: Easing) -> Self {
+        if !(from.is_finite() && to.is_finite()) {
+            log::warn!("Tween from {from} to {to} isn't finite, holding still");
+        }
+        let (from, to) = match (from.is_finite(), to.is_finite()) {
+            (true, true) => (from, to),
+            (false, true) => (to, to),
+            (true, false) => (from, from),
+            (false, false) => (0.0, 0.0),
+        };
+        Self {
+            from,
+            to,
+            duration,
+            easing,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn step(&mut self, step: Duration) {
+        self.elapsed = (self.elapsed + step).min(self.duration);
+    }
+
+    pub fn value(&self) -> f32 {
+        if self.duration.is_zero() {
+            return self.to;
+        }
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    // Towards `to` from wherever it is now, so reversing halfway doesn't jump
+    pub fn retarget(&mut self, to: f32) {
+        *self = Self::new(self.value(), to, self.duration, self.easing);
+    }
+}
+
+// After a stall (window drag, breakpoint) don't try to catch up more than this
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+// Decides how many fixed updates each frame runs and keeps rendering at the target rate,
+// which is the refresh rate of the monitor the window is on unless --target-fps says otherwise
+pub struct FramePacer {
+    pub fixed_step: Duration,
+    accumulator: Duration,
+    last_tick: Instant,
+    // 0 until the monitor is known
+    pub refresh_rate: u32,
+    target_override: Option<u32>,
+    next_frame: Instant,
+    // Headless rendering: every frame is exactly one fixed step, whatever the wall clock says
+    forced: bool,
+}
+
+impl FramePacer {
+    pub fn new(fixed_rate: u32, target_override: Option<u32>) -> Self {
+        Self {
+            fixed_step: Duration::from_secs(1) / fixed_rate,
+            accumulator: Duration::ZERO,
+            last_tick: Instant::now(),
+            refresh_rate: 0,
+            target_override,
+            next_frame: Instant::now(),
+            forced: false,
+        }
+    }
+
+    // One update of 1/fps per frame and no waiting, for `render`
+    pub fn forced(fps: u32) -> Self {
+        Self {
+            forced: true,
+            ..Self::new(fps, Some(fps))
+        }
+    }
+
+    // Every frame, so moving to a monitor with another rate takes effect right away
+    pub fn set_refresh_rate(&mut self, rate: u32) {
+        if rate != self.refresh_rate {
+            println!("Refresh rate {rate} Hz");
+            self.refresh_rate = rate;
+        }
+    }
+
+    fn frame_interval(&self) -> Option<Duration> {
+        match self.target_override.unwrap_or(self.refresh_rate) {
+            0 => None,
+            rate => Some(Duration::from_secs(1) / rate),
+        }
+    }
+
+    // How many fixed updates to run before drawing this frame
+    pub fn advance(&mut self) -> u32 {
+        if self.forced {
+            return 1;
+        }
+        let now = Instant::now();
+        self.accumulator = (self.accumulator + (now - self.last_tick)).min(MAX_CATCH_UP);
+        self.last_tick = now;
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_step {
+            self.accumulator -= self.fixed_step;
+            steps += 1;
+        }
+        steps
+    }
+
+    // How far between the last two fixed updates this frame is drawn
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.fixed_step.as_secs_f32()
+    }
+
+    // Sleeps off what's left of this frame's slot. With Fifo the present already blocks,
+    // this is for the other present modes and for iterations that don't render at all
+    pub fn wait(&mut self) {
+        if self.forced {
+            return;
+        }
+        let Some(interval) = self.frame_interval() else {
+            return;
+        };
+        let now = Instant::now();
+        if now < self.next_frame {
+            std::thread::sleep(self.next_frame - now);
+        }
+        // Fell behind, start over from now instead of rushing frames out
+        self.next_frame = self.next_frame.max(now) + interval;
+    }
+}
+
+// When the next frame is wanted, if at all. Anything that changes what's on screen without
+// an input event (a tween, a fade, a finished load) asks here, and the loop renders once
+// the earliest request is due. Nothing asked means nothing to draw
+#[derive(Clone, Debug, Default)]
+pub struct RedrawRequests {
+    next: Option<Instant>,
+}
+
+impl RedrawRequests {
+    // As soon as possible, the next loop iteration
+    pub fn now(&mut self) {
+        self.at(Instant::now());
+    }
+
+    pub fn after(&mut self, delay: Duration) {
+        self.at(Instant::now() + delay);
+    }
+
+    // The earliest request wins
+    pub fn at(&mut self, when: Instant) {
+        self.next = Some(self.next.map_or(when, |next| next.min(when)));
+    }
+
+    // Whether a frame is due, in which case the request is used up
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        let due = self.next.is_some_and(|next| next <= now);
+        if due {
+            self.next = None;
+        }
+        due
+    }
+
+    // How long the loop can sleep before it has a frame to draw. None when it can sleep
+    // until the next input event
+    pub fn idle_for(&self, now: Instant) -> Option<Duration> {
+        self.next.map(|next| next.saturating_duration_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The event driven loop in run without a window: sleep until the earliest request,
+    // run the fixed updates, draw if a frame is due, ask for the next step while still
+    // moving. Time is simulated, a sleep just moves `now` along. Returns the tween's value
+    // in every frame drawn, stopping once the loop would wait for input
+    fn drive(tween: &mut Tween, redraw: &mut RedrawRequests, start: Instant) -> Vec<f32> {
+        let mut pacer = FramePacer::forced(60);
+        let mut now = start;
+        let mut frames = Vec::new();
+        for _ in 0..1000 {
+            let Some(idle) = redraw.idle_for(now) else {
+                return frames;
+            };
+            // A due request still leaves a frame's worth of time going by
+            now += idle.max(pacer.fixed_step);
+            for _ in 0..pacer.advance() {
+                // Like State::update, checked before stepping so the last step is drawn
+                if !tween.is_done() {
+                    redraw.at(now);
+                }
+                tween.step(pacer.fixed_step);
+            }
+            if redraw.take_due(now) {
+                frames.push(tween.value());
+            }
+            // What the loop asks for after drawing, the request above is used up
+            if !tween.is_done() {
+                redraw.at(now + pacer.fixed_step);
+            }
+        }
+        panic!("still drawing after 1000 iterations");
+    }
+
+    #[test]
+    fn a_tween_draws_until_it_finishes_then_the_loop_sleeps() {
+        let start = Instant::now();
+        // A second in whole steps, 1 / 60 s doesn't come out even in nanoseconds
+        let second = Duration::from_secs(1) / 60 * 60;
+        let mut tween = Tween::new(0.0, 1.0, second, Easing::Linear);
+        let mut redraw = RedrawRequests::default();
+        // Whatever started the tween (a key press) asked for the first frame
+        redraw.at(start);
+        let frames = drive(&mut tween, &mut redraw, start);
+        // One a step for the second it runs, the last one showing where it ended
+        assert_eq!(frames.len(), 60);
+        assert!(frames.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((frames[0] - 1.0 / 60.0).abs() < 1e-6);
+        assert!((frames[59] - 1.0).abs() < 1e-6);
+        assert!(tween.is_done());
+        // Nothing left to ask for a frame, so no timeout either: wait for input
+        assert_eq!(redraw.idle_for(start + Duration::from_secs(5)), None);
+        assert!(drive(&mut tween, &mut redraw, start).is_empty());
+    }
+
+    #[test]
+    fn the_earliest_request_wins_and_is_used_up() {
+        let start = Instant::now();
+        let mut redraw = RedrawRequests::default();
+        assert_eq!(redraw.idle_for(start), None);
+        assert!(!redraw.take_due(start));
+        redraw.at(start + Duration::from_millis(500));
+        redraw.at(start + Duration::from_millis(100));
+        redraw.at(start + Duration::from_millis(300));
+        assert_eq!(redraw.idle_for(start), Some(Duration::from_millis(100)));
+        // Not due yet, and checking doesn't use it up
+        assert!(!redraw.take_due(start + Duration::from_millis(99)));
+        assert!(redraw.take_due(start + Duration::from_millis(100)));
+        // The later ones went with it, one frame covers them all
+        assert_eq!(redraw.idle_for(start), None);
+        // Overdue is a zero sleep, not a negative one
+        redraw.at(start);
+        assert_eq!(
+            redraw.idle_for(start + Duration::from_secs(1)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn a_delayed_request_sleeps_until_it_is_due() {
+        let start = Instant::now();
+        let mut tween = Tween::new(0.0, 0.0, Duration::ZERO, Easing::Linear);
+        let mut redraw = RedrawRequests::default();
+        redraw.at(start + Duration::from_millis(250));
+        assert_eq!(drive(&mut tween, &mut redraw, start), [0.0]);
+        assert_eq!(redraw.idle_for(start), None);
+    }
+}
@@ -0,0 +1,1374 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::depth::DepthConvention;
+use crate::error::ForayError;
+use crate::mesh::VertexLayoutId;
+use crate::reflect::{Reflection, ShaderBinding};
+use crate::surface_views::SurfaceViews;
+
+// The `override encode_srgb: bool` every register_ui shader has, see SurfaceViews::ui_encodes
+const ENCODE_SRGB: &str = "encode_srgb";
+
+// A pipeline plus what we need to know to validate its use in a pass
+pub struct Pipeline {
+    pub raw: wgpu::RenderPipeline,
+    // Color target formats, in @location order
+    pub targets: Vec<wgpu::TextureFormat>,
+    pub depth: Option<wgpu::TextureFormat>,
+    // What meshes drawn with it have to be, checked in Pass::draw_mesh
+    pub topology: wgpu::PrimitiveTopology,
+    pub vertex_layout: Option<VertexLayoutId>,
+    // Override constants it was built with, sorted by name
+    pub constants: Vec<(String, f64)>,
+    // What its entry points bind, when the builder had a Reflection
+    pub bindings: Option<Vec<ShaderBinding>>,
+    // Bind group layouts and vertex buffer slots it was built with, all of which a draw
+    // has to fill even when the shader doesn't use them
+    pub bind_groups: u32,
+    pub vertex_buffers: u32,
+    // MSAA samples per pixel of the attachments it draws into
+    pub samples: u32,
+}
+
+// How a pipeline's output combines with what's already in the target
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    // Overwrites, what PipelineBuilder does unless told otherwise
+    Replace,
+    Alpha,
+    // Adds up, overlapping particles and glow sprites get brighter
+    Additive,
+    // Darkens by the source color, white leaves the target alone
+    Multiply,
+    // Source color already multiplied by its alpha
+    Premultiplied,
+    Custom(wgpu::BlendState),
+    // Overwrites like Replace, but the shader's ALPHA_TEST override is on and it discards
+    // what its mask cuts out. Foliage and fences, opaque as far as sorting goes. Only for
+    // shaders that declare the override
+    Cutout,
+}
+
+// The bool override BlendMode::Cutout turns on
+pub const ALPHA_TEST: &str = "ALPHA_TEST";
+// Takes ALPHA_TEST's place in multisampled cutouts built with alpha_to_coverage: the shader
+// writes its mask as alpha and the samples it covers follow, instead of discarding
+pub const ALPHA_TO_COVERAGE: &str = "ALPHA_TO_COVERAGE";
+
+impl BlendMode {
+    pub fn state(self) -> wgpu::BlendState {
+        let add = |src_factor, dst_factor| wgpu::BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        match self {
+            BlendMode::Replace | BlendMode::Cutout => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: add(wgpu::BlendFactor::One, wgpu::BlendFactor::One),
+                alpha: add(wgpu::BlendFactor::One, wgpu::BlendFactor::One),
+            },
+            // The target keeps its own alpha
+            BlendMode::Multiply => wgpu::BlendState {
+                color: add(wgpu::BlendFactor::Dst, wgpu::BlendFactor::Zero),
+                alpha: add(wgpu::BlendFactor::Zero, wgpu::BlendFactor::One),
+            },
+            BlendMode::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Custom(state) => state,
+        }
+    }
+
+    // Blend state is part of what a pipeline is, so each mode of a pipeline gets its own
+    // entry in the bank, "<pipeline>@<mode>"
+    pub fn key(self, pipeline: &str) -> String {
+        let mode = match self {
+            BlendMode::Replace => "replace".to_owned(),
+            BlendMode::Alpha => "alpha".to_owned(),
+            BlendMode::Additive => "additive".to_owned(),
+            BlendMode::Multiply => "multiply".to_owned(),
+            BlendMode::Premultiplied => "premultiplied".to_owned(),
+            BlendMode::Cutout => "cutout".to_owned(),
+            // Same state, same hash, so two materials asking for it share the pipeline
+            BlendMode::Custom(state) => {
+                let mut hasher = DefaultHasher::new();
+                state.hash(&mut hasher);
+                format!("custom-{:016x}", hasher.finish())
+            }
+        };
+        format!("{pipeline}@{mode}")
+    }
+
+    // Whether it mixes with what's already drawn, which has to come first
+    pub fn blends(self) -> bool {
+        !matches!(self, BlendMode::Replace | BlendMode::Cutout)
+    }
+}
+
+// Which of a pipeline's depth variants a pass binds, see register_depth_stages
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DepthStage {
+    // The pipeline as registered, testing and writing depth itself
+    Single,
+    // Depth only, laying down the nearest surface ahead of shading
+    Prepass,
+    // After a pre-pass: compares Equal and doesn't write, so each pixel gets shaded once
+    Shaded,
+}
+
+impl DepthStage {
+    // "<pipeline>", "<pipeline>/depth" or "<pipeline>/equal"
+    pub fn key(self, pipeline: &str) -> String {
+        match self {
+            DepthStage::Single => pipeline.to_owned(),
+            DepthStage::Prepass => format!("{pipeline}/depth"),
+            DepthStage::Shaded => format!("{pipeline}/equal"),
+        }
+    }
+}
+
+// The build of `pipeline` for passes with `samples` per pixel, see register_sample_counts.
+// "<pipeline>" itself at 1 sample, "<pipeline>/msaa<samples>" otherwise
+pub fn sample_key(pipeline: &str, samples: u32) -> String {
+    if samples > 1 {
+        format!("{pipeline}/msaa{samples}")
+    } else {
+        pipeline.to_owned()
+    }
+}
+
+enum Slot {
+    Ready(Pipeline),
+    // Being built on another thread, `placeholder` stands in for it until then
+    Pending {
+        receiver: mpsc::Receiver<Result<Pipeline, String>>,
+        placeholder: Option<String>,
+        state: Arc<Mutex<BuildState>>,
+    },
+    // The build panicked, kept so asking for it says why instead of that it doesn't exist
+    Failed(String),
+}
+
+// Where a requested pipeline is at, as of the bank's last poll
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildState {
+    Building,
+    Built,
+    Failed(String),
+}
+
+// Refers to a pipeline asked for with `request`
+pub struct PipelineHandle {
+    pub name: String,
+    state: Arc<Mutex<BuildState>>,
+}
+
+impl PipelineHandle {
+    pub fn state(&self) -> BuildState {
+        self.state.lock().expect("Pipeline state poisoned").clone()
+    }
+
+    // Neither in nor failed yet
+    pub fn is_building(&self) -> bool {
+        self.state() == BuildState::Building
+    }
+}
+
+// Render Pipeline Bank
+// Pipelines are registered under a name so the rest of the code can ask for
+// "default" or "sdf_circle" instead of remembering indices.
+pub struct RenderPipelineBank {
+    store: Vec<(String, Slot)>,
+    // Pipelines that draw into the swapchain and how to build them again, see
+    // rebuild_for_format
+    surface: Vec<(String, Recipe)>,
+    // Same for pipelines compositing UI through the swapchain's UI view
+    ui: Vec<(String, Recipe)>,
+    // Times a placeholder got bound since the last take_placeholder_uses()
+    placeholder_uses: Cell<u32>,
+    // (pipeline, vertex layout, entry): the entry draws what `pipeline` does, for meshes
+    // with that layout, see specialize
+    specializations: Vec<(String, VertexLayoutId, String)>,
+    // Pipelines built or swapped since the last take_cold, which the driver may not have
+    // finished compiling until their first draw. See warmup::warm_up
+    cold: Vec<String>,
+}
+
+impl RenderPipelineBank {
+    pub fn new() -> Self {
+        Self {
+            store: Vec::new(),
+            surface: Vec::new(),
+            ui: Vec::new(),
+            placeholder_uses: Cell::new(0),
+            specializations: Vec::new(),
+            cold: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, name: String, slot: Slot) {
+        if matches!(slot, Slot::Ready(_)) {
+            self.mark_cold(&name);
+        }
+        match self.store.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = slot,
+            None => self.store.push((name, slot)),
+        }
+    }
+
+    // Re-registering a name swaps the pipeline in place (keeps the ordering stable)
+    pub fn register(&mut self, name: impl Into<String>, pipeline: Pipeline) {
+        self.insert(name.into(), Slot::Ready(pipeline));
+    }
+
+    // For pipelines whose output format is the surface's, `format` being the current one
+    pub fn register_surface(
+        &mut self,
+        device: &wgpu::Device,
+        name: impl Into<String>,
+        builder: &PipelineBuilder,
+        format: wgpu::TextureFormat,
+    ) {
+        let name = name.into();
+        let recipe = builder.recipe();
+        self.register(name.clone(), recipe.build(device, format));
+        self.follow_surface(name, recipe);
+    }
+
+    // Marks `name` as drawing into the swapchain without building anything, for pipelines
+    // that come in through `request`
+    pub fn follow_surface(&mut self, name: impl Into<String>, recipe: Recipe) {
+        let name = name.into();
+        match self.surface.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = recipe,
+            None => self.surface.push((name, recipe)),
+        }
+    }
+
+    // For pipelines drawing into ColorTarget::SwapchainUi. Built for the UI view's format,
+    // with the shader's encode_srgb override set to match it
+    pub fn register_ui(
+        &mut self,
+        device: &wgpu::Device,
+        name: impl Into<String>,
+        builder: &PipelineBuilder,
+        views: SurfaceViews,
+    ) {
+        let name = name.into();
+        let mut recipe = builder.recipe();
+        recipe.encode_srgb(views);
+        self.register(name.clone(), recipe.build(device, views.ui));
+        match self.ui.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = recipe,
+            None => self.ui.push((name, recipe)),
+        }
+    }
+
+    // After the surface format changed, builds everything registered with register_surface
+    // again for the scene view and everything from register_ui for the UI view. Pipelines
+    // already built for theirs are left alone, so calling this with the views they have
+    // builds nothing. Returns how many were rebuilt
+    pub fn rebuild_for_format(&mut self, device: &wgpu::Device, views: SurfaceViews) -> usize {
+        for (_, recipe) in &mut self.ui {
+            recipe.encode_srgb(views);
+        }
+        let surface = self.surface.iter().map(|entry| (entry, views.scene));
+        let ui = self.ui.iter().map(|entry| (entry, views.ui));
+        let stale: Vec<(String, Recipe, wgpu::TextureFormat)> = surface
+            .chain(ui)
+            .filter(|((name, recipe), format)| {
+                self.get(name)
+                    .is_none_or(|pipeline| !recipe.built(pipeline, *format))
+            })
+            .map(|((name, recipe), format)| (name.clone(), recipe.clone(), format))
+            .collect();
+        for (name, recipe, format) in &stale {
+            self.register(name.clone(), recipe.build(device, *format));
+        }
+        stale.len()
+    }
+
+    // Sets WGSL override constant `constant` of a register_surface or register_ui pipeline
+    // and swaps in the rebuilt pipeline, between frames since passes only borrow the bank
+    // while they record. Names are checked against the shader when the builder had
+    // overrides_from, an unknown one would otherwise only show up as a wgpu validation error
+    pub fn set_override(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline: &str,
+        constant: &str,
+        value: f64,
+    ) -> Result<(), ForayError> {
+        let Some((_, recipe)) = self
+            .surface
+            .iter_mut()
+            .chain(&mut self.ui)
+            .find(|(n, _)| n == pipeline)
+        else {
+            return Err(match self.get(pipeline) {
+                Some(_) => ForayError::NotRebuildable(pipeline.to_owned()),
+                None => ForayError::UnknownPipeline(pipeline.to_owned()),
+            });
+        };
+        if let Some(known) = &recipe.overrides {
+            if !known.iter().any(|name| name == constant) {
+                return Err(ForayError::UnknownOverride {
+                    pipeline: pipeline.to_owned(),
+                    constant: constant.to_owned(),
+                    known: known.clone(),
+                });
+            }
+        }
+        recipe.constants.insert(constant.to_owned(), value);
+        let recipe = recipe.clone();
+        let current = self.get(pipeline);
+        let format = current
+            .and_then(|p| p.targets.first().copied())
+            .ok_or_else(|| ForayError::PipelineNotReady(pipeline.to_owned()))?;
+        // Same constants and format, same pipeline
+        if current.is_some_and(|p| recipe.built(p, format)) {
+            return Ok(());
+        }
+        self.register(pipeline, recipe.build(device, format));
+        Ok(())
+    }
+
+    // Builds the pipeline on a background thread so the frame loop doesn't stall on it.
+    // Until it's in, passes asking for `name` get `placeholder` (which has to take the
+    // same targets and bindings) or, without one, skip their draw.
+    pub fn request(
+        &mut self,
+        name: impl Into<String>,
+        placeholder: Option<&str>,
+        device: &wgpu::Device,
+        build: impl FnOnce(&wgpu::Device) -> Pipeline + Send + 'static,
+    ) -> PipelineHandle {
+        let name = name.into();
+        let (sender, receiver) = mpsc::channel();
+        let state = Arc::new(Mutex::new(BuildState::Building));
+        let device = device.clone();
+        std::thread::spawn(move || {
+            // Validation errors panic out of wgpu's create calls, that's a failed build rather
+            // than a dead thread
+            let built = panic::catch_unwind(AssertUnwindSafe(|| build(&device))).map_err(|e| {
+                e.downcast_ref::<&str>()
+                    .map(|message| (*message).to_owned())
+                    .or_else(|| e.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "the build panicked".to_owned())
+            });
+            // The bank may be gone by the time this finishes, nothing to do then
+            let _ = sender.send(built);
+        });
+
+        self.insert(
+            name.clone(),
+            Slot::Pending {
+                receiver,
+                placeholder: placeholder.map(String::from),
+                state: Arc::clone(&state),
+            },
+        );
+        PipelineHandle { name, state }
+    }
+
+    fn mark_cold(&mut self, name: &str) {
+        if !self.cold.iter().any(|n| n == name) {
+            self.cold.push(name.to_owned());
+        }
+    }
+
+    // Ready pipelines nothing has drawn with since they came in, in the order they did
+    pub fn take_cold(&mut self) -> Vec<String> {
+        let cold = std::mem::take(&mut self.cold);
+        cold.into_iter()
+            .filter(|name| self.get(name).is_some())
+            .collect()
+    }
+
+    // Once per frame, swaps in whatever finished building. Builds that failed are logged,
+    // marked on their handle and returned
+    pub fn poll(&mut self) -> Vec<ForayError> {
+        let mut failed = Vec::new();
+        for (name, slot) in &mut self.store {
+            let Slot::Pending {
+                receiver, state, ..
+            } = slot
+            else {
+                continue;
+            };
+            let built = match receiver.try_recv() {
+                Ok(built) => built,
+                Err(mpsc::TryRecvError::Empty) => continue,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    Err("the build thread went away".to_owned())
+                }
+            };
+            let mut state = state.lock().expect("Pipeline state poisoned");
+            match built {
+                Ok(pipeline) => {
+                    *state = BuildState::Built;
+                    drop(state);
+                    *slot = Slot::Ready(pipeline);
+                    if !self.cold.contains(name) {
+                        self.cold.push(name.clone());
+                    }
+                }
+                Err(reason) => {
+                    *state = BuildState::Failed(reason.clone());
+                    drop(state);
+                    *slot = Slot::Failed(reason.clone());
+                    let error = ForayError::PipelineBuild {
+                        pipeline: name.clone(),
+                        reason,
+                    };
+                    log::error!("{error}");
+                    failed.push(error);
+                }
+            }
+        }
+        failed
+    }
+
+    // Polls until nothing is building or `timeout` is up, returns how many are still building.
+    // Those threads hold on to the device, so this goes before it's dropped
+    pub fn finish_pending(&mut self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.poll();
+            let pending = self.pending_count();
+            if pending == 0 || Instant::now() >= deadline {
+                return pending;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.store
+            .iter()
+            .filter(|(_, slot)| matches!(slot, Slot::Pending { .. }))
+            .count()
+    }
+
+    pub fn take_placeholder_uses(&self) -> u32 {
+        self.placeholder_uses.take()
+    }
+
+    // Ready or still building
+    pub fn contains(&self, name: &str) -> bool {
+        self.store.iter().any(|(n, _)| n == name)
+    }
+
+    // Only pipelines that are ready, placeholders aren't considered
+    pub fn get(&self, name: &str) -> Option<&Pipeline> {
+        match self.store.iter().find(|(n, _)| n == name) {
+            Some((_, Slot::Ready(pipeline))) => Some(pipeline),
+            _ => None,
+        }
+    }
+
+    // What a pass should bind for `name`, falling back to the placeholder while it builds
+    pub fn resolve(&self, name: &str) -> Result<&Pipeline, ForayError> {
+        match self.store.iter().find(|(n, _)| n == name) {
+            Some((_, Slot::Ready(pipeline))) => Ok(pipeline),
+            Some((_, Slot::Pending { placeholder, .. })) => {
+                match placeholder.as_deref().and_then(|p| self.get(p)) {
+                    Some(pipeline) => {
+                        self.placeholder_uses.set(self.placeholder_uses.get() + 1);
+                        Ok(pipeline)
+                    }
+                    None => Err(ForayError::PipelineNotReady(name.to_owned())),
+                }
+            }
+            Some((_, Slot::Failed(reason))) => Err(ForayError::PipelineBuild {
+                pipeline: name.to_owned(),
+                reason: reason.clone(),
+            }),
+            None => Err(ForayError::UnknownPipeline(name.to_owned())),
+        }
+    }
+
+    // Builds `builder` once per blend mode under BlendMode::key. Modes already in the bank
+    // are left as they are, so materials can ask for whatever they use without duplicates
+    pub fn register_blends(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        name: &str,
+        builder: &PipelineBuilder,
+        modes: &[BlendMode],
+    ) {
+        for &mode in modes {
+            let key = mode.key(name);
+            if self.store.iter().any(|(n, _)| *n == key) {
+                continue;
+            }
+            let pipeline = builder.clone().blend_mode(mode).build(device, format);
+            self.register(key, pipeline);
+        }
+    }
+
+    // The Prepass and Shaded variants of `builder`, registered as `name` already. Both come
+    // from the same builder, so they run the same vertex entry point with the same layouts
+    // and Equal finds exactly the depth the pre-pass wrote (the shader's position should be
+    // @invariant too)
+    pub fn register_depth_stages(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        name: &str,
+        builder: &PipelineBuilder,
+    ) {
+        let prepass = builder.clone().depth_only().build(device, format);
+        self.register(DepthStage::Prepass.key(name), prepass);
+        let shaded = builder
+            .clone()
+            .depth_compare(wgpu::CompareFunction::Equal)
+            .depth_write(false)
+            .build(device, format);
+        self.register(DepthStage::Shaded.key(name), shaded);
+    }
+
+    // `builder`, registered as `name` already, again for each of `sample_counts` past 1
+    // under sample_key. Cutouts among them cover samples by alpha instead of discarding,
+    // single sampled ones keep the discard
+    pub fn register_sample_counts(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        name: &str,
+        builder: &PipelineBuilder,
+        sample_counts: &[u32],
+    ) {
+        let cutout = builder.constants.contains_key(ALPHA_TEST);
+        for &samples in sample_counts.iter().filter(|&&samples| samples > 1) {
+            let pipeline = builder
+                .clone()
+                .sample_count(samples)
+                .alpha_to_coverage(cutout)
+                .build(device, format);
+            self.register(sample_key(name, samples), pipeline);
+        }
+    }
+
+    // register_blends for pipelines drawing into the swapchain
+    pub fn register_surface_blends(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        name: &str,
+        builder: &PipelineBuilder,
+        modes: &[BlendMode],
+    ) {
+        for &mode in modes {
+            let key = mode.key(name);
+            if self.store.iter().any(|(n, _)| *n == key) {
+                continue;
+            }
+            self.register_surface(device, key, &builder.clone().blend_mode(mode), format);
+        }
+    }
+
+    // Pipelines can come in families named "<family>/fill", "<family>/line" and
+    // "<family>/point". This is the member for `topology`, or `family` itself if there's none
+    pub fn family_member(&self, family: &str, topology: wgpu::PrimitiveTopology) -> String {
+        let member = format!("{family}/{}", crate::mesh::family_variant(topology));
+        if self.store.iter().any(|(n, _)| *n == member) {
+            member
+        } else {
+            family.to_owned()
+        }
+    }
+
+    // Marks the registered entry `name` as `pipeline` built for meshes with `layout`
+    // instead of the one `pipeline` itself takes. A pipeline with more than one
+    // specialization picks by the mesh's layout in specialization_for
+    pub fn specialize(&mut self, pipeline: &str, layout: VertexLayoutId, name: impl Into<String>) {
+        let name = name.into();
+        match self
+            .specializations
+            .iter_mut()
+            .find(|(p, l, _)| p == pipeline && *l == layout)
+        {
+            Some(entry) => entry.2 = name,
+            None => self
+                .specializations
+                .push((pipeline.to_owned(), layout, name)),
+        }
+    }
+
+    // The entry that draws `pipeline` for meshes with `layout`: its specialization for that
+    // layout, or `pipeline` itself so the draw check can name both layouts
+    pub fn specialization_for(&self, pipeline: &str, layout: VertexLayoutId) -> String {
+        self.specializations
+            .iter()
+            .find(|(p, l, _)| p == pipeline && *l == layout)
+            .map_or_else(|| pipeline.to_owned(), |(_, _, name)| name.clone())
+    }
+
+    // Everything registered in registration order, None while still building
+    pub fn entries(&self) -> impl Iterator<Item = (&str, Option<&Pipeline>)> {
+        self.store.iter().map(|(name, slot)| match slot {
+            Slot::Ready(pipeline) => (name.as_str(), Some(pipeline)),
+            Slot::Pending { .. } | Slot::Failed(_) => (name.as_str(), None),
+        })
+    }
+
+    // Registration order, which is what the cycling keys walk through
+    pub fn names_with_prefix<'s>(&'s self, prefix: &'s str) -> impl Iterator<Item = &'s str> {
+        self.store
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .filter(move |n| n.starts_with(prefix))
+    }
+}
+
+// Builder so we stop copy-pasting 50 lines of descriptor per pipeline
+#[derive(Clone)]
+pub struct PipelineBuilder<'a> {
+    label: &'a str,
+    shader: &'a wgpu::ShaderModule,
+    vs_entry: &'a str,
+    fs_entry: &'a str,
+    vertex_buffers: Vec<wgpu::VertexBufferLayout<'a>>,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+    blend: Option<wgpu::BlendState>,
+    targets: Vec<wgpu::TextureFormat>,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    // Off for depth-only pipelines, which have no color targets either. Alpha tested ones
+    // keep the fragment stage anyway, its discards decide what depth gets written
+    fragment: bool,
+    // WGSL override constants by name (or @id), filled in by set_override on the recipe
+    constants: HashMap<String, f64>,
+    // The shader's module, for override names and bind group requirements
+    reflection: Option<&'a Reflection>,
+    samples: u32,
+    alpha_to_coverage: bool,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    pub fn new(label: &'a str, shader: &'a wgpu::ShaderModule) -> Self {
+        Self {
+            label,
+            shader,
+            vs_entry: "vs_main",
+            fs_entry: "fs_main",
+            vertex_buffers: Vec::new(),
+            bind_group_layouts: Vec::new(),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+            blend: Some(wgpu::BlendState::REPLACE),
+            targets: Vec::new(),
+            depth_stencil: None,
+            fragment: true,
+            constants: HashMap::new(),
+            reflection: None,
+            samples: 1,
+            alpha_to_coverage: false,
+        }
+    }
+
+    pub fn vertex_entry(mut self, entry: &'a str) -> Self {
+        self.vs_entry = entry;
+        self
+    }
+
+    pub fn fragment_entry(mut self, entry: &'a str) -> Self {
+        self.fs_entry = entry;
+        self
+    }
+
+    // Leaving this out is the no-vertex-layout path (positions come from vertex_index)
+    pub fn vertex_buffer(mut self, layout: wgpu::VertexBufferLayout<'a>) -> Self {
+        self.vertex_buffers.push(layout);
+        self
+    }
+
+    pub fn bind_group_layout(mut self, layout: &'a wgpu::BindGroupLayout) -> Self {
+        self.bind_group_layouts.push(layout);
+        self
+    }
+
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn blend_mode(mut self, mode: BlendMode) -> Self {
+        if mode == BlendMode::Cutout {
+            self.constants.insert(ALPHA_TEST.to_owned(), 1.0);
+        } else {
+            self.constants.remove(ALPHA_TEST);
+        }
+        self.blend(Some(mode.state()))
+    }
+
+    // MSAA samples per pixel of the attachments it'll draw into, 1 unless told otherwise
+    pub fn sample_count(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    // Lets the alpha written to the first target decide how many samples the fragment
+    // covers. Only does anything multisampled, where a cutout's ALPHA_TEST discard gives way
+    // to ALPHA_TO_COVERAGE
+    pub fn alpha_to_coverage(mut self, enabled: bool) -> Self {
+        self.alpha_to_coverage = enabled;
+        self
+    }
+
+    // One call per @location output, for multiple render targets
+    pub fn color_target(mut self, format: wgpu::TextureFormat) -> Self {
+        self.targets.push(format);
+        self
+    }
+
+    pub fn depth(mut self, format: wgpu::TextureFormat, compare: wgpu::CompareFunction) -> Self {
+        self.depth_stencil = Some(wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        });
+        self
+    }
+
+    // Turns the compare given to depth() around for reverse-Z. Every pipeline with depth
+    // goes through this, after depth()
+    pub fn depth_convention(mut self, convention: DepthConvention) -> Self {
+        if let Some(depth) = &mut self.depth_stencil {
+            depth.depth_compare = convention.compare(depth.depth_compare);
+        }
+        self
+    }
+
+    // A WGSL override constant, the same ones set_override changes later
+    pub fn constant(mut self, name: &str, value: f64) -> Self {
+        self.constants.insert(name.to_owned(), value);
+        self
+    }
+
+    // Only meaningful after depth()
+    pub fn depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        if let Some(depth) = &mut self.depth_stencil {
+            depth.depth_compare = compare;
+        }
+        self
+    }
+
+    // Test against the depth buffer without writing to it, for overlays like gizmos.
+    // Only meaningful after depth()
+    pub fn depth_write(mut self, enabled: bool) -> Self {
+        if let Some(depth) = &mut self.depth_stencil {
+            depth.depth_write_enabled = enabled;
+        }
+        self
+    }
+
+    // Writes depth and nothing else, no fragment stage and no color targets. The vertex stage
+    // stays as it is, which a depth pre-pass needs to match the pass shading after it
+    pub fn depth_only(mut self) -> Self {
+        self.fragment = false;
+        self.targets.clear();
+        self
+    }
+
+    // The shader's reflection, so set_override can refuse names the shader doesn't have
+    // and bind group layouts can be checked against what the entry points use. None (a
+    // shader that didn't reflect) leaves both unchecked
+    pub fn reflect(mut self, reflection: Option<&'a Reflection>) -> Self {
+        self.reflection = reflection;
+        self
+    }
+
+    fn bindings(&self) -> Option<Vec<ShaderBinding>> {
+        self.reflection
+            .map(|reflection| reflection.bindings(&[self.vs_entry, self.fs_entry]))
+    }
+
+    // Everything it was given, owned, so the pipeline can be built again later
+    pub fn recipe(&self) -> Recipe {
+        Recipe {
+            label: self.label.to_owned(),
+            shader: self.shader.clone(),
+            vs_entry: self.vs_entry.to_owned(),
+            fs_entry: self.fs_entry.to_owned(),
+            vertex_buffers: self
+                .vertex_buffers
+                .iter()
+                .map(|layout| {
+                    (
+                        layout.array_stride,
+                        layout.step_mode,
+                        layout.attributes.to_vec(),
+                    )
+                })
+                .collect(),
+            bind_group_layouts: self
+                .bind_group_layouts
+                .iter()
+                .map(|&layout| layout.clone())
+                .collect(),
+            topology: self.topology,
+            cull_mode: self.cull_mode,
+            blend: self.blend,
+            targets: self.targets.clone(),
+            depth_stencil: self.depth_stencil.clone(),
+            fragment: self.fragment,
+            constants: self.constants.clone(),
+            overrides: self.reflection.map(Reflection::overrides),
+            bindings: self.bindings(),
+            samples: self.samples,
+            alpha_to_coverage: self.alpha_to_coverage,
+        }
+    }
+
+    // `format` is only used when no color targets were given explicitly
+    pub fn build(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> Pipeline {
+        let targets = if !self.fragment {
+            Vec::new()
+        } else if self.targets.is_empty() {
+            vec![format]
+        } else {
+            self.targets.clone()
+        };
+        let color_targets: Vec<_> = targets
+            .iter()
+            .map(|&format| {
+                Some(wgpu::ColorTargetState {
+                    format,
+                    blend: self.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+            })
+            .collect();
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(self.label),
+            bind_group_layouts: &self.bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        let coverage = self.alpha_to_coverage && self.samples > 1;
+        let constants = shader_constants(&self.constants, coverage);
+        let compilation_options = || wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        };
+        let fragment = self.fragment || constants.contains_key(ALPHA_TEST);
+
+        let raw = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(self.label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: self.shader,
+                entry_point: Some(self.vs_entry),
+                compilation_options: compilation_options(),
+                buffers: &self.vertex_buffers,
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: self.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: self.cull_mode,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: wgpu::MultisampleState {
+                count: self.samples,
+                mask: !0,
+                alpha_to_coverage_enabled: coverage,
+            },
+            fragment: fragment.then(|| wgpu::FragmentState {
+                module: self.shader,
+                entry_point: Some(self.fs_entry),
+                compilation_options: compilation_options(),
+                targets: &color_targets,
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Pipeline {
+            raw,
+            targets,
+            depth: self.depth_stencil.as_ref().map(|depth| depth.format),
+            topology: self.topology,
+            vertex_layout: self.vertex_buffers.first().map(VertexLayoutId::of),
+            constants: sorted(&constants),
+            bindings: self.bindings(),
+            bind_groups: self.bind_group_layouts.len() as u32,
+            vertex_buffers: self.vertex_buffers.len() as u32,
+            samples: self.samples,
+        }
+    }
+}
+
+// What the shader gets out of `constants`. With alpha to coverage a cutout's ALPHA_TEST
+// becomes ALPHA_TO_COVERAGE
+fn shader_constants(constants: &HashMap<String, f64>, coverage: bool) -> HashMap<String, f64> {
+    let mut constants = constants.clone();
+    if coverage {
+        if let Some(test) = constants.remove(ALPHA_TEST) {
+            constants.insert(ALPHA_TO_COVERAGE.to_owned(), test);
+        }
+    }
+    constants
+}
+
+fn sorted(constants: &HashMap<String, f64>) -> Vec<(String, f64)> {
+    let mut sorted: Vec<_> = constants.iter().map(|(k, &v)| (k.clone(), v)).collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    sorted
+}
+
+// A PipelineBuilder that owns what it borrowed. The bank keeps these for pipelines that
+// have to be built again when the surface format changes
+#[derive(Clone)]
+pub struct Recipe {
+    label: String,
+    shader: wgpu::ShaderModule,
+    vs_entry: String,
+    fs_entry: String,
+    vertex_buffers: Vec<(
+        wgpu::BufferAddress,
+        wgpu::VertexStepMode,
+        Vec<wgpu::VertexAttribute>,
+    )>,
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+    blend: Option<wgpu::BlendState>,
+    targets: Vec<wgpu::TextureFormat>,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    fragment: bool,
+    constants: HashMap<String, f64>,
+    overrides: Option<Vec<String>>,
+    bindings: Option<Vec<ShaderBinding>>,
+    samples: u32,
+    alpha_to_coverage: bool,
+}
+
+impl Recipe {
+    fn encode_srgb(&mut self, views: SurfaceViews) {
+        let encode = if views.ui_encodes() { 1.0 } else { 0.0 };
+        self.constants.insert(ENCODE_SRGB.to_owned(), encode);
+    }
+
+    // Whether `pipeline` is what this builds for `format`, the format and override
+    // constants being what can change after registering
+    fn built(&self, pipeline: &Pipeline, format: wgpu::TextureFormat) -> bool {
+        let targets = if !self.fragment {
+            Vec::new()
+        } else if self.targets.is_empty() {
+            vec![format]
+        } else {
+            self.targets.clone()
+        };
+        let coverage = self.alpha_to_coverage && self.samples > 1;
+        pipeline.targets == targets
+            && pipeline.constants == sorted(&shader_constants(&self.constants, coverage))
+    }
+
+    pub fn build(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> Pipeline {
+        let mut pipeline = PipelineBuilder {
+            label: &self.label,
+            shader: &self.shader,
+            vs_entry: &self.vs_entry,
+            fs_entry: &self.fs_entry,
+            vertex_buffers: self
+                .vertex_buffers
+                .iter()
+                .map(
+                    |(array_stride, step_mode, attributes)| wgpu::VertexBufferLayout {
+                        array_stride: *array_stride,
+                        step_mode: *step_mode,
+                        attributes,
+                    },
+                )
+                .collect(),
+            bind_group_layouts: self.bind_group_layouts.iter().collect(),
+            topology: self.topology,
+            cull_mode: self.cull_mode,
+            blend: self.blend,
+            targets: self.targets.clone(),
+            depth_stencil: self.depth_stencil.clone(),
+            fragment: self.fragment,
+            constants: self.constants.clone(),
+            reflection: None,
+            samples: self.samples,
+            alpha_to_coverage: self.alpha_to_coverage,
+        }
+        .build(device, format);
+        // Reflected when the recipe was made, the module itself isn't kept
+        pipeline.bindings.clone_from(&self.bindings);
+        pipeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+
+    // family_member and specialization_for only look at names, so these stand in for
+    // pipelines without a device
+    fn named(names: &[&str]) -> RenderPipelineBank {
+        let mut bank = RenderPipelineBank::new();
+        for name in names {
+            bank.insert((*name).to_owned(), Slot::Failed(String::new()));
+        }
+        bank
+    }
+
+    fn layout(attributes: &[wgpu::VertexAttribute]) -> VertexLayoutId {
+        VertexLayoutId::of(&wgpu::VertexBufferLayout {
+            array_stride: 32,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        })
+    }
+
+    #[test]
+    fn family_members_are_picked_by_topology() {
+        let bank = named(&["outline", "outline/line", "outline/point", "shapes"]);
+        assert_eq!(
+            bank.family_member("outline", wgpu::PrimitiveTopology::LineStrip),
+            "outline/line"
+        );
+        assert_eq!(
+            bank.family_member("outline", wgpu::PrimitiveTopology::PointList),
+            "outline/point"
+        );
+        // No fill member, the family's own pipeline draws triangles
+        assert_eq!(
+            bank.family_member("outline", wgpu::PrimitiveTopology::TriangleList),
+            "outline"
+        );
+        // Not a family at all
+        assert_eq!(
+            bank.family_member("shapes", wgpu::PrimitiveTopology::LineList),
+            "shapes"
+        );
+    }
+
+    // What Pass::set_pipeline_for binds: the topology's member, then its build for the layout
+    #[test]
+    fn set_pipeline_for_resolves_member_then_layout() {
+        let mut bank = named(&["wire", "wire/line", "wire/line#packed", "wire#packed"]);
+        let plain = layout(&wgpu::vertex_attr_array![0 => Float32x3]);
+        let packed = layout(&wgpu::vertex_attr_array![0 => Snorm16x4]);
+        bank.specialize("wire/line", packed, "wire/line#packed");
+        bank.specialize("wire", packed, "wire#packed");
+        let pick = |topology, layout| {
+            bank.specialization_for(&bank.family_member("wire", topology), layout)
+        };
+        assert_eq!(pick(wgpu::PrimitiveTopology::LineList, plain), "wire/line");
+        assert_eq!(
+            pick(wgpu::PrimitiveTopology::LineList, packed),
+            "wire/line#packed"
+        );
+        assert_eq!(pick(wgpu::PrimitiveTopology::TriangleList, plain), "wire");
+        assert_eq!(
+            pick(wgpu::PrimitiveTopology::TriangleList, packed),
+            "wire#packed"
+        );
+    }
+
+    // The "default" family as main registers it, with the vertex types that really draw
+    // with it. The lit build only exists for triangles, lit lines fall back to the member
+    // itself so the draw check can say what went wrong
+    fn default_family() -> RenderPipelineBank {
+        let mut bank = named(&[
+            "default",
+            "default/line",
+            "default#lit",
+            "default#packed",
+            "default/line#packed",
+        ]);
+        let lit = VertexLayoutId::of(&crate::deferred::DeferredDemo::vertex_layout());
+        let packed =
+            VertexLayoutId::of(&<crate::CompactVertex as crate::mesh::PackedVertex>::desc());
+        bank.specialize("default", lit, "default#lit");
+        bank.specialize("default", packed, "default#packed");
+        bank.specialize("default/line", packed, "default/line#packed");
+        bank
+    }
+
+    #[test]
+    fn default_family_picks_by_real_vertex_layouts() {
+        let bank = default_family();
+        let plain = VertexLayoutId::of(&crate::Vertex::desc());
+        let lit = VertexLayoutId::of(&crate::deferred::DeferredDemo::vertex_layout());
+        let packed =
+            VertexLayoutId::of(&<crate::CompactVertex as crate::mesh::PackedVertex>::desc());
+        assert!(plain != lit && plain != packed && lit != packed);
+        let pick = |topology, layout| {
+            bank.specialization_for(&bank.family_member("default", topology), layout)
+        };
+        let (fill, line) = (
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::PrimitiveTopology::LineList,
+        );
+        assert_eq!(pick(fill, plain), "default");
+        assert_eq!(pick(fill, lit), "default#lit");
+        assert_eq!(pick(fill, packed), "default#packed");
+        assert_eq!(pick(line, plain), "default/line");
+        assert_eq!(pick(line, lit), "default/line");
+        assert_eq!(pick(line, packed), "default/line#packed");
+        // Layouts nothing was specialized for keep the pipeline asked for
+        let unknown = layout(&wgpu::vertex_attr_array![0 => Float32x2]);
+        assert_eq!(pick(fill, unknown), "default");
+        assert_eq!(bank.specialization_for("shapes", lit), "shapes");
+        // Specializing the same layout again replaces the entry rather than adding one
+        let mut bank = default_family();
+        bank.specialize("default", lit, "default#lit2");
+        assert_eq!(bank.specialization_for("default", lit), "default#lit2");
+        assert_eq!(bank.specializations.len(), 3);
+    }
+
+    // A lit mesh drawn through what the selection hands back: the lit build takes it, the
+    // plain build it would fall back to without one is turned away naming both layouts
+    #[test]
+    fn unspecialized_fallback_is_a_layout_mismatch() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let bank = default_family();
+        let lit_layout = crate::deferred::DeferredDemo::vertex_layout();
+        let lit = VertexLayoutId::of(&lit_layout);
+        let plain = VertexLayoutId::of(&crate::Vertex::desc());
+        // Stands in for the pipelines' own layouts, what Pipeline::vertex_layout would hold
+        let layout_of = |name: &str| if name.contains("#lit") { lit } else { plain };
+        let mesh = crate::mesh::Mesh::new(
+            &gpu.device,
+            &crate::memory::GpuMemoryTracker::new(),
+            "Lit Cube",
+            &lit_layout,
+            wgpu::PrimitiveTopology::TriangleList,
+            &[[0.0f32; 9]; 3],
+            crate::mesh::Indices::U16(&[0, 1, 2]),
+        );
+        let fill = wgpu::PrimitiveTopology::TriangleList;
+        let name = bank.specialization_for(&bank.family_member("default", fill), lit);
+        assert!(mesh.check(&name, fill, Some(layout_of(&name))).is_ok());
+        // The same mesh asked to draw with the plain "default" build
+        let error = mesh
+            .check("default", fill, Some(layout_of("default")))
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            ForayError::LayoutMismatch { mesh, pipeline, mesh_layout, pipeline_layout }
+                if mesh == "Lit Cube" && pipeline == "default"
+                    && *mesh_layout == lit && *pipeline_layout == Some(plain)
+        ));
+        let message = error.to_string();
+        assert!(message.contains(&lit.to_string()) && message.contains(&plain.to_string()));
+    }
+
+    #[test]
+    fn failed_builds_are_marked_and_reported() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let mut bank = RenderPipelineBank::new();
+        let (go, wait) = mpsc::channel::<()>();
+        let handle = bank.request("broken", None, &gpu.device, move |_| {
+            let _ = wait.recv();
+            panic!("no such entry point");
+        });
+        assert!(bank.poll().is_empty());
+        assert!(handle.is_building());
+        assert!(matches!(
+            bank.resolve("broken"),
+            Err(ForayError::PipelineNotReady(_))
+        ));
+
+        go.send(()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut failed = Vec::new();
+        while failed.is_empty() && Instant::now() < deadline {
+            failed = bank.poll();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(matches!(
+            failed.as_slice(),
+            [ForayError::PipelineBuild { pipeline, reason }]
+                if pipeline == "broken" && reason == "no such entry point"
+        ));
+        assert_eq!(
+            handle.state(),
+            BuildState::Failed("no such entry point".to_owned())
+        );
+        assert!(matches!(
+            bank.resolve("broken"),
+            Err(ForayError::PipelineBuild { .. })
+        ));
+        assert_eq!(bank.pending_count(), 0);
+        assert_eq!(bank.finish_pending(Duration::ZERO), 0);
+        // Reported once, not on every poll after
+        assert!(bank.poll().is_empty());
+    }
+
+    fn custom(src_factor: wgpu::BlendFactor) -> BlendMode {
+        let component = wgpu::BlendComponent {
+            src_factor,
+            dst_factor: wgpu::BlendFactor::OneMinusSrc,
+            operation: wgpu::BlendOperation::Add,
+        };
+        BlendMode::Custom(wgpu::BlendState {
+            color: component,
+            alpha: component,
+        })
+    }
+
+    #[test]
+    fn blend_modes_key_apart() {
+        let modes = [
+            BlendMode::Replace,
+            BlendMode::Alpha,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+            BlendMode::Premultiplied,
+            BlendMode::Cutout,
+            custom(wgpu::BlendFactor::One),
+            custom(wgpu::BlendFactor::SrcAlpha),
+            // The same state as Alpha, but asked for as a custom one
+            BlendMode::Custom(wgpu::BlendState::ALPHA_BLENDING),
+        ];
+        let keys: Vec<String> = modes.iter().map(|mode| mode.key("sprites")).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(key.starts_with("sprites@"), "{key}");
+            assert!(!keys[..i].contains(key), "{key} twice");
+        }
+        // Equal custom states are one pipeline
+        assert_eq!(custom(wgpu::BlendFactor::One).key("sprites"), keys[6],);
+        assert_ne!(keys[6], custom(wgpu::BlendFactor::One).key("particles"));
+    }
+
+    #[test]
+    fn register_blends_builds_each_state_once() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let shader = crate::shaders::create_module(
+            &gpu.device,
+            "Blend Test Shader",
+            "@vertex fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(f32(i % 2u), f32(i / 2u), 0.0, 1.0);
+            }
+            @fragment fn fs_main() -> @location(0) vec4<f32> {
+                return vec4<f32>(1.0);
+            }",
+        );
+        let builder = PipelineBuilder::new("Blend Test", &shader);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let mut bank = RenderPipelineBank::new();
+        let (one, src_alpha) = (
+            custom(wgpu::BlendFactor::One),
+            custom(wgpu::BlendFactor::SrcAlpha),
+        );
+        bank.register_blends(
+            &gpu.device,
+            format,
+            "quad",
+            &builder,
+            &[one, BlendMode::Additive, one],
+        );
+        // Asking again for what's there adds only the new state
+        bank.register_blends(&gpu.device, format, "quad", &builder, &[src_alpha, one]);
+        let mut names: Vec<&str> = bank.names_with_prefix("quad@").collect();
+        names.sort_unstable();
+        let mut expected = vec![
+            one.key("quad"),
+            BlendMode::Additive.key("quad"),
+            src_alpha.key("quad"),
+        ];
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+        for name in &expected {
+            assert!(bank.resolve(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn sample_keys_leave_single_sampled_names_alone() {
+        assert_eq!(sample_key("grass@cutout", 1), "grass@cutout");
+        assert_eq!(sample_key("grass@cutout", 4), "grass@cutout/msaa4");
+        assert_eq!(
+            sample_key(&DepthStage::Shaded.key("grass@cutout"), 4),
+            "grass@cutout/equal/msaa4"
+        );
+    }
+
+    #[test]
+    fn multisampled_cutouts_trade_the_discard_for_coverage() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let shader = crate::shaders::create_module(
+            &gpu.device,
+            "Cutout Test Shader",
+            "override ALPHA_TEST: bool = false;
+            override ALPHA_TO_COVERAGE: bool = false;
+            @vertex fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(f32(i % 2u), f32(i / 2u), 0.0, 1.0);
+            }
+            @fragment fn fs_main() -> @location(0) vec4<f32> {
+                if ALPHA_TEST {
+                    discard;
+                }
+                return vec4<f32>(1.0, 1.0, 1.0, select(1.0, 0.5, ALPHA_TO_COVERAGE));
+            }",
+        );
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let builder = PipelineBuilder::new("Cutout Test", &shader).blend_mode(BlendMode::Cutout);
+        let mut bank = RenderPipelineBank::new();
+        let key = BlendMode::Cutout.key("quad");
+        bank.register(key.clone(), builder.build(&gpu.device, format));
+        bank.register_sample_counts(&gpu.device, format, &key, &builder, &[1, 4]);
+
+        let single = bank.resolve(&key).unwrap();
+        assert_eq!(single.samples, 1);
+        assert_eq!(single.constants, [(ALPHA_TEST.to_owned(), 1.0)]);
+        let multisampled = bank.resolve(&sample_key(&key, 4)).unwrap();
+        assert_eq!(multisampled.samples, 4);
+        assert_eq!(
+            multisampled.constants,
+            [(ALPHA_TO_COVERAGE.to_owned(), 1.0)]
+        );
+        // 1 is the pipeline as registered, nothing new under another name
+        assert_eq!(bank.names_with_prefix(&key).count(), 2);
+
+        // Without ALPHA_TEST there is no discard to swap out, only the sample count changes
+        let replace = PipelineBuilder::new("Replace Test", &shader)
+            .sample_count(4)
+            .alpha_to_coverage(true)
+            .build(&gpu.device, format);
+        assert_eq!(replace.samples, 4);
+        assert!(replace.constants.is_empty());
+    }
+}
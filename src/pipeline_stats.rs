@@ -0,0 +1,307 @@
+use std::sync::{Arc, Mutex};
+
+use crate::maintain::Maintain;
+
+// Passes measured per frame at most, later ones that opt in go unmeasured
+const MAX_PASSES: u32 = 16;
+// Frames whose counts can be on the way back at once. Frames finding none free go unmeasured
+const IN_FLIGHT: usize = 3;
+// vertex invocations, clipper primitives out, fragment invocations, in the flags' bit order
+const COUNTERS: u64 = 3;
+const RESULT_SIZE: u64 = COUNTERS * std::mem::size_of::<u64>() as u64;
+
+// One measured pass of a finished frame
+#[derive(Clone, Debug)]
+pub struct PassStatistics {
+    pub pass: String,
+    pub vertex_invocations: u64,
+    pub primitives: u64,
+    pub fragment_invocations: u64,
+}
+
+// What a Frame carries while it's recorded: the query set and the passes that opted in so
+// far, in query order. See Pass::measure
+pub struct StatisticsQueries {
+    set: wgpu::QuerySet,
+    readback: usize,
+    labels: Vec<String>,
+}
+
+impl StatisticsQueries {
+    // The next free query for `label`, None once the set is used up
+    pub fn next(&mut self, label: &str) -> Option<(&wgpu::QuerySet, u32)> {
+        let index = self.labels.len() as u32;
+        if index >= MAX_PASSES {
+            return None;
+        }
+        self.labels.push(label.to_owned());
+        Some((&self.set, index))
+    }
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    // Labels of the frame being read back, empty when the buffer is free
+    labels: Vec<String>,
+    // map_async was called for those labels
+    waiting: bool,
+    // Set by the map_async callback, whether the buffer got mapped
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+// Pipeline statistics queries around the passes that ask for them, read back a few frames
+// later without stalling. Without Features::PIPELINE_STATISTICS_QUERY there's none of it,
+// nothing gets measured and the stats just don't show counts
+pub struct PipelineStatistics {
+    set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    // The last frame that came back
+    pub latest: Vec<PassStatistics>,
+}
+
+impl PipelineStatistics {
+    // Only with Optional::PipelineStatistics, the device needs the feature
+    pub fn new(device: &wgpu::Device) -> Self {
+        let set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pipeline Statistics"),
+            ty: wgpu::QueryType::PipelineStatistics(
+                wgpu::PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS
+                    | wgpu::PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT
+                    | wgpu::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS,
+            ),
+            count: MAX_PASSES,
+        });
+        let size = RESULT_SIZE * u64::from(MAX_PASSES);
+        let resolve = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pipeline Statistics Resolve"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readbacks = (0..IN_FLIGHT)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Pipeline Statistics Readback"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                labels: Vec::new(),
+                waiting: false,
+                mapped: Arc::new(Mutex::new(None)),
+            })
+            .collect();
+        Self {
+            set,
+            resolve,
+            readbacks,
+            latest: Vec::new(),
+        }
+    }
+
+    // For Frame::statistics, None when every readback is still busy with an earlier frame
+    pub fn begin_frame(&self) -> Option<StatisticsQueries> {
+        let readback = self
+            .readbacks
+            .iter()
+            .position(|readback| readback.labels.is_empty())?;
+        Some(StatisticsQueries {
+            set: self.set.clone(),
+            readback,
+            labels: Vec::new(),
+        })
+    }
+
+    // After the frame's last pass, before its submit
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, queries: StatisticsQueries) {
+        let count = queries.labels.len() as u32;
+        if count == 0 {
+            return;
+        }
+        let size = RESULT_SIZE * u64::from(count);
+        encoder.resolve_query_set(&self.set, 0..count, &self.resolve, 0);
+        let readback = &mut self.readbacks[queries.readback];
+        encoder.copy_buffer_to_buffer(&self.resolve, 0, &readback.buffer, 0, size);
+        readback.labels = queries.labels;
+    }
+
+    // After the submit: starts mapping what resolve copied, and picks up whatever has come
+    // back since the last frame into `latest`. The maps are polled by `maintain`
+    pub fn collect(&mut self, maintain: &mut Maintain) {
+        for readback in &mut self.readbacks {
+            if readback.labels.is_empty() || readback.waiting {
+                continue;
+            }
+            let mapped = readback.mapped.clone();
+            let done = maintain.register("pipeline statistics readback", IN_FLIGHT as u64);
+            let size = RESULT_SIZE * readback.labels.len() as u64;
+            readback
+                .buffer
+                .slice(..size)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if let Err(e) = &result {
+                        log::warn!("Pipeline statistics readback failed: {e}");
+                    }
+                    *mapped.lock().unwrap() = Some(result.is_ok());
+                    done.finish();
+                });
+            readback.waiting = true;
+        }
+
+        for readback in &mut self.readbacks {
+            let Some(mapped) = readback.mapped.lock().unwrap().take() else {
+                continue;
+            };
+            readback.waiting = false;
+            // That frame's counts are lost, the buffer's free for another
+            if !mapped {
+                readback.labels.clear();
+                continue;
+            }
+            let size = RESULT_SIZE * readback.labels.len() as u64;
+            let counts: Vec<u64> =
+                bytemuck::pod_collect_to_vec(&readback.buffer.slice(..size).get_mapped_range());
+            readback.buffer.unmap();
+            self.latest = std::mem::take(&mut readback.labels)
+                .into_iter()
+                .zip(counts.chunks_exact(COUNTERS as usize))
+                .map(|(pass, counts)| PassStatistics {
+                    pass,
+                    vertex_invocations: counts[0],
+                    primitives: counts[1],
+                    fragment_invocations: counts[2],
+                })
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Background, ColorTarget, Frame};
+    use crate::gpu_context::GpuContext;
+    use crate::memory::GpuMemoryTracker;
+    use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+    use crate::targets::TargetRegistry;
+
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    // A quad over the whole target per instance
+    const SHADER: &str = "
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            var corners = array<vec2<f32>, 6>(
+                vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(-1.0, 1.0),
+                vec2<f32>(-1.0, 1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+            );
+            return vec4<f32>(corners[index], 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0, 0.0, 1.0, 0.5);
+        }
+    ";
+
+    #[test]
+    fn overlapping_quads_count_twice_the_fragments() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        // The shared device asks for no features, this needs one of its own
+        let feature = wgpu::Features::PIPELINE_STATISTICS_QUERY;
+        if !gpu.adapter.features().contains(feature) {
+            eprintln!("No pipeline statistics on this adapter, skipping");
+            return;
+        }
+        let (device, queue) = pollster::block_on(gpu.adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: feature,
+                required_limits:
+                    wgpu::Limits::downlevel_defaults().using_resolution(gpu.adapter.limits()),
+                label: Some("Statistics Test Device"),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))
+        .expect("No device with pipeline statistics");
+
+        let size = (64, 32);
+        let memory = GpuMemoryTracker::new();
+        let targets = TargetRegistry::new(size, &memory);
+        let mut bank = RenderPipelineBank::new();
+        let shader = crate::shaders::create_module(&device, "Statistics Test Shader", SHADER);
+        bank.register(
+            "quads",
+            PipelineBuilder::new("Statistics Test Quads", &shader)
+                .cull_mode(None)
+                .blend_mode(BlendMode::Alpha)
+                .build(&device, FORMAT),
+        );
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Statistics Test Target"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let mut statistics = PipelineStatistics::new(&device);
+        let mut frame = Frame::offscreen(
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &device,
+            FORMAT,
+            Background::Clear(wgpu::Color::BLACK),
+        );
+        frame.statistics = statistics.begin_frame();
+        let load = frame.background.color();
+        for (label, quads) in [("one quad", 1), ("two quads", 2)] {
+            let mut pass = frame.pass(label, &[(ColorTarget::Swapchain, load)], &targets);
+            pass.measure();
+            pass.set_pipeline(&bank, "quads").unwrap();
+            pass.raw.draw(0..6, 0..quads);
+        }
+        // Not measured, so not in the results
+        drop(frame.pass("unmeasured", &[(ColorTarget::Swapchain, load)], &targets));
+        let queries = frame.statistics.take().expect("No readback free");
+        statistics.resolve(&mut frame.encoder, queries);
+        frame.finish(&queue);
+
+        let mut maintain = Maintain::new();
+        statistics.collect(&mut maintain);
+        device.poll(wgpu::Maintain::Wait);
+        statistics.collect(&mut maintain);
+
+        let passes: Vec<&str> = statistics
+            .latest
+            .iter()
+            .map(|pass| pass.pass.as_str())
+            .collect();
+        assert_eq!(passes, ["one quad", "two quads"]);
+        let (one, two) = (&statistics.latest[0], &statistics.latest[1]);
+        let pixels = f64::from(size.0 * size.1);
+        assert!(one.vertex_invocations >= 4 && two.vertex_invocations >= 8);
+        assert!(one.primitives >= 2 && two.primitives == 2 * one.primitives);
+        // Some GPUs shade a few helper fragments along the diagonal
+        let overdraw = |pass: &PassStatistics| pass.fragment_invocations as f64 / pixels;
+        assert!(
+            (1.0..1.25).contains(&overdraw(one)),
+            "One quad shaded {} fragments over {pixels} pixels",
+            one.fragment_invocations
+        );
+        let ratio = two.fragment_invocations as f64 / one.fragment_invocations as f64;
+        assert!(
+            (1.9..2.1).contains(&ratio),
+            "Two quads shaded {ratio}x the fragments of one"
+        );
+    }
+}
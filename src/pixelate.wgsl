@@ -0,0 +1,21 @@
+#include "post.wgsl"
+
+struct Pixelate {
+    // Side of one virtual pixel in screen pixels
+    pixel_size: f32,
+    _padding: vec3<f32>,
+};
+
+@group(1) @binding(0) var<uniform> pixelate: Pixelate;
+
+// Every screen pixel in a block loads the texel at the block's center, no filtering, so the
+// blocks come out flat and the result doesn't depend on the sampler
+@fragment
+fn fs_pixelate(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = max(floor(pixelate.pixel_size), 1.0);
+    let dimensions = vec2<f32>(textureDimensions(input));
+    let block = floor(in.clip_position.xy / size);
+    let center = min((block + 0.5) * size, dimensions - 1.0);
+    let color = textureLoad(input, vec2<i32>(center), 0).rgb;
+    return vec4<f32>(color, 1.0);
+}
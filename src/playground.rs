@@ -0,0 +1,161 @@
+use crate::accumulate::Accumulator;
+use crate::pipeline_bank::{
+    BlendMode, PipelineBuilder, PipelineHandle, Recipe, RenderPipelineBank,
+};
+use crate::shaders;
+
+// Every fragment entry point of playground.wgsl, registered as "sdf_<name>", plus
+// "sdf_<name>/accumulate" for drawing into the Accumulator
+#[cfg(not(feature = "audio"))]
+const SDF_ENTRY_POINTS: &[&str] = &["fs_sdf_circle", "fs_sdf_box", "fs_sdf_blend"];
+// The audio bars would only ever be flat without it
+#[cfg(feature = "audio")]
+const SDF_ENTRY_POINTS: &[&str] = &[
+    "fs_sdf_circle",
+    "fs_sdf_box",
+    "fs_sdf_blend",
+    "fs_sdf_audio",
+];
+const PREFIX: &str = "sdf_";
+
+// The first entry is built right away, the rest in the background with the first one
+// standing in for them (they all share the same interface)
+pub fn register_pipelines(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    accumulate_format: wgpu::TextureFormat,
+    globals_layout: &wgpu::BindGroupLayout,
+    bank: &mut RenderPipelineBank,
+) -> Vec<PipelineHandle> {
+    let shader =
+        shaders::create_module(device, "Playground Shader", include_str!("playground.wgsl"));
+
+    let recipe = |name: &str, entry: &str, accumulate: bool| {
+        fullscreen_recipe(name, &shader, entry, globals_layout, accumulate)
+    };
+    // Accumulating variants draw into the accumulator, the others into the swapchain and
+    // follow its format
+    let target = |accumulate: bool| {
+        if accumulate {
+            accumulate_format
+        } else {
+            format
+        }
+    };
+    let variants = [("", false), ("/accumulate", true)];
+
+    let placeholder = SDF_ENTRY_POINTS[0].replacen("fs_sdf_", PREFIX, 1);
+    for (suffix, accumulate) in variants {
+        let name = format!("{placeholder}{suffix}");
+        let recipe = recipe(&name, SDF_ENTRY_POINTS[0], accumulate);
+        bank.register(name.clone(), recipe.build(device, target(accumulate)));
+        if !accumulate {
+            bank.follow_surface(name, recipe);
+        }
+    }
+
+    SDF_ENTRY_POINTS[1..]
+        .iter()
+        .flat_map(|&entry| variants.map(|variant| (entry, variant)))
+        .map(|(entry, (suffix, accumulate))| {
+            let name = format!("{}{suffix}", entry.replacen("fs_sdf_", PREFIX, 1));
+            let recipe = recipe(&name, entry, accumulate);
+            if !accumulate {
+                bank.follow_surface(name.clone(), recipe.clone());
+            }
+            let format = target(accumulate);
+            bank.request(
+                name,
+                Some(&format!("{placeholder}{suffix}")),
+                device,
+                move |device| recipe.build(device, format),
+            )
+        })
+        .collect()
+}
+
+// A fullscreen pipeline of an embedding application's, `source` drawing from fs_main. Built
+// right away along with its "/accumulate" variant, like the first playground entry
+pub fn register_fullscreen(
+    device: &wgpu::Device,
+    name: &str,
+    source: &str,
+    format: wgpu::TextureFormat,
+    accumulate_format: wgpu::TextureFormat,
+    globals_layout: &wgpu::BindGroupLayout,
+    bank: &mut RenderPipelineBank,
+) {
+    let shader = shaders::create_module(device, name, source);
+    let recipe = fullscreen_recipe(name, &shader, "fs_main", globals_layout, false);
+    bank.register(name, recipe.build(device, format));
+    bank.follow_surface(name, recipe);
+    let accumulate = format!("{name}/accumulate");
+    let recipe = fullscreen_recipe(&accumulate, &shader, "fs_main", globals_layout, true);
+    bank.register(accumulate, recipe.build(device, accumulate_format));
+}
+
+// No vertex buffer and no culling, the fullscreen triangle is all we need
+fn fullscreen_recipe(
+    name: &str,
+    shader: &wgpu::ShaderModule,
+    entry: &str,
+    globals_layout: &wgpu::BindGroupLayout,
+    accumulate: bool,
+) -> Recipe {
+    let builder = PipelineBuilder::new(name, shader)
+        .vertex_entry("vs_fullscreen")
+        .fragment_entry(entry)
+        .bind_group_layout(globals_layout)
+        .cull_mode(None);
+    if accumulate {
+        builder
+            .blend_mode(BlendMode::Custom(Accumulator::blend()))
+            .recipe()
+    } else {
+        builder.recipe()
+    }
+}
+
+// Which SDF pipeline is on screen, cycled with a key
+pub struct Playground {
+    pub active: bool,
+    entries: Vec<String>,
+    current: usize,
+    // From register_pipelines, to tell which entries are still building
+    requested: Vec<PipelineHandle>,
+}
+
+impl Playground {
+    pub fn new(bank: &RenderPipelineBank, requested: Vec<PipelineHandle>) -> Self {
+        Self {
+            active: false,
+            // Variants like "/accumulate" go with their entry, they aren't entries of their own
+            entries: bank
+                .names_with_prefix(PREFIX)
+                .filter(|name| !name.contains('/'))
+                .map(String::from)
+                .collect(),
+            current: 0,
+            requested,
+        }
+    }
+
+    // The current entry is still showing its placeholder
+    pub fn is_building(&self) -> bool {
+        self.current().is_some_and(|name| {
+            self.requested
+                .iter()
+                .any(|handle| handle.name == name && handle.is_building())
+        })
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.entries.get(self.current).map(String::as_str)
+    }
+
+    pub fn cycle(&mut self) {
+        if !self.entries.is_empty() {
+            self.current = (self.current + 1) % self.entries.len();
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// SDF playground, the whole window is one big fragment shader
+#include "globals.wgsl"
+#include "sdf.wgsl"
+#include "fullscreen.wgsl"
+
+// Pixel to "world" coordinates: origin at the center, y up, short side spans [-1, 1]
+fn to_world(pixel: vec2<f32>) -> vec2<f32> {
+    let p = (2.0 * pixel - globals.resolution) / min(globals.resolution.x, globals.resolution.y);
+    return vec2<f32>(p.x, -p.y);
+}
+
+// The distance under the cursor as tag 1, shown in the overlay and by `debug` in debug
+// builds. Once a frame, accumulated samples would repeat it
+fn print_under_cursor(pixel: vec2<f32>, d: f32) {
+    if all(floor(pixel) == floor(globals.mouse)) && globals.sample == 0u {
+        debug_print(1u, d);
+    }
+}
+
+@fragment
+fn fs_sdf_circle(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = to_world(in.clip_position.xy + sample_offset());
+    let center = to_world(globals.mouse);
+    let d = sd_circle(p - center, 0.3 + 0.05 * sin(globals.time * 2.0));
+    print_under_cursor(in.clip_position.xy, d);
+    return vec4<f32>(sdf_shade(d), 1.0);
+}
+
+@fragment
+fn fs_sdf_box(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = rotate2d(to_world(in.clip_position.xy + sample_offset()), globals.time * 0.5);
+    let d = sd_rounded_box(p, vec2<f32>(0.5, 0.3), 0.1);
+    print_under_cursor(in.clip_position.xy, d);
+    return vec4<f32>(sdf_shade(d), 1.0);
+}
+
+@fragment
+fn fs_sdf_blend(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = to_world(in.clip_position.xy + sample_offset());
+    let orbit = vec2<f32>(cos(globals.time), sin(globals.time)) * 0.5;
+    let a = sd_circle(p - to_world(globals.mouse), 0.25);
+    let b = sd_box(rotate2d(p - orbit, globals.time), vec2<f32>(0.2));
+    let c = sd_circle(p + orbit, 0.2);
+    let d = op_smooth_union(op_smooth_union(a, b, 0.2), c, 0.2);
+    print_under_cursor(in.clip_position.xy, d);
+    return vec4<f32>(sdf_shade(d), 1.0);
+}
+
+// globals.audio as bars, low bands on the left, over a disc that swells with the bass.
+// Registered with the audio feature only
+@fragment
+fn fs_sdf_audio(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = to_world(in.clip_position.xy + sample_offset());
+    let bass = globals.audio[0].x;
+    var d = sd_circle(p - vec2<f32>(0.0, 0.35), 0.1 + 0.15 * bass);
+    for (var i = 0u; i < 8u; i++) {
+        let level = globals.audio[i / 4u][i % 4u];
+        let half_height = 0.02 + level * 0.35;
+        let center = vec2<f32>((f32(i) - 3.5) * 0.2, -0.8 + half_height);
+        d = op_union(d, sd_rounded_box(p - center, vec2<f32>(0.07, half_height), 0.02));
+    }
+    return vec4<f32>(sdf_shade(d), 1.0);
+}
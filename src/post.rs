@@ -0,0 +1,451 @@
+use crate::bind_groups::BindGroupHandle;
+use crate::buffer_pool::BufferPool;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::pipeline_bank::{PipelineBuilder, RenderPipelineBank};
+use crate::targets::{TargetDesc, TargetHandle, TargetRegistry};
+
+// What a view renders into when the chain is active, and what effects pass between them
+pub const SCENE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Uniform bindings can't be empty, blocks get padded up to this
+const MIN_UNIFORM_SIZE: usize = 16;
+
+// Handed to Effect::prepare for work of its own before the effect's pass
+pub struct EffectContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub registry: &'a TargetRegistry,
+    pub pool: &'a mut BufferPool,
+}
+
+// One fullscreen pass of the chain. The fragment shader includes post.wgsl, which puts the
+// chain's input at group 0, then gets its uniform block at group 1 and bind_group() at group 2
+pub trait Effect {
+    // Module and fragment entry point, the chain builds the pipelines from them
+    fn fragment(&self) -> (&wgpu::ShaderModule, &'static str);
+
+    // The effect's uniform block as it should be this frame
+    fn uniforms(&self) -> Vec<u8>;
+
+    // For effects that bind more than their input and uniforms
+    fn extra_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+        None
+    }
+
+    // Built through TargetRegistry::create_bind_group
+    fn bind_group(&self) -> Option<&BindGroupHandle> {
+        None
+    }
+
+    // Runs before the effect's pass, `input` is what that pass will read
+    fn prepare(&mut self, _ctx: &mut EffectContext, _input: TargetHandle) {}
+
+    // A tweakable number by name, for timelines. None for names the effect doesn't have
+    fn param(&mut self, _name: &str) -> Option<&mut f32> {
+        None
+    }
+}
+
+struct Slot {
+    name: String,
+    enabled: bool,
+    effect: Box<dyn Effect>,
+}
+
+// Effects run in order over the view's output, ping-ponging between two targets. The last
+// enabled one writes straight to the swapchain, disabled ones are skipped entirely
+pub struct EffectChain {
+    effects: Vec<Slot>,
+    ping_pong: [TargetHandle; 2],
+    input_layout: wgpu::BindGroupLayout,
+    uniform_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    output_format: wgpu::TextureFormat,
+}
+
+impl EffectChain {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        registry: &mut TargetRegistry,
+    ) -> Self {
+        let target = |label| TargetDesc {
+            label,
+            format: SCENE_FORMAT,
+            scale: 1.0,
+            storage: false,
+        };
+        let ping_pong = [
+            registry.create(device, target("Post Ping")),
+            registry.create(device, target("Post Pong")),
+        ];
+
+        let input_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Input Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Uniform Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            effects: Vec::new(),
+            ping_pong,
+            input_layout,
+            uniform_layout,
+            sampler,
+            output_format: format,
+        }
+    }
+
+    // Appended at the end of the chain, disabled. Registers "post/<name>" for when it's
+    // last (swapchain format) and "post/<name>/hdr" for when it isn't
+    pub fn add(
+        &mut self,
+        device: &wgpu::Device,
+        bank: &mut RenderPipelineBank,
+        name: &str,
+        effect: Box<dyn Effect>,
+    ) {
+        let (shader, entry) = effect.fragment();
+        let label = format!("{name} Effect Pipeline");
+        let mut layouts = vec![&self.input_layout, &self.uniform_layout];
+        layouts.extend(effect.extra_layout());
+        let builder = layouts.iter().fold(
+            PipelineBuilder::new(&label, shader)
+                .vertex_entry("vs_fullscreen")
+                .fragment_entry(entry)
+                .cull_mode(None),
+            |builder, layout| builder.bind_group_layout(layout),
+        );
+        // The last effect draws into the swapchain, the others into float targets
+        bank.register_surface(device, format!("post/{name}"), &builder, self.output_format);
+        bank.register(
+            format!("post/{name}/hdr"),
+            builder.build(device, SCENE_FORMAT),
+        );
+
+        self.effects.push(Slot {
+            name: name.to_owned(),
+            enabled: false,
+            effect,
+        });
+    }
+
+    // Effects added from now on end in `format`, the bank rebuilds the existing ones
+    pub fn set_output_format(&mut self, format: wgpu::TextureFormat) {
+        self.output_format = format;
+    }
+
+    // Swaps in a new effect under an existing name, keeping its place and enabled state
+    pub fn replace(
+        &mut self,
+        device: &wgpu::Device,
+        bank: &mut RenderPipelineBank,
+        name: &str,
+        effect: Box<dyn Effect>,
+    ) -> Result<(), ForayError> {
+        let index = self
+            .effects
+            .iter()
+            .position(|slot| slot.name == name)
+            .ok_or_else(|| ForayError::UnknownEffect(name.to_owned()))?;
+        let enabled = self.effects[index].enabled;
+        self.add(device, bank, name, effect);
+        let mut slot = self.effects.pop().expect("Just added");
+        slot.enabled = enabled;
+        self.effects[index] = slot;
+        Ok(())
+    }
+
+    fn slot(&mut self, name: &str) -> Result<&mut Slot, ForayError> {
+        self.effects
+            .iter_mut()
+            .find(|slot| slot.name == name)
+            .ok_or_else(|| ForayError::UnknownEffect(name.to_owned()))
+    }
+
+    // Returns whether it's now enabled
+    pub fn toggle(&mut self, name: &str) -> Result<bool, ForayError> {
+        let slot = self.slot(name)?;
+        slot.enabled = !slot.enabled;
+        Ok(slot.enabled)
+    }
+
+    // For tweaking a parameter relative to where it is
+    pub fn param(&mut self, name: &str, param: &str) -> Result<&mut f32, ForayError> {
+        self.slot(name)?
+            .effect
+            .param(param)
+            .ok_or_else(|| ForayError::UnknownEffectParam {
+                effect: name.to_owned(),
+                param: param.to_owned(),
+            })
+    }
+
+    pub fn set_param(&mut self, name: &str, param: &str, value: f32) -> Result<(), ForayError> {
+        *self.param(name, param)? = value;
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), ForayError> {
+        self.slot(name)?.enabled = enabled;
+        Ok(())
+    }
+
+    // Moves `name` to position `index` in the run order, clamped to the end
+    pub fn move_effect(&mut self, name: &str, index: usize) -> Result<(), ForayError> {
+        let from = self
+            .effects
+            .iter()
+            .position(|slot| slot.name == name)
+            .ok_or_else(|| ForayError::UnknownEffect(name.to_owned()))?;
+        let slot = self.effects.remove(from);
+        let index = index.min(self.effects.len());
+        self.effects.insert(index, slot);
+        Ok(())
+    }
+
+    // Names in run order, with whether they're enabled
+    pub fn order(&self) -> Vec<(&str, bool)> {
+        self.effects
+            .iter()
+            .map(|slot| (slot.name.as_str(), slot.enabled))
+            .collect()
+    }
+
+    // With nothing enabled views draw to the swapchain directly
+    pub fn is_active(&self) -> bool {
+        self.effects.iter().any(|slot| slot.enabled)
+    }
+
+    // Where the view draws when the chain is active, in SCENE_FORMAT
+    pub fn scene_target(&self) -> TargetHandle {
+        self.ping_pong[0]
+    }
+
+    // Runs the enabled effects over scene_target(), the last one onto the swapchain
+    pub fn apply(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        registry: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+    ) -> Result<(), ForayError> {
+        let enabled = self.effects.iter().filter(|slot| slot.enabled).count();
+        let mut input = 0;
+        for (step, slot) in self
+            .effects
+            .iter_mut()
+            .filter(|slot| slot.enabled)
+            .enumerate()
+        {
+            let last = step + 1 == enabled;
+            let source = self.ping_pong[input];
+            slot.effect.prepare(
+                &mut EffectContext {
+                    device,
+                    queue,
+                    encoder: &mut frame.encoder,
+                    registry,
+                    pool,
+                },
+                source,
+            );
+
+            let mut uniforms = slot.effect.uniforms();
+            uniforms.resize(uniforms.len().max(MIN_UNIFORM_SIZE), 0);
+            let uniform_buffer = pool.acquire(
+                device,
+                "Post Uniforms",
+                wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                uniforms.len() as u64,
+            );
+            queue.write_buffer(&uniform_buffer, 0, &uniforms);
+            let input_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Input Bind Group"),
+                layout: &self.input_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(registry.view(source)),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            let uniform_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Uniform Bind Group"),
+                layout: &self.uniform_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(uniforms.len() as u64),
+                    }),
+                }],
+            });
+            let mut bind_groups = vec![&input_group, &uniform_group];
+            bind_groups.extend(
+                slot.effect
+                    .bind_group()
+                    .map(|handle| registry.bind_group(handle)),
+            );
+
+            let (target, pipeline) = if last {
+                (ColorTarget::Swapchain, format!("post/{}", slot.name))
+            } else {
+                (
+                    ColorTarget::Offscreen(self.ping_pong[1 - input]),
+                    format!("post/{}/hdr", slot.name),
+                )
+            };
+            frame.fullscreen_pass(
+                &slot.name,
+                target,
+                frame.background.color(),
+                registry,
+                bank,
+                &pipeline,
+                &bind_groups,
+            )?;
+            input = 1 - input;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::frame::Background;
+    use crate::gpu_context::GpuContext;
+    use crate::memory::GpuMemoryTracker;
+
+    // What run_effect renders into, sRGB like the surface
+    pub const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // f32 to f16 bits, truncating. Exact for zero and for values with at most 11
+    // significant bits in the normal half range, which is all the tests feed in
+    pub fn to_half(value: f32) -> u16 {
+        if value == 0.0 {
+            return 0;
+        }
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+        assert!(
+            (1..31).contains(&exponent),
+            "{value} is out of the normal half range"
+        );
+        sign | ((exponent as u16) << 10) | ((bits >> 13) & 0x3ff) as u16
+    }
+
+    // `effect` alone in a chain, run over `input` (linear, row by row) in the scene target.
+    // Comes back as the sRGB output the way the surface would show it
+    pub fn run_effect(
+        gpu: &GpuContext,
+        effect: Box<dyn Effect>,
+        (width, height): (u32, u32),
+        input: &[[f32; 4]],
+    ) -> image::RgbaImage {
+        let memory = GpuMemoryTracker::new();
+        let mut bank = RenderPipelineBank::new();
+        let mut registry = TargetRegistry::new((width, height), &memory);
+        let mut chain = EffectChain::new(&gpu.device, OUTPUT_FORMAT, &mut registry);
+        chain.add(&gpu.device, &mut bank, "effect", effect);
+        chain.set_enabled("effect", true).unwrap();
+
+        let halves: Vec<u16> = input.iter().flatten().map(|&c| to_half(c)).collect();
+        gpu.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: registry.texture(chain.scene_target()),
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&halves),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 8),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let output = gpu.target((width, height), OUTPUT_FORMAT);
+        let mut frame = Frame::offscreen(
+            output.create_view(&wgpu::TextureViewDescriptor::default()),
+            &gpu.device,
+            OUTPUT_FORMAT,
+            Background::Clear(wgpu::Color::BLACK),
+        );
+        let mut pool = BufferPool::new(&memory, 0);
+        chain
+            .apply(
+                &gpu.device,
+                &gpu.queue,
+                &mut frame,
+                &registry,
+                &bank,
+                &mut pool,
+            )
+            .unwrap();
+        frame.finish(&gpu.queue);
+        gpu.read_back(&output)
+    }
+
+    #[test]
+    fn halves_are_exact_for_simple_values() {
+        assert_eq!(to_half(0.0), 0);
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(0.5), 0x3800);
+        assert_eq!(to_half(-2.0), 0xc000);
+        // 3/64, well inside the normal range
+        assert_eq!(to_half(3.0 / 64.0), 0x2a00);
+    }
+}
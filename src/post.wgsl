@@ -0,0 +1,6 @@
+#include "fullscreen.wgsl"
+
+// What every effect in the chain reads, see post.rs. Effects put their uniform block at
+// group 1 and anything else they need at group 2
+@group(0) @binding(0) var input: texture_2d<f32>;
+@group(0) @binding(1) var input_sampler: sampler;
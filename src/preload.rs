@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+use crate::assets::{AssetHandle, AssetRequest, Assets};
+use crate::scene::{MeshRef, Scene};
+
+// Outlines loaded for the scenes so far. A switch only requests what isn't in here
+pub type OutlineCache = HashMap<MeshRef, Vec<Vec2>>;
+
+// A scene switch waiting on the scene's manifest (Scene::manifest). The current scene stays
+// up behind the loading view until everything's in, so nothing loads on first use
+pub struct ScenePreload {
+    pub scene: Scene,
+    // Manifest entries still loading, the ones already loaded were never requested
+    requests: Vec<(MeshRef, AssetHandle)>,
+    total: usize,
+}
+
+impl ScenePreload {
+    pub fn new(scene: Scene, requests: Vec<(MeshRef, AssetHandle)>) -> Self {
+        let total = requests.len();
+        Self {
+            scene,
+            requests,
+            total,
+        }
+    }
+
+    // Requests whatever `scene`'s manifest lists that `cache` doesn't have yet
+    pub fn start(scene: Scene, cache: &OutlineCache, assets: &mut Assets) -> Self {
+        let requests = scene
+            .manifest()
+            .into_iter()
+            .filter(|mesh| !cache.contains_key(mesh))
+            .map(|mesh| {
+                let handle = assets.request(AssetRequest::Outline(mesh.clone()));
+                (mesh, handle)
+            })
+            .collect();
+        Self::new(scene, requests)
+    }
+
+    // The mesh `handle` was loading, if it's one of ours
+    pub fn finish(&mut self, handle: AssetHandle) -> Option<MeshRef> {
+        let position = self
+            .requests
+            .iter()
+            .position(|&(_, request)| request == handle)?;
+        Some(self.requests.remove(position).0)
+    }
+
+    // Failed loads count as done, their items get drawn as a cross like anywhere else
+    pub fn drop_failed(&mut self, assets: &Assets) {
+        self.requests
+            .retain(|&(_, handle)| assets.error(handle).is_none());
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    // (done, total) for the loading view
+    pub fn progress(&self) -> (usize, usize) {
+        (self.total - self.requests.len(), self.total)
+    }
+
+    // Switched away before it was done. Returns how many loads got dropped
+    pub fn cancel(self, assets: &mut Assets) -> usize {
+        for &(_, handle) in &self.requests {
+            assets.cancel(handle);
+        }
+        self.requests.len()
+    }
+}
+
+// Once `scene` is shown, outlines it doesn't use go. The ones it shares with the last scene
+// stay, they were never requested again
+pub fn evict_unused(cache: &mut OutlineCache, scene: &Scene) {
+    let manifest = scene.manifest();
+    cache.retain(|mesh, _| manifest.contains(mesh));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::assets::Asset;
+
+    fn builtin(name: &str) -> MeshRef {
+        MeshRef::Builtin(name.to_owned())
+    }
+
+    // The starter scene cut down to the items using `meshes`
+    fn scene_of(meshes: &[&str]) -> Scene {
+        let mut scene = Scene::starter();
+        scene
+            .items
+            .retain(|item| meshes.iter().any(|&name| item.mesh == builtin(name)));
+        scene
+    }
+
+    // Polls like the frame loop does until `preload` is done
+    fn load(preload: &mut ScenePreload, assets: &mut Assets, cache: &mut OutlineCache) {
+        let started = Instant::now();
+        while !preload.is_done() {
+            assert!(started.elapsed() < Duration::from_secs(10), "Preload hung");
+            for (handle, asset) in assets.poll() {
+                let Asset::Outline(outline) = asset else {
+                    panic!("Only outlines were requested");
+                };
+                let mesh = preload
+                    .finish(handle)
+                    .expect("Nothing but the preload is loading");
+                assert!(cache.insert(mesh, outline).is_none(), "Loaded twice");
+            }
+            preload.drop_failed(assets);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn shared_meshes_load_once_and_stay_across_a_switch() {
+        let mut assets = Assets::new();
+        let mut cache = OutlineCache::new();
+
+        let mut preload =
+            ScenePreload::start(scene_of(&["pentagon", "square"]), &cache, &mut assets);
+        assert_eq!(preload.progress(), (0, 2));
+        load(&mut preload, &mut assets, &mut cache);
+        assert_eq!(preload.progress(), (2, 2));
+        evict_unused(&mut cache, &preload.scene);
+        // The same allocation later on means it wasn't loaded again
+        let square = cache[&builtin("square")].as_ptr();
+
+        let mut preload =
+            ScenePreload::start(scene_of(&["square", "triangle"]), &cache, &mut assets);
+        assert_eq!(preload.progress(), (0, 1));
+        load(&mut preload, &mut assets, &mut cache);
+        evict_unused(&mut cache, &preload.scene);
+
+        assert_eq!(assets.requested(), 3);
+        let mut loaded: Vec<_> = cache.keys().cloned().collect();
+        loaded.sort_by_key(|mesh| format!("{mesh:?}"));
+        assert_eq!(loaded, [builtin("square"), builtin("triangle")]);
+        assert_eq!(cache[&builtin("square")].as_ptr(), square);
+        assert_eq!(cache[&builtin("square")].len(), 4);
+        assets.shutdown(Duration::from_secs(1));
+    }
+
+    #[test]
+    fn switching_mid_preload_drops_the_old_loads() {
+        let mut assets = Assets::new();
+        let mut cache = OutlineCache::new();
+
+        let preload = ScenePreload::start(scene_of(&["pentagon", "square"]), &cache, &mut assets);
+        assert_eq!(preload.cancel(&mut assets), 2);
+        // The cancelled loads never come out of poll, load() would trip on them
+        let mut preload =
+            ScenePreload::start(scene_of(&["square", "triangle"]), &cache, &mut assets);
+        load(&mut preload, &mut assets, &mut cache);
+        evict_unused(&mut cache, &preload.scene);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains_key(&builtin("pentagon")));
+        assert_eq!(
+            assets.list(),
+            [
+                "builtin pentagon: cancelled",
+                "builtin square: cancelled",
+                "builtin square: loaded",
+                "builtin triangle: loaded",
+            ]
+        );
+        // Nothing's left for a later poll to hand out
+        assert_eq!(assets.pending(), 0);
+        assert!(assets.poll().is_empty());
+        assets.shutdown(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,622 @@
+use wgpu::naga;
+
+use crate::error::ForayError;
+use crate::shaders::{DEBUG_BINDING, DEBUG_GROUP, DEBUG_NAME};
+
+// What a shader declares for one @group/@binding, as far as a bind group layout has to
+// agree with it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderBinding {
+    pub group: u32,
+    pub binding: u32,
+    // The variable's name in the shader, for messages
+    pub name: String,
+    pub kind: BindingKind,
+    // Stages of the entry points that use it
+    pub visibility: wgpu::ShaderStages,
+}
+
+// The part of a wgpu::BindingType a shader pins down. Dynamic offsets, minimum sizes and
+// whether a float texture is filterable are up to the layout
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BindingKind {
+    Uniform,
+    Storage {
+        read_only: bool,
+    },
+    Texture {
+        sample: SampleKind,
+        dimension: wgpu::TextureViewDimension,
+        multisampled: bool,
+    },
+    StorageTexture {
+        access: wgpu::StorageTextureAccess,
+        format: Option<wgpu::TextureFormat>,
+        dimension: wgpu::TextureViewDimension,
+    },
+    Sampler {
+        comparison: bool,
+    },
+    // Acceleration structures, which nothing here reflects or checks
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleKind {
+    Float,
+    Depth,
+    Sint,
+    Uint,
+}
+
+impl BindingKind {
+    fn of(ty: &wgpu::BindingType) -> Self {
+        match *ty {
+            wgpu::BindingType::Buffer { ty, .. } => match ty {
+                wgpu::BufferBindingType::Uniform => BindingKind::Uniform,
+                wgpu::BufferBindingType::Storage { read_only } => {
+                    BindingKind::Storage { read_only }
+                }
+            },
+            wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled,
+            } => BindingKind::Texture {
+                sample: match sample_type {
+                    wgpu::TextureSampleType::Float { .. } => SampleKind::Float,
+                    wgpu::TextureSampleType::Depth => SampleKind::Depth,
+                    wgpu::TextureSampleType::Sint => SampleKind::Sint,
+                    wgpu::TextureSampleType::Uint => SampleKind::Uint,
+                },
+                dimension: view_dimension,
+                multisampled,
+            },
+            wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension,
+            } => BindingKind::StorageTexture {
+                access,
+                format: Some(format),
+                dimension: view_dimension,
+            },
+            wgpu::BindingType::Sampler(kind) => BindingKind::Sampler {
+                comparison: kind == wgpu::SamplerBindingType::Comparison,
+            },
+            wgpu::BindingType::AccelerationStructure => BindingKind::Other,
+        }
+    }
+
+    // Whether a layout entry of kind `self` can stand where the shader wants `wanted`.
+    // A storage texture format the reflection couldn't name is taken on trust
+    fn fits(self, wanted: BindingKind) -> bool {
+        match (self, wanted) {
+            (
+                BindingKind::StorageTexture {
+                    access,
+                    format,
+                    dimension,
+                },
+                BindingKind::StorageTexture {
+                    access: wanted_access,
+                    format: wanted_format,
+                    dimension: wanted_dimension,
+                },
+            ) => {
+                access == wanted_access
+                    && dimension == wanted_dimension
+                    && (wanted_format.is_none() || format == wanted_format)
+            }
+            _ => self == wanted,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            BindingKind::Uniform => "a uniform buffer".to_owned(),
+            BindingKind::Storage { read_only: true } => "a read-only storage buffer".to_owned(),
+            BindingKind::Storage { read_only: false } => "a read-write storage buffer".to_owned(),
+            BindingKind::Texture {
+                sample,
+                dimension,
+                multisampled,
+            } => format!(
+                "a {}{sample:?} {dimension:?} texture",
+                if multisampled { "multisampled " } else { "" },
+            ),
+            BindingKind::StorageTexture {
+                access, dimension, ..
+            } => format!("a {access:?} {dimension:?} storage texture"),
+            BindingKind::Sampler { comparison: true } => "a comparison sampler".to_owned(),
+            BindingKind::Sampler { comparison: false } => "a sampler".to_owned(),
+            BindingKind::Other => "an acceleration structure".to_owned(),
+        }
+    }
+}
+
+// A parsed and validated WGSL module, for asking what its entry points bind
+pub struct Reflection {
+    module: naga::Module,
+    info: naga::valid::ModuleInfo,
+}
+
+impl Reflection {
+    // `source` goes through the #include preprocessor first, like shaders::create_module
+    pub fn new(source: &str) -> Result<Self, String> {
+        let module = naga::front::wgsl::parse_str(&crate::shaders::preprocess(source))
+            .map_err(|e| e.message().to_owned())?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|e| e.as_inner().to_string())?;
+        for (_, variable) in module.global_variables.iter() {
+            let reserved = variable.binding.as_ref().is_some_and(|binding| {
+                binding.group == DEBUG_GROUP && binding.binding == DEBUG_BINDING
+            });
+            if reserved && variable.name.as_deref() != Some(DEBUG_NAME) {
+                return Err(format!(
+                    "@group({DEBUG_GROUP}) @binding({DEBUG_BINDING}) is kept for debug_print, `{}` can't have it",
+                    variable.name.as_deref().unwrap_or("?")
+                ));
+            }
+        }
+        Ok(Self { module, info })
+    }
+
+    // new() with the error logged, for callers that can go on without reflection
+    pub fn of(label: &str, source: &str) -> Option<Self> {
+        Self::new(source)
+            .inspect_err(|e| log::warn!("Couldn't reflect \"{label}\", {e}"))
+            .ok()
+    }
+
+    // Names of the `override` declarations, plus the @id of those that have one since
+    // either works as a constants key
+    pub fn overrides(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for (_, declaration) in self.module.overrides.iter() {
+            names.extend(declaration.name.clone());
+            names.extend(declaration.id.map(|id| id.to_string()));
+        }
+        names
+    }
+
+    // Every resource the named entry points use, sorted by group then binding. One used by
+    // several of them shows up once with their stages combined. Unknown entry point names
+    // are skipped, pipeline creation reports those
+    pub fn bindings(&self, entry_points: &[&str]) -> Vec<ShaderBinding> {
+        let mut bindings: Vec<ShaderBinding> = Vec::new();
+        for (index, entry) in self.module.entry_points.iter().enumerate() {
+            if !entry_points.contains(&entry.name.as_str()) {
+                continue;
+            }
+            let stage = match entry.stage {
+                naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+            };
+            let usage = self.info.get_entry_point(index);
+            for (handle, variable) in self.module.global_variables.iter() {
+                let Some(resource) = &variable.binding else {
+                    continue;
+                };
+                if usage[handle].is_empty() {
+                    continue;
+                }
+                match bindings
+                    .iter_mut()
+                    .find(|b| b.group == resource.group && b.binding == resource.binding)
+                {
+                    Some(existing) => existing.visibility |= stage,
+                    None => bindings.push(ShaderBinding {
+                        group: resource.group,
+                        binding: resource.binding,
+                        name: variable.name.clone().unwrap_or_default(),
+                        kind: self.kind(variable),
+                        visibility: stage,
+                    }),
+                }
+            }
+        }
+        bindings.sort_by_key(|b| (b.group, b.binding));
+        bindings
+    }
+
+    fn kind(&self, variable: &naga::GlobalVariable) -> BindingKind {
+        let mut inner = &self.module.types[variable.ty].inner;
+        // Arrays of bindings are checked by their element
+        if let naga::TypeInner::BindingArray { base, .. } = inner {
+            inner = &self.module.types[*base].inner;
+        }
+        match (variable.space, inner) {
+            (naga::AddressSpace::Storage { access }, _) => BindingKind::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            },
+            (_, naga::TypeInner::Sampler { comparison }) => BindingKind::Sampler {
+                comparison: *comparison,
+            },
+            (
+                _,
+                naga::TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                },
+            ) => {
+                let dimension = dimension(*dim, *arrayed);
+                match *class {
+                    naga::ImageClass::Sampled { kind, multi } => BindingKind::Texture {
+                        sample: match kind {
+                            naga::ScalarKind::Sint => SampleKind::Sint,
+                            naga::ScalarKind::Uint => SampleKind::Uint,
+                            _ => SampleKind::Float,
+                        },
+                        dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Depth { multi } => BindingKind::Texture {
+                        sample: SampleKind::Depth,
+                        dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Storage { format, access } => BindingKind::StorageTexture {
+                        access: match (
+                            access.contains(naga::StorageAccess::LOAD),
+                            access.contains(naga::StorageAccess::STORE),
+                        ) {
+                            (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                            (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                            _ => wgpu::StorageTextureAccess::WriteOnly,
+                        },
+                        format: storage_format(format),
+                        dimension,
+                    },
+                }
+            }
+            _ => BindingKind::Uniform,
+        }
+    }
+}
+
+fn dimension(dim: naga::ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+// The storage formats the shaders here might use, None for the rest
+fn storage_format(format: naga::StorageFormat) -> Option<wgpu::TextureFormat> {
+    match format {
+        naga::StorageFormat::R32Float => Some(wgpu::TextureFormat::R32Float),
+        naga::StorageFormat::R32Uint => Some(wgpu::TextureFormat::R32Uint),
+        naga::StorageFormat::R32Sint => Some(wgpu::TextureFormat::R32Sint),
+        naga::StorageFormat::Rgba8Unorm => Some(wgpu::TextureFormat::Rgba8Unorm),
+        naga::StorageFormat::Rgba16Float => Some(wgpu::TextureFormat::Rgba16Float),
+        naga::StorageFormat::Rgba32Float => Some(wgpu::TextureFormat::Rgba32Float),
+        _ => None,
+    }
+}
+
+// Checks a bind group layout's entries against what `pipeline`'s shader wants in `group`.
+// Entries the shader doesn't use are fine, a missing or different one is an error naming
+// the binding
+pub fn check(
+    pipeline: &str,
+    group: u32,
+    wanted: &[ShaderBinding],
+    provided: &[wgpu::BindGroupLayoutEntry],
+) -> Result<(), ForayError> {
+    for binding in wanted.iter().filter(|b| b.group == group) {
+        let mismatch = |reason: String| ForayError::BindingMismatch {
+            pipeline: pipeline.to_owned(),
+            group,
+            binding: binding.binding,
+            reason,
+        };
+        let Some(entry) = provided.iter().find(|e| e.binding == binding.binding) else {
+            return Err(mismatch(format!(
+                "the shader's `{}` isn't in the layout",
+                binding.name
+            )));
+        };
+        let kind = BindingKind::of(&entry.ty);
+        if !kind.fits(binding.kind) {
+            return Err(mismatch(format!(
+                "the shader's `{}` is {} but the layout has {}",
+                binding.name,
+                binding.kind.describe(),
+                kind.describe(),
+            )));
+        }
+        if !entry.visibility.contains(binding.visibility) {
+            return Err(mismatch(format!(
+                "the shader's `{}` is used in {:?} but the layout only shows it to {:?}",
+                binding.name, binding.visibility, entry.visibility,
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Layout entries for `group` straight from the shader, so the two can't drift apart. Float
+// textures come out filterable only if the group also has a sampler that filters, anything
+// read with textureLoad works with every float format that way. Err for a storage texture
+// whose format reflection can't name
+pub fn layout_entries(
+    bindings: &[ShaderBinding],
+    group: u32,
+) -> Result<Vec<wgpu::BindGroupLayoutEntry>, String> {
+    let in_group: Vec<_> = bindings.iter().filter(|b| b.group == group).collect();
+    let filtering = in_group
+        .iter()
+        .any(|b| b.kind == BindingKind::Sampler { comparison: false });
+    in_group
+        .iter()
+        .map(|b| {
+            let ty = match b.kind {
+                BindingKind::Uniform => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                BindingKind::Storage { read_only } => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                BindingKind::Texture {
+                    sample,
+                    dimension,
+                    multisampled,
+                } => wgpu::BindingType::Texture {
+                    sample_type: match sample {
+                        SampleKind::Float => wgpu::TextureSampleType::Float {
+                            filterable: filtering,
+                        },
+                        SampleKind::Depth => wgpu::TextureSampleType::Depth,
+                        SampleKind::Sint => wgpu::TextureSampleType::Sint,
+                        SampleKind::Uint => wgpu::TextureSampleType::Uint,
+                    },
+                    view_dimension: dimension,
+                    multisampled,
+                },
+                BindingKind::StorageTexture {
+                    access,
+                    format,
+                    dimension,
+                } => wgpu::BindingType::StorageTexture {
+                    access,
+                    format: format.ok_or_else(|| {
+                        format!("`{}` has a storage format this can't name", b.name)
+                    })?,
+                    view_dimension: dimension,
+                },
+                BindingKind::Sampler { comparison: true } => {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+                }
+                BindingKind::Sampler { comparison: false } => {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+                }
+                BindingKind::Other => return Err(format!("`{}` can't be reflected", b.name)),
+            };
+            Ok(wgpu::BindGroupLayoutEntry {
+                binding: b.binding,
+                visibility: b.visibility,
+                ty,
+                count: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A camera uniform for the vertex stage, a texture and sampler pair for the fragment
+    // stage and a storage buffer nothing reads, which shouldn't be asked for
+    const SHADER: &str = r"
+struct Camera { view_proj: mat4x4<f32> }
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var albedo: texture_2d<f32>;
+@group(1) @binding(1) var albedo_sampler: sampler;
+@group(1) @binding(2) var<storage, read> unused: array<f32>;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) at: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureSample(albedo, albedo_sampler, at.xy / 64.0);
+}
+";
+
+    fn entry(
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        ty: wgpu::BindingType,
+    ) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty,
+            count: None,
+        }
+    }
+
+    fn uniform() -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+
+    fn texture() -> wgpu::BindingType {
+        wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        }
+    }
+
+    fn sampler() -> wgpu::BindingType {
+        wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+    }
+
+    // What the material pipeline gets checked with, the shader's message and all
+    fn check_message(group: u32, provided: &[wgpu::BindGroupLayoutEntry]) -> Option<String> {
+        let reflection = Reflection::new(SHADER).unwrap();
+        let wanted = reflection.bindings(&["vs_main", "fs_main"]);
+        check("textured", group, &wanted, provided)
+            .err()
+            .map(|e| e.to_string())
+    }
+
+    #[test]
+    fn bindings_follow_the_entry_points_that_use_them() {
+        let reflection = Reflection::new(SHADER).unwrap();
+        let both = reflection.bindings(&["vs_main", "fs_main"]);
+        let found: Vec<_> = both
+            .iter()
+            .map(|b| (b.group, b.binding, b.name.as_str(), b.visibility))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (0, 0, "camera", wgpu::ShaderStages::VERTEX),
+                (1, 0, "albedo", wgpu::ShaderStages::FRAGMENT),
+                (1, 1, "albedo_sampler", wgpu::ShaderStages::FRAGMENT),
+            ]
+        );
+        assert_eq!(both[0].kind, BindingKind::Uniform);
+        assert_eq!(
+            both[1].kind,
+            BindingKind::Texture {
+                sample: SampleKind::Float,
+                dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            }
+        );
+        assert_eq!(both[2].kind, BindingKind::Sampler { comparison: false });
+        // Only the vertex stage, or an entry point that isn't there
+        assert_eq!(reflection.bindings(&["vs_main"]).len(), 1);
+        assert!(reflection.bindings(&["vs_missing"]).is_empty());
+    }
+
+    #[test]
+    fn matching_layouts_pass() {
+        let fragment = wgpu::ShaderStages::FRAGMENT;
+        assert_eq!(
+            check_message(0, &[entry(0, wgpu::ShaderStages::VERTEX, uniform())]),
+            None
+        );
+        // Extra entries and wider visibility are the layout's business
+        assert_eq!(
+            check_message(
+                1,
+                &[
+                    entry(1, wgpu::ShaderStages::VERTEX_FRAGMENT, sampler()),
+                    entry(0, fragment, texture()),
+                    entry(7, fragment, uniform()),
+                ]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_sampler_is_named() {
+        assert_eq!(
+            check_message(1, &[entry(0, wgpu::ShaderStages::FRAGMENT, texture())]).as_deref(),
+            Some(
+                "Pipeline \"textured\" @group(1) @binding(1): the shader's `albedo_sampler` isn't in the layout"
+            )
+        );
+    }
+
+    #[test]
+    fn wrong_group_index_is_a_type_mismatch() {
+        // The camera's layout handed over for the material group
+        assert_eq!(
+            check_message(1, &[entry(0, wgpu::ShaderStages::all(), uniform())]).as_deref(),
+            Some(
+                "Pipeline \"textured\" @group(1) @binding(0): the shader's `albedo` is a Float D2 texture but the layout has a uniform buffer"
+            )
+        );
+    }
+
+    #[test]
+    fn mistyped_bindings_are_described() {
+        let fragment = wgpu::ShaderStages::FRAGMENT;
+        let depth = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        };
+        assert_eq!(
+            check_message(1, &[entry(0, fragment, depth), entry(1, fragment, sampler())])
+                .as_deref(),
+            Some(
+                "Pipeline \"textured\" @group(1) @binding(0): the shader's `albedo` is a Float D2 texture but the layout has a Depth D2 texture"
+            )
+        );
+        let comparison = wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison);
+        assert_eq!(
+            check_message(
+                1,
+                &[entry(0, fragment, texture()), entry(1, fragment, comparison)]
+            )
+            .as_deref(),
+            Some(
+                "Pipeline \"textured\" @group(1) @binding(1): the shader's `albedo_sampler` is a sampler but the layout has a comparison sampler"
+            )
+        );
+    }
+
+    #[test]
+    fn hidden_stages_are_named() {
+        assert_eq!(
+            check_message(0, &[entry(0, wgpu::ShaderStages::FRAGMENT, uniform())]).as_deref(),
+            Some(
+                "Pipeline \"textured\" @group(0) @binding(0): the shader's `camera` is used in ShaderStages(VERTEX) but the layout only shows it to ShaderStages(FRAGMENT)"
+            )
+        );
+    }
+
+    #[test]
+    fn generated_layouts_pass_their_own_check() {
+        let reflection = Reflection::new(SHADER).unwrap();
+        let wanted = reflection.bindings(&["vs_main", "fs_main"]);
+        for group in 0..2 {
+            let entries = layout_entries(&wanted, group).unwrap();
+            assert!(check("textured", group, &wanted, &entries).is_ok());
+        }
+        // The filtering sampler makes the texture filterable
+        let material = layout_entries(&wanted, 1).unwrap();
+        assert_eq!(material[0].ty, texture());
+        assert_eq!(material[1].ty, sampler());
+    }
+
+    #[test]
+    fn the_debug_slot_is_kept_for_debug_print() {
+        let error = Reflection::new(
+            "@group(0) @binding(15) var<uniform> mine: vec4<f32>;
+             @fragment fn fs_main() -> @location(0) vec4<f32> { return mine; }",
+        )
+        .err()
+        .unwrap();
+        assert!(error.contains("`mine` can't have it"), "{error}");
+        assert!(Reflection::new("fn broken( {").is_err());
+    }
+}
@@ -0,0 +1,515 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ForayError;
+
+pub const DEFAULT_PORT: u16 = 7878;
+// The token for --remote, the only way to listen anywhere but localhost
+pub const TOKEN_VARIABLE: &str = "FORAY_REMOTE_TOKEN";
+// A command the main loop hasn't answered by then (a screenshot while minimized, say) is
+// answered with an error so the client isn't left hanging
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// One line from a client. `id` is anything, handed back as it came so scripts can match
+// answers to commands
+#[derive(Deserialize)]
+struct Request {
+    command: String,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    // A screenshot comes back as the PNG itself, in base64, besides where it was saved
+    #[serde(default)]
+    inline: bool,
+}
+
+// One line back per request
+#[derive(Serialize)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<serde_json::Value>,
+    ok: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    output: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    png: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Response {
+    fn failed(id: Option<serde_json::Value>, error: String) -> Self {
+        Self {
+            id,
+            ok: false,
+            output: Vec::new(),
+            path: None,
+            png: None,
+            error: Some(error),
+        }
+    }
+}
+
+// What a command that worked answers with. Stats are lines of output, a screenshot is
+// where it was saved and, asked for inline, the file in base64
+#[derive(Clone, Debug, Default)]
+pub struct Answer {
+    pub output: Vec<String>,
+    pub path: Option<PathBuf>,
+    pub png: Option<String>,
+}
+
+impl Answer {
+    // The answer to a `screenshot` that was saved to `path`
+    pub fn screenshot(path: PathBuf, inline: bool) -> Result<Self, String> {
+        let png = if inline {
+            let bytes = std::fs::read(&path)
+                .map_err(|e| format!("can't read back {}, {e}", path.display()))?;
+            Some(base64::engine::general_purpose::STANDARD.encode(bytes))
+        } else {
+            None
+        };
+        Ok(Self {
+            output: Vec::new(),
+            path: Some(path),
+            png,
+        })
+    }
+}
+
+// A command from a client, run by the main loop between frames
+pub struct RemoteCommand {
+    pub command: String,
+    // Asked for a screenshot's PNG in the answer, see Answer::screenshot
+    pub inline: bool,
+    reply: mpsc::Sender<Result<Answer, String>>,
+}
+
+impl RemoteCommand {
+    // The client gets `ok: false` and the error for Err. A client that went away is fine
+    pub fn answer(self, result: Result<Answer, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+// --remote: newline-delimited JSON over TCP, each line a console command. A thread per
+// client hands commands over a channel and waits for the answer, so they run on the main
+// thread and never while a frame is drawn. The listener thread is left behind at exit
+//
+//   {"command": "scene stress", "id": 1}  ->  {"id":1,"ok":true}
+//   {"command": "stats"}                  ->  {"ok":true,"output":["...", ...]}
+//   {"command": "screenshot"}             ->  {"ok":true,"path":"screenshot-....png"}
+//   {"command": "screenshot", "inline": true}
+//                                         ->  {"ok":true,"path":"...","png":"iVBORw0KGgo..."}
+//   {"command": "scene nope"}             ->  {"ok":false,"error":"No built-in scene ..."}
+pub struct Remote {
+    commands: mpsc::Receiver<RemoteCommand>,
+    address: SocketAddr,
+}
+
+impl Remote {
+    // Anywhere but a loopback address needs a `token`, which every request then has to
+    // carry. An empty one is refused, it would let in anyone who sends an empty token.
+    // `wake` is called after each command is handed over, for a main loop that
+    // sleeps until there are events
+    pub fn start(
+        bind: IpAddr,
+        port: u16,
+        token: Option<String>,
+        wake: impl Fn() + Send + Sync + 'static,
+    ) -> Result<Self, ForayError> {
+        let error = |reason: String| ForayError::Remote { reason };
+        if token
+            .as_deref()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            return Err(error(format!("{TOKEN_VARIABLE} is set but empty")));
+        }
+        if !bind.is_loopback() && token.is_none() {
+            return Err(error(format!(
+                "won't listen on {bind} without a token, set {TOKEN_VARIABLE} or stay on localhost"
+            )));
+        }
+        let listener = TcpListener::bind((bind, port))
+            .map_err(|e| error(format!("can't listen on {bind}:{port}, {e}")))?;
+        let address = listener.local_addr().map_err(|e| error(e.to_string()))?;
+        let (sender, commands) = mpsc::channel();
+        let token = Arc::new(token);
+        let wake: Arc<dyn Fn() + Send + Sync> = Arc::new(wake);
+        std::thread::Builder::new()
+            .name("Remote Listener".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            log::warn!("Remote: a client couldn't connect, {e}");
+                            continue;
+                        }
+                    };
+                    let (sender, token, wake) =
+                        (sender.clone(), Arc::clone(&token), Arc::clone(&wake));
+                    let spawned = std::thread::Builder::new()
+                        .name("Remote Client".to_owned())
+                        .spawn(move || serve(stream, &sender, token.as_deref(), &*wake));
+                    if let Err(e) = spawned {
+                        log::warn!("Remote: no thread for a client, {e}");
+                    }
+                }
+            })
+            .map_err(|e| error(e.to_string()))?;
+        Ok(Self { commands, address })
+    }
+
+    // Where it ended up listening, the port is picked by the OS when asked for 0
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // Whatever came in since the last call, oldest first
+    pub fn take(&self) -> Vec<RemoteCommand> {
+        self.commands.try_iter().collect()
+    }
+
+    // take for a loop with nothing else to do, waiting up to `timeout` for the first one
+    pub fn wait(&self, timeout: Duration) -> Vec<RemoteCommand> {
+        match self.commands.recv_timeout(timeout) {
+            Ok(first) => std::iter::once(first).chain(self.take()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// One client, until it disconnects
+fn serve(
+    stream: TcpStream,
+    sender: &mpsc::Sender<RemoteCommand>,
+    token: Option<&str>,
+    wake: &dyn Fn(),
+) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "a client".to_owned(), |peer| peer.to_string());
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            log::warn!("Remote: dropping {peer}, {e}");
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = respond(&line, sender, token, wake);
+        let written = serde_json::to_writer(&mut writer, &response)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"));
+        if written.is_err() {
+            break;
+        }
+    }
+}
+
+fn respond(
+    line: &str,
+    sender: &mpsc::Sender<RemoteCommand>,
+    token: Option<&str>,
+    wake: &dyn Fn(),
+) -> Response {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Response::failed(None, format!("not a command, {e}")),
+    };
+    if token.is_some() && request.token.as_deref() != token {
+        return Response::failed(request.id, "wrong or missing token".to_owned());
+    }
+    let (reply, answer) = mpsc::channel();
+    let command = RemoteCommand {
+        command: request.command,
+        inline: request.inline,
+        reply,
+    };
+    if sender.send(command).is_err() {
+        return Response::failed(request.id, "the app is shutting down".to_owned());
+    }
+    wake();
+    match answer.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(answer)) => Response {
+            id: request.id,
+            ok: true,
+            output: answer.output,
+            path: answer.path,
+            png: answer.png,
+            error: None,
+        },
+        Ok(Err(error)) => Response::failed(request.id, error),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            Response::failed(request.id, format!("no answer in {REPLY_TIMEOUT:?}"))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Response::failed(request.id, "dropped without an answer".to_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    // A listener on a free port and how many times it has woken the main loop
+    fn listen(token: Option<&str>) -> (Remote, Arc<AtomicUsize>) {
+        let woken = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&woken);
+        let wake = move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        };
+        let remote =
+            Remote::start(LOCALHOST, 0, token.map(str::to_owned), wake).expect("Can't listen");
+        (remote, woken)
+    }
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(remote: &Remote) -> Self {
+            let writer = TcpStream::connect(remote.address()).expect("Can't connect");
+            writer
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            Self {
+                reader: BufReader::new(writer.try_clone().unwrap()),
+                writer,
+            }
+        }
+
+        fn send(&mut self, line: &str) {
+            self.writer.write_all(line.as_bytes()).unwrap();
+            self.writer.write_all(b"\n").unwrap();
+        }
+
+        fn receive(&mut self) -> Value {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).expect("No answer");
+            serde_json::from_str(&line).expect("Answer isn't JSON")
+        }
+    }
+
+    // What the main loop does between frames, for the one command the client is waiting on
+    fn next_command(remote: &Remote) -> RemoteCommand {
+        let started = Instant::now();
+        loop {
+            let mut commands = remote.take();
+            if let Some(command) = commands.pop() {
+                assert!(commands.is_empty(), "More than one command came in");
+                return command;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "No command came in"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Sends `line`, answers the command it turns into with `answer` and returns the reply
+    fn round_trip(
+        remote: &Remote,
+        client: &mut Client,
+        line: &str,
+        answer: impl FnOnce(&str) -> Result<Answer, String>,
+    ) -> Value {
+        client.send(line);
+        let command = next_command(remote);
+        let result = answer(&command.command);
+        command.answer(result);
+        client.receive()
+    }
+
+    #[test]
+    fn a_scripted_session_gets_an_answer_per_command() {
+        let (remote, woken) = listen(None);
+        let mut client = Client::connect(&remote);
+
+        let reply = round_trip(
+            &remote,
+            &mut client,
+            r#"{"command": "scene stress", "id": 1}"#,
+            |command| {
+                assert_eq!(command, "scene stress");
+                Ok(Answer::default())
+            },
+        );
+        assert_eq!(reply, json!({"id": 1, "ok": true}));
+
+        let stats = || Answer {
+            output: vec!["fps 60.0".to_owned(), "draws 12".to_owned()],
+            ..Answer::default()
+        };
+        let reply = round_trip(
+            &remote,
+            &mut client,
+            r#"{"command": "stats", "id": "s"}"#,
+            |_| Ok(stats()),
+        );
+        assert_eq!(
+            reply,
+            json!({"id": "s", "ok": true, "output": ["fps 60.0", "draws 12"]})
+        );
+
+        let shot = || Answer {
+            path: Some(PathBuf::from("shots/one.png")),
+            ..Answer::default()
+        };
+        let reply = round_trip(
+            &remote,
+            &mut client,
+            r#"{"command": "screenshot shots/one.png"}"#,
+            |_| Ok(shot()),
+        );
+        assert_eq!(reply, json!({"ok": true, "path": "shots/one.png"}));
+
+        let reply = round_trip(
+            &remote,
+            &mut client,
+            r#"{"command": "scene nope", "id": [2]}"#,
+            |_| Err("No built-in scene nope".to_owned()),
+        );
+        assert_eq!(
+            reply,
+            json!({"id": [2], "ok": false, "error": "No built-in scene nope"})
+        );
+
+        assert_eq!(woken.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn bad_lines_are_answered_without_reaching_the_main_loop() {
+        let (remote, woken) = listen(None);
+        let mut client = Client::connect(&remote);
+        // Blank lines are skipped, no answer for them
+        client.send("");
+        client.send("   ");
+        client.send("scene stress");
+        let reply = client.receive();
+        assert_eq!(reply["ok"], json!(false));
+        assert!(reply["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("not a command"));
+        client.send(r#"{"id": 3}"#);
+        assert_eq!(client.receive()["ok"], json!(false));
+        assert!(remote.take().is_empty());
+        assert_eq!(woken.load(Ordering::SeqCst), 0);
+
+        // And the connection is still good for real ones
+        let reply = round_trip(&remote, &mut client, r#"{"command": "stats"}"#, |_| {
+            Ok(Answer::default())
+        });
+        assert_eq!(reply, json!({"ok": true}));
+    }
+
+    #[test]
+    fn commands_from_several_clients_each_get_their_own_answer() {
+        let (remote, _) = listen(None);
+        let mut clients: Vec<_> = (0..3).map(|_| Client::connect(&remote)).collect();
+        for (i, client) in clients.iter_mut().enumerate() {
+            client.send(&format!(r#"{{"command": "echo {i}", "id": {i}}}"#));
+        }
+        let mut answered = 0;
+        let started = Instant::now();
+        while answered < clients.len() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "Commands went missing"
+            );
+            for command in remote.take() {
+                let echoed = command.command.trim_start_matches("echo ").to_owned();
+                command.answer(Ok(Answer {
+                    output: vec![echoed],
+                    ..Answer::default()
+                }));
+                answered += 1;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        for (i, client) in clients.iter_mut().enumerate() {
+            assert_eq!(
+                client.receive(),
+                json!({"id": i, "ok": true, "output": [i.to_string()]})
+            );
+        }
+    }
+
+    #[test]
+    fn a_token_has_to_come_with_every_request() {
+        let (remote, _) = listen(Some("hunter2"));
+        let mut client = Client::connect(&remote);
+        for line in [
+            r#"{"command": "stats", "id": 1}"#,
+            r#"{"command": "stats", "id": 1, "token": ""}"#,
+            r#"{"command": "stats", "id": 1, "token": "hunter3"}"#,
+        ] {
+            client.send(line);
+            assert_eq!(
+                client.receive(),
+                json!({"id": 1, "ok": false, "error": "wrong or missing token"}),
+                "{line}"
+            );
+        }
+        assert!(remote.take().is_empty());
+        let line = r#"{"command": "stats", "token": "hunter2"}"#;
+        let reply = round_trip(&remote, &mut client, line, |_| Ok(Answer::default()));
+        assert_eq!(reply, json!({"ok": true}));
+    }
+
+    #[test]
+    fn refuses_to_listen_without_a_usable_token() {
+        let anywhere = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let refused = |bind, token: Option<&str>| {
+            let started = Remote::start(bind, 0, token.map(str::to_owned), || {});
+            matches!(started, Err(ForayError::Remote { .. }))
+        };
+        assert!(refused(anywhere, None));
+        assert!(refused(anywhere, Some("")));
+        assert!(refused(anywhere, Some("  ")));
+        assert!(refused(LOCALHOST, Some("")));
+        assert!(!refused(LOCALHOST, None));
+    }
+
+    #[test]
+    fn a_command_nobody_answers_fails_instead_of_hanging() {
+        let (remote, _) = listen(None);
+        let mut client = Client::connect(&remote);
+        client.send(r#"{"command": "screenshot", "id": 9}"#);
+        // Dropped without an answer, the way a main loop shutting down would
+        drop(next_command(&remote));
+        assert_eq!(
+            client.receive(),
+            json!({"id": 9, "ok": false, "error": "dropped without an answer"})
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::BTreeSet;
+
+use crate::error::ForayError;
+use crate::frame::{Background, ColorTarget, Frame};
+use crate::targets::TargetHandle;
+
+// What a pass reads or writes, the swapchain or an offscreen target (color or depth)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resource {
+    Swapchain,
+    Target(TargetHandle),
+}
+
+impl From<ColorTarget> for Resource {
+    fn from(target: ColorTarget) -> Self {
+        match target {
+            ColorTarget::Swapchain | ColorTarget::SwapchainUi => Resource::Swapchain,
+            ColorTarget::Offscreen(handle) => Resource::Target(handle),
+        }
+    }
+}
+
+impl From<TargetHandle> for Resource {
+    fn from(handle: TargetHandle) -> Self {
+        Resource::Target(handle)
+    }
+}
+
+// Handed to each pass as it's recorded: which of its writes start the resource this frame
+pub struct PassLoads {
+    cleared: Vec<Resource>,
+}
+
+impl PassLoads {
+    // The first pass to write `resource` starts it from scratch, later ones draw on top
+    pub fn clears(&self, resource: impl Into<Resource>) -> bool {
+        self.cleared.contains(&resource.into())
+    }
+
+    // For a color attachment, the background's op when clearing and Load otherwise
+    pub fn color(
+        &self,
+        resource: impl Into<Resource>,
+        background: Background,
+    ) -> wgpu::LoadOp<wgpu::Color> {
+        if self.clears(resource) {
+            background.color()
+        } else {
+            wgpu::LoadOp::Load
+        }
+    }
+
+    // Same for attachments with their own clear value, see Background::load
+    pub fn load<V>(
+        &self,
+        resource: impl Into<Resource>,
+        background: Background,
+        cleared: V,
+        debug: V,
+    ) -> wgpu::LoadOp<V> {
+        if self.clears(resource) {
+            background.load(cleared, debug)
+        } else {
+            wgpu::LoadOp::Load
+        }
+    }
+}
+
+type Record<'g> = Box<dyn FnOnce(&mut Frame, &PassLoads) -> Result<(), ForayError> + 'g>;
+
+struct GraphPass<'g> {
+    name: String,
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+    record: Record<'g>,
+}
+
+impl GraphPass<'_> {
+    // Writes it without reading, so it starts the resource over
+    fn produces(&self, resource: Resource) -> bool {
+        self.writes.contains(&resource) && !self.reads.contains(&resource)
+    }
+
+    // Reads and writes, drawing on top of what's there
+    fn modifies(&self, resource: Resource) -> bool {
+        self.writes.contains(&resource) && self.reads.contains(&resource)
+    }
+
+    fn loads(&self) -> PassLoads {
+        PassLoads {
+            cleared: self
+                .writes
+                .iter()
+                .copied()
+                .filter(|&resource| self.produces(resource))
+                .collect(),
+        }
+    }
+}
+
+// One frame's passes, recorded in the order their reads and writes call for rather than the
+// order they were added. Per resource there's at most one pass that writes it from scratch,
+// then the ones that draw on top of it (they read it too) in the order they were added, then
+// the ones that only read it. Passes nothing in `outputs` depends on aren't recorded at all
+pub struct RenderGraph<'g> {
+    passes: Vec<GraphPass<'g>>,
+    outputs: Vec<Resource>,
+}
+
+impl<'g> RenderGraph<'g> {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    // A pass that writes something it doesn't list in `reads` clears it, so it has to be
+    // the only one
+    pub fn add_pass(
+        &mut self,
+        name: &str,
+        reads: &[Resource],
+        writes: &[Resource],
+        record: impl FnOnce(&mut Frame, &PassLoads) -> Result<(), ForayError> + 'g,
+    ) -> &mut Self {
+        self.passes.push(GraphPass {
+            name: name.to_owned(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            record: Box::new(record),
+        });
+        self
+    }
+
+    // What the frame is for, usually the swapchain. Passes that don't lead here get culled
+    pub fn output(&mut self, resource: impl Into<Resource>) -> &mut Self {
+        self.outputs.push(resource.into());
+        self
+    }
+
+    // Records the passes that contribute to the outputs, in dependency order. Err for two
+    // passes clearing the same resource or passes that wait on each other, before anything
+    // gets recorded
+    pub fn execute(self, frame: &mut Frame) -> Result<(), ForayError> {
+        let order = self.schedule()?;
+        let mut passes: Vec<Option<GraphPass>> = self.passes.into_iter().map(Some).collect();
+        for index in order {
+            let pass = passes[index].take().expect("Scheduled once");
+            let loads = pass.loads();
+            let _span = tracing::info_span!("graph pass", name = pass.name.as_str()).entered();
+            (pass.record)(frame, &loads)?;
+        }
+        for pass in passes.into_iter().flatten() {
+            log::debug!("Render graph: culled \"{}\"", pass.name);
+        }
+        Ok(())
+    }
+
+    // Indices of the passes to record, in order
+    fn schedule(&self) -> Result<Vec<usize>, ForayError> {
+        let count = self.passes.len();
+        // before[pass]: the passes it has to wait for
+        let mut before: Vec<Vec<usize>> = vec![Vec::new(); count];
+        let mut resources: Vec<Resource> = Vec::new();
+        for pass in &self.passes {
+            for &resource in pass.reads.iter().chain(&pass.writes) {
+                if !resources.contains(&resource) {
+                    resources.push(resource);
+                }
+            }
+        }
+        for resource in resources {
+            let producers: Vec<usize> = (0..count)
+                .filter(|&i| self.passes[i].produces(resource))
+                .collect();
+            if let [first, second, ..] = producers[..] {
+                return Err(ForayError::RenderGraph(format!(
+                    "\"{}\" and \"{}\" both clear {resource:?}, the second one has to read it to draw on top",
+                    self.passes[first].name, self.passes[second].name
+                )));
+            }
+            let chain: Vec<usize> = producers
+                .into_iter()
+                .chain((0..count).filter(|&i| self.passes[i].modifies(resource)))
+                .collect();
+            for pair in chain.windows(2) {
+                before[pair[1]].push(pair[0]);
+            }
+            if let Some(&last) = chain.last() {
+                let readers = (0..count).filter(|&i| {
+                    self.passes[i].reads.contains(&resource)
+                        && !self.passes[i].writes.contains(&resource)
+                });
+                for reader in readers {
+                    before[reader].push(last);
+                }
+            }
+        }
+
+        // Everything an output's writers wait on, transitively
+        let mut live = vec![false; count];
+        let mut pending: Vec<usize> = (0..count)
+            .filter(|&i| {
+                self.passes[i]
+                    .writes
+                    .iter()
+                    .any(|resource| self.outputs.contains(resource))
+            })
+            .collect();
+        while let Some(index) = pending.pop() {
+            if !live[index] {
+                live[index] = true;
+                pending.extend(&before[index]);
+            }
+        }
+
+        // Kahn's, taking the earliest added of the passes that are ready so unrelated passes
+        // keep the order they were added in
+        let mut waiting: Vec<usize> = before.iter().map(Vec::len).collect();
+        let mut ready: BTreeSet<usize> = (0..count).filter(|&i| waiting[i] == 0).collect();
+        let mut order = Vec::with_capacity(count);
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+            for (next, waits_on) in before.iter().enumerate() {
+                for _ in waits_on.iter().filter(|&&i| i == index) {
+                    waiting[next] -= 1;
+                    if waiting[next] == 0 {
+                        ready.insert(next);
+                    }
+                }
+            }
+        }
+        if order.len() < count {
+            let stuck: Vec<String> = (0..count)
+                .filter(|i| !order.contains(i))
+                .map(|i| format!("\"{}\"", self.passes[i].name))
+                .collect();
+            return Err(ForayError::RenderGraph(format!(
+                "{} wait on each other",
+                stuck.join(", ")
+            )));
+        }
+        order.retain(|&index| live[index]);
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(index: usize) -> Resource {
+        Resource::Target(TargetHandle::unregistered(index))
+    }
+
+    impl RenderGraph<'_> {
+        fn pass(&mut self, name: &str, reads: &[Resource], writes: &[Resource]) -> &mut Self {
+            self.add_pass(name, reads, writes, |_, _| Ok(()))
+        }
+
+        // The passes execute would record, in order, with what each of them clears
+        fn planned(&self) -> Result<Vec<(&str, Vec<Resource>)>, ForayError> {
+            Ok(self
+                .schedule()?
+                .into_iter()
+                .map(|index| {
+                    let pass = &self.passes[index];
+                    (pass.name.as_str(), pass.loads().cleared)
+                })
+                .collect())
+        }
+
+        fn order(&self) -> Vec<&str> {
+            let planned = self.planned().expect("Couldn't schedule");
+            planned.into_iter().map(|(name, _)| name).collect()
+        }
+    }
+
+    const SWAPCHAIN: Resource = Resource::Swapchain;
+
+    #[test]
+    fn passes_go_after_what_they_read() {
+        let (scene, bloom) = (target(0), target(1));
+        let mut graph = RenderGraph::new();
+        graph
+            .pass("Composite", &[scene, bloom], &[SWAPCHAIN])
+            .pass("UI", &[SWAPCHAIN], &[SWAPCHAIN])
+            .pass("Bloom", &[scene], &[bloom])
+            .pass("Scene", &[], &[scene])
+            .output(SWAPCHAIN);
+        assert_eq!(graph.order(), ["Scene", "Bloom", "Composite", "UI"]);
+    }
+
+    #[test]
+    fn passes_that_draw_on_top_keep_the_order_they_were_added_in() {
+        let mut graph = RenderGraph::new();
+        graph
+            .pass("Text", &[SWAPCHAIN], &[SWAPCHAIN])
+            .pass("Overlay", &[SWAPCHAIN], &[SWAPCHAIN])
+            .pass("View", &[], &[SWAPCHAIN])
+            .output(SWAPCHAIN);
+        assert_eq!(graph.order(), ["View", "Text", "Overlay"]);
+    }
+
+    #[test]
+    fn passes_nothing_reads_are_culled() {
+        let (picking, inset) = (target(0), target(1));
+        let mut graph = RenderGraph::new();
+        graph
+            .pass("View", &[], &[SWAPCHAIN])
+            // No click this frame, so nothing reads the ids back
+            .pass("Picking", &[], &[picking])
+            // Hidden, so its view isn't composited
+            .pass("Inset View", &[], &[inset])
+            .pass("Inset Outline", &[inset], &[inset])
+            .pass("UI", &[SWAPCHAIN], &[SWAPCHAIN])
+            .output(SWAPCHAIN);
+        assert_eq!(graph.order(), ["View", "UI"]);
+
+        graph.pass("Inset", &[SWAPCHAIN, inset], &[SWAPCHAIN]);
+        assert_eq!(
+            graph.order(),
+            ["View", "Inset View", "Inset Outline", "UI", "Inset"]
+        );
+    }
+
+    #[test]
+    fn the_first_pass_to_write_a_target_clears_it_and_the_rest_load() {
+        let scene = target(0);
+        let mut graph = RenderGraph::new();
+        graph
+            .pass("Scene", &[], &[scene])
+            .pass("Particles", &[scene], &[scene])
+            .pass("Post", &[scene], &[SWAPCHAIN])
+            .pass("UI", &[SWAPCHAIN], &[SWAPCHAIN])
+            .output(SWAPCHAIN);
+        assert_eq!(
+            graph.planned().unwrap(),
+            [
+                ("Scene", vec![scene]),
+                ("Particles", vec![]),
+                ("Post", vec![SWAPCHAIN]),
+                ("UI", vec![]),
+            ]
+        );
+        let loads = graph.passes[0].loads();
+        assert!(loads.clears(scene) && !loads.clears(SWAPCHAIN));
+        let background = Background::Clear(wgpu::Color::RED);
+        assert_eq!(loads.color(scene, background), background.color());
+        let loads = graph.passes[1].loads();
+        assert_eq!(loads.color(scene, background), wgpu::LoadOp::Load);
+        assert_eq!(loads.load(scene, background, 0.0, 1.0), wgpu::LoadOp::Load);
+    }
+
+    #[test]
+    fn two_passes_clearing_a_target_is_an_error_naming_them() {
+        let mut graph = RenderGraph::new();
+        graph
+            .pass("View", &[], &[SWAPCHAIN])
+            .pass("Overlay", &[], &[SWAPCHAIN])
+            .output(SWAPCHAIN);
+        let message = graph.planned().unwrap_err().to_string();
+        assert!(
+            message.contains("\"View\" and \"Overlay\" both clear"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn passes_waiting_on_each_other_is_an_error_naming_them() {
+        let (scene, shadows) = (target(0), target(1));
+        let mut graph = RenderGraph::new();
+        graph
+            .pass("Scene", &[], &[scene])
+            // Draws on the scene before the shadow pass does, but needs its shadows
+            .pass("Lighting", &[scene, shadows], &[scene])
+            .pass("Shadows", &[scene], &[scene, shadows])
+            .pass("Unrelated", &[], &[SWAPCHAIN])
+            .output(scene);
+        let message = graph.planned().unwrap_err().to_string();
+        assert!(
+            message.contains("\"Lighting\", \"Shadows\" wait on each other"),
+            "{message}"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use crate::error::ForayError;
+
+// Picks one limit out of wgpu::Limits, to read the adapter's and raise the request's
+pub type LimitField = fn(&mut wgpu::Limits) -> &mut u32;
+
+struct FeatureRequest {
+    subsystem: &'static str,
+    feature: wgpu::Features,
+    required: bool,
+}
+
+struct LimitRequest {
+    subsystem: &'static str,
+    name: &'static str,
+    field: LimitField,
+    minimum: u32,
+    required: bool,
+}
+
+// What a device was asked for once the adapter had its say
+#[derive(Clone, Debug)]
+pub struct Granted {
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    // Subsystems that asked for something optional the adapter doesn't have, and what
+    pub degraded: Vec<(&'static str, String)>,
+}
+
+// Features and limits the subsystems want from the device, each either required or nice to
+// have. negotiate() intersects them with what an adapter offers, so the device is created
+// with exactly what's both wanted and there. It only looks at feature and limit values, not
+// at an adapter, so any made up set can be thrown at it
+pub struct DeviceRequirements {
+    features: Vec<FeatureRequest>,
+    limits: Vec<LimitRequest>,
+}
+
+impl DeviceRequirements {
+    pub fn new() -> Self {
+        Self {
+            features: Vec::new(),
+            limits: Vec::new(),
+        }
+    }
+
+    // Device creation fails without it
+    pub fn require_feature(
+        &mut self,
+        subsystem: &'static str,
+        feature: wgpu::Features,
+    ) -> &mut Self {
+        self.features.push(FeatureRequest {
+            subsystem,
+            feature,
+            required: true,
+        });
+        self
+    }
+
+    // Asked for when the adapter has it, the subsystem is marked degraded otherwise
+    pub fn optional_feature(
+        &mut self,
+        subsystem: &'static str,
+        feature: wgpu::Features,
+    ) -> &mut Self {
+        self.features.push(FeatureRequest {
+            subsystem,
+            feature,
+            required: false,
+        });
+        self
+    }
+
+    // At least `minimum` for the limit `field` picks, `name` is for messages
+    pub fn require_limit(
+        &mut self,
+        subsystem: &'static str,
+        name: &'static str,
+        field: LimitField,
+        minimum: u32,
+    ) -> &mut Self {
+        self.limits.push(LimitRequest {
+            subsystem,
+            name,
+            field,
+            minimum,
+            required: true,
+        });
+        self
+    }
+
+    pub fn optional_limit(
+        &mut self,
+        subsystem: &'static str,
+        name: &'static str,
+        field: LimitField,
+        minimum: u32,
+    ) -> &mut Self {
+        self.limits.push(LimitRequest {
+            subsystem,
+            name,
+            field,
+            minimum,
+            required: false,
+        });
+        self
+    }
+
+    // `base` is what gets requested before any minimums, and has to be within the adapter's
+    // limits already. Limits only ever go up from it. Err for the first required feature or
+    // limit the adapter can't do, naming it and who asked
+    pub fn negotiate(
+        &self,
+        adapter_features: wgpu::Features,
+        adapter_limits: &wgpu::Limits,
+        base: wgpu::Limits,
+    ) -> Result<Granted, ForayError> {
+        let mut granted = Granted {
+            features: wgpu::Features::empty(),
+            limits: base,
+            degraded: Vec::new(),
+        };
+        for request in &self.features {
+            if adapter_features.contains(request.feature) {
+                granted.features |= request.feature;
+                continue;
+            }
+            let missing: Vec<&str> = (request.feature - adapter_features)
+                .iter_names()
+                .map(|(name, _)| name)
+                .collect();
+            let missing = missing.join(" | ");
+            if request.required {
+                return Err(ForayError::DeviceRequirement {
+                    subsystem: request.subsystem.to_owned(),
+                    requirement: missing,
+                });
+            }
+            granted
+                .degraded
+                .push((request.subsystem, format!("no {missing}")));
+        }
+
+        let mut adapter_limits = adapter_limits.clone();
+        for request in &self.limits {
+            let available = *(request.field)(&mut adapter_limits);
+            if available >= request.minimum {
+                let value = (request.field)(&mut granted.limits);
+                *value = (*value).max(request.minimum);
+                continue;
+            }
+            let reason = format!(
+                "{} of at least {}, the adapter has {available}",
+                request.name, request.minimum
+            );
+            if request.required {
+                return Err(ForayError::DeviceRequirement {
+                    subsystem: request.subsystem.to_owned(),
+                    requirement: reason,
+                });
+            }
+            granted.degraded.push((request.subsystem, reason));
+        }
+        Ok(granted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_constant_size(limits: &mut wgpu::Limits) -> &mut u32 {
+        &mut limits.max_push_constant_size
+    }
+
+    fn texture_size(limits: &mut wgpu::Limits) -> &mut u32 {
+        &mut limits.max_texture_dimension_2d
+    }
+
+    // A bit of everything, like State::new asks for
+    fn requirements() -> DeviceRequirements {
+        let mut requirements = DeviceRequirements::new();
+        requirements
+            .require_limit(
+                "screenshots",
+                "max_texture_dimension_2d",
+                texture_size,
+                4096,
+            )
+            .optional_feature("wireframe", wgpu::Features::POLYGON_MODE_LINE)
+            .optional_feature("push constants", wgpu::Features::PUSH_CONSTANTS)
+            .optional_limit(
+                "push constants",
+                "max_push_constant_size",
+                push_constant_size,
+                128,
+            );
+        requirements
+    }
+
+    // What a capable desktop adapter has
+    fn desktop() -> (wgpu::Features, wgpu::Limits) {
+        (
+            wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::PUSH_CONSTANTS,
+            wgpu::Limits {
+                max_push_constant_size: 256,
+                ..wgpu::Limits::default()
+            },
+        )
+    }
+
+    #[test]
+    fn everything_there_is_granted_and_nothing_more() {
+        let (features, limits) = desktop();
+        let features = features | wgpu::Features::TIMESTAMP_QUERY;
+        let granted = requirements()
+            .negotiate(features, &limits, wgpu::Limits::downlevel_defaults())
+            .unwrap();
+        assert_eq!(
+            granted.features,
+            wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::PUSH_CONSTANTS
+        );
+        // Raised to the minimums asked for, not to what the adapter could do
+        assert_eq!(granted.limits.max_push_constant_size, 128);
+        assert_eq!(granted.limits.max_texture_dimension_2d, 4096);
+        assert!(granted.degraded.is_empty());
+        // A base that's already higher stays
+        let granted = requirements()
+            .negotiate(features, &limits, limits.clone())
+            .unwrap();
+        assert_eq!(granted.limits.max_texture_dimension_2d, 8192);
+    }
+
+    #[test]
+    fn missing_optional_ones_degrade_their_subsystem() {
+        let granted = requirements()
+            .negotiate(
+                wgpu::Features::POLYGON_MODE_LINE,
+                &wgpu::Limits::default(),
+                wgpu::Limits::downlevel_defaults(),
+            )
+            .unwrap();
+        assert_eq!(granted.features, wgpu::Features::POLYGON_MODE_LINE);
+        assert_eq!(granted.limits.max_push_constant_size, 0);
+        assert_eq!(
+            granted.degraded,
+            [
+                ("push constants", "no PUSH_CONSTANTS".to_owned()),
+                (
+                    "push constants",
+                    "max_push_constant_size of at least 128, the adapter has 0".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_required_feature_names_it_and_who_asked() {
+        let (features, limits) = desktop();
+        let mut requirements = requirements();
+        requirements.require_feature(
+            "command line (--require)",
+            wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::PUSH_CONSTANTS,
+        );
+        let error = requirements
+            .negotiate(features, &limits, wgpu::Limits::downlevel_defaults())
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            ForayError::DeviceRequirement { subsystem, requirement }
+                if subsystem == "command line (--require)" && requirement == "TIMESTAMP_QUERY"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Can't create the device, command line (--require) needs TIMESTAMP_QUERY"
+        );
+    }
+
+    #[test]
+    fn missing_required_limit_names_it_and_who_asked() {
+        let (features, _) = desktop();
+        let weak = wgpu::Limits::downlevel_webgl2_defaults();
+        let error = requirements()
+            .negotiate(features, &weak, weak.clone())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Can't create the device, screenshots needs max_texture_dimension_2d of at least 4096, the adapter has 2048"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+// SplitMix64. Small, fast and the same sequence for a seed on every platform and run, which
+// is all generated content needs. Nothing that has to be unpredictable should use it
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut x = self.0;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^ (x >> 31)
+    }
+
+    // 0..1, from the top 24 bits so every value is exact in an f32
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, range: std::ops::Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    // 0..count, count > 0. The modulo bias is far below anything a scene would show
+    pub fn below(&mut self, count: usize) -> usize {
+        (self.next_u64() % count as u64) as usize
+    }
+}
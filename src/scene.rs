@@ -0,0 +1,1225 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use glam::Vec2;
+#[cfg(feature = "serde-scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::camera2d::Camera2d;
+use crate::colors::{self, RgbaColor};
+use crate::dirty::DirtySet;
+use crate::error::ForayError;
+use crate::geometry::{self, regular_polygon, regular_polygon_unchecked};
+use crate::pacing::Easing;
+use crate::physics::{self, Collider, Physics, PhysicsBody};
+use crate::pipeline_bank::RenderPipelineBank;
+use crate::rng::Rng;
+use crate::shapes::{ShapeInstance, Width, BLEND_MODES};
+use crate::spatial_hash::SpatialHash;
+use crate::transform::{Affine, Transform2d};
+
+// Where an item is in fading in or out. The number is how visible it is before easing,
+// so turning around halfway through a fade carries on from the same opacity
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Visibility {
+    // Going up from 0 to 1
+    Appearing(f32),
+    #[default]
+    Visible,
+    // Going down from 1 to 0
+    Disappearing(f32),
+    Hidden,
+}
+
+impl Visibility {
+    fn progress(self) -> f32 {
+        match self {
+            Visibility::Appearing(p) | Visibility::Disappearing(p) => p,
+            Visibility::Visible => 1.0,
+            Visibility::Hidden => 0.0,
+        }
+    }
+
+    pub fn opacity(self, easing: Easing) -> f32 {
+        easing.apply(self.progress())
+    }
+
+    fn advance(self, step: Duration, fade: &Fade) -> Self {
+        // A zero duration is an instant switch
+        let rate =
+            |duration: Duration| step.as_secs_f32() / duration.as_secs_f32().max(f32::EPSILON);
+        match self {
+            Visibility::Appearing(p) if p + rate(fade.fade_in) >= 1.0 => Visibility::Visible,
+            Visibility::Appearing(p) => Visibility::Appearing(p + rate(fade.fade_in)),
+            Visibility::Disappearing(p) if p - rate(fade.fade_out) <= 0.0 => Visibility::Hidden,
+            Visibility::Disappearing(p) => Visibility::Disappearing(p - rate(fade.fade_out)),
+            settled => settled,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fade {
+    pub fade_in: Duration,
+    pub fade_out: Duration,
+    pub easing: Easing,
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            fade_in: Duration::from_millis(250),
+            fade_out: Duration::from_millis(350),
+            easing: Easing::SmoothStep,
+        }
+    }
+}
+
+// Regular polygons "ngon3" up to this many sides are built in, for generated scenes
+const MAX_SIDES: u32 = 12;
+// Scene::min_scale when the scene file doesn't say. Anything smaller collapses the item to a
+// point, and the inverse (picking, the gizmo) blows up
+pub const DEFAULT_MIN_SCALE: f32 = 1e-3;
+
+#[cfg(feature = "serde-scene")]
+fn default_min_scale() -> f32 {
+    DEFAULT_MIN_SCALE
+}
+
+// What Scene::stress generates, --items, --seed and --spin on the command line
+#[derive(Copy, Clone, Debug)]
+pub struct StressParams {
+    pub items: usize,
+    pub seed: u64,
+    // Radians per second every item turns at, every other one the other way. 0 holds still
+    pub spin: f32,
+}
+
+impl Default for StressParams {
+    fn default() -> Self {
+        Self {
+            items: 5000,
+            seed: 0,
+            spin: 0.0,
+        }
+    }
+}
+
+// Meshes are referenced, never stored in the scene file
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-scene", derive(Serialize, Deserialize))]
+pub enum MeshRef {
+    // Generated in code, see `builtin_outline`
+    Builtin(String),
+    // A RON list of (x, y) points, relative to the working directory
+    Asset(PathBuf),
+}
+
+// Stays with an item for as long as the scene is loaded, unlike its index, which shifts as
+// items come and go. Not saved, loading numbers the items afresh
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItemId(u64);
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-scene", derive(Serialize, Deserialize))]
+pub struct SceneItem {
+    // Handed out by Scene::add
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    pub id: ItemId,
+    pub name: String,
+    pub transform: Transform2d,
+    pub color: [f32; 4],
+    pub mesh: MeshRef,
+    // What the item is meant to be drawn with, checked against the bank on load
+    pub pipeline: String,
+    // Moves on its own in Scene::update when there is one
+    #[cfg_attr(
+        feature = "serde-scene",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub body: Option<PhysicsBody>,
+    // Runtime only, loaded items start out visible
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    pub visibility: Visibility,
+    // Taken out of the scene once it has faded out, see Scene::remove
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    pub removing: bool,
+}
+
+// Everything the user built up interactively. GPU resources are never part of it,
+// they get re-resolved from the mesh references after loading.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-scene", derive(Serialize, Deserialize))]
+pub struct Scene {
+    pub items: Vec<SceneItem>,
+    pub camera: Camera2d,
+    #[cfg_attr(feature = "serde-scene", serde(default))]
+    pub physics: Physics,
+    // Radians per second the items turn at in update, see StressParams::spin
+    #[cfg_attr(feature = "serde-scene", serde(default))]
+    pub spin: f32,
+    // Scales closer to zero than this get pushed out to it, see set_transform
+    #[cfg_attr(feature = "serde-scene", serde(default = "default_min_scale"))]
+    pub min_scale: f32,
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    pub fade: Fade,
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    next_id: u64,
+    // Items whose shapes changed since the ShapeBuffer last got them, marked by whatever
+    // changes them here
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    pub dirty: DirtySet,
+}
+
+impl Scene {
+    // What you get without --scene-file
+    pub fn starter() -> Self {
+        let item = |name: &str, mesh: &str, x: f32, color| SceneItem {
+            id: ItemId::default(),
+            name: name.to_owned(),
+            transform: Transform2d::at(Vec2::new(x, -300.0)),
+            color,
+            mesh: MeshRef::Builtin(mesh.to_owned()),
+            pipeline: "shapes".to_owned(),
+            body: None,
+            visibility: Visibility::Visible,
+            removing: false,
+        };
+        Self {
+            items: vec![
+                item("Pentagon", "pentagon", -200.0, [0.5, 0.0, 0.5, 1.0]),
+                item("Square", "square", 0.0, [0.2, 0.6, 0.9, 1.0]),
+                item("Triangle", "triangle", 200.0, [0.9, 0.7, 0.2, 1.0]),
+            ],
+            camera: Camera2d::new(),
+            physics: Physics::default(),
+            spin: 0.0,
+            min_scale: DEFAULT_MIN_SCALE,
+            fade: Fade::default(),
+            next_id: 0,
+            dirty: DirtySet::default(),
+        }
+        .numbered()
+    }
+
+    // Gives every item a fresh id
+    fn numbered(mut self) -> Self {
+        for item in &mut self.items {
+            item.id = ItemId(self.next_id);
+            self.next_id += 1;
+        }
+        self
+    }
+
+    // What has to be loaded before the scene can be shown, each mesh once however many
+    // items use it
+    pub fn manifest(&self) -> Vec<MeshRef> {
+        let mut meshes: Vec<MeshRef> = Vec::new();
+        for item in &self.items {
+            if !meshes.contains(&item.mesh) {
+                meshes.push(item.mesh.clone());
+            }
+        }
+        meshes
+    }
+
+    // Built-in scenes by name, for --scene and `render --scene`. `stress` is only used by
+    // the stress scene
+    pub fn named(name: &str, stress: &StressParams) -> Option<Self> {
+        match name {
+            "starter" => Some(Self::starter()),
+            "instancing_ring" => Some(Self::instancing_ring()),
+            "bouncing_pentagons" => Some(Self::bouncing_pentagons()),
+            "stress" => Some(Self::stress(stress)),
+            _ => None,
+        }
+    }
+
+    // Shapes around a circle, each turned to face outwards. They fade in one after the
+    // other going counter-clockwise, an eighth of a second apart with the default fade
+    fn instancing_ring() -> Self {
+        const COUNT: usize = 24;
+        const RADIUS: f32 = 220.0;
+        let meshes = ["triangle", "square", "pentagon"];
+        let palette = colors::palette(COUNT);
+        let items = (0..COUNT)
+            .map(|i| {
+                let angle = i as f32 / COUNT as f32 * std::f32::consts::TAU;
+                SceneItem {
+                    id: ItemId::default(),
+                    name: format!("Ring {i}"),
+                    transform: Transform2d {
+                        translation: Vec2::from_angle(angle) * RADIUS,
+                        rotation: angle,
+                        scale: 0.4,
+                    },
+                    color: palette[i].to_f32_array(),
+                    mesh: MeshRef::Builtin(meshes[i % meshes.len()].to_owned()),
+                    pipeline: "shapes".to_owned(),
+                    body: None,
+                    // Eased opacity clamps at 0, so starting below it is a delay
+                    visibility: Visibility::Appearing(-0.5 * i as f32),
+                    removing: false,
+                }
+            })
+            .collect();
+        Self {
+            items,
+            camera: Camera2d::new(),
+            physics: Physics::default(),
+            spin: 0.0,
+            min_scale: DEFAULT_MIN_SCALE,
+            fade: Fade::default(),
+            next_id: 0,
+            dirty: DirtySet::default(),
+        }
+        .numbered()
+    }
+
+    // Pentagons in a grid, thrown in different directions, bouncing off the window and
+    // each other under gravity. Speeds come from an integer hash of the index rather than
+    // a random generator, so every run starts the same
+    fn bouncing_pentagons() -> Self {
+        const COUNT: u32 = 50;
+        const COLUMNS: u32 = 10;
+        const SCALE: f32 = 0.3;
+        let palette = colors::palette(COUNT as usize);
+        let hash = |i: u32, salt: u32| {
+            let mut x = i.wrapping_mul(0x9e37_79b9) ^ salt;
+            x ^= x >> 16;
+            x = x.wrapping_mul(0x85eb_ca6b);
+            x ^= x >> 13;
+            // 0..1
+            (x & 0xffff) as f32 / 65535.0
+        };
+        let items = (0..COUNT)
+            .map(|i| {
+                let (column, row) = (i % COLUMNS, i / COLUMNS);
+                let position = Vec2::new(
+                    (column as f32 - (COLUMNS - 1) as f32 * 0.5) * 60.0,
+                    row as f32 * 60.0 - 60.0,
+                );
+                let velocity = Vec2::new(hash(i, 1) - 0.5, hash(i, 2) - 0.5) * 500.0;
+                SceneItem {
+                    id: ItemId::default(),
+                    name: format!("Pentagon {i}"),
+                    transform: Transform2d {
+                        translation: position,
+                        rotation: 0.0,
+                        scale: SCALE,
+                    },
+                    color: palette[i as usize].to_f32_array(),
+                    mesh: MeshRef::Builtin("pentagon".to_owned()),
+                    pipeline: "shapes".to_owned(),
+                    body: Some(PhysicsBody {
+                        velocity,
+                        acceleration: Vec2::ZERO,
+                        // The pentagon's corners are on a circle of 60
+                        collider: Collider::Circle { radius: 60.0 },
+                        mass: 1.0,
+                        restitution: 1.0,
+                    }),
+                    visibility: Visibility::Visible,
+                    removing: false,
+                }
+            })
+            .collect();
+        Self {
+            items,
+            camera: Camera2d::new(),
+            physics: Physics::default(),
+            spin: 0.0,
+            min_scale: DEFAULT_MIN_SCALE,
+            fade: Fade::default(),
+            next_id: 0,
+            dirty: DirtySet::default(),
+        }
+        .numbered()
+    }
+
+    // A field of polygons for profiling, far bigger than the window so most of it is off
+    // screen. Random side counts, sizes, turns, palette colors and blend pipelines, about one
+    // in four see-through. Everything comes from Rng in a fixed order, so the same params
+    // give the same scene on every run and platform. Prints what it made
+    fn stress(params: &StressParams) -> Self {
+        const WORLD: f32 = 20_000.0;
+        const PALETTE: usize = 16;
+        let palette = colors::palette(PALETTE);
+        let pipelines: Vec<String> = std::iter::once("shapes".to_owned())
+            .chain(BLEND_MODES[1..].iter().map(|mode| mode.key("shapes")))
+            .collect();
+        let mut rng = Rng::new(params.seed);
+        let mut per_pipeline = vec![0; pipelines.len()];
+        let mut transparent = 0;
+        let items = (0..params.items)
+            .map(|i| {
+                let sides = 3 + rng.below(MAX_SIDES as usize - 2);
+                let translation =
+                    Vec2::new(rng.range(-0.5..0.5) * WORLD, rng.range(-0.5..0.5) * WORLD);
+                let rotation = rng.range(0.0..std::f32::consts::TAU);
+                let scale = rng.range(0.1..0.8);
+                let mut color = palette[rng.below(PALETTE)].to_f32_array();
+                if rng.below(4) == 0 {
+                    color[3] = rng.range(0.2..0.8);
+                    transparent += 1;
+                }
+                let pipeline = rng.below(pipelines.len());
+                per_pipeline[pipeline] += 1;
+                SceneItem {
+                    id: ItemId::default(),
+                    name: format!("Stress {i}"),
+                    transform: Transform2d {
+                        translation,
+                        rotation,
+                        scale,
+                    },
+                    color,
+                    mesh: MeshRef::Builtin(format!("ngon{sides}")),
+                    pipeline: pipelines[pipeline].clone(),
+                    body: None,
+                    visibility: Visibility::Visible,
+                    removing: false,
+                }
+            })
+            .collect();
+        let per_pipeline: Vec<String> = pipelines
+            .iter()
+            .zip(&per_pipeline)
+            .map(|(pipeline, count)| format!("{count} {pipeline}"))
+            .collect();
+        println!(
+            "Stress scene, seed {}: {} items over {WORLD}x{WORLD}, 3 to {MAX_SIDES} sides, \
+             {transparent} see-through, {}, spinning at {} rad/s",
+            params.seed,
+            params.items,
+            per_pipeline.join(", "),
+            params.spin
+        );
+        Self {
+            items,
+            camera: Camera2d::new(),
+            physics: Physics::default(),
+            spin: params.spin,
+            min_scale: DEFAULT_MIN_SCALE,
+            fade: Fade::default(),
+            next_id: 0,
+            dirty: DirtySet::default(),
+        }
+        .numbered()
+    }
+
+    // Fades in under a new id, returns its index
+    pub fn add(&mut self, mut item: SceneItem) -> usize {
+        item.id = ItemId(self.next_id);
+        self.next_id += 1;
+        self.insert(self.items.len(), item)
+    }
+
+    // Puts an item that was in the scene before back at `index` (or the end, if there are
+    // fewer items now), keeping its id. Fades in, returns where it ended up
+    pub fn insert(&mut self, index: usize, mut item: SceneItem) -> usize {
+        item.visibility = Visibility::Appearing(0.0);
+        item.removing = false;
+        let index = index.min(self.items.len());
+        self.items.insert(index, item);
+        self.dirty.mark_all();
+        index
+    }
+
+    // Where the item is now, including while it fades out after a remove()
+    pub fn index_of(&self, id: ItemId) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    // Starts fading the item out, it's only taken out of `items` once that's done (see
+    // update), so indices stay valid until then
+    pub fn remove(&mut self, index: usize) {
+        self.dirty.mark(index);
+        let item = &mut self.items[index];
+        item.removing = true;
+        item.visibility = Visibility::Disappearing(item.visibility.progress());
+    }
+
+    // Undoes a remove() that hasn't finished, fading back in from where it got to
+    pub fn restore(&mut self, index: usize) {
+        self.dirty.mark(index);
+        let item = &mut self.items[index];
+        item.removing = false;
+        item.visibility = match item.visibility {
+            Visibility::Visible => Visibility::Visible,
+            other => Visibility::Appearing(other.progress()),
+        };
+    }
+
+    // Interactive edits go through SceneCommand so they can be undone, see undo.rs.
+    // Everything that moves an item by hand comes through here, so this is where a NaN from
+    // a bad drag or a degenerate gizmo inverse gets stopped instead of reaching the GPU.
+    // The item keeps its old transform then
+    pub fn set_transform(
+        &mut self,
+        index: usize,
+        transform: Transform2d,
+    ) -> Result<(), ForayError> {
+        let item = &mut self.items[index];
+        item.transform = checked_transform(&item.name, transform, self.min_scale)?;
+        self.dirty.mark(index);
+        Ok(())
+    }
+
+    pub fn set_pipeline(&mut self, index: usize, pipeline: &str) {
+        self.items[index].pipeline = pipeline.to_owned();
+    }
+
+    // Fades the item out without removing it, or back in
+    pub fn toggle_hidden(&mut self, index: usize) {
+        self.dirty.mark(index);
+        let item = &mut self.items[index];
+        let progress = item.visibility.progress();
+        item.visibility = match item.visibility {
+            Visibility::Visible | Visibility::Appearing(_) => Visibility::Disappearing(progress),
+            Visibility::Hidden | Visibility::Disappearing(_) => Visibility::Appearing(progress),
+        };
+    }
+
+    // Visible or on its way there
+    pub fn is_shown(&self, index: usize) -> bool {
+        matches!(
+            self.items[index].visibility,
+            Visibility::Visible | Visibility::Appearing(_)
+        )
+    }
+
+    // First item called `name` that isn't on its way out
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| item.name == name && !item.removing)
+    }
+
+    // Something is mid-fade, so the view has to keep redrawing
+    pub fn is_fading(&self) -> bool {
+        self.items.iter().any(|item| {
+            matches!(
+                item.visibility,
+                Visibility::Appearing(_) | Visibility::Disappearing(_)
+            )
+        })
+    }
+
+    pub fn opacity(&self, index: usize) -> f32 {
+        self.items[index].visibility.opacity(self.fade.easing)
+    }
+
+    // Steps the physics, advances the fades and drops the items that finished fading out.
+    // Returns their former indices, highest first, so anything indexed alongside `items`
+    // can follow
+    pub fn update(&mut self, step: Duration) -> Vec<usize> {
+        physics::step(&mut self.items, &self.physics, step.as_secs_f32());
+        if self.spin != 0.0 {
+            self.dirty.mark_all();
+        }
+        for (index, item) in self.items.iter_mut().enumerate() {
+            // Moved by physics, or with an opacity still changing
+            let fading = matches!(
+                item.visibility,
+                Visibility::Appearing(_) | Visibility::Disappearing(_)
+            );
+            if item.body.is_some() || fading {
+                self.dirty.mark(index);
+            }
+            item.visibility = item.visibility.advance(step, &self.fade);
+            let direction = if index % 2 == 0 { 1.0 } else { -1.0 };
+            item.transform.rotation += self.spin * direction * step.as_secs_f32();
+        }
+        let removed: Vec<usize> = (0..self.items.len())
+            .rev()
+            .filter(|&index| {
+                let item = &self.items[index];
+                item.removing && item.visibility == Visibility::Hidden
+            })
+            .collect();
+        for &index in &removed {
+            self.items.remove(index);
+        }
+        if !removed.is_empty() {
+            self.dirty.mark_all();
+        }
+        removed
+    }
+
+    // The items as outlines for the ShapeRenderer, `outlines` is indexed like `items`.
+    // The shape pipeline blends already, fading items only need their alpha scaled. The
+    // `highlight`ed item is tinted towards white
+    pub fn shapes(&self, outlines: &[Vec<Vec2>], highlight: Option<usize>) -> Vec<ShapeInstance> {
+        self.shapes_with_spans(outlines, highlight).0
+    }
+
+    // shapes(), along with which of them each item made, for ShapeBuffer::update
+    pub fn shapes_with_spans(
+        &self,
+        outlines: &[Vec<Vec2>],
+        highlight: Option<usize>,
+    ) -> (Vec<ShapeInstance>, Vec<Range<usize>>) {
+        let mut shapes = Vec::new();
+        let mut spans = Vec::with_capacity(self.items.len());
+        for (index, (item, outline)) in self.items.iter().zip(outlines).enumerate() {
+            let start = shapes.len();
+            let mut rgba = item.color;
+            if highlight == Some(index) {
+                for channel in &mut rgba[..3] {
+                    *channel += (1.0 - *channel) * 0.5;
+                }
+            }
+            rgba[3] *= self.opacity(index);
+            let color = RgbaColor::from_f32_array(rgba);
+            if outline.is_empty() {
+                // Mesh didn't resolve, mark the spot so the item can still be found and moved
+                let at = item.transform.translation;
+                for corner in [Vec2::new(10.0, 10.0), Vec2::new(10.0, -10.0)] {
+                    shapes.push(ShapeInstance::line(
+                        at - corner,
+                        at + corner,
+                        Width::Pixels(2.0),
+                        color,
+                    ));
+                }
+                spans.push(start..shapes.len());
+                continue;
+            }
+            // Everything that changes transforms is guarded, one getting here means a path
+            // that isn't
+            debug_assert!(
+                item.transform.is_finite(),
+                "Non-finite transform on \"{}\": {:?}",
+                item.name,
+                item.transform
+            );
+            let points: Vec<_> = outline
+                .iter()
+                .map(|&p| item.transform.transform_point(p))
+                .collect();
+            for (i, &p0) in points.iter().enumerate() {
+                let p1 = points[(i + 1) % points.len()];
+                shapes.push(ShapeInstance::line(p0, p1, Width::Pixels(2.0), color));
+            }
+            spans.push(start..shapes.len());
+        }
+        (shapes, spans)
+    }
+
+    // Items still fading out after a remove() are as good as gone, they're left out
+    #[cfg(feature = "serde-scene")]
+    pub fn save(&self, path: &Path) -> Result<(), ForayError> {
+        let error = |reason: String| ForayError::SceneFile {
+            path: path.to_owned(),
+            reason,
+        };
+        let mut kept = self.clone();
+        kept.items.retain(|item| !item.removing);
+        let text = ron::ser::to_string_pretty(&kept, ron::ser::PrettyConfig::default())
+            .map_err(|e| error(e.to_string()))?;
+        std::fs::write(path, text).map_err(|e| error(e.to_string()))
+    }
+
+    #[cfg(feature = "serde-scene")]
+    pub fn load(path: &Path) -> Result<Self, ForayError> {
+        let error = |reason: String| ForayError::SceneFile {
+            path: path.to_owned(),
+            reason,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let mut scene: Self = ron::from_str(&text).map_err(|e| error(e.to_string()))?;
+        if !(scene.spin.is_finite() && scene.min_scale.is_finite() && scene.min_scale > 0.0) {
+            return Err(error(format!(
+                "spin {} and min_scale {} have to be finite, min_scale above 0",
+                scene.spin, scene.min_scale
+            )));
+        }
+        if !scene.physics.gravity.is_finite() {
+            return Err(error(format!(
+                "gravity {} has to be finite",
+                scene.physics.gravity
+            )));
+        }
+        for item in &mut scene.items {
+            item.transform = checked_transform(&item.name, item.transform, scene.min_scale)
+                .map_err(|e| error(e.to_string()))?;
+            if item
+                .body
+                .is_some_and(|body| !(body.velocity.is_finite() && body.acceleration.is_finite()))
+            {
+                return Err(error(format!(
+                    "item \"{}\" has a non-finite velocity or acceleration",
+                    item.name
+                )));
+            }
+        }
+        Ok(scene.numbered())
+    }
+
+    // Built without scene files, --scene-file still takes the built-in names
+    #[cfg(not(feature = "serde-scene"))]
+    pub fn save(&self, path: &Path) -> Result<(), ForayError> {
+        Err(without_scene_files(path))
+    }
+
+    #[cfg(not(feature = "serde-scene"))]
+    pub fn load(path: &Path) -> Result<Self, ForayError> {
+        Err(without_scene_files(path))
+    }
+
+    // Unknown pipelines are reported and the item kept, so saving again loses nothing.
+    // Outlines are loaded separately, through Assets
+    pub fn check_pipelines(&self, bank: &RenderPipelineBank) {
+        for item in &self.items {
+            if bank.get(&item.pipeline).is_none() {
+                log::warn!(
+                    "Scene item \"{}\": {}",
+                    item.name,
+                    ForayError::UnknownPipeline(item.pipeline.clone())
+                );
+            }
+        }
+    }
+
+    // Topmost item whose outline's bounding circle contains `point`. Items without an
+    // outline are drawn as a small cross, that's what they can be grabbed by. `grid` holds
+    // item_bounds from after the items last moved
+    pub fn pick(&self, outlines: &[Vec<Vec2>], grid: &SpatialHash, point: Vec2) -> Option<usize> {
+        grid.query_point(point).into_iter().rev().find(|&index| {
+            let transform = &self.items[index].transform;
+            transform.translation.distance(point) <= self.reach(outlines, index)
+        })
+    }
+
+    // World space (index, min, max) boxes around the items that can still be picked, for
+    // filling a SpatialHash
+    pub fn item_bounds<'a>(
+        &'a self,
+        outlines: &'a [Vec<Vec2>],
+    ) -> impl Iterator<Item = (usize, Vec2, Vec2)> + 'a {
+        (0..self.items.len())
+            .filter(|&index| !self.items[index].removing)
+            .map(|index| {
+                let center = self.items[index].transform.translation;
+                let reach = Vec2::splat(self.reach(outlines, index));
+                (index, center - reach, center + reach)
+            })
+    }
+
+    // Distance from the item's center picking goes out to, its furthest outline point or
+    // the marker drawn for items without one
+    fn reach(&self, outlines: &[Vec<Vec2>], index: usize) -> f32 {
+        outlines[index]
+            .iter()
+            .map(|p| p.length())
+            .fold(15.0, f32::max)
+            * self.items[index].transform.scale
+    }
+}
+
+// "ngon2" is a polygon with too few sides rather than a missing asset, "ngon99" (past
+// MAX_SIDES) and anything else unknown is missing
+fn builtin_outline(name: &str) -> Result<Vec<Vec2>, ForayError> {
+    let quarter = std::f32::consts::FRAC_PI_2;
+    let missing = || ForayError::MissingAsset(PathBuf::from(name));
+    match name {
+        "pentagon" => Ok(regular_polygon_unchecked(5, 60.0, quarter)),
+        "square" => Ok(regular_polygon_unchecked(4, 60.0, quarter * 0.5)),
+        "triangle" => Ok(regular_polygon_unchecked(3, 60.0, quarter)),
+        _ => {
+            let sides: u32 = name
+                .strip_prefix("ngon")
+                .and_then(|sides| sides.parse().ok())
+                .filter(|&sides| sides <= MAX_SIDES)
+                .ok_or_else(missing)?;
+            regular_polygon(sides, 60.0, quarter).map_err(|error| ForayError::Geometry {
+                mesh: name.to_owned(),
+                error,
+            })
+        }
+    }
+}
+
+pub fn load_outline(mesh: &MeshRef) -> Result<Vec<Vec2>, ForayError> {
+    match mesh {
+        MeshRef::Builtin(name) => builtin_outline(name),
+        MeshRef::Asset(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|_| ForayError::MissingAsset(path.clone()))?;
+            let points: Vec<(f32, f32)> =
+                ron::from_str(&text).map_err(|e| ForayError::SceneFile {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                })?;
+            geometry::closed_outline(points.into_iter().map(Vec2::from).collect(), "the outline")
+                .map_err(|error| ForayError::Geometry {
+                    mesh: path.display().to_string(),
+                    error,
+                })
+        }
+    }
+}
+
+// Non-finite parts are an error, a scale within `min_scale` of zero is pushed out to it
+// (keeping its sign) with a warning
+fn checked_transform(
+    name: &str,
+    mut transform: Transform2d,
+    min_scale: f32,
+) -> Result<Transform2d, ForayError> {
+    if !transform.is_finite() {
+        return Err(ForayError::NonFiniteTransform {
+            item: name.to_owned(),
+            transform,
+        });
+    }
+    if transform.scale.abs() < min_scale {
+        log::warn!(
+            "Scene item \"{name}\": scale {} is too close to 0, using {min_scale}",
+            transform.scale
+        );
+        transform.scale = min_scale.copysign(transform.scale);
+    }
+    Ok(transform)
+}
+
+#[cfg(not(feature = "serde-scene"))]
+fn without_scene_files(path: &Path) -> ForayError {
+    ForayError::SceneFile {
+        path: path.to_owned(),
+        reason: "built without the serde-scene feature".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loading resolves meshes from this, each one once however many items share it
+    #[test]
+    fn manifest_lists_each_mesh_once() {
+        let mut scene = Scene::starter();
+        let mut copy = scene.items[0].clone();
+        copy.name = "Second Pentagon".to_owned();
+        scene.items.push(copy);
+        assert_eq!(
+            scene.manifest(),
+            ["pentagon", "square", "triangle"].map(|name| MeshRef::Builtin(name.to_owned()))
+        );
+    }
+
+    // Fades short enough to step through quickly, linear so opacity is the progress
+    fn fading() -> Scene {
+        let mut scene = Scene::starter().numbered();
+        scene.fade = Fade {
+            fade_in: Duration::from_millis(100),
+            fade_out: Duration::from_millis(200),
+            easing: Easing::Linear,
+        };
+        scene
+    }
+
+    // Fixed steps of 10ms, returning everything update() took out
+    fn run(scene: &mut Scene, millis: u64) -> Vec<usize> {
+        (0..millis / 10)
+            .flat_map(|_| scene.update(Duration::from_millis(10)))
+            .collect()
+    }
+
+    fn item(name: &str) -> SceneItem {
+        let mut item = Scene::starter().items.remove(0);
+        item.name = name.to_owned();
+        item
+    }
+
+    #[test]
+    fn added_items_fade_in_then_settle() {
+        let mut scene = fading();
+        let index = scene.add(item("New"));
+        assert_eq!(scene.items[index].visibility, Visibility::Appearing(0.0));
+        assert!(scene.is_shown(index) && scene.is_fading());
+        assert!(scene.opacity(index).abs() < 1e-6);
+        run(&mut scene, 50);
+        assert!((scene.opacity(index) - 0.5).abs() < 0.01);
+        run(&mut scene, 60);
+        assert_eq!(scene.items[index].visibility, Visibility::Visible);
+        assert!(!scene.is_fading());
+    }
+
+    #[test]
+    fn removed_items_go_once_faded_out() {
+        let mut scene = fading();
+        let id = scene.items[1].id;
+        scene.remove(1);
+        assert_eq!(scene.items[1].visibility, Visibility::Disappearing(1.0));
+        // Still there and indexable while it fades, but not findable or pickable
+        assert!(run(&mut scene, 150).is_empty());
+        assert_eq!(scene.index_of(id), Some(1));
+        assert_eq!(scene.find("Square"), None);
+        assert_eq!(scene.item_bounds(&[vec![], vec![], vec![]]).count(), 2);
+        assert!(scene.opacity(1) > 0.0 && scene.opacity(1) < 0.5);
+        assert_eq!(run(&mut scene, 60), vec![1]);
+        assert_eq!(scene.index_of(id), None);
+        assert_eq!(scene.items.len(), 2);
+        assert!(!scene.is_fading());
+    }
+
+    #[test]
+    fn restoring_turns_a_fade_out_around() {
+        let mut scene = fading();
+        scene.remove(0);
+        run(&mut scene, 100);
+        let halfway = scene.opacity(0);
+        scene.restore(0);
+        // Carries on from where it got to rather than jumping
+        assert!((scene.opacity(0) - halfway).abs() < 1e-6);
+        assert!(matches!(
+            scene.items[0].visibility,
+            Visibility::Appearing(_)
+        ));
+        assert!(run(&mut scene, 300).is_empty());
+        assert_eq!(scene.items[0].visibility, Visibility::Visible);
+        assert_eq!(scene.find("Pentagon"), Some(0));
+
+        // Restoring something that never went anywhere leaves it be
+        scene.restore(1);
+        assert_eq!(scene.items[1].visibility, Visibility::Visible);
+    }
+
+    #[test]
+    fn hiding_fades_without_removing() {
+        let mut scene = fading();
+        scene.toggle_hidden(2);
+        assert!(!scene.is_shown(2));
+        assert!(run(&mut scene, 300).is_empty());
+        assert_eq!(scene.items[2].visibility, Visibility::Hidden);
+        scene.toggle_hidden(2);
+        assert_eq!(scene.items[2].visibility, Visibility::Appearing(0.0));
+        assert!(scene.is_shown(2));
+    }
+
+    #[test]
+    fn re_adding_during_a_fade_out_keeps_both_apart() {
+        let mut scene = fading();
+        let old = scene.items[0].id;
+        scene.remove(0);
+        run(&mut scene, 50);
+        let again = scene.add(scene.items[0].clone());
+        let new = scene.items[again].id;
+        assert_ne!(new, old);
+        assert!(!scene.items[again].removing);
+        assert_eq!(scene.find("Pentagon"), Some(again));
+
+        // The old one finishes going, taking only itself with it
+        assert_eq!(run(&mut scene, 200), vec![0]);
+        assert_eq!(scene.index_of(old), None);
+        let again = scene.index_of(new).unwrap();
+        assert_eq!(scene.items[again].visibility, Visibility::Visible);
+        assert_eq!(scene.find("Pentagon"), Some(again));
+        assert_eq!(scene.items.len(), 3);
+    }
+
+    #[cfg(not(feature = "serde-scene"))]
+    #[test]
+    fn scene_files_need_the_feature() {
+        let path = Path::new("scene.ron");
+        assert!(Scene::starter().save(path).is_err());
+        assert!(Scene::load(path).is_err());
+    }
+
+    // Something of everything a scene file holds, none of it at its default
+    #[cfg(feature = "serde-scene")]
+    fn synthetic() -> Scene {
+        let mut scene = Scene::starter();
+        scene.camera.center = Vec2::new(12.5, -40.0);
+        scene.camera.zoom = 2.25;
+        scene.physics.gravity = Vec2::new(3.0, -250.0);
+        scene.spin = -0.75;
+        scene.min_scale = 0.01;
+        scene.items[0].transform = Transform2d {
+            translation: Vec2::new(-123.456, 0.1),
+            rotation: 1.0e-3,
+            scale: -0.5,
+        };
+        scene.items[1].color = [0.1, 0.2, 0.3, 0.4];
+        scene.items[1].pipeline = "shapes_add".to_owned();
+        scene.items[2].mesh = MeshRef::Asset(PathBuf::from("outlines/star.ron"));
+        scene.items[2].body = Some(PhysicsBody {
+            velocity: Vec2::new(80.0, 120.0),
+            acceleration: Vec2::new(0.0, -5.0),
+            collider: Collider::Aabb {
+                half_size: Vec2::new(30.0, 12.0),
+            },
+            mass: 2.5,
+            restitution: 0.6,
+        });
+        scene
+    }
+
+    #[cfg(feature = "serde-scene")]
+    fn scene_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wgpu-foray-{}-{name}.ron", std::process::id()))
+    }
+
+    #[cfg(feature = "serde-scene")]
+    #[test]
+    fn save_and_load_round_trips() {
+        let path = scene_file("round-trip");
+        let scene = synthetic();
+        scene.save(&path).unwrap();
+        let loaded = Scene::load(&path);
+        // Saving what was loaded writes the same file again
+        let again = scene_file("round-trip-again");
+        loaded.as_ref().unwrap().save(&again).unwrap();
+        let (first, second) = (
+            std::fs::read_to_string(&path).unwrap(),
+            std::fs::read_to_string(&again).unwrap(),
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&again).unwrap();
+        assert_eq!(loaded.unwrap(), scene);
+        assert_eq!(first, second);
+    }
+
+    #[cfg(feature = "serde-scene")]
+    #[test]
+    fn items_fading_out_are_not_saved() {
+        let path = scene_file("fading-out");
+        let mut scene = fading();
+        scene.remove(1);
+        run(&mut scene, 50);
+        scene.save(&path).unwrap();
+        let loaded = Scene::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let names: Vec<String> = loaded
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        assert_eq!(names, ["Pentagon", "Triangle"]);
+    }
+
+    #[cfg(feature = "serde-scene")]
+    #[test]
+    fn missing_mesh_files_are_reported_not_fatal() {
+        let path = scene_file("missing-mesh");
+        let mut scene = Scene::starter();
+        let missing = PathBuf::from("outlines/not-there.ron");
+        scene.items[0].mesh = MeshRef::Asset(missing.clone());
+        scene.save(&path).unwrap();
+        let loaded = Scene::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert!(matches!(
+            load_outline(&loaded.items[0].mesh),
+            Err(ForayError::MissingAsset(path)) if path == missing
+        ));
+    }
+
+    #[cfg(feature = "serde-scene")]
+    #[test]
+    fn broken_scene_files_are_errors() {
+        let path = scene_file("broken");
+        let mut scene = synthetic();
+        scene.spin = f32::NAN;
+        // RON writes NaN as NaN, load is what has to turn it down
+        scene.save(&path).unwrap();
+        let nan = Scene::load(&path);
+        std::fs::write(&path, "(items: [").unwrap();
+        let truncated = Scene::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(nan, Err(ForayError::SceneFile { .. })));
+        assert!(matches!(truncated, Err(ForayError::SceneFile { .. })));
+        assert!(matches!(
+            Scene::load(&scene_file("never-written")),
+            Err(ForayError::SceneFile { .. })
+        ));
+    }
+
+    // The warnings `f` logged
+    fn warnings(f: impl FnOnce()) -> Vec<String> {
+        crate::log_sink::init();
+        crate::log_sink::capture(f)
+    }
+
+    // What the scene would upload is all finite, and every item is still in it
+    fn assert_renderable(scene: &Scene) {
+        let outlines: Vec<Vec<Vec2>> = scene
+            .items
+            .iter()
+            .map(|item| load_outline(&item.mesh).unwrap_or_default())
+            .collect();
+        let (shapes, spans) = scene.shapes_with_spans(&outlines, None);
+        assert_eq!(spans.len(), scene.items.len());
+        assert!(shapes.iter().all(ShapeInstance::is_finite));
+    }
+
+    #[test]
+    fn set_transform_turns_down_non_finite_parts() {
+        let mut scene = Scene::starter();
+        let before = scene.items[0].transform;
+        for bad in [
+            Transform2d::at(Vec2::new(f32::NAN, 0.0)),
+            Transform2d::at(Vec2::new(0.0, f32::INFINITY)),
+            Transform2d {
+                rotation: f32::NEG_INFINITY,
+                ..before
+            },
+            Transform2d {
+                scale: f32::NAN,
+                ..before
+            },
+        ] {
+            let result = scene.set_transform(0, bad);
+            let named = matches!(
+                &result,
+                Err(ForayError::NonFiniteTransform { item, .. }) if item == "Pentagon"
+            );
+            assert!(named, "{bad:?} gave {result:?}");
+            assert_eq!(scene.items[0].transform, before);
+        }
+        assert_renderable(&scene);
+    }
+
+    #[test]
+    fn set_transform_pushes_tiny_scales_out_with_a_warning() {
+        let mut scene = Scene::starter();
+        scene.min_scale = 0.01;
+        for (scale, expected) in [(0.0, 0.01), (-0.0, -0.01), (1e-9, 0.01), (-0.005, -0.01)] {
+            let logged = warnings(|| {
+                scene
+                    .set_transform(
+                        1,
+                        Transform2d {
+                            scale,
+                            ..Transform2d::at(Vec2::ZERO)
+                        },
+                    )
+                    .unwrap();
+            });
+            assert!(
+                (scene.items[1].transform.scale - expected).abs() < 1e-9,
+                "{scale}"
+            );
+            assert_eq!(logged.len(), 1, "{scale}");
+            assert!(logged[0].contains("\"Square\"") && logged[0].contains("too close to 0"));
+        }
+        // Right at the limit is left alone, quietly
+        let logged = warnings(|| {
+            scene
+                .set_transform(
+                    1,
+                    Transform2d {
+                        scale: -0.01,
+                        ..Transform2d::at(Vec2::ZERO)
+                    },
+                )
+                .unwrap();
+        });
+        assert!(logged.is_empty());
+        assert_renderable(&scene);
+    }
+
+    #[test]
+    fn physics_stops_a_body_that_goes_non_finite() {
+        let mut scene = Scene::named("bouncing_pentagons", &StressParams::default()).unwrap();
+        scene.physics.bounds = Some(scene.camera.visible((800, 600)));
+        scene.update(Duration::from_millis(16));
+        let at = [0, 1].map(|index| scene.items[index].transform.translation);
+        scene.items[0].body.as_mut().unwrap().velocity = Vec2::new(f32::NAN, 0.0);
+        scene.items[1].body.as_mut().unwrap().acceleration = Vec2::new(0.0, f32::INFINITY);
+        let logged = warnings(|| {
+            scene.update(Duration::from_millis(16));
+        });
+        for (index, at) in at.into_iter().enumerate() {
+            let item = &scene.items[index];
+            assert_eq!(item.transform.translation, at, "{}", item.name);
+            assert_eq!(item.body.unwrap().velocity, Vec2::ZERO, "{}", item.name);
+            assert!(
+                logged
+                    .iter()
+                    .any(|line| line
+                        .contains(&format!("\"{}\" left the step non-finite", item.name))),
+                "{logged:?}"
+            );
+        }
+        // Nothing it touched caught it
+        for item in &scene.items {
+            assert!(item.transform.is_finite(), "{}", item.name);
+            assert!(item.body.unwrap().velocity.is_finite(), "{}", item.name);
+        }
+        assert_renderable(&scene);
+    }
+
+    #[test]
+    fn tweens_hold_at_the_finite_end() {
+        use crate::pacing::{Easing, Tween};
+
+        let second = Duration::from_secs(1);
+        let logged = warnings(|| {
+            for (from, to, held) in [
+                (f32::NAN, 2.0, 2.0),
+                (1.0, f32::INFINITY, 1.0),
+                (f32::NEG_INFINITY, f32::NAN, 0.0),
+            ] {
+                let mut tween = Tween::new(from, to, second, Easing::SmoothStep);
+                for _ in 0..3 {
+                    assert!((tween.value() - held).abs() < 1e-6, "{from} to {to}");
+                    tween.step(second / 2);
+                }
+            }
+            // Retargeting part way to something broken stays where it got to
+            let mut tween = Tween::new(0.0, 4.0, second, Easing::Linear);
+            tween.step(second / 4);
+            tween.retarget(f32::NAN);
+            tween.step(second);
+            assert!((tween.value() - 1.0).abs() < 1e-6);
+        });
+        assert_eq!(logged.len(), 4, "{logged:?}");
+        assert!(logged
+            .iter()
+            .all(|line| line.contains("isn't finite, holding still")));
+    }
+
+    #[test]
+    fn spin_on_the_command_line_has_to_be_finite() {
+        let mut stress = StressParams::default();
+        for text in ["nan", "inf", "-inf", "NaN"] {
+            let mut args = std::iter::once(text.to_owned());
+            assert!(crate::options::stress_arg(&mut stress, "--spin", &mut args));
+            assert!(
+                (stress.spin - StressParams::default().spin).abs() < f32::EPSILON,
+                "{text}"
+            );
+        }
+        let mut args = std::iter::once("1.5".to_owned());
+        crate::options::stress_arg(&mut stress, "--spin", &mut args);
+        let mut scene = Scene::stress(&stress);
+        for _ in 0..10 {
+            scene.update(Duration::from_millis(16));
+        }
+        assert!(scene.items.iter().all(|item| item.transform.is_finite()));
+        assert_renderable(&scene);
+    }
+
+    #[cfg(feature = "serde-scene")]
+    #[test]
+    fn loading_pushes_tiny_scales_out_too() {
+        let path = scene_file("tiny");
+        let mut scene = synthetic();
+        scene.items[1].transform.scale = 0.0;
+        scene.save(&path).unwrap();
+        let mut loaded = None;
+        let logged = warnings(|| loaded = Some(Scene::load(&path)));
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap().unwrap();
+        assert!((loaded.items[1].transform.scale - 0.01).abs() < 1e-9);
+        assert!(
+            logged.iter().any(|line| line.contains("too close to 0")),
+            "{logged:?}"
+        );
+    }
+}
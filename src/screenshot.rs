@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use image::RgbaImage;
+
+use crate::buffer_pool::BufferPool;
+use crate::maintain;
+
+// How long a screenshot taken between frames waits on the GPU before giving up
+pub const TIMEOUT: Duration = Duration::from_secs(3);
+
+// A surface texture on its way into a buffer the CPU can map. `copy` records the copy,
+// `read` waits for it once that's been submitted
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_row_bytes: u32,
+    // Surfaces are often BGRA, the image wants RGBA
+    bgra: bool,
+}
+
+impl Readback {
+    // Recorded into `encoder`, so it sees whatever was drawn before it in the same submit
+    pub fn copy(
+        device: &wgpu::Device,
+        pool: &mut BufferPool,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        label: &str,
+    ) -> Result<Self, String> {
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err("the surface can't be copied from".to_owned());
+        }
+        let bgra = match texture.format() {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            format => return Err(format!("can't save a {format:?} surface")),
+        };
+        let (width, height) = (texture.width(), texture.height());
+        let padded_row_bytes = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        // Rounded up by the pool, only the start of it is copied to and read
+        let buffer = pool.acquire(
+            device,
+            label,
+            wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            u64::from(padded_row_bytes) * u64::from(height),
+        );
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_row_bytes,
+            bgra,
+        })
+    }
+
+    // Blocks until the copy is done, `timeout` at most. The buffer is unmapped again however
+    // it goes, the pool hands it out to later frames
+    pub fn read(self, device: &wgpu::Device, timeout: Duration) -> Result<RgbaImage, String> {
+        let pixels = self.map(device, timeout);
+        self.buffer.unmap();
+        let mut pixels = pixels?;
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| "the readback is smaller than the frame".to_owned())
+    }
+
+    fn map(&self, device: &wgpu::Device, timeout: Duration) -> Result<Vec<u8>, String> {
+        let size = u64::from(self.padded_row_bytes) * u64::from(self.height);
+        let slice = self.buffer.slice(..size);
+        let done = maintain::untracked();
+        let finished = done.clone();
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+            finished.finish();
+        });
+        if !maintain::wait_bounded(device, &done, timeout) {
+            return Err(format!("the GPU didn't answer in {timeout:?}"));
+        }
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("reading it back failed, {e}"))?;
+
+        let row_bytes = self.width as usize * 4;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(self.padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes])
+            .copied()
+            .collect();
+        Ok(pixels)
+    }
+}
+
+// screenshot-<unix milliseconds>.png in the working directory, for when nothing says where
+pub fn default_path() -> PathBuf {
+    timestamped("screenshot", "png")
+}
+
+// <stem>-<unix milliseconds>.<extension> in the working directory
+pub fn timestamped(stem: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    PathBuf::from(format!("{stem}-{millis}.{extension}"))
+}
@@ -0,0 +1,37 @@
+// 2D signed distance functions, negative inside, positive outside
+fn sd_circle(p: vec2<f32>, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+
+fn sd_box(p: vec2<f32>, half_size: vec2<f32>) -> f32 {
+    let d = abs(p) - half_size;
+    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0);
+}
+
+fn sd_rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    return sd_box(p, half_size - vec2<f32>(radius)) - radius;
+}
+
+fn op_union(a: f32, b: f32) -> f32 {
+    return min(a, b);
+}
+
+// Polynomial smooth min, k is the blend radius
+fn op_smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) - k * h * (1.0 - h);
+}
+
+fn rotate2d(p: vec2<f32>, angle: f32) -> vec2<f32> {
+    let c = cos(angle);
+    let s = sin(angle);
+    return vec2<f32>(c * p.x - s * p.y, s * p.x + c * p.y);
+}
+
+// The usual Shadertoy-ish shading: inside/outside tint, distance bands and a crisp edge
+fn sdf_shade(d: f32) -> vec3<f32> {
+    var color = select(vec3<f32>(0.9, 0.6, 0.3), vec3<f32>(0.65, 0.85, 1.0), d > 0.0);
+    color *= 1.0 - exp(-6.0 * abs(d));
+    color *= 0.8 + 0.2 * cos(150.0 * d);
+    return mix(color, vec3<f32>(1.0), 1.0 - smoothstep(0.0, 0.01, abs(d)));
+}
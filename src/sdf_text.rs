@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use glam::Vec2;
+
+use crate::buffer_pool::BufferPool;
+use crate::camera2d::Camera2d;
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::shaders;
+use crate::shapes::CameraUniform;
+use crate::targets::TargetRegistry;
+use crate::text::{Font, TextBounds};
+
+// Glyphs are rasterized this big before turning them into distance fields. Bigger keeps
+// sharper corners when zoomed far in, at the cost of atlas space
+const BASE_PX: f32 = 48.0;
+// How far from the outline, in base pixels, the field still tells distances apart. Also
+// the widest outline that can be drawn
+const SPREAD: f32 = 6.0;
+const ATLAS_WIDTH: u32 = 1024;
+// Characters that get a distance field, anything else is drawn as a tofu box
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SdfVertex {
+    position: [f32; 2], // world units, y up
+    uv: [f32; 2],
+    color: [f32; 4],
+    outline_color: [f32; 4],
+    // In distance field units, 0.5 is SPREAD base pixels. 0 for no outline
+    outline_width: f32,
+}
+
+impl SdfVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SdfVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Where a glyph's field sits in the atlas and how it's placed, in base pixels
+#[derive(Copy, Clone, Debug)]
+struct SdfGlyph {
+    // Atlas pixels (x, y, width, height), padded by SPREAD on every side
+    rect: (u32, u32, u32, u32),
+    // Bottom-left corner of the padded bitmap relative to the pen on the baseline, y up
+    offset: Vec2,
+    advance: f32,
+    index: u16,
+}
+
+struct SdfAtlas {
+    // Unique per font, the renderer keeps one texture per atlas it has seen
+    id: usize,
+    size: (u32, u32),
+    pixels: Vec<u8>,
+    glyphs: HashMap<char, SdfGlyph>,
+    tofu: SdfGlyph,
+}
+
+// A Font with distance fields for the printable ASCII range, generated on the CPU when it's
+// created. Text drawn with it stays sharp at any size and zoom, unlike the raster glyphs
+// of the TextRenderer, which are cheaper and what screen space text uses
+#[derive(Clone)]
+pub struct SdfFont {
+    font: Font,
+    atlas: Arc<SdfAtlas>,
+}
+
+// Offsets to the nearest seed pixel, far away where there's none yet
+const FAR: (i32, i32) = (9999, 9999);
+
+fn length_squared((x, y): (i32, i32)) -> i32 {
+    x * x + y * y
+}
+
+// 8SSEDT: distance from every pixel to the nearest pixel where `seed` is true, in two
+// passes over the grid, each propagating the nearest offset from already visited neighbours
+fn distances(seed: &[bool], width: usize, height: usize) -> Vec<f32> {
+    let mut grid: Vec<(i32, i32)> = seed
+        .iter()
+        .map(|&seed| if seed { (0, 0) } else { FAR })
+        .collect();
+    let compare = |grid: &mut [(i32, i32)], x: usize, y: usize, dx: i32, dy: i32| {
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+            return;
+        }
+        let other = grid[ny as usize * width + nx as usize];
+        let other = (other.0 + dx, other.1 + dy);
+        if length_squared(other) < length_squared(grid[y * width + x]) {
+            grid[y * width + x] = other;
+        }
+    };
+    for y in 0..height {
+        for x in 0..width {
+            compare(&mut grid, x, y, -1, 0);
+            compare(&mut grid, x, y, 0, -1);
+            compare(&mut grid, x, y, -1, -1);
+            compare(&mut grid, x, y, 1, -1);
+        }
+        for x in (0..width).rev() {
+            compare(&mut grid, x, y, 1, 0);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            compare(&mut grid, x, y, 1, 0);
+            compare(&mut grid, x, y, 0, 1);
+            compare(&mut grid, x, y, -1, 1);
+            compare(&mut grid, x, y, 1, 1);
+        }
+        for x in 0..width {
+            compare(&mut grid, x, y, -1, 0);
+        }
+    }
+    grid.into_iter()
+        .map(|offset| (length_squared(offset) as f32).sqrt())
+        .collect()
+}
+
+// Coverage bitmap to a padded distance field, 0.5 on the outline and more inside
+fn distance_field(coverage: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let pad = SPREAD as usize;
+    let (padded_width, padded_height) = (width + 2 * pad, height + 2 * pad);
+    let mut inside = vec![false; padded_width * padded_height];
+    for y in 0..height {
+        for x in 0..width {
+            inside[(y + pad) * padded_width + x + pad] = coverage[y * width + x] >= 128;
+        }
+    }
+    let outside: Vec<bool> = inside.iter().map(|&inside| !inside).collect();
+    let to_inside = distances(&inside, padded_width, padded_height);
+    let to_outside = distances(&outside, padded_width, padded_height);
+    let field = to_outside
+        .iter()
+        .zip(&to_inside)
+        .map(|(&inner, &outer)| {
+            let signed = inner - outer;
+            ((0.5 + signed / (2.0 * SPREAD)).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+    (field, padded_width, padded_height)
+}
+
+impl SdfFont {
+    pub fn new(font: &Font) -> Self {
+        let face = font.face();
+        let mut bitmaps = Vec::new();
+        for c in FIRST_CHAR..=LAST_CHAR {
+            let index = face.lookup_glyph_index(c);
+            let (metrics, coverage) = face.rasterize_indexed(index, BASE_PX);
+            let (field, width, height) = distance_field(&coverage, metrics.width, metrics.height);
+            let offset = Vec2::new(metrics.xmin as f32, metrics.ymin as f32) - SPREAD;
+            bitmaps.push((
+                Some(c),
+                index,
+                field,
+                width,
+                height,
+                offset,
+                metrics.advance_width,
+            ));
+        }
+        // An outlined box, for everything that isn't in the range above
+        let (box_width, box_height) = ((BASE_PX * 0.4) as usize, (BASE_PX * 0.65) as usize);
+        let border = (BASE_PX / 16.0).ceil() as usize;
+        let coverage: Vec<u8> = (0..box_width * box_height)
+            .map(|i| {
+                let (x, y) = (i % box_width, i / box_width);
+                let edge =
+                    x < border || y < border || x >= box_width - border || y >= box_height - border;
+                if edge {
+                    255
+                } else {
+                    0
+                }
+            })
+            .collect();
+        let (field, width, height) = distance_field(&coverage, box_width, box_height);
+        let tofu_offset = Vec2::new(BASE_PX * 0.05, 0.0) - SPREAD;
+        bitmaps.push((None, 0, field, width, height, tofu_offset, BASE_PX * 0.5));
+
+        // Shelves along a fixed width, as tall as they need to be
+        let (mut x, mut y, mut shelf) = (0u32, 0u32, 0u32);
+        let mut placed = Vec::new();
+        for (_, _, _, width, height, _, _) in &bitmaps {
+            let (width, height) = (*width as u32, *height as u32);
+            if x + width > ATLAS_WIDTH {
+                (x, y, shelf) = (0, y + shelf, 0);
+            }
+            placed.push((x, y));
+            x += width;
+            shelf = shelf.max(height);
+        }
+        let atlas_height = (y + shelf).next_power_of_two();
+        let mut pixels = vec![0u8; (ATLAS_WIDTH * atlas_height) as usize];
+        let mut glyphs = HashMap::new();
+        let mut tofu = None;
+        for ((c, index, field, width, height, offset, advance), (x, y)) in
+            bitmaps.into_iter().zip(placed)
+        {
+            for row in 0..height {
+                let start = (y as usize + row) * ATLAS_WIDTH as usize + x as usize;
+                pixels[start..start + width].copy_from_slice(&field[row * width..][..width]);
+            }
+            let glyph = SdfGlyph {
+                rect: (x, y, width as u32, height as u32),
+                offset,
+                advance,
+                index,
+            };
+            match c {
+                Some(c) => {
+                    glyphs.insert(c, glyph);
+                }
+                None => tofu = Some(glyph),
+            }
+        }
+
+        Self {
+            font: font.clone(),
+            atlas: Arc::new(SdfAtlas {
+                id: font.id(),
+                size: (ATLAS_WIDTH, atlas_height),
+                pixels,
+                glyphs,
+                tofu: tofu.expect("The tofu box is always generated"),
+            }),
+        }
+    }
+
+    // Quads for `text` at `size` world units per em. `position` is where the first
+    // baseline starts, lines go down from there. The bounds' y is their top edge
+    pub fn layout(
+        &self,
+        size: f32,
+        position: Vec2,
+        text: &str,
+        color: RgbaColor,
+        outline: Option<(RgbaColor, f32)>,
+    ) -> (Vec<SdfVertex>, TextBounds) {
+        let face = self.font.face();
+        let scale = size / BASE_PX;
+        let line = face.horizontal_line_metrics(size);
+        let (ascent, line_height) =
+            line.map_or((size * 0.8, size), |line| (line.ascent, line.new_line_size));
+        let color = color.to_f32_array_linear();
+        let (outline_color, outline_width) = match outline {
+            // World units to base pixels to distance field units
+            Some((color, width)) => (
+                color.to_f32_array_linear(),
+                (width / scale / (2.0 * SPREAD)).clamp(0.0, 0.49),
+            ),
+            None => ([0.0; 4], 0.0),
+        };
+        let (atlas_width, atlas_height) = (self.atlas.size.0 as f32, self.atlas.size.1 as f32);
+
+        let mut vertices = Vec::new();
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+        for (row, text_line) in text.split('\n').enumerate() {
+            lines = row + 1;
+            let baseline = position.y - row as f32 * line_height;
+            let mut pen = position.x;
+            let mut previous: Option<u16> = None;
+            for c in text_line.chars() {
+                let glyph = self.atlas.glyphs.get(&c).unwrap_or(&self.atlas.tofu);
+                if let Some(left) = previous {
+                    pen += face
+                        .horizontal_kern_indexed(left, glyph.index, size)
+                        .unwrap_or(0.0);
+                }
+                let (ax, ay, aw, ah) = glyph.rect;
+                if c != ' ' {
+                    let corner = Vec2::new(pen, baseline) + glyph.offset * scale;
+                    let (w, h) = (aw as f32 * scale, ah as f32 * scale);
+                    let (u0, u1) = (ax as f32 / atlas_width, (ax + aw) as f32 / atlas_width);
+                    // Bitmap rows go down, world y goes up
+                    let (v_top, v_bottom) =
+                        (ay as f32 / atlas_height, (ay + ah) as f32 / atlas_height);
+                    let vertex = |x: f32, y: f32, u: f32, v: f32| SdfVertex {
+                        position: [x, y],
+                        uv: [u, v],
+                        color,
+                        outline_color,
+                        outline_width,
+                    };
+                    let bottom_left = vertex(corner.x, corner.y, u0, v_bottom);
+                    let bottom_right = vertex(corner.x + w, corner.y, u1, v_bottom);
+                    let top_left = vertex(corner.x, corner.y + h, u0, v_top);
+                    let top_right = vertex(corner.x + w, corner.y + h, u1, v_top);
+                    vertices.extend([
+                        top_left,
+                        bottom_left,
+                        top_right,
+                        top_right,
+                        bottom_left,
+                        bottom_right,
+                    ]);
+                }
+                pen += glyph.advance * scale;
+                previous = (glyph.index != 0).then_some(glyph.index);
+            }
+            width = width.max(pen - position.x);
+        }
+        let bounds = TextBounds {
+            x: position.x,
+            y: position.y + ascent,
+            width,
+            height: lines as f32 * line_height,
+        };
+        (vertices, bounds)
+    }
+
+    pub fn measure(&self, size: f32, text: &str) -> TextBounds {
+        self.layout(
+            size,
+            Vec2::ZERO,
+            text,
+            RgbaColor::rgba(0.0, 0.0, 0.0, 0.0),
+            None,
+        )
+        .1
+    }
+}
+
+// Queued by Frame::draw_text_world
+pub struct SdfRun {
+    pub font: SdfFont,
+    pub vertices: Vec<SdfVertex>,
+}
+
+// World space SDF text through the 2D camera. Each font's atlas goes to the GPU the first
+// time text in it is drawn
+pub struct SdfTextRenderer {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    atlases: HashMap<usize, (Tracked<wgpu::Texture>, wgpu::TextureView)>,
+}
+
+impl SdfTextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bank: &mut RenderPipelineBank,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SDF Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        // The field has to be interpolated for the outline to come out smooth
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("SDF Text Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let shader =
+            shaders::create_module(device, "SDF Text Shader", include_str!("sdf_text.wgsl"));
+        bank.register_surface(
+            device,
+            "sdf_text",
+            &PipelineBuilder::new("SDF Text Pipeline", &shader)
+                .vertex_entry("vs_sdf_text")
+                .fragment_entry("fs_sdf_text")
+                .vertex_buffer(SdfVertex::desc())
+                .bind_group_layout(&layout)
+                .cull_mode(None)
+                .blend_mode(BlendMode::Alpha),
+            format,
+        );
+        Self {
+            layout,
+            sampler,
+            atlases: HashMap::new(),
+        }
+    }
+
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        atlas: &SdfAtlas,
+    ) {
+        if self.atlases.contains_key(&atlas.id) {
+            return;
+        }
+        let size = wgpu::Extent3d {
+            width: atlas.size.0,
+            height: atlas.size.1,
+            depth_or_array_layers: 1,
+        };
+        let texture = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("SDF Text Atlas"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            MemoryCategory::Textures,
+        );
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &atlas.pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(atlas.size.0),
+                rows_per_image: None,
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.atlases.insert(atlas.id, (texture, view));
+    }
+
+    // The text queued on `frame`, seen through `camera`, over the swapchain. Empties the queue
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        memory: &GpuMemoryTracker,
+        camera: &Camera2d,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let runs = std::mem::take(&mut frame.world_text);
+        let vertices: Vec<SdfVertex> = runs
+            .iter()
+            .flat_map(|run| run.vertices.iter().copied())
+            .collect();
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        for run in &runs {
+            self.upload(device, queue, memory, &run.font.atlas);
+        }
+
+        let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let vertex_buffer = pool.acquire(
+            device,
+            "SDF Text Vertex Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            bytes.len() as u64,
+        );
+        queue.write_buffer(&vertex_buffer, 0, bytes);
+        let uniform = CameraUniform::new(camera, viewport);
+        let camera_buffer = pool.acquire(
+            device,
+            "SDF Text Camera Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<CameraUniform>() as u64,
+        );
+        queue.write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let mut pass = frame.pass(
+            "SDF Text Pass",
+            &[(ColorTarget::Swapchain, wgpu::LoadOp::Load)],
+            targets,
+        );
+        pass.set_pipeline(bank, "sdf_text")?;
+        pass.raw
+            .set_vertex_buffer(0, vertex_buffer.slice(..bytes.len() as u64));
+        let mut first = 0;
+        for run in &runs {
+            let (_, view) = &self.atlases[&run.font.atlas.id];
+            let bind_group =
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("SDF Text Bind Group"),
+                    layout: &self.layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &camera_buffer,
+                                offset: 0,
+                                size: wgpu::BufferSize::new(
+                                    std::mem::size_of::<CameraUniform>() as u64
+                                ),
+                            }),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                });
+            let count = run.vertices.len() as u32;
+            pass.raw.set_bind_group(0, &bind_group, &[]);
+            pass.raw.draw(first..first + count, 0..1);
+            first += count;
+        }
+        Ok(())
+    }
+}
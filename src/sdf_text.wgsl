@@ -0,0 +1,61 @@
+// World space text from single channel distance fields. 0.5 in the atlas is the glyph
+// outline, the edge is antialiased over however much the field changes per pixel, so it
+// stays about one pixel wide at any zoom
+
+struct Camera2d {
+    center: vec2<f32>,
+    viewport: vec2<f32>,
+    zoom: f32,
+    _pad0: f32,
+    _pad1: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera2d;
+@group(0) @binding(1)
+var atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var atlas_sampler: sampler;
+
+struct SdfInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) outline_color: vec4<f32>,
+    @location(4) outline_width: f32,
+}
+
+struct SdfOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) outline_color: vec4<f32>,
+    @location(3) @interpolate(flat) outline_width: f32,
+}
+
+@vertex
+fn vs_sdf_text(in: SdfInput) -> SdfOutput {
+    var out: SdfOutput;
+    let pixels = (in.position - camera.center) * camera.zoom;
+    out.clip_position = vec4<f32>(pixels / (camera.viewport * 0.5), 0.0, 1.0);
+    out.uv = in.uv;
+    out.color = in.color;
+    out.outline_color = in.outline_color;
+    out.outline_width = in.outline_width;
+    return out;
+}
+
+@fragment
+fn fs_sdf_text(in: SdfOutput) -> @location(0) vec4<f32> {
+    let distance = textureSample(atlas, atlas_sampler, in.uv).r - 0.5;
+    // Half a pixel either side of the edge, in field units
+    let feather = max(length(vec2<f32>(dpdx(distance), dpdy(distance))) * 0.7, 1e-4);
+    let fill = smoothstep(-feather, feather, distance);
+    if in.outline_width <= 0.0 {
+        return vec4<f32>(in.color.rgb, in.color.a * fill);
+    }
+    // The outline grows outwards from the glyph's edge
+    let outer = smoothstep(-feather, feather, distance + in.outline_width);
+    let color = mix(in.outline_color, in.color, fill);
+    return vec4<f32>(color.rgb, color.a * outer);
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::ForayError;
+use crate::pipeline_bank::PipelineBuilder;
+use crate::shaders;
+
+// assets/shaders as the build script found it, for when it isn't on disk
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/shader_files.rs"));
+}
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/shaders");
+const MANIFEST: &str = "manifest.ron";
+
+// One line of the manifest: the file a pipeline's shader is in and which entry points
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShaderEntry {
+    pub file: String,
+    pub vs: String,
+    pub fs: String,
+}
+
+// The mesh shaders in assets/shaders, compiled once per file at startup and handed out by
+// pipeline name as builders with the right module and entry points
+pub struct ShaderBank {
+    entries: BTreeMap<String, ShaderEntry>,
+    modules: HashMap<String, wgpu::ShaderModule>,
+}
+
+impl ShaderBank {
+    // Err when the manifest doesn't parse or names a file that isn't there. Debug builds read
+    // the directory as it is now, so shader edits only need a restart
+    pub fn load(device: &wgpu::Device) -> Result<Self, ForayError> {
+        let error = |file: &str, reason: String| ForayError::ShaderFile {
+            file: file.to_owned(),
+            reason,
+        };
+        let manifest = read(MANIFEST).ok_or_else(|| error(MANIFEST, "not found".to_owned()))?;
+        let entries: BTreeMap<String, ShaderEntry> =
+            ron::from_str(&manifest).map_err(|e| error(MANIFEST, e.to_string()))?;
+
+        let mut modules = HashMap::new();
+        for (name, entry) in &entries {
+            if modules.contains_key(&entry.file) {
+                continue;
+            }
+            let source = read(&entry.file)
+                .ok_or_else(|| error(&entry.file, format!("not found, \"{name}\" is in it")))?;
+            let source = shaders::preprocess_with(&source, &read);
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&entry.file),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            modules.insert(entry.file.clone(), module);
+        }
+        Ok(Self { entries, modules })
+    }
+
+    // A builder for the manifest's `name`. The names are the code's own, so a missing one
+    // is a bug and panics like an unknown #include does
+    pub fn builder<'a>(&'a self, label: &'a str, name: &str) -> PipelineBuilder<'a> {
+        let entry = self
+            .entries
+            .get(name)
+            .unwrap_or_else(|| panic!("No shader \"{name}\" in the manifest"));
+        PipelineBuilder::new(label, &self.modules[&entry.file])
+            .vertex_entry(&entry.vs)
+            .fragment_entry(&entry.fs)
+    }
+}
+
+// Off the disk in debug builds, falling back to the embedded copy when it's not there
+fn read(file: &str) -> Option<String> {
+    if cfg!(debug_assertions) {
+        if let Ok(text) = std::fs::read_to_string(Path::new(SHADER_DIR).join(file)) {
+            return Some(text);
+        }
+    }
+    embedded::FILES
+        .iter()
+        .find(|(name, _)| *name == file)
+        .map(|(_, text)| (*text).to_owned())
+}
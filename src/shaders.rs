@@ -0,0 +1,95 @@
+// Tiny preprocessor so shaders can share code with `#include "file.wgsl"`.
+// WGSL has no include mechanism of its own, so we splice the text in ourselves.
+const INCLUDES: &[(&str, &str)] = &[
+    ("globals.wgsl", include_str!("globals.wgsl")),
+    ("sdf.wgsl", include_str!("sdf.wgsl")),
+    ("fullscreen.wgsl", include_str!("fullscreen.wgsl")),
+    ("depth.wgsl", include_str!("depth.wgsl")),
+    ("post.wgsl", include_str!("post.wgsl")),
+];
+
+// Where debug_print's buffer goes, see debug_channel.rs. Reflection turns down shaders that
+// put anything else there, in release builds too so they don't break in debug ones
+pub const DEBUG_GROUP: u32 = 0;
+pub const DEBUG_BINDING: u32 = 15;
+pub const DEBUG_NAME: &str = "debug_channel";
+// What shaders calling debug_print get without the channel, release builds always
+const DEBUG_PRINT_OFF: &str = "fn debug_print(tag: u32, value: f32) {}\n";
+
+pub fn preprocess(source: &str) -> String {
+    preprocess_with(source, &|_| None)
+}
+
+// Same, but `files` gets asked for each include first, for shaders that include their
+// neighbours in a directory (see ShaderBank). The built-in ones are still there after it
+pub fn preprocess_with(source: &str, files: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut seen = Vec::new();
+    let mut out = String::with_capacity(source.len());
+    expand(source, files, &mut seen, &mut out);
+    if out.contains("debug_print(") && !out.contains("fn debug_print(") {
+        out.push_str(debug_print());
+    }
+    out
+}
+
+#[cfg(debug_assertions)]
+fn debug_print() -> &'static str {
+    if crate::debug_channel::enabled() {
+        crate::debug_channel::SHADER
+    } else {
+        DEBUG_PRINT_OFF
+    }
+}
+
+#[cfg(not(debug_assertions))]
+fn debug_print() -> &'static str {
+    DEBUG_PRINT_OFF
+}
+
+// Each include is only pasted once, so diamond includes don't redefine structs
+fn expand(
+    source: &str,
+    files: &dyn Fn(&str) -> Option<String>,
+    seen: &mut Vec<String>,
+    out: &mut String,
+) {
+    for line in source.lines() {
+        if let Some(rest) = line.trim().strip_prefix("#include") {
+            let name = rest.trim().trim_matches('"');
+            if seen.iter().any(|n| n == name) {
+                continue;
+            }
+            let text = files(name)
+                .or_else(|| {
+                    INCLUDES
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, text)| (*text).to_owned())
+                })
+                .unwrap_or_else(|| panic!("Unknown shader include \"{name}\""));
+            seen.push(name.to_owned());
+            expand(&text, files, seen, out);
+        } else {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+}
+
+// The first #include in `source` that isn't one of the built-in files, which preprocess
+// would panic on. For shaders that come from outside the app
+pub fn unknown_include(source: &str) -> Option<&str> {
+    source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("#include"))
+        .map(|rest| rest.trim().trim_matches('"'))
+        .find(|name| INCLUDES.iter().all(|(n, _)| n != name))
+}
+
+// Preprocesses and compiles in one go
+pub fn create_module(device: &wgpu::Device, label: &str, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(preprocess(source).into()),
+    })
+}
@@ -0,0 +1,582 @@
+use std::ops::Range;
+
+use glam::Vec2;
+
+use crate::buffer_pool::BufferPool;
+use crate::camera2d::Camera2d;
+use crate::colors::RgbaColor;
+use crate::dirty::{self, DirtySet};
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::reflect::Reflection;
+use crate::shaders;
+use crate::targets::TargetRegistry;
+
+// Must match the constants in shapes.wgsl
+const KIND_LINE: u32 = 0;
+const KIND_DISC: u32 = 1;
+const KIND_RING: u32 = 2;
+const FLAG_WIDTH_PIXELS: u32 = 1;
+
+// What ShapeRenderer::blend can be set to besides Alpha, the U key cycles through them
+pub const BLEND_MODES: [BlendMode; 4] = [
+    BlendMode::Alpha,
+    BlendMode::Additive,
+    BlendMode::Multiply,
+    BlendMode::Premultiplied,
+];
+
+// Pixels stay the same on screen whatever the zoom, world units scale with it
+#[derive(Copy, Clone, Debug)]
+pub enum Width {
+    Pixels(f32),
+    World(f32),
+}
+
+impl Width {
+    fn split(self) -> (f32, u32) {
+        match self {
+            Width::Pixels(width) => (width, FLAG_WIDTH_PIXELS),
+            Width::World(width) => (width, 0),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Stroke {
+    Fill,
+    Outline(Width),
+}
+
+// One line, disc or ring. Positions and radii are in world units (see Camera2d). The
+// linear color is packed as Unorm8, 36 bytes a shape instead of 48
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShapeInstance {
+    a: [f32; 2],
+    b: [f32; 2],
+    color: [u8; 4],
+    radius: f32,
+    width: f32,
+    kind: u32,
+    flags: u32,
+}
+
+impl ShapeInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Unorm8x4,
+        3 => Float32,
+        4 => Float32,
+        5 => Uint32,
+        6 => Uint32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ShapeInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    pub fn line(p0: Vec2, p1: Vec2, width: Width, color: RgbaColor) -> Self {
+        let (width, flags) = width.split();
+        Self {
+            a: p0.into(),
+            b: p1.into(),
+            color: color.to_unorm8_array_linear(),
+            radius: 0.0,
+            width,
+            kind: KIND_LINE,
+            flags,
+        }
+    }
+
+    pub fn circle(center: Vec2, radius: f32, stroke: Stroke, color: RgbaColor) -> Self {
+        let (kind, (width, flags)) = match stroke {
+            Stroke::Fill => (KIND_DISC, (0.0, 0)),
+            Stroke::Outline(width) => (KIND_RING, width.split()),
+        };
+        Self {
+            a: center.into(),
+            b: center.into(),
+            color: color.to_unorm8_array_linear(),
+            radius,
+            width,
+            kind,
+            flags,
+        }
+    }
+}
+
+// Mirrors `struct Camera2d` in shapes.wgsl (and sdf_text.wgsl)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    center: [f32; 2],
+    viewport: [f32; 2],
+    zoom: f32,
+    _padding: [f32; 3],
+}
+
+impl CameraUniform {
+    // `viewport` is the size in pixels of the target drawn into
+    pub fn new(camera: &Camera2d, viewport: (u32, u32)) -> Self {
+        Self {
+            center: camera.center.into(),
+            viewport: camera.clip_extent(viewport),
+            zoom: camera.zoom,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+// Shapes kept on the GPU between frames, the scene's. Each update only writes what its
+// DirtySet marked, the rest of the buffer already holds it
+pub struct ShapeBuffer {
+    buffer: Option<Tracked<wgpu::Buffer>>,
+    // What the buffer holds. Debug builds compare it with what it should hold, so a change
+    // that wasn't marked shows up as an error instead of a stale shape
+    shadow: Vec<ShapeInstance>,
+    // Bytes and write_buffer calls the last update took, for FrameStats
+    pub uploaded: (u64, u32),
+}
+
+impl ShapeBuffer {
+    // Smallest the buffer gets, it grows to the next power of two from there
+    const MIN_CAPACITY: usize = 256;
+
+    pub fn new() -> Self {
+        Self {
+            buffer: None,
+            shadow: Vec::new(),
+            uploaded: (0, 0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shadow.len()
+    }
+
+    // `shapes` is everything that should be in the buffer now, `spans` which of them each
+    // item `dirty` marks by made. Growing the buffer or a different number of shapes
+    // rewrites it all
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        shapes: &[ShapeInstance],
+        spans: &[Range<usize>],
+        dirty: &mut DirtySet,
+    ) {
+        let stride = std::mem::size_of::<ShapeInstance>();
+        let capacity = self
+            .buffer
+            .as_ref()
+            .map_or(0, |buffer| buffer.size() as usize / stride);
+        if shapes.len() > capacity {
+            let capacity = shapes.len().next_power_of_two().max(Self::MIN_CAPACITY);
+            self.buffer = Some(memory.create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("Scene Shape Buffer"),
+                    size: (capacity * stride) as u64,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+                MemoryCategory::Meshes,
+            ));
+            dirty.mark_all();
+        } else if shapes.len() != self.shadow.len() {
+            dirty.mark_all();
+        }
+
+        let mut ranges = dirty.take_ranges(spans, shapes.len());
+        self.shadow
+            .resize(shapes.len(), bytemuck::Zeroable::zeroed());
+        for range in &ranges {
+            self.shadow[range.clone()].copy_from_slice(&shapes[range.clone()]);
+        }
+        if cfg!(debug_assertions)
+            && bytemuck::cast_slice::<_, u8>(&self.shadow) != bytemuck::cast_slice::<_, u8>(shapes)
+        {
+            log::error!("Scene shapes changed without being marked dirty, uploading all of them");
+            self.shadow.copy_from_slice(shapes);
+            ranges = dirty::everything(shapes.len());
+        }
+
+        self.uploaded = (0, 0);
+        let Some(buffer) = &self.buffer else {
+            return;
+        };
+        for range in ranges {
+            let bytes: &[u8] = bytemuck::cast_slice(&shapes[range.clone()]);
+            queue.write_buffer(buffer, (range.start * stride) as u64, bytes);
+            self.uploaded.0 += bytes.len() as u64;
+            self.uploaded.1 += 1;
+        }
+    }
+}
+
+// Draws the shapes queued on a Frame in one instanced draw, with the scene's ShapeBuffer
+// in between if the frame asked for it
+pub struct ShapeRenderer {
+    layout: wgpu::BindGroupLayout,
+    // Alpha unless changed, one of BLEND_MODES
+    pub blend: BlendMode,
+}
+
+impl ShapeRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bank: &mut RenderPipelineBank,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shape Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let source = include_str!("shapes.wgsl");
+        let shader = shaders::create_module(device, "Shape Shader", source);
+        let reflection = Reflection::of("Shape Shader", source);
+        let builder = PipelineBuilder::new("Shape Pipeline", &shader)
+            .reflect(reflection.as_ref())
+            .vertex_entry("vs_shape")
+            .fragment_entry("fs_shape")
+            .vertex_buffer(ShapeInstance::desc())
+            .bind_group_layout(&layout)
+            .cull_mode(None);
+        // Plain "shapes" is the alpha blended one, it's what scene items name
+        bank.register_surface(
+            device,
+            "shapes",
+            &builder.clone().blend_mode(BlendMode::Alpha),
+            format,
+        );
+        bank.register_surface_blends(device, format, "shapes", &builder, &BLEND_MODES[1..]);
+
+        Self {
+            layout,
+            blend: BlendMode::Alpha,
+        }
+    }
+
+    // On top of whatever is on the swapchain already, empties the frame's queue. `scene`
+    // goes where Frame::draw_scene_shapes put it
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        scene: &ShapeBuffer,
+        camera: &Camera2d,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let shapes = std::mem::take(&mut frame.shapes);
+        let scene = frame.scene_shapes_at.take().map(|at| (at, scene));
+        self.draw_instances(
+            device,
+            queue,
+            frame,
+            targets,
+            bank,
+            pool,
+            (&shapes, scene),
+            camera,
+            viewport,
+            (ColorTarget::Swapchain, wgpu::LoadOp::Load),
+        )
+    }
+
+    // `shapes` seen through `camera` into any target (in the format the pipeline was built
+    // for). `viewport` is the target's size in pixels
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_into(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        shapes: &[ShapeInstance],
+        camera: &Camera2d,
+        viewport: (u32, u32),
+        (target, load): (ColorTarget, wgpu::LoadOp<wgpu::Color>),
+    ) -> Result<(), ForayError> {
+        self.draw_instances(
+            device,
+            queue,
+            frame,
+            targets,
+            bank,
+            pool,
+            (shapes, None),
+            camera,
+            viewport,
+            (target, load),
+        )
+    }
+
+    // `shapes`, with the ShapeBuffer drawn before shapes[at..] when there is one. Still the
+    // one pass, just a draw either side of it
+    #[allow(clippy::too_many_arguments)]
+    fn draw_instances(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        (shapes, scene): (&[ShapeInstance], Option<(usize, &ShapeBuffer)>),
+        camera: &Camera2d,
+        viewport: (u32, u32),
+        (target, load): (ColorTarget, wgpu::LoadOp<wgpu::Color>),
+    ) -> Result<(), ForayError> {
+        let scene = scene.and_then(|(at, scene)| {
+            let buffer = scene.buffer.as_ref().filter(|_| scene.len() > 0)?;
+            Some((at.min(shapes.len()), buffer, scene.len() as u32))
+        });
+        if shapes.is_empty() && scene.is_none() {
+            // Still clears the target when asked to
+            if !matches!(load, wgpu::LoadOp::Load) {
+                drop(frame.pass("Shape Pass", &[(target, load)], targets));
+            }
+            return Ok(());
+        }
+
+        let bytes: &[u8] = bytemuck::cast_slice(shapes);
+        let instance_buffer = (!shapes.is_empty()).then(|| {
+            let buffer = pool.acquire(
+                device,
+                "Shape Instance Buffer",
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                bytes.len() as u64,
+            );
+            queue.write_buffer(&buffer, 0, bytes);
+            buffer
+        });
+        // Pooled rather than one buffer kept around, several cameras can draw in one frame
+        let uniform = CameraUniform::new(camera, viewport);
+        let camera_buffer = pool.acquire(
+            device,
+            "Shape Camera Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<CameraUniform>() as u64,
+        );
+        queue.write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&uniform));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shape Bind Group"),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &camera_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as u64),
+                }),
+            }],
+        });
+
+        let mut pass = frame.pass("Shape Pass", &[(target, load)], targets);
+        // Instanced and alpha blended, where the overdraw tends to pile up
+        pass.measure();
+        let pipeline = match self.blend {
+            BlendMode::Alpha => "shapes".to_owned(),
+            mode => mode.key("shapes"),
+        };
+        pass.set_pipeline(bank, &pipeline)?;
+        pass.raw.set_bind_group(0, &bind_group, &[]);
+        let count = shapes.len() as u32;
+        let (at, scene) = match scene {
+            Some((at, buffer, len)) => (at as u32, Some((buffer, len))),
+            None => (count, None),
+        };
+        if let Some(instance_buffer) = &instance_buffer {
+            pass.raw
+                .set_vertex_buffer(0, instance_buffer.slice(..bytes.len() as u64));
+            if at > 0 {
+                pass.raw.draw(0..6, 0..at);
+            }
+        }
+        if let Some((buffer, len)) = scene {
+            pass.raw.set_vertex_buffer(0, buffer.slice(..));
+            pass.raw.draw(0..6, 0..len);
+            if let Some(instance_buffer) = instance_buffer.as_ref().filter(|_| at < count) {
+                pass.raw
+                    .set_vertex_buffer(0, instance_buffer.slice(..bytes.len() as u64));
+                pass.raw.draw(0..6, at..count);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl ShapeInstance {
+    // Nothing in it that'd put a NaN or an infinity into a vertex
+    pub fn is_finite(&self) -> bool {
+        self.a
+            .iter()
+            .chain(&self.b)
+            .chain([&self.radius, &self.width])
+            .all(|value| value.is_finite())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::colors::{self, Colors};
+    use crate::frame::Background;
+    use crate::gpu_context::GpuContext;
+
+    // Of the targets `draw` takes, sRGB like the surface
+    pub const SIZE: (u32, u32) = (32, 32);
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // One frame of `shapes` (camera at the origin, a world unit a pixel) into `texture`,
+    // which starts out the way `background` says. Read it back with GpuContext::read_back
+    pub fn draw(
+        gpu: &GpuContext,
+        texture: &wgpu::Texture,
+        shapes: &[ShapeInstance],
+        background: Background,
+    ) {
+        draw_blended(gpu, texture, shapes, background, BlendMode::Alpha);
+    }
+
+    // `draw` with the renderer set to `blend`
+    pub fn draw_blended(
+        gpu: &GpuContext,
+        texture: &wgpu::Texture,
+        shapes: &[ShapeInstance],
+        background: Background,
+        blend: BlendMode,
+    ) {
+        let memory = GpuMemoryTracker::new();
+        let mut bank = RenderPipelineBank::new();
+        let mut renderer = ShapeRenderer::new(&gpu.device, FORMAT, &mut bank);
+        renderer.blend = blend;
+        let targets = TargetRegistry::new(SIZE, &memory);
+        let mut pool = BufferPool::new(&memory, 0);
+        let mut frame = Frame::offscreen(
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &gpu.device,
+            FORMAT,
+            background,
+        );
+        let load = frame.background.color();
+        renderer
+            .draw_into(
+                &gpu.device,
+                &gpu.queue,
+                &mut frame,
+                &targets,
+                &bank,
+                &mut pool,
+                shapes,
+                &Camera2d::new(),
+                SIZE,
+                (ColorTarget::Swapchain, load),
+            )
+            .unwrap();
+        frame.finish(&gpu.queue);
+    }
+
+    // `shapes` over a clear to `clear`
+    fn render(gpu: &GpuContext, shapes: &[ShapeInstance], clear: RgbaColor) -> image::RgbaImage {
+        let texture = gpu.target(SIZE, FORMAT);
+        draw(
+            gpu,
+            &texture,
+            shapes,
+            Background::Clear(clear.to_wgpu_linear()),
+        );
+        gpu.read_back(&texture)
+    }
+
+    #[test]
+    fn additive_overlaps_add_up() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        // Half intensity in linear, so the overlap lands on full
+        let half = colors::linear_to_srgb(0.5);
+        let color = RgbaColor::rgba(half, half, half, 1.0);
+        let quads = [
+            ShapeInstance::circle(Vec2::new(-8.0, 0.0), 12.0, Stroke::Fill, color),
+            ShapeInstance::circle(Vec2::new(8.0, 0.0), 12.0, Stroke::Fill, color),
+        ];
+        let texture = gpu.target(SIZE, FORMAT);
+        draw_blended(
+            gpu,
+            &texture,
+            &quads,
+            Background::Clear(wgpu::Color::BLACK),
+            BlendMode::Additive,
+        );
+        let image = gpu.read_back(&texture);
+        let expect = |x, y, linear: f64| {
+            let expected = (colors::linear_to_srgb(linear) * 255.0).round() as u8;
+            let pixel = image.get_pixel(x, y).0;
+            for channel in 0..3 {
+                assert!(
+                    pixel[channel].abs_diff(expected) <= 1,
+                    "({x}, {y}) is {pixel:?}, expected {expected}"
+                );
+            }
+        };
+        // Both, one of them and neither
+        expect(16, 16, 1.0);
+        expect(3, 16, 0.5);
+        expect(29, 16, 0.5);
+        expect(16, 1, 0.0);
+    }
+
+    #[test]
+    fn vertex_colors_match_the_same_clear() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        for color in [
+            RgbaColor::rgba(0.5, 0.0, 0.5, 1.0),
+            RgbaColor::rgba(0.2, 0.6, 0.9, 1.0),
+            RgbaColor::from_hex(0x08_10_20),
+        ] {
+            // Far bigger than the target, so every pixel is inside the disc
+            let disc = ShapeInstance::circle(Vec2::ZERO, 1000.0, Stroke::Fill, color);
+            let drawn = render(gpu, &[disc], Colors::WHITE);
+            let cleared = render(gpu, &[], color);
+            for (d, c) in drawn.pixels().zip(cleared.pixels()) {
+                for channel in 0..3 {
+                    assert!(
+                        d.0[channel].abs_diff(c.0[channel]) <= 1,
+                        "{color:?}: drawn {d:?}, cleared {c:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// Analytic 2D primitives. Each instance is a screen-space quad around the shape and the
+// fragment shader turns the distance to the ideal shape into coverage.
+
+struct Camera2d {
+    center: vec2<f32>,
+    viewport: vec2<f32>,
+    zoom: f32,
+    _pad0: f32,
+    _pad1: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera2d;
+
+const KIND_LINE: u32 = 0u;
+const KIND_DISC: u32 = 1u;
+const KIND_RING: u32 = 2u;
+
+const FLAG_WIDTH_PIXELS: u32 = 1u;
+
+// Width of the soft edge in pixels, `override shapes feather <pixels>` in the console
+override feather: f32 = 1.5;
+
+struct ShapeInstance {
+    @location(0) a: vec2<f32>,
+    @location(1) b: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) radius: f32,
+    @location(4) width: f32,
+    @location(5) kind: u32,
+    @location(6) flags: u32,
+}
+
+// Everything is in pixels relative to `a`, y up
+struct ShapeOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) b: vec2<f32>,
+    @location(3) @interpolate(flat) radius: f32,
+    @location(4) @interpolate(flat) half_width: f32,
+    @location(5) @interpolate(flat) kind: u32,
+}
+
+fn to_pixels(world: vec2<f32>) -> vec2<f32> {
+    return (world - camera.center) * camera.zoom;
+}
+
+@vertex
+fn vs_shape(@builtin(vertex_index) vertex_index: u32, shape: ShapeInstance) -> ShapeOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    let a = to_pixels(shape.a);
+    let b = to_pixels(shape.b) - a;
+    var half_width = shape.width * 0.5;
+    if (shape.flags & FLAG_WIDTH_PIXELS) == 0u {
+        half_width *= camera.zoom;
+    }
+    let radius = shape.radius * camera.zoom;
+
+    var local: vec2<f32>;
+    if shape.kind == KIND_LINE {
+        // Quad along the segment, padded on every side by the half width plus the feather
+        let len = length(b);
+        var dir = vec2<f32>(1.0, 0.0);
+        if len > 0.0 {
+            dir = b / len;
+        }
+        let normal = vec2<f32>(-dir.y, dir.x);
+        let pad = half_width + feather;
+        let along = mix(-pad, len + pad, corner.x * 0.5 + 0.5);
+        local = dir * along + normal * corner.y * pad;
+    } else {
+        var extent = radius + feather;
+        if shape.kind == KIND_RING {
+            extent += half_width;
+        }
+        local = corner * extent;
+    }
+
+    var out: ShapeOutput;
+    out.clip_position = vec4<f32>((a + local) / (camera.viewport * 0.5), 0.0, 1.0);
+    out.local = local;
+    out.color = shape.color;
+    out.b = b;
+    out.radius = radius;
+    out.half_width = half_width;
+    out.kind = shape.kind;
+    return out;
+}
+
+@fragment
+fn fs_shape(in: ShapeOutput) -> @location(0) vec4<f32> {
+    var d: f32;
+    switch in.kind {
+        case KIND_LINE: {
+            let h = clamp(dot(in.local, in.b) / max(dot(in.b, in.b), 1e-6), 0.0, 1.0);
+            d = length(in.local - in.b * h) - in.half_width;
+        }
+        case KIND_DISC: {
+            d = length(in.local) - in.radius;
+        }
+        default: {
+            d = abs(length(in.local) - in.radius) - in.half_width;
+        }
+    }
+
+    let coverage = 1.0 - smoothstep(-feather * 0.5, feather * 0.5, d);
+    if coverage <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use crate::assets::Assets;
+use crate::maintain::Maintain;
+use crate::pipeline_bank::RenderPipelineBank;
+
+// Code built around the app that has wrapping up of its own, a capture to write out or a
+// cursor to give back
+pub trait App {
+    // Runs first, once the loop has stopped drawing and before anything is torn down. GPU
+    // work started here (a last readback, say) is registered with `teardown.maintain` and
+    // waited out with everything else
+    fn on_exit(&mut self, teardown: &mut Teardown);
+}
+
+// What's still running when the app closes, and the device it runs on
+pub struct Teardown<'a> {
+    pub device: &'a wgpu::Device,
+    pub assets: &'a mut Assets,
+    pub pipelines: &'a mut RenderPipelineBank,
+    pub maintain: &'a mut Maintain,
+}
+
+// What didn't finish within the timeout and was left behind, each already warned about
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LeftBehind {
+    pub decodes: usize,
+    pub pipelines: usize,
+    pub gpu_operations: usize,
+}
+
+impl Teardown<'_> {
+    // The app's hook, then background work is stopped or waited on, each stage for at most
+    // `timeout` so a stuck decode or a hung device can't hold up the exit. Pipeline builds
+    // hold the device and map_async callbacks point into buffers, so both are done with
+    // before the caller drops anything
+    pub fn run(mut self, app: &mut impl App, timeout: Duration) -> LeftBehind {
+        println!("Shutdown: wrapping up");
+        app.on_exit(&mut self);
+
+        println!("Shutdown: stopping the asset loaders");
+        let decodes = self.assets.shutdown(timeout);
+        if decodes > 0 {
+            log::warn!("{decodes} asset loader(s) still decoding, leaving them behind");
+        }
+        println!("Shutdown: waiting on pipeline builds");
+        let pipelines = self.pipelines.finish_pending(timeout);
+        if pipelines > 0 {
+            log::warn!("{pipelines} pipeline(s) still building, leaving them behind");
+        }
+        // Whatever's left is failed when the device goes
+        println!("Shutdown: waiting for the GPU");
+        let gpu_operations = self.maintain.drain(self.device, timeout);
+        if gpu_operations > 0 {
+            log::warn!("{gpu_operations} GPU operation(s) never finished, leaving them behind");
+        }
+        LeftBehind {
+            decodes,
+            pipelines,
+            gpu_operations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::gpu_context::GpuContext;
+    use crate::maintain::OpDone;
+    use crate::stats;
+
+    // A copy into a mapped buffer, submitted and mapping, that finishes `done` once it's read
+    fn readback(device: &wgpu::Device, queue: &wgpu::Queue, done: OpDone) -> wgpu::Buffer {
+        let buffer = |label, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: 256,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let source = buffer("Shutdown Test Source", wgpu::BufferUsages::COPY_SRC);
+        let readback = buffer(
+            "Shutdown Test Readback",
+            wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(&source, 0, &readback, 0, 256);
+        stats::submit(queue, [encoder.finish()]);
+        readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                result.expect("Mapping after a copy works");
+                done.finish();
+            });
+        readback
+    }
+
+    // An app whose wrapping up is one more readback, like a recording's last frame
+    struct Recording<'q> {
+        queue: &'q wgpu::Queue,
+        last_frame: Option<(OpDone, wgpu::Buffer)>,
+    }
+
+    impl App for Recording<'_> {
+        fn on_exit(&mut self, teardown: &mut Teardown) {
+            let done = teardown.maintain.register("last frame", 3);
+            let buffer = readback(teardown.device, self.queue, done.clone());
+            self.last_frame = Some((done, buffer));
+        }
+    }
+
+    #[test]
+    fn shutdown_waits_out_pending_readbacks_and_the_apps_own() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let (mut assets, mut pipelines, mut maintain) =
+            (Assets::new(), RenderPipelineBank::new(), Maintain::new());
+        let screenshot = maintain.register("screenshot", 3);
+        let _buffer = readback(&gpu.device, &gpu.queue, screenshot.clone());
+        let mut app = Recording {
+            queue: &gpu.queue,
+            last_frame: None,
+        };
+
+        let timeout = Duration::from_secs(2);
+        let started = Instant::now();
+        let left = Teardown {
+            device: &gpu.device,
+            assets: &mut assets,
+            pipelines: &mut pipelines,
+            maintain: &mut maintain,
+        }
+        .run(&mut app, timeout);
+        assert!(started.elapsed() < timeout, "Took {:?}", started.elapsed());
+        assert_eq!(left, LeftBehind::default());
+        assert!(screenshot.is_done());
+        // Started in on_exit, so before the GPU was drained
+        let (last_frame, _buffer) = app.last_frame.expect("on_exit wasn't called");
+        assert!(last_frame.is_done());
+    }
+
+    #[test]
+    fn shutdown_leaves_what_never_finishes_behind_in_bounded_time() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let (mut assets, mut pipelines, mut maintain) =
+            (Assets::new(), RenderPipelineBank::new(), Maintain::new());
+        let screenshot = maintain.register("screenshot", 3);
+        let _buffer = readback(&gpu.device, &gpu.queue, screenshot.clone());
+        let _never = maintain.register("never", 3);
+
+        let timeout = Duration::from_millis(100);
+        let started = Instant::now();
+        let left = Teardown {
+            device: &gpu.device,
+            assets: &mut assets,
+            pipelines: &mut pipelines,
+            maintain: &mut maintain,
+        }
+        .run(
+            &mut Recording {
+                queue: &gpu.queue,
+                last_frame: None,
+            },
+            timeout,
+        );
+        // One timeout for the GPU, the loaders and pipelines had nothing to wait on
+        assert!(
+            started.elapsed() < timeout * 3,
+            "Took {:?}",
+            started.elapsed()
+        );
+        assert_eq!(left.gpu_operations, 1);
+        assert_eq!((left.decodes, left.pipelines), (0, 0));
+        assert!(screenshot.is_done());
+    }
+}
@@ -0,0 +1,79 @@
+use glam::Vec2;
+
+use crate::camera2d::Camera2d;
+
+// Grid lines closer together than this on screen get thinned out, see SnapGrid::step
+const MIN_LINE_GAP: f32 = 8.0;
+// Ruler labels are spaced at least this far apart, in pixels
+const MIN_LABEL_GAP: f32 = 80.0;
+
+// The world space grid of the 2D view. What's drawn and what dragged items snap to both
+// come from step(), so they can't disagree
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SnapGrid {
+    // World units between lines at zoom 1
+    pub spacing: f32,
+    // Snap dropped items (Ctrl while dragging snaps continuously either way)
+    pub enabled: bool,
+    pub rulers: bool,
+}
+
+impl SnapGrid {
+    pub fn new(spacing: f32) -> Self {
+        Self {
+            spacing,
+            enabled: false,
+            rulers: true,
+        }
+    }
+
+    // The spacing as it's drawn under `camera`. Zoomed far out it doubles until the lines
+    // are at least MIN_LINE_GAP pixels apart
+    pub fn step(&self, camera: &Camera2d) -> f32 {
+        let mut step = self.spacing;
+        while step * camera.zoom < MIN_LINE_GAP {
+            step *= 2.0;
+        }
+        step
+    }
+
+    // Nearest grid point. Rounding the quotient keeps this symmetric around 0, so negative
+    // coordinates snap the same way positive ones do
+    pub fn snap(&self, point: Vec2, camera: &Camera2d) -> Vec2 {
+        let step = self.step(camera);
+        (point / step).round() * step
+    }
+
+    // Positions of the lines between `min` and `max` along one axis. Computed from an index
+    // rather than by adding up steps, so long runs don't drift off the snap points
+    pub fn lines(&self, min: f32, max: f32, camera: &Camera2d) -> Vec<f32> {
+        Self::multiples(self.step(camera), min, max)
+    }
+
+    // Every how many world units a ruler gets a label, a multiple of the line step
+    pub fn label_step(&self, camera: &Camera2d) -> f32 {
+        let mut step = self.step(camera);
+        while step * camera.zoom < MIN_LABEL_GAP {
+            step *= 2.0;
+        }
+        step
+    }
+
+    pub fn labels(&self, min: f32, max: f32, camera: &Camera2d) -> Vec<f32> {
+        Self::multiples(self.label_step(camera), min, max)
+    }
+
+    pub fn halve(&mut self) {
+        self.spacing = (self.spacing * 0.5).max(1.0);
+    }
+
+    pub fn double(&mut self) {
+        self.spacing = (self.spacing * 2.0).min(10_000.0);
+    }
+
+    fn multiples(step: f32, min: f32, max: f32) -> Vec<f32> {
+        let first = (min / step).ceil() as i64;
+        let last = (max / step).floor() as i64;
+        (first..=last).map(|i| i as f32 * step).collect()
+    }
+}
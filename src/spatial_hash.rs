@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+// Entries that would cover more cells than this go on a list every query checks instead,
+// so one huge box doesn't fill thousands of cells
+const MAX_CELLS_PER_ENTRY: i64 = 64;
+
+// Uniform grid over world space (min, max) boxes, for finding what might be at a point or
+// overlapping a box without looking at everything. Entries are rebuilt as a whole whenever
+// things have moved, the cell size follows the average entry size so a typical entry
+// covers a cell or four
+#[derive(Clone, Debug, Default)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // (id, min, max) as given to rebuild
+    entries: Vec<(usize, Vec2, Vec2)>,
+    // Slots of the entries too big for the grid
+    oversized: Vec<usize>,
+}
+
+impl SpatialHash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces everything with these (id, min, max) boxes. Ids are the caller's, usually an
+    // index into its own list
+    pub fn rebuild(&mut self, entries: impl IntoIterator<Item = (usize, Vec2, Vec2)>) {
+        self.entries.clear();
+        self.entries.extend(entries);
+        self.oversized.clear();
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+
+        let sizes: f32 = self
+            .entries
+            .iter()
+            .map(|&(_, min, max)| (max - min).max_element())
+            .sum();
+        // Points and slivers still need some cell to land in
+        self.cell_size = (sizes / self.entries.len().max(1) as f32).max(1.0);
+
+        for (slot, &(_, min, max)) in self.entries.iter().enumerate() {
+            let (low, high) = (self.cell(min), self.cell(max));
+            if cells_between(low, high) > MAX_CELLS_PER_ENTRY {
+                self.oversized.push(slot);
+                continue;
+            }
+            for x in low.0..=high.0 {
+                for y in low.1..=high.1 {
+                    self.cells.entry((x, y)).or_default().push(slot);
+                }
+            }
+        }
+        // Cells that emptied out since the last rebuild
+        self.cells.retain(|_, bucket| !bucket.is_empty());
+    }
+
+    // Ids whose box contains `point`, edges included, in ascending order
+    pub fn query_point(&self, point: Vec2) -> Vec<usize> {
+        let bucket = self
+            .cells
+            .get(&self.cell(point))
+            .map_or(&[][..], Vec::as_slice);
+        let mut found: Vec<usize> = bucket
+            .iter()
+            .chain(&self.oversized)
+            .map(|&slot| self.entries[slot])
+            .filter(|&(_, min, max)| point.cmpge(min).all() && point.cmple(max).all())
+            .map(|(id, _, _)| id)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    // Ids whose box overlaps (min, max), touching counts, in ascending order
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<usize> {
+        let (low, high) = (self.cell(min), self.cell(max));
+        let mut slots = self.oversized.clone();
+        if cells_between(low, high) > self.cells.len() as i64 {
+            // Bigger than the occupied part of the grid, going through that is cheaper
+            for (&(x, y), bucket) in &self.cells {
+                if (low.0..=high.0).contains(&x) && (low.1..=high.1).contains(&y) {
+                    slots.extend_from_slice(bucket);
+                }
+            }
+        } else {
+            for x in low.0..=high.0 {
+                for y in low.1..=high.1 {
+                    if let Some(bucket) = self.cells.get(&(x, y)) {
+                        slots.extend_from_slice(bucket);
+                    }
+                }
+            }
+        }
+        // An entry spanning several of the cells shows up once per cell
+        slots.sort_unstable();
+        slots.dedup();
+        let mut found: Vec<usize> = slots
+            .into_iter()
+            .map(|slot| self.entries[slot])
+            .filter(|&(_, other_min, other_max)| {
+                min.cmple(other_max).all() && other_min.cmple(max).all()
+            })
+            .map(|(id, _, _)| id)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    // Cell holding `point`. A point exactly on a cell edge belongs to the cell above it,
+    // boxes reach into every cell their corners land in so both sides get searched
+    fn cell(&self, point: Vec2) -> (i32, i32) {
+        let cell = (point / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+}
+
+// How many cells from `low` to `high`, corners included. In i64 and saturating, a box
+// across the whole i32 range doesn't fit otherwise
+fn cells_between(low: (i32, i32), high: (i32, i32)) -> i64 {
+    (i64::from(high.0) - i64::from(low.0) + 1)
+        .saturating_mul(i64::from(high.1) - i64::from(low.1) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Boxes in a 1000 x 1000 world, mostly small with the odd big one, from a fixed seed
+    fn random_boxes(seed: u64, count: usize) -> Vec<(usize, Vec2, Vec2)> {
+        let mut state = seed;
+        let mut next = move || {
+            // Knuth's MMIX LCG, the top bits are the good ones
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 40) as f32 / (1u64 << 24) as f32
+        };
+        (0..count)
+            .map(|id| {
+                let min = Vec2::new(next(), next()) * 1000.0 - 500.0;
+                let size = if id % 17 == 0 {
+                    Vec2::new(next(), next()) * 600.0
+                } else {
+                    Vec2::new(next(), next()) * 30.0
+                };
+                (id, min, min + size)
+            })
+            .collect()
+    }
+
+    fn brute_point(entries: &[(usize, Vec2, Vec2)], point: Vec2) -> Vec<usize> {
+        entries
+            .iter()
+            .filter(|&&(_, min, max)| point.cmpge(min).all() && point.cmple(max).all())
+            .map(|&(id, _, _)| id)
+            .collect()
+    }
+
+    fn brute_aabb(entries: &[(usize, Vec2, Vec2)], min: Vec2, max: Vec2) -> Vec<usize> {
+        entries
+            .iter()
+            .filter(|&&(_, a, b)| min.cmple(b).all() && a.cmple(max).all())
+            .map(|&(id, _, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn entries_across_cells_are_found_from_each() {
+        let mut grid = SpatialHash::new();
+        // Average size 10, so the long one spans five cells one way and two the other
+        grid.rebuild([
+            (7, Vec2::new(0.0, 0.0), Vec2::new(45.0, 15.0)),
+            (3, Vec2::new(100.0, 100.0), Vec2::new(100.0, 100.0)),
+            (9, Vec2::new(-5.0, -5.0), Vec2::new(-1.0, -1.0)),
+        ]);
+        for x in [1.0, 12.0, 25.0, 38.0, 44.9] {
+            for y in [1.0, 14.0] {
+                assert_eq!(grid.query_point(Vec2::new(x, y)), [7], "({x}, {y})");
+            }
+        }
+        assert_eq!(grid.query_point(Vec2::new(46.0, 5.0)), Vec::<usize>::new());
+        // Reported once however many cells it and the query share
+        assert_eq!(
+            grid.query_aabb(Vec2::new(-10.0, -10.0), Vec2::new(200.0, 200.0)),
+            [3, 7, 9]
+        );
+    }
+
+    #[test]
+    fn boundaries_count_as_inside() {
+        let mut grid = SpatialHash::new();
+        // Cells of 10, the box's edges on cell edges
+        grid.rebuild([(1, Vec2::new(10.0, 10.0), Vec2::new(20.0, 20.0))]);
+        for corner in [
+            Vec2::new(10.0, 10.0),
+            Vec2::new(20.0, 20.0),
+            Vec2::new(10.0, 20.0),
+            Vec2::new(20.0, 15.0),
+        ] {
+            assert_eq!(grid.query_point(corner), [1], "{corner}");
+        }
+        assert!(grid.query_point(Vec2::new(20.0 + 1e-3, 15.0)).is_empty());
+        assert!(grid.query_point(Vec2::new(15.0, 10.0 - 1e-3)).is_empty());
+        // Touching boxes overlap, from either side of a cell edge
+        assert_eq!(
+            grid.query_aabb(Vec2::new(20.0, 20.0), Vec2::new(30.0, 30.0)),
+            [1]
+        );
+        assert_eq!(
+            grid.query_aabb(Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0)),
+            [1]
+        );
+        assert!(grid
+            .query_aabb(Vec2::new(0.0, 0.0), Vec2::new(9.99, 30.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        for seed in 1..=8 {
+            let entries = random_boxes(seed, 300);
+            let mut grid = SpatialHash::new();
+            grid.rebuild(entries.iter().copied());
+            let probes = random_boxes(seed + 100, 200);
+            for &(_, min, max) in &probes {
+                assert_eq!(grid.query_point(min), brute_point(&entries, min), "{min}");
+                assert_eq!(
+                    grid.query_aabb(min, max),
+                    brute_aabb(&entries, min, max),
+                    "{min} {max}"
+                );
+            }
+            // Corners of the entries themselves land exactly on box edges
+            for &(_, min, max) in entries.iter().take(50) {
+                assert_eq!(grid.query_point(max), brute_point(&entries, max));
+                assert_eq!(grid.query_point(min), brute_point(&entries, min));
+            }
+        }
+    }
+
+    #[test]
+    fn huge_boxes_and_queries_stay_bounded() {
+        let mut entries = random_boxes(42, 100);
+        entries.push((1000, Vec2::new(-1.0e9, -1.0e9), Vec2::new(1.0e9, 1.0e9)));
+        let mut grid = SpatialHash::new();
+        grid.rebuild(entries.iter().copied());
+        // The huge one is kept aside instead of filling cells
+        assert_eq!(grid.oversized.len(), 1);
+        assert!(grid
+            .cells
+            .values()
+            .all(|bucket| bucket.len() <= entries.len()));
+        assert!(grid.cells.len() <= entries.len() * MAX_CELLS_PER_ENTRY as usize);
+        assert!(grid.query_point(Vec2::new(5.0e8, -5.0e8)).contains(&1000));
+
+        // A query over everything goes through the occupied cells, not the whole range
+        let everything = grid.query_aabb(Vec2::splat(-f32::MAX), Vec2::splat(f32::MAX));
+        assert_eq!(everything.len(), entries.len());
+        let (min, max) = (Vec2::new(-300.0, -300.0), Vec2::new(300.0, 300.0));
+        assert_eq!(grid.query_aabb(min, max), brute_aabb(&entries, min, max));
+
+        // Rebuilding without it leaves nothing of it behind
+        grid.rebuild(entries[..100].iter().copied());
+        assert!(grid.oversized.iter().all(|&slot| slot < 100));
+        assert!(!grid.query_point(Vec2::new(5.0e8, -5.0e8)).contains(&1000));
+    }
+}
@@ -0,0 +1,461 @@
+use glam::Vec2;
+use image::RgbaImage;
+
+use crate::buffer_pool::BufferPool;
+use crate::camera2d::Camera2d;
+use crate::colors::{self, RgbaColor};
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::reflect::Reflection;
+use crate::rng::Rng;
+use crate::shaders;
+use crate::shapes::CameraUniform;
+use crate::targets::TargetRegistry;
+
+// What ArrayTexture::new does with an image that isn't the tile size
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TilePolicy {
+    // Scaled to fit the tile keeping its aspect, centered on transparent
+    Letterbox,
+    // Refused with ForayError::TileSize
+    Reject,
+}
+
+// One image of an ArrayTexture, which of its arrays and which layer in it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpriteImage {
+    pub array: u32,
+    pub layer: u32,
+}
+
+// Same-sized images stacked as the layers of 2D texture arrays, so sprites showing any of
+// them draw in one batch. More images than the device's max_texture_array_layers spill
+// into another array, which is another batch. For images of all sizes an atlas fits
+// better, this is for uniform tiles
+pub struct ArrayTexture {
+    // Kept alive for the bind groups, one per array
+    _textures: Vec<Tracked<wgpu::Texture>>,
+    bind_groups: Vec<wgpu::BindGroup>,
+    images: Vec<SpriteImage>,
+}
+
+impl ArrayTexture {
+    // `images` are sRGB, each with the name errors call it by
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        renderer: &SpriteRenderer,
+        tile: (u32, u32),
+        images: &[(String, RgbaImage)],
+        policy: TilePolicy,
+    ) -> Result<Self, ForayError> {
+        let tiles = images
+            .iter()
+            .map(|(label, image)| fit(label, image, tile, policy))
+            .collect::<Result<Vec<_>, _>>()?;
+        let max_layers = device.limits().max_texture_array_layers.max(1) as usize;
+        let mut textures = Vec::new();
+        let mut bind_groups = Vec::new();
+        for chunk in tiles.chunks(max_layers) {
+            let size = wgpu::Extent3d {
+                width: tile.0,
+                height: tile.1,
+                depth_or_array_layers: chunk.len() as u32,
+            };
+            let texture = memory.create_texture(
+                device,
+                &wgpu::TextureDescriptor {
+                    label: Some("Sprite Array"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+                MemoryCategory::Textures,
+            );
+            let texels: Vec<u8> = chunk.iter().flat_map(RgbaImage::as_raw).copied().collect();
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &texels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(tile.0 * 4),
+                    rows_per_image: Some(tile.1),
+                },
+                size,
+            );
+            // A one layer array would otherwise come out as a plain 2D view
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+            bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Sprite Array Bind Group"),
+                layout: &renderer.images_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&renderer.sampler),
+                    },
+                ],
+            }));
+            textures.push(texture);
+        }
+        let images = (0..tiles.len())
+            .map(|index| SpriteImage {
+                array: (index / max_layers) as u32,
+                layer: (index % max_layers) as u32,
+            })
+            .collect();
+        Ok(Self {
+            _textures: textures,
+            bind_groups,
+            images,
+        })
+    }
+
+    // The `index`th of the images it was made from
+    pub fn image(&self, index: usize) -> SpriteImage {
+        self.images[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn arrays(&self) -> usize {
+        self.bind_groups.len()
+    }
+
+    // For the images layout, what a SpriteImage's array is drawn with
+    pub fn bind_group(&self, array: u32) -> &wgpu::BindGroup {
+        &self.bind_groups[array as usize]
+    }
+}
+
+// `image` as a tile, or the error the policy asks for
+fn fit(
+    label: &str,
+    image: &RgbaImage,
+    tile: (u32, u32),
+    policy: TilePolicy,
+) -> Result<RgbaImage, ForayError> {
+    if image.dimensions() == tile {
+        return Ok(image.clone());
+    }
+    if policy == TilePolicy::Reject || image.width() == 0 || image.height() == 0 {
+        return Err(ForayError::TileSize {
+            image: label.to_owned(),
+            size: image.dimensions(),
+            tile,
+        });
+    }
+    let scale = (tile.0 as f32 / image.width() as f32).min(tile.1 as f32 / image.height() as f32);
+    let width = ((image.width() as f32 * scale).round() as u32).clamp(1, tile.0);
+    let height = ((image.height() as f32 * scale).round() as u32).clamp(1, tile.1);
+    let scaled =
+        image::imageops::resize(image, width, height, image::imageops::FilterType::Triangle);
+    let mut letterboxed = RgbaImage::new(tile.0, tile.1);
+    image::imageops::overlay(
+        &mut letterboxed,
+        &scaled,
+        i64::from((tile.0 - width) / 2),
+        i64::from((tile.1 - height) / 2),
+    );
+    Ok(letterboxed)
+}
+
+// One sprite, centered on a world position. The tint multiplies the image and is packed
+// linear as Unorm8 like ShapeInstance's color
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstance {
+    center: [f32; 2],
+    size: [f32; 2],
+    tint: [u8; 4],
+    layer: u32,
+}
+
+impl SpriteInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Unorm8x4,
+        3 => Uint32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// A sprite and the array its image is in, which picks its batch
+#[derive(Copy, Clone, Debug)]
+pub struct Sprite {
+    array: u32,
+    instance: SpriteInstance,
+}
+
+impl Sprite {
+    pub fn new(image: SpriteImage, center: Vec2, size: Vec2, tint: RgbaColor) -> Self {
+        Self {
+            array: image.array,
+            instance: SpriteInstance {
+                center: center.into(),
+                size: size.into(),
+                tint: tint.to_unorm8_array_linear(),
+                layer: image.layer,
+            },
+        }
+    }
+}
+
+// Draws sprites out of an ArrayTexture, one instanced draw per array they use
+pub struct SpriteRenderer {
+    // TileRenderer's pipeline takes the same two, so tile maps draw out of ArrayTextures too
+    pub camera_layout: wgpu::BindGroupLayout,
+    pub images_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl SpriteRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bank: &mut RenderPipelineBank,
+    ) -> Self {
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Camera Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let images_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Images Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sprite Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let source = include_str!("sprites.wgsl");
+        let shader = shaders::create_module(device, "Sprite Shader", source);
+        let reflection = Reflection::of("Sprite Shader", source);
+        bank.register_surface(
+            device,
+            "sprites",
+            &PipelineBuilder::new("Sprite Pipeline", &shader)
+                .reflect(reflection.as_ref())
+                .vertex_entry("vs_sprite")
+                .fragment_entry("fs_sprite")
+                .vertex_buffer(SpriteInstance::desc())
+                .bind_group_layout(&camera_layout)
+                .bind_group_layout(&images_layout)
+                .blend_mode(BlendMode::Alpha)
+                .cull_mode(None),
+            format,
+        );
+        Self {
+            camera_layout,
+            images_layout,
+            sampler,
+        }
+    }
+
+    // Onto the swapchain, clearing it first. Sprites are grouped by array, so ones from a
+    // later array draw over earlier arrays' whatever order they came in. Within an array
+    // they keep their order
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        images: &ArrayTexture,
+        sprites: &[Sprite],
+        camera: &Camera2d,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let mut pass = frame.pass(
+            "Sprite Pass",
+            &[(ColorTarget::Swapchain, frame.background.color())],
+            targets,
+        );
+        if sprites.is_empty() {
+            return Ok(());
+        }
+        let mut sorted = sprites.to_vec();
+        sorted.sort_by_key(|sprite| sprite.array);
+        let instances: Vec<SpriteInstance> = sorted.iter().map(|sprite| sprite.instance).collect();
+        let bytes: &[u8] = bytemuck::cast_slice(&instances);
+        let instance_buffer = pool.acquire(
+            device,
+            "Sprite Instance Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            bytes.len() as u64,
+        );
+        queue.write_buffer(&instance_buffer, 0, bytes);
+        let uniform = CameraUniform::new(camera, viewport);
+        let camera_buffer = pool.acquire(
+            device,
+            "Sprite Camera Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<CameraUniform>() as u64,
+        );
+        queue.write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&uniform));
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Camera Bind Group"),
+            layout: &self.camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &camera_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as u64),
+                }),
+            }],
+        });
+
+        pass.set_pipeline(bank, "sprites")?;
+        pass.raw.set_bind_group(0, &camera_bind_group, &[]);
+        pass.raw
+            .set_vertex_buffer(0, instance_buffer.slice(..bytes.len() as u64));
+        // One batch per run of the same array
+        let mut start = 0;
+        for run in sorted.chunk_by(|a, b| a.array == b.array) {
+            let end = start + run.len() as u32;
+            pass.raw
+                .set_bind_group(1, images.bind_group(run[0].array), &[]);
+            pass.draw_sprites(start..end);
+            start = end;
+        }
+        Ok(())
+    }
+}
+
+// Sprites in the stress test unless the sprites command asks for another count
+pub const STRESS_SPRITES: usize = 10_000;
+// Images in the stress test, and the side of each
+const STRESS_IMAGES: usize = 64;
+const STRESS_TILE: u32 = 32;
+
+// The J view: sprites over 64 generated images, to watch FrameStats' draw count stay at
+// one per array
+pub struct SpriteStress {
+    pub images: ArrayTexture,
+    pub sprites: Vec<Sprite>,
+}
+
+impl SpriteStress {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        renderer: &SpriteRenderer,
+        count: usize,
+        policy: TilePolicy,
+    ) -> Result<Self, ForayError> {
+        let images = ArrayTexture::new(
+            device,
+            queue,
+            memory,
+            renderer,
+            (STRESS_TILE, STRESS_TILE),
+            &stress_images(),
+            policy,
+        )?;
+        let mut rng = Rng::new(0);
+        let sprites = (0..count)
+            .map(|_| {
+                let center = Vec2::new(rng.range(-600.0..600.0), rng.range(-350.0..350.0));
+                let size = Vec2::splat(rng.range(12.0..40.0));
+                let image = images.image(rng.below(images.len()));
+                Sprite::new(image, center, size, colors::Colors::WHITE)
+            })
+            .collect();
+        println!(
+            "Sprite stress: {count} sprites over {} images in {} array(s)",
+            images.len(),
+            images.arrays()
+        );
+        Ok(Self { images, sprites })
+    }
+}
+
+// Discs, rings and squares around the palette. Every eighth one is narrower than the tile,
+// so the policy has something to do
+fn stress_images() -> Vec<(String, RgbaImage)> {
+    colors::palette(STRESS_IMAGES)
+        .into_iter()
+        .enumerate()
+        .map(|(index, color)| {
+            let width = if index % 8 == 7 {
+                STRESS_TILE * 3 / 4
+            } else {
+                STRESS_TILE
+            };
+            let [red, green, blue, _] = color.to_unorm8_array();
+            let half = (width as f32 / 2.0, STRESS_TILE as f32 / 2.0);
+            let image = RgbaImage::from_fn(width, STRESS_TILE, |x, y| {
+                let (dx, dy) = (x as f32 + 0.5 - half.0, y as f32 + 0.5 - half.1);
+                let distance = (dx * dx + dy * dy).sqrt() / half.0.min(half.1);
+                let inside = match index % 3 {
+                    0 => distance < 0.9,
+                    1 => (0.55..0.9).contains(&distance),
+                    _ => dx.abs() < half.0 * 0.8 && dy.abs() < half.1 * 0.8,
+                };
+                image::Rgba([red, green, blue, if inside { 255 } else { 0 }])
+            });
+            (format!("stress {index}"), image)
+        })
+        .collect()
+}
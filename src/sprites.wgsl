@@ -0,0 +1,60 @@
+// Textured quads out of a 2D texture array, one layer per image. Positions are in world
+// units through the same camera as shapes.wgsl
+
+struct Camera2d {
+    center: vec2<f32>,
+    viewport: vec2<f32>,
+    zoom: f32,
+    _pad0: f32,
+    _pad1: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera2d;
+
+@group(1) @binding(0)
+var images: texture_2d_array<f32>;
+@group(1) @binding(1)
+var images_sampler: sampler;
+
+struct SpriteInstance {
+    @location(0) center: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) tint: vec4<f32>,
+    @location(3) layer: u32,
+}
+
+struct SpriteOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    @location(2) @interpolate(flat) layer: u32,
+}
+
+@vertex
+fn vs_sprite(@builtin(vertex_index) vertex_index: u32, sprite: SpriteInstance) -> SpriteOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let world = sprite.center + corner * sprite.size * 0.5;
+    let pixels = (world - camera.center) * camera.zoom;
+
+    var out: SpriteOutput;
+    out.clip_position = vec4<f32>(pixels / (camera.viewport * 0.5), 0.0, 1.0);
+    // Image rows go down, world y goes up
+    out.uv = vec2<f32>(corner.x * 0.5 + 0.5, 0.5 - corner.y * 0.5);
+    out.tint = sprite.tint;
+    out.layer = sprite.layer;
+    return out;
+}
+
+@fragment
+fn fs_sprite(in: SpriteOutput) -> @location(0) vec4<f32> {
+    return textureSample(images, images_sampler, in.uv, in.layer) * in.tint;
+}
@@ -0,0 +1,202 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use crate::effects::ColorBlindMode;
+use crate::memory::{format_bytes, MemoryCategory, MemoryReport};
+use crate::pipeline_stats::PassStatistics;
+use crate::tilemap::TileCounts;
+
+thread_local! {
+    // Submits from this thread since the last take_submits. Per thread so the frame loop's
+    // count isn't mixed up with anything submitting beside it, a test on another thread say
+    static SUBMITS: Cell<u32> = const { Cell::new(0) };
+}
+
+// Every queue submit goes through here, so the frame's count has all of them: the frame's
+// own, warm-up draws, blocking readbacks, the crash hook's screenshot
+pub fn submit<I>(queue: &wgpu::Queue, command_buffers: I) -> wgpu::SubmissionIndex
+where
+    I: IntoIterator<Item = wgpu::CommandBuffer>,
+{
+    SUBMITS.with(|submits| submits.set(submits.get() + 1));
+    queue.submit(command_buffers)
+}
+
+// Submits on this thread since the last call
+pub fn take_submits() -> u32 {
+    SUBMITS.with(|submits| submits.replace(0))
+}
+
+// Numbers about the last frames, shown by the debug overlay
+pub struct FrameStats {
+    pub frame_index: u64,
+    pub frame_time: Duration,
+    // Exponentially smoothed so the readout doesn't flicker
+    pub fps: f32,
+    pub memory: MemoryReport,
+    // Live bytes per scene they were allocated under, more than one means a scene leaked
+    pub scene_memory: Vec<(String, u64)>,
+    // Set by whoever renders, before end_frame
+    pub placeholder_draws: u32,
+    pub pipelines_building: usize,
+    // Drawn through Pass mesh draws, instanced shapes and text aren't counted
+    pub triangles: u64,
+    pub mesh_draws: u64,
+    // How many of those draws had to bind their vertex and index buffers first
+    pub buffer_binds: u64,
+    // Through SpriteRenderer, and in how many instanced draws
+    pub sprites: u64,
+    pub sprite_batches: u64,
+    // Chunks of the tile map drawn out of all it has, None when the view doesn't draw one
+    pub tiles: Option<TileCounts>,
+    // Bytes and writes the scene's ShapeBuffer took this frame, None when the view doesn't
+    // draw the scene
+    pub scene_upload: Option<(u64, u32)>,
+    // Queue submits (through submit) since the frame before. All of a frame's passes go into
+    // the Frame's one encoder, so it should stay at 1
+    pub frame_submits: u32,
+    // Pipeline statistics of the passes that measure themselves, from a frame or two back.
+    // Empty when the adapter can't count
+    pub passes: Vec<PassStatistics>,
+    // What overdraw is relative to
+    pub surface_pixels: u64,
+    // Of the monitor the window is on, 0 when unknown
+    pub refresh_rate: u32,
+    // Where between the last two fixed updates the frame was drawn
+    pub interpolation_alpha: f32,
+    // Samples in the running average and the format it's kept in, when accumulating
+    pub accumulation: Option<(u32, wgpu::TextureFormat)>,
+    // What the post chain is simulating, shown unless Off
+    pub color_blind: ColorBlindMode,
+    last_frame: Instant,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self {
+            frame_index: 0,
+            frame_time: Duration::ZERO,
+            fps: 0.0,
+            memory: MemoryReport::default(),
+            scene_memory: Vec::new(),
+            placeholder_draws: 0,
+            pipelines_building: 0,
+            triangles: 0,
+            mesh_draws: 0,
+            buffer_binds: 0,
+            sprites: 0,
+            sprite_batches: 0,
+            tiles: None,
+            scene_upload: None,
+            frame_submits: 0,
+            passes: Vec::new(),
+            surface_pixels: 0,
+            refresh_rate: 0,
+            interpolation_alpha: 0.0,
+            accumulation: None,
+            color_blind: ColorBlindMode::Off,
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn end_frame(&mut self, memory: MemoryReport) {
+        let now = Instant::now();
+        self.frame_time = now - self.last_frame;
+        self.last_frame = now;
+        self.frame_index += 1;
+        self.memory = memory;
+        self.frame_submits = take_submits();
+
+        let instant_fps = 1.0 / self.frame_time.as_secs_f32().max(f32::EPSILON);
+        self.fps = if self.fps == 0.0 {
+            instant_fps
+        } else {
+            self.fps * 0.9 + instant_fps * 0.1
+        };
+    }
+
+    // What the overlay prints, one line per entry
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "FPS {:.0} ({:.2} ms)",
+                self.fps,
+                self.frame_time.as_secs_f64() * 1000.0
+            ),
+            format!(
+                "Refresh {} Hz, interpolation {:.2}",
+                self.refresh_rate, self.interpolation_alpha
+            ),
+            format!(
+                "Pipelines building {} (placeholder draws {})",
+                self.pipelines_building, self.placeholder_draws
+            ),
+            format!(
+                "Triangles {} in {} draws ({} buffer binds), {} submit(s)",
+                self.triangles, self.mesh_draws, self.buffer_binds, self.frame_submits
+            ),
+            format!("GPU memory {}", format_bytes(self.memory.total_bytes())),
+        ];
+        if self.sprites > 0 {
+            lines.insert(
+                4,
+                format!("Sprites {} in {} draws", self.sprites, self.sprite_batches),
+            );
+        }
+        if let Some(tiles) = self.tiles {
+            lines.insert(
+                4,
+                format!(
+                    "Tile chunks {} of {} drawn, {} rebuilt",
+                    tiles.drawn, tiles.total, tiles.rebuilt
+                ),
+            );
+        }
+        if let Some((bytes, writes)) = self.scene_upload {
+            lines.insert(
+                4,
+                format!(
+                    "Scene shapes uploaded {} in {writes} write(s)",
+                    format_bytes(bytes)
+                ),
+            );
+        }
+        if let Some((samples, format)) = self.accumulation {
+            lines.insert(3, format!("Accumulated {samples} samples ({format:?})"));
+        }
+        for pass in &self.passes {
+            lines.push(format!(
+                "{}: {} vertices, {} primitives, overdraw ≈ {:.2}",
+                pass.pass,
+                pass.vertex_invocations,
+                pass.primitives,
+                pass.fragment_invocations as f64 / self.surface_pixels.max(1) as f64
+            ));
+        }
+        if self.color_blind != ColorBlindMode::Off {
+            lines.push(format!(
+                "Color blind simulation: {}",
+                self.color_blind.name()
+            ));
+        }
+        lines.extend(MemoryCategory::ALL.iter().map(|&category| {
+            format!(
+                "  {:<9} {}",
+                category.name(),
+                format_bytes(self.memory.bytes_in(category))
+            )
+        }));
+        let transient = self.memory.bytes_in(MemoryCategory::Transient);
+        lines.push(format!(
+            "    active {} / pooled {}",
+            format_bytes(transient - self.memory.pool_idle.min(transient)),
+            format_bytes(self.memory.pool_idle)
+        ));
+        lines.extend(
+            self.scene_memory
+                .iter()
+                .map(|(scene, bytes)| format!("  scene {scene} {}", format_bytes(*bytes))),
+        );
+        lines
+    }
+}
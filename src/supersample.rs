@@ -0,0 +1,304 @@
+use glam::Vec2;
+use image::RgbaImage;
+
+use crate::colors::{linear_to_srgb, srgb_to_linear};
+
+// Shift+F12 renders this many jittered samples at this multiple of the window's size,
+// unless --supersample and --supersample-scale say otherwise
+pub const DEFAULT_SAMPLES: u32 = 16;
+pub const DEFAULT_SCALE: u32 = 2;
+// Each sample is a full readback, past this it's just slow
+pub const MAX_SAMPLES: u32 = 256;
+pub const MAX_SCALE: u32 = 4;
+
+// Where sample `index` sits inside its pixel, -0.5 to 0.5 both ways. Halton 2, 3, so any
+// number of samples covers the pixel about evenly
+fn jitter(index: u32) -> Vec2 {
+    Vec2::new(halton(index + 1, 2), halton(index + 1, 3)) - 0.5
+}
+
+// What a supersampled capture draws: the view, with its cameras shifted a little before
+// each sample
+pub trait Jittered {
+    // Shifts every camera by `offset` pixels of a `size` render. Zero puts them back
+    fn jitter(&mut self, offset: Vec2, size: (u32, u32));
+    // Draws a sample at `size` and reads it back
+    fn sample(&mut self, size: (u32, u32)) -> Result<RgbaImage, String>;
+    // After the last sample, whether or not they all came back. Anything that averaged
+    // frames at the capture's size starts over
+    fn finish(&mut self);
+}
+
+// `samples` jittered samples of `subject` at `size`, averaged and shrunk by `scale`. The
+// jitter's back at zero afterwards, even when a sample fails
+pub fn capture(
+    subject: &mut impl Jittered,
+    size: (u32, u32),
+    samples: u32,
+    scale: u32,
+    srgb: bool,
+) -> Result<RgbaImage, String> {
+    let mut accumulation = Accumulation::new(size, srgb);
+    let mut result = Ok(());
+    for index in 0..samples {
+        subject.jitter(jitter(index), size);
+        match subject.sample(size) {
+            Ok(image) => accumulation.add(&image),
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    subject.jitter(Vec2::ZERO, size);
+    subject.finish();
+    result.map(|()| accumulation.resolve(scale))
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+// The running sum of the samples read back so far, in linear light when the surface
+// encodes sRGB so edges average the way light does
+struct Accumulation {
+    size: (u32, u32),
+    srgb: bool,
+    sum: Vec<f32>,
+    samples: u32,
+}
+
+impl Accumulation {
+    fn new(size: (u32, u32), srgb: bool) -> Self {
+        Self {
+            size,
+            srgb,
+            sum: vec![0.0; size.0 as usize * size.1 as usize * 4],
+            samples: 0,
+        }
+    }
+
+    // One sample, the size Accumulation was made for
+    fn add(&mut self, image: &RgbaImage) {
+        for (sum, (index, &value)) in self.sum.iter_mut().zip(image.as_raw().iter().enumerate()) {
+            let value = f64::from(value) / 255.0;
+            *sum += if self.srgb && index % 4 != 3 {
+                srgb_to_linear(value) as f32
+            } else {
+                value as f32
+            };
+        }
+        self.samples += 1;
+    }
+
+    // The average, shrunk by `scale` with a tent filter, as 8 bits like the surface has
+    fn resolve(&self, scale: u32) -> RgbaImage {
+        let samples = self.samples.max(1) as f32;
+        let average: Vec<f32> = self.sum.iter().map(|sum| sum / samples).collect();
+        let (width, height) = (self.size.0 / scale, self.size.1 / scale);
+        let weights = tent(scale);
+        let mut image = RgbaImage::new(width, height);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let mut color = [0.0f32; 4];
+            let mut total = 0.0;
+            for &(dy, wy) in &weights {
+                let sy = i64::from(y * scale) + dy;
+                if sy < 0 || sy >= i64::from(self.size.1) {
+                    continue;
+                }
+                for &(dx, wx) in &weights {
+                    let sx = i64::from(x * scale) + dx;
+                    if sx < 0 || sx >= i64::from(self.size.0) {
+                        continue;
+                    }
+                    let weight = wx * wy;
+                    let at = (sy as usize * self.size.0 as usize + sx as usize) * 4;
+                    for (channel, value) in color.iter_mut().zip(&average[at..at + 4]) {
+                        *channel += value * weight;
+                    }
+                    total += weight;
+                }
+            }
+            for (channel, (out, value)) in pixel.0.iter_mut().zip(color).enumerate() {
+                let value = f64::from(value / total);
+                let encoded = if self.srgb && channel != 3 {
+                    linear_to_srgb(value)
+                } else {
+                    value
+                };
+                *out = (encoded.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+        image
+    }
+}
+
+// Source pixels by offset from the first one an output pixel covers, with their weights.
+// The tent is centred on the output pixel and `scale` source pixels wide each way, so
+// neighbouring output pixels overlap and edges don't alias again on the way down. A scale
+// of 1 is just that pixel
+fn tent(scale: u32) -> Vec<(i64, f32)> {
+    let radius = scale as f32;
+    let scale = i64::from(scale);
+    (-scale..2 * scale)
+        .map(|offset| {
+            let distance = offset as f32 + 0.5 - radius / 2.0;
+            (offset, 1.0 - distance.abs() / radius)
+        })
+        .filter(|&(_, weight)| weight > 0.0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The window's size, what the screenshots come out as
+    const WINDOW: (u32, u32) = (64, 48);
+
+    // A black disc on white, sampled at pixel centers like a rasterizer with no MSAA. The
+    // disc is placed in fractions of the image, so it's the same picture at any size
+    struct Disc {
+        offset: Vec2,
+        jitters: Vec<Vec2>,
+        finished: u32,
+        // Samples that come back before the next one fails, None for all of them
+        fails_after: Option<usize>,
+    }
+
+    impl Disc {
+        fn new() -> Self {
+            Self {
+                offset: Vec2::ZERO,
+                jitters: Vec::new(),
+                finished: 0,
+                fails_after: None,
+            }
+        }
+
+        // Whether the point, in fractions of the image, is on the disc
+        fn covers(point: Vec2) -> bool {
+            let aspect = WINDOW.0 as f32 / WINDOW.1 as f32;
+            let from_center = (point - Vec2::new(0.5, 0.5)) * Vec2::new(aspect, 1.0);
+            from_center.length() < 0.36
+        }
+
+        // How much of the window's pixel (x, y) the disc covers
+        fn coverage(x: u32, y: u32) -> f32 {
+            let steps = 32;
+            let covered = (0..steps * steps)
+                .filter(|index| {
+                    let within =
+                        Vec2::new((index % steps) as f32 + 0.5, (index / steps) as f32 + 0.5)
+                            / steps as f32;
+                    let pixel = Vec2::new(x as f32, y as f32) + within;
+                    Self::covers(pixel / Vec2::new(WINDOW.0 as f32, WINDOW.1 as f32))
+                })
+                .count();
+            covered as f32 / (steps * steps) as f32
+        }
+    }
+
+    impl Jittered for Disc {
+        fn jitter(&mut self, offset: Vec2, _size: (u32, u32)) {
+            self.offset = offset;
+            self.jitters.push(offset);
+        }
+
+        fn sample(&mut self, size: (u32, u32)) -> Result<RgbaImage, String> {
+            if self.fails_after.map(|count| count + 1) == Some(self.jitters.len()) {
+                return Err("device lost".to_owned());
+            }
+            let scale = Vec2::new(size.0 as f32, size.1 as f32);
+            Ok(RgbaImage::from_fn(size.0, size.1, |x, y| {
+                let center = Vec2::new(x as f32, y as f32) + 0.5 + self.offset;
+                let value = if Self::covers(center / scale) { 0 } else { 255 };
+                image::Rgba([value, value, value, 255])
+            }))
+        }
+
+        fn finish(&mut self) {
+            self.finished += 1;
+        }
+    }
+
+    // How far the pixels the disc's edge crosses are from the share of them it covers,
+    // mean squared. Aliasing makes them all or nothing
+    fn edge_variance(image: &RgbaImage) -> f32 {
+        let errors: Vec<f32> = image
+            .enumerate_pixels()
+            .filter_map(|(x, y, pixel)| {
+                let coverage = Disc::coverage(x, y);
+                let on_edge = coverage > 0.0 && coverage < 1.0;
+                on_edge.then(|| {
+                    let expected = 1.0 - coverage;
+                    (f32::from(pixel.0[0]) / 255.0 - expected).powi(2)
+                })
+            })
+            .collect();
+        assert!(errors.len() > 40, "Only {} edge pixels", errors.len());
+        errors.iter().sum::<f32>() / errors.len() as f32
+    }
+
+    #[test]
+    fn captures_come_out_the_size_of_the_window() {
+        for scale in [1, 2, 3] {
+            let size = (WINDOW.0 * scale, WINDOW.1 * scale);
+            let image = capture(&mut Disc::new(), size, 4, scale, true).unwrap();
+            assert_eq!(image.dimensions(), WINDOW, "At {scale}x");
+        }
+    }
+
+    #[test]
+    fn supersampling_aliases_less_than_a_screenshot() {
+        let mut disc = Disc::new();
+        let screenshot = disc.sample(WINDOW).unwrap();
+        let size = (WINDOW.0 * DEFAULT_SCALE, WINDOW.1 * DEFAULT_SCALE);
+        let supersampled = capture(&mut disc, size, DEFAULT_SAMPLES, DEFAULT_SCALE, false).unwrap();
+        let (plain, smooth) = (edge_variance(&screenshot), edge_variance(&supersampled));
+        assert!(
+            smooth < plain / 4.0,
+            "Supersampled edges are off by {smooth}, plain ones by {plain}"
+        );
+        // Away from the edge it's the same picture
+        assert_eq!(supersampled.get_pixel(0, 0), screenshot.get_pixel(0, 0));
+        assert_eq!(
+            supersampled.get_pixel(WINDOW.0 / 2, WINDOW.1 / 2),
+            screenshot.get_pixel(WINDOW.0 / 2, WINDOW.1 / 2)
+        );
+    }
+
+    #[test]
+    fn the_jitter_is_put_back_after_a_capture() {
+        let mut disc = Disc::new();
+        capture(&mut disc, WINDOW, 8, 1, true).unwrap();
+        let (last, samples) = disc.jitters.split_last().unwrap();
+        assert_eq!(*last, Vec2::ZERO);
+        assert_eq!(disc.offset, Vec2::ZERO);
+        assert_eq!(disc.finished, 1);
+        // Every sample somewhere else inside its pixel
+        assert_eq!(samples.len(), 8);
+        for (index, jitter) in samples.iter().enumerate() {
+            assert!(jitter.abs().max_element() < 0.5, "{jitter}");
+            assert!(!samples[..index].contains(jitter), "{jitter} twice");
+        }
+
+        // And when a sample doesn't come back
+        let mut disc = Disc {
+            fails_after: Some(3),
+            ..Disc::new()
+        };
+        let error = capture(&mut disc, WINDOW, 8, 1, true).unwrap_err();
+        assert_eq!(error, "device lost");
+        assert_eq!(disc.jitters.len(), 5);
+        assert_eq!(disc.offset, Vec2::ZERO);
+        assert_eq!(disc.finished, 1);
+    }
+}
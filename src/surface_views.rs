@@ -0,0 +1,109 @@
+// The two ways the swapchain gets drawn through. The scene renders into an sRGB view, so
+// its shading and blending happen in linear light and the hardware encodes. The UI renders
+// into the plain view and its shaders encode their colors themselves, so translucent panels
+// blend in sRGB space like they do in a browser or an image editor. Which of the two
+// formats the surface came first with doesn't change a pixel then
+//
+// Without the pairing there's one view for both. A plain one still works that way for the
+// UI (the scene then writes linear values unencoded, as it always has there). An sRGB one
+// is the approximation: the UI blends in linear light, so a translucent dark panel comes
+// out lighter over a bright scene than the same panel would in sRGB space
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SurfaceViews {
+    // What the surface itself is configured with
+    pub surface: wgpu::TextureFormat,
+    pub scene: wgpu::TextureFormat,
+    pub ui: wgpu::TextureFormat,
+}
+
+impl SurfaceViews {
+    // `pairing` is whether views can differ from the surface in sRGB-ness, which
+    // DownlevelFlags::SURFACE_VIEW_FORMATS says for surfaces. Formats without an sRGB twin
+    // (the float ones) get the single view either way
+    pub fn new(surface: wgpu::TextureFormat, pairing: bool) -> Self {
+        let (srgb, plain) = (surface.add_srgb_suffix(), surface.remove_srgb_suffix());
+        if pairing && srgb != plain {
+            Self {
+                surface,
+                scene: srgb,
+                ui: plain,
+            }
+        } else {
+            Self {
+                surface,
+                scene: surface,
+                ui: surface,
+            }
+        }
+    }
+
+    pub fn paired(self) -> bool {
+        self.scene != self.ui
+    }
+
+    // For view_formats when configuring the surface or creating a texture that stands in
+    // for it, the formats the views use that the surface doesn't have itself
+    pub fn view_formats(self) -> Vec<wgpu::TextureFormat> {
+        [self.scene, self.ui]
+            .into_iter()
+            .filter(|&format| format != self.surface)
+            .collect()
+    }
+
+    // Whether UI shaders encode to sRGB themselves, the encode_srgb override. Always on a
+    // plain view, never on an sRGB one where the hardware does it
+    pub fn ui_encodes(self) -> bool {
+        !self.ui.is_srgb()
+    }
+
+    // Printed at startup and whenever the surface format changes
+    pub fn describe(self) -> String {
+        if self.paired() {
+            format!("scene {:?}, UI {:?}", self.scene, self.ui)
+        } else if self.ui_encodes() {
+            format!("{:?} for both, the UI encodes sRGB itself", self.surface)
+        } else {
+            format!(
+                "{:?} for both, the UI blends in linear light (approximate)",
+                self.surface
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRGB: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+    const PLAIN: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+
+    #[test]
+    fn paired_views_are_the_same_whichever_format_came_first() {
+        let (srgb_first, plain_first) = (
+            SurfaceViews::new(SRGB, true),
+            SurfaceViews::new(PLAIN, true),
+        );
+        for views in [srgb_first, plain_first] {
+            assert!(views.paired());
+            assert_eq!((views.scene, views.ui), (SRGB, PLAIN));
+            assert!(views.ui_encodes());
+        }
+        assert_eq!(srgb_first.view_formats(), [PLAIN]);
+        assert_eq!(plain_first.view_formats(), [SRGB]);
+    }
+
+    #[test]
+    fn without_the_pairing_there_is_one_view() {
+        let plain = SurfaceViews::new(PLAIN, false);
+        assert_eq!((plain.scene, plain.ui), (PLAIN, PLAIN));
+        assert!(plain.ui_encodes());
+        let srgb = SurfaceViews::new(SRGB, false);
+        assert_eq!((srgb.scene, srgb.ui), (SRGB, SRGB));
+        assert!(!srgb.ui_encodes());
+        assert!(srgb.describe().contains("approximate"));
+        // Nothing to pair a float format with
+        let float = SurfaceViews::new(wgpu::TextureFormat::Rgba16Float, true);
+        assert!(!float.paired() && float.view_formats().is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::bind_groups::{BindGroupBuilder, BindGroupHandle, BindGroups};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+
+// Offscreen render targets, sized relative to the window and recreated on resize
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TargetHandle(usize);
+
+pub struct TargetDesc {
+    pub label: &'static str,
+    pub format: wgpu::TextureFormat,
+    // Fraction of the surface size, 1.0 is full resolution
+    pub scale: f32,
+    // Also bindable as a storage texture, for the compute passes in gpu_image
+    pub storage: bool,
+}
+
+struct Target {
+    desc: TargetDesc,
+    texture: Tracked<wgpu::Texture>,
+    view: wgpu::TextureView,
+    // Bumped when this one is recreated, see BindGroups
+    generation: u64,
+    // What passes draw into when the target is multisampled, resolved into `texture` at the
+    // end of each pass. Depth isn't resolved, whoever reads it needs a single sampled copy
+    multisampled: Option<(Tracked<wgpu::Texture>, wgpu::TextureView)>,
+}
+
+pub struct TargetRegistry {
+    targets: Vec<Target>,
+    size: (u32, u32),
+    memory: GpuMemoryTracker,
+    // Bumped whenever the textures get recreated, bind groups holding views compare against it
+    generation: u64,
+    // Built with create_bind_group, kept pointing at the current views
+    bind_groups: BindGroups,
+}
+
+impl TargetRegistry {
+    pub fn new(size: (u32, u32), memory: &GpuMemoryTracker) -> Self {
+        Self {
+            targets: Vec::new(),
+            size,
+            memory: memory.clone(),
+            generation: 0,
+            bind_groups: BindGroups::default(),
+        }
+    }
+
+    pub fn create(&mut self, device: &wgpu::Device, desc: TargetDesc) -> TargetHandle {
+        let (texture, view) = Self::allocate(device, &self.memory, &desc, self.size, 1);
+        self.targets.push(Target {
+            desc,
+            texture,
+            view,
+            generation: 0,
+            multisampled: None,
+        });
+        TargetHandle(self.targets.len() - 1)
+    }
+
+    pub fn view(&self, handle: TargetHandle) -> &wgpu::TextureView {
+        &self.targets[handle.0].view
+    }
+
+    // What a pass attaches: the multisampled texture when there is one, with the view to
+    // resolve it into
+    pub fn attachment(
+        &self,
+        handle: TargetHandle,
+    ) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        let target = &self.targets[handle.0];
+        match &target.multisampled {
+            Some((_, multisampled)) => (multisampled, Some(&target.view)),
+            None => (&target.view, None),
+        }
+    }
+
+    pub fn samples(&self, handle: TargetHandle) -> u32 {
+        self.targets[handle.0]
+            .multisampled
+            .as_ref()
+            .map_or(1, |(texture, _)| texture.sample_count())
+    }
+
+    pub fn format(&self, handle: TargetHandle) -> wgpu::TextureFormat {
+        self.targets[handle.0].desc.format
+    }
+
+    pub fn label(&self, handle: TargetHandle) -> &'static str {
+        self.targets[handle.0].desc.label
+    }
+
+    // Actual size in texels, after scaling
+    pub fn size(&self, handle: TargetHandle) -> (u32, u32) {
+        let texture = &self.targets[handle.0].texture;
+        (texture.width(), texture.height())
+    }
+
+    // Label, format and actual size of every target, in creation order
+    pub fn entries(
+        &self,
+    ) -> impl Iterator<Item = (&'static str, wgpu::TextureFormat, (u32, u32))> + '_ {
+        self.targets.iter().map(|target| {
+            (
+                target.desc.label,
+                target.desc.format,
+                (target.texture.width(), target.texture.height()),
+            )
+        })
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn target_generation(&self, handle: TargetHandle) -> u64 {
+        self.targets[handle.0].generation
+    }
+
+    // A bind group that follows the targets it points at through resizes and format
+    // changes, nobody has to rebuild it by hand
+    pub fn create_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        builder: BindGroupBuilder,
+    ) -> BindGroupHandle {
+        let mut bind_groups = std::mem::take(&mut self.bind_groups);
+        let handle = bind_groups.create(device, self, builder);
+        self.bind_groups = bind_groups;
+        handle
+    }
+
+    pub fn bind_group(&self, handle: &BindGroupHandle) -> &wgpu::BindGroup {
+        self.bind_groups.get(handle)
+    }
+
+    // At the start of a frame, rebuilds the bind groups whose targets were recreated since
+    pub fn validate_bind_groups(&mut self, device: &wgpu::Device) {
+        let mut bind_groups = std::mem::take(&mut self.bind_groups);
+        bind_groups.validate(device, self);
+        self.bind_groups = bind_groups;
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.generation += 1;
+        // Old textures are dropped as they're replaced, which takes them out of the tracker
+        for target in &mut self.targets {
+            Self::reallocate(device, &self.memory, target, size);
+        }
+    }
+
+    // Gives `handle` a texture with `samples` per pixel for passes to draw into, or takes it
+    // away at 1. Bind groups keep reading the single sampled one
+    pub fn set_samples(&mut self, device: &wgpu::Device, handle: TargetHandle, samples: u32) {
+        if samples == self.samples(handle) {
+            return;
+        }
+        let target = &mut self.targets[handle.0];
+        target.multisampled = (samples > 1)
+            .then(|| Self::allocate(device, &self.memory, &target.desc, self.size, samples));
+    }
+
+    // Recreates `handle` in another format, for targets that have to match the surface
+    pub fn set_format(
+        &mut self,
+        device: &wgpu::Device,
+        handle: TargetHandle,
+        format: wgpu::TextureFormat,
+    ) {
+        let target = &mut self.targets[handle.0];
+        if target.desc.format == format {
+            return;
+        }
+        target.desc.format = format;
+        Self::reallocate(device, &self.memory, target, self.size);
+        self.generation += 1;
+    }
+
+    // Its textures again at `size`, multisampled one included
+    fn reallocate(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        target: &mut Target,
+        size: (u32, u32),
+    ) {
+        let (texture, view) = Self::allocate(device, memory, &target.desc, size, 1);
+        target.texture = texture;
+        target.view = view;
+        target.generation += 1;
+        if let Some((multisampled, _)) = &target.multisampled {
+            let samples = multisampled.sample_count();
+            target.multisampled = Some(Self::allocate(device, memory, &target.desc, size, samples));
+        }
+    }
+
+    fn allocate(
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        desc: &TargetDesc,
+        size: (u32, u32),
+        samples: u32,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let scaled = |x: u32| ((x as f32 * desc.scale) as u32).max(1);
+        // Copyable both ways, so a target can be seeded and read back (the tests do). The
+        // multisampled ones are only ever drawn into
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if samples == 1 {
+            usage |= wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST;
+        }
+        if desc.storage && samples == 1 {
+            usage |= wgpu::TextureUsages::STORAGE_BINDING;
+        }
+        let texture = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(desc.label),
+                size: wgpu::Extent3d {
+                    width: scaled(size.0),
+                    height: scaled(size.1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: samples,
+                dimension: wgpu::TextureDimension::D2,
+                format: desc.format,
+                usage,
+                view_formats: &[],
+            },
+            MemoryCategory::Targets,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+}
+
+// For seeding and reading back targets in tests
+#[cfg(test)]
+impl TargetRegistry {
+    pub fn texture(&self, handle: TargetHandle) -> &wgpu::Texture {
+        &self.targets[handle.0].texture
+    }
+}
+
+// For tests that only compare handles, like the render graph's
+#[cfg(test)]
+impl TargetHandle {
+    pub fn unregistered(index: usize) -> Self {
+        Self(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+
+    #[test]
+    fn scene_targets_leave_nothing_behind() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let baseline = memory.report();
+        for scene in ["starter", "stress"] {
+            memory.set_scene(Some(scene));
+            let mut targets = TargetRegistry::new((64, 48), &memory);
+            for (label, scale, storage) in [("Scene Color", 1.0, false), ("Bloom", 0.5, true)] {
+                targets.create(
+                    &gpu.device,
+                    TargetDesc {
+                        label,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        scale,
+                        storage,
+                    },
+                );
+            }
+            targets.resize(&gpu.device, (128, 96));
+            assert_eq!(memory.scene_bytes().len(), 1);
+            assert_eq!(memory.live_allocations().len(), 2);
+            memory.set_scene(None);
+            drop(targets);
+            assert_eq!(memory.report(), baseline);
+            assert!(memory.scene_bytes().is_empty());
+        }
+    }
+}
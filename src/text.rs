@@ -0,0 +1,809 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use glam::Vec2;
+
+use crate::buffer_pool::BufferPool;
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::{GpuMemoryTracker, MemoryCategory, Tracked};
+use crate::overlay::OverlayVertex;
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::shaders;
+use crate::targets::TargetRegistry;
+
+// DejaVu Sans (Bitstream Vera license), used when no --font is given
+const DEFAULT_FONT: &[u8] = include_bytes!("DejaVuSans.ttf");
+
+// The atlas starts out this big and doubles when a glyph doesn't fit anymore
+const ATLAS_START: u32 = 256;
+const ATLAS_MAX: u32 = 4096;
+// Empty pixels around every glyph, so linear filtering never picks up a neighbour
+const PADDING: u32 = 1;
+// A solid block in the corner of the atlas, tofu boxes are drawn with it
+const SOLID: u32 = 2;
+
+static NEXT_FONT: AtomicUsize = AtomicUsize::new(0);
+
+// A loaded TTF/OTF. Clones share the parsed font, the id tells the glyph cache which one
+// a glyph came from
+#[derive(Clone)]
+pub struct Font {
+    id: usize,
+    pub name: String,
+    face: Arc<fontdue::Font>,
+}
+
+// Where a run of text ended up, in pixels with the origin top-left
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextBounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// One glyph of a laid out run. `glyph` is None when the font doesn't have the character,
+// that one is drawn as an outlined box of the size in `size`
+#[derive(Copy, Clone, Debug)]
+pub struct PlacedGlyph {
+    pub glyph: Option<u16>,
+    // Top-left corner of the bitmap and its size
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+// Queued by Frame::draw_text, drawn by the TextRenderer at the end of the frame
+pub struct TextRun {
+    pub font: Font,
+    pub size_px: f32,
+    pub glyphs: Vec<PlacedGlyph>,
+    pub color: [f32; 4],
+}
+
+impl TextRun {
+    pub fn new(font: &Font, size_px: f32, glyphs: Vec<PlacedGlyph>, color: RgbaColor) -> Self {
+        Self {
+            font: font.clone(),
+            size_px,
+            glyphs,
+            color: color.to_f32_array_linear(),
+        }
+    }
+}
+
+impl Font {
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, ForayError> {
+        let face = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(
+            |reason| ForayError::FontLoad {
+                font: name.to_owned(),
+                reason: reason.to_owned(),
+            },
+        )?;
+        Ok(Self {
+            id: NEXT_FONT.fetch_add(1, Ordering::Relaxed),
+            name: name.to_owned(),
+            face: Arc::new(face),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ForayError> {
+        let bytes = std::fs::read(path).map_err(|e| ForayError::FontLoad {
+            font: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+        Self::from_bytes(&path.display().to_string(), &bytes)
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn face(&self) -> &fontdue::Font {
+        &self.face
+    }
+
+    pub fn embedded() -> Self {
+        Self::from_bytes("DejaVu Sans", DEFAULT_FONT).expect("The embedded font parses")
+    }
+
+    // --font, the embedded one without it or when it doesn't load
+    pub fn load_or_embedded(path: Option<&Path>) -> Self {
+        match path.map(Self::load) {
+            Some(Ok(font)) => {
+                println!("Text in {}", font.name);
+                font
+            }
+            Some(Err(e)) => {
+                log::warn!("{e}, using the embedded font");
+                Self::embedded()
+            }
+            None => Self::embedded(),
+        }
+    }
+
+    fn line_metrics(&self, size_px: f32) -> fontdue::LineMetrics {
+        // Fonts without a hhea table don't say, roughly what most fonts use then
+        self.face
+            .horizontal_line_metrics(size_px)
+            .unwrap_or(fontdue::LineMetrics {
+                ascent: size_px * 0.8,
+                descent: -size_px * 0.2,
+                line_gap: 0.0,
+                new_line_size: size_px,
+            })
+    }
+
+    // Left to right, kerned with the font's own pairs, '\n' starts a new line. `position`
+    // is the top-left corner of the first line
+    pub fn layout(
+        &self,
+        size_px: f32,
+        position: Vec2,
+        text: &str,
+    ) -> (Vec<PlacedGlyph>, TextBounds) {
+        let line = self.line_metrics(size_px);
+        let mut glyphs = Vec::new();
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+        for (row, text_line) in text.split('\n').enumerate() {
+            lines = row + 1;
+            let baseline = position.y + line.ascent + row as f32 * line.new_line_size;
+            let mut pen = position.x;
+            let mut previous: Option<u16> = None;
+            for c in text_line.chars() {
+                let index = self.face.lookup_glyph_index(c);
+                if index == 0 && !c.is_whitespace() {
+                    // Tofu: as wide as the font's .notdef, from the baseline up to the ascent
+                    let advance = self.face.metrics_indexed(0, size_px).advance_width;
+                    let advance = if advance > 0.0 {
+                        advance
+                    } else {
+                        size_px * 0.5
+                    };
+                    glyphs.push(PlacedGlyph {
+                        glyph: None,
+                        position: Vec2::new(pen + advance * 0.1, baseline - line.ascent * 0.9),
+                        size: Vec2::new(advance * 0.8, line.ascent * 0.9),
+                    });
+                    pen += advance;
+                    previous = None;
+                    continue;
+                }
+                if let Some(left) = previous {
+                    pen += self
+                        .face
+                        .horizontal_kern_indexed(left, index, size_px)
+                        .unwrap_or(0.0);
+                }
+                let metrics = self.face.metrics_indexed(index, size_px);
+                if metrics.width > 0 && metrics.height > 0 {
+                    glyphs.push(PlacedGlyph {
+                        glyph: Some(index),
+                        position: Vec2::new(
+                            (pen + metrics.xmin as f32).round(),
+                            (baseline - (metrics.ymin + metrics.height as i32) as f32).round(),
+                        ),
+                        size: Vec2::new(metrics.width as f32, metrics.height as f32),
+                    });
+                }
+                pen += metrics.advance_width;
+                previous = Some(index);
+            }
+            width = width.max(pen - position.x);
+        }
+        let bounds = TextBounds {
+            x: position.x,
+            y: position.y,
+            width,
+            height: lines as f32 * line.new_line_size,
+        };
+        (glyphs, bounds)
+    }
+
+    pub fn measure(&self, size_px: f32, text: &str) -> TextBounds {
+        self.layout(size_px, Vec2::ZERO, text).1
+    }
+}
+
+// A glyph rasterized at one pixel size. The size is part of the key, the same glyph at
+// another size is another bitmap
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    glyph: u16,
+    size_bits: u32,
+}
+
+// Rows of glyphs, each as tall as the tallest glyph in it
+struct ShelfPacker {
+    size: u32,
+    x: u32,
+    y: u32,
+    shelf_height: u32,
+}
+
+impl ShelfPacker {
+    fn new(size: u32) -> Self {
+        // The solid block comes first
+        Self {
+            size,
+            x: SOLID + PADDING,
+            y: 0,
+            shelf_height: SOLID + PADDING,
+        }
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + PADDING, height + PADDING);
+        if self.x + width > self.size {
+            self.y += self.shelf_height;
+            self.x = 0;
+            self.shelf_height = 0;
+        }
+        if self.x + width > self.size || self.y + height > self.size {
+            return None;
+        }
+        let at = (self.x, self.y);
+        self.x += width;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(at)
+    }
+}
+
+// TTF text on top of the frame. Glyphs are rasterized the first time they're drawn at a
+// size and kept in an R8 atlas. When the atlas is full it's repacked into one twice as big,
+// at the largest size the cache starts over with what the current frame needs
+pub struct TextRenderer {
+    atlas: Tracked<wgpu::Texture>,
+    packer: ShelfPacker,
+    // Atlas pixel rectangles (x, y, width, height)
+    glyphs: HashMap<GlyphKey, (u32, u32, u32, u32)>,
+    // Every font drawn with so far, the repack re-rasterizes from them
+    faces: HashMap<usize, Arc<fontdue::Font>>,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    screen_buffer: Tracked<wgpu::Buffer>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        bank: &mut RenderPipelineBank,
+        memory: &GpuMemoryTracker,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        // Glyphs are drawn at their rasterized size, linear only matters for the tofu edges
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Text Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let screen_buffer = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Text Screen Buffer"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+            MemoryCategory::Uniforms,
+        );
+
+        // Same vertices and shader as the debug overlay, just another atlas
+        let shader = shaders::create_module(device, "Text Shader", include_str!("overlay.wgsl"));
+        bank.register_surface(
+            device,
+            "text",
+            &PipelineBuilder::new("Text Pipeline", &shader)
+                .vertex_entry("vs_overlay")
+                .fragment_entry("fs_overlay")
+                .vertex_buffer(OverlayVertex::desc())
+                .bind_group_layout(&layout)
+                .cull_mode(None)
+                .blend_mode(BlendMode::Alpha),
+            format,
+        );
+
+        let atlas = Self::create_atlas(device, queue, memory, ATLAS_START);
+        let bind_group = Self::bind_group(device, &layout, &atlas, &sampler, &screen_buffer);
+        Self {
+            atlas,
+            packer: ShelfPacker::new(ATLAS_START),
+            glyphs: HashMap::new(),
+            faces: HashMap::new(),
+            layout,
+            sampler,
+            screen_buffer,
+            bind_group,
+        }
+    }
+
+    fn create_atlas(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        size: u32,
+    ) -> Tracked<wgpu::Texture> {
+        let atlas = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("Text Atlas"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            MemoryCategory::Textures,
+        );
+        Self::write(
+            queue,
+            &atlas,
+            (0, 0, SOLID, SOLID),
+            &[255; (SOLID * SOLID) as usize],
+        );
+        atlas
+    }
+
+    fn bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        atlas: &wgpu::Texture,
+        sampler: &wgpu::Sampler,
+        screen_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    fn write(
+        queue: &wgpu::Queue,
+        atlas: &wgpu::Texture,
+        (x, y, w, h): (u32, u32, u32, u32),
+        pixels: &[u8],
+    ) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(w),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // Rasterizes `key` into the atlas, false when there's no room left
+    fn rasterize(&mut self, queue: &wgpu::Queue, key: GlyphKey) -> bool {
+        let face = &self.faces[&key.font];
+        let (metrics, bitmap) = face.rasterize_indexed(key.glyph, f32::from_bits(key.size_bits));
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let Some((x, y)) = self.packer.insert(width, height) else {
+            return false;
+        };
+        Self::write(queue, &self.atlas, (x, y, width, height), &bitmap);
+        self.glyphs.insert(key, (x, y, width, height));
+        true
+    }
+
+    // Into a new atlas of `size`, re-rasterizing `keys`, tallest first so the shelves
+    // waste less. Whatever else was cached is dropped
+    fn repack(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        size: u32,
+        mut keys: Vec<GlyphKey>,
+    ) {
+        self.atlas = Self::create_atlas(device, queue, memory, size);
+        self.bind_group = Self::bind_group(
+            device,
+            &self.layout,
+            &self.atlas,
+            &self.sampler,
+            &self.screen_buffer,
+        );
+        self.packer = ShelfPacker::new(size);
+        self.glyphs.clear();
+        keys.sort_by_key(|key| {
+            let face = &self.faces[&key.font];
+            std::cmp::Reverse(
+                face.metrics_indexed(key.glyph, f32::from_bits(key.size_bits))
+                    .height,
+            )
+        });
+        for key in keys {
+            if !self.rasterize(queue, key) {
+                log::warn!("Text atlas is full at {size}x{size}, some glyphs won't show");
+                return;
+            }
+        }
+    }
+
+    // Makes sure every glyph of `runs` is in the atlas
+    fn cache(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        runs: &[TextRun],
+    ) {
+        let mut wanted = Vec::new();
+        for run in runs {
+            self.faces
+                .entry(run.font.id)
+                .or_insert_with(|| Arc::clone(&run.font.face));
+            for glyph in &run.glyphs {
+                if let Some(glyph) = glyph.glyph {
+                    let key = GlyphKey {
+                        font: run.font.id,
+                        glyph,
+                        size_bits: run.size_px.to_bits(),
+                    };
+                    if !wanted.contains(&key) {
+                        wanted.push(key);
+                    }
+                }
+            }
+        }
+
+        for &key in &wanted {
+            if self.glyphs.contains_key(&key) || self.rasterize(queue, key) {
+                continue;
+            }
+            let size = self.packer.size;
+            if size < ATLAS_MAX {
+                // Everything cached so far comes along into the bigger atlas
+                let mut keys: Vec<_> = self.glyphs.keys().copied().collect();
+                keys.extend(wanted.iter().filter(|key| !self.glyphs.contains_key(key)));
+                self.repack(device, queue, memory, size * 2, keys);
+            } else {
+                self.repack(device, queue, memory, size, wanted.clone());
+            }
+            return;
+        }
+    }
+
+    fn quad(
+        vertices: &mut Vec<OverlayVertex>,
+        (x, y, w, h): (f32, f32, f32, f32),
+        (u0, v0, u1, v1): (f32, f32, f32, f32),
+        color: [f32; 4],
+    ) {
+        let corner = |px, py, u, v| OverlayVertex {
+            position: [px, py],
+            uv: [u, v],
+            color,
+        };
+        let top_left = corner(x, y, u0, v0);
+        let top_right = corner(x + w, y, u1, v0);
+        let bottom_left = corner(x, y + h, u0, v1);
+        let bottom_right = corner(x + w, y + h, u1, v1);
+        vertices.extend([
+            top_left,
+            bottom_left,
+            top_right,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ]);
+    }
+
+    // Draws the text queued on `frame` over the swapchain and clears the queue
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        memory: &GpuMemoryTracker,
+        screen_size: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let runs = std::mem::take(&mut frame.text);
+        if runs.is_empty() {
+            return Ok(());
+        }
+        self.cache(device, queue, memory, &runs);
+
+        let atlas_size = self.packer.size as f32;
+        let solid = (
+            0.5 / atlas_size,
+            0.5 / atlas_size,
+            1.5 / atlas_size,
+            1.5 / atlas_size,
+        );
+        let mut vertices = Vec::new();
+        for run in &runs {
+            let border = (run.size_px / 16.0).max(1.0);
+            for placed in &run.glyphs {
+                let (x, y) = (placed.position.x, placed.position.y);
+                let (w, h) = (placed.size.x, placed.size.y);
+                let Some(glyph) = placed.glyph else {
+                    for edge in [
+                        (x, y, w, border),
+                        (x, y + h - border, w, border),
+                        (x, y, border, h),
+                        (x + w - border, y, border, h),
+                    ] {
+                        Self::quad(&mut vertices, edge, solid, run.color);
+                    }
+                    continue;
+                };
+                let key = GlyphKey {
+                    font: run.font.id,
+                    glyph,
+                    size_bits: run.size_px.to_bits(),
+                };
+                // Only missing when the atlas ran out of room
+                let Some(&(ax, ay, aw, ah)) = self.glyphs.get(&key) else {
+                    continue;
+                };
+                let uv = (
+                    ax as f32 / atlas_size,
+                    ay as f32 / atlas_size,
+                    (ax + aw) as f32 / atlas_size,
+                    (ay + ah) as f32 / atlas_size,
+                );
+                Self::quad(&mut vertices, (x, y, w, h), uv, run.color);
+            }
+        }
+        if vertices.is_empty() {
+            return Ok(());
+        }
+
+        let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let vertex_buffer = pool.acquire(
+            device,
+            "Text Vertex Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            bytes.len() as u64,
+        );
+        queue.write_buffer(&vertex_buffer, 0, bytes);
+        let screen = [screen_size.0 as f32, screen_size.1 as f32, 0.0, 0.0];
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&screen));
+
+        let mut pass = frame.pass(
+            "Text Pass",
+            &[(ColorTarget::Swapchain, wgpu::LoadOp::Load)],
+            targets,
+        );
+        let result = pass.set_pipeline(bank, "text").map(|()| {
+            pass.raw.set_bind_group(0, &self.bind_group, &[]);
+            pass.raw
+                .set_vertex_buffer(0, vertex_buffer.slice(..bytes.len() as u64));
+            pass.raw.draw(0..vertices.len() as u32, 0..1);
+        });
+        drop(pass);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // DejaVu Sans has 2048 units per em, at 2048px a font unit is a pixel
+    const UNITS_PER_EM: f32 = 2048.0;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    // Advances and kerning pairs as they are in DejaVuSans.ttf's hmtx and kern tables
+    #[test]
+    fn advances_match_the_font_metrics() {
+        let font = Font::embedded();
+        for (text, units) in [
+            // H 1540, e 1260, l 569, o 1253
+            ("Hello", 5191.0),
+            // A 1401 and V 1401, kerned by -131
+            ("AV", 2671.0),
+            // T 1251 and o 1253, kerned by -348
+            ("To", 2156.0),
+            // The comma and space are 651 each, W 2025, r 842, d 1300, W-o kerned by -120
+            ("Hello, World", 12362.0),
+        ] {
+            let bounds = font.measure(UNITS_PER_EM, text);
+            assert!(close(bounds.width, units), "{text}: {}", bounds.width);
+            // And scaled down to a size text is actually drawn at
+            let bounds = font.measure(32.0, text);
+            assert!(
+                close(bounds.width, units * 32.0 / UNITS_PER_EM),
+                "{text}: {}",
+                bounds.width
+            );
+        }
+    }
+
+    #[test]
+    fn lines_stack_by_the_line_metrics() {
+        let font = Font::embedded();
+        // hhea ascent 1901, descent -483, no line gap
+        let one = font.measure(UNITS_PER_EM, "Hello");
+        assert!(close(one.height, 2384.0), "{}", one.height);
+        let two = font.measure(UNITS_PER_EM, "AV\nHello");
+        assert!(close(two.height, 2.0 * 2384.0), "{}", two.height);
+        // The widest line is the width
+        assert!(close(two.width, 5191.0), "{}", two.width);
+    }
+
+    #[test]
+    fn missing_characters_take_the_notdef_advance() {
+        let font = Font::embedded();
+        // Nothing in the private use area, the .notdef glyph is 1229 units wide
+        let (glyphs, bounds) = font.layout(UNITS_PER_EM, Vec2::new(10.0, 20.0), "H\u{e000}H");
+        assert!(
+            close(bounds.width, 1540.0 + 1229.0 + 1540.0),
+            "{}",
+            bounds.width
+        );
+        assert!(close(bounds.x, 10.0) && close(bounds.y, 20.0));
+        let tofu: Vec<&PlacedGlyph> = glyphs.iter().filter(|g| g.glyph.is_none()).collect();
+        assert_eq!(tofu.len(), 1);
+        assert!(tofu[0].position.x > 10.0 + 1540.0 && tofu[0].size.x > 0.0);
+    }
+
+    // What the console would hold after typing accented Latin, a ZWJ emoji and CJK
+    fn typed_mixed_script() -> String {
+        let mut input = crate::text_input::TextInput::new();
+        for c in "Gr\u{FC}\u{DF}e e\u{301} \u{1F468}\u{200D}\u{1F469} \u{65E5}\u{672C}".chars() {
+            input.insert(c);
+        }
+        // A backspace takes the second CJK character out whole
+        input.backspace();
+        input.as_str().to_owned()
+    }
+
+    #[test]
+    fn mixed_script_input_lays_out_with_tofu_for_what_the_font_lacks() {
+        let font = Font::embedded();
+        let text = typed_mixed_script();
+        assert!(text.ends_with(" \u{65E5}"));
+        let (glyphs, bounds) = font.layout(24.0, Vec2::ZERO, &text);
+        assert!(bounds.width.is_finite() && bounds.width > 0.0);
+        assert!(close(bounds.height, font.measure(24.0, "Hello").height));
+        for glyph in &glyphs {
+            assert!(
+                glyph.position.is_finite() && glyph.size.is_finite(),
+                "{glyph:?}"
+            );
+        }
+        // DejaVu has the Latin and the combining accent, not the emoji or the CJK
+        for c in ['\u{FC}', '\u{DF}', 'e', '\u{301}'] {
+            let index = font.face().lookup_glyph_index(c);
+            assert!(index != 0, "{c}");
+            assert!(glyphs.iter().any(|g| g.glyph == Some(index)), "{c}");
+        }
+        let tofu = glyphs.iter().filter(|g| g.glyph.is_none()).count();
+        assert!(tofu >= 3, "{tofu}");
+        // The CJK character is the last thing on the line, a tofu box
+        let last = glyphs.last().expect("Something was laid out");
+        assert!(last.glyph.is_none());
+        assert!(glyphs.iter().all(|g| g.position.x <= last.position.x));
+        assert!(last.position.x + last.size.x <= bounds.width);
+    }
+
+    #[test]
+    fn mixed_script_glyphs_are_rasterized_into_the_atlas_on_demand() {
+        let Ok(gpu) = crate::gpu_context::GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let memory = GpuMemoryTracker::new();
+        let mut bank = RenderPipelineBank::new();
+        let mut renderer = TextRenderer::new(
+            &gpu.device,
+            &gpu.queue,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            &mut bank,
+            &memory,
+        );
+        let font = Font::embedded();
+        let text = typed_mixed_script();
+        let runs: Vec<TextRun> = [16.0, 48.0]
+            .into_iter()
+            .map(|size| {
+                let glyphs = font.layout(size, Vec2::ZERO, &text).0;
+                TextRun::new(&font, size, glyphs, crate::colors::Colors::WHITE)
+            })
+            .collect();
+        renderer.cache(&gpu.device, &gpu.queue, &memory, &runs);
+        for run in &runs {
+            for glyph in run.glyphs.iter().filter_map(|g| g.glyph) {
+                let key = GlyphKey {
+                    font: font.id,
+                    glyph,
+                    size_bits: run.size_px.to_bits(),
+                };
+                assert!(renderer.glyphs.contains_key(&key), "{key:?}");
+            }
+        }
+        // Nothing overlaps in the atlas
+        let rects: Vec<_> = renderer.glyphs.values().collect();
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                let apart =
+                    a.0 + a.2 <= b.0 || b.0 + b.2 <= a.0 || a.1 + a.3 <= b.1 || b.1 + b.3 <= a.1;
+                assert!(apart, "{a:?} and {b:?}");
+            }
+        }
+    }
+}
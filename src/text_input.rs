@@ -0,0 +1,240 @@
+// One line of editable text with a cursor. The cursor is a byte offset that only ever sits
+// between graphemes, so slicing at it can't split a character and backspace takes an
+// accented letter or an emoji sequence out whole rather than leaving half of it behind
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    // The text either side of the cursor
+    pub fn split(&self) -> (&str, &str) {
+        self.text.split_at(self.cursor)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    // The grapheme before the cursor
+    pub fn backspace(&mut self) {
+        let start = previous_boundary(&self.text, self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    // The grapheme after the cursor
+    pub fn delete(&mut self) {
+        let end = next_boundary(&self.text, self.cursor);
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = previous_boundary(&self.text, self.cursor);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = next_boundary(&self.text, self.cursor);
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    // Everything typed, leaving the line empty
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+}
+
+// Whether `c` belongs to the grapheme before it instead of starting one: combining marks,
+// variation selectors, emoji modifiers and tags, and whatever follows a zero width joiner.
+// Not all of UAX #29, but what keyboards and IMEs actually produce
+pub fn is_extending(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{0591}'..='\u{05BD}'
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0900}'..='\u{0903}'
+        | '\u{093A}'..='\u{094F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{3099}'..='\u{309A}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}')
+}
+
+// Where the grapheme ending at `index` starts, 0 at the start
+pub fn previous_boundary(text: &str, index: usize) -> usize {
+    let mut chars = text[..index].char_indices().rev().peekable();
+    while let Some((at, c)) = chars.next() {
+        let joined = chars
+            .peek()
+            .is_some_and(|&(_, before)| before == '\u{200D}');
+        if !is_extending(c) && !joined {
+            return at;
+        }
+    }
+    0
+}
+
+// Where the grapheme starting at `index` ends, the text's length at the end
+pub fn next_boundary(text: &str, index: usize) -> usize {
+    let mut chars = text[index..].char_indices().peekable();
+    // The first one always belongs, whatever it is
+    let Some((_, mut previous)) = chars.next() else {
+        return index;
+    };
+    for (at, c) in chars {
+        if !is_extending(c) && previous != '\u{200D}' {
+            return index + at;
+        }
+        previous = c;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> TextInput {
+        let mut input = TextInput::new();
+        for c in text.chars() {
+            input.insert(c);
+        }
+        input
+    }
+
+    // Graphemes left of the cursor, walking it back one step at a time
+    fn graphemes(text: &str) -> Vec<String> {
+        let mut input = typed(text);
+        let mut found = Vec::new();
+        loop {
+            let before = input.split().0.len();
+            input.left();
+            let at = input.split().0.len();
+            if at == before {
+                break;
+            }
+            found.push(input.as_str()[at..before].to_owned());
+        }
+        found.reverse();
+        found
+    }
+
+    // Precomposed and combining \u{E9}, ß, a ZWJ family, a skin tone, a keycap and CJK
+    const MIXED: &str = "\u{E9}e\u{301}ß👨\u{200D}👩\u{200D}👧👍🏽1\u{FE0F}\u{20E3}日本語";
+
+    #[test]
+    fn the_cursor_steps_over_whole_graphemes() {
+        assert_eq!(
+            graphemes(MIXED),
+            [
+                "\u{E9}",
+                "e\u{301}",
+                "ß",
+                "👨\u{200D}👩\u{200D}👧",
+                "👍🏽",
+                "1\u{FE0F}\u{20E3}",
+                "日",
+                "本",
+                "語"
+            ]
+        );
+        // And right walks the same boundaries forward
+        let mut input = typed(MIXED);
+        input.home();
+        let mut stops = vec![0];
+        while !input.split().1.is_empty() {
+            input.right();
+            stops.push(input.split().0.len());
+        }
+        let mut expected = vec![0];
+        for grapheme in graphemes(MIXED) {
+            expected.push(expected.last().unwrap() + grapheme.len());
+        }
+        assert_eq!(stops, expected);
+    }
+
+    #[test]
+    fn backspace_and_delete_take_one_grapheme() {
+        let mut input = typed(MIXED);
+        input.backspace();
+        assert_eq!(
+            input.as_str(),
+            "\u{E9}e\u{301}ß👨\u{200D}👩\u{200D}👧👍🏽1\u{FE0F}\u{20E3}日本"
+        );
+        // Back over the CJK and the keycap, then out go the skin tone and the family whole
+        for _ in 0..3 {
+            input.left();
+        }
+        input.backspace();
+        assert_eq!(
+            input.split(),
+            (
+                "\u{E9}e\u{301}ß👨\u{200D}👩\u{200D}👧",
+                "1\u{FE0F}\u{20E3}日本"
+            )
+        );
+        input.backspace();
+        assert_eq!(input.split(), ("\u{E9}e\u{301}ß", "1\u{FE0F}\u{20E3}日本"));
+        input.home();
+        input.right();
+        input.delete();
+        assert_eq!(input.split(), ("\u{E9}", "ß1\u{FE0F}\u{20E3}日本"));
+        input.insert('a');
+        assert_eq!(input.take(), "\u{E9}aß1\u{FE0F}\u{20E3}日本");
+        assert_eq!(input.split(), ("", ""));
+    }
+
+    #[test]
+    fn editing_at_either_end_never_panics() {
+        let mut input = typed(MIXED);
+        input.right();
+        input.delete();
+        assert_eq!(input.as_str(), MIXED);
+        // Emptying it from the end and then some
+        for _ in 0..20 {
+            input.backspace();
+        }
+        assert_eq!(input.as_str(), "");
+        let mut input = typed(MIXED);
+        input.home();
+        input.backspace();
+        input.left();
+        for _ in 0..20 {
+            input.delete();
+        }
+        assert_eq!(input.split(), ("", ""));
+        // Marks typed with nothing before them stay at the start without splitting anything
+        let mut input = typed("\u{301}\u{200D}x");
+        input.left();
+        input.left();
+        assert_eq!(input.split(), ("", "\u{301}\u{200D}x"));
+    }
+}
@@ -0,0 +1,648 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use glam::{IVec2, Vec2};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::buffer_pool::BufferPool;
+use crate::camera2d::Camera2d;
+use crate::colors;
+use crate::error::ForayError;
+use crate::frame::{ColorTarget, Frame};
+use crate::memory::GpuMemoryTracker;
+use crate::mesh::{Indices, Mesh, SubMesh};
+use crate::pipeline_bank::{BlendMode, PipelineBuilder, RenderPipelineBank};
+use crate::reflect::Reflection;
+use crate::shaders;
+use crate::shapes::CameraUniform;
+use crate::sprites::{ArrayTexture, SpriteRenderer, TilePolicy};
+use crate::targets::TargetRegistry;
+
+// Tiles along a side of a chunk. A chunk's quads fit u16 indices with room to spare
+pub const CHUNK: i32 = 32;
+// The side of the map the stress test fills unless the tiles command asks for another
+pub const STRESS_SIDE: i32 = 1024;
+// World units along a side of the tiles command's maps' tiles
+pub const TILE_SIZE: f32 = 16.0;
+// Images in the generated tile set, and the side of each
+const TILES: usize = 16;
+const TILE_PIXELS: u32 = 16;
+
+// A corner of a tile's quad. Which corner it is comes from its index in the chunk, see
+// tilemap.wgsl, so the UVs aren't stored: a 1024x1024 map is a million quads
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileVertex {
+    position: [f32; 2],
+    layer: u32,
+}
+
+impl TileVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Uint32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TileVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// CHUNK x CHUNK tiles and the mesh drawing them
+struct Chunk {
+    // Rows from the chunk's bottom, None where there's no tile
+    tiles: Vec<Option<u32>>,
+    // None until the chunk is first seen
+    mesh: Option<Mesh>,
+    // The array each of the mesh's submeshes draws out of
+    arrays: Vec<u32>,
+    // A tile changed since the mesh was built
+    dirty: bool,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Self {
+            tiles: vec![None; (CHUNK * CHUNK) as usize],
+            mesh: None,
+            arrays: Vec::new(),
+            dirty: true,
+        }
+    }
+}
+
+// What the last TileRenderer::draw did, for FrameStats
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TileCounts {
+    // Chunks inside the camera's view that had tiles to draw
+    pub drawn: usize,
+    // Chunks with tiles, on screen or not
+    pub total: usize,
+    // Meshes rebuilt because a tile in them changed or they were seen for the first time
+    pub rebuilt: usize,
+}
+
+// A grid of tile indices into an ArrayTexture, one world-aligned square per tile. Tiles are
+// kept in CHUNK x CHUNK chunks, each drawn as one static mesh that's only rebuilt when one
+// of its tiles changes, and only once the chunk is on screen. Chunks off screen aren't drawn
+pub struct TileMap {
+    pub name: String,
+    images: ArrayTexture,
+    // World units along a tile's side
+    pub tile_size: f32,
+    // Where tile (0, 0)'s bottom-left corner is
+    pub origin: Vec2,
+    // By chunk, which is the tile coordinates divided by CHUNK rounding down, so tile -1 is
+    // in chunk -1 and not chunk 0 with tile 1. Ordered so draws come in the same order
+    // every frame
+    chunks: BTreeMap<(i32, i32), Chunk>,
+    pub counts: TileCounts,
+}
+
+impl TileMap {
+    pub fn new(name: &str, images: ArrayTexture, tile_size: f32) -> Self {
+        Self {
+            name: name.to_owned(),
+            images,
+            tile_size,
+            origin: Vec2::ZERO,
+            chunks: BTreeMap::new(),
+            counts: TileCounts::default(),
+        }
+    }
+
+    // The images a tile index can pick from
+    pub fn tile_count(&self) -> usize {
+        self.images.len()
+    }
+
+    // Chunks with at least one tile
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Marks the tile's chunk for a rebuild unless it already showed that image
+    pub fn set_tile(&mut self, x: i32, y: i32, index: u32) -> Result<(), ForayError> {
+        if index as usize >= self.images.len() {
+            return Err(ForayError::TileIndex {
+                map: self.name.clone(),
+                index,
+                tiles: self.images.len(),
+            });
+        }
+        self.put(x, y, Some(index));
+        Ok(())
+    }
+
+    pub fn clear_tile(&mut self, x: i32, y: i32) {
+        self.put(x, y, None);
+    }
+
+    fn put(&mut self, x: i32, y: i32, tile: Option<u32>) {
+        let (key, at) = Self::locate(x, y);
+        // Clearing where there's no chunk doesn't need one
+        if tile.is_none() && !self.chunks.contains_key(&key) {
+            return;
+        }
+        let chunk = self.chunks.entry(key).or_insert_with(Chunk::new);
+        if chunk.tiles[at] == tile {
+            return;
+        }
+        chunk.tiles[at] = tile;
+        chunk.dirty = true;
+        // Its mesh goes with it, there's nothing left to draw
+        if tile.is_none() && chunk.tiles.iter().all(Option::is_none) {
+            self.chunks.remove(&key);
+        }
+    }
+
+    // The chunk a tile is in and where in its tiles
+    fn locate(x: i32, y: i32) -> ((i32, i32), usize) {
+        let key = (x.div_euclid(CHUNK), y.div_euclid(CHUNK));
+        let at = y.rem_euclid(CHUNK) * CHUNK + x.rem_euclid(CHUNK);
+        (key, at as usize)
+    }
+
+    // The tile a world position falls on
+    pub fn tile_at(&self, world: Vec2) -> IVec2 {
+        ((world - self.origin) / self.tile_size).floor().as_ivec2()
+    }
+
+    // Every tile on the line from `from` to `to`, so a quick drag doesn't leave gaps. A
+    // stroke over a chunk boundary marks the chunks on both sides. None clears them
+    pub fn paint_line(
+        &mut self,
+        from: IVec2,
+        to: IVec2,
+        index: Option<u32>,
+    ) -> Result<(), ForayError> {
+        let steps = (to - from).abs().max_element();
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f32 / steps as f32
+            };
+            let tile = (from.as_vec2() + (to - from).as_vec2() * t)
+                .round()
+                .as_ivec2();
+            match index {
+                Some(index) => self.set_tile(tile.x, tile.y, index)?,
+                None => self.clear_tile(tile.x, tile.y),
+            }
+        }
+        Ok(())
+    }
+
+    // Replaces every tile the layout covers, nothing if any of its indices is out of range
+    pub fn apply(&mut self, layout: &TileLayout) -> Result<usize, ForayError> {
+        if let Some(&index) = layout
+            .rows
+            .iter()
+            .flatten()
+            .find(|&&index| index >= 0 && index as usize >= self.images.len())
+        {
+            return Err(ForayError::TileIndex {
+                map: self.name.clone(),
+                index: index as u32,
+                tiles: self.images.len(),
+            });
+        }
+        let height = layout.rows.len() as i32;
+        let mut tiles = 0;
+        for (row, indices) in layout.rows.iter().enumerate() {
+            // The first row is the top one
+            let y = layout.origin.1 + height - 1 - row as i32;
+            for (column, &index) in indices.iter().enumerate() {
+                let x = layout.origin.0 + column as i32;
+                if index < 0 {
+                    self.clear_tile(x, y);
+                } else {
+                    self.put(x, y, Some(index as u32));
+                    tiles += 1;
+                }
+            }
+        }
+        Ok(tiles)
+    }
+
+    // The world space (min, max) corners of a chunk
+    fn chunk_bounds(&self, key: (i32, i32)) -> (Vec2, Vec2) {
+        let side = CHUNK as f32 * self.tile_size;
+        let min = self.origin + Vec2::new(key.0 as f32, key.1 as f32) * side;
+        (min, min + Vec2::splat(side))
+    }
+
+    // The chunk's quads grouped by the array their image is in, a submesh per array, and
+    // those arrays in the same order
+    fn build(
+        &self,
+        device: &wgpu::Device,
+        memory: &GpuMemoryTracker,
+        key: (i32, i32),
+    ) -> (Mesh, Vec<u32>) {
+        let chunk = &self.chunks[&key];
+        let mut by_array: BTreeMap<u32, Vec<(IVec2, u32)>> = BTreeMap::new();
+        for (at, tile) in chunk.tiles.iter().enumerate() {
+            if let Some(index) = tile {
+                let image = self.images.image(*index as usize);
+                let local = IVec2::new(at as i32 % CHUNK, at as i32 / CHUNK);
+                by_array
+                    .entry(image.array)
+                    .or_default()
+                    .push((local, image.layer));
+            }
+        }
+
+        let (min, _) = self.chunk_bounds(key);
+        let mut vertices = Vec::new();
+        let mut indices: Vec<u16> = Vec::new();
+        let mut submeshes = Vec::new();
+        let mut arrays = Vec::new();
+        for (array, quads) in by_array {
+            let start = indices.len() as u32;
+            for (local, layer) in quads {
+                let corner = min + local.as_vec2() * self.tile_size;
+                let first = vertices.len() as u16;
+                // The order tilemap.wgsl expects the corners in
+                for offset in [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::ONE] {
+                    vertices.push(TileVertex {
+                        position: (corner + offset * self.tile_size).into(),
+                        layer,
+                    });
+                }
+                indices.extend([0, 1, 2, 2, 1, 3].map(|i| first + i));
+            }
+            submeshes.push(SubMesh {
+                name: format!("array {array}"),
+                index_range: start..indices.len() as u32,
+            });
+            arrays.push(array);
+        }
+        let mut mesh = Mesh::new(
+            device,
+            memory,
+            &format!("{} chunk ({}, {})", self.name, key.0, key.1),
+            &TileVertex::desc(),
+            wgpu::PrimitiveTopology::TriangleList,
+            &vertices,
+            Indices::U16(&indices),
+        );
+        mesh.submeshes = submeshes;
+        (mesh, arrays)
+    }
+}
+
+// A block of tiles for TileMap::apply, from a RON file or a CSV one. Rows of tile indices,
+// the first row on top, -1 (or an empty CSV cell) for no tile
+#[derive(Deserialize)]
+pub struct TileLayout {
+    // The tile coordinates of the bottom-left corner. CSV files start at (0, 0)
+    #[serde(default)]
+    pub origin: (i32, i32),
+    pub rows: Vec<Vec<i32>>,
+}
+
+impl TileLayout {
+    // RON for .ron files, CSV for anything else
+    pub fn read(path: &Path) -> Result<Self, ForayError> {
+        let error = |reason: String| ForayError::TileLayout {
+            path: path.to_owned(),
+            reason,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        if path.extension().is_some_and(|extension| extension == "ron") {
+            return ron::from_str(&text).map_err(|e| error(e.to_string()));
+        }
+        let rows = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+            .map(|(number, line)| {
+                line.split(',')
+                    .map(|cell| match cell.trim() {
+                        "" => Ok(-1),
+                        cell => cell
+                            .parse()
+                            .map_err(|_| error(format!("line {}: {cell:?}", number + 1))),
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            origin: (0, 0),
+            rows,
+        })
+    }
+
+    // A `side` x `side` block of bands and blobs over the generated tile set, for the stress
+    // test. Centered on tile (0, 0), so half of it has negative coordinates
+    pub fn stress(side: i32) -> Self {
+        let rows = (0..side)
+            .map(|y| {
+                (0..side)
+                    .map(|x| {
+                        let (x, y) = (x as f32, y as f32);
+                        let wave = (x * 0.05).sin() + (y * 0.07).cos() + (x * y * 0.0003).sin();
+                        ((wave + 3.0) / 6.0 * TILES as f32) as i32 % TILES as i32
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            origin: (-side / 2, -side / 2),
+            rows,
+        }
+    }
+}
+
+// Squares around the palette with a darker edge, so the grid shows
+pub fn tile_set() -> Vec<(String, RgbaImage)> {
+    colors::palette(TILES)
+        .into_iter()
+        .enumerate()
+        .map(|(index, color)| {
+            let [red, green, blue, _] = color.to_unorm8_array();
+            let image = RgbaImage::from_fn(TILE_PIXELS, TILE_PIXELS, |x, y| {
+                let edge = x == 0 || y == 0 || x == TILE_PIXELS - 1 || y == TILE_PIXELS - 1;
+                let shade = |channel: u8| if edge { channel / 2 } else { channel };
+                image::Rgba([shade(red), shade(green), shade(blue), 255])
+            });
+            (format!("tile {index}"), image)
+        })
+        .collect()
+}
+
+// The generated tile set as an ArrayTexture, what the tiles command's maps draw with
+pub fn tile_images(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    memory: &GpuMemoryTracker,
+    sprites: &SpriteRenderer,
+) -> Result<ArrayTexture, ForayError> {
+    ArrayTexture::new(
+        device,
+        queue,
+        memory,
+        sprites,
+        (TILE_PIXELS, TILE_PIXELS),
+        &tile_set(),
+        TilePolicy::Reject,
+    )
+}
+
+// Draws TileMaps out of the same ArrayTextures as SpriteRenderer, with its layouts
+pub struct TileRenderer {
+    camera_layout: wgpu::BindGroupLayout,
+}
+
+impl TileRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bank: &mut RenderPipelineBank,
+        sprites: &SpriteRenderer,
+    ) -> Self {
+        let source = include_str!("tilemap.wgsl");
+        let shader = shaders::create_module(device, "Tile Shader", source);
+        let reflection = Reflection::of("Tile Shader", source);
+        bank.register_surface(
+            device,
+            "tiles",
+            &PipelineBuilder::new("Tile Pipeline", &shader)
+                .reflect(reflection.as_ref())
+                .vertex_entry("vs_tile")
+                .fragment_entry("fs_tile")
+                .vertex_buffer(TileVertex::desc())
+                .bind_group_layout(&sprites.camera_layout)
+                .bind_group_layout(&sprites.images_layout)
+                .blend_mode(BlendMode::Alpha)
+                .cull_mode(None),
+            format,
+        );
+        Self {
+            camera_layout: sprites.camera_layout.clone(),
+        }
+    }
+
+    // Onto the swapchain, clearing it first. Rebuilds the dirty chunks it's about to draw,
+    // the ones off screen wait until they're seen
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &GpuMemoryTracker,
+        frame: &mut Frame,
+        targets: &TargetRegistry,
+        bank: &RenderPipelineBank,
+        pool: &mut BufferPool,
+        map: &mut TileMap,
+        camera: &Camera2d,
+        viewport: (u32, u32),
+    ) -> Result<(), ForayError> {
+        let mut pass = frame.pass(
+            "Tile Pass",
+            &[(ColorTarget::Swapchain, frame.background.color())],
+            targets,
+        );
+        let (view_min, view_max) = camera.visible(viewport);
+        let visible: Vec<(i32, i32)> = map
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&key| {
+                let (min, max) = map.chunk_bounds(key);
+                min.cmplt(view_max).all() && max.cmpgt(view_min).all()
+            })
+            .collect();
+        let mut counts = TileCounts::default();
+        for &key in &visible {
+            if map.chunks[&key].dirty {
+                let (mesh, arrays) = map.build(device, memory, key);
+                let chunk = map.chunks.get_mut(&key).unwrap();
+                chunk.mesh = Some(mesh);
+                chunk.arrays = arrays;
+                chunk.dirty = false;
+                counts.rebuilt += 1;
+            }
+        }
+        counts.total = map.chunks.len();
+
+        let uniform = CameraUniform::new(camera, viewport);
+        let camera_buffer = pool.acquire(
+            device,
+            "Tile Camera Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            std::mem::size_of::<CameraUniform>() as u64,
+        );
+        queue.write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&uniform));
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tile Camera Bind Group"),
+            layout: &self.camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &camera_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as u64),
+                }),
+            }],
+        });
+
+        pass.set_pipeline(bank, "tiles")?;
+        pass.raw.set_bind_group(0, &camera_bind_group, &[]);
+        // Most tile sets are one array, bound once for the whole map
+        let mut bound = None;
+        for key in visible {
+            let chunk = &map.chunks[&key];
+            let Some(mesh) = &chunk.mesh else {
+                continue;
+            };
+            for (submesh, &array) in chunk.arrays.iter().enumerate() {
+                if bound != Some(array) {
+                    pass.raw
+                        .set_bind_group(1, map.images.bind_group(array), &[]);
+                    bound = Some(array);
+                }
+                pass.draw_submesh(mesh, submesh, 0..1)?;
+            }
+            counts.drawn += 1;
+        }
+        map.counts = counts;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+
+    // A map over the generated tile set, on its own sprite renderer
+    fn map(gpu: &GpuContext) -> TileMap {
+        let memory = GpuMemoryTracker::new();
+        let mut bank = RenderPipelineBank::new();
+        let sprites =
+            SpriteRenderer::new(&gpu.device, wgpu::TextureFormat::Rgba8UnormSrgb, &mut bank);
+        let images = tile_images(&gpu.device, &gpu.queue, &memory, &sprites).unwrap();
+        TileMap::new("test", images, TILE_SIZE)
+    }
+
+    fn layout(name: &str, text: &str) -> Result<TileLayout, ForayError> {
+        let path = std::env::temp_dir().join(format!("wgpu-foray-{}-{name}", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let layout = TileLayout::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        layout
+    }
+
+    #[test]
+    fn negative_tiles_are_in_negative_chunks() {
+        let last = (CHUNK * CHUNK - 1) as usize;
+        assert_eq!(TileMap::locate(-1, -1), ((-1, -1), last));
+        assert_eq!(TileMap::locate(-CHUNK, -CHUNK), ((-1, -1), 0));
+        assert_eq!(
+            TileMap::locate(-CHUNK - 1, 0),
+            ((-2, 0), (CHUNK - 1) as usize)
+        );
+        assert_eq!(TileMap::locate(0, 0), ((0, 0), 0));
+        assert_eq!(
+            TileMap::locate(CHUNK - 1, 0),
+            ((0, 0), (CHUNK - 1) as usize)
+        );
+        assert_eq!(TileMap::locate(CHUNK, 0), ((1, 0), 0));
+        assert_eq!(
+            TileMap::locate(3, CHUNK + 2),
+            ((0, 1), (2 * CHUNK + 3) as usize)
+        );
+    }
+
+    #[test]
+    fn csv_layouts_read_empty_cells_as_no_tile() {
+        let layout = layout("empty.csv", "# a comment\n1,,2\n\n , 3,\n").unwrap();
+        assert_eq!(layout.origin, (0, 0));
+        assert_eq!(layout.rows, vec![vec![1, -1, 2], vec![-1, 3, -1]]);
+    }
+
+    #[test]
+    fn a_bad_csv_cell_names_its_line() {
+        let Err(ForayError::TileLayout { reason, .. }) = layout("bad.csv", "1,2\n\n3,grass\n")
+        else {
+            panic!("a cell that isn't a number should be refused");
+        };
+        assert_eq!(reason, "line 3: \"grass\"");
+    }
+
+    #[test]
+    fn an_out_of_range_layout_changes_nothing() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let mut map = map(gpu);
+        map.set_tile(0, 0, 1).unwrap();
+        let tiles = map.tile_count() as i32;
+        // The bad index is last, after cells that would have cleared and set tiles
+        let layout = TileLayout {
+            origin: (-1, 0),
+            rows: vec![vec![2, -1, 3, tiles]],
+        };
+        let Err(ForayError::TileIndex { index, .. }) = map.apply(&layout) else {
+            panic!("index {tiles} should be out of range");
+        };
+        assert_eq!(index, tiles as u32);
+        assert_eq!(map.chunk_count(), 1);
+        let (key, at) = TileMap::locate(0, 0);
+        assert_eq!(map.chunks[&key].tiles[at], Some(1));
+        assert_eq!(map.chunks[&key].tiles.iter().flatten().count(), 1);
+        assert!(!map.chunks.contains_key(&(-1, 0)));
+    }
+
+    #[test]
+    fn a_stroke_over_a_chunk_edge_dirties_both_chunks() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let mut map = map(gpu);
+        for x in [0, CHUNK, 2 * CHUNK] {
+            map.set_tile(x, 0, 0).unwrap();
+        }
+        // As if the last draw had rebuilt them
+        for chunk in map.chunks.values_mut() {
+            chunk.dirty = false;
+        }
+        map.paint_line(IVec2::new(CHUNK - 2, 5), IVec2::new(CHUNK + 1, 5), Some(2))
+            .unwrap();
+        let dirty: Vec<(i32, i32)> = map
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.dirty)
+            .map(|(&key, _)| key)
+            .collect();
+        assert_eq!(dirty, vec![(0, 0), (1, 0)]);
+
+        // Painting what's already there doesn't
+        for chunk in map.chunks.values_mut() {
+            chunk.dirty = false;
+        }
+        map.paint_line(IVec2::new(CHUNK - 2, 5), IVec2::new(CHUNK + 1, 5), Some(2))
+            .unwrap();
+        assert!(map.chunks.values().all(|chunk| !chunk.dirty));
+
+        // Neither does clearing a chunk that isn't there, and clearing a chunk's last tile
+        // drops it
+        map.clear_tile(-5, -5);
+        map.clear_tile(2 * CHUNK, 0);
+        assert_eq!(
+            map.chunks.keys().copied().collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0)]
+        );
+    }
+}
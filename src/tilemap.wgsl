@@ -0,0 +1,53 @@
+// A tile map chunk: one quad per tile, four vertices each in the order TileMap writes them,
+// so which corner a vertex is comes from its index. Positions are in world units through
+// the same camera as sprites.wgsl
+
+struct Camera2d {
+    center: vec2<f32>,
+    viewport: vec2<f32>,
+    zoom: f32,
+    _pad0: f32,
+    _pad1: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera2d;
+
+@group(1) @binding(0)
+var images: texture_2d_array<f32>;
+@group(1) @binding(1)
+var images_sampler: sampler;
+
+struct TileVertex {
+    @location(0) position: vec2<f32>,
+    @location(1) layer: u32,
+}
+
+struct TileOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+}
+
+@vertex
+fn vs_tile(@builtin(vertex_index) vertex_index: u32, tile: TileVertex) -> TileOutput {
+    // Bottom-left, bottom-right, top-left, top-right. Image rows go down, world y goes up
+    var uvs = array<vec2<f32>, 4>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+    );
+    let pixels = (tile.position - camera.center) * camera.zoom;
+
+    var out: TileOutput;
+    out.clip_position = vec4<f32>(pixels / (camera.viewport * 0.5), 0.0, 1.0);
+    out.uv = uvs[vertex_index % 4u];
+    out.layer = tile.layer;
+    return out;
+}
+
+@fragment
+fn fs_tile(in: TileOutput) -> @location(0) vec4<f32> {
+    return textureSample(images, images_sampler, in.uv, in.layer);
+}
@@ -0,0 +1,398 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::camera2d::Camera2d;
+use crate::colors::RgbaColor;
+use crate::error::ForayError;
+use crate::pacing::{Easing, Interpolate};
+use crate::post::EffectChain;
+use crate::scene::Scene;
+
+// Left/Right jump this many seconds
+pub const SEEK_STEP: f32 = 1.0;
+
+// What a timeline file looks like, every track can be left out:
+//
+// (
+//     looping: true,
+//     camera: [
+//         (time: 0.0, center: (0.0, 0.0), zoom: 1.0),
+//         (time: 4.0, center: (200.0, 50.0), zoom: 3.0, easing: "smoothstep"),
+//     ],
+//     clear_color: [(time: 0.0, color: "#202830"), (time: 4.0, color: "#f0e0c0")],
+//     params: [(time: 2.0, effect: "vignette", param: "strength", value: 0.8)],
+//     visibility: [(time: 3.0, item: "Pentagon", visible: false)],
+// )
+//
+// A key's easing shapes the way into it from the key before, linear when left out
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct TimelineFile {
+    looping: bool,
+    camera: Vec<CameraKey>,
+    clear_color: Vec<ColorKey>,
+    params: Vec<ParamKey>,
+    visibility: Vec<VisibilityKey>,
+}
+
+#[derive(Deserialize)]
+struct CameraKey {
+    time: f32,
+    center: (f32, f32),
+    zoom: f32,
+    #[serde(default)]
+    easing: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ColorKey {
+    time: f32,
+    // Hex, like the clipboard paste takes
+    color: String,
+    #[serde(default)]
+    easing: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ParamKey {
+    time: f32,
+    effect: String,
+    param: String,
+    value: f32,
+    #[serde(default)]
+    easing: Option<String>,
+}
+
+// Visibility switches rather than blends, the item fades like a toggle from the inspector
+#[derive(Deserialize)]
+struct VisibilityKey {
+    time: f32,
+    item: String,
+    visible: bool,
+}
+
+struct Key<T> {
+    time: f32,
+    value: T,
+    easing: Easing,
+}
+
+// Keys sorted by time. Before the first key it holds the first value, after the last the last
+struct Track<T> {
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Interpolate + Clone> Track<T> {
+    fn sample(&self, time: f32) -> Option<T> {
+        match self.keys.iter().position(|key| key.time > time) {
+            None => self.keys.last().map(|key| key.value.clone()),
+            Some(0) => Some(self.keys[0].value.clone()),
+            Some(next) => {
+                let (from, to) = (&self.keys[next - 1], &self.keys[next]);
+                let t = (time - from.time) / (to.time - from.time);
+                Some(from.value.lerp_state(&to.value, to.easing.apply(t)))
+            }
+        }
+    }
+}
+
+// One effect parameter's keys
+struct ParamTrack {
+    effect: String,
+    param: String,
+    track: Track<f32>,
+}
+
+// Camera, clear color, effect parameters and scene item visibility keyed over time, stepped
+// with the fixed updates. Everything is worked out from the time alone, so seeking, looping
+// and a forced clock all land on the same state a straight play-through would
+pub struct Timeline {
+    pub path: PathBuf,
+    pub time: f32,
+    // Time of the last key of any track
+    pub duration: f32,
+    pub playing: bool,
+    pub looping: bool,
+    camera: Track<Camera2d>,
+    clear_color: Track<RgbaColor>,
+    params: Vec<ParamTrack>,
+    // Item name, then (time, visible) in time order
+    visibility: Vec<(String, Vec<(f32, bool)>)>,
+}
+
+// Where in the file something is wrong, like "camera keyframe 2, easing"
+fn at(track: &str, index: usize, field: &str, reason: impl std::fmt::Display) -> String {
+    format!("{track} keyframe {index}, {field}: {reason}")
+}
+
+fn check_time(track: &str, index: usize, time: f32, previous: f32) -> Result<(), String> {
+    if !time.is_finite() || time < 0.0 {
+        Err(at(
+            track,
+            index,
+            "time",
+            format!("{time} isn't a time, they start at 0"),
+        ))
+    } else if time < previous {
+        Err(at(
+            track,
+            index,
+            "time",
+            format!("{time} comes before the key before it ({previous})"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn easing(track: &str, index: usize, name: &Option<String>) -> Result<Easing, String> {
+    match name.as_deref() {
+        None => Ok(Easing::Linear),
+        Some(name) => Easing::from_name(name).ok_or_else(|| {
+            at(
+                track,
+                index,
+                "easing",
+                format!("unknown easing \"{name}\" (linear, smoothstep, ease-out)"),
+            )
+        }),
+    }
+}
+
+impl Timeline {
+    pub fn load(path: &Path) -> Result<Self, ForayError> {
+        let error = |reason: String| ForayError::TimelineFile {
+            path: path.to_owned(),
+            reason,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let file: TimelineFile = ron::from_str(&text).map_err(|e| error(e.to_string()))?;
+        Self::from_file(path, file).map_err(error)
+    }
+
+    fn from_file(path: &Path, file: TimelineFile) -> Result<Self, String> {
+        let mut camera = Vec::new();
+        let mut previous = 0.0;
+        for (index, key) in file.camera.iter().enumerate() {
+            check_time("camera", index, key.time, previous)?;
+            if !(Camera2d::MIN_ZOOM..=Camera2d::MAX_ZOOM).contains(&key.zoom) {
+                return Err(at(
+                    "camera",
+                    index,
+                    "zoom",
+                    format!(
+                        "{} is outside {} to {}",
+                        key.zoom,
+                        Camera2d::MIN_ZOOM,
+                        Camera2d::MAX_ZOOM
+                    ),
+                ));
+            }
+            camera.push(Key {
+                time: key.time,
+                value: Camera2d {
+                    center: Vec2::from(key.center),
+                    zoom: key.zoom,
+                    ..Camera2d::new()
+                },
+                easing: easing("camera", index, &key.easing)?,
+            });
+            previous = key.time;
+        }
+
+        let mut clear_color = Vec::new();
+        let mut previous = 0.0;
+        for (index, key) in file.clear_color.iter().enumerate() {
+            check_time("clear_color", index, key.time, previous)?;
+            let value = RgbaColor::parse_hex(&key.color)
+                .map_err(|e| at("clear_color", index, "color", e))?;
+            clear_color.push(Key {
+                time: key.time,
+                value,
+                easing: easing("clear_color", index, &key.easing)?,
+            });
+            previous = key.time;
+        }
+
+        // Keys of different parameters can be mixed in the file, times only have to go up
+        // within one parameter
+        let mut params: Vec<ParamTrack> = Vec::new();
+        for (index, key) in file.params.iter().enumerate() {
+            let position = params
+                .iter()
+                .position(|track| track.effect == key.effect && track.param == key.param)
+                .unwrap_or_else(|| {
+                    params.push(ParamTrack {
+                        effect: key.effect.clone(),
+                        param: key.param.clone(),
+                        track: Track { keys: Vec::new() },
+                    });
+                    params.len() - 1
+                });
+            let keys = &mut params[position].track.keys;
+            check_time(
+                "params",
+                index,
+                key.time,
+                keys.last().map_or(0.0, |k| k.time),
+            )?;
+            if !key.value.is_finite() {
+                return Err(at("params", index, "value", "not a number"));
+            }
+            keys.push(Key {
+                time: key.time,
+                value: key.value,
+                easing: easing("params", index, &key.easing)?,
+            });
+        }
+
+        let mut visibility: Vec<(String, Vec<(f32, bool)>)> = Vec::new();
+        for (index, key) in file.visibility.iter().enumerate() {
+            let position = visibility
+                .iter()
+                .position(|(item, _)| *item == key.item)
+                .unwrap_or_else(|| {
+                    visibility.push((key.item.clone(), Vec::new()));
+                    visibility.len() - 1
+                });
+            let keys = &mut visibility[position].1;
+            check_time(
+                "visibility",
+                index,
+                key.time,
+                keys.last().map_or(0.0, |&(time, _)| time),
+            )?;
+            keys.push((key.time, key.visible));
+        }
+
+        let duration = camera
+            .iter()
+            .map(|key| key.time)
+            .chain(clear_color.iter().map(|key| key.time))
+            .chain(
+                params
+                    .iter()
+                    .flat_map(|p| p.track.keys.iter().map(|key| key.time)),
+            )
+            .chain(
+                visibility
+                    .iter()
+                    .flat_map(|(_, keys)| keys.iter().map(|k| k.0)),
+            )
+            .fold(0.0, f32::max);
+        Ok(Self {
+            path: path.to_owned(),
+            time: 0.0,
+            duration,
+            playing: false,
+            looping: file.looping,
+            camera: Track { keys: camera },
+            clear_color: Track { keys: clear_color },
+            params,
+            visibility,
+        })
+    }
+
+    // Drops parameter tracks the chain doesn't know, reported once here instead of every
+    // step. The first key's value gets set, which playback would do anyway
+    pub fn check_params(&mut self, post: &mut EffectChain) {
+        self.params.retain(|track| {
+            let first = track.track.keys[0].value;
+            match post.set_param(&track.effect, &track.param, first) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("Timeline {}: {e}, ignoring its keys", self.path.display());
+                    false
+                }
+            }
+        });
+    }
+
+    // Moves the clock while playing. At the end it starts over when looping, otherwise stops
+    pub fn step(&mut self, step: Duration) {
+        if !self.playing {
+            return;
+        }
+        self.time += step.as_secs_f32();
+        if self.time >= self.duration {
+            if self.looping && self.duration > 0.0 {
+                self.time %= self.duration;
+            } else {
+                self.time = self.duration;
+                self.playing = false;
+            }
+        }
+    }
+
+    // Jumps `seconds` either way, clamped to the timeline
+    pub fn seek(&mut self, seconds: f32) {
+        self.time = (self.time + seconds).clamp(0.0, self.duration);
+    }
+
+    // Pressing play at the end starts from the top
+    pub fn toggle_playing(&mut self) {
+        self.playing = !self.playing;
+        if self.playing && self.time >= self.duration {
+            self.time = 0.0;
+        }
+    }
+
+    // None when the file has no keys for it
+    pub fn camera(&self) -> Option<Camera2d> {
+        self.camera.sample(self.time)
+    }
+
+    pub fn clear_color(&self) -> Option<RgbaColor> {
+        self.clear_color.sample(self.time)
+    }
+
+    pub fn apply_params(&self, post: &mut EffectChain) {
+        for track in &self.params {
+            if let Some(value) = track.track.sample(self.time) {
+                if let Err(e) = post.set_param(&track.effect, &track.param, value) {
+                    log::warn!("{e}");
+                }
+            }
+        }
+    }
+
+    // Each named item is made to match its last key at or before now, so jumping around
+    // the timeline shows and hides whatever it has to. Before its first key an item is
+    // left alone
+    pub fn apply_visibility(&self, scene: &mut Scene) {
+        for (name, keys) in &self.visibility {
+            let Some(&(_, visible)) = keys.iter().rev().find(|&&(time, _)| time <= self.time)
+            else {
+                continue;
+            };
+            match scene.find(name) {
+                Some(index) if scene.is_shown(index) != visible => scene.toggle_hidden(index),
+                Some(_) => {}
+                None => log::debug!("Timeline: no scene item named \"{name}\""),
+            }
+        }
+    }
+
+    // What the overlay shows next to the scrub bar
+    pub fn readout(&self) -> String {
+        format!(
+            "Timeline {:.1}s / {:.1}s{}{}",
+            self.time,
+            self.duration,
+            if self.playing { "" } else { " paused" },
+            if self.looping { " loop" } else { "" }
+        )
+    }
+
+    // 0 to 1, how far along the scrub bar is
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            self.time / self.duration
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tracing::span;
+
+// A capture left running stops recording here instead of eating all the memory. Spans
+// are capped the same way, the ones past it get an id that's never recorded
+const MAX_EVENTS: usize = 1 << 20;
+
+// One begin or end of a span, in Chrome's trace event format
+struct TraceEvent {
+    name: &'static str,
+    begin: bool,
+    // Microseconds since the capture started
+    timestamp: f64,
+    thread: u64,
+}
+
+// Spans recorded into memory while a capture runs, written out as a Chrome trace
+// (chrome://tracing, Perfetto) when it stops. Outside a capture enabled() says no, so spans
+// are never even created
+pub struct ChromeTrace {
+    path: PathBuf,
+    capturing: AtomicBool,
+    started: Mutex<Instant>,
+    // Name of every span created during the capture, a span's id is its index + 1
+    spans: Mutex<Vec<&'static str>>,
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // Small numbers read better on the viewer's tracks than OS thread ids
+    static THREAD: Cell<u64> = const { Cell::new(0) };
+}
+
+fn thread_id() -> u64 {
+    THREAD.with(|thread| {
+        if thread.get() == 0 {
+            thread.set(NEXT_THREAD.fetch_add(1, Ordering::Relaxed));
+        }
+        thread.get()
+    })
+}
+
+// Installs the recorder when --trace-chrome was given. Without it there's no subscriber
+// at all and the spans around the frame phases cost next to nothing
+pub fn init(path: Option<PathBuf>) -> Option<&'static ChromeTrace> {
+    let trace: &'static ChromeTrace = Box::leak(Box::new(ChromeTrace {
+        path: path?,
+        capturing: AtomicBool::new(false),
+        started: Mutex::new(Instant::now()),
+        spans: Mutex::new(Vec::new()),
+        events: Mutex::new(Vec::new()),
+    }));
+    match tracing::subscriber::set_global_default(trace) {
+        Ok(()) => Some(trace),
+        Err(e) => {
+            log::warn!("Couldn't install the trace recorder: {e}");
+            None
+        }
+    }
+}
+
+impl ChromeTrace {
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.load(Ordering::Relaxed)
+    }
+
+    // Starts a capture, or stops the running one and writes it out
+    pub fn toggle(&self) {
+        if self.is_capturing() {
+            self.stop();
+        } else {
+            self.spans.lock().expect("Trace spans poisoned").clear();
+            self.events.lock().expect("Trace events poisoned").clear();
+            *self.started.lock().expect("Trace start poisoned") = Instant::now();
+            self.capturing.store(true, Ordering::Relaxed);
+            println!("Trace capture started");
+        }
+    }
+
+    pub fn stop(&self) {
+        if !self.capturing.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let mut events = std::mem::take(&mut *self.events.lock().expect("Trace events poisoned"));
+        let ended = self.started.lock().expect("Trace start poisoned").elapsed();
+        close_open_spans(&mut events, ended.as_secs_f64() * 1e6);
+        match std::fs::write(&self.path, Self::to_json(&events)) {
+            Ok(()) => println!(
+                "Wrote {} trace events to {}",
+                events.len(),
+                self.path.display()
+            ),
+            Err(e) => log::error!("Couldn't write {}: {e}", self.path.display()),
+        }
+    }
+
+    fn to_json(events: &[TraceEvent]) -> String {
+        let mut json = String::from("{\"traceEvents\":[\n");
+        for (index, event) in events.iter().enumerate() {
+            if index > 0 {
+                json.push_str(",\n");
+            }
+            let _ = write!(
+                json,
+                "{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{:.3},\"pid\":1,\"tid\":{}}}",
+                event.name.escape_default(),
+                if event.begin { "B" } else { "E" },
+                event.timestamp,
+                event.thread
+            );
+        }
+        json.push_str("\n]}\n");
+        json
+    }
+
+    fn push(&self, id: &span::Id, begin: bool) {
+        if !self.is_capturing() {
+            return;
+        }
+        // Spans from before the capture started aren't in the table
+        let Some(&name) = self
+            .spans
+            .lock()
+            .expect("Trace spans poisoned")
+            .get(id.into_u64() as usize - 1)
+        else {
+            return;
+        };
+        let started = *self.started.lock().expect("Trace start poisoned");
+        let mut events = self.events.lock().expect("Trace events poisoned");
+        if events.len() < MAX_EVENTS {
+            events.push(TraceEvent {
+                name,
+                begin,
+                timestamp: started.elapsed().as_secs_f64() * 1e6,
+                thread: thread_id(),
+            });
+        }
+    }
+}
+
+// Id of the span just created. Past MAX_EVENTS nothing is kept and they all get the id one
+// past the end, which push() doesn't find
+fn add_span(spans: &mut Vec<&'static str>, name: &'static str) -> span::Id {
+    if spans.len() < MAX_EVENTS {
+        spans.push(name);
+        span::Id::from_u64(spans.len() as u64)
+    } else {
+        span::Id::from_u64(MAX_EVENTS as u64 + 1)
+    }
+}
+
+// A capture can stop with spans still entered, the frame span around the key that stops
+// it for one. Viewers drop a B without its E, so they're ended at `end`, innermost first
+fn close_open_spans(events: &mut Vec<TraceEvent>, end: f64) {
+    let mut open: Vec<(u64, &'static str)> = Vec::new();
+    for event in events.iter() {
+        if event.begin {
+            open.push((event.thread, event.name));
+        } else if let Some(at) = open
+            .iter()
+            .rposition(|&(thread, name)| thread == event.thread && name == event.name)
+        {
+            open.remove(at);
+        }
+    }
+    events.extend(open.into_iter().rev().map(|(thread, name)| TraceEvent {
+        name,
+        begin: false,
+        timestamp: end,
+        thread,
+    }));
+}
+
+impl tracing::Subscriber for &'static ChromeTrace {
+    // Asked again every time instead of cached, so starting a capture takes effect
+    fn register_callsite(
+        &self,
+        _metadata: &'static tracing::Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        tracing::subscriber::Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.is_span() && self.is_capturing()
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let mut spans = self.spans.lock().expect("Trace spans poisoned");
+        add_span(&mut spans, span.metadata().name())
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, span: &span::Id) {
+        self.push(span, true);
+    }
+
+    fn exit(&self, span: &span::Id) {
+        self.push(span, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &'static str, begin: bool, timestamp: f64, thread: u64) -> TraceEvent {
+        TraceEvent {
+            name,
+            begin,
+            timestamp,
+            thread,
+        }
+    }
+
+    #[test]
+    fn open_spans_are_closed_innermost_first() {
+        let mut events = vec![
+            event("frame", true, 0.0, 1),
+            event("update", true, 1.0, 1),
+            event("update", false, 2.0, 1),
+            event("render", true, 3.0, 1),
+            event("loader", true, 3.5, 2),
+        ];
+        close_open_spans(&mut events, 10.0);
+        let closing: Vec<(&str, bool, u64)> = events[5..]
+            .iter()
+            .map(|e| (e.name, e.begin, e.thread))
+            .collect();
+        assert_eq!(
+            closing,
+            [
+                ("loader", false, 2),
+                ("render", false, 1),
+                ("frame", false, 1)
+            ]
+        );
+        assert!(events[5..]
+            .iter()
+            .all(|e| (e.timestamp - 10.0).abs() < 1e-9));
+
+        // Every B has its E in the file
+        let json = ChromeTrace::to_json(&events);
+        assert_eq!(
+            json.matches("\"ph\":\"B\"").count(),
+            json.matches("\"ph\":\"E\"").count()
+        );
+    }
+
+    #[test]
+    fn balanced_captures_are_left_alone() {
+        let mut events = vec![event("frame", true, 0.0, 1), event("frame", false, 1.0, 1)];
+        close_open_spans(&mut events, 5.0);
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn spans_stop_being_kept_at_the_cap() {
+        let mut spans = Vec::new();
+        assert_eq!(add_span(&mut spans, "frame"), span::Id::from_u64(1));
+        spans.resize(MAX_EVENTS, "filler");
+        let past = add_span(&mut spans, "late");
+        assert_eq!(spans.len(), MAX_EVENTS);
+        assert_eq!(past, span::Id::from_u64(MAX_EVENTS as u64 + 1));
+    }
+}
@@ -0,0 +1,358 @@
+use std::cell::Cell;
+
+use glam::{Mat4, Quat, Vec2, Vec3};
+#[cfg(feature = "serde-scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::pacing::Interpolate;
+
+// What both transforms can do, so code placing things doesn't care whether it's 2D or 3D.
+// Kept as translation, rotation and scale rather than a matrix so each part can be edited,
+// tweened and saved on its own. Composing and inverting stay exact as long as scale is
+// uniform, a non-uniform scale under a rotation shears, which TRS can't hold
+pub trait Affine: Sized {
+    type Vector;
+
+    fn to_matrix(&self) -> Mat4;
+    // `child` placed in this one's space, applying the result is applying child then self
+    fn then(&self, child: &Self) -> Self;
+    fn inverse(&self) -> Self;
+    fn transform_point(&self, point: Self::Vector) -> Self::Vector;
+    // Directions and offsets, no translation
+    fn transform_vector(&self, vector: Self::Vector) -> Self::Vector;
+}
+
+// Scene items. Copy and edited in place (physics, drags, undo), so nothing is cached, the
+// matrix is a handful of multiplies anyway
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde-scene", derive(Serialize, Deserialize))]
+pub struct Transform2d {
+    pub translation: Vec2,
+    // Radians, counter-clockwise
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Transform2d {
+    pub fn at(translation: Vec2) -> Self {
+        Self {
+            translation,
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.translation.is_finite() && self.rotation.is_finite() && self.scale.is_finite()
+    }
+}
+
+impl Affine for Transform2d {
+    type Vector = Vec2;
+
+    // In the z = 0 plane
+    fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            Vec3::new(self.scale, self.scale, 1.0),
+            Quat::from_rotation_z(self.rotation),
+            self.translation.extend(0.0),
+        )
+    }
+
+    fn then(&self, child: &Self) -> Self {
+        Self {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation + child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+
+    fn inverse(&self) -> Self {
+        let scale = 1.0 / self.scale;
+        Self {
+            translation: Vec2::from_angle(-self.rotation).rotate(-self.translation) * scale,
+            rotation: -self.rotation,
+            scale,
+        }
+    }
+
+    fn transform_point(&self, point: Vec2) -> Vec2 {
+        self.translation + self.transform_vector(point)
+    }
+
+    fn transform_vector(&self, vector: Vec2) -> Vec2 {
+        Vec2::from_angle(self.rotation).rotate(vector * self.scale)
+    }
+}
+
+impl Interpolate for Transform2d {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(next.translation, alpha),
+            rotation: self.rotation + (next.rotation - self.rotation) * alpha,
+            scale: self.scale + (next.scale - self.scale) * alpha,
+        }
+    }
+}
+
+// Things in the 3D views. The parts can't be changed in place, a changed transform is a new
+// one, so the matrix built on first use stays right for as long as the value lives
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-scene", derive(Serialize, Deserialize))]
+pub struct Transform {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    #[cfg_attr(feature = "serde-scene", serde(skip))]
+    matrix: Cell<Option<Mat4>>,
+}
+
+impl Transform {
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+            matrix: Cell::new(None),
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self::new(translation, Quat::IDENTITY, Vec3::ONE)
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self::new(Vec3::ZERO, rotation, Vec3::ONE)
+    }
+
+    pub fn with_scale(self, scale: f32) -> Self {
+        Self::new(self.translation, self.rotation, Vec3::splat(scale))
+    }
+}
+
+impl Affine for Transform {
+    type Vector = Vec3;
+
+    fn to_matrix(&self) -> Mat4 {
+        if let Some(matrix) = self.matrix.get() {
+            return matrix;
+        }
+        let matrix =
+            Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation);
+        self.matrix.set(Some(matrix));
+        matrix
+    }
+
+    fn then(&self, child: &Self) -> Self {
+        Self::new(
+            self.transform_point(child.translation),
+            self.rotation * child.rotation,
+            self.scale * child.scale,
+        )
+    }
+
+    fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        let scale = self.scale.recip();
+        Self::new(rotation * -self.translation * scale, rotation, scale)
+    }
+
+    fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.transform_vector(point)
+    }
+
+    fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.rotation * (vector * self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, Quat::IDENTITY, Vec3::ONE)
+    }
+}
+
+// The parts, whatever the cache holds
+impl PartialEq for Transform {
+    fn eq(&self, other: &Self) -> bool {
+        self.translation == other.translation
+            && self.rotation == other.rotation
+            && self.scale == other.scale
+    }
+}
+
+impl Interpolate for Transform {
+    fn lerp_state(&self, next: &Self, alpha: f32) -> Self {
+        Self::new(
+            self.translation.lerp(next.translation, alpha),
+            self.rotation.slerp(next.rotation, alpha),
+            self.scale.lerp(next.scale, alpha),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CASES: usize = 500;
+
+    // Xorshift, plenty for picking transforms
+    struct Rng(u32);
+
+    impl Rng {
+        // Evenly in lo..hi
+        fn range(&mut self, lo: f32, hi: f32) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            lo + (hi - lo) * (self.0 >> 8) as f32 / 16_777_216.0
+        }
+
+        fn vec2(&mut self, extent: f32) -> Vec2 {
+            Vec2::new(self.range(-extent, extent), self.range(-extent, extent))
+        }
+
+        fn vec3(&mut self, extent: f32) -> Vec3 {
+            self.vec2(extent).extend(self.range(-extent, extent))
+        }
+
+        // Either sign, kept away from zero so the inverse stays reasonable
+        fn scale(&mut self) -> f32 {
+            let scale = self.range(0.2, 5.0);
+            if self.range(0.0, 1.0) < 0.2 {
+                -scale
+            } else {
+                scale
+            }
+        }
+
+        fn transform2d(&mut self) -> Transform2d {
+            Transform2d {
+                translation: self.vec2(100.0),
+                rotation: self.range(-10.0, 10.0),
+                scale: self.scale(),
+            }
+        }
+
+        fn transform(&mut self) -> Transform {
+            let axis = self.vec3(1.0).try_normalize().unwrap_or(Vec3::Z);
+            let rotation = Quat::from_axis_angle(axis, self.range(-10.0, 10.0));
+            Transform::new(self.vec3(100.0), rotation, Vec3::splat(self.scale()))
+        }
+    }
+
+    // Relative to the size of what's compared, the points go up to a few hundred
+    fn near2(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() <= 1e-4 * a.length().max(b.length()).max(1.0)
+    }
+
+    fn near3(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() <= 1e-4 * a.length().max(b.length()).max(1.0)
+    }
+
+    #[test]
+    fn composed_with_its_inverse_2d_is_the_identity() {
+        let mut rng = Rng(0x2d2d_2d2d);
+        for _ in 0..CASES {
+            let composed = rng.transform2d().then(&rng.transform2d());
+            let point = rng.vec2(50.0);
+            for identity in [
+                composed.then(&composed.inverse()),
+                composed.inverse().then(&composed),
+            ] {
+                assert!(
+                    near2(identity.transform_point(point), point),
+                    "{composed:?} {identity:?}"
+                );
+                assert!((identity.scale - 1.0).abs() < 1e-4, "{identity:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn composed_with_its_inverse_3d_is_the_identity() {
+        let mut rng = Rng(0x3d3d_3d3d);
+        for _ in 0..CASES {
+            let composed = rng.transform().then(&rng.transform());
+            let point = rng.vec3(50.0);
+            for identity in [
+                composed.then(&composed.inverse()),
+                composed.inverse().then(&composed),
+            ] {
+                assert!(
+                    near3(identity.transform_point(point), point),
+                    "{composed:?} {identity:?}"
+                );
+                assert!(
+                    identity.to_matrix().abs_diff_eq(Mat4::IDENTITY, 1e-3),
+                    "{identity:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_matrix_matches_applying_the_parts_2d() {
+        let mut rng = Rng(0x0bad_f00d);
+        for _ in 0..CASES {
+            let (parent, child) = (rng.transform2d(), rng.transform2d());
+            let (point, vector) = (rng.vec2(50.0), rng.vec2(1.0));
+            for transform in [parent, parent.then(&child)] {
+                let matrix = transform.to_matrix();
+                let moved = matrix.transform_point3(point.extend(0.0));
+                assert!(near2(moved.truncate(), transform.transform_point(point)));
+                // Stays in the z = 0 plane
+                assert!(moved.z.abs() < 1e-4);
+                let turned = matrix.transform_vector3(vector.extend(0.0)).truncate();
+                assert!(near2(turned, transform.transform_vector(vector)));
+            }
+            // Composing the parts is multiplying the matrices
+            let product = parent.to_matrix() * child.to_matrix();
+            let composed = parent.then(&child).to_matrix();
+            assert!(near3(
+                product.transform_point3(point.extend(0.0)),
+                composed.transform_point3(point.extend(0.0))
+            ));
+        }
+    }
+
+    #[test]
+    fn the_matrix_matches_applying_the_parts_3d() {
+        let mut rng = Rng(0xfeed_beef);
+        for _ in 0..CASES {
+            let (parent, child) = (rng.transform(), rng.transform());
+            let (point, vector) = (rng.vec3(50.0), rng.vec3(1.0));
+            for transform in [parent.clone(), parent.then(&child)] {
+                // Built once, then the cached one, the same either way
+                for _ in 0..2 {
+                    let matrix = transform.to_matrix();
+                    assert!(near3(
+                        matrix.transform_point3(point),
+                        transform.transform_point(point)
+                    ));
+                    assert!(near3(
+                        matrix.transform_vector3(vector),
+                        transform.transform_vector(vector)
+                    ));
+                }
+            }
+            let product = parent.to_matrix() * child.to_matrix();
+            assert!(near3(
+                product.transform_point3(point),
+                parent.then(&child).transform_point(point)
+            ));
+        }
+        // A non-uniform scale can't compose, but on its own the matrix still agrees
+        let stretched = Transform::new(
+            Vec3::new(1.0, 2.0, 3.0),
+            Quat::from_rotation_y(0.7),
+            Vec3::new(2.0, -0.5, 3.0),
+        );
+        let point = Vec3::new(4.0, 5.0, -6.0);
+        assert!(near3(
+            stretched.to_matrix().transform_point3(point),
+            stretched.transform_point(point)
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+use glam::Vec2;
+
+use crate::camera2d::Camera2d;
+use crate::colors::{RgbaColor, Theme};
+use crate::frame::Frame;
+use crate::shapes::{Stroke, Width};
+use crate::transform::{Affine, Transform2d};
+
+// Sizes are in screen pixels and get divided by the zoom when drawn, so the gizmo stays the
+// same size on screen however far in or out the camera is
+const MOVE_ARM: f32 = 24.0;
+const RING_RADIUS: f32 = 80.0;
+// Scale handles sit on the diagonals, inside the ring
+const SCALE_OFFSET: f32 = 40.0;
+const SCALE_HANDLE: f32 = 5.0;
+// How far off a handle a click still grabs it
+const GRAB: f32 = 6.0;
+// Constrained rotation goes in steps of this many radians (15 degrees)
+const ROTATE_STEP: f32 = std::f32::consts::PI / 12.0;
+const SCALE_STEP: f32 = 0.1;
+const MIN_SCALE: f32 = 0.05;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Handle {
+    Move,
+    Rotate,
+    // Corner, counter-clockwise from the top right (before the item's rotation)
+    Scale(usize),
+}
+
+const CORNERS: [Vec2; 4] = [
+    Vec2::new(1.0, 1.0),
+    Vec2::new(-1.0, 1.0),
+    Vec2::new(-1.0, -1.0),
+    Vec2::new(1.0, -1.0),
+];
+
+struct Drag {
+    handle: Handle,
+    // Where the cursor was in world space when the handle was grabbed
+    grab: Vec2,
+    start: Transform2d,
+}
+
+// Move cross, rotation ring and corner scale handles around the selected scene item.
+// Dragging works out the new transform from where the drag started rather than adding up
+// per-event deltas, so constraining or letting go of a modifier mid-drag doesn't drift
+pub struct TransformGizmo {
+    // Index of the selected scene item
+    pub target: Option<usize>,
+    drag: Option<Drag>,
+}
+
+impl TransformGizmo {
+    pub fn new() -> Self {
+        Self {
+            target: None,
+            drag: None,
+        }
+    }
+
+    // Scale handle positions in world space, they turn with the item
+    fn corners(transform: &Transform2d, camera: &Camera2d) -> [Vec2; 4] {
+        let handles = Transform2d {
+            scale: SCALE_OFFSET / camera.zoom,
+            ..*transform
+        };
+        CORNERS.map(|corner| handles.transform_point(corner))
+    }
+
+    // The handle under `cursor` (glfw screen pixels) for an item at `transform`. Scale
+    // handles win over the others, they're the smallest
+    pub fn hit(
+        &self,
+        transform: &Transform2d,
+        camera: &Camera2d,
+        viewport: (u32, u32),
+        cursor: Vec2,
+    ) -> Option<Handle> {
+        let screen = |world: Vec2| camera.world_to_screen(world, viewport);
+        if let Some(corner) = Self::corners(transform, camera)
+            .iter()
+            .position(|&corner| screen(corner).distance(cursor) <= SCALE_HANDLE + GRAB)
+        {
+            return Some(Handle::Scale(corner));
+        }
+        let distance = screen(transform.translation).distance(cursor);
+        if distance <= MOVE_ARM + GRAB {
+            Some(Handle::Move)
+        } else if (distance - RING_RADIUS).abs() <= GRAB {
+            Some(Handle::Rotate)
+        } else {
+            None
+        }
+    }
+
+    // `grab` is the cursor in world space
+    pub fn begin(&mut self, handle: Handle, grab: Vec2, start: Transform2d) {
+        self.drag = Some(Drag {
+            handle,
+            grab,
+            start,
+        });
+    }
+
+    pub fn dragging(&self) -> Option<Handle> {
+        self.drag.as_ref().map(|drag| drag.handle)
+    }
+
+    // Lets go, returns what was being dragged and the transform before the drag
+    pub fn end(&mut self) -> Option<(Handle, Transform2d)> {
+        self.drag.take().map(|drag| (drag.handle, drag.start))
+    }
+
+    // The transform for the cursor at `cursor` (world space). Working in world space is
+    // what converts pixel drags by the zoom. `constrain` keeps moves on the X or Y axis,
+    // whichever the drag is mostly along, and snaps rotation and scale to steps. Scale is
+    // always uniform, Transform2d has a single factor
+    pub fn drag(&self, cursor: Vec2, constrain: bool) -> Option<Transform2d> {
+        let drag = self.drag.as_ref()?;
+        // Rotation and scale are measured around the item, in its own space as it was
+        let local = drag.start.inverse();
+        let (grab, now) = (
+            local.transform_point(drag.grab),
+            local.transform_point(cursor),
+        );
+        let mut transform = drag.start;
+        match drag.handle {
+            Handle::Move => {
+                let mut delta = cursor - drag.grab;
+                if constrain {
+                    if delta.x.abs() >= delta.y.abs() {
+                        delta.y = 0.0;
+                    } else {
+                        delta.x = 0.0;
+                    }
+                }
+                transform.translation += delta;
+            }
+            Handle::Rotate => {
+                let mut angle = grab.angle_to(now);
+                if constrain {
+                    angle = (angle / ROTATE_STEP).round() * ROTATE_STEP;
+                }
+                transform.rotation += angle;
+            }
+            Handle::Scale(_) => {
+                let from = grab.length().max(f32::EPSILON);
+                let mut scale = drag.start.scale * now.length() / from;
+                if constrain {
+                    scale = (scale / SCALE_STEP).round() * SCALE_STEP;
+                }
+                transform.scale = scale.max(MIN_SCALE);
+            }
+        }
+        Some(transform)
+    }
+
+    // Scene item `index` went away, the selection follows the indices moving down
+    pub fn removed(&mut self, index: usize) {
+        self.target = match self.target {
+            Some(target) if target == index => {
+                self.drag = None;
+                None
+            }
+            Some(target) if target > index => Some(target - 1),
+            other => other,
+        };
+    }
+
+    // An item came (back) in at `index`, the ones from there on moved up
+    pub fn inserted(&mut self, index: usize) {
+        if let Some(target) = &mut self.target {
+            if *target >= index {
+                *target += 1;
+            }
+        }
+    }
+
+    // Handles around `transform` with the shape primitives, the one being dragged highlighted
+    pub fn queue(
+        &self,
+        frame: &mut Frame,
+        theme: &Theme,
+        transform: &Transform2d,
+        camera: &Camera2d,
+    ) {
+        let pixels = |size: f32| size / camera.zoom;
+        let active = self.dragging();
+        let color = |handle: Handle, normal: RgbaColor| {
+            if active == Some(handle) {
+                theme.selection
+            } else {
+                normal
+            }
+        };
+        let center = transform.translation;
+
+        let ring = color(Handle::Rotate, theme.axis_z);
+        frame.draw_circle(
+            center,
+            pixels(RING_RADIUS),
+            Stroke::Outline(Width::Pixels(2.0)),
+            ring,
+        );
+        // Knob where the item's rotation points
+        frame.draw_circle(
+            center + Vec2::from_angle(transform.rotation) * pixels(RING_RADIUS),
+            pixels(4.0),
+            Stroke::Fill,
+            ring,
+        );
+
+        let corners = Self::corners(transform, camera);
+        for (index, &corner) in corners.iter().enumerate() {
+            frame.world_line(
+                corner,
+                corners[(index + 1) % corners.len()],
+                theme.grid_major,
+            );
+        }
+        for (index, &corner) in corners.iter().enumerate() {
+            frame.draw_circle(
+                corner,
+                pixels(SCALE_HANDLE),
+                Stroke::Fill,
+                color(Handle::Scale(index), theme.text),
+            );
+        }
+
+        // World axes, moves go along those whatever the item's rotation
+        let arm = pixels(MOVE_ARM);
+        frame.draw_line(
+            center - Vec2::X * arm,
+            center + Vec2::X * arm,
+            Width::Pixels(3.0),
+            color(Handle::Move, theme.axis_x),
+        );
+        frame.draw_line(
+            center - Vec2::Y * arm,
+            center + Vec2::Y * arm,
+            Width::Pixels(3.0),
+            color(Handle::Move, theme.axis_y),
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::backend::WindowBackend;
+
+// How the window shows what's behind it. Per-pixel transparency (--transparent) needs a
+// transparent framebuffer from the compositor and a surface alpha mode that blends, and is
+// settled once at startup. Opacity and click-through apply to the whole window and can change
+// any time
+pub struct Transparency {
+    pub alpha_mode: wgpu::CompositeAlphaMode,
+    // Alpha the views clear to, 1 for an opaque window
+    pub background_alpha: f64,
+    pub opacity: f32,
+    pub click_through: bool,
+}
+
+impl Transparency {
+    // `requested` is the --transparent alpha. Anything the window or the surface can't do
+    // leaves it opaque and says why, instead of clearing to a black that was meant to be see-through
+    pub fn negotiate(
+        requested: Option<f64>,
+        framebuffer_transparent: bool,
+        alpha_modes: &[wgpu::CompositeAlphaMode],
+    ) -> Self {
+        let opaque = Self {
+            alpha_mode: alpha_modes[0],
+            background_alpha: 1.0,
+            opacity: 1.0,
+            click_through: false,
+        };
+        let Some(alpha) = requested else {
+            return opaque;
+        };
+        if !framebuffer_transparent {
+            log::warn!(
+                "The window system didn't give the window a transparent framebuffer (no compositor?), staying opaque"
+            );
+            return opaque;
+        }
+        let blending = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ]
+        .into_iter()
+        .find(|mode| alpha_modes.contains(mode));
+        let Some(alpha_mode) = blending else {
+            log::warn!(
+                "The surface only composites as {alpha_modes:?}, none of which blend with the desktop, staying opaque"
+            );
+            return opaque;
+        };
+        println!("Transparent window, background alpha {alpha}, composited {alpha_mode:?}");
+        Self {
+            alpha_mode,
+            background_alpha: alpha.clamp(0.0, 1.0),
+            ..opaque
+        }
+    }
+
+    // A view's clear color as the surface wants it, with the background alpha and, for a
+    // premultiplied surface, the color channels scaled by it
+    pub fn clear_color(&self, color: wgpu::Color) -> wgpu::Color {
+        let color = wgpu::Color {
+            a: color.a * self.background_alpha,
+            ..color
+        };
+        match self.alpha_mode {
+            wgpu::CompositeAlphaMode::PreMultiplied => crate::colors::premultiply(color),
+            _ => color,
+        }
+    }
+
+    pub fn set_opacity(&mut self, window: &mut impl WindowBackend, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        window.set_opacity(self.opacity);
+        println!("Window opacity {:.2}", self.opacity);
+    }
+
+    pub fn set_click_through(&mut self, window: &mut impl WindowBackend, enabled: bool) {
+        self.click_through = enabled;
+        window.set_click_through(enabled);
+        println!(
+            "Click-through {}",
+            if enabled {
+                "on, clicks go to the window behind"
+            } else {
+                "off"
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::{linear_to_srgb, RgbaColor};
+    use crate::gpu_context::GpuContext;
+    use crate::stats;
+    use wgpu::CompositeAlphaMode::{Auto, Inherit, Opaque, PostMultiplied, PreMultiplied};
+
+    const SRGB: [f64; 3] = [0.2, 0.5, 0.75];
+    const BLUE: RgbaColor = RgbaColor::rgba(SRGB[0], SRGB[1], SRGB[2], 1.0);
+
+    #[test]
+    fn transparency_takes_the_first_alpha_mode_that_blends() {
+        let window =
+            Transparency::negotiate(Some(0.5), true, &[Opaque, PostMultiplied, PreMultiplied]);
+        assert_eq!(window.alpha_mode, PreMultiplied);
+        let window = Transparency::negotiate(Some(0.5), true, &[Opaque, PostMultiplied]);
+        assert_eq!(window.alpha_mode, PostMultiplied);
+        assert!((window.background_alpha - 0.5).abs() < 1e-9);
+        // Past 1 is still just opaque
+        let window = Transparency::negotiate(Some(3.0), true, &[PreMultiplied]);
+        assert!((window.background_alpha - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn windows_stay_opaque_when_they_cant_be_see_through() {
+        for window in [
+            Transparency::negotiate(None, true, &[Opaque, PreMultiplied]),
+            Transparency::negotiate(Some(0.5), false, &[Opaque, PreMultiplied]),
+            Transparency::negotiate(Some(0.5), true, &[Auto, Opaque, Inherit]),
+        ] {
+            assert!(!matches!(window.alpha_mode, PreMultiplied | PostMultiplied));
+            assert!((window.background_alpha - 1.0).abs() < 1e-9);
+        }
+    }
+
+    // What a view cleared for the window reads back as, one texel of an sRGB target like the
+    // scene's view of the swapchain
+    fn cleared(gpu: &GpuContext, window: &Transparency) -> [u8; 4] {
+        let target = gpu.target((4, 4), wgpu::TextureFormat::Rgba8UnormSrgb);
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transparent Clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(window.clear_color(BLUE.to_wgpu_linear())),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        stats::submit(&gpu.queue, [encoder.finish()]);
+        let texels = gpu.read_texels(&target);
+        // Every texel of a clear is the same
+        assert!(texels.chunks(4).all(|texel| texel == &texels[..4]));
+        texels[..4].try_into().unwrap()
+    }
+
+    fn assert_near(actual: [u8; 4], expected: [f64; 4]) {
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            let expected = (expected * 255.0).round();
+            assert!(
+                (f64::from(actual) - expected).abs() <= 1.0,
+                "Read back {actual}, wanted {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn transparent_regions_read_back_with_the_alpha_the_surface_wants() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let linear = BLUE.to_wgpu_linear();
+
+        // Premultiplied: the color scaled by alpha in linear light, then encoded
+        let window = Transparency::negotiate(Some(0.25), true, &[Opaque, PreMultiplied]);
+        assert_near(
+            cleared(gpu, &window),
+            [
+                linear_to_srgb(linear.r * 0.25),
+                linear_to_srgb(linear.g * 0.25),
+                linear_to_srgb(linear.b * 0.25),
+                0.25,
+            ],
+        );
+        // Postmultiplied keeps the color as it is
+        let window = Transparency::negotiate(Some(0.25), true, &[Opaque, PostMultiplied]);
+        assert_near(cleared(gpu, &window), [SRGB[0], SRGB[1], SRGB[2], 0.25]);
+        // And the fallback is the opaque color, not the black it would be premultiplied by 0
+        let window = Transparency::negotiate(Some(0.0), false, &[Opaque, PreMultiplied]);
+        assert_near(cleared(gpu, &window), [SRGB[0], SRGB[1], SRGB[2], 1.0]);
+    }
+}
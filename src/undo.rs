@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+
+use crate::scene::{ItemId, Scene, SceneItem};
+use crate::transform::Transform2d;
+
+// Oldest edits fall off past this
+pub const UNDO_LIMIT: usize = 100;
+
+// One edit of the scene that can be taken back. Items are referred to by id, an index
+// recorded before some other item was added or removed would point at the wrong one
+#[derive(Clone, Debug)]
+pub enum SceneCommand {
+    SetTransform {
+        item: ItemId,
+        before: Transform2d,
+        after: Transform2d,
+    },
+    SetPipeline {
+        item: ItemId,
+        before: String,
+        after: String,
+    },
+    ToggleHidden(ItemId),
+    // The item as it was added and where, so redo can put the same one back
+    Add {
+        item: SceneItem,
+        index: usize,
+    },
+    Remove {
+        item: SceneItem,
+        index: usize,
+    },
+}
+
+impl SceneCommand {
+    // Remove for the item at `index`, with what it takes to bring it back
+    pub fn remove(scene: &Scene, index: usize) -> Self {
+        SceneCommand::Remove {
+            item: scene.items[index].clone(),
+            index,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SceneCommand::SetTransform { .. } => "transform",
+            SceneCommand::SetPipeline { .. } => "pipeline change",
+            SceneCommand::ToggleHidden(_) => "show/hide",
+            SceneCommand::Add { .. } => "add",
+            SceneCommand::Remove { .. } => "remove",
+        }
+    }
+
+    // Returns the index of the item that came back into the scene, when one did, so
+    // whatever is kept per item can make room for it
+    pub fn apply(&self, scene: &mut Scene) -> Option<usize> {
+        match self {
+            SceneCommand::SetTransform { item, after, .. } => {
+                set_transform(scene, *item, *after);
+                None
+            }
+            SceneCommand::SetPipeline { item, after, .. } => {
+                set_pipeline(scene, *item, after);
+                None
+            }
+            SceneCommand::ToggleHidden(item) => {
+                toggle_hidden(scene, *item);
+                None
+            }
+            SceneCommand::Add { item, index } => bring_back(scene, item, *index),
+            SceneCommand::Remove { item, .. } => {
+                take_away(scene, item.id);
+                None
+            }
+        }
+    }
+
+    pub fn revert(&self, scene: &mut Scene) -> Option<usize> {
+        match self {
+            SceneCommand::SetTransform { item, before, .. } => {
+                set_transform(scene, *item, *before);
+                None
+            }
+            SceneCommand::SetPipeline { item, before, .. } => {
+                set_pipeline(scene, *item, before);
+                None
+            }
+            SceneCommand::ToggleHidden(item) => {
+                toggle_hidden(scene, *item);
+                None
+            }
+            SceneCommand::Add { item, .. } => {
+                take_away(scene, item.id);
+                None
+            }
+            SceneCommand::Remove { item, index } => bring_back(scene, item, *index),
+        }
+    }
+}
+
+// Commands only refer to items by id, one that's gone can't be edited anymore
+fn find(scene: &Scene, item: ItemId) -> Option<usize> {
+    let index = scene.index_of(item);
+    if index.is_none() {
+        log::warn!("Scene item {item:?} is gone, skipping");
+    }
+    index
+}
+
+fn set_transform(scene: &mut Scene, item: ItemId, transform: Transform2d) {
+    if let Some(index) = find(scene, item) {
+        if let Err(e) = scene.set_transform(index, transform) {
+            log::warn!("{e}");
+        }
+    }
+}
+
+fn set_pipeline(scene: &mut Scene, item: ItemId, pipeline: &str) {
+    if let Some(index) = find(scene, item) {
+        scene.set_pipeline(index, pipeline);
+    }
+}
+
+fn toggle_hidden(scene: &mut Scene, item: ItemId) {
+    if let Some(index) = find(scene, item) {
+        scene.toggle_hidden(index);
+    }
+}
+
+// Fades it out, it leaves `items` once that's done
+fn take_away(scene: &mut Scene, item: ItemId) {
+    if let Some(index) = find(scene, item) {
+        scene.remove(index);
+    }
+}
+
+// Still fading out it only turns around, otherwise it goes back in where it was
+fn bring_back(scene: &mut Scene, item: &SceneItem, index: usize) -> Option<usize> {
+    match scene.index_of(item.id) {
+        Some(current) => {
+            scene.restore(current);
+            None
+        }
+        None => Some(scene.insert(index, item.clone())),
+    }
+}
+
+// Ctrl+Z / Ctrl+Shift+Z. Edits are recorded once they're done: a drag is one transform
+// command from where it started to where it was let go, not one per mouse move
+pub struct UndoStack {
+    done: VecDeque<SceneCommand>,
+    undone: Vec<SceneCommand>,
+}
+
+impl UndoStack {
+    pub fn new() -> Self {
+        Self {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    // Applies it and records it, see SceneCommand::apply for what comes back
+    pub fn apply(&mut self, scene: &mut Scene, command: SceneCommand) -> Option<usize> {
+        let inserted = command.apply(scene);
+        self.record(command);
+        inserted
+    }
+
+    // For edits that already happened, like a finished drag. A new edit makes whatever
+    // was undone unreachable
+    pub fn record(&mut self, command: SceneCommand) {
+        self.undone.clear();
+        self.done.push_back(command);
+        if self.done.len() > UNDO_LIMIT {
+            self.done.pop_front();
+        }
+    }
+
+    // The command taken back and the index of an item it brought back, None when there
+    // was nothing to undo
+    pub fn undo(&mut self, scene: &mut Scene) -> Option<(&SceneCommand, Option<usize>)> {
+        let command = self.done.pop_back()?;
+        let inserted = command.revert(scene);
+        self.undone.push(command);
+        self.undone.last().map(|command| (command, inserted))
+    }
+
+    pub fn redo(&mut self, scene: &mut Scene) -> Option<(&SceneCommand, Option<usize>)> {
+        let command = self.undone.pop()?;
+        let inserted = command.apply(scene);
+        self.done.push_back(command);
+        self.done.back().map(|command| (command, inserted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glam::Vec2;
+
+    use super::*;
+
+    fn scene() -> Scene {
+        let mut scene = Scene::starter();
+        scene.items.clear();
+        for item in Scene::starter().items {
+            scene.add(item);
+        }
+        // Long enough to be done fading
+        scene.update(Duration::from_secs(1));
+        scene
+    }
+
+    // Names and positions of what's in the scene and not on its way out
+    fn state(scene: &Scene) -> Vec<(String, Vec2)> {
+        scene
+            .items
+            .iter()
+            .filter(|item| !item.removing)
+            .map(|item| (item.name.clone(), item.transform.translation))
+            .collect()
+    }
+
+    fn moved(scene: &Scene, name: &str, to: Vec2) -> SceneCommand {
+        let item = &scene.items[scene.find(name).unwrap()];
+        SceneCommand::SetTransform {
+            item: item.id,
+            before: item.transform,
+            after: Transform2d::at(to),
+        }
+    }
+
+    #[test]
+    fn scripted_edits_undo_and_redo_step_by_step() {
+        let mut scene = scene();
+        let mut history = UndoStack::new();
+        let mut states = vec![state(&scene)];
+
+        let command = moved(&scene, "Square", Vec2::new(5.0, 5.0));
+        history.apply(&mut scene, command);
+        states.push(state(&scene));
+
+        let mut circle = Scene::starter().items.remove(0);
+        circle.name = "Circle".to_owned();
+        let index = scene.add(circle);
+        history.record(SceneCommand::Add {
+            item: scene.items[index].clone(),
+            index,
+        });
+        states.push(state(&scene));
+
+        let command = SceneCommand::remove(&scene, scene.find("Pentagon").unwrap());
+        history.apply(&mut scene, command);
+        scene.update(Duration::from_secs(1));
+        states.push(state(&scene));
+
+        let command = moved(&scene, "Circle", Vec2::new(-40.0, 7.0));
+        history.apply(&mut scene, command);
+        states.push(state(&scene));
+
+        for expected in states.iter().rev().skip(1) {
+            assert!(history.undo(&mut scene).is_some());
+            scene.update(Duration::from_secs(1));
+            assert_eq!(&state(&scene), expected);
+        }
+        assert!(history.undo(&mut scene).is_none());
+        for expected in states.iter().skip(1) {
+            assert!(history.redo(&mut scene).is_some());
+            scene.update(Duration::from_secs(1));
+            assert_eq!(&state(&scene), expected);
+        }
+        assert!(history.redo(&mut scene).is_none());
+    }
+
+    // Undoing a remove puts the same item back, so a later edit recorded against it (and
+    // redoing the remove) still finds it after other items moved its index around
+    #[test]
+    fn redo_after_remove_follows_the_item_not_its_index() {
+        let mut scene = scene();
+        let mut history = UndoStack::new();
+        let square = scene.items[1].id;
+
+        let command = SceneCommand::remove(&scene, 1);
+        history.apply(&mut scene, command);
+        scene.update(Duration::from_secs(1));
+        assert_eq!(scene.index_of(square), None);
+
+        let (_, inserted) = history.undo(&mut scene).unwrap();
+        assert_eq!(inserted, Some(1));
+        assert_eq!(scene.index_of(square), Some(1));
+
+        // The item in front of it goes, the square is now at 0 and the triangle at 1
+        scene.remove(0);
+        scene.update(Duration::from_secs(1));
+        assert_eq!(scene.index_of(square), Some(0));
+        history.redo(&mut scene).unwrap();
+        scene.update(Duration::from_secs(1));
+        assert_eq!(scene.index_of(square), None);
+        let names: Vec<&str> = scene.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["Triangle"]);
+
+        // Undone again it comes back with its id, edits of it still apply
+        history.undo(&mut scene).unwrap();
+        let back = scene.index_of(square).unwrap();
+        assert_eq!(scene.items[back].name, "Square");
+        let command = moved(&scene, "Square", Vec2::new(1.0, 2.0));
+        history.apply(&mut scene, command);
+        assert_eq!(
+            scene.items[scene.index_of(square).unwrap()]
+                .transform
+                .translation,
+            Vec2::new(1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn removes_undone_mid_fade_turn_around() {
+        let mut scene = scene();
+        let mut history = UndoStack::new();
+        let command = SceneCommand::remove(&scene, 0);
+        history.apply(&mut scene, command);
+        scene.update(Duration::from_millis(50));
+        // Still there, restored in place rather than inserted twice
+        let (_, inserted) = history.undo(&mut scene).unwrap();
+        assert_eq!(inserted, None);
+        scene.update(Duration::from_secs(1));
+        assert_eq!(scene.items.len(), 3);
+        assert!(scene.items.iter().all(|item| !item.removing));
+    }
+
+    #[test]
+    fn the_stack_is_capped() {
+        let mut scene = scene();
+        let mut history = UndoStack::new();
+        for step in 0..UNDO_LIMIT + 10 {
+            let command = moved(&scene, "Triangle", Vec2::splat(step as f32));
+            history.apply(&mut scene, command);
+        }
+        let undone = std::iter::from_fn(|| history.undo(&mut scene).map(|_| ())).count();
+        assert_eq!(undone, UNDO_LIMIT);
+        // Back as far as the oldest edit kept
+        let triangle = &scene.items[scene.find("Triangle").unwrap()];
+        assert_eq!(triangle.transform.translation, Vec2::splat(9.0));
+    }
+}
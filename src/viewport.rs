@@ -0,0 +1,158 @@
+use glam::Vec2;
+
+use crate::camera2d::Camera2d;
+use crate::targets::{TargetDesc, TargetHandle, TargetRegistry};
+
+// Fraction of the window each side of an inset takes, its target is created at the same
+// scale so it's drawn 1:1
+const INSET_SCALE: f32 = 0.25;
+// Gap between the inset and the window edges, in pixels
+const INSET_MARGIN: f32 = 16.0;
+
+// A second look at the scene: its own camera, rendered into its own target and shown as
+// an inset in the bottom right corner of the window
+pub struct Viewport {
+    pub enabled: bool,
+    pub camera: Camera2d,
+    pub target: TargetHandle,
+}
+
+impl Viewport {
+    pub fn new(
+        device: &wgpu::Device,
+        registry: &mut TargetRegistry,
+        label: &'static str,
+        format: wgpu::TextureFormat,
+        camera: Camera2d,
+    ) -> Self {
+        let target = registry.create(
+            device,
+            TargetDesc {
+                label,
+                format,
+                scale: INSET_SCALE,
+                storage: false,
+            },
+        );
+        Self {
+            enabled: false,
+            camera,
+            target,
+        }
+    }
+
+    // The inset is drawn with the swapchain's pipelines, so its target follows the surface
+    pub fn set_format(
+        &self,
+        device: &wgpu::Device,
+        registry: &mut TargetRegistry,
+        format: wgpu::TextureFormat,
+    ) {
+        registry.set_format(device, self.target, format);
+    }
+
+    // Where it goes on screen (x, y, width, height in pixels, origin top-left), following
+    // the window size. The size is the target's, which the registry keeps up with resizes
+    pub fn rect(&self, registry: &TargetRegistry, screen: (u32, u32)) -> (f32, f32, f32, f32) {
+        let (width, height) = registry.size(self.target);
+        let (width, height) = (width as f32, height as f32);
+        (
+            screen.0 as f32 - width - INSET_MARGIN,
+            screen.1 as f32 - height - INSET_MARGIN,
+            width,
+            height,
+        )
+    }
+
+    // Whether `point` (glfw screen pixels) is covered by the inset, clicks there aren't
+    // meant for the world underneath
+    pub fn covers(&self, registry: &TargetRegistry, screen: (u32, u32), point: Vec2) -> bool {
+        let (x, y, width, height) = self.rect(registry, screen);
+        self.enabled && point.x >= x && point.x < x + width && point.y >= y && point.y < y + height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blit::Blitter;
+    use crate::buffer_pool::BufferPool;
+    use crate::colors::{Colors, RgbaColor};
+    use crate::frame::{Background, ColorTarget, Frame};
+    use crate::gpu_context::GpuContext;
+    use crate::memory::GpuMemoryTracker;
+    use crate::pipeline_bank::RenderPipelineBank;
+    use crate::shapes::tests::{FORMAT, SIZE};
+    use crate::shapes::{ShapeInstance, ShapeRenderer, Stroke};
+    use crate::stats;
+
+    #[test]
+    fn a_frame_with_an_inset_is_submitted_once() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let device = &gpu.device;
+        let memory = GpuMemoryTracker::new();
+        let mut bank = RenderPipelineBank::new();
+        let shapes = ShapeRenderer::new(device, FORMAT, &mut bank);
+        let blitter = Blitter::new(device, FORMAT);
+        let mut targets = TargetRegistry::new(SIZE, &memory);
+        let inset = Viewport::new(device, &mut targets, "Test Inset", FORMAT, Camera2d::new());
+        let mut pool = BufferPool::new(&memory, 0);
+        let texture = gpu.target(SIZE, FORMAT);
+        let dot = [ShapeInstance::circle(
+            Vec2::ZERO,
+            4.0,
+            Stroke::Fill,
+            RgbaColor::from_hex(0x000000),
+        )];
+        let inset_clear = RgbaColor::from_hex(0x2060a0);
+
+        stats::take_submits();
+        let mut frame = Frame::offscreen(
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            device,
+            FORMAT,
+            Background::Clear(Colors::WHITE.to_wgpu_linear()),
+        );
+        let load = frame.background.color();
+        // Like State::render with the inset on: the main view, the inset's view into its
+        // target, then the inset onto the main one, all in the frame's encoder
+        for (camera, size, target) in [
+            (Camera2d::new(), SIZE, (ColorTarget::Swapchain, load)),
+            (
+                inset.camera,
+                targets.size(inset.target),
+                (
+                    ColorTarget::Offscreen(inset.target),
+                    wgpu::LoadOp::Clear(inset_clear.to_wgpu_linear()),
+                ),
+            ),
+        ] {
+            shapes
+                .draw_into(
+                    device, &gpu.queue, &mut frame, &targets, &bank, &mut pool, &dot, &camera,
+                    size, target,
+                )
+                .unwrap();
+        }
+        let rect = inset.rect(&targets, SIZE);
+        blitter.blit_to_rect(device, &mut frame, &targets, inset.target, rect);
+        frame.finish(&gpu.queue);
+        assert_eq!(stats::take_submits(), 1);
+
+        // And the inset made it on, in the corner it covers
+        let image = gpu.read_back(&texture);
+        let (x, y) = (rect.0 as u32, rect.1 as u32);
+        let corner = image.get_pixel(x, y).0;
+        let expected = inset_clear.to_unorm8_array();
+        assert!(
+            corner
+                .iter()
+                .zip(expected)
+                .all(|(&a, b)| a.abs_diff(b) <= 1),
+            "Inset corner is {corner:?}, wanted {expected:?}"
+        );
+    }
+}
@@ -0,0 +1,19 @@
+#include "post.wgsl"
+
+struct Vignette {
+    strength: f32,
+    // Distance from the center where darkening starts, 1 is the middle of an edge
+    radius: f32,
+    softness: f32,
+    _padding: f32,
+};
+
+@group(1) @binding(0) var<uniform> vignette: Vignette;
+
+@fragment
+fn fs_vignette(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv).rgb;
+    let distance = length(in.uv - 0.5) * 2.0;
+    let edge = smoothstep(vignette.radius, vignette.radius + vignette.softness, distance);
+    return vec4<f32>(color * (1.0 - vignette.strength * edge), 1.0);
+}
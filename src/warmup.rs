@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::pipeline_bank::{Pipeline, RenderPipelineBank};
+use crate::reflect::{BindingKind, SampleKind, ShaderBinding};
+use crate::stats;
+
+// Every placeholder uniform and storage buffer. Bigger than any struct or array the shaders
+// here declare, and within the default max_uniform_buffer_binding_size
+const BUFFER_SIZE: u64 = 64 * 1024;
+// Bound to every vertex buffer slot, 3 vertices or one instance of any layout fit
+const VERTEX_BUFFER_SIZE: u64 = 4096;
+// A pipeline taking longer than this gets a warning of its own
+const SLOW: Duration = Duration::from_millis(50);
+// How many of the slowest the summary names
+const SLOWEST: usize = 5;
+
+// Some drivers only finish compiling a pipeline at its first draw, which is a hitch the first
+// time a key switches to it. This draws a triangle with each of `names` into 1x1 targets of
+// its formats, with placeholder resources bound where the shader wants them, and waits for
+// each so the time it took can be reported. Resources come from the reflected bindings, a
+// pipeline with bind groups but no reflection is skipped. Anything that still fails
+// validation is logged and skipped, it doesn't reach the device's error handler
+pub fn warm_up(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    bank: &RenderPipelineBank,
+    names: &[String],
+) {
+    let started = Instant::now();
+    let mut placeholders = Placeholders::new(device);
+    let mut times: Vec<(&str, Duration)> = Vec::new();
+    let mut skipped = 0;
+    for name in names {
+        let Some(pipeline) = bank.get(name) else {
+            continue;
+        };
+        let bindings = match &pipeline.bindings {
+            Some(bindings) => bindings.as_slice(),
+            None if pipeline.bind_groups == 0 => &[],
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let pipeline_started = Instant::now();
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        draw(device, queue, &mut placeholders, name, pipeline, bindings);
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            log::warn!("Warming up pipeline \"{name}\" failed, {e}");
+            skipped += 1;
+            continue;
+        }
+        let time = pipeline_started.elapsed();
+        if time > SLOW {
+            log::warn!("Pipeline \"{name}\" took {time:.1?} to warm up");
+        }
+        times.push((name, time));
+    }
+    if times.is_empty() && skipped == 0 {
+        return;
+    }
+
+    times.sort_by_key(|&(_, time)| std::cmp::Reverse(time));
+    let slowest: Vec<String> = times
+        .iter()
+        .take(SLOWEST)
+        .map(|(name, time)| format!("{name} {time:.1?}"))
+        .collect();
+    println!(
+        "Warmed up {} pipelines in {:.1?}{}{}",
+        times.len(),
+        started.elapsed(),
+        if slowest.is_empty() {
+            String::new()
+        } else {
+            format!(", slowest: {}", slowest.join(", "))
+        },
+        if skipped > 0 {
+            format!(". Skipped {skipped} without usable bind group reflection")
+        } else {
+            String::new()
+        },
+    );
+}
+
+// One pipeline's draw, submitted and waited for
+fn draw(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    placeholders: &mut Placeholders,
+    name: &str,
+    pipeline: &Pipeline,
+    bindings: &[ShaderBinding],
+) {
+    let resources: Vec<(&ShaderBinding, Resource)> = bindings
+        .iter()
+        .map(|binding| (binding, Resource::new(device, binding.kind)))
+        .collect();
+    let bind_groups: Vec<wgpu::BindGroup> = (0..pipeline.bind_groups)
+        .map(|group| {
+            let entries: Vec<wgpu::BindGroupEntry> = resources
+                .iter()
+                .filter(|(binding, _)| binding.group == group)
+                .map(|(binding, resource)| wgpu::BindGroupEntry {
+                    binding: binding.binding,
+                    resource: placeholders.bind(resource),
+                })
+                .collect();
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Warm-up"),
+                layout: &pipeline.raw.get_bind_group_layout(group),
+                entries: &entries,
+            })
+        })
+        .collect();
+    let color_views: Vec<wgpu::TextureView> = pipeline
+        .targets
+        .iter()
+        .map(|&format| placeholders.attachment(device, format))
+        .collect();
+    let depth_view = pipeline
+        .depth
+        .map(|format| placeholders.attachment(device, format));
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Warm-up"),
+    });
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(name),
+        color_attachments: &color_views
+            .iter()
+            .map(|view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations::default(),
+                })
+            })
+            .collect::<Vec<_>>(),
+        depth_stencil_attachment: depth_view.as_ref().map(|view| {
+            wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations::default()),
+                stencil_ops: None,
+            }
+        }),
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    pass.set_pipeline(&pipeline.raw);
+    for (index, bind_group) in (0u32..).zip(&bind_groups) {
+        pass.set_bind_group(index, bind_group, &[]);
+    }
+    for slot in 0..pipeline.vertex_buffers {
+        pass.set_vertex_buffer(slot, placeholders.vertices.slice(..));
+    }
+    // All zeros, so a triangle out of a vertex buffer has no area. Fullscreen ones cover the
+    // one pixel
+    pass.draw(0..3, 0..1);
+    drop(pass);
+    let submission = stats::submit(queue, std::iter::once(encoder.finish()));
+    device.poll(wgpu::Maintain::wait_for(submission));
+}
+
+// What warm-up draws bind and draw into, made on first use and shared by all of them
+struct Placeholders {
+    vertices: wgpu::Buffer,
+    attachments: HashMap<wgpu::TextureFormat, wgpu::Texture>,
+    sampler: wgpu::Sampler,
+    comparison_sampler: wgpu::Sampler,
+}
+
+impl Placeholders {
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            vertices: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Warm-up Vertices"),
+                size: VERTEX_BUFFER_SIZE,
+                usage: wgpu::BufferUsages::VERTEX,
+                mapped_at_creation: false,
+            }),
+            attachments: HashMap::new(),
+            // Nearest filtering fits filtering and non-filtering layout entries alike
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Warm-up"),
+                ..Default::default()
+            }),
+            comparison_sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Warm-up Comparison"),
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn attachment(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::TextureView {
+        self.attachments
+            .entry(format)
+            .or_insert_with(|| {
+                texture(
+                    device,
+                    format,
+                    wgpu::TextureDimension::D2,
+                    1,
+                    wgpu::TextureUsages::RENDER_ATTACHMENT,
+                )
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn bind<'a>(&'a self, resource: &'a Resource) -> wgpu::BindingResource<'a> {
+        match resource {
+            Resource::Buffer(buffer) => buffer.as_entire_binding(),
+            Resource::Texture(view) => wgpu::BindingResource::TextureView(view),
+            Resource::Sampler { comparison: true } => {
+                wgpu::BindingResource::Sampler(&self.comparison_sampler)
+            }
+            Resource::Sampler { comparison: false } => {
+                wgpu::BindingResource::Sampler(&self.sampler)
+            }
+        }
+    }
+}
+
+// One binding's placeholder. A uniform and a storage buffer can't be the same buffer in
+// one bind group, nor can two read-write storage buffers, so each binding gets its own
+enum Resource {
+    Buffer(wgpu::Buffer),
+    Texture(wgpu::TextureView),
+    Sampler { comparison: bool },
+}
+
+impl Resource {
+    fn new(device: &wgpu::Device, kind: BindingKind) -> Self {
+        match kind {
+            BindingKind::Uniform | BindingKind::Storage { .. } | BindingKind::Other => {
+                Resource::Buffer(device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Warm-up"),
+                    size: BUFFER_SIZE,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                }))
+            }
+            BindingKind::Sampler { comparison } => Resource::Sampler { comparison },
+            BindingKind::Texture {
+                sample, dimension, ..
+            } => {
+                let format = match sample {
+                    SampleKind::Float => wgpu::TextureFormat::Rgba8Unorm,
+                    SampleKind::Depth => wgpu::TextureFormat::Depth32Float,
+                    SampleKind::Sint => wgpu::TextureFormat::R32Sint,
+                    SampleKind::Uint => wgpu::TextureFormat::R32Uint,
+                };
+                Resource::texture(
+                    device,
+                    format,
+                    dimension,
+                    wgpu::TextureUsages::TEXTURE_BINDING,
+                )
+            }
+            BindingKind::StorageTexture {
+                format, dimension, ..
+            } => Resource::texture(
+                device,
+                // Validation turns a format reflection couldn't name down, and the pipeline
+                // is skipped
+                format.unwrap_or(wgpu::TextureFormat::R32Float),
+                dimension,
+                wgpu::TextureUsages::STORAGE_BINDING,
+            ),
+        }
+    }
+
+    fn texture(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        dimension: wgpu::TextureViewDimension,
+        usage: wgpu::TextureUsages,
+    ) -> Self {
+        let (texture_dimension, layers) = match dimension {
+            wgpu::TextureViewDimension::D1 => (wgpu::TextureDimension::D1, 1),
+            wgpu::TextureViewDimension::D3 => (wgpu::TextureDimension::D3, 1),
+            wgpu::TextureViewDimension::Cube | wgpu::TextureViewDimension::CubeArray => {
+                (wgpu::TextureDimension::D2, 6)
+            }
+            wgpu::TextureViewDimension::D2 | wgpu::TextureViewDimension::D2Array => {
+                (wgpu::TextureDimension::D2, 1)
+            }
+        };
+        let texture = texture(device, format, texture_dimension, layers, usage);
+        Resource::Texture(texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(dimension),
+            ..Default::default()
+        }))
+    }
+}
+
+fn texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    dimension: wgpu::TextureDimension,
+    layers: u32,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Warm-up"),
+        size: wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::maintain::{self, OpDone};
+
+// --gpu-timeout when not given. Well past any frame this renders, heavy scenes on a slow
+// GPU can raise it
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct Submission {
+    frame: u64,
+    submitted: Instant,
+    // What the frame drew with, see Frame::pipelines
+    pipelines: Vec<String>,
+    done: OpDone,
+}
+
+// What the frame loop should do, from Watchdog::check
+pub enum GpuHealth {
+    Running,
+    // A submission is overdue, nothing more gets submitted until it finishes
+    Hung,
+    // The device is gone. There's no recreating it, the app can only stop
+    Lost(String),
+}
+
+// Notices when the GPU stops finishing work, a shader stuck in a loop mostly. Every frame's
+// submit gets an on_submitted_work_done callback, and one that doesn't fire within the
+// timeout is reported with the pipelines that frame used. Frames stop being submitted until
+// it does (piling more work behind a hang only makes the driver reset take longer), and a
+// device lost arriving after that is reported with the same context
+pub struct Watchdog {
+    timeout: Duration,
+    // Not known to be done yet, oldest first
+    pending: VecDeque<Submission>,
+    // The submission that was reported overdue, until it finishes
+    hung: Option<(u64, Instant, Vec<String>)>,
+    lost: Arc<Mutex<Option<String>>>,
+}
+
+impl Watchdog {
+    pub fn new(device: &wgpu::Device, timeout: Duration) -> Self {
+        let watchdog = Self::with_timeout(timeout);
+        let reported = watchdog.lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Destroyed is us dropping the device on the way out
+            if reason != wgpu::DeviceLostReason::Destroyed {
+                *reported.lock().unwrap() = Some(message);
+            }
+        });
+        watchdog
+    }
+
+    // Not watching any device yet
+    fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: VecDeque::new(),
+            hung: None,
+            lost: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Right after a frame's submit
+    pub fn submitted(&mut self, queue: &wgpu::Queue, frame: u64, pipelines: Vec<String>) {
+        let done = maintain::untracked();
+        let finished = done.clone();
+        queue.on_submitted_work_done(move || finished.finish());
+        self.track(frame, pipelines, done, Instant::now());
+    }
+
+    fn track(&mut self, frame: u64, pipelines: Vec<String>, done: OpDone, submitted: Instant) {
+        self.pending.push_back(Submission {
+            frame,
+            submitted,
+            pipelines,
+            done,
+        });
+    }
+
+    // Before each frame. Polls first, the last poll may have been long ago (an idle event
+    // driven window, or frames held back by a hang) and the callbacks only fire in one
+    pub fn check(&mut self, device: &wgpu::Device) -> GpuHealth {
+        device.poll(wgpu::Maintain::Poll);
+        self.health(Instant::now())
+    }
+
+    // What check says once the callbacks are in, as of `now`
+    fn health(&mut self, now: Instant) -> GpuHealth {
+        if let Some(reason) = self.lost.lock().unwrap().clone() {
+            return GpuHealth::Lost(self.lost_report(&reason));
+        }
+        while self
+            .pending
+            .front()
+            .is_some_and(|submission| submission.done.is_done())
+        {
+            self.pending.pop_front();
+        }
+
+        if let Some((frame, since, _)) = &self.hung {
+            if self
+                .pending
+                .front()
+                .is_some_and(|oldest| oldest.frame == *frame)
+            {
+                return GpuHealth::Hung;
+            }
+            log::warn!(
+                "The GPU finished frame {frame} after {:.1?}, submitting frames again",
+                now.saturating_duration_since(*since) + self.timeout
+            );
+            self.hung = None;
+        }
+
+        let Some(oldest) = self.pending.front() else {
+            return GpuHealth::Running;
+        };
+        if now.saturating_duration_since(oldest.submitted) < self.timeout {
+            return GpuHealth::Running;
+        }
+        log::error!("{}", self.hang_report(oldest));
+        self.hung = Some((oldest.frame, now, oldest.pipelines.clone()));
+        GpuHealth::Hung
+    }
+
+    fn hang_report(&self, submission: &Submission) -> String {
+        format!(
+            "GPU appears hung, frame {}'s work hasn't finished in {:?}. Last pipelines: {}. \
+             No more frames are submitted until it does (--gpu-timeout raises the limit)",
+            submission.frame,
+            self.timeout,
+            pipeline_list(&submission.pipelines)
+        )
+    }
+
+    fn lost_report(&self, reason: &str) -> String {
+        match &self.hung {
+            Some((frame, _, pipelines)) => format!(
+                "GPU device lost ({reason}) after hanging on frame {frame}. Last pipelines: {}",
+                pipeline_list(pipelines)
+            ),
+            None => format!("GPU device lost ({reason})"),
+        }
+    }
+}
+
+fn pipeline_list(pipelines: &[String]) -> String {
+    if pipelines.is_empty() {
+        "none recorded".to_owned()
+    } else {
+        pipelines.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_context::GpuContext;
+    use crate::stats;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn pipelines(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| name.to_owned()).collect()
+    }
+
+    #[test]
+    fn an_overdue_frame_is_reported_until_it_finishes() {
+        let mut watchdog = Watchdog::with_timeout(2 * SECOND);
+        let start = Instant::now();
+        let first = maintain::untracked();
+        watchdog.track(1, pipelines(&["sdf_blob", "shapes"]), first.clone(), start);
+        let second = maintain::untracked();
+        watchdog.track(2, pipelines(&[]), second.clone(), start + SECOND);
+
+        // Just under the threshold is still fine
+        assert!(matches!(
+            watchdog.health(start + Duration::from_millis(1999)),
+            GpuHealth::Running
+        ));
+        assert!(matches!(
+            watchdog.health(start + 2 * SECOND),
+            GpuHealth::Hung
+        ));
+        let report = watchdog.hang_report(&watchdog.pending[0]);
+        assert!(
+            report.starts_with("GPU appears hung, frame 1's work hasn't finished in 2s."),
+            "{report}"
+        );
+        assert!(
+            report.contains("Last pipelines: sdf_blob, shapes."),
+            "{report}"
+        );
+        assert!(report.contains("--gpu-timeout"), "{report}");
+        // Held back for as long as it takes, however late
+        assert!(matches!(
+            watchdog.health(start + 60 * SECOND),
+            GpuHealth::Hung
+        ));
+
+        // Frame 1 finishing lets frames through again. Frame 2 is overdue by then too, and
+        // is reported as its own hang
+        first.finish();
+        assert!(matches!(
+            watchdog.health(start + 2 * SECOND + SECOND / 2),
+            GpuHealth::Running
+        ));
+        assert!(matches!(
+            watchdog.health(start + 4 * SECOND),
+            GpuHealth::Hung
+        ));
+        assert_eq!(watchdog.hung.as_ref().map(|(frame, ..)| *frame), Some(2));
+        assert!(watchdog
+            .hang_report(&watchdog.pending[0])
+            .contains("Last pipelines: none recorded."));
+        second.finish();
+        assert!(matches!(
+            watchdog.health(start + 5 * SECOND),
+            GpuHealth::Running
+        ));
+        assert!(watchdog.pending.is_empty() && watchdog.hung.is_none());
+    }
+
+    #[test]
+    fn a_raised_timeout_lets_heavy_frames_through() {
+        let mut watchdog = Watchdog::with_timeout(10 * SECOND);
+        let start = Instant::now();
+        watchdog.track(1, pipelines(&["stress"]), maintain::untracked(), start);
+        assert!(matches!(
+            watchdog.health(start + 5 * SECOND),
+            GpuHealth::Running
+        ));
+    }
+
+    #[test]
+    fn a_lost_device_is_reported_with_the_hang_before_it() {
+        let mut watchdog = Watchdog::with_timeout(2 * SECOND);
+        *watchdog.lost.lock().unwrap() = Some("timed out".to_owned());
+        let GpuHealth::Lost(report) = watchdog.health(Instant::now()) else {
+            panic!("The loss wasn't noticed");
+        };
+        assert_eq!(report, "GPU device lost (timed out)");
+
+        let mut watchdog = Watchdog::with_timeout(2 * SECOND);
+        let start = Instant::now();
+        watchdog.track(7, pipelines(&["sdf_loop"]), maintain::untracked(), start);
+        assert!(matches!(
+            watchdog.health(start + 3 * SECOND),
+            GpuHealth::Hung
+        ));
+        *watchdog.lost.lock().unwrap() = Some("timed out".to_owned());
+        let GpuHealth::Lost(report) = watchdog.health(start + 4 * SECOND) else {
+            panic!("The loss wasn't noticed");
+        };
+        assert_eq!(
+            report,
+            "GPU device lost (timed out) after hanging on frame 7. Last pipelines: sdf_loop"
+        );
+    }
+
+    // Bounded, but long enough for several polls to find it unfinished
+    const SLOW: &str = "
+        @group(0) @binding(0) var<storage, read_write> out: array<u32>;
+
+        @compute @workgroup_size(64)
+        fn spin(@builtin(global_invocation_id) id: vec3<u32>) {
+            var x = id.x;
+            for (var i = 0u; i < 200000u; i++) {
+                x = x * 1664525u + 1013904223u;
+            }
+            out[id.x] = x;
+        }
+    ";
+
+    #[test]
+    fn a_long_dispatch_trips_the_watchdog_and_frames_resume() {
+        let Ok(gpu) = GpuContext::get_or_init() else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let device = &gpu.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Watchdog Test Shader"),
+            source: wgpu::ShaderSource::Wgsl(SLOW.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("slow compute"),
+            layout: None,
+            module: &module,
+            entry_point: Some("spin"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+        let invocations = 256 * 64;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Watchdog Test Output"),
+            size: invocations * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Watchdog Test Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Watchdog Test Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Watchdog Test Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(256, 1, 1);
+        }
+
+        // Anything unfinished is overdue, so the slow dispatch is caught as long as the first
+        // check comes before it's done
+        let mut watchdog = Watchdog::with_timeout(Duration::ZERO);
+        stats::submit(&gpu.queue, std::iter::once(encoder.finish()));
+        watchdog.submitted(&gpu.queue, 1, pipelines(&["slow compute"]));
+        let caught = matches!(watchdog.check(device), GpuHealth::Hung);
+        if caught {
+            assert!(watchdog
+                .hang_report(&watchdog.pending[0])
+                .contains("Last pipelines: slow compute."));
+        } else {
+            eprintln!("The dispatch was done before the first check, the GPU is too quick");
+        }
+
+        device.poll(wgpu::Maintain::Wait);
+        assert!(matches!(watchdog.check(device), GpuHealth::Running));
+        assert!(watchdog.pending.is_empty() && watchdog.hung.is_none());
+    }
+}
@@ -0,0 +1,793 @@
+use glam::{Vec2, Vec3};
+use glfw::{fail_on_errors, Action, Context, Key, MouseButton, WindowEvent};
+
+use crate::backend::{MonitorInfo, WindowBackend};
+use crate::bindings::{Binding, Chord};
+use crate::capabilities::{self, Capabilities};
+use crate::colors::{Colors, RgbaColor};
+use crate::console::ConsoleInput;
+use crate::cursor::{CursorId, CursorKind, CursorStack};
+use crate::options::Options;
+use crate::pacing::FramePacer;
+use crate::playground::Playground;
+#[cfg(feature = "remote")]
+use crate::remote;
+use crate::scene::Scene;
+use crate::shutdown::{App, Teardown};
+use crate::timeline::Timeline;
+#[cfg(feature = "trace")]
+use crate::trace;
+use crate::{backend, crash, headless, log_sink};
+use crate::{device_requirements, open_adapter, State, StateBuilder, View, WindowRequest};
+
+// What run's loop keeps between iterations besides State: the views and toggles that only
+// exist in the window app, the cursors it pushed and what it knows about the monitor. The
+// input State can't handle without the window goes through here
+pub struct WindowLoop {
+    pub pacer: FramePacer,
+    pub cursors: CursorStack,
+    pub playground: Playground,
+    #[cfg(feature = "trace")]
+    pub trace: Option<&'static trace::ChromeTrace>,
+    // --latency-test
+    latency_test: bool,
+    // glfw timestamp of the click the latency test is currently flashing for
+    latency_flash: Option<f64>,
+    triangle_toggle: bool,
+    // The Shapes clear follows the cursor unless a color was pasted, which then sticks
+    cursor_color: RgbaColor,
+    pasted_color: Option<RgbaColor>,
+    // Where the captured cursor was last reported, mouse-look goes by how far it moved
+    look_from: Option<(f64, f64)>,
+    needs_redraw: bool,
+    // Until the startup assets are in
+    loading: bool,
+    show_exposure: bool,
+    show_sprites: bool,
+    show_tiles: bool,
+    // Pushed on the cursor stack while picking is possible and while dragging
+    picking_cursor: Option<CursorId>,
+    dragging_cursor: Option<CursorId>,
+    // The monitor the window is on, to notice it moving to another. Asked for again only
+    // when the window moves or resizes, it's a trip through every monitor's video mode
+    monitor: Option<MonitorInfo>,
+    window_moved: bool,
+}
+
+impl WindowLoop {
+    pub fn new(
+        options: &Options,
+        window: &mut glfw::Window,
+        cursors: CursorStack,
+        playground: Playground,
+    ) -> Self {
+        let mut pacer = FramePacer::new(60, options.target_fps);
+        let monitor = window.current_monitor();
+        if let Some(monitor) = &monitor {
+            pacer.set_refresh_rate(monitor.refresh_rate);
+        }
+        Self {
+            pacer,
+            cursors,
+            playground,
+            #[cfg(feature = "trace")]
+            trace: None,
+            latency_test: options.latency_test,
+            latency_flash: None,
+            triangle_toggle: false,
+            cursor_color: Colors::WHITE,
+            pasted_color: None,
+            look_from: None,
+            needs_redraw: false,
+            loading: true,
+            show_exposure: false,
+            show_sprites: false,
+            show_tiles: false,
+            picking_cursor: None,
+            dragging_cursor: None,
+            monitor,
+            window_moved: false,
+        }
+    }
+
+    // Polled rather than taken from key events, the camera flies for as long as they're held
+    pub fn fly_keys(state: &mut State, window: &glfw::Window) {
+        if !state.mouse_captured {
+            return;
+        }
+        let held = |key| window.get_key(key) == Action::Press;
+        let axis = |negative, positive| match (held(negative), held(positive)) {
+            (false, true) => 1.0,
+            (true, false) => -1.0,
+            _ => 0.0,
+        };
+        state.camera_input.movement = Vec3::new(
+            axis(Key::A, Key::D),
+            axis(Key::Q, Key::E),
+            axis(Key::S, Key::W),
+        );
+        state.camera_input.boost = held(Key::LeftShift) || held(Key::RightShift);
+    }
+
+    // What a key does comes from the same bindings the splash screen lists, with the views
+    // and modes that are on right now
+    fn lookup(&self, state: &State, event: &WindowEvent) -> Option<Binding> {
+        let WindowEvent::Key(key, _, press, mods) = *event else {
+            return None;
+        };
+        if press == Action::Release {
+            return None;
+        }
+        state.bindings.lookup(
+            Chord::pressed(key, mods),
+            press == Action::Repeat,
+            |only_in| match only_in {
+                "captured mouse" => state.mouse_captured,
+                "deferred" => state.deferred.active,
+                "primitives" => state.show_primitives,
+                "inspector" => state.inspector.enabled,
+                "exposure" => self.show_exposure,
+                "tiles" => self.show_tiles,
+                "timeline" => state.timeline.is_some(),
+                _ => false,
+            },
+        )
+    }
+
+    // One event from the last poll. The console gets first go while it's open, then bound
+    // keys: the loop's own actions, then State's. Anything else is the pointer's
+    pub fn handle(
+        &mut self,
+        state: &mut State,
+        window: &mut glfw::Window,
+        time: f64,
+        event: WindowEvent,
+    ) {
+        let bound = self.lookup(state, &event);
+        match state.console.handle(&event) {
+            ConsoleInput::Passed => {}
+            ConsoleInput::Swallowed => return,
+            ConsoleInput::Changed => {
+                self.needs_redraw = true;
+                return;
+            }
+            ConsoleInput::Command(command) => {
+                state.run_command(&command);
+                self.needs_redraw = true;
+                return;
+            }
+        }
+        if let Some(binding) = bound {
+            if !self.run_action(state, window, binding.action) && !state.run_action(binding.action)
+            {
+                log::warn!(
+                    "{} is bound to \"{}\" but nothing handled it",
+                    binding.chord.label(),
+                    binding.action
+                );
+            }
+            return;
+        }
+        self.pointer(state, window, time, event);
+    }
+
+    // The bound actions that need the window or the views only the loop has. False for
+    // anything else
+    fn run_action(&mut self, state: &mut State, window: &mut glfw::Window, action: &str) -> bool {
+        match action {
+            // Held down they fly the camera, see fly_keys
+            "forward" | "back" | "left" | "right" | "down" | "up" | "faster" => return true,
+            "quit" => window.set_should_close(true),
+            "release mouse" => state.capture_mouse(false),
+            "capture mouse" => {
+                state.capture_mouse(!state.mouse_captured);
+                self.look_from = None;
+            }
+            "click-through" => {
+                let enabled = !state.transparency.click_through;
+                state.transparency.set_click_through(window, enabled);
+            }
+            "copy screenshot path" => match &state.last_screenshot {
+                Some(path) => {
+                    WindowBackend::set_clipboard_string(window, &path.display().to_string());
+                }
+                None => log::warn!("No screenshot taken yet, nothing to copy"),
+            },
+            "copy color" => {
+                let hex = self.clear_color(state).to_hex();
+                WindowBackend::set_clipboard_string(window, &hex);
+                println!("Copied {hex}");
+            }
+            "paste color" => {
+                let pasted = window.clipboard_string().unwrap_or_default();
+                match RgbaColor::parse_hex(&pasted) {
+                    Ok(color) => {
+                        self.pasted_color = Some(color);
+                        self.needs_redraw = true;
+                    }
+                    Err(e) => log::warn!("{e}"),
+                }
+            }
+            #[cfg(feature = "trace")]
+            "chrome trace" => match self.trace {
+                Some(trace) => trace.toggle(),
+                None => log::warn!("Start with --trace-chrome <path> to capture traces"),
+            },
+            _ => return self.view_action(state, action),
+        }
+        true
+    }
+
+    // The views that live in the loop rather than in State
+    fn view_action(&mut self, state: &mut State, action: &str) -> bool {
+        match action {
+            "pentagon pipeline" => self.triangle_toggle = !self.triangle_toggle,
+            "SDF playground" => self.playground.active = !self.playground.active,
+            "next playground shader" => {
+                self.playground.cycle();
+                if let Some(name) = self.playground.current() {
+                    let building = if self.playground.is_building() {
+                        " (still building, showing a placeholder)"
+                    } else {
+                        ""
+                    };
+                    println!("SDF playground: {name}{building}");
+                }
+                return true;
+            }
+            "sprite stress view" => {
+                self.show_sprites = !self.show_sprites;
+                if self.show_sprites {
+                    state.ensure_sprite_stress();
+                }
+            }
+            "tile map view" => {
+                self.show_tiles = !self.show_tiles;
+                if self.show_tiles {
+                    state.ensure_tile_map();
+                }
+            }
+            "exposure view" => {
+                self.show_exposure = !self.show_exposure;
+                // The view is there to show it off
+                if self.show_exposure {
+                    if let Err(e) = state.post.set_enabled("exposure", true) {
+                        log::warn!("{e}");
+                    }
+                }
+            }
+            _ => return false,
+        }
+        self.needs_redraw = true;
+        true
+    }
+
+    // Mouse, scroll, drops and the window itself moving or resizing
+    fn pointer(
+        &mut self,
+        state: &mut State,
+        window: &mut glfw::Window,
+        time: f64,
+        event: WindowEvent,
+    ) {
+        let held = |window: &glfw::Window, keys: [Key; 2]| {
+            keys.into_iter()
+                .any(|key| window.get_key(key) == Action::Press)
+        };
+        match event {
+            WindowEvent::CursorPos(x, y) if state.mouse_captured => {
+                // The captured cursor's position is virtual and unbounded, only how far it
+                // went counts
+                if let Some((from_x, from_y)) = self.look_from {
+                    state.camera_input.look += Vec2::new((x - from_x) as f32, (y - from_y) as f32);
+                }
+                self.look_from = Some((x, y));
+            }
+            WindowEvent::Scroll(_, y) if state.mouse_captured => {
+                state.camera_input.scroll += y as f32;
+            }
+            WindowEvent::MouseButton(MouseButton::Right, Action::Press, _)
+                if state.show_primitives =>
+            {
+                if state.begin_drag() {
+                    self.dragging_cursor = Some(self.cursors.push(window, CursorKind::Hand));
+                }
+            }
+            WindowEvent::MouseButton(MouseButton::Right, Action::Release, _) => {
+                state.end_drag();
+                if let Some(id) = self.dragging_cursor.take() {
+                    self.cursors.pop(window, id);
+                }
+                return;
+            }
+            WindowEvent::FileDrop(paths) => state.drop_files(paths),
+            WindowEvent::Scroll(_, y)
+                if state.console.contains({
+                    let (x, y) = window.get_cursor_pos();
+                    (x as f32, y as f32)
+                }) =>
+            {
+                state.console.scroll(y as f32);
+            }
+            WindowEvent::Scroll(_, y) => {
+                let (cursor_x, cursor_y) = window.get_cursor_pos();
+                state.camera2d.zoom_at(
+                    Vec2::new(cursor_x as f32, cursor_y as f32),
+                    1.1f32.powf(y as f32),
+                    (state.config.width, state.config.height),
+                );
+            }
+            WindowEvent::Size(width, height) => {
+                state.resize((width, height));
+                self.window_moved = true;
+            }
+            WindowEvent::Pos(..) => {
+                self.window_moved = true;
+                return;
+            }
+            WindowEvent::MouseButton(MouseButton::Left, Action::Press, _) if self.latency_test => {
+                self.latency_flash = Some(time);
+                return;
+            }
+            WindowEvent::MouseButton(MouseButton::Left, Action::Press, mods)
+                if self.show_tiles && state.tile_brush.is_some() =>
+            {
+                state.tile_stroke = None;
+                state.paint_tiles(mods.contains(glfw::Modifiers::Shift));
+            }
+            WindowEvent::MouseButton(MouseButton::Left, Action::Release, _)
+                if state.tile_stroke.is_some() =>
+            {
+                state.tile_stroke = None;
+                return;
+            }
+            WindowEvent::MouseButton(MouseButton::Left, Action::Press, _) => {
+                window.set_should_close(true);
+                return;
+            }
+            WindowEvent::CursorPos(_, _) if state.transform_gizmo.dragging().is_some() => {
+                // Snapping from the unsnapped position with Ctrl, so the item keeps up with
+                // the cursor instead of getting stuck on the point it snapped to. Shift keeps
+                // moves to one axis, rotation and scale to steps
+                let continuous = held(window, [Key::LeftControl, Key::RightControl]);
+                let constrain = held(window, [Key::LeftShift, Key::RightShift]);
+                state.drag_to(continuous, constrain);
+            }
+            WindowEvent::CursorPos(x, y) if state.tile_stroke.is_some() => {
+                state.cursor = (x, y);
+                state.paint_tiles(held(window, [Key::LeftShift, Key::RightShift]));
+            }
+            WindowEvent::CursorPos(x, y) => {
+                let x_normalized = x / f64::from(state.size.0);
+                let y_normalized = y / f64::from(state.size.1);
+
+                self.cursor_color = RgbaColor::rgba(
+                    x_normalized,
+                    y_normalized,
+                    (x_normalized + y_normalized) / 2.,
+                    1.,
+                );
+            }
+            _ => return,
+        }
+        self.needs_redraw = true;
+    }
+
+    // Once however many moves came in, the window's center decides the monitor
+    pub fn follow_monitor(&mut self, state: &mut State, window: &mut glfw::Window) {
+        if !std::mem::take(&mut self.window_moved) {
+            return;
+        }
+        let Some(now_on) = window.current_monitor() else {
+            return;
+        };
+        self.pacer.set_refresh_rate(now_on.refresh_rate);
+        let same = |on: &MonitorInfo| (&on.name, on.position) == (&now_on.name, now_on.position);
+        if !self.monitor.as_ref().is_some_and(same) {
+            println!("Moved to monitor {}", now_on.name);
+            state.check_surface();
+            self.needs_redraw = true;
+        }
+        self.monitor = Some(now_on);
+    }
+
+    // Crosshair while items can be picked, whichever way the primitives view came up
+    pub fn picking_cursor(&mut self, state: &State, window: &mut glfw::Window) {
+        match (state.show_primitives, self.picking_cursor) {
+            (true, None) => {
+                self.picking_cursor = Some(self.cursors.push(window, CursorKind::Crosshair));
+            }
+            (false, Some(id)) => {
+                self.cursors.pop(window, id);
+                self.picking_cursor = None;
+            }
+            _ => {}
+        }
+    }
+
+    // The Shapes view's background: the timeline's, a pasted one or the cursor's
+    fn clear_color(&self, state: &State) -> RgbaColor {
+        state
+            .timeline
+            .as_ref()
+            .and_then(Timeline::clear_color)
+            .or(self.pasted_color)
+            .unwrap_or(self.cursor_color)
+    }
+
+    // What gets drawn this iteration. Only the startup assets get the loading screen, later
+    // ones come in behind the view
+    pub fn view(&mut self, state: &State) -> View {
+        let preloading = state.preload_progress();
+        if self.loading && state.assets.pending() == 0 {
+            self.loading = false;
+            self.needs_redraw = true;
+        }
+        match self.playground.current().filter(|_| self.playground.active) {
+            _ if self.loading => View::Loading {
+                done: state.assets.requested() - state.assets.pending(),
+                total: state.assets.requested(),
+            },
+            _ if preloading.is_some() => {
+                let (done, total) = preloading.unwrap_or_default();
+                View::Loading { done, total }
+            }
+            _ if self.latency_flash.is_some() => View::Shapes {
+                clear_color: Colors::WHITE,
+                toggle: self.triangle_toggle,
+            },
+            Some(name) => View::Fullscreen(name.to_owned()),
+            None if state.deferred.active => View::Deferred,
+            None if self.show_exposure => View::Exposure,
+            None if self.show_sprites && state.sprite_stress.is_some() => View::Sprites,
+            None if self.show_tiles && state.tile_map.is_some() => View::Tiles,
+            None if state.show_primitives => View::Primitives,
+            None => match state.mrt.view {
+                Some(target) => View::Mrt(target),
+                None if state.splash.is_some() => View::Splash,
+                None => View::Shapes {
+                    clear_color: self.clear_color(state),
+                    toggle: self.triangle_toggle,
+                },
+            },
+        }
+    }
+
+    // Draws `view` when anything asked for it since the last time
+    pub fn draw(&mut self, state: &mut State, view: &View) {
+        // Any other view replaces the splash for good
+        if !matches!(view, View::Splash | View::Loading { .. }) {
+            state.splash = None;
+        }
+        if !matches!(view, View::Deferred) {
+            state.capture_mouse(false);
+        }
+
+        // Animated views and the overlay and inspector (their numbers change every frame)
+        // redraw every iteration. Tweens, fades and the timeline ask for it in update
+        let animating = matches!(
+            view,
+            View::Fullscreen(_)
+                | View::Deferred
+                | View::Exposure
+                | View::Loading { .. }
+                | View::Splash
+        ) || state.overlay.enabled
+            || state.inspector.enabled;
+        if animating {
+            state.request_redraw();
+        }
+        let alpha = self.pacer.alpha();
+        if std::mem::take(&mut self.needs_redraw)
+            || state.redraw.take_due(std::time::Instant::now())
+            || self.latency_flash.is_some()
+        {
+            state.stats.refresh_rate = self.pacer.refresh_rate;
+            state.stats.interpolation_alpha = alpha;
+            state.stats.accumulation = match view {
+                View::Fullscreen(_) if state.accumulator.enabled => {
+                    Some((state.accumulator.samples(), state.accumulator.format))
+                }
+                _ => None,
+            };
+            state.render(view, alpha);
+            if let Some(path) = state.supersample_request.take() {
+                state.capture_supersampled(view, alpha, path);
+            }
+        }
+        // That frame used up what update asked for. Whatever is still moving wants the next
+        // step drawn too, or an event driven loop would sleep until input with it half done
+        if state.is_moving() {
+            state.request_redraw_after(self.pacer.fixed_step);
+        }
+    }
+
+    // How long the click took to show up, once it has
+    pub fn report_latency(&mut self, state: &mut State, options: &Options, now: f64) {
+        if let Some(clicked_at) = self.latency_flash.take() {
+            println!(
+                "Click to present: {:.2} ms (frame latency {}, sync {})",
+                (now - clicked_at) * 1000.0,
+                options.frame_latency,
+                options.sync_after_present
+            );
+            // Back to the normal background next iteration
+            state.request_redraw();
+        }
+    }
+
+    // A remote command or anything else from outside the window wants the next frame
+    #[cfg(feature = "remote")]
+    pub fn redraw(&mut self) {
+        self.needs_redraw = true;
+    }
+}
+
+// What State asked of the window since the last iteration
+pub fn apply_window_requests(state: &mut State, window: &mut glfw::Window) {
+    for request in state.take_window_requests() {
+        match request {
+            WindowRequest::Opacity(opacity) => state.transparency.set_opacity(window, opacity),
+            WindowRequest::ClickThrough(enabled) => {
+                state.transparency.set_click_through(window, enabled);
+            }
+            WindowRequest::CaptureCursor(captured) => window.set_cursor_captured(captured),
+            WindowRequest::Close => window.set_should_close(true),
+        }
+    }
+}
+
+// What the loop in run owns that's wrapped up when the window closes, before State tears
+// down the rest
+struct Closing<'w> {
+    #[cfg(feature = "trace")]
+    trace: Option<&'static trace::ChromeTrace>,
+    cursors: CursorStack,
+    window: &'w mut glfw::Window,
+}
+
+impl App for Closing<'_> {
+    fn on_exit(&mut self, _teardown: &mut Teardown) {
+        // A capture still running when the window closes is written out too
+        #[cfg(feature = "trace")]
+        if let Some(trace) = self.trace {
+            println!("Shutdown: writing the trace");
+            trace.stop();
+        }
+        self.cursors.clear(self.window);
+    }
+}
+
+// The window, with every event the loop handles turned on. It never shows up when it's
+// only there to list monitors or capabilities
+fn open_window(
+    glfw: &mut glfw::Glfw,
+    options: &Options,
+) -> (glfw::PWindow, glfw::GlfwReceiver<(f64, glfw::WindowEvent)>) {
+    glfw.window_hint(glfw::WindowHint::Resizable(true));
+    glfw.window_hint(glfw::WindowHint::TransparentFramebuffer(
+        options.transparent.is_some(),
+    ));
+    glfw.window_hint(glfw::WindowHint::Visible(
+        !options.list_monitors && !options.capabilities,
+    ));
+
+    let (mut window, events) = glfw
+        .create_window(800, 600, "wGPU training arc", glfw::WindowMode::Windowed)
+        .expect("Failed to get window and events");
+
+    window.make_current();
+    window.set_key_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_cursor_enter_polling(true);
+    window.set_scroll_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_drag_and_drop_polling(true);
+    window.set_pos_polling(true);
+    window.set_char_polling(true);
+    (window, events)
+}
+
+// Where the window opens, its icon and --cursor, pushed on the stack the loop takes over
+fn dress_window(window: &mut glfw::Window, options: &Options) -> CursorStack {
+    if let Some(monitor) = backend::place_window(window, options) {
+        println!("Opening on {monitor}");
+    }
+    if let Err(e) = window.set_window_icon(options.icon.as_deref()) {
+        log::warn!("{e}, using the default icon");
+        let _ = window.set_window_icon(None);
+    }
+    let mut cursors = CursorStack::new();
+    if let Some(path) = &options.cursor {
+        match CursorKind::from_arg(path) {
+            Ok(kind) => {
+                cursors.push(window, kind);
+            }
+            Err(e) => log::warn!("{e}, keeping the system cursor"),
+        }
+    }
+    cursors
+}
+
+// --scene-file, a built-in scene's name or a file, and the name it goes by. The starter
+// scene without one or when the file doesn't load
+fn startup_scene(options: &Options) -> (String, Scene) {
+    let Some(path) = &options.scene_file else {
+        return ("starter".to_owned(), Scene::starter());
+    };
+    let scene = match path
+        .to_str()
+        .and_then(|name| Scene::named(name, &options.stress))
+    {
+        Some(scene) => scene,
+        None => Scene::load(path).unwrap_or_else(|e| {
+            log::error!("{e}");
+            Scene::starter()
+        }),
+    };
+    (path.display().to_string(), scene)
+}
+
+// --effects and --timeline, once State is up
+fn apply_startup_options(state: &mut State, options: &Options) {
+    for name in &options.effects {
+        if let Err(e) = state.post.set_enabled(name, true) {
+            log::warn!("{e}");
+        }
+    }
+    if let Some(path) = &options.timeline {
+        if state.load_timeline(path) {
+            if let Some(timeline) = &mut state.timeline {
+                timeline.playing = true;
+            }
+            state.apply_timeline();
+        }
+    }
+}
+
+// --remote. Its wake-up is an empty event, so an event driven loop sleeping in wait_events
+// still gets to the commands
+#[cfg(feature = "remote")]
+fn start_remote(glfw: &mut glfw::Glfw, options: &Options) -> Option<remote::Remote> {
+    let port = options.remote?;
+    let waker = std::sync::Mutex::new(glfw::ThreadSafeGlfw::from(glfw));
+    let wake = move || {
+        if let Ok(waker) = waker.lock() {
+            waker.post_empty_event();
+        }
+    };
+    let token = std::env::var(remote::TOKEN_VARIABLE).ok();
+    remote::Remote::start(options.remote_bind, port, token, wake)
+        .inspect(|remote| println!("Remote control listening on {}", remote.address()))
+        .map_err(|e| log::error!("{e}"))
+        .ok()
+}
+
+pub async fn run() {
+    log_sink::init();
+    crash::install();
+    let options = Options::from_args();
+    if let Some(job) = &options.render {
+        // Nonzero exit when anything didn't make it to disk, so scripts can tell
+        match headless::render(job).await {
+            Ok(0) => return,
+            Ok(_) => std::process::exit(1),
+            Err(e) => {
+                log::error!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    #[cfg(feature = "trace")]
+    let trace = trace::init(options.trace_chrome.clone());
+
+    let mut glfw = glfw::init(fail_on_errors!()).expect("Failed to get glfw");
+    let (mut window, events) = open_window(&mut glfw, &options);
+    if options.list_monitors {
+        for (index, monitor) in window.monitors().iter().enumerate() {
+            println!("{index}: {monitor}");
+        }
+        return;
+    }
+    let target =
+        unsafe { wgpu::SurfaceTargetUnsafe::from_window(&*window) }.expect("Failed to get target");
+    if options.capabilities {
+        let report = open_adapter(target, capabilities::backends())
+            .await
+            .and_then(|(surface, adapter)| {
+                Capabilities::new(&adapter, &surface, &device_requirements(&options), &[])
+            });
+        match report {
+            Ok(capabilities) => print!("{}", capabilities.report()),
+            Err(e) => log::error!("{e}"),
+        }
+        return;
+    }
+    let cursors = dress_window(&mut window, &options);
+    // The window outlives the State, it's dropped after shutdown at the end of run
+    let built = unsafe { StateBuilder::new(&options).surface_target(target) }
+        .size(window.get_size())
+        .backends(capabilities::backends())
+        .framebuffer_transparent(window.is_framebuffer_transparent())
+        .build()
+        .await;
+    let mut state = match built {
+        Ok(state) => state,
+        Err(e) => {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let (name, scene) = startup_scene(&options);
+    state.scene.fade.easing = options.easing;
+    state.set_scene(&name, scene);
+    let requests = std::mem::take(&mut state.playground_requests);
+    let playground = Playground::new(&state.render_pipelines, requests);
+    state.clear_screen_to(Colors::WHITE.to_wgpu_linear());
+    apply_startup_options(&mut state, &options);
+
+    let mut app = WindowLoop::new(&options, &mut window, cursors, playground);
+    #[cfg(feature = "trace")]
+    {
+        app.trace = trace;
+    }
+    #[cfg(feature = "remote")]
+    let remote = start_remote(&mut glfw, &options);
+
+    while !window.should_close() {
+        let _frame = tracing::info_span!("frame").entered();
+        let poll = tracing::info_span!("poll_events").entered();
+        if options.event_driven {
+            // Sleeps until there's input or the earliest redraw request is due
+            match state.redraw.idle_for(std::time::Instant::now()) {
+                None => glfw.wait_events(),
+                Some(idle) if idle.is_zero() => glfw.poll_events(),
+                Some(idle) => glfw.wait_events_timeout(idle.as_secs_f64()),
+            }
+        } else {
+            glfw.poll_events();
+        }
+        drop(poll);
+        state.cursor = window.get_cursor_pos();
+        state.content_scale = window.get_content_scale().0;
+
+        let update = tracing::info_span!("update").entered();
+        WindowLoop::fly_keys(&mut state, &window);
+        for _ in 0..app.pacer.advance() {
+            state.update(app.pacer.fixed_step);
+        }
+        state.index_items();
+        drop(update);
+
+        // Capture all the events here, drawing happens once they've all been handled
+        let handling = tracing::info_span!("events").entered();
+        for (time, event) in glfw::flush_messages(&events) {
+            app.handle(&mut state, &mut window, time, event);
+        }
+        drop(handling);
+        app.follow_monitor(&mut state, &mut window);
+        #[cfg(feature = "remote")]
+        if let Some(remote) = &remote {
+            for command in remote.take() {
+                state.run_remote(command);
+                app.redraw();
+            }
+        }
+        app.picking_cursor(&state, &mut window);
+
+        state.receive_assets();
+        let view = app.view(&state);
+        app.draw(&mut state, &view);
+        app.report_latency(&mut state, &options, glfw.get_time());
+        apply_window_requests(&mut state, &mut window);
+        let _wait = tracing::info_span!("wait").entered();
+        app.pacer.wait();
+    }
+    state.shutdown(&mut Closing {
+        #[cfg(feature = "trace")]
+        trace: app.trace,
+        cursors: app.cursors,
+        window: &mut window,
+    });
+}